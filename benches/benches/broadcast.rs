@@ -0,0 +1,100 @@
+//! Compares the spectator broadcast with sending to each spectator in turn, which encodes the
+//! frame once per spectator on the broadcasting task.
+//!
+//! Run with `cargo bench` from `benches/`.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tcp_server_bench::tcp::handshake::NegotiatedProtocol;
+use tcp_server_bench::tcp::header::HeaderType;
+use tcp_server_bench::tcp::packet::Packet;
+use tcp_server_bench::tcp::spectator::{broadcast, Spectator};
+use tcp_server_bench::utils::checksum::ChecksumKind;
+use tcp_server_bench::utils::compression::Compression;
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream};
+
+/// Broadcasts timed for each spectator count, after one untimed warm-up.
+const ROUNDS: u32 = 20;
+
+/// Spectator counts compared.
+const SPECTATORS: [usize; 5] = [1, 2, 8, 32, 64];
+
+/// Connects `count` spectators to a local listener that drains everything they are sent.
+async fn spectators(count: usize, compression: Compression) -> Vec<Arc<Spectator>> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let local = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buffer = vec![0u8; 64 * 1024];
+                while matches!(stream.read(&mut buffer).await, Ok(n) if n > 0) {}
+            });
+        }
+    });
+
+    let negotiated = NegotiatedProtocol {
+        checksum: ChecksumKind::Crc32c,
+        compression,
+        ..NegotiatedProtocol::legacy()
+    };
+
+    let mut spectators = Vec::with_capacity(count);
+    for _ in 0..count {
+        let stream = TcpStream::connect(local).await.unwrap();
+        spectators.push(Arc::new(Spectator::new(stream.into(), local, negotiated)));
+    }
+    spectators
+}
+
+/// A game state packet of `size` bytes: compressible, but not trivially.
+fn game_state(size: usize) -> Arc<Packet> {
+    let mut seed = 0x2545_f491_u32;
+    let payload: Vec<u8> = (0..size)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            b"abcdefghijklmnop"[(seed >> 28) as usize]
+        })
+        .collect();
+    Arc::new(Packet::new(HeaderType::GameState, &payload))
+}
+
+async fn serial(spectators: &[Arc<Spectator>], packet: &Packet) -> Duration {
+    let started = Instant::now();
+    for spectator in spectators {
+        spectator.send(packet).await.unwrap();
+    }
+    started.elapsed()
+}
+
+async fn parallel(spectators: &[Arc<Spectator>], packet: &Arc<Packet>) -> Duration {
+    let started = Instant::now();
+    let unreachable = broadcast(spectators.to_vec(), Arc::clone(packet)).await;
+    assert!(unreachable.is_empty());
+    started.elapsed()
+}
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    let packet = game_state(256 * 1024);
+    for compression in [Compression::None, Compression::Zstd] {
+        println!("{compression:?}, {} byte game state", packet.payload.len());
+        for count in SPECTATORS {
+            let spectators = spectators(count, compression).await;
+            serial(&spectators, &packet).await;
+            parallel(&spectators, &packet).await;
+
+            let (mut one_by_one, mut broadcasts) = (Duration::ZERO, Duration::ZERO);
+            for _ in 0..ROUNDS {
+                one_by_one += serial(&spectators, &packet).await;
+                broadcasts += parallel(&spectators, &packet).await;
+            }
+
+            println!(
+                "{count:>4} spectators: {:>12?} one by one, {:>12?} broadcast",
+                one_by_one / ROUNDS,
+                broadcasts / ROUNDS
+            );
+        }
+    }
+}
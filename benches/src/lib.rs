@@ -0,0 +1,15 @@
+//! The spectator broadcast of the server, built on its own for the benchmarks.
+//!
+//! The server is a binary crate, so the modules a broadcast is made of are included by path, in
+//! the same module tree, with a logger that discards everything in place of the server's.
+
+pub mod models;
+pub mod tcp;
+pub mod utils;
+
+#[macro_export]
+macro_rules! logger {
+    ($level:ident, $($arg:tt)*) => {
+        Logger::discard(format_args!($($arg)*))
+    };
+}
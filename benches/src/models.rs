@@ -0,0 +1,2 @@
+#[path = "../../src/models/ids.rs"]
+pub mod ids;
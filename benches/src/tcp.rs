@@ -0,0 +1,18 @@
+#[path = "../../src/tcp/compat.rs"]
+pub mod compat;
+#[path = "../../src/tcp/encoding.rs"]
+pub mod encoding;
+#[path = "../../src/tcp/error_response.rs"]
+pub mod error_response;
+#[path = "../../src/tcp/handshake.rs"]
+pub mod handshake;
+#[path = "../../src/tcp/header.rs"]
+pub mod header;
+#[path = "../../src/tcp/packet.rs"]
+pub mod packet;
+#[path = "../../src/tcp/rejection.rs"]
+pub mod rejection;
+#[path = "../../src/tcp/spectator.rs"]
+pub mod spectator;
+#[path = "../../src/tcp/transport.rs"]
+pub mod transport;
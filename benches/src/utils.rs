@@ -0,0 +1,17 @@
+#[path = "../../src/utils/checksum.rs"]
+pub mod checksum;
+#[path = "../../src/utils/compression.rs"]
+pub mod compression;
+#[path = "../../src/utils/errors.rs"]
+pub mod errors;
+
+pub mod logger {
+    use std::fmt::Arguments;
+
+    /// Stands in for the logger of the server, which the benchmarks have no use for.
+    pub struct Logger;
+
+    impl Logger {
+        pub fn discard(_: Arguments) {}
+    }
+}
@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tcp_server_fuzz::tcp::header::{Header, HEADER_SIZE, MAX_PAYLOAD_SIZE};
+use tcp_server_fuzz::tcp::packet::Packet;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(packet) = Packet::parse(data) {
+        assert!(packet.payload.len() <= MAX_PAYLOAD_SIZE as usize);
+        assert_eq!(packet.payload.len(), packet.header.payload_length as usize);
+    }
+
+    if data.len() >= HEADER_SIZE {
+        if let Ok(header) = Header::from_bytes(&data[..HEADER_SIZE]) {
+            // A header that parses is written back as it was read.
+            assert_eq!(&data[..HEADER_SIZE], &*header.wrap_header());
+        }
+    }
+});
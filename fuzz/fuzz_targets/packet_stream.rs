@@ -0,0 +1,28 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::sync::LazyLock;
+use tcp_server_fuzz::tcp::header::MAX_PAYLOAD_SIZE;
+use tcp_server_fuzz::tcp::packet::Packet;
+use tcp_server_fuzz::utils::errors::ProtocolError;
+use tokio::runtime::{Builder, Runtime};
+
+static RUNTIME: LazyLock<Runtime> =
+    LazyLock::new(|| Builder::new_current_thread().build().unwrap());
+
+// Reads packets off the bytes the way the server reads a connection, going on after the errors
+// the server goes on after.
+fuzz_target!(|data: &[u8]| {
+    RUNTIME.block_on(async {
+        let mut reader = data;
+        loop {
+            match Packet::read_from(&mut reader).await {
+                Ok(Some(packet)) => {
+                    assert!(packet.payload.len() <= MAX_PAYLOAD_SIZE as usize);
+                }
+                Err(ProtocolError::CorruptPayload(_)) => continue,
+                Ok(None) | Err(_) => break,
+            }
+        }
+    });
+});
@@ -0,0 +1,4 @@
+#[path = "../../src/tcp/header.rs"]
+pub mod header;
+#[path = "../../src/tcp/packet.rs"]
+pub mod packet;
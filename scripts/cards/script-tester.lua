@@ -0,0 +1,7 @@
+-- Scripts of the `script-tester` card, called as `script-tester:<name>`. The functions of a card
+-- script stay in the namespace of its card, so other cards may define an `on_death` of their own.
+function on_death(context)
+    return {
+        { type = "DealDamage", target = "None", amount = 5 },
+    }
+end
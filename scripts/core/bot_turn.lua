@@ -0,0 +1,44 @@
+-- Plans the turn of a bot playing with the `{"script": "core:bot_turn"}` strategy. The returned
+-- table lists the `plays` to make in order, each with the `instance_id` of a card of the hand and
+-- an optional `target_id` and `target_position`, and the `attackers` to declare afterwards.
+--
+-- `state` is the game state as the bot sees it: its own `player`, the public view of the
+-- `opponent` and the current `turn`. This plan plays the cheapest cards first, so as many cards
+-- as possible are played, then attacks with every creature.
+function bot_turn(state)
+    local hand = {}
+    for _, card in pairs(state.player.current_hand) do
+        if type(card) == "table" then
+            table.insert(hand, card)
+        end
+    end
+    table.sort(hand, function(a, b) return a.play_cost < b.play_cost end)
+
+    local plan = { plays = {}, attackers = {} }
+    for _, creature in pairs(state.player.board.creatures) do
+        if type(creature) == "table" then
+            for _, instance_id in ipairs(creature.instances) do
+                table.insert(plan.attackers, instance_id)
+            end
+        end
+    end
+
+    local mana = state.player.mana
+    for _, card in ipairs(hand) do
+        if card.play_cost <= mana then
+            mana = mana - card.play_cost
+            table.insert(plan.plays, { instance_id = card.instance_id })
+            if card.card_type == "creature" then
+                table.insert(plan.attackers, card.instance_id)
+            end
+        end
+    end
+
+    -- Empty tables convert to maps rather than lists, so empty lists are left out instead.
+    for key, list in pairs(plan) do
+        if #list == 0 then
+            plan[key] = nil
+        end
+    end
+    return plan
+end
@@ -0,0 +1,25 @@
+-- Computes the rewards of a match once it ended. The returned table is sent to the platform
+-- with the match report, under `rewards`.
+--
+-- `match` holds `match_id`, `match_type`, `winner_id`, `players`, `reason`, `rounds`, the
+-- `counts` of each event type over the whole match (`total`, and per player under `players`)
+-- and the latest `events` of the event log.
+function match_rewards(match)
+    local rewards = {}
+    for _, player in ipairs(match.players) do
+        local counts = match.counts.players[player] or {}
+        rewards[player] = {
+            xp = 10,
+            won = player == match.winner_id,
+            cards_played = counts.CardPlayed or 0,
+        }
+    end
+
+    for _, reward in pairs(rewards) do
+        if reward.won then
+            reward.xp = reward.xp + 20
+        end
+    end
+
+    return rewards
+end
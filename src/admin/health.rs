@@ -0,0 +1,156 @@
+use crate::admin::http;
+use crate::models::ids::MatchId;
+use crate::tcp::server::ServerInstance;
+use crate::{logger, utils::logger::Logger, LIFECYCLE, MATCHES};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::{Arc, LazyLock};
+use std::time::Instant;
+use tokio::net::{TcpListener, TcpStream};
+
+/// When the process started, for the uptime of the health report.
+static STARTED: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+/// Where the server is in the life of its matches.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerState {
+    /// Bound and waiting for the matchmaker to send `InitServer`.
+    Waiting,
+    /// Initialized, hosting a match still being played.
+    Running,
+    /// The matches ended and the process is shutting down.
+    Ended,
+}
+
+/// Served by the health endpoint, so the orchestrator spawning the match servers can monitor them
+/// and reap the stuck ones.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    pub state: ServerState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_id: Option<MatchId>, // The match hosted, once initialized, if it is the only one.
+    pub matches: usize,    // Matches hosted by the process.
+    pub players: usize,    // Players connected to the matches.
+    pub spectators: usize, // Spectators watching the matches.
+    pub uptime_ms: u64,    // Time since the process started.
+}
+
+impl HealthReport {
+    /// Reports on the hosted matches, or on the process waiting for `InitServer` without any.
+    pub async fn collect(servers: &[Arc<ServerInstance>]) -> Self {
+        let uptime_ms = STARTED.elapsed().as_millis() as u64;
+        let mut report = Self {
+            state: match LIFECYCLE.status() {
+                Some(_) => ServerState::Ended,
+                None => ServerState::Waiting,
+            },
+            match_id: None,
+            matches: servers.len(),
+            players: 0,
+            spectators: 0,
+            uptime_ms,
+        };
+        if let [server] = servers {
+            report.match_id = Some(server.match_id.clone());
+        }
+
+        for server in servers {
+            // Running while any of the matches is still being played.
+            if server.exit_status.read().await.is_none() {
+                report.state = ServerState::Running;
+            } else if report.state != ServerState::Running {
+                report.state = ServerState::Ended;
+            }
+            report.players += server.connected_clients.read().await.len();
+            report.spectators += server.spectators.read().await.len();
+        }
+        report
+    }
+
+    /// Answers a request of the health endpoint.
+    ///
+    /// * `/livez` - `200` while the process runs and has not started shutting down.
+    /// * `/readyz` - `200` while a match is hosted, `503` while waiting for `InitServer` or once
+    ///   the match ended.
+    /// * `/health` - `200` with the report as JSON.
+    ///
+    /// # Returns
+    /// The status code and the JSON body of the response.
+    pub fn respond(&self, method: &str, path: &str) -> (u16, String) {
+        if method != "GET" {
+            return (405, String::from(r#"{"error":"method not allowed"}"#));
+        }
+
+        let ok = match path {
+            "/livez" => self.state != ServerState::Ended,
+            "/readyz" => self.state == ServerState::Running,
+            "/health" => true,
+            _ => return (404, String::from(r#"{"error":"not found"}"#)),
+        };
+        let body = serde_json::to_string(self).unwrap_or_default();
+        (if ok { 200 } else { 503 }, body)
+    }
+}
+
+/// Binds the health endpoint.
+pub async fn bind(address: SocketAddr) -> std::io::Result<TcpListener> {
+    LazyLock::force(&STARTED);
+    TcpListener::bind(address).await
+}
+
+/// Answers health probes until the listener fails.
+pub async fn serve(listener: TcpListener) {
+    if let Ok(address) = listener.local_addr() {
+        logger!(INFO, "[HEALTH] Health endpoint listening on `{address}`");
+    }
+
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(async move {
+            if let Err(error) = answer(stream).await {
+                logger!(DEBUG, "[HEALTH] Could not answer a probe: {error}");
+            }
+        });
+    }
+}
+
+/// Reads one HTTP request and answers it, closing the connection.
+async fn answer(mut stream: TcpStream) -> std::io::Result<()> {
+    let Some((method, path)) = http::read_request(&mut stream).await else {
+        return Ok(());
+    };
+
+    let servers = MATCHES.servers().await;
+    let (status, body) = HealthReport::collect(&servers)
+        .await
+        .respond(&method, &path);
+    http::write_response(&mut stream, status, "application/json", &body).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_probes_before_initialization() {
+        let listener = bind("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener));
+
+        let mut responses = Vec::new();
+        for path in ["/livez", "/readyz", "/health", "/metrics"] {
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            responses.push(response);
+        }
+
+        assert!(responses[0].starts_with("HTTP/1.1 200"));
+        assert!(responses[1].starts_with("HTTP/1.1 503"));
+        assert!(responses[2].contains(r#""state":"waiting""#));
+        assert!(responses[3].starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,473 @@
+pub mod health;
+pub mod http;
+pub mod prometheus;
+pub mod remote;
+#[cfg(feature = "dev-repl")]
+pub mod repl;
+
+use crate::game::script_blocklist::BlockedScript;
+use crate::models::exit_code::ExitCode;
+use crate::models::ids::{MatchId, PlayerId};
+use crate::tcp::server::ServerInstance;
+use crate::utils::runtime_flags::LogLevel;
+use crate::{
+    logger, utils::logger::Logger, CONFIG_WATCHER, MATCHES, METRICS, RUNTIME_FLAGS, SETTINGS,
+};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+/// Operator commands that act on the running server.
+#[derive(Debug, PartialEq)]
+pub enum AdminCommand {
+    /// Lists the available commands.
+    Help,
+    /// Shows the addresses the server listens on and who is connected.
+    Health,
+    /// Shows how many match reports are waiting in the dead-letter queue.
+    DeadLetters,
+    /// Retries every match report in the dead-letter queue now.
+    FlushDeadLetters,
+    /// Shows the performance report of the match, live until the match ends.
+    Profile,
+    /// Shows the canonical hash of the live game state, to compare against clients and replays.
+    StateHash,
+    /// Shows the bytes exchanged with each client and with the whole match.
+    Bandwidth,
+    /// Shows the current runtime flags.
+    Flags,
+    /// Sets the lowest log level that is printed.
+    LogLevel(LogLevel),
+    /// Turns the full logging of every packet sent and received on or off.
+    PacketDump(bool),
+    /// Sets how many seconds spectators lag behind the match.
+    SpectatorDelay(u64),
+    /// Offers or withholds a protocol feature in the next handshakes.
+    Feature(String, bool),
+    /// Shows the card scripts blocked by operators.
+    BlockedScripts,
+    /// Stops a card's scripts, or a script function, from running; kept across restarts.
+    BlockScript(BlockedScript),
+    /// Lets a blocked card or script function run again.
+    UnblockScript(BlockedScript),
+    /// Closes the connection of a player; the player may reconnect.
+    Kick(PlayerId),
+    /// Ends the match as a draw, with the reason given, and shuts the server down.
+    EndMatch(String),
+    /// Shows the full game state as JSON, hidden information included.
+    DumpState,
+    /// Loads the card scripts again from disk.
+    ReloadScripts,
+    /// Reloads the tunable settings of `config.toml` right away.
+    ReloadConfig,
+    /// Relays emotes between the players again, or stops relaying them.
+    Emotes(bool),
+}
+
+impl AdminCommand {
+    /// Parses a command line typed by an operator.
+    ///
+    /// # Returns
+    /// * `Ok(AdminCommand)` - The parsed command.
+    /// * `Err(String)` - A message explaining why the line is not a command.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["help"] => Ok(AdminCommand::Help),
+            ["health"] => Ok(AdminCommand::Health),
+            ["dead-letters"] => Ok(AdminCommand::DeadLetters),
+            ["flush-dead-letters"] => Ok(AdminCommand::FlushDeadLetters),
+            ["profile"] => Ok(AdminCommand::Profile),
+            ["state-hash"] => Ok(AdminCommand::StateHash),
+            ["bandwidth"] => Ok(AdminCommand::Bandwidth),
+            ["flags"] => Ok(AdminCommand::Flags),
+            ["log-level", level] => Ok(AdminCommand::LogLevel(level.parse()?)),
+            ["packet-dump", state] => Ok(AdminCommand::PacketDump(parse_switch(state)?)),
+            ["spectator-delay", seconds] => seconds
+                .parse()
+                .map(AdminCommand::SpectatorDelay)
+                .map_err(|_| format!("Invalid delay `{seconds}`, expected whole seconds")),
+            ["feature", name, state] => Ok(AdminCommand::Feature(
+                name.to_string(),
+                parse_switch(state)?,
+            )),
+            ["blocked-scripts"] => Ok(AdminCommand::BlockedScripts),
+            ["block-script", kind, name] => {
+                Ok(AdminCommand::BlockScript(BlockedScript::parse(kind, name)?))
+            }
+            ["unblock-script", kind, name] => Ok(AdminCommand::UnblockScript(
+                BlockedScript::parse(kind, name)?,
+            )),
+            ["kick", player_id] => Ok(AdminCommand::Kick((*player_id).into())),
+            ["end-match"] => Ok(AdminCommand::EndMatch(String::from(
+                "The match was ended by an operator",
+            ))),
+            ["end-match", reason @ ..] => Ok(AdminCommand::EndMatch(reason.join(" "))),
+            ["dump-state"] => Ok(AdminCommand::DumpState),
+            ["reload-scripts"] => Ok(AdminCommand::ReloadScripts),
+            ["reload-config"] => Ok(AdminCommand::ReloadConfig),
+            ["emotes", state] => Ok(AdminCommand::Emotes(parse_switch(state)?)),
+            _ => Err(format!("Unknown command `{}`, try `help`", line.trim())),
+        }
+    }
+
+    /// Runs the command against the server.
+    ///
+    /// # Returns
+    /// The text to show to the operator.
+    pub async fn execute(&self, server: &ServerInstance) -> String {
+        match self {
+            AdminCommand::Help => {
+                let help = String::from(
+                    "Commands: help, health, dead-letters, flush-dead-letters, profile, state-hash, bandwidth, flags, \
+                     log-level <debug|info|warn|error>, packet-dump <on|off>, \
+                     spectator-delay <seconds>, feature <name> <on|off>, blocked-scripts, \
+                     block-script <card|function> <name>, unblock-script <card|function> <name>, \
+                     kick <player id>, end-match [reason], dump-state, reload-scripts, reload-config, \
+                     emotes <on|off>; prefix a command with `match <match id>` when several matches \
+                     are hosted",
+                );
+                #[cfg(feature = "dev-repl")]
+                let help = help + "; developer commands: lua <snippet>, action <json>";
+                help
+            }
+            AdminCommand::Health => {
+                let addresses: Vec<_> = server
+                    .addresses
+                    .iter()
+                    .map(|address| format!("`{address}`"))
+                    .collect();
+                format!(
+                    "Match `{}` listening on {}; {} players connected, {} spectators",
+                    server.match_id,
+                    addresses.join(", "),
+                    server.connected_clients.read().await.len(),
+                    server.spectators.read().await.len()
+                )
+            }
+            AdminCommand::DeadLetters => format!(
+                "{} match reports in the dead-letter queue",
+                server.reporter.dead_letters.len()
+            ),
+            AdminCommand::FlushDeadLetters => {
+                let (delivered, remaining) = server.reporter.flush_dead_letters().await;
+                format!("Delivered {delivered} match reports, {remaining} still queued")
+            }
+            AdminCommand::Profile => {
+                let profile = METRICS
+                    .match_profile(&server.match_id)
+                    .unwrap_or_else(|| server.game_instance.profiler.report());
+                format!("Match `{}` profile: {profile}", server.match_id)
+            }
+            AdminCommand::StateHash => {
+                let game_state = server.game_instance.game_state.read().await;
+                match game_state.state_hash().await {
+                    Ok(hash) => format!("Turn {} state hash: {hash}", game_state.rounds),
+                    Err(error) => format!("Could not hash the game state: {error}"),
+                }
+            }
+            AdminCommand::Bandwidth => {
+                let totals = METRICS.match_bandwidth(&server.match_id);
+                let mut lines = vec![format!(
+                    "Match `{}`: {} bytes sent, {} bytes received",
+                    server.match_id, totals.sent, totals.received
+                )];
+                for (player_id, client) in server.connected_clients.read().await.iter() {
+                    lines.push(format!(
+                        "  `{player_id}`: {} bytes sent, {} bytes received",
+                        client.bandwidth.sent(),
+                        client.bandwidth.received()
+                    ));
+                }
+                lines.join("\n")
+            }
+            AdminCommand::Flags => format!("Runtime flags: {}", *RUNTIME_FLAGS),
+            AdminCommand::LogLevel(level) => {
+                RUNTIME_FLAGS.set_log_level(*level);
+                format!("Log level set to {level}")
+            }
+            AdminCommand::PacketDump(enabled) => {
+                RUNTIME_FLAGS.set_packet_dump(*enabled);
+                format!("Packet dump {}", if *enabled { "on" } else { "off" })
+            }
+            AdminCommand::SpectatorDelay(seconds) => {
+                RUNTIME_FLAGS.set_spectator_delay(Duration::from_secs(*seconds));
+                format!("Spectators now lag {seconds}s behind the match")
+            }
+            AdminCommand::Feature(name, enabled) => {
+                match RUNTIME_FLAGS.set_feature(name, *enabled) {
+                    Ok(()) => format!(
+                        "Feature `{name}` {} for the next handshakes",
+                        if *enabled { "offered" } else { "withheld" }
+                    ),
+                    Err(error) => error,
+                }
+            }
+            AdminCommand::BlockedScripts => {
+                let blocklist = server.game_instance.scripts.blocklist.read().await;
+                format!("Blocked scripts: {}", *blocklist)
+            }
+            AdminCommand::BlockScript(entry) | AdminCommand::UnblockScript(entry) => {
+                let mut blocklist = server.game_instance.scripts.blocklist.write().await;
+                let changed = match self {
+                    AdminCommand::BlockScript(_) => blocklist.insert(entry.clone()),
+                    _ => blocklist.remove(entry),
+                };
+                if !changed {
+                    return format!("The {entry} was already in that state");
+                }
+
+                let verb = match self {
+                    AdminCommand::BlockScript(_) => "blocked",
+                    _ => "unblocked",
+                };
+                let saved = SETTINGS
+                    .get()
+                    .map(|s| blocklist.save(Path::new(&s.script_blocklist_path)));
+                match saved {
+                    Some(Err(error)) => {
+                        format!("The {entry} is {verb} until restart, could not save: {error}")
+                    }
+                    _ => format!("The {entry} is {verb}"),
+                }
+            }
+            AdminCommand::Kick(player_id) => {
+                let client = server.connected_clients.read().await.get(player_id).cloned();
+                match client {
+                    Some(client) => {
+                        client.close().await;
+                        format!("Player `{player_id}` was kicked")
+                    }
+                    None => format!("Player `{player_id}` is not connected"),
+                }
+            }
+            AdminCommand::EndMatch(reason) => {
+                server
+                    .end_match(ExitCode::EndedByOperator, None, reason, Vec::new())
+                    .await;
+                format!("Match `{}` ended: {reason}", server.match_id)
+            }
+            AdminCommand::DumpState => {
+                let view = server
+                    .game_instance
+                    .game_state
+                    .read()
+                    .await
+                    .private_view()
+                    .await;
+                serde_json::to_string(&view)
+                    .unwrap_or_else(|error| format!("Could not serialize the game state: {error}"))
+            }
+            AdminCommand::ReloadScripts => match server.game_instance.reload_scripts().await {
+                Ok(()) => String::from("Card scripts reloaded"),
+                Err(error) => format!("Could not reload the card scripts: {error}"),
+            },
+            AdminCommand::ReloadConfig => match CONFIG_WATCHER.get().map(|w| w.reload()) {
+                Some(Ok(reload)) => format!("Settings reloaded, {reload}"),
+                Some(Err(error)) => format!("Could not reload the settings: {error}"),
+                None => String::from("The settings file is not watched"),
+            },
+            AdminCommand::Emotes(enabled) => {
+                server.social.mute_emotes(!*enabled);
+                format!("Emotes {}", if *enabled { "on" } else { "off" })
+            }
+        }
+    }
+}
+
+/// Parses an `on` / `off` argument.
+fn parse_switch(value: &str) -> Result<bool, String> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        other => Err(format!("Expected `on` or `off`, got `{other}`")),
+    }
+}
+
+/// Splits a command line into the match it names with a `match <match id>` prefix, if any, and
+/// the command itself.
+fn split_target(line: &str) -> (Option<MatchId>, &str) {
+    let line = line.trim();
+    match line.split_once(char::is_whitespace) {
+        Some(("match", rest)) => {
+            let rest = rest.trim_start();
+            match rest.split_once(char::is_whitespace) {
+                Some((match_id, command)) => (Some(match_id.into()), command.trim_start()),
+                None => (Some(rest.into()), ""),
+            }
+        }
+        _ => (None, line),
+    }
+}
+
+/// Finds the hosted match a command is for: the match it names, else the only match hosted.
+///
+/// # Returns
+/// * `Ok(Arc<ServerInstance>)` - The server instance of the match.
+/// * `Err(String)` - Why no match fits, naming the hosted matches when there are several.
+async fn target(match_id: Option<&MatchId>) -> Result<Arc<ServerInstance>, String> {
+    let mut servers = MATCHES.servers().await;
+    if let Some(match_id) = match_id {
+        return servers
+            .into_iter()
+            .find(|server| &server.match_id == match_id)
+            .ok_or_else(|| format!("Match `{match_id}` is not hosted"));
+    }
+
+    match servers.len() {
+        0 => Err(String::from("No match is hosted")),
+        1 => Ok(servers.remove(0)),
+        count => {
+            let match_ids: Vec<_> = servers
+                .iter()
+                .map(|server| format!("`{}`", server.match_id))
+                .collect();
+            Err(format!(
+                "{count} matches are hosted ({}), name one with `match <match id> <command>`",
+                match_ids.join(", ")
+            ))
+        }
+    }
+}
+
+/// Runs a command line against the match it is for, as typed in the console or sent over the
+/// admin channel.
+///
+/// # Returns
+/// * `Ok(String)` - The text to show to the operator.
+/// * `Err(String)` - Why the line was not run.
+pub async fn run(line: &str) -> Result<String, String> {
+    let (match_id, command) = split_target(line);
+    #[cfg(feature = "dev-repl")]
+    if let Some(command) = repl::ReplCommand::parse(command) {
+        let server = target(match_id.as_ref()).await?;
+        return Ok(command.execute(&server).await);
+    }
+
+    let command = AdminCommand::parse(command)?;
+    let server = target(match_id.as_ref()).await?;
+    Ok(command.execute(&server).await)
+}
+
+/// Starts the operator channels of the process: the admin console and, when `ADMIN_ADDRESS` and
+/// `ADMIN_SECRET` are set, the signed admin channel. Both take the developer REPL commands in
+/// `dev-repl` builds.
+///
+/// They are started once, whatever the number of matches hosted; commands name the match they
+/// are for.
+pub fn start() {
+    tokio::spawn(console());
+
+    if let Some(settings) = SETTINGS.get() {
+        if let (Some(address), Some(secret)) = (settings.admin_address, &settings.admin_secret) {
+            tokio::spawn(remote::serve(address, secret.clone()));
+        }
+    }
+}
+
+/// Reads admin commands from the standard input until it is closed.
+pub async fn console() {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match run(&line).await {
+            Ok(output) => logger!(INFO, "[ADMIN] {output}"),
+            Err(error) => logger!(WARN, "[ADMIN] {error}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            Ok(AdminCommand::FlushDeadLetters),
+            AdminCommand::parse(" flush-dead-letters\n")
+        );
+        assert_eq!(
+            Ok(AdminCommand::Bandwidth),
+            AdminCommand::parse("bandwidth")
+        );
+        assert_eq!(
+            Ok(AdminCommand::ReloadConfig),
+            AdminCommand::parse("reload-config")
+        );
+        assert!(AdminCommand::parse("drop-tables").is_err());
+    }
+
+    #[test]
+    fn test_parse_flag_commands() {
+        assert_eq!(
+            Ok(AdminCommand::LogLevel(LogLevel::Warn)),
+            AdminCommand::parse("log-level warn")
+        );
+        assert_eq!(
+            Ok(AdminCommand::Feature("prompts".to_string(), false)),
+            AdminCommand::parse("feature prompts off")
+        );
+        assert_eq!(
+            Ok(AdminCommand::SpectatorDelay(30)),
+            AdminCommand::parse("spectator-delay 30")
+        );
+        assert_eq!(
+            Ok(AdminCommand::Emotes(false)),
+            AdminCommand::parse("emotes off")
+        );
+        assert!(AdminCommand::parse("packet-dump maybe").is_err());
+        assert!(AdminCommand::parse("log-level loud").is_err());
+    }
+
+    #[test]
+    fn test_parse_blocklist_commands() {
+        assert_eq!(
+            Ok(AdminCommand::BlockScript(BlockedScript::Card(
+                "wolf".into()
+            ))),
+            AdminCommand::parse("block-script card wolf")
+        );
+        assert_eq!(
+            Ok(AdminCommand::UnblockScript(BlockedScript::Function(
+                "cards:fireball".to_string()
+            ))),
+            AdminCommand::parse("unblock-script function cards:fireball")
+        );
+        assert!(AdminCommand::parse("block-script deck wolf").is_err());
+    }
+
+    #[test]
+    fn test_commands_may_name_their_match() {
+        assert_eq!((None, "kick red"), split_target(" kick red\n"));
+        assert_eq!(
+            (Some(MatchId::from("m-42")), "kick red"),
+            split_target("match m-42  kick red")
+        );
+        assert_eq!(
+            (Some(MatchId::from("m-42")), ""),
+            split_target("match m-42")
+        );
+    }
+
+    #[test]
+    fn test_parse_match_control_commands() {
+        assert_eq!(
+            Ok(AdminCommand::Kick("red".into())),
+            AdminCommand::parse("kick red")
+        );
+        assert_eq!(
+            Ok(AdminCommand::EndMatch("server maintenance".to_string())),
+            AdminCommand::parse("end-match server maintenance")
+        );
+        assert!(matches!(
+            AdminCommand::parse("end-match"),
+            Ok(AdminCommand::EndMatch(_))
+        ));
+        assert!(AdminCommand::parse("kick").is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use crate::admin::http;
+use crate::tcp::server::ServerInstance;
+use crate::utils::metrics::MatchGauges;
+use crate::{logger, utils::logger::Logger, MATCHES, METRICS};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+
+/// Content type of the Prometheus text exposition format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Reads the gauges of the hosted matches, all zero while waiting for `InitServer`.
+pub async fn gauges(servers: &[Arc<ServerInstance>]) -> MatchGauges {
+    let mut gauges = MatchGauges::default();
+    for server in servers {
+        for client in server.connected_clients.read().await.values() {
+            if *client.connected.read().await {
+                gauges.connected_clients += 1;
+            }
+            gauges.missed_packets += client.missed_packets.read().await.len();
+            gauges.outbound_queued += client.outbound.depth();
+        }
+    }
+    gauges
+}
+
+/// Binds the metrics endpoint.
+pub async fn bind(address: SocketAddr) -> std::io::Result<TcpListener> {
+    TcpListener::bind(address).await
+}
+
+/// Answers Prometheus scrapes on `/metrics` until the listener fails.
+pub async fn serve(listener: TcpListener) {
+    if let Ok(address) = listener.local_addr() {
+        logger!(INFO, "[METRICS] Metrics endpoint listening on `{address}`");
+    }
+
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(async move {
+            if let Err(error) = answer(stream).await {
+                logger!(DEBUG, "[METRICS] Could not answer a scrape: {error}");
+            }
+        });
+    }
+}
+
+/// Reads one HTTP request and answers it, closing the connection.
+async fn answer(mut stream: TcpStream) -> std::io::Result<()> {
+    let Some((method, path)) = http::read_request(&mut stream).await else {
+        return Ok(());
+    };
+
+    if method != "GET" {
+        return http::write_response(&mut stream, 405, CONTENT_TYPE, "method not allowed\n").await;
+    }
+    if path != "/metrics" {
+        return http::write_response(&mut stream, 404, CONTENT_TYPE, "not found\n").await;
+    }
+
+    let servers = MATCHES.servers().await;
+    let body = METRICS.render(gauges(&servers).await);
+    http::write_response(&mut stream, 200, CONTENT_TYPE, &body).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_scrape_before_initialization() {
+        let listener = bind("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener));
+
+        let mut responses = Vec::new();
+        for path in ["/metrics", "/health"] {
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            responses.push(response);
+        }
+
+        assert!(responses[0].starts_with("HTTP/1.1 200"));
+        assert!(responses[0].contains("ccg_connected_clients 0\n"));
+        assert!(responses[1].starts_with("HTTP/1.1 404"));
+    }
+}
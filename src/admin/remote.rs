@@ -0,0 +1,242 @@
+use crate::admin;
+use crate::game::card_pool::decode_hex;
+use crate::tcp::header::HeaderType;
+use crate::tcp::packet::Packet;
+use crate::tcp::payload::{self, VersionedRequest};
+use crate::{logger, utils::logger::Logger};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::{TcpListener, TcpStream};
+
+/// How far the time a command was signed at may drift from the server clock, so a captured
+/// request cannot be replayed later.
+const MAX_CLOCK_SKEW_MS: i64 = 30_000;
+
+/// Sent by operator tools in an `AdminCommand` packet.
+///
+/// The signature is the hex HMAC-SHA256, keyed by `ADMIN_SECRET`, of the signing time and the
+/// command line joined by a newline, so the secret never crosses the network.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AdminRequest {
+    pub command: String,   // The command line, as typed in the admin console.
+    pub issued_at: i64,    // Unix timestamp (milliseconds) the request was signed at.
+    pub signature: String, // Hex HMAC-SHA256 of `issued_at` and `command`.
+}
+
+impl VersionedRequest for AdminRequest {}
+
+impl AdminRequest {
+    /// Signs a command line with the admin secret, as operator tools do.
+    #[cfg(test)]
+    fn sign(command: &str, issued_at: i64, secret: &[u8]) -> Self {
+        let signature = Self::mac(command, issued_at, secret)
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        Self {
+            command: command.to_string(),
+            issued_at,
+            signature,
+        }
+    }
+
+    /// Whether the request was signed with the admin secret, recently enough.
+    ///
+    /// # Arguments
+    /// * `secret` - The secret shared with the operator tools.
+    /// * `now` - The current Unix timestamp, in milliseconds.
+    pub fn verify(&self, secret: &[u8], now: i64) -> bool {
+        if (now - self.issued_at).abs() > MAX_CLOCK_SKEW_MS {
+            return false;
+        }
+
+        let Some(signature) = decode_hex(&self.signature) else {
+            return false;
+        };
+        Self::mac(&self.command, self.issued_at, secret)
+            .verify_slice(&signature)
+            .is_ok()
+    }
+
+    fn mac(command: &str, issued_at: i64, secret: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+        mac.update(format!("{issued_at}\n{command}").as_bytes());
+        mac
+    }
+}
+
+/// The requests run within the clock skew window, so a captured request cannot be run again
+/// while its signing time is still accepted.
+#[derive(Default)]
+struct SeenRequests {
+    seen: HashSet<(i64, String)>, // Signing time and lowercase signature of each recent request.
+}
+
+impl SeenRequests {
+    /// Records a verified request, forgetting the ones too old to pass verification anyway.
+    ///
+    /// # Returns
+    /// `false` if the same request was already run.
+    fn first_use(&mut self, request: &AdminRequest, now: i64) -> bool {
+        self.seen
+            .retain(|(issued_at, _)| (now - issued_at).abs() <= MAX_CLOCK_SKEW_MS);
+        self.seen
+            .insert((request.issued_at, request.signature.to_ascii_lowercase()))
+    }
+}
+
+/// Sent back in an `AdminResponse` packet.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AdminResponse {
+    pub ok: bool,       // Whether the command was authenticated, parsed and run.
+    pub output: String, // The text the command printed, or why it was refused.
+}
+
+impl AdminResponse {
+    fn packet(request: &Packet, ok: bool, output: String) -> Packet {
+        let payload = serde_cbor::to_vec(&AdminResponse { ok, output }).unwrap_or_default();
+        Packet::reply_to(request, HeaderType::AdminResponse, &payload)
+    }
+}
+
+/// Accepts operator tools on the admin address until the listener fails.
+///
+/// Every `AdminCommand` packet must be signed with the admin secret, and is only run once; a
+/// connection sending an unsigned, stale or replayed request is answered and closed.
+pub async fn serve(address: SocketAddr, secret: String) {
+    let listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(error) => {
+            logger!(ERROR, "[ADMIN] Could not listen on `{address}`: {error}");
+            return;
+        }
+    };
+
+    logger!(INFO, "[ADMIN] Admin channel listening on `{address}`");
+    let secret: Arc<[u8]> = Arc::from(secret.into_bytes());
+    let seen = Arc::new(Mutex::new(SeenRequests::default()));
+    while let Ok((stream, addr)) = listener.accept().await {
+        tokio::spawn(session(
+            stream,
+            addr,
+            Arc::clone(&secret),
+            Arc::clone(&seen),
+        ));
+    }
+}
+
+/// Runs the commands of one operator connection.
+async fn session(
+    mut stream: TcpStream,
+    addr: SocketAddr,
+    secret: Arc<[u8]>,
+    seen: Arc<Mutex<SeenRequests>>,
+) {
+    while let Ok(Some(packet)) = Packet::read_from(&mut stream).await {
+        if packet.header.header_type != HeaderType::AdminCommand {
+            let response =
+                AdminResponse::packet(&packet, false, String::from("Expected an admin command"));
+            let _ = response.write_to(&mut stream).await;
+            continue;
+        }
+
+        let request =
+            match payload::decode::<AdminRequest>(&HeaderType::AdminCommand, &packet.payload) {
+                Ok(request) => request,
+                Err(error) => {
+                    let response = AdminResponse::packet(&packet, false, error.to_string());
+                    let _ = response.write_to(&mut stream).await;
+                    continue;
+                }
+            };
+
+        let now = Utc::now().timestamp_millis();
+        if !request.verify(&secret, now) {
+            logger!(
+                WARN,
+                "[ADMIN] Refused an unauthenticated command from `{addr}`"
+            );
+            let response = AdminResponse::packet(&packet, false, String::from("Unauthorized"));
+            let _ = response.write_to(&mut stream).await;
+            return;
+        }
+
+        let first_use = seen
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .first_use(&request, now);
+        if !first_use {
+            logger!(WARN, "[ADMIN] Refused a replayed command from `{addr}`");
+            let response = AdminResponse::packet(&packet, false, String::from("Replayed"));
+            let _ = response.write_to(&mut stream).await;
+            return;
+        }
+
+        let (ok, output) = match admin::run(&request.command).await {
+            Ok(output) => {
+                logger!(INFO, "[ADMIN] `{addr}` ran `{}`", request.command.trim());
+                (true, output)
+            }
+            Err(error) => (false, error),
+        };
+        if AdminResponse::packet(&packet, ok, output)
+            .write_to(&mut stream)
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_must_be_signed_recently() {
+        let secret = b"admin-secret";
+        let now = Utc::now().timestamp_millis();
+        let request = AdminRequest::sign("kick red", now, secret);
+        assert!(request.verify(secret, now));
+        assert!(!request.verify(b"other-secret", now));
+        assert!(!request.verify(secret, now + 2 * MAX_CLOCK_SKEW_MS));
+
+        let tampered = AdminRequest {
+            command: String::from("end-match"),
+            ..request
+        };
+        assert!(!tampered.verify(secret, now));
+    }
+
+    #[test]
+    fn test_requests_run_only_once() {
+        let secret = b"admin-secret";
+        let now = Utc::now().timestamp_millis();
+        let request = AdminRequest::sign("kick red", now, secret);
+        let mut seen = SeenRequests::default();
+
+        assert!(seen.first_use(&request, now));
+        let shouted = AdminRequest {
+            signature: request.signature.to_ascii_uppercase(),
+            ..request.clone()
+        };
+        assert!(!seen.first_use(&shouted, now + 1_000));
+        assert!(seen.first_use(
+            &AdminRequest::sign("kick red", now + 1, secret),
+            now + 1_000
+        ));
+
+        // Requests past the window are forgotten, verification refusing them anyway.
+        seen.first_use(&request, now + 2 * MAX_CLOCK_SKEW_MS);
+        assert_eq!(1, seen.seen.len());
+    }
+}
@@ -0,0 +1,83 @@
+use crate::game::event_bus::MatchEvent;
+use crate::models::game_action::GameAction;
+use crate::tcp::server::ServerInstance;
+
+/// Namespace of the match variables set by actions applied from the REPL.
+const REPL_NAMESPACE: &str = "repl";
+
+/// A developer command, run from the admin console or the signed admin channel.
+#[derive(Debug, PartialEq)]
+pub enum ReplCommand {
+    /// Evaluates a Lua snippet, with `ctx` bound to the live match view.
+    Lua(String),
+    /// Applies a JSON encoded `GameAction` to the live game state.
+    Action(String),
+}
+
+impl ReplCommand {
+    /// Parses a command line meant for the REPL: `lua <snippet>` or `action <json>`.
+    ///
+    /// # Returns
+    /// The command, or `None` if the line is not a developer command.
+    pub fn parse(line: &str) -> Option<Self> {
+        match line.trim().split_once(' ') {
+            Some(("lua", code)) => Some(ReplCommand::Lua(code.to_string())),
+            Some(("action", action)) => Some(ReplCommand::Action(action.to_string())),
+            _ => None,
+        }
+    }
+
+    /// Runs the command against the server.
+    ///
+    /// An action goes through the same resolution as the actions of a card script and, once
+    /// resolved, the new state is published to the clients and spectators.
+    ///
+    /// # Returns
+    /// The text to send back to the developer.
+    pub async fn execute(&self, server: &ServerInstance) -> String {
+        let game = &server.game_instance;
+        match self {
+            ReplCommand::Lua(code) => {
+                let view = game.game_state.read().await.private_view().await;
+                match game.scripts.eval(code, &view).await {
+                    Ok(value) => value.to_string(),
+                    Err(error) => format!("Lua error: {error}"),
+                }
+            }
+            ReplCommand::Action(action) => match serde_json::from_str::<GameAction>(action) {
+                Ok(action) => {
+                    let described = format!("{action:?}");
+                    let applied = game
+                        .apply_repl_actions(&REPL_NAMESPACE.into(), vec![action])
+                        .await;
+                    match applied {
+                        Ok(_) => {
+                            game.bus.publish(MatchEvent::StateChanged);
+                            format!("Applied {described}")
+                        }
+                        Err(error) => format!("Rolled back {described}: {error}"),
+                    }
+                }
+                Err(error) => format!("Invalid game action: {error}"),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_repl_commands() {
+        assert_eq!(
+            Some(ReplCommand::Lua(String::from("return ctx.turn"))),
+            ReplCommand::parse("lua return ctx.turn\n")
+        );
+        assert_eq!(
+            Some(ReplCommand::Action(String::from("{}"))),
+            ReplCommand::parse("action {}")
+        );
+        assert_eq!(None, ReplCommand::parse("profile"));
+    }
+}
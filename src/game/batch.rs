@@ -0,0 +1,150 @@
+use crate::game::checkpoint::Checkpoint;
+use crate::game::game_state::GameState;
+#[cfg(debug_assertions)]
+use crate::game::invariants;
+use crate::utils::errors::GameLogicError;
+
+/// Maximum number of sub-actions accepted in a single batch.
+pub const MAX_BATCH_SIZE: usize = 16;
+
+/// Makes an ordered list of sub-actions resolve as a single atomic unit.
+///
+/// A checkpoint of the match is captured when the batch begins. Sub-actions then run in order,
+/// and the first one that fails rolls the match back to the checkpoint, so a batch either applies
+/// completely or not at all. Later sub-actions are not attempted once one has failed. The replay
+/// records of the sub-actions are held back until the batch commits.
+pub struct AtomicBatch {
+    checkpoint: Checkpoint, // The state before the first sub-action.
+}
+
+impl AtomicBatch {
+    /// Starts a batch of `size` sub-actions.
+    ///
+    /// # Returns
+    /// * `Ok(AtomicBatch)` - The batch, holding the state to roll back to.
+    /// * `Err(GameLogicError::BatchTooLarge)` - If the batch has more than `MAX_BATCH_SIZE` sub-actions.
+    pub async fn begin(game_state: &GameState, size: usize) -> Result<Self, GameLogicError> {
+        if size > MAX_BATCH_SIZE {
+            return Err(GameLogicError::BatchTooLarge(size));
+        }
+
+        if let Some(replay) = &game_state.replay {
+            replay.hold();
+        }
+        Ok(Self {
+            checkpoint: Checkpoint::capture(game_state).await,
+        })
+    }
+
+    /// Finishes a batch whose sub-actions all succeeded.
+    ///
+    /// In debug and test builds, the board state invariants are checked and a violation panics
+    /// with a diff against the state the batch started from.
+    ///
+    /// # Returns
+    /// The state before the batch, so the whole batch can be undone later.
+    pub async fn commit(self, game_state: &GameState) -> Checkpoint {
+        #[cfg(debug_assertions)]
+        invariants::assert_invariants(&self.checkpoint.views, &game_state.snapshot_views().await);
+        if let Some(replay) = &game_state.replay {
+            replay.release();
+        }
+        self.checkpoint
+    }
+
+    /// Restores the state captured by `begin` after the sub-action at `index` failed.
+    ///
+    /// # Returns
+    /// The error reported for the whole batch, naming the failing sub-action.
+    pub async fn rollback(
+        self,
+        game_state: &GameState,
+        index: usize,
+        error: GameLogicError,
+    ) -> GameLogicError {
+        if let Some(replay) = &game_state.replay {
+            replay.discard();
+        }
+        self.checkpoint.restore(game_state).await;
+        GameLogicError::BatchActionFailed(index, Box::new(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entity::player::PlayerView;
+    use crate::game::prompt::{PromptKind, PromptManager, PromptOrigin};
+    use crate::models::ids::{CardInstanceId, PlayerId};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    fn game_state() -> GameState {
+        let mut views = HashMap::new();
+        let red = PlayerView::from_player(&"red".into(), 30);
+        views.insert("red".into(), Arc::new(RwLock::new(red)));
+        GameState::new_game(views)
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "invariants broken")]
+    async fn test_commit_catches_corrupted_state() {
+        let state = game_state();
+        let batch = AtomicBatch::begin(&state, 1).await.unwrap();
+        state.player_views.read().await[&"red".into()]
+            .write()
+            .await
+            .hand_size = 3;
+        batch.commit(&state).await;
+    }
+
+    #[tokio::test]
+    async fn test_rollback_rewinds_the_random_number_generator() {
+        let state = game_state();
+        let batch = AtomicBatch::begin(&state, 1).await.unwrap();
+        let drawn = state.rng.random_int(1, 1_000_000).unwrap();
+        batch
+            .rollback(&state, 0, GameLogicError::InvalidGameActions)
+            .await;
+
+        assert_eq!(drawn, state.rng.random_int(1, 1_000_000).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_batch_is_rejected_before_resolving() {
+        let state = game_state();
+        assert!(matches!(
+            AtomicBatch::begin(&state, MAX_BATCH_SIZE + 1).await,
+            Err(GameLogicError::BatchTooLarge(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rollback_closes_the_prompts_and_attacks_it_opened() {
+        let state = game_state();
+        let red: PlayerId = "red".into();
+        let open_prompt = |prompts: &mut PromptManager| {
+            let options = vec![String::from("wolf")];
+            let origin = PromptOrigin::Undo(red.clone());
+            prompts.open(&red, PromptKind::ChooseTarget, None, options, origin, 30)
+        };
+
+        let batch = AtomicBatch::begin(&state, 1).await.unwrap();
+        let prompt = open_prompt(&mut *state.prompts.write().await);
+        let attackers = vec![CardInstanceId::nth(1)];
+        state
+            .combat
+            .write()
+            .await
+            .open_window(&red, &"blue".into(), attackers, 30);
+        batch
+            .rollback(&state, 0, GameLogicError::InvalidGameActions)
+            .await;
+
+        assert!(!state.combat.read().await.in_progress());
+        let mut prompts = state.prompts.write().await;
+        assert!(prompts.answer(prompt.id, &red, "wolf").is_err());
+        assert!(open_prompt(&mut prompts).id > prompt.id);
+    }
+}
@@ -0,0 +1,392 @@
+use crate::game::entity::card::{CardRef, CardType, CardView};
+use crate::game::entity::player::PlayerView;
+use crate::models::ids::{CardDefId, CardInstanceId};
+use crate::utils::errors::GameLogicError;
+use std::fmt::Display;
+
+/// A row of the board.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zone {
+    Creatures,
+    Artifacts,
+    Enchantments,
+}
+
+impl Zone {
+    /// The zone cards of a type are placed in; spells never reach the board.
+    pub fn for_card(card_type: CardType) -> Option<Zone> {
+        match card_type {
+            CardType::Creature => Some(Zone::Creatures),
+            CardType::Artifact => Some(Zone::Artifacts),
+            CardType::Enchantment => Some(Zone::Enchantments),
+            CardType::Spell => None,
+        }
+    }
+
+    /// The type of the cards placed in the zone; artifacts and enchantments have their own.
+    pub fn card_type(&self) -> CardType {
+        match self {
+            Zone::Creatures => CardType::Creature,
+            Zone::Artifacts => CardType::Artifact,
+            Zone::Enchantments => CardType::Enchantment,
+        }
+    }
+
+    fn parse(name: &str) -> Option<Zone> {
+        match name {
+            "creature" => Some(Zone::Creatures),
+            "artifact" => Some(Zone::Artifacts),
+            "enchantment" => Some(Zone::Enchantments),
+            _ => None,
+        }
+    }
+
+    fn slots<'a>(&self, view: &'a PlayerView) -> &'a [Option<CardRef>] {
+        match self {
+            Zone::Creatures => &view.board.creatures,
+            Zone::Artifacts => &view.board.artifacts,
+            Zone::Enchantments => &view.board.enchantments,
+        }
+    }
+
+    fn slots_mut<'a>(&self, view: &'a mut PlayerView) -> &'a mut [Option<CardRef>] {
+        match self {
+            Zone::Creatures => &mut view.board.creatures,
+            Zone::Artifacts => &mut view.board.artifacts,
+            Zone::Enchantments => &mut view.board.enchantments,
+        }
+    }
+}
+
+impl Display for Zone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Zone::Creatures => write!(f, "creature"),
+            Zone::Artifacts => write!(f, "artifact"),
+            Zone::Enchantments => write!(f, "enchantment"),
+        }
+    }
+}
+
+/// Where a played card goes on its player's board.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    pub zone: Zone,
+    pub slot: usize,
+}
+
+/// Decides where a card played from hand goes, without changing the board.
+///
+/// Positions are written `<zone>:<slot>` (`creature:2`) or only `<slot>`, the zone then being the
+/// one of the card type. Without a position the card takes the first free slot of its zone. A
+/// card with a copy already on the board joins that copy's stack, so a position must then name the
+/// slot of the stack.
+///
+/// # Arguments
+/// * `view` - The board of the player playing the card.
+/// * `card_id` - The card played.
+/// * `card_type` - The type of the card, which decides its zone.
+/// * `position` - The `target_position` of the play request.
+///
+/// # Returns
+/// * `Ok(None)` - For spells, which do not stay on the board.
+/// * `Ok(Some(Placement))` - The zone and slot the card goes into.
+/// * `Err(GameLogicError)` - If the position is invalid, of the wrong zone or occupied.
+pub fn plan(
+    view: &PlayerView,
+    card_id: &CardDefId,
+    card_type: CardType,
+    position: Option<&str>,
+) -> Result<Option<Placement>, GameLogicError> {
+    let Some(zone) = Zone::for_card(card_type) else {
+        return Ok(None);
+    };
+
+    let requested = match position {
+        None => None,
+        Some(position) => {
+            let (requested_zone, slot) = parse_position(position)?;
+            if requested_zone.is_some_and(|z| z != zone) {
+                return Err(GameLogicError::WrongZone(
+                    card_type.to_string(),
+                    position.to_string(),
+                ));
+            }
+            Some(slot)
+        }
+    };
+
+    let slots = zone.slots(view);
+    if requested.is_some_and(|slot| slot >= slots.len()) {
+        return Err(GameLogicError::InvalidPosition(
+            position.unwrap_or_default().to_string(),
+        ));
+    }
+
+    let stack = slots
+        .iter()
+        .position(|s| matches!(s, Some(c) if &c.id == card_id && c.owner_id.is_none()));
+    let slot = match (stack, requested) {
+        (Some(stack), Some(slot)) if stack != slot => {
+            return Err(GameLogicError::InvalidPosition(format!("{zone}:{slot}")))
+        }
+        (Some(stack), _) => stack,
+        (None, Some(slot)) if slots[slot].is_some() => {
+            return Err(GameLogicError::SlotOccupied(format!("{zone}:{slot}")))
+        }
+        (None, Some(slot)) => slot,
+        (None, None) => slots
+            .iter()
+            .position(Option::is_none)
+            .ok_or_else(|| GameLogicError::ZoneFull(zone.to_string()))?,
+    };
+
+    Ok(Some(Placement { zone, slot }))
+}
+
+/// Puts a copy of a card into the slot chosen by `plan`, stacking it with the copy already there,
+/// and keeps the view of the copy as the board state of its instance.
+pub fn place(view: &mut PlayerView, card: &CardView, placement: Placement) {
+    let slot = &mut placement.zone.slots_mut(view)[placement.slot];
+    match slot {
+        Some(stack) => {
+            stack.amount += 1;
+            stack.instances.push(card.instance_id.clone());
+        }
+        None => {
+            *slot = Some(CardRef {
+                id: card.id.clone(),
+                amount: 1,
+                owner_id: None,
+                instances: vec![card.instance_id.clone()],
+            })
+        }
+    }
+
+    let mut card = card.clone();
+    card.controller_id = view.id.clone();
+    card.in_deck = false;
+    card.in_hand = false;
+    card.in_board = true;
+    card.in_graveyard = false;
+    view.board.cards.insert(card.instance_id.clone(), card);
+}
+
+/// Takes a copy out of a board slot, along with its board state. Once the stack is empty, the
+/// cards to its right move left so the zone has no gaps.
+///
+/// # Returns
+/// The card removed, or `None` if the copy is not in that slot.
+pub fn remove(
+    view: &mut PlayerView,
+    zone: Zone,
+    slot: usize,
+    instance_id: &CardInstanceId,
+) -> Option<CardRef> {
+    let slots = zone.slots_mut(view);
+    let stack = slots.get_mut(slot)?.as_mut()?;
+    let index = stack.instances.iter().position(|id| id == instance_id)?;
+    let removed = CardRef {
+        amount: 1,
+        instances: vec![stack.instances.remove(index)],
+        ..stack.clone()
+    };
+
+    stack.amount -= 1;
+    if stack.amount == 0 {
+        slots[slot] = None;
+        slots[slot..].rotate_left(1);
+    }
+
+    view.board.cards.remove(instance_id);
+    Some(removed)
+}
+
+/// Finds the stack holding a copy of a card on a board, in any zone.
+pub fn find<'a>(
+    view: &'a PlayerView,
+    instance_id: &CardInstanceId,
+) -> Option<(Placement, &'a CardRef)> {
+    [Zone::Creatures, Zone::Artifacts, Zone::Enchantments]
+        .into_iter()
+        .find_map(|zone| {
+            let (slot, stack) =
+                zone.slots(view)
+                    .iter()
+                    .enumerate()
+                    .find_map(|(slot, s)| match s {
+                        Some(c) if c.instances.contains(instance_id) => Some((slot, c)),
+                        _ => None,
+                    })?;
+            Some((Placement { zone, slot }, stack))
+        })
+}
+
+/// Whether a card is a creature on a board.
+pub fn has_creature(view: &PlayerView, instance_id: &CardInstanceId) -> bool {
+    find(view, instance_id).is_some_and(|(placement, _)| placement.zone == Zone::Creatures)
+}
+
+/// Parses a `<zone>:<slot>` or `<slot>` position.
+pub fn parse_position(position: &str) -> Result<(Option<Zone>, usize), GameLogicError> {
+    let invalid = || GameLogicError::InvalidPosition(position.to_string());
+    let (zone, slot) = match position.split_once(':') {
+        Some((zone, slot)) => (Some(Zone::parse(zone).ok_or_else(invalid)?), slot),
+        None => (None, position),
+    };
+
+    Ok((zone, slot.trim().parse().map_err(|_| invalid())?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixtures::sample_card;
+
+    fn view() -> PlayerView {
+        PlayerView::from_player(&"red".into(), 30)
+    }
+
+    fn card(id: &str, instance: u64) -> CardView {
+        CardView::create_view(
+            &sample_card(id),
+            "red".into(),
+            CardInstanceId::nth(instance),
+        )
+    }
+
+    #[test]
+    fn test_cards_are_placed_in_their_zone() {
+        let mut view = view();
+        let wolf = CardDefId::from("wolf");
+
+        let first = plan(&view, &wolf, CardType::Creature, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            Placement {
+                zone: Zone::Creatures,
+                slot: 0
+            },
+            first
+        );
+        place(&mut view, &card("wolf", 0), first);
+
+        let bear = CardDefId::from("bear");
+        let placement = plan(&view, &bear, CardType::Creature, Some("creature:3"));
+        place(&mut view, &card("bear", 1), placement.unwrap().unwrap());
+        assert_eq!(Some(&bear), view.board.creatures[3].as_ref().map(|c| &c.id));
+
+        // A second wolf joins the first one's stack.
+        let second = plan(&view, &wolf, CardType::Creature, Some("0"))
+            .unwrap()
+            .unwrap();
+        place(&mut view, &card("wolf", 2), second);
+        assert_eq!(2, view.board.creatures[0].as_ref().unwrap().amount);
+        let (found, stack) = find(&view, &CardInstanceId::nth(2)).unwrap();
+        assert_eq!(first, found);
+        assert_eq!(
+            vec![CardInstanceId::nth(0), CardInstanceId::nth(2)],
+            stack.instances
+        );
+        assert!(view.board.cards[&CardInstanceId::nth(2)].in_board);
+
+        let totem = CardDefId::from("totem");
+        let artifact = plan(&view, &totem, CardType::Artifact, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(Zone::Artifacts, artifact.zone);
+        assert_eq!(
+            None,
+            plan(&view, &totem, CardType::Spell, Some("creature:1")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_invalid_placements_are_rejected() {
+        let mut view = view();
+        let wolf = CardDefId::from("wolf");
+        place(
+            &mut view,
+            &card("wolf", 0),
+            Placement {
+                zone: Zone::Creatures,
+                slot: 1,
+            },
+        );
+
+        let bear = CardDefId::from("bear");
+        for (position, expected) in [
+            ("creature:1", "SlotOccupied"),
+            ("artifact:0", "WrongZone"),
+            ("creature:6", "InvalidPosition"),
+            ("graveyard:0", "InvalidPosition"),
+            ("left", "InvalidPosition"),
+        ] {
+            let error = plan(&view, &bear, CardType::Creature, Some(position)).unwrap_err();
+            assert!(
+                format!("{error:?}").starts_with(expected),
+                "{position}: {error:?}"
+            );
+        }
+        assert!(plan(&view, &wolf, CardType::Creature, Some("creature:2")).is_err());
+
+        view.board.enchantments = std::array::from_fn(|i| {
+            Some(CardRef {
+                id: format!("aura-{i}").into(),
+                amount: 1,
+                owner_id: None,
+                instances: vec![CardInstanceId::nth(10 + i as u64)],
+            })
+        });
+        assert!(matches!(
+            plan(&view, &bear, CardType::Enchantment, None),
+            Err(GameLogicError::ZoneFull(_))
+        ));
+    }
+
+    #[test]
+    fn test_removal_compacts_the_zone() {
+        let mut view = view();
+        for (slot, id) in ["wolf", "bear", "boar"].iter().enumerate() {
+            place(
+                &mut view,
+                &card(id, slot as u64),
+                Placement {
+                    zone: Zone::Creatures,
+                    slot,
+                },
+            );
+        }
+        place(
+            &mut view,
+            &card("bear", 3),
+            Placement {
+                zone: Zone::Creatures,
+                slot: 1,
+            },
+        );
+
+        // Only the copy asked for leaves the stack.
+        assert!(remove(&mut view, Zone::Creatures, 1, &CardInstanceId::nth(0)).is_none());
+        let removed = remove(&mut view, Zone::Creatures, 1, &CardInstanceId::nth(1)).unwrap();
+        assert_eq!(vec![CardInstanceId::nth(1)], removed.instances);
+        assert!(!view.board.cards.contains_key(&CardInstanceId::nth(1)));
+        assert_eq!(
+            vec![CardInstanceId::nth(3)],
+            view.board.creatures[1].as_ref().unwrap().instances
+        );
+
+        remove(&mut view, Zone::Creatures, 1, &CardInstanceId::nth(3));
+        let ids: Vec<_> = view
+            .board
+            .creatures
+            .iter()
+            .flatten()
+            .map(|c| c.id.to_string())
+            .collect();
+        assert_eq!(vec!["wolf", "boar"], ids);
+        assert!(view.board.creatures[2].is_none());
+        assert!(remove(&mut view, Zone::Creatures, 5, &CardInstanceId::nth(0)).is_none());
+    }
+}
@@ -0,0 +1,222 @@
+use crate::game::entity::card::{Card, CardRef};
+use crate::models::ids::CardDefId;
+use crate::utils::errors::CardRequestError;
+use crate::{logger, utils::logger::Logger, METRICS, SETTINGS};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A card definition fetched from the card server.
+#[derive(Serialize, Deserialize, Clone)]
+struct CachedCard {
+    card: Card,
+    fetched_at: i64, // Unix timestamp (milliseconds) the definition was fetched at.
+}
+
+/// What the catalog holds for a card.
+enum Lookup {
+    /// A definition younger than the lifetime of the entries.
+    Fresh(Card),
+    /// A definition to fetch again, still served if the card server cannot be reached.
+    Stale(Card),
+    Missing,
+}
+
+/// Card definitions fetched from the card server, kept so playing a card never waits on it.
+///
+/// The cards of both decks are fetched when the match is created; a card missing then is fetched
+/// when it is first played. Entries older than `CARD_CACHE_TTL` are fetched again when they are
+/// next needed. With `CARD_CACHE_PATH` set, the entries are kept in that file, so the next
+/// matches hosted on the machine start from them.
+pub struct CardCatalog {
+    entries: Mutex<HashMap<CardDefId, CachedCard>>, // Definitions fetched, by card.
+    ttl: Duration,                                  // Age after which a definition is fetched again.
+    store: Option<PathBuf>,                         // File the entries are kept in across matches.
+}
+
+impl Default for CardCatalog {
+    fn default() -> Self {
+        let settings = SETTINGS.get();
+        let ttl = Duration::from_secs(settings.map_or(3600, |s| s.card_cache_ttl));
+        let store = settings.and_then(|s| s.card_cache_path.as_ref().map(PathBuf::from));
+        Self::new(ttl, store)
+    }
+}
+
+impl CardCatalog {
+    /// Creates a catalog, loading the entries kept in `store` if any.
+    pub fn new(ttl: Duration, store: Option<PathBuf>) -> Self {
+        let entries = match &store {
+            Some(path) => Self::load(path).unwrap_or_else(|error| {
+                logger!(
+                    WARN,
+                    "[CATALOG] Could not read `{}`: {error}",
+                    path.display()
+                );
+                HashMap::new()
+            }),
+            None => HashMap::new(),
+        };
+
+        Self {
+            entries: Mutex::new(entries),
+            ttl,
+            store,
+        }
+    }
+
+    /// The definitions of the cards of a deck, fetching the ones missing or expired in a single
+    /// request.
+    pub async fn cards(&self, cards: &[CardRef]) -> Result<Vec<Card>, CardRequestError> {
+        let mut found = Vec::with_capacity(cards.len());
+        let mut stale = Vec::new();
+        let mut to_fetch = Vec::new();
+        for card in cards {
+            match self.lookup(&card.id) {
+                Lookup::Fresh(definition) => found.push(definition),
+                Lookup::Stale(definition) => {
+                    stale.push(definition);
+                    to_fetch.push(card.clone());
+                }
+                Lookup::Missing => to_fetch.push(card.clone()),
+            }
+        }
+        self.record(found.len(), to_fetch.len());
+        if to_fetch.is_empty() {
+            return Ok(found);
+        }
+
+        match Card::request_cards(&to_fetch).await {
+            Ok(fetched) => {
+                self.insert(&fetched);
+                found.extend(fetched);
+            }
+            Err(error) if stale.len() == to_fetch.len() => {
+                logger!(WARN, "[CATALOG] Serving expired card definitions: {error}");
+                found.extend(stale);
+            }
+            Err(error) => return Err(error),
+        }
+        Ok(found)
+    }
+
+    /// The definition of a card, fetched if it is missing or expired.
+    pub async fn card(&self, card_id: &CardDefId) -> Result<Card, CardRequestError> {
+        let stale = match self.lookup(card_id) {
+            Lookup::Fresh(card) => {
+                self.record(1, 0);
+                return Ok(card);
+            }
+            Lookup::Stale(card) => Some(card),
+            Lookup::Missing => None,
+        };
+        self.record(0, 1);
+
+        match (Card::request_card(card_id).await, stale) {
+            (Ok(card), _) => {
+                self.insert(std::slice::from_ref(&card));
+                Ok(card)
+            }
+            (Err(error), Some(card)) => {
+                logger!(WARN, "[CATALOG] Serving an expired `{card_id}`: {error}");
+                Ok(card)
+            }
+            (Err(error), None) => Err(error),
+        }
+    }
+
+    /// The cards the catalog holds a definition of, expired ones included, sorted by id.
+    pub fn card_ids(&self) -> Vec<CardDefId> {
+        let mut card_ids: Vec<CardDefId> = self.lock().keys().cloned().collect();
+        card_ids.sort();
+        card_ids
+    }
+
+    fn lookup(&self, card_id: &CardDefId) -> Lookup {
+        let entries = self.lock();
+        let Some(entry) = entries.get(card_id) else {
+            return Lookup::Missing;
+        };
+        let age = Utc::now().timestamp_millis() - entry.fetched_at;
+        match age < self.ttl.as_millis() as i64 {
+            true => Lookup::Fresh(entry.card.clone()),
+            false => Lookup::Stale(entry.card.clone()),
+        }
+    }
+
+    /// Keeps definitions fetched now, writing them to the store if any.
+    fn insert(&self, cards: &[Card]) {
+        let fetched_at = Utc::now().timestamp_millis();
+        let mut entries = self.lock();
+        for card in cards {
+            let card = card.clone();
+            entries.insert(card.id.clone(), CachedCard { card, fetched_at });
+        }
+
+        if let Some(path) = &self.store {
+            let saved = serde_json::to_string(&*entries)
+                .map_err(std::io::Error::from)
+                .and_then(|content| std::fs::write(path, content));
+            if let Err(error) = saved {
+                logger!(
+                    WARN,
+                    "[CATALOG] Could not write `{}`: {error}",
+                    path.display()
+                );
+            }
+        }
+    }
+
+    fn load(path: &Path) -> std::io::Result<HashMap<CardDefId, CachedCard>> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn record(&self, hits: usize, misses: usize) {
+        METRICS.record_card_cache(hits as u64, misses as u64);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<CardDefId, CachedCard>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wolf() -> Card {
+        serde_json::from_value(serde_json::json!({
+            "id": "wolf", "name": "Wolf", "description": "", "play_cost": 2, "attack": 2,
+            "health": 1, "rarity": 0, "on_play": [], "on_draw": [], "on_attack": [],
+            "on_hit": [], "on_turn_start": [], "on_turn_end": [], "on_death": [],
+            "on_ally_death": [], "on_enemy_death": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_entries_expire_and_survive_in_the_store() {
+        let store = std::env::temp_dir().join(format!("catalog-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&store);
+        let wolf_id = CardDefId::from("wolf");
+
+        let catalog = CardCatalog::new(Duration::from_secs(60), Some(store.clone()));
+        assert!(matches!(catalog.lookup(&wolf_id), Lookup::Missing));
+        catalog.insert(&[wolf()]);
+        assert!(matches!(catalog.lookup(&wolf_id), Lookup::Fresh(card) if card.name == "Wolf"));
+
+        let next_match = CardCatalog::new(Duration::from_secs(60), Some(store.clone()));
+        assert!(matches!(next_match.lookup(&wolf_id), Lookup::Fresh(_)));
+        let expired = CardCatalog::new(Duration::ZERO, Some(store.clone()));
+        assert!(matches!(expired.lookup(&wolf_id), Lookup::Stale(_)));
+
+        let _ = std::fs::remove_file(&store);
+    }
+}
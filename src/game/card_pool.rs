@@ -0,0 +1,160 @@
+use crate::game::entity::deck::Deck;
+use crate::models::ids::{CardDefId, PlayerId};
+use crate::utils::errors::DeckConstraintError;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+
+/// The cards offered to a player during an arena run, signed by the platform.
+///
+/// The signature is the hex-encoded HMAC-SHA256, keyed by `ARENA_POOL_SECRET`, of the run id, the
+/// player id, the deck id and the card ids joined by commas, each on its own line. A card offered
+/// several times is listed once per copy.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignedPool {
+    pub run_id: String,
+    pub player_id: PlayerId,
+    pub deck_id: String,
+    pub cards: Vec<CardDefId>,
+    pub signature: String,
+}
+
+impl SignedPool {
+    /// The text covered by the signature.
+    fn message(&self) -> String {
+        let cards: Vec<_> = self.cards.iter().map(|id| id.to_string()).collect();
+        format!(
+            "{}\n{}\n{}\n{}",
+            self.run_id,
+            self.player_id,
+            self.deck_id,
+            cards.join(",")
+        )
+    }
+
+    /// Checks that the pool was signed by the platform and that a deck of a player was built from it.
+    ///
+    /// # Arguments
+    /// * `player_id` - The player bringing the deck.
+    /// * `deck` - The deck to validate.
+    /// * `secret` - The secret shared with the platform.
+    ///
+    /// # Returns
+    /// * `Ok(())` - If every card of the deck was offered during the run.
+    /// * `Err(DeckConstraintError)` - If the pool was tampered with, belongs to another player or
+    ///   deck, or the deck holds cards the pool did not offer.
+    pub fn validate(
+        &self,
+        player_id: &PlayerId,
+        deck: &Deck,
+        secret: &[u8],
+    ) -> Result<(), DeckConstraintError> {
+        let signature = decode_hex(&self.signature).ok_or(DeckConstraintError::TamperedPool)?;
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+        mac.update(self.message().as_bytes());
+        mac.verify_slice(&signature)
+            .map_err(|_| DeckConstraintError::TamperedPool)?;
+
+        if &self.player_id != player_id || self.deck_id != deck.id {
+            return Err(DeckConstraintError::PoolMismatch(self.run_id.clone()));
+        }
+
+        let mut offered: HashMap<&CardDefId, u32> = HashMap::new();
+        for card_id in &self.cards {
+            *offered.entry(card_id).or_default() += 1;
+        }
+
+        for card in &deck.cards {
+            let left = offered.entry(&card.id).or_default();
+            if *left < card.amount {
+                return Err(DeckConstraintError::CardNotInPool(card.id.to_string()));
+            }
+            *left -= card.amount;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entity::card::CardRef;
+
+    const SECRET: &[u8] = b"arena-secret";
+
+    fn pool(cards: &[&str]) -> SignedPool {
+        let mut pool = SignedPool {
+            run_id: "run-1".to_string(),
+            player_id: "red".into(),
+            deck_id: "deck-1".to_string(),
+            cards: cards.iter().map(|id| (*id).into()).collect(),
+            signature: String::new(),
+        };
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET).unwrap();
+        mac.update(pool.message().as_bytes());
+        let tag = mac.finalize().into_bytes();
+        pool.signature = tag.iter().map(|byte| format!("{byte:02x}")).collect();
+        pool
+    }
+
+    fn deck(cards: &[(&str, u32)]) -> Deck {
+        Deck {
+            id: "deck-1".to_string(),
+            player_id: "red".into(),
+            name: "Arena".to_string(),
+            cards: cards
+                .iter()
+                .map(|(id, amount)| CardRef {
+                    id: (*id).into(),
+                    amount: *amount,
+                    owner_id: None,
+                    instances: Vec::new(),
+                })
+                .collect(),
+            sideboard: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_decks_must_come_from_the_signed_pool() {
+        let red = "red".into();
+        let pool = pool(&["wolf", "wolf", "fireball"]);
+        let valid = deck(&[("wolf", 2), ("fireball", 1)]);
+        assert!(pool.validate(&red, &valid, SECRET).is_ok());
+
+        let extra = deck(&[("wolf", 3)]);
+        assert!(matches!(
+            pool.validate(&red, &extra, SECRET),
+            Err(DeckConstraintError::CardNotInPool(_))
+        ));
+        assert!(matches!(
+            pool.validate(&"blue".into(), &valid, SECRET),
+            Err(DeckConstraintError::PoolMismatch(_))
+        ));
+
+        let mut tampered = pool.clone();
+        tampered.cards.push("dragon".into());
+        assert!(matches!(
+            tampered.validate(&red, &valid, SECRET),
+            Err(DeckConstraintError::TamperedPool)
+        ));
+        assert!(matches!(
+            pool.validate(&red, &valid, b"other-secret"),
+            Err(DeckConstraintError::TamperedPool)
+        ));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::game::combat::CombatTracker;
+use crate::game::control::ControlTracker;
+use crate::game::cooldown::CooldownTracker;
+use crate::game::entity::player::PlayerView;
+use crate::game::game_state::GameState;
+use crate::game::highlights::HighlightDetector;
+use crate::game::prompt::PromptManager;
+use crate::game::think_time::ThinkTimeTracker;
+use crate::game::variables::MatchVariables;
+use crate::models::ids::PlayerId;
+use std::collections::HashMap;
+
+/// The state of a match at a point in time, to roll back to.
+///
+/// Holds everything an action can change: the player views, the match variables, the activations
+/// used, the pending control changes, the attack waiting on blockers, the open prompts, the replay
+/// bookmarks, the turn clock pauses and the position of the random number generator. The event
+/// log is not copied; restoring forgets the events recorded since the checkpoint was captured.
+#[derive(Clone)]
+pub struct Checkpoint {
+    pub views: HashMap<PlayerId, PlayerView>,
+    variables: MatchVariables,
+    cooldowns: CooldownTracker,
+    control: ControlTracker,
+    combat: CombatTracker,
+    prompts: PromptManager,
+    highlights: HighlightDetector,
+    think_time: ThinkTimeTracker,
+    last_event: u64,  // Sequence of the last event recorded before the checkpoint.
+    rng_cursor: u128, // Position of the random number generator.
+}
+
+impl Checkpoint {
+    /// Captures the current state of the match.
+    pub async fn capture(game_state: &GameState) -> Self {
+        Self {
+            views: game_state.snapshot_views().await,
+            variables: game_state.variables.read().await.clone(),
+            cooldowns: game_state.cooldowns.read().await.clone(),
+            control: game_state.control.read().await.clone(),
+            combat: game_state.combat.read().await.clone(),
+            prompts: game_state.prompts.read().await.clone(),
+            highlights: game_state.highlights.read().await.clone(),
+            think_time: game_state.think_time.read().await.clone(),
+            last_event: game_state.events.read().await.last_sequence(),
+            rng_cursor: game_state.rng.cursor(),
+        }
+    }
+
+    /// Puts the match back in the captured state.
+    pub async fn restore(self, game_state: &GameState) {
+        game_state.restore_views(self.views).await;
+        *game_state.variables.write().await = self.variables;
+        *game_state.cooldowns.write().await = self.cooldowns;
+        *game_state.control.write().await = self.control;
+        *game_state.combat.write().await = self.combat;
+        game_state.prompts.write().await.rewind(self.prompts);
+        *game_state.highlights.write().await = self.highlights;
+        *game_state.think_time.write().await = self.think_time;
+        game_state.events.write().await.truncate(self.last_event);
+        game_state.rng.rewind(self.rng_cursor);
+    }
+}
@@ -0,0 +1,270 @@
+use crate::models::ids::{CardInstanceId, PlayerId};
+use crate::utils::errors::GameLogicError;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// How creatures fight, chosen with the match type.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CombatMode {
+    /// Attacking creatures hit the defending player straight away.
+    #[default]
+    DirectAttack,
+    /// The defending player may block each attacker with one of their creatures before damage.
+    Blockers,
+}
+
+/// A creature of the defending player blocking an attacker.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Block {
+    pub blocker: CardInstanceId,
+    pub attacker: CardInstanceId,
+}
+
+/// Sent to the defending player in a `DeclareAttackers` packet, opening the window in which they
+/// may declare blockers.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CombatWindow {
+    pub attacker_id: PlayerId,
+    pub defender_id: PlayerId,
+    pub attackers: Vec<CardInstanceId>,
+    /// Unix timestamp (milliseconds) after which the attack resolves unblocked.
+    pub deadline: i64,
+}
+
+/// What a combat did once resolved.
+#[derive(Debug, Default, PartialEq)]
+pub struct CombatOutcome {
+    pub player_damage: u32, // Damage dealt to the defending player by unblocked attackers.
+    pub creature_damage: Vec<(CardInstanceId, u32)>, // Damage dealt to blockers and blocked.
+}
+
+/// Keeps track of the attack waiting on blockers and of the creatures that attacked this turn.
+#[derive(Default, Clone)]
+pub struct CombatTracker {
+    pending: Option<CombatWindow>,
+    attacked: HashSet<CardInstanceId>,
+}
+
+impl CombatTracker {
+    /// Checks that creatures may attack: at least one, each once, none that attacked this turn,
+    /// and no attack already waiting on blockers.
+    pub fn check_attackers(&self, attackers: &[CardInstanceId]) -> Result<(), GameLogicError> {
+        if self.pending.is_some() {
+            return Err(GameLogicError::CombatInProgress);
+        }
+
+        if attackers.is_empty() {
+            return Err(GameLogicError::NoAttackers);
+        }
+
+        let mut seen = HashSet::new();
+        for attacker in attackers {
+            if self.attacked.contains(attacker) || !seen.insert(attacker) {
+                return Err(GameLogicError::InvalidAttacker(attacker.to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Marks creatures as having attacked this turn.
+    pub fn record_attackers(&mut self, attackers: &[CardInstanceId]) {
+        self.attacked.extend(attackers.iter().cloned());
+    }
+
+    /// Opens the window in which the defender may block an attack.
+    ///
+    /// The window closes `timeout_secs` seconds from now.
+    pub fn open_window(
+        &mut self,
+        attacker_id: &PlayerId,
+        defender_id: &PlayerId,
+        attackers: Vec<CardInstanceId>,
+        timeout_secs: u64,
+    ) -> CombatWindow {
+        let window = CombatWindow {
+            attacker_id: attacker_id.clone(),
+            defender_id: defender_id.clone(),
+            attackers,
+            deadline: Utc::now().timestamp_millis() + (timeout_secs * 1000) as i64,
+        };
+
+        self.pending = Some(window.clone());
+        window
+    }
+
+    /// The attack a player is defending against.
+    ///
+    /// # Returns
+    /// * `Ok(&CombatWindow)` - The attack waiting on the player's blockers.
+    /// * `Err(GameLogicError::NoCombatToBlock)` - If no attack waits on the player's blockers.
+    pub fn window(&self, defender_id: &PlayerId) -> Result<&CombatWindow, GameLogicError> {
+        self.pending
+            .as_ref()
+            .filter(|window| &window.defender_id == defender_id)
+            .ok_or(GameLogicError::NoCombatToBlock)
+    }
+
+    /// Closes the window of the attack a player is defending against.
+    ///
+    /// # Returns
+    /// * `Ok(CombatWindow)` - The attack to resolve.
+    /// * `Err(GameLogicError::NoCombatToBlock)` - If no attack waits on the player's blockers.
+    pub fn take_window(&mut self, defender_id: &PlayerId) -> Result<CombatWindow, GameLogicError> {
+        self.window(defender_id)?;
+        self.pending.take().ok_or(GameLogicError::NoCombatToBlock)
+    }
+
+    /// Closes the window of an attack whose deadline is earlier than `now` (unix milliseconds).
+    pub fn take_expired(&mut self, now: i64) -> Option<CombatWindow> {
+        match &self.pending {
+            Some(window) if window.deadline < now => self.pending.take(),
+            _ => None,
+        }
+    }
+
+    /// Whether an attack waits on blockers.
+    pub fn in_progress(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Creatures may attack again once a new turn starts.
+    pub fn start_turn(&mut self) {
+        self.attacked.clear();
+    }
+}
+
+/// Checks that each blocker blocks a single attacker of the attack, and each attacker is blocked
+/// at most once.
+///
+/// # Arguments
+/// * `window` - The attack being blocked.
+/// * `blocks` - The blocks declared by the defender.
+/// * `can_block` - Whether a creature is on the defender's board.
+pub fn check_blocks(
+    window: &CombatWindow,
+    blocks: &[Block],
+    can_block: impl Fn(&CardInstanceId) -> bool,
+) -> Result<(), GameLogicError> {
+    let mut blockers = HashSet::new();
+    let mut blocked = HashSet::new();
+    for block in blocks {
+        if !can_block(&block.blocker) || !blockers.insert(&block.blocker) {
+            return Err(GameLogicError::InvalidBlock(block.blocker.to_string()));
+        }
+
+        if !window.attackers.contains(&block.attacker) || !blocked.insert(&block.attacker) {
+            return Err(GameLogicError::InvalidBlock(block.attacker.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves the damage of an attack.
+///
+/// Unblocked attackers deal their attack to the defending player. A blocked attacker and its
+/// blocker deal their attack to each other; the caller applies that damage to their current
+/// health.
+///
+/// # Arguments
+/// * `attackers` - The attacking creatures.
+/// * `blocks` - The blocks declared by the defender, already checked.
+/// * `attack` - The current attack of a creature, or `None` if it left the board.
+pub fn resolve(
+    attackers: &[CardInstanceId],
+    blocks: &[Block],
+    attack: impl Fn(&CardInstanceId) -> Option<i32>,
+) -> CombatOutcome {
+    let blockers: HashMap<_, _> = blocks.iter().map(|b| (&b.attacker, &b.blocker)).collect();
+    let mut outcome = CombatOutcome::default();
+
+    for attacker in attackers {
+        let Some(attacker_attack) = attack(attacker) else {
+            continue;
+        };
+
+        let Some(blocker) = blockers.get(attacker) else {
+            outcome.player_damage += attacker_attack.max(0) as u32;
+            continue;
+        };
+
+        let Some(blocker_attack) = attack(blocker) else {
+            continue;
+        };
+
+        outcome
+            .creature_damage
+            .push(((*blocker).clone(), attacker_attack.max(0) as u32));
+        outcome
+            .creature_damage
+            .push((attacker.clone(), blocker_attack.max(0) as u32));
+    }
+
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(n: u64) -> CardInstanceId {
+        CardInstanceId::nth(n)
+    }
+
+    #[test]
+    fn test_blocked_attackers_trade_damage_with_their_blockers() {
+        let mut tracker = CombatTracker::default();
+        let attackers = vec![id(0), id(1), id(2)];
+        tracker.check_attackers(&attackers).unwrap();
+        tracker.record_attackers(&attackers);
+        let window = tracker.open_window(&"red".into(), &"blue".into(), attackers, 20);
+        assert!(matches!(
+            tracker.check_attackers(&[id(3)]),
+            Err(GameLogicError::CombatInProgress)
+        ));
+        assert!(tracker.take_window(&"red".into()).is_err());
+        assert_eq!(window, tracker.take_window(&"blue".into()).unwrap());
+        assert!(tracker.check_attackers(&[id(0)]).is_err());
+
+        // 0: attack 3 blocked by attack 2, 1: attack 1 blocked by attack 4, 2: attack 5 unblocked.
+        let blocks = vec![
+            Block {
+                blocker: id(10),
+                attacker: id(0),
+            },
+            Block {
+                blocker: id(11),
+                attacker: id(1),
+            },
+        ];
+        let on_board = |card: &CardInstanceId| [id(10), id(11)].contains(card);
+        check_blocks(&window, &blocks, on_board).unwrap();
+        let twice = vec![
+            Block {
+                blocker: id(10),
+                attacker: id(0),
+            },
+            Block {
+                blocker: id(10),
+                attacker: id(1),
+            },
+        ];
+        assert!(check_blocks(&window, &twice, on_board).is_err());
+
+        let attack: HashMap<_, _> = [(id(0), 3), (id(1), 1), (id(2), 5), (id(10), 2), (id(11), 4)]
+            .into_iter()
+            .collect();
+        let outcome = resolve(&window.attackers, &blocks, |card| attack.get(card).copied());
+        assert_eq!(5, outcome.player_damage);
+        assert_eq!(
+            vec![(id(10), 3), (id(0), 2), (id(11), 1), (id(1), 4)],
+            outcome.creature_damage
+        );
+
+        tracker.start_turn();
+        assert!(tracker.check_attackers(&[id(0)]).is_ok());
+    }
+}
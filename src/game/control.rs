@@ -0,0 +1,211 @@
+use crate::game::board::{self, Zone};
+use crate::game::entity::card::CardRef;
+use crate::game::entity::player::PlayerView;
+use crate::models::ids::{CardDefId, CardInstanceId, PlayerId};
+
+/// A temporary change of controller, reverted once the game reaches `expires_on_turn`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlChange {
+    pub instance_id: CardInstanceId,
+    pub card_id: CardDefId,
+    pub owner_id: PlayerId,
+    pub controller_id: PlayerId,
+    pub expires_on_turn: u32,
+}
+
+/// Keeps track of the control changes that must be reverted when their effect expires.
+#[derive(Default, Clone)]
+pub struct ControlTracker {
+    changes: Vec<ControlChange>,
+}
+
+impl ControlTracker {
+    pub fn record(&mut self, change: ControlChange) {
+        self.changes.push(change);
+    }
+
+    /// Removes and returns every change that expires on or before `turn`.
+    pub fn take_expired(&mut self, turn: u32) -> Vec<ControlChange> {
+        let (expired, active) = self
+            .changes
+            .drain(..)
+            .partition(|c| c.expires_on_turn <= turn);
+        self.changes = active;
+        expired
+    }
+}
+
+/// Where a creature ended up after changing controller.
+#[derive(Debug, PartialEq)]
+pub enum Transfer {
+    /// The creature now sits in this slot of the new controller's board.
+    Moved(usize),
+    /// The new controller's board was full, so the creature went to its owner's graveyard.
+    Destroyed,
+    /// No creature with that instance id was on the board.
+    NotOnBoard,
+}
+
+/// Moves one copy of a creature from `from`'s board to `to`'s board.
+///
+/// A card keeps its owner wherever it goes: on the board of another player its `CardRef` carries
+/// the owner id, and it only stacks with copies that have the same owner. Ally and enemy triggers
+/// follow the board the card sits on, while the graveyard it dies into is always its owner's. The
+/// board state of the copy moves with it, its damage and effects kept, under its new controller.
+///
+/// # Arguments
+/// * `from` - The current controller of the creature.
+/// * `to` - The new controller.
+/// * `instance_id` - The copy of the creature to move.
+pub fn transfer_creature(
+    from: &mut PlayerView,
+    to: &mut PlayerView,
+    instance_id: &CardInstanceId,
+) -> Transfer {
+    let Some((placement, stack)) = board::find(from, instance_id) else {
+        return Transfer::NotOnBoard;
+    };
+    if placement.zone != Zone::Creatures {
+        return Transfer::NotOnBoard;
+    }
+
+    let card_id = stack.id.clone();
+    let owner_id = stack.owner_or(&from.id).clone();
+    let state = from.board.cards.get(instance_id).cloned();
+    board::remove(from, Zone::Creatures, placement.slot, instance_id);
+    let take_state = |to: &mut PlayerView| {
+        if let Some(mut state) = state.clone() {
+            state.controller_id = to.id.clone();
+            to.board.cards.insert(instance_id.clone(), state);
+        }
+    };
+
+    let moved_owner = (owner_id != to.id).then(|| owner_id.clone());
+    let creatures = &mut to.board.creatures;
+    let stacked = creatures
+        .iter()
+        .position(|s| matches!(s, Some(c) if c.id == card_id && c.owner_id == moved_owner));
+    if let Some(index) = stacked {
+        if let Some(stack) = &mut creatures[index] {
+            stack.amount += 1;
+            stack.instances.push(instance_id.clone());
+        }
+        take_state(to);
+        return Transfer::Moved(index);
+    }
+
+    if let Some(index) = creatures.iter().position(Option::is_none) {
+        creatures[index] = Some(CardRef {
+            id: card_id,
+            amount: 1,
+            owner_id: moved_owner,
+            instances: vec![instance_id.clone()],
+        });
+        take_state(to);
+        return Transfer::Moved(index);
+    }
+
+    let owner = if owner_id == from.id { from } else { to };
+    owner.graveyard.creatures.push(CardRef {
+        id: card_id,
+        amount: 1,
+        owner_id: None,
+        instances: vec![instance_id.clone()],
+    });
+    owner.graveyard_size += 1;
+    Transfer::Destroyed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entity::card::CardView;
+    use crate::test_support::fixtures::sample_card;
+
+    fn wolf(instances: &[u64]) -> Option<CardRef> {
+        Some(CardRef {
+            id: "wolf".into(),
+            amount: instances.len() as u32,
+            owner_id: None,
+            instances: instances.iter().map(|n| CardInstanceId::nth(*n)).collect(),
+        })
+    }
+
+    #[test]
+    fn test_transfer_and_return() {
+        let mut red = PlayerView::from_player(&"red".into(), 30);
+        let mut blue = PlayerView::from_player(&"blue".into(), 30);
+        red.board.creatures[2] = wolf(&[0, 1]);
+        blue.board.creatures[0] = wolf(&[2]);
+        let mut state =
+            CardView::create_view(&sample_card("wolf"), "red".into(), CardInstanceId::nth(1));
+        state.health = 1;
+        red.board.cards.insert(CardInstanceId::nth(1), state);
+
+        let moved = transfer_creature(&mut red, &mut blue, &CardInstanceId::nth(1));
+        assert_eq!(Transfer::Moved(1), moved);
+        assert_eq!(1, red.board.creatures[2].as_ref().unwrap().amount);
+        assert_eq!(
+            Some("red".into()),
+            blue.board.creatures[1].as_ref().unwrap().owner_id
+        );
+        // The copy keeps its state under its new controller.
+        let state = &blue.board.cards[&CardInstanceId::nth(1)];
+        assert_eq!(
+            (PlayerId::from("blue"), 1),
+            (state.controller_id.clone(), state.health)
+        );
+        assert!(!red.board.cards.contains_key(&CardInstanceId::nth(1)));
+
+        let back = transfer_creature(&mut blue, &mut red, &CardInstanceId::nth(1));
+        assert_eq!(Transfer::Moved(2), back);
+        assert_eq!(
+            PlayerId::from("red"),
+            red.board.cards[&CardInstanceId::nth(1)].controller_id
+        );
+        assert_eq!(2, red.board.creatures[2].as_ref().unwrap().amount);
+        assert_eq!(
+            Transfer::NotOnBoard,
+            transfer_creature(&mut blue, &mut red, &CardInstanceId::nth(1))
+        );
+        assert!(blue.board.creatures[1].is_none());
+    }
+
+    #[test]
+    fn test_full_board_destroys_creature() {
+        let mut red = PlayerView::from_player(&"red".into(), 30);
+        let mut blue = PlayerView::from_player(&"blue".into(), 30);
+        red.board.creatures[0] = wolf(&[0]);
+        blue.board.creatures = std::array::from_fn(|i| {
+            Some(CardRef {
+                id: format!("bear-{i}").into(),
+                amount: 1,
+                owner_id: None,
+                instances: vec![CardInstanceId::nth(10 + i as u64)],
+            })
+        });
+
+        let moved = transfer_creature(&mut red, &mut blue, &CardInstanceId::nth(0));
+        assert_eq!(Transfer::Destroyed, moved);
+        assert!(red.board.creatures[0].is_none());
+        assert_eq!(1, red.graveyard_size);
+    }
+
+    #[test]
+    fn test_take_expired() {
+        let mut tracker = ControlTracker::default();
+        for expires_on_turn in [3, 5] {
+            tracker.record(ControlChange {
+                expires_on_turn,
+                instance_id: CardInstanceId::nth(0),
+                card_id: "wolf".into(),
+                owner_id: "red".into(),
+                controller_id: "blue".into(),
+            });
+        }
+
+        assert!(tracker.take_expired(2).is_empty());
+        assert_eq!(1, tracker.take_expired(4).len());
+        assert_eq!(5, tracker.take_expired(9)[0].expires_on_turn);
+    }
+}
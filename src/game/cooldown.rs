@@ -0,0 +1,127 @@
+use crate::models::ids::{CardDefId, PlayerId};
+use crate::utils::errors::GameLogicError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How often the activated ability of a card may be used.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ActivationLimit {
+    /// Up to `uses` activations during each turn of the player.
+    PerTurn { uses: u32 },
+    /// Up to `charges` activations for the whole match.
+    Charges { charges: u32 },
+}
+
+impl ActivationLimit {
+    /// The activations allowed per turn or per match.
+    pub fn allowance(&self) -> u32 {
+        match self {
+            ActivationLimit::PerTurn { uses } => *uses,
+            ActivationLimit::Charges { charges } => *charges,
+        }
+    }
+}
+
+/// Enforces the activation limits of every card, so scripts never have to count uses themselves.
+///
+/// Uses are counted per player and card, copies of a card sharing their limit.
+#[derive(Default, Clone)]
+pub struct CooldownTracker {
+    limits: HashMap<CardDefId, ActivationLimit>,
+    used: HashMap<(PlayerId, CardDefId), u32>, // Activations this turn, or this match for charges.
+}
+
+impl CooldownTracker {
+    /// Registers the activation limit of a card definition.
+    pub fn register(&mut self, card_id: &CardDefId, limit: ActivationLimit) {
+        self.limits.insert(card_id.clone(), limit);
+    }
+
+    /// The activations a player has left for a card, or `None` if the card has no ability.
+    pub fn remaining(&self, player_id: &PlayerId, card_id: &CardDefId) -> Option<u32> {
+        let limit = self.limits.get(card_id)?;
+        let used = self
+            .used
+            .get(&(player_id.clone(), card_id.clone()))
+            .copied()
+            .unwrap_or(0);
+        Some(limit.allowance().saturating_sub(used))
+    }
+
+    /// Checks that a player has an activation of a card left, without using it up.
+    ///
+    /// # Returns
+    /// * `Ok(u32)` - The activations left, at least one.
+    /// * `Err(GameLogicError)` - If the card has no activated ability or no activation left.
+    pub fn check(&self, player_id: &PlayerId, card_id: &CardDefId) -> Result<u32, GameLogicError> {
+        let remaining = self
+            .remaining(player_id, card_id)
+            .ok_or_else(|| GameLogicError::NoActivatedAbility(card_id.to_string()))?;
+        match remaining {
+            0 => Err(GameLogicError::AbilityExhausted(card_id.to_string())),
+            remaining => Ok(remaining),
+        }
+    }
+
+    /// Uses up one activation of a card.
+    ///
+    /// # Returns
+    /// * `Ok(u32)` - The activations left afterwards.
+    /// * `Err(GameLogicError)` - If the card has no activated ability or no activation left.
+    pub fn activate(
+        &mut self,
+        player_id: &PlayerId,
+        card_id: &CardDefId,
+    ) -> Result<u32, GameLogicError> {
+        let remaining = self.check(player_id, card_id)?;
+        *self
+            .used
+            .entry((player_id.clone(), card_id.clone()))
+            .or_default() += 1;
+        Ok(remaining - 1)
+    }
+
+    /// Gives a player back the per-turn uses of their cards, at the start of their turn.
+    pub fn start_turn(&mut self, player_id: &PlayerId) {
+        let limits = &self.limits;
+        self.used.retain(|(owner, card_id), _| {
+            owner != player_id
+                || !matches!(limits.get(card_id), Some(ActivationLimit::PerTurn { .. }))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_activation_limits() {
+        let mut cooldowns = CooldownTracker::default();
+        let (red, blue) = (PlayerId::from("red"), PlayerId::from("blue"));
+        let (totem, wand) = (CardDefId::from("totem"), CardDefId::from("wand"));
+        cooldowns.register(&totem, ActivationLimit::PerTurn { uses: 1 });
+        cooldowns.register(&wand, ActivationLimit::Charges { charges: 2 });
+
+        assert_eq!(1, cooldowns.check(&red, &totem).unwrap());
+        assert_eq!(Some(1), cooldowns.remaining(&red, &totem));
+        assert_eq!(0, cooldowns.activate(&red, &totem).unwrap());
+        assert!(cooldowns.check(&red, &totem).is_err());
+        assert!(matches!(
+            cooldowns.activate(&red, &totem),
+            Err(GameLogicError::AbilityExhausted(_))
+        ));
+        assert_eq!(Some(1), cooldowns.remaining(&blue, &totem));
+        assert_eq!(1, cooldowns.activate(&red, &wand).unwrap());
+
+        // A new turn restores per-turn uses, but not charges.
+        cooldowns.start_turn(&red);
+        assert_eq!(Some(1), cooldowns.remaining(&red, &totem));
+        assert_eq!(Some(1), cooldowns.remaining(&red, &wand));
+        assert!(matches!(
+            cooldowns.activate(&red, &"bear".into()),
+            Err(GameLogicError::NoActivatedAbility(_))
+        ));
+    }
+}
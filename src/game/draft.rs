@@ -0,0 +1,271 @@
+use crate::game::entity::card::CardRef;
+use crate::game::entity::deck::Deck;
+use crate::game::rng::MatchRng;
+use crate::models::ids::{CardDefId, PlayerId};
+use crate::utils::errors::DraftError;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// How a limited match is drafted, sent by the matchmaker in `InitServer`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DraftSettings {
+    #[serde(default)]
+    pub set: Option<String>, // The card set the card server deals packs of; the local catalog deals them if unset.
+    #[serde(default = "default_packs")]
+    pub packs: u32, // Packs opened by each player.
+    #[serde(default = "default_pack_size")]
+    pub pack_size: u32, // Cards in each pack.
+}
+
+impl DraftSettings {
+    /// Packs dealt for the whole draft.
+    pub fn pack_count(&self, players: usize) -> usize {
+        self.packs as usize * players
+    }
+}
+
+/// Sent to every drafting player in a `DraftPack` packet whenever the draft moves on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DraftView {
+    pub round: u32,            // The pack being picked from, counting from 1.
+    pub rounds: u32,           // Packs opened in the whole draft.
+    pub pack: Vec<CardDefId>,  // The cards left in the open pack.
+    pub picker: PlayerId,      // The player picking from it.
+    pub pick_deadline: i64,    // Unix timestamp (milliseconds) the pick is made for them at.
+    pub picks: Vec<CardDefId>, // The cards drafted by the receiving player so far.
+}
+
+/// The pick phase of a limited match: the players take turns picking one card of the open pack
+/// until it is empty, then the next pack is opened.
+///
+/// The first pick of each pack alternates between the players, and the cards a player picks
+/// become their deck.
+pub struct Draft {
+    players: Vec<PlayerId>,                   // In the order they open packs.
+    packs: VecDeque<Vec<CardDefId>>,          // The packs not opened yet.
+    open: Vec<CardDefId>,                     // The cards left in the open pack.
+    round: u32,                               // Packs opened so far.
+    rounds: u32,                              // Packs of the whole draft.
+    picker: usize,                            // Index in `players` of the player picking.
+    picks: HashMap<PlayerId, Vec<CardDefId>>, // The cards each player drafted, in pick order.
+}
+
+impl Draft {
+    /// Starts a draft by opening its first pack.
+    pub fn new(players: Vec<PlayerId>, packs: Vec<Vec<CardDefId>>) -> Self {
+        let picks = players.iter().map(|p| (p.clone(), Vec::new())).collect();
+        let mut draft = Self {
+            players,
+            rounds: packs.len() as u32,
+            packs: packs.into(),
+            open: Vec::new(),
+            round: 0,
+            picker: 0,
+            picks,
+        };
+        draft.open_next();
+        draft
+    }
+
+    /// Deals packs of random cards, which may repeat.
+    ///
+    /// # Arguments
+    /// * `cards` - The cards the packs are dealt from.
+    /// * `count` - How many packs to deal.
+    /// * `size` - How many cards each pack holds.
+    pub fn deal(
+        cards: &[CardDefId],
+        count: usize,
+        size: usize,
+        rng: &MatchRng,
+    ) -> Result<Vec<Vec<CardDefId>>, DraftError> {
+        if cards.is_empty() {
+            return Err(DraftError::NoCards);
+        }
+
+        let mut packs = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut pack = Vec::with_capacity(size);
+            for _ in 0..size {
+                let index = rng
+                    .random_choice(cards.len())
+                    .map_err(|_| DraftError::NoCards)?;
+                pack.push(cards[index].clone());
+            }
+            packs.push(pack);
+        }
+        Ok(packs)
+    }
+
+    /// Opens the next pack that holds cards, if any is left.
+    fn open_next(&mut self) {
+        while self.open.is_empty() {
+            let Some(pack) = self.packs.pop_front() else {
+                return;
+            };
+            self.picker = self.round as usize % self.players.len().max(1);
+            self.round += 1;
+            self.open = pack;
+        }
+    }
+
+    /// The player picking now, or `None` once the draft is complete.
+    pub fn picker(&self) -> Option<&PlayerId> {
+        match self.is_complete() {
+            true => None,
+            false => self.players.get(self.picker),
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.open.is_empty()
+    }
+
+    /// Takes a card of the open pack for a player, then hands the pack to the next player.
+    ///
+    /// # Returns
+    /// An error if the draft is complete, it is not the player's pick or the pack does not hold
+    /// the card.
+    pub fn pick(&mut self, player_id: &PlayerId, card_id: &CardDefId) -> Result<(), DraftError> {
+        if self.is_complete() {
+            return Err(DraftError::DraftComplete);
+        }
+        if self.picker() != Some(player_id) {
+            return Err(DraftError::NotYourPick);
+        }
+        let index = self
+            .open
+            .iter()
+            .position(|card| card == card_id)
+            .ok_or_else(|| DraftError::CardNotInPack(card_id.to_string()))?;
+
+        let card = self.open.remove(index);
+        self.picks.entry(player_id.clone()).or_default().push(card);
+        self.picker = (self.picker + 1) % self.players.len();
+        self.open_next();
+        Ok(())
+    }
+
+    /// Picks the first card of the open pack for the player whose pick timer ran out.
+    ///
+    /// # Returns
+    /// The player and the card picked for them, or `None` once the draft is complete.
+    pub fn auto_pick(&mut self) -> Option<(PlayerId, CardDefId)> {
+        let player_id = self.picker()?.clone();
+        let card_id = self.open.first()?.clone();
+        self.pick(&player_id, &card_id).ok()?;
+        Some((player_id, card_id))
+    }
+
+    /// The draft as seen by a player.
+    ///
+    /// # Arguments
+    /// * `player_id` - The player the view is for.
+    /// * `pick_deadline` - When the running pick is made for its player.
+    pub fn view(&self, player_id: &PlayerId, pick_deadline: i64) -> DraftView {
+        DraftView {
+            round: self.round,
+            rounds: self.rounds,
+            pack: self.open.clone(),
+            picker: self.picker().cloned().unwrap_or_default(),
+            pick_deadline,
+            picks: self.picks.get(player_id).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// The deck of the cards a player drafted, one entry per card with its copies.
+    pub fn deck(&self, player_id: &PlayerId) -> Deck {
+        let mut cards: Vec<CardRef> = Vec::new();
+        for card_id in self.picks.get(player_id).into_iter().flatten() {
+            match cards.iter_mut().find(|card| &card.id == card_id) {
+                Some(card) => card.amount += 1,
+                None => cards.push(CardRef {
+                    id: card_id.clone(),
+                    amount: 1,
+                    owner_id: None,
+                    instances: Vec::new(),
+                }),
+            }
+        }
+
+        Deck {
+            id: format!("draft-{player_id}"),
+            player_id: player_id.clone(),
+            name: String::from("Draft"),
+            cards,
+            sideboard: Vec::new(),
+        }
+    }
+}
+
+fn default_packs() -> u32 {
+    3
+}
+
+fn default_pack_size() -> u32 {
+    10
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(cards: &[&str]) -> Vec<CardDefId> {
+        cards.iter().map(|card| CardDefId::from(*card)).collect()
+    }
+
+    #[test]
+    fn test_players_alternate_picks_and_openers() {
+        let (red, blue) = (PlayerId::from("red"), PlayerId::from("blue"));
+        let packs = vec![ids(&["wolf", "mage", "wolf"]), ids(&["fireball", "bear"])];
+        let mut draft = Draft::new(vec![red.clone(), blue.clone()], packs);
+
+        assert_eq!(Some(&red), draft.picker());
+        assert_eq!(
+            Err(DraftError::NotYourPick),
+            draft.pick(&blue, &"wolf".into())
+        );
+        assert_eq!(
+            Err(DraftError::CardNotInPack("bear".to_string())),
+            draft.pick(&red, &"bear".into())
+        );
+        draft.pick(&red, &"wolf".into()).unwrap();
+        draft.pick(&blue, &"mage".into()).unwrap();
+        assert_eq!(Some((red.clone(), "wolf".into())), draft.auto_pick());
+
+        // Blue opens the second pack.
+        let view = draft.view(&red, 0);
+        assert_eq!((2, 2), (view.round, view.rounds));
+        assert_eq!(blue, view.picker);
+        assert_eq!(ids(&["wolf", "wolf"]), view.picks);
+        draft.pick(&blue, &"bear".into()).unwrap();
+        draft.pick(&red, &"fireball".into()).unwrap();
+        assert!(draft.is_complete());
+        assert_eq!(None, draft.auto_pick());
+
+        let deck = draft.deck(&red);
+        let cards: Vec<_> = deck
+            .cards
+            .iter()
+            .map(|c| (c.id.to_string(), c.amount))
+            .collect();
+        assert_eq!(
+            vec![("wolf".to_string(), 2), ("fireball".to_string(), 1)],
+            cards
+        );
+    }
+
+    #[test]
+    fn test_packs_are_dealt_from_the_cards_given() {
+        let cards = ids(&["wolf", "mage"]);
+        let packs = Draft::deal(&cards, 4, 5, &MatchRng::new(7)).unwrap();
+        assert_eq!(4, packs.len());
+        assert!(packs.iter().all(|pack| pack.len() == 5));
+        assert!(packs.iter().flatten().all(|card| cards.contains(card)));
+        assert_eq!(packs, Draft::deal(&cards, 4, 5, &MatchRng::new(7)).unwrap());
+        assert_eq!(
+            Err(DraftError::NoCards),
+            Draft::deal(&[], 1, 5, &MatchRng::new(7))
+        );
+    }
+}
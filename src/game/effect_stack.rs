@@ -0,0 +1,147 @@
+use crate::game::entity::card::CardView;
+use crate::utils::errors::GameLogicError;
+use serde::Deserialize;
+use std::collections::VecDeque;
+
+/// Order in which queued effects resolve, see `EFFECT_RESOLUTION_ORDER`.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResolutionOrder {
+    /// The effects queued last resolve first, so an effect's own triggers resolve before the
+    /// effects queued alongside it.
+    #[default]
+    Lifo,
+    /// Effects resolve in the order they were queued.
+    Fifo,
+}
+
+/// A trigger of a card waiting on the effect stack.
+#[derive(Debug, Clone)]
+pub struct PendingEffect {
+    pub card: CardView,            // The card whose trigger runs.
+    pub event: String,             // The trigger, such as `on_play` or `on_death`.
+    pub function: String,          // The script function the trigger runs.
+    pub target_id: Option<String>, // The target chosen for the effect, if any.
+    pub depth: u32,                // Triggers that led to this one; 0 for the effects of a play.
+}
+
+impl PendingEffect {
+    pub fn new(card: &CardView, event: &str, function: &str, target_id: Option<String>) -> Self {
+        Self {
+            card: card.clone(),
+            event: event.to_string(),
+            function: function.to_string(),
+            target_id,
+            depth: 0,
+        }
+    }
+
+    /// Makes the effect a trigger of an effect at `depth`.
+    pub fn nested(mut self, depth: u32) -> Self {
+        self.depth = depth + 1;
+        self
+    }
+}
+
+/// Queues the triggered effects of a play so they resolve one at a time, in a set order.
+///
+/// Effects triggered while another resolves are queued rather than run straight away, so scripts
+/// never recurse into one another. A chain of triggers deeper than the maximum depth is taken for
+/// a loop between cards and stops the resolution.
+pub struct EffectStack {
+    order: ResolutionOrder,
+    max_depth: u32,
+    pending: VecDeque<PendingEffect>,
+}
+
+impl EffectStack {
+    pub fn new(order: ResolutionOrder, max_depth: u32) -> Self {
+        Self {
+            order,
+            max_depth,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Queues effects triggered together, such as the triggers of one card. They resolve in the
+    /// order given, whatever the resolution order.
+    ///
+    /// # Returns
+    /// * `Ok(())` - If every effect was queued.
+    /// * `Err(GameLogicError::EffectLoopDetected)` - If an effect is nested deeper than the maximum depth.
+    pub fn push_all(&mut self, effects: Vec<PendingEffect>) -> Result<(), GameLogicError> {
+        if let Some(effect) = effects.iter().find(|e| e.depth > self.max_depth) {
+            return Err(GameLogicError::EffectLoopDetected(
+                effect.card.id.to_string(),
+                self.max_depth,
+            ));
+        }
+
+        match self.order {
+            ResolutionOrder::Lifo => effects
+                .into_iter()
+                .rev()
+                .for_each(|effect| self.pending.push_back(effect)),
+            ResolutionOrder::Fifo => self.pending.extend(effects),
+        }
+
+        Ok(())
+    }
+
+    /// Takes the next effect to resolve.
+    pub fn pop(&mut self) -> Option<PendingEffect> {
+        match self.order {
+            ResolutionOrder::Lifo => self.pending.pop_back(),
+            ResolutionOrder::Fifo => self.pending.pop_front(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entity::card::Card;
+    use crate::models::ids::CardInstanceId;
+
+    fn effect(function: &str) -> PendingEffect {
+        let card: Card = serde_json::from_value(serde_json::json!({
+            "id": "wolf", "name": "Wolf", "description": "", "play_cost": 1, "attack": 1,
+            "health": 1, "rarity": 0, "on_play": [], "on_draw": [], "on_attack": [], "on_hit": [],
+            "on_turn_start": [], "on_turn_end": [], "on_death": [], "on_ally_death": [],
+            "on_enemy_death": []
+        }))
+        .unwrap();
+        let view = CardView::create_view(&card, "red".into(), CardInstanceId::nth(0));
+        PendingEffect::new(&view, "on_play", function, None)
+    }
+
+    fn drain(stack: &mut EffectStack) -> Vec<String> {
+        std::iter::from_fn(|| stack.pop().map(|e| e.function)).collect()
+    }
+
+    #[test]
+    fn test_nested_triggers_resolve_before_their_siblings() {
+        let mut stack = EffectStack::new(ResolutionOrder::Lifo, 2);
+        stack.push_all(vec![effect("a"), effect("b")]).unwrap();
+        let first = stack.pop().unwrap();
+        assert_eq!("a", first.function);
+        stack
+            .push_all(vec![effect("a1").nested(first.depth)])
+            .unwrap();
+        assert_eq!(vec!["a1", "b"], drain(&mut stack));
+
+        let mut stack = EffectStack::new(ResolutionOrder::Fifo, 2);
+        stack.push_all(vec![effect("a"), effect("b")]).unwrap();
+        let first = stack.pop().unwrap();
+        stack
+            .push_all(vec![effect("a1").nested(first.depth)])
+            .unwrap();
+        assert_eq!(vec!["b", "a1"], drain(&mut stack));
+
+        let deep = effect("loop").nested(0).nested(1).nested(2);
+        assert!(matches!(
+            stack.push_all(vec![deep]),
+            Err(GameLogicError::EffectLoopDetected(_, 2))
+        ));
+    }
+}
@@ -1,11 +1,18 @@
 use serde::{Deserialize, Serialize};
-use crate::game::entity::card::CardRef;
+use std::collections::BTreeMap;
+use crate::game::entity::card::{CardRef, CardView};
+use crate::models::ids::CardInstanceId;
 
 #[derive(Serialize, Clone, Deserialize, Debug)]
 pub struct BoardView {
     pub creatures: [Option<CardRef>; 6],
     pub artifacts: [Option<CardRef>; 3],
     pub enchantments: [Option<CardRef>; 3],
+    /// Every copy on the board as it is now: its current attack and health, status effects,
+    /// activations left and controller. Kept in step with the slots by `board::place` and
+    /// `board::remove`.
+    #[serde(default)]
+    pub cards: BTreeMap<CardInstanceId, CardView>,
 }
 
 impl Default for BoardView {
@@ -14,6 +21,7 @@ impl Default for BoardView {
             artifacts: [None, None, None],
             enchantments: [None, None, None],
             creatures: [None, None, None, None, None, None],
+            cards: BTreeMap::new(),
         }
     }
 }
@@ -23,4 +31,6 @@ pub struct GraveyardView {
     pub creatures: Vec<CardRef>,
     pub artifacts: Vec<CardRef>,
     pub enchantments: Vec<CardRef>,
+    #[serde(default)]
+    pub spells: Vec<CardRef>,
 }
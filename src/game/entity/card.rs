@@ -1,24 +1,70 @@
+use crate::game::cooldown::ActivationLimit;
+use crate::game::status_effect::StatusEffect;
+use crate::game::targeting::TargetRule;
 use crate::models::http_response::SelectedCardsResponse;
 use crate::utils::errors::CardRequestError;
-use crate::SETTINGS;
+use crate::utils::http_service::Upstream;
+use crate::{HTTP_SERVICE, SETTINGS};
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use crate::models::ids::{CardDefId, CardInstanceId, PlayerId};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct CardRef {
-    pub id: String,
+    pub id: CardDefId,
     pub amount: u32,
+    /// Set while the card sits on the board of a player who does not own it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner_id: Option<PlayerId>,
+    /// The copies in the stack, one per card; empty in decks, which only list definitions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub instances: Vec<CardInstanceId>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl CardRef {
+    /// The owner of the card, given the player whose board, deck or graveyard holds it.
+    pub fn owner_or<'a>(&'a self, holder_id: &'a PlayerId) -> &'a PlayerId {
+        self.owner_id.as_ref().unwrap_or(holder_id)
+    }
+}
+
+/// What a card is, which decides the board zone it is played into.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CardType {
+    #[default]
+    Creature,
+    Artifact,
+    Enchantment,
+    /// Resolves when played and never stays on the board.
+    Spell,
+}
+
+impl Display for CardType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CardType::Creature => write!(f, "creature"),
+            CardType::Artifact => write!(f, "artifact"),
+            CardType::Enchantment => write!(f, "enchantment"),
+            CardType::Spell => write!(f, "spell"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Card {
-    pub id: String,
+    pub id: CardDefId,
     pub name: String,
     pub description: String,
     pub play_cost: i32,
     pub attack: i32,
     pub health: i32,
     pub rarity: i16,
+    #[serde(default)]
+    pub targeting: TargetRule,
+    #[serde(default)]
+    pub card_type: CardType,
 
     // These will contain lua function names, I guess
     pub on_play: Vec<String>,
@@ -33,83 +79,126 @@ pub struct Card {
     pub on_death: Vec<String>,
     pub on_ally_death: Vec<String>,
     pub on_enemy_death: Vec<String>,
+
+    /// Scripts of the activated ability, run when the card is activated from the board.
+    #[serde(default)]
+    pub on_activate: Vec<String>,
+    /// How often the ability may be activated; enforced by the `CooldownTracker`.
+    #[serde(default)]
+    pub activation_limit: Option<ActivationLimit>,
+    /// Milliseconds of client-side animation played when the card resolves; the turn timer of the
+    /// player pauses for it, within the bounds of the rules of the match.
+    #[serde(default)]
+    pub cinematic_ms: u64,
 }
 
 impl Card {
+    /// The scripts a trigger of the card runs, such as `on_play`.
+    ///
+    /// # Returns
+    /// `None` if cards have no such trigger.
+    pub fn trigger(&self, event: &str) -> Option<&[String]> {
+        let scripts = match event {
+            "on_play" => &self.on_play,
+            "on_draw" => &self.on_draw,
+            "on_attack" => &self.on_attack,
+            "on_hit" => &self.on_hit,
+            "on_turn_start" => &self.on_turn_start,
+            "on_turn_end" => &self.on_turn_end,
+            "on_death" => &self.on_death,
+            "on_ally_death" => &self.on_ally_death,
+            "on_enemy_death" => &self.on_enemy_death,
+            "on_activate" => &self.on_activate,
+            _ => return None,
+        };
+        Some(scripts)
+    }
+
     /// Request the CARD_SERVER for one card by ID
     /// Should not require authentication, so the only response possible is errors or OKs and NOT FOUND
-    pub async fn request_card(card_id: &str) -> Result<Card, CardRequestError> {
+    pub async fn request_card(card_id: &CardDefId) -> Result<Card, CardRequestError> {
         let settings = SETTINGS.get().expect("Settings not initialized");
         let api_url = format!("{}/api/card/{}", settings.card_server, card_id);
-        match reqwest::get(api_url).await {
-            Err(error) => Err(CardRequestError::UnexpectedCardRequestError(
-                error.to_string(),
-            )),
-            Ok(response) => match response.status() {
-                StatusCode::NOT_FOUND => Err(CardRequestError::CardNotFound(card_id.to_string())),
-                StatusCode::OK => Ok(response.json::<Card>().await.map_err(|e| {
-                    return CardRequestError::UnexpectedCardRequestError(e.to_string());
-                })?),
-                _ => {
-                    let response_body = response.text().await.unwrap_or("NO MESSAGE".to_string());
-                    Err(CardRequestError::UnexpectedCardRequestError(response_body))
-                }
-            },
+        let response = HTTP_SERVICE
+            .send(Upstream::Card, |client| client.get(&api_url))
+            .await?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Err(CardRequestError::CardNotFound(card_id.to_string())),
+            StatusCode::OK => Ok(response.json::<Card>().await.map_err(|e| {
+                return CardRequestError::UnexpectedCardRequestError(e.to_string());
+            })?),
+            _ => {
+                let response_body = response.text().await.unwrap_or("NO MESSAGE".to_string());
+                Err(CardRequestError::UnexpectedCardRequestError(response_body))
+            }
         }
     }
 
     pub async fn request_cards(cards: &Vec<CardRef>) -> Result<Vec<Card>, CardRequestError> {
         let settings = SETTINGS.get().expect("Settings not initialized");
         let api_url = format!("{}/api/card/selected", settings.card_server);
-        let card_ids: Vec<&String> = cards.iter().map(|c| &c.id).collect();
-        let client = reqwest::Client::new();
+        let card_ids: Vec<&CardDefId> = cards.iter().map(|c| &c.id).collect();
         let body = serde_json::json!({"cardIds": card_ids});
+        let response = HTTP_SERVICE
+            .send(Upstream::Card, |client| client.post(&api_url).json(&body))
+            .await?;
 
-        match client.post(api_url).json(&body).send().await {
-            Err(e) => Err(CardRequestError::UnexpectedCardRequestError(e.to_string())),
-            Ok(response) => match response.status() {
-                StatusCode::OK => {
-                    let selected_cards =
-                        response
-                            .json::<SelectedCardsResponse>()
-                            .await
-                            .map_err(|_| {
-                                return CardRequestError::SelectedCardsParseError;
-                            })?;
-
-                    if selected_cards.cards_not_found.len() != 0
-                        || selected_cards.invalid_card_guid.len() != 0
-                    {
-                        let message = format!(
-                            "Not found: {}, Invalid cards: {}",
-                            selected_cards.cards_not_found.len(),
-                            selected_cards.invalid_card_guid.len()
-                        );
-                        return Err(CardRequestError::MissingCardData(message));
-                    }
-
-                    Ok(selected_cards.cards)
-                }
-                _ => {
-                    let response_body = response.text().await.unwrap_or("NO MESSAGE".to_string());
-                    Err(CardRequestError::UnexpectedCardRequestError(response_body))
+        match response.status() {
+            StatusCode::OK => {
+                let selected_cards =
+                    response
+                        .json::<SelectedCardsResponse>()
+                        .await
+                        .map_err(|_| {
+                            return CardRequestError::SelectedCardsParseError;
+                        })?;
+
+                if selected_cards.cards_not_found.len() != 0
+                    || selected_cards.invalid_card_guid.len() != 0
+                {
+                    let message = format!(
+                        "Not found: {}, Invalid cards: {}",
+                        selected_cards.cards_not_found.len(),
+                        selected_cards.invalid_card_guid.len()
+                    );
+                    return Err(CardRequestError::MissingCardData(message));
                 }
-            },
+
+                Ok(selected_cards.cards)
+            }
+            _ => {
+                let response_body = response.text().await.unwrap_or("NO MESSAGE".to_string());
+                Err(CardRequestError::UnexpectedCardRequestError(response_body))
+            }
         }
     }
 }
 
 #[derive(Serialize, Clone, Debug, Deserialize)]
 pub struct CardView {
-    pub id: String,
+    pub id: CardDefId,
+    /// The copy of the card this view is of, unique in the match.
+    #[serde(default)]
+    pub instance_id: CardInstanceId,
+    #[serde(default)]
+    pub card_type: CardType,
     pub name: String,
     pub attack: i32,
     pub health: i32,
     pub play_cost: i32,
     
-    pub owner_id: String,
+    pub owner_id: PlayerId,
+    /// The player controlling the card; differs from the owner while under a mind control effect.
+    pub controller_id: PlayerId,
     pub effects: Vec<String>,
+    /// Status effects currently on the card, see `status_effect`.
+    #[serde(default)]
+    pub status_effects: Vec<StatusEffect>,
     pub position: Option<String>,
+    /// Activations left for the ability of the card, `None` if it has no activated ability.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remaining_uses: Option<u32>,
     
     pub in_deck: bool,
     pub in_hand: bool,
@@ -119,13 +208,18 @@ pub struct CardView {
 }
 
 impl CardView {
-    pub fn create_view(card: &Card, owner_id: String) -> Self {
+    pub fn create_view(card: &Card, owner_id: PlayerId, instance_id: CardInstanceId) -> Self {
         CardView {
+            instance_id,
+            card_type: card.card_type,
             position: None,
+            controller_id: owner_id.clone(),
             owner_id: owner_id,
             is_exhausted: false,
             id: card.id.clone(),
             effects: Vec::new(),
+            status_effects: Vec::new(),
+            remaining_uses: card.activation_limit.map(|limit| limit.allowance()),
             name: card.name.clone(),
             attack: card.attack.clone(),
             health: card.health.clone(),
@@ -1,30 +1,50 @@
 use std::collections::HashMap;
 use crate::game::entity::card::{Card, CardRef, CardView};
 use serde::{Deserialize, Serialize};
+use crate::models::ids::{CardDefId, CardInstanceId, PlayerId};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Deck {
     pub id: String,
     #[serde(rename = "playerId")]
-    pub player_id: String,
+    pub player_id: PlayerId,
     pub name: String,
     pub cards: Vec<CardRef>,
+    /// The cards the player may swap into the deck between the games of a series.
+    #[serde(default)]
+    pub sideboard: Vec<CardRef>,
 }
 
 impl Deck {
-    pub fn create_view(&self, cards: &HashMap<String, Card>, owner_id: &str) -> DeckView {
-        let mut card_views: HashMap<String, CardView> = HashMap::new();
+    /// Instantiates the deck for a match: every copy of a card gets its own view and instance id.
+    ///
+    /// # Arguments
+    /// * `cards` - The definitions of the cards of the deck.
+    /// * `owner_id` - The player owning the deck.
+    /// * `next_instance` - The counter instance ids are taken from, shared by every deck of the
+    ///   match so ids stay unique.
+    pub fn create_view(
+        &self,
+        cards: &HashMap<CardDefId, Card>,
+        owner_id: &PlayerId,
+        next_instance: &mut u64,
+    ) -> DeckView {
+        let mut card_views: HashMap<CardInstanceId, CardView> = HashMap::new();
         for card in &self.cards {
             let full_card = cards.get(&card.id).unwrap();
-            let view = CardView::create_view(full_card, owner_id.to_string());
-            card_views.insert(card.id.to_string(), view);
+            for _ in 0..card.amount {
+                let instance_id = CardInstanceId::nth(*next_instance);
+                *next_instance += 1;
+                let view = CardView::create_view(full_card, owner_id.clone(), instance_id.clone());
+                card_views.insert(instance_id, view);
+            }
         }
         
         DeckView {
             card_views,
             id: self.id.clone(),
             name: self.name.to_string(),
-            player_id: self.player_id.to_string(),
+            player_id: self.player_id.clone(),
         }
     }
 }
@@ -32,7 +52,7 @@ impl Deck {
 #[derive(Debug, Deserialize, Serialize)]
 pub struct DeckView {
     pub id: String,
-    pub player_id: String,
+    pub player_id: PlayerId,
     pub name: String,
-    pub card_views: HashMap<String, CardView>,
+    pub card_views: HashMap<CardInstanceId, CardView>,
 }
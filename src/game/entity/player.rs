@@ -1,22 +1,28 @@
 use crate::game::entity::board::{BoardView, GraveyardView};
-use crate::game::entity::card::{CardRef, CardView};
+use crate::game::entity::card::CardView;
 use crate::game::entity::deck::{Deck, DeckView};
+use crate::game::status_effect::StatusEffect;
 use crate::models::client_requests::{ConnectionRequest, ReconnectionRequest};
 use crate::models::http_response::{AuthenticatedPlayer, PreloadedPlayer};
+use crate::tcp::header::HeaderType;
+use crate::tcp::payload;
+use crate::tcp::session::SessionTokens;
+use crate::utils::http_service::Upstream;
 use crate::{
     logger,
     models::http_response::PartialPlayerProfile,
     utils::{errors::PlayerConnectionError, logger::Logger},
-    SETTINGS,
+    HTTP_SERVICE, SETTINGS,
 };
 use reqwest::{header::AUTHORIZATION, StatusCode};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use crate::models::ids::{CardInstanceId, PlayerId};
 
 /// Represents a player in the game, including their profile, deck, and authentication details.
 pub struct Player {
-    pub id: String,
+    pub id: PlayerId,
     pub level: u32,
     pub username: String,
     pub current_deck: Deck,
@@ -44,88 +50,127 @@ impl Player {
     }
 
     pub async fn new_connection(payload: &[u8]) -> Result<AuthenticatedPlayer, PlayerConnectionError> {
-        match serde_cbor::from_slice::<ConnectionRequest>(payload) {
+        match payload::decode::<ConnectionRequest>(&HeaderType::Connect, payload) {
             Err(error) => Err(PlayerConnectionError::InvalidPlayerPayload(error.to_string())),
             Ok(request) => {
-                Ok(Player::verify_authentication(&request.auth_token).await?)
+                let mut player = Player::verify_authentication(&request.auth_token).await?;
+                player.session_token = request.auth_token;
+                Ok(player)
             }
         }
     }
     
+    /// Authenticates a client that is not joining as a player, such as a spectator, with the
+    /// auth server.
+    pub async fn authenticate(
+        auth_token: &str,
+    ) -> Result<AuthenticatedPlayer, PlayerConnectionError> {
+        if auth_token.is_empty() {
+            return Err(PlayerConnectionError::UnauthorizedPlayerError);
+        }
+        Player::verify_authentication(auth_token).await
+    }
+
     pub async fn preload_player_profile(
-        player_id: &str,
+        player_id: &PlayerId,
     ) -> Result<PreloadedPlayer, PlayerConnectionError> {
         let settings = SETTINGS.get().expect("Settings not initialized");
         let api_url = format!("{}/api/player/preload/{player_id}", settings.auth_server);
-        let reqwest_client = reqwest::Client::new();
+        let response = HTTP_SERVICE
+            .send(Upstream::Auth, |client| client.get(&api_url))
+            .await?;
 
-        match reqwest_client.get(api_url).send().await {
-            Ok(response) => Ok(response
-                .json::<PreloadedPlayer>()
-                .await
-                .map_err(|e| PlayerConnectionError::InvalidPlayerPayload(e.to_string()))?),
-            Err(error) => Err(PlayerConnectionError::UnexpectedDeckError(
-                error.to_string(),
-            ))?,
-        }
+        response
+            .json::<PreloadedPlayer>()
+            .await
+            .map_err(|e| PlayerConnectionError::InvalidPlayerPayload(e.to_string()))
     }
 
     pub async fn preload_player_deck(deck_id: &str) -> Result<Deck, PlayerConnectionError> {
         let settings = SETTINGS.get().expect("Settings not initialized");
         let api_url = format!("{}/api/deck/{}", settings.deck_server, deck_id);
-        let reqwest_client = reqwest::Client::new();
+        let response = HTTP_SERVICE
+            .send(Upstream::Deck, |client| client.get(&api_url))
+            .await?;
 
-        match reqwest_client.get(api_url).send().await {
-            Err(e) => Err(PlayerConnectionError::UnexpectedDeckError(e.to_string())),
-            Ok(response) => match response.status() {
-                StatusCode::UNAUTHORIZED => Err(PlayerConnectionError::UnauthorizedDeckError),
+        match response.status() {
+            StatusCode::UNAUTHORIZED => Err(PlayerConnectionError::UnauthorizedDeckError),
 
-                StatusCode::NOT_FOUND => Err(PlayerConnectionError::DeckNotFound),
+            StatusCode::NOT_FOUND => Err(PlayerConnectionError::DeckNotFound),
 
-                StatusCode::OK => {
-                    let deck = response
-                        .json::<Deck>()
-                        .await
-                        .map_err(|_| PlayerConnectionError::InvalidDeckFormat)?;
+            StatusCode::OK => {
+                let deck = response
+                    .json::<Deck>()
+                    .await
+                    .map_err(|_| PlayerConnectionError::InvalidDeckFormat)?;
 
-                    Ok(deck)
-                }
+                Ok(deck)
+            }
 
-                _ => {
-                    let error_msg = response.text().await.unwrap_or("NO MESSAGE".to_string());
-                    Err(PlayerConnectionError::UnexpectedDeckError(error_msg))
-                }
-            },
+            _ => {
+                let error_msg = response.text().await.unwrap_or("NO MESSAGE".to_string());
+                Err(PlayerConnectionError::UnexpectedDeckError(error_msg))
+            }
         }
     }
 
-    /// Handles player reconnection by verifying the authentication token and matching the player ID.
+    /// Handles player reconnection by verifying the session token issued by this server, or the
+    /// authentication token, and matching the player ID.
+    ///
+    /// A valid session token is checked locally; the auth server is only contacted when the
+    /// session token is missing, expired or unknown.
     ///
     /// # Arguments
     /// * `payload` - A byte slice containing the serialized reconnection request.
+    /// * `sessions` - The session tokens issued by this server.
     ///
     /// # Returns
     /// * `Ok(AuthenticatedPlayer)` - The authenticated player instance.
     /// * `Err(PlayerConnectionError)` - An error if the payload is invalid or authentication fails.
     pub async fn reconnection(
         payload: &[u8],
+        sessions: &SessionTokens,
     ) -> Result<AuthenticatedPlayer, PlayerConnectionError> {
-        match serde_cbor::from_slice::<ReconnectionRequest>(payload) {
+        match payload::decode::<ReconnectionRequest>(&HeaderType::Reconnect, payload) {
             Err(error) => Err(PlayerConnectionError::InvalidPlayerPayload(
                 error.to_string(),
             )),
             Ok(request) => {
-                let player_profile = Player::verify_authentication(&request.auth_token).await?;
+                if let Some(session_token) = request.session_token {
+                    match sessions.validate(&request.player_id, &session_token) {
+                        Ok(username) => {
+                            return Ok(AuthenticatedPlayer {
+                                player_id: request.player_id,
+                                username,
+                                is_banned: false,
+                                session_token,
+                            })
+                        }
+                        Err(refusal) => logger!(
+                            DEBUG,
+                            "[PLAYER] Session token of `{}` refused ({refusal:?})",
+                            &request.player_id
+                        ),
+                    }
+                }
+                if request.auth_token.is_empty() {
+                    return Err(PlayerConnectionError::UnauthorizedPlayerError);
+                }
+
+                let mut player_profile = Player::verify_authentication(&request.auth_token).await?;
                 if player_profile.player_id != request.player_id {
                     return Err(PlayerConnectionError::PlayerDiscrepancy);
                 }
 
+                player_profile.session_token = request.auth_token;
                 Ok(player_profile)
             }
         }
     }
 
     /// Verifies the player's authentication token by contacting the authentication server.
+    /// With `MOCK_AUTH` set, the server is not contacted and `mock:<player id>` tokens are
+    /// accepted instead, so load tests can connect as any seated player.
     ///
     /// # Arguments
     /// * `token` - The authentication token to verify.
@@ -137,41 +182,47 @@ impl Player {
         token: &str,
     ) -> Result<AuthenticatedPlayer, PlayerConnectionError> {
         let settings = SETTINGS.get().expect("Settings not initialized");
+        if settings.mock_auth {
+            return match token.strip_prefix("mock:") {
+                Some(player_id) if !player_id.is_empty() => Ok(AuthenticatedPlayer {
+                    player_id: PlayerId::from(player_id),
+                    username: player_id.to_string(),
+                    is_banned: false,
+                    session_token: String::new(),
+                }),
+                _ => Err(PlayerConnectionError::UnauthorizedPlayerError),
+            };
+        }
+
         let api_url = format!("{}/api/auth/verify", settings.auth_server);
-        let reqwest_client = reqwest::Client::new();
+        let response = HTTP_SERVICE
+            .send(Upstream::Auth, |client| {
+                client
+                    .get(&api_url)
+                    .header(AUTHORIZATION, format!("Bearer {}", token))
+            })
+            .await?;
 
-        match reqwest_client
-            .get(api_url)
-            .header(AUTHORIZATION, format!("Bearer {}", token))
-            .send()
-            .await
-        {
-            Err(error) => Err(PlayerConnectionError::UnexpectedPlayerError(
-                error.to_string(),
-            )),
-            Ok(response) => match response.status() {
-                StatusCode::OK => {
-                    let result = response.json::<AuthenticatedPlayer>().await.map_err(|e| {
-                        logger!(ERROR, "{}", e.to_string());
-                        PlayerConnectionError::InvalidResponseBody(
-                            "AuthenticatedPlayer".to_string(),
-                        )
-                    })?;
-
-                    if result.is_banned == true {
-                        return Err(PlayerConnectionError::BannedPlayer(
-                            result.username.to_string(),
-                        ));
-                    }
+        match response.status() {
+            StatusCode::OK => {
+                let result = response.json::<AuthenticatedPlayer>().await.map_err(|e| {
+                    logger!(ERROR, "{}", e.to_string());
+                    PlayerConnectionError::InvalidResponseBody("AuthenticatedPlayer".to_string())
+                })?;
 
-                    Ok(result)
+                if result.is_banned == true {
+                    return Err(PlayerConnectionError::BannedPlayer(
+                        result.username.to_string(),
+                    ));
                 }
-                StatusCode::UNAUTHORIZED => Err(PlayerConnectionError::UnauthorizedPlayerError),
-                _ => Err(PlayerConnectionError::UnexpectedPlayerError(format!(
-                    "Unexpected authentication response status: {}",
-                    &response.status()
-                ))),
-            },
+
+                Ok(result)
+            }
+            StatusCode::UNAUTHORIZED => Err(PlayerConnectionError::UnauthorizedPlayerError),
+            _ => Err(PlayerConnectionError::UnexpectedPlayerError(format!(
+                "Unexpected authentication response status: {}",
+                &response.status()
+            ))),
         }
     }
 
@@ -188,32 +239,31 @@ impl Player {
     ) -> Result<PartialPlayerProfile, PlayerConnectionError> {
         let settings = SETTINGS.get().expect("Settings not initialized");
         let api_url = format!("{}/api/player/account", settings.auth_server);
-        let reqwest_client = reqwest::Client::new();
-        match reqwest_client
-            .get(api_url)
-            .header(AUTHORIZATION, format!("Bearer {}", token))
-            .send()
-            .await
-        {
-            Err(e) => Err(PlayerConnectionError::UnexpectedDeckError(e.to_string())),
-            Ok(response) => match response.status() {
-                StatusCode::UNAUTHORIZED => Err(PlayerConnectionError::UnauthorizedPlayerError),
-                StatusCode::OK => response
-                    .json::<PartialPlayerProfile>()
-                    .await
-                    .map_err(|e| PlayerConnectionError::InvalidPlayerPayload(e.to_string())),
-                _ => {
-                    let error_msg = response.text().await.unwrap_or("NO MESSAGE".to_string());
-                    Err(PlayerConnectionError::UnexpectedDeckError(error_msg))
-                }
-            },
+        let response = HTTP_SERVICE
+            .send(Upstream::Auth, |client| {
+                client
+                    .get(&api_url)
+                    .header(AUTHORIZATION, format!("Bearer {}", token))
+            })
+            .await?;
+
+        match response.status() {
+            StatusCode::UNAUTHORIZED => Err(PlayerConnectionError::UnauthorizedPlayerError),
+            StatusCode::OK => response
+                .json::<PartialPlayerProfile>()
+                .await
+                .map_err(|e| PlayerConnectionError::InvalidPlayerPayload(e.to_string())),
+            _ => {
+                let error_msg = response.text().await.unwrap_or("NO MESSAGE".to_string());
+                Err(PlayerConnectionError::UnexpectedDeckError(error_msg))
+            }
         }
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PlayerView {
-    pub id: String,
+    pub id: PlayerId,
     pub mana: i32,
     pub health: i32,
 
@@ -224,32 +274,38 @@ pub struct PlayerView {
     pub board: BoardView,
     pub graveyard_size: usize,
     pub graveyard: GraveyardView,
+    /// Status effects currently on the player, see `status_effect`.
+    #[serde(default)]
+    pub status_effects: Vec<StatusEffect>,
 }
 
 impl PlayerView {
-    pub fn from_player(player_id: &str, deck_size: usize) -> Self {
+    pub fn from_player(player_id: &PlayerId, deck_size: usize) -> Self {
         PlayerView {
             mana: 1,
             health: 30,
-            id: player_id.to_string(),
+            id: player_id.clone(),
 
             deck_size,
             hand_size: 0,
             graveyard_size: 0,
             board: BoardView::default(),
             graveyard: GraveyardView::default(),
+            status_effects: Vec::new(),
             current_hand: [None, None, None, None, None, None, None, None, None, None],
         }
     }
-}
 
-#[derive(Serialize, Clone)]
-pub struct PublicPlayerView {
-    pub id: String,
-    pub health: i32,
-    pub mana: i32,
-    pub hand_size: usize,
-    pub deck_size: usize,
-    pub graveyard_size: usize,
-    pub board: BoardView,
+    /// Takes a card out of the hand.
+    ///
+    /// # Returns
+    /// The card taken, or `None` if it is not in the hand.
+    pub fn take_from_hand(&mut self, instance_id: &CardInstanceId) -> Option<CardView> {
+        let slot = self.current_hand.iter_mut().find(|slot| {
+            slot.as_ref()
+                .is_some_and(|card| &card.instance_id == instance_id)
+        })?;
+        self.hand_size -= 1;
+        slot.take()
+    }
 }
@@ -0,0 +1,176 @@
+use crate::game::script_blocklist::SkippedScript;
+use crate::game::start_barrier::MatchCountdown;
+use crate::models::client_requests::BoardPing;
+use crate::models::ids::PlayerId;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Events kept for subscribers lagging behind; a lagging subscriber only misses the oldest ones.
+const BUS_CAPACITY: usize = 64;
+
+/// Something the connections of the match should hear about, published by the game logic.
+///
+/// Events carry no packet and no view of the game state: every subscriber filters them by what
+/// its audience may see, then builds and serializes its own packets, so the game logic never
+/// deals with the wire format and no client is sent information hidden from it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchEvent {
+    /// The game state changed; subscribers build the view their audience may see.
+    StateChanged,
+    /// A trigger did not run because operators blocked its script.
+    ScriptSkipped(SkippedScript),
+    /// Time left before a scheduled match starts.
+    Countdown(MatchCountdown),
+    /// A player's turn started.
+    TurnStarted(TurnStarted),
+    /// The match is over.
+    MatchEnded(MatchEnded),
+    /// A player dropped during the match.
+    PlayerDisconnected(PlayerDisconnected),
+    /// A player who dropped during the match is back.
+    PlayerReconnected(PlayerReconnected),
+    /// A player sent an emote.
+    Emote(EmoteSent),
+    /// A player sent a chat message.
+    Chat(ChatSent),
+}
+
+/// Sent to every player and spectator in a `TurnStarted` packet.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TurnStarted {
+    pub turn: u32,           // The number of the turn.
+    pub player_id: PlayerId, // The player whose turn it is.
+}
+
+/// Sent to every player and spectator in a `MatchEnded` packet.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MatchEnded {
+    pub winner_id: Option<PlayerId>, // The winner, `None` on a draw or an aborted match.
+    pub reason: String,              // Why the match ended.
+}
+
+/// Sent to the opponent in an `OpponentDisconnected` packet when a player drops mid-match.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PlayerDisconnected {
+    pub player_id: PlayerId, // The player who dropped.
+    pub forfeit_at: i64,     // Unix timestamp (milliseconds) the player forfeits at unless back.
+    pub clock_paused: bool,  // Whether the turn clock of the player stopped meanwhile.
+}
+
+/// Sent to the opponent in an `OpponentReconnected` packet.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PlayerReconnected {
+    pub player_id: PlayerId, // The player who is back.
+}
+
+/// Relayed to the opponent and the spectators in an `Emote` packet.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct EmoteSent {
+    pub player_id: PlayerId, // The player who sent the emote.
+    pub emote_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ping: Option<BoardPing>, // The spot of the board the emote points at.
+}
+
+/// Relayed to the opponent, unless they muted the sender, in a `Chat` packet.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChatSent {
+    pub player_id: PlayerId, // The player who sent the message.
+    pub message: String,     // The message, as cleaned up by the profanity filter.
+    #[serde(skip)]
+    pub public: bool, // Whether the spectators are relayed the message too.
+}
+
+/// Who a subscriber of the bus serves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Audience {
+    Player,
+    Spectator,
+}
+
+impl MatchEvent {
+    /// Whether the event concerns the given audience.
+    pub fn visible_to(&self, audience: Audience) -> bool {
+        match self {
+            MatchEvent::StateChanged
+            | MatchEvent::Countdown(_)
+            | MatchEvent::TurnStarted(_)
+            | MatchEvent::MatchEnded(_)
+            | MatchEvent::Emote(_) => true,
+            MatchEvent::ScriptSkipped(_)
+            | MatchEvent::PlayerDisconnected(_)
+            | MatchEvent::PlayerReconnected(_) => audience == Audience::Player,
+            MatchEvent::Chat(chat) => chat.public || audience == Audience::Player,
+        }
+    }
+
+    /// Whether the event comes from or tells about the given player, who is not sent it.
+    pub fn is_about(&self, player_id: &PlayerId) -> bool {
+        match self {
+            MatchEvent::PlayerDisconnected(notice) => &notice.player_id == player_id,
+            MatchEvent::PlayerReconnected(notice) => &notice.player_id == player_id,
+            MatchEvent::Emote(emote) => &emote.player_id == player_id,
+            MatchEvent::Chat(chat) => &chat.player_id == player_id,
+            _ => false,
+        }
+    }
+}
+
+/// Broadcasts the events of the match to the tasks serving players and spectators.
+pub struct GameEventBus {
+    sender: broadcast::Sender<MatchEvent>,
+}
+
+impl Default for GameEventBus {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::Sender::new(BUS_CAPACITY),
+        }
+    }
+}
+
+impl GameEventBus {
+    /// Publishes an event to every current subscriber.
+    pub fn publish(&self, event: MatchEvent) {
+        // Sending only fails when nobody is subscribed, which is not an error.
+        let _ = self.sender.send(event);
+    }
+
+    /// Receives the events published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<MatchEvent> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ids::CardDefId;
+
+    #[tokio::test]
+    async fn test_subscribers_filter_events_by_audience() {
+        let bus = GameEventBus::default();
+        let mut player = bus.subscribe();
+        let mut spectator = bus.subscribe();
+
+        let skipped = MatchEvent::ScriptSkipped(SkippedScript {
+            card_id: CardDefId::from("fireball"),
+            event: String::from("on_play"),
+            function: String::from("fireball_on_play"),
+        });
+        bus.publish(skipped.clone());
+        bus.publish(MatchEvent::StateChanged);
+
+        let mut seen = Vec::new();
+        while let Ok(event) = player.try_recv() {
+            seen.extend(event.visible_to(Audience::Player).then_some(event));
+        }
+        assert_eq!(vec![skipped, MatchEvent::StateChanged], seen);
+
+        let mut seen = Vec::new();
+        while let Ok(event) = spectator.try_recv() {
+            seen.extend(event.visible_to(Audience::Spectator).then_some(event));
+        }
+        assert_eq!(vec![MatchEvent::StateChanged], seen);
+    }
+}
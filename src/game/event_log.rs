@@ -0,0 +1,376 @@
+use crate::game::combat::Block;
+use crate::game::status_effect::StatusKind;
+use crate::models::ids::{CardDefId, CardInstanceId, PlayerId};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Number of events kept in memory; older ones are dropped first.
+pub const MAX_EVENTS: usize = 1024;
+
+/// Something that happened in the match.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum GameEventKind {
+    /// A new turn started.
+    TurnChanged {
+        turn: u32,
+    },
+    /// A player played a card from their hand.
+    CardPlayed {
+        player_id: PlayerId,
+        card_id: CardDefId,
+        target_id: Option<String>,
+    },
+    /// A player activated the ability of a card on their board.
+    AbilityActivated {
+        player_id: PlayerId,
+        card_id: CardDefId,
+        target_id: Option<String>,
+    },
+    DamageDealt {
+        target: String,
+        amount: u32,
+    },
+    Healed {
+        target: String,
+        amount: u32,
+    },
+    Summoned {
+        card_id: CardDefId,
+        position: String,
+    },
+    CardsDrawn {
+        player_id: PlayerId,
+        count: u32,
+    },
+    ControlChanged {
+        card_id: CardDefId,
+        controller_id: PlayerId,
+    },
+    /// A creature left the board for its owner's graveyard.
+    CreatureDied {
+        card_id: CardDefId,
+        instance_id: CardInstanceId, // The copy of the card that died.
+        owner_id: PlayerId,
+    },
+    /// An artifact or an enchantment left the board for its owner's graveyard.
+    CardDestroyed {
+        card_id: CardDefId,
+        owner_id: PlayerId,
+    },
+    /// A card went from a hand to its owner's graveyard.
+    CardDiscarded {
+        card_id: CardDefId,
+        owner_id: PlayerId,
+    },
+    /// A card came back from a graveyard onto its owner's board.
+    CardResurrected {
+        card_id: CardDefId,
+        owner_id: PlayerId,
+    },
+    /// A card came back from a graveyard to its owner's hand.
+    CardReturnedToHand {
+        card_id: CardDefId,
+        owner_id: PlayerId,
+    },
+    /// A trigger of a card did not run because operators blocked its script.
+    ScriptSkipped {
+        card_id: CardDefId,
+        function: String,
+    },
+    StatusEffectApplied {
+        target: String,
+        kind: StatusKind,
+    },
+    StatusEffectExpired {
+        target: String,
+        kind: StatusKind,
+    },
+    /// The last play of a player was taken back with the consent of their opponent.
+    PlayUndone {
+        player_id: PlayerId,
+    },
+    /// The opponent of a player refused to let them take back their last play.
+    UndoDeclined {
+        player_id: PlayerId,
+    },
+    /// A player attacked with creatures of their board.
+    AttackersDeclared {
+        player_id: PlayerId,
+        attackers: Vec<CardInstanceId>,
+    },
+    /// The defending player blocked attackers with their creatures.
+    BlockersDeclared {
+        player_id: PlayerId,
+        blocks: Vec<Block>,
+    },
+    /// A card script asked the clients to play a long animation.
+    CinematicPlayed {
+        card_id: CardDefId,
+        name: String,
+        duration_ms: u64,
+    },
+    /// The turn timer of a player paused while a long resolution played on the clients.
+    TurnTimerPaused {
+        player_id: PlayerId,
+        millis: u64,
+    },
+}
+
+impl GameEventKind {
+    /// The type of the event, as it is tagged when serialized.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::TurnChanged { .. } => "TurnChanged",
+            Self::CardPlayed { .. } => "CardPlayed",
+            Self::AbilityActivated { .. } => "AbilityActivated",
+            Self::DamageDealt { .. } => "DamageDealt",
+            Self::Healed { .. } => "Healed",
+            Self::Summoned { .. } => "Summoned",
+            Self::CardsDrawn { .. } => "CardsDrawn",
+            Self::ControlChanged { .. } => "ControlChanged",
+            Self::CreatureDied { .. } => "CreatureDied",
+            Self::CardDestroyed { .. } => "CardDestroyed",
+            Self::CardDiscarded { .. } => "CardDiscarded",
+            Self::CardResurrected { .. } => "CardResurrected",
+            Self::CardReturnedToHand { .. } => "CardReturnedToHand",
+            Self::ScriptSkipped { .. } => "ScriptSkipped",
+            Self::StatusEffectApplied { .. } => "StatusEffectApplied",
+            Self::StatusEffectExpired { .. } => "StatusEffectExpired",
+            Self::PlayUndone { .. } => "PlayUndone",
+            Self::UndoDeclined { .. } => "UndoDeclined",
+            Self::AttackersDeclared { .. } => "AttackersDeclared",
+            Self::BlockersDeclared { .. } => "BlockersDeclared",
+            Self::CinematicPlayed { .. } => "CinematicPlayed",
+            Self::TurnTimerPaused { .. } => "TurnTimerPaused",
+        }
+    }
+
+    /// The player who acted, or who owns the card the event is about.
+    pub fn player_id(&self) -> Option<&PlayerId> {
+        match self {
+            Self::CardPlayed { player_id, .. }
+            | Self::AbilityActivated { player_id, .. }
+            | Self::CardsDrawn { player_id, .. }
+            | Self::PlayUndone { player_id }
+            | Self::UndoDeclined { player_id }
+            | Self::AttackersDeclared { player_id, .. }
+            | Self::BlockersDeclared { player_id, .. }
+            | Self::TurnTimerPaused { player_id, .. } => Some(player_id),
+            Self::CreatureDied { owner_id, .. }
+            | Self::CardDestroyed { owner_id, .. }
+            | Self::CardDiscarded { owner_id, .. }
+            | Self::CardResurrected { owner_id, .. }
+            | Self::CardReturnedToHand { owner_id, .. } => Some(owner_id),
+            Self::ControlChanged { controller_id, .. } => Some(controller_id),
+            _ => None,
+        }
+    }
+}
+
+/// Number of events of each type recorded over the whole match, unlike the log itself which only
+/// keeps the latest `MAX_EVENTS`.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct EventCounts {
+    pub total: BTreeMap<&'static str, u64>, // Events of each type.
+    pub players: BTreeMap<PlayerId, BTreeMap<&'static str, u64>>, // The same, per player.
+}
+
+impl EventCounts {
+    fn add(&mut self, kind: &GameEventKind) {
+        *self.total.entry(kind.name()).or_default() += 1;
+        if let Some(player_id) = kind.player_id() {
+            let counts = self.players.entry(player_id.clone()).or_default();
+            *counts.entry(kind.name()).or_default() += 1;
+        }
+    }
+
+    fn remove(&mut self, kind: &GameEventKind) {
+        if let Some(count) = self.total.get_mut(kind.name()) {
+            *count = count.saturating_sub(1);
+        }
+        if let Some(count) = kind
+            .player_id()
+            .and_then(|player_id| self.players.get_mut(player_id))
+            .and_then(|counts| counts.get_mut(kind.name()))
+        {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+/// An entry of the event log.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GameEvent {
+    pub sequence: u64,  // Position of the event in the match, starting at 1.
+    pub timestamp: i64, // Unix timestamp (milliseconds) of when the event was recorded.
+    pub turn: u32,
+    #[serde(flatten)]
+    pub kind: GameEventKind,
+}
+
+/// Ordered history of everything that was resolved in the match.
+///
+/// Events are numbered in the order they are recorded, so clients can ask for everything after
+/// the last sequence they have seen. Recording an event on a turn the log has not seen yet first
+/// records a `TurnChanged` event.
+#[derive(Default)]
+pub struct GameEventLog {
+    events: VecDeque<GameEvent>,
+    last_sequence: u64,
+    last_turn: Option<u32>,
+    counts: EventCounts, // Every event recorded, including the ones no longer kept.
+}
+
+impl GameEventLog {
+    /// Appends an event to the log.
+    ///
+    /// # Returns
+    /// The sequence number given to the event.
+    pub fn record(&mut self, turn: u32, kind: GameEventKind) -> u64 {
+        self.start_turn(turn);
+        self.push(turn, kind)
+    }
+
+    /// Records the `TurnChanged` event of a turn, unless the log already has it, so turns in
+    /// which nothing happens are logged too.
+    pub fn start_turn(&mut self, turn: u32) {
+        if self.last_turn != Some(turn) {
+            self.last_turn = Some(turn);
+            self.push(turn, GameEventKind::TurnChanged { turn });
+        }
+    }
+
+    fn push(&mut self, turn: u32, kind: GameEventKind) -> u64 {
+        self.last_sequence += 1;
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.counts.add(&kind);
+
+        self.events.push_back(GameEvent {
+            sequence: self.last_sequence,
+            timestamp: Utc::now().timestamp_millis(),
+            turn,
+            kind,
+        });
+        self.last_sequence
+    }
+
+    /// How many events of each type were recorded over the whole match.
+    pub fn counts(&self) -> &EventCounts {
+        &self.counts
+    }
+
+    /// Sequence number of the latest event, `0` if nothing was recorded yet.
+    pub fn last_sequence(&self) -> u64 {
+        self.last_sequence
+    }
+
+    /// Returns the events recorded after `sequence`, at most the `limit` latest of them.
+    pub fn since(&self, sequence: u64, limit: usize) -> Vec<GameEvent> {
+        let events: Vec<_> = self
+            .events
+            .iter()
+            .filter(|e| e.sequence > sequence)
+            .collect();
+        let skip = events.len().saturating_sub(limit);
+        events.into_iter().skip(skip).cloned().collect()
+    }
+
+    /// Returns the events of the given turn, oldest first.
+    pub fn turn(&self, turn: u32) -> Vec<GameEvent> {
+        self.events
+            .iter()
+            .filter(|e| e.turn == turn)
+            .cloned()
+            .collect()
+    }
+
+    /// Forgets every event recorded after `sequence`, used when a batch of actions is rolled back.
+    pub fn truncate(&mut self, sequence: u64) {
+        while let Some(event) = self.events.pop_back() {
+            if event.sequence <= sequence {
+                self.events.push_back(event);
+                break;
+            }
+            self.counts.remove(&event.kind);
+        }
+
+        self.last_sequence = sequence;
+        self.last_turn = self.events.back().map(|e| e.turn);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn played(card: &str) -> GameEventKind {
+        GameEventKind::CardPlayed {
+            player_id: "red".into(),
+            card_id: card.into(),
+            target_id: None,
+        }
+    }
+
+    #[test]
+    fn test_turn_changes_are_recorded() {
+        let mut log = GameEventLog::default();
+        log.record(1, played("wolf"));
+        log.record(1, played("bear"));
+        log.record(2, played("owl"));
+
+        let turn_two = log.turn(2);
+        assert_eq!(GameEventKind::TurnChanged { turn: 2 }, turn_two[0].kind);
+        assert_eq!(played("owl"), turn_two[1].kind);
+        assert_eq!(5, log.last_sequence());
+    }
+
+    #[test]
+    fn test_since_and_limit() {
+        let mut log = GameEventLog::default();
+        for card in ["wolf", "bear", "owl"] {
+            log.record(0, played(card));
+        }
+
+        let sequences: Vec<_> = log.since(1, 10).iter().map(|e| e.sequence).collect();
+        assert_eq!(vec![2, 3, 4], sequences);
+        assert_eq!(played("owl"), log.since(0, 1)[0].kind);
+    }
+
+    #[test]
+    fn test_truncate_forgets_rolled_back_events() {
+        let mut log = GameEventLog::default();
+        let kept = log.record(0, played("wolf"));
+        log.record(1, played("bear"));
+
+        log.truncate(kept);
+        assert_eq!(kept, log.last_sequence());
+        assert_eq!(4, log.record(1, played("owl")));
+        assert_eq!(
+            GameEventKind::TurnChanged { turn: 1 },
+            log.since(kept, 10)[0].kind
+        );
+    }
+
+    #[test]
+    fn test_counts_cover_the_whole_match() {
+        let mut log = GameEventLog::default();
+        for _ in 0..MAX_EVENTS {
+            log.record(0, played("wolf"));
+        }
+        let kept = log.last_sequence();
+        log.record(0, played("bear"));
+        log.truncate(kept);
+
+        let counts = log.counts();
+        assert_eq!(Some(&1), counts.total.get("TurnChanged"));
+        assert_eq!(Some(&(MAX_EVENTS as u64)), counts.total.get("CardPlayed"));
+        assert_eq!(
+            Some(&(MAX_EVENTS as u64)),
+            counts.players[&"red".into()].get("CardPlayed")
+        );
+    }
+}
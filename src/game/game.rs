@@ -1,61 +1,163 @@
-use crate::game::entity::card::Card;
+use crate::game::board;
+use crate::game::card_catalog::CardCatalog;
+use crate::game::checkpoint::Checkpoint;
+use crate::game::combat::{self, Block, CombatMode, CombatWindow};
+use crate::game::graveyard;
+#[cfg(debug_assertions)]
+use crate::game::invariants;
+use crate::game::entity::card::{Card, CardRef, CardView};
+use crate::game::entity::deck::Deck;
 use crate::game::entity::player::{Player, PlayerView};
+use crate::game::effect_stack::{EffectStack, PendingEffect, ResolutionOrder};
+use crate::game::event_log::GameEventKind;
 use crate::game::game_state::GameState;
 use crate::game::lua_context::LuaContext;
+use crate::game::match_format::{FirstPlayer, MatchFormat};
+use crate::game::prompt::{
+    PendingPrompt, Prompt, PromptKind, PromptOrigin, UNDO_ACCEPT, UNDO_DECLINE,
+};
+use crate::game::rng::MatchRng;
+use crate::game::rules::RulesProfile;
+use crate::game::seating::{Seating, TEAMS};
+use crate::game::event_bus::{GameEventBus, MatchEvent, TurnStarted};
+use crate::game::script_blocklist::SkippedScript;
 use crate::game::script_manager::ScriptManager;
+use crate::game::script_worker::ScriptWorker;
+use crate::game::status_effect;
+use crate::game::targeting::{self, TargetResolution};
 use crate::logger;
-use crate::models::client_requests::PlayCardRequest;
+use crate::game::batch::AtomicBatch;
+use crate::models::client_requests::{
+    ActionBatchRequest, ActivateAbilityRequest, BatchedAction, DeclareAttackersRequest,
+    DeclareBlockersRequest, PlayCardRequest, PromptResponse,
+};
+use crate::models::http_response::PreloadedPlayer;
 use crate::models::init_server::PreloadPlayer;
 use crate::tcp::client::Client;
-use crate::utils::errors::{GameInstanceError, GameLogicError};
+use crate::utils::errors::{DeckConstraintError, DeckIllegal, GameInstanceError, GameLogicError};
 use crate::utils::logger::Logger;
-use std::collections::HashMap;
+use crate::utils::profiler::MatchProfiler;
+use crate::utils::replay::{ReplayRecord, ReplayWriter};
+use crate::utils::runtime_config::SharedConfig;
+use crate::{METRICS, RUNTIME_CONFIG, SETTINGS};
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
+use tokio::task::JoinSet;
+use crate::models::ids::{CardDefId, CardInstanceId, PlayerId};
+#[cfg(feature = "dev-repl")]
+use crate::models::game_action::GameAction;
+
+/// The result of a player action that passed validation.
+pub enum PlayOutcome {
+    /// The action was fully resolved.
+    Resolved,
+    /// The action is on hold until the player answers the prompt.
+    Prompted(Prompt),
+}
 
 pub struct GameInstance {
     pub game_state: Arc<RwLock<GameState>>, // The current game state, shared across tasks.
-    pub script_manager: Arc<RwLock<ScriptManager>>, // The Lua script manager for handling game logic scripts.
-    pub full_cards: Arc<RwLock<HashMap<String, Card>>>,
-    pub connected_players: Arc<RwLock<HashMap<String, Arc<RwLock<Player>>>>>,
+    pub scripts: ScriptWorker, // Runs the Lua scripts of the match on a worker of its own.
+    pub full_cards: Arc<RwLock<HashMap<CardDefId, Card>>>,
+    pub catalog: Arc<CardCatalog>, // Card definitions fetched from the card server, kept across matches.
+    pub connected_players: Arc<RwLock<HashMap<PlayerId, Arc<RwLock<Player>>>>>,
+    pub profiler: Arc<MatchProfiler>, // Timings summarized into a performance report at match end.
+    pub rng: Arc<MatchRng>,           // Source of every random value drawn by card scripts.
+    pub bus: Arc<GameEventBus>, // Tells the connections what happened, for them to build their packets.
+    pub format: MatchFormat,    // Starting health, deck rules and turn structure of the match.
+    pub config: SharedConfig,   // Prompt and blockers timeouts, tunable while the match runs.
 }
 
 impl GameInstance {
-    pub async fn create_instance(players: Vec<PreloadPlayer>) -> Result<Self, GameInstanceError> {
-        let mut lua_vm = ScriptManager::new_vm();
-        lua_vm
-            .load_scripts()
-            .map_err(|e| GameInstanceError::PlaceHolderError)?;
-        lua_vm.set_globals().await;
-        let scripts = Arc::new(RwLock::new(lua_vm));
-        //
-
-        let mut full_cards_map: HashMap<String, Card> = HashMap::new();
-        let mut connected_players: HashMap<String, Arc<RwLock<Player>>> = HashMap::new();
-        let mut connect_players_views: HashMap<String, Arc<RwLock<PlayerView>>> = HashMap::new();
+    pub async fn create_instance(
+        players: Vec<PreloadPlayer>,
+        seed: u64,
+        format: MatchFormat,
+        rules: RulesProfile,
+        replay: Option<Arc<ReplayWriter>>,
+    ) -> Result<Self, GameInstanceError> {
+        if let Some(teams) = &format.teams {
+            if players.len() != teams.seats() {
+                return Err(GameInstanceError::SeatsMismatch(
+                    teams.seats(),
+                    players.len(),
+                ));
+            }
+        }
 
-        for player in &players {
-            let player_profile = Player::preload_player_profile(&player.id)
-                .await
-                .map_err(|e| GameInstanceError::PlaceHolderError)?;
+        let rng = Arc::new(MatchRng::new(seed));
+        if let Some(replay) = &replay {
+            replay.record(0, ReplayRecord::Seed { seed });
+        }
 
-            let player_deck = Player::preload_player_deck(&player.deck_id)
-                .await
-                .map_err(|e| GameInstanceError::PlaceHolderError)?;
+        // The profiles and decks are fetched by tasks of their own while the scripts load.
+        let (preloaded, lua_vm) = tokio::join!(
+            Self::preload_players(&players),
+            Self::load_script_manager(&rng)
+        );
+        let lua_vm = lua_vm.map_err(GameInstanceError::ScriptsUnavailable)?;
+        let preloaded = preloaded?;
+        lua_vm.load_blocklist().await;
+        let scripts = ScriptWorker::spawn(lua_vm);
 
-            let full_cards = Card::request_cards(&player_deck.cards)
-                .await
-                .map_err(|e| GameInstanceError::PlaceHolderError)?;
+        for (player, (_, player_deck)) in players.iter().zip(&preloaded) {
+            let violations = format.deck.violations(player_deck);
+            if !violations.is_empty() {
+                let illegal = DeckIllegal {
+                    player_id: player.id.clone(),
+                    deck_id: player_deck.id.clone(),
+                    format: format.name.clone(),
+                    violations,
+                };
+                logger!(WARN, "[GAME] {illegal}");
+                return Err(GameInstanceError::DeckIllegal(illegal));
+            }
+            if rules.constrained_pool || player.pool.is_some() {
+                Self::validate_pool(player, player_deck).map_err(|error| {
+                    logger!(WARN, "[GAME] Deck of `{}` rejected: {error}", &player.id);
+                    GameInstanceError::DeckRejected(player.id.to_string(), error)
+                })?;
+            }
+        }
 
-            for card in full_cards {
-                full_cards_map.insert(card.id.clone(), card);
+        // The cards of every deck are fetched in a single request.
+        let catalog = Arc::new(CardCatalog::default());
+        let mut requested = HashSet::new();
+        let card_refs: Vec<CardRef> = preloaded
+            .iter()
+            .flat_map(|(_, deck)| deck.cards.iter())
+            .filter(|card| requested.insert(card.id.clone()))
+            .cloned()
+            .collect();
+        let full_cards = catalog.cards(&card_refs).await.map_err(|source| {
+            GameInstanceError::CardsUnavailable {
+                deck_ids: preloaded.iter().map(|(_, deck)| deck.id.clone()).collect(),
+                source,
             }
+        })?;
+        let full_cards_map: HashMap<CardDefId, Card> = full_cards
+            .into_iter()
+            .map(|card| (card.id.clone(), card))
+            .collect();
+
+        let mut connected_players: HashMap<PlayerId, Arc<RwLock<Player>>> = HashMap::new();
+        let mut connect_players_views: HashMap<PlayerId, Arc<RwLock<PlayerView>>> = HashMap::new();
 
-            let deck_view = player_deck.create_view(&full_cards_map, &player_profile.id);
-            let player_view = Arc::new(RwLock::new(PlayerView::from_player(
-                &player_profile.id,
-                player_deck.cards.len(),
-            )));
+        // Every copy of every card in the match gets its own instance id.
+        let mut next_instance = 0;
+        let mut card_instances = HashMap::new();
+        for (player_profile, player_deck) in preloaded {
+            let deck_view =
+                player_deck.create_view(&full_cards_map, &player_profile.id, &mut next_instance);
+            card_instances.extend(deck_view.card_views.clone());
+            let mut player_view =
+                PlayerView::from_player(&player_profile.id, player_deck.cards.len());
+            player_view.health = format.starting_health;
+            player_view.mana = format.turns.starting_mana;
+            let player_view = Arc::new(RwLock::new(player_view));
             
             let player = Player::preload_player(player_profile, player_deck, deck_view, player_view.clone()).await;
 
@@ -63,13 +165,164 @@ impl GameInstance {
             connected_players.insert(player.id.clone(), Arc::new(RwLock::new(player)));
         }
 
+        let mut game_state = GameState::new_game(connect_players_views)
+            .with_replay(replay)
+            .with_rng(Arc::clone(&rng))
+            .with_rules(rules)
+            .with_card_instances(card_instances);
+        {
+            let mut cooldowns = game_state.cooldowns.write().await;
+            for card in full_cards_map.values() {
+                if let Some(limit) = card.activation_limit {
+                    cooldowns.register(&card.id, limit);
+                }
+            }
+        }
+        let health = format.teams.as_ref().map(|teams| teams.health);
+        game_state.seating = Seating::new(
+            players.iter().map(|player| player.id.clone()).collect(),
+            health.unwrap_or_default(),
+        );
+        if format.turns.first_player == FirstPlayer::Random {
+            if rng.random_choice(TEAMS) == Ok(1) {
+                game_state.seating = game_state.seating.blue_first();
+            }
+            game_state.record_draws(rng.take_draws());
+        }
+
         Ok(Self {
-            script_manager: scripts,
+            scripts,
             full_cards: Arc::new(RwLock::new(full_cards_map)),
+            catalog,
             connected_players: Arc::new(RwLock::new(connected_players)),
-            game_state: Arc::new(RwLock::new(GameState::new_game(connect_players_views))),
+            game_state: Arc::new(RwLock::new(game_state)),
+            profiler: Arc::new(MatchProfiler::default()),
+            rng,
+            bus: Arc::new(GameEventBus::default()),
+            format,
+            config: RUNTIME_CONFIG.clone(),
         })
     }
+
+    /// Fetches the profile and deck of every player at once, each player in a task of their own.
+    /// Bots need no profile, and drafted decks are not fetched.
+    ///
+    /// # Returns
+    /// * `Ok(Vec<(PreloadedPlayer, Deck)>)` - The profile and deck of every player, in the order of
+    ///   `players`.
+    /// * `Err(GameInstanceError)` - Why the first player, in that order, could not be preloaded.
+    async fn preload_players(
+        players: &[PreloadPlayer],
+    ) -> Result<Vec<(PreloadedPlayer, Deck)>, GameInstanceError> {
+        let mut preloads = JoinSet::new();
+        for (seat, player) in players.iter().enumerate() {
+            let player_id = player.id.clone();
+            let deck_id = player.deck_id.clone();
+            let profile = player.bot.as_ref().map(|bot| bot.profile(&player.id));
+            let deck = player.deck.clone();
+            preloads.spawn(async move {
+                let profile = async {
+                    match profile {
+                        Some(profile) => Ok(profile),
+                        None => {
+                            Player::preload_player_profile(&player_id)
+                                .await
+                                .map_err(|source| GameInstanceError::ProfileUnavailable {
+                                    player_id: player_id.clone(),
+                                    source,
+                                })
+                        }
+                    }
+                };
+                let deck = async {
+                    match deck {
+                        Some(deck) => Ok(deck),
+                        None => Player::preload_player_deck(&deck_id)
+                            .await
+                            .map_err(|source| GameInstanceError::DeckUnavailable {
+                                player_id: player_id.clone(),
+                                deck_id: deck_id.clone(),
+                                source,
+                            }),
+                    }
+                };
+                (seat, tokio::try_join!(profile, deck))
+            });
+        }
+
+        let mut preloaded: Vec<_> = players.iter().map(|_| None).collect();
+        while let Some(joined) = preloads.join_next().await {
+            let (seat, result) = joined.expect("preloading a player should not panic");
+            preloaded[seat] = Some(result);
+        }
+        preloaded.into_iter().flatten().collect()
+    }
+
+    /// Builds a script VM with the match random number generator and every script loaded.
+    async fn load_script_manager(rng: &Arc<MatchRng>) -> Result<ScriptManager, String> {
+        let mut lua_vm = ScriptManager::new_vm();
+        rng.register(&lua_vm.lua).map_err(|e| e.to_string())?;
+        lua_vm.load_scripts().map_err(|e| e.to_string())?;
+        lua_vm.set_globals().await;
+        Ok(lua_vm)
+    }
+
+    /// Loads the card scripts again from disk into a new VM, replacing the running one once every
+    /// script loaded. The blocked scripts are kept, and the VM in use is left untouched if the
+    /// loading fails.
+    ///
+    /// # Returns
+    /// * `Ok(())` - If the new scripts are used by the calls from now on.
+    /// * `Err(String)` - Why the scripts could not be loaded.
+    pub async fn reload_scripts(&self) -> Result<(), String> {
+        let mut lua_vm = Self::load_script_manager(&self.rng).await?;
+        lua_vm.blocklist = Arc::clone(&self.scripts.blocklist);
+        self.scripts
+            .replace(lua_vm)
+            .await
+            .map_err(|e| e.to_string())?;
+        logger!(INFO, "[SCRIPTS] Card scripts reloaded");
+        Ok(())
+    }
+
+    /// Checks that the deck of an arena player was built from the card pool signed by the platform.
+    fn validate_pool(player: &PreloadPlayer, deck: &Deck) -> Result<(), DeckConstraintError> {
+        let pool = player
+            .pool
+            .as_ref()
+            .ok_or(DeckConstraintError::MissingPool)?;
+        let secret = SETTINGS
+            .get()
+            .and_then(|s| s.arena_pool_secret.as_ref())
+            .ok_or(DeckConstraintError::NoPoolSecret)?;
+        pool.validate(&player.id, deck, secret.as_bytes())
+    }
+
+    /// Records that a trigger of a card was skipped because its script is blocked, so players are
+    /// told the effect did not happen.
+    async fn skip_script(
+        &self,
+        game_state: &GameState,
+        card_id: &CardDefId,
+        event: &str,
+        action: &str,
+    ) {
+        logger!(
+            WARN,
+            "[GAME] Skipped the blocked script `{action}` of `{card_id}` ({event})"
+        );
+        game_state
+            .record_event(GameEventKind::ScriptSkipped {
+                card_id: card_id.clone(),
+                function: action.to_string(),
+            })
+            .await;
+        self.bus.publish(MatchEvent::ScriptSkipped(SkippedScript {
+            card_id: card_id.clone(),
+            event: event.to_string(),
+            function: action.to_string(),
+        }));
+    }
 }
 
 // Player Actions
@@ -78,8 +331,19 @@ impl GameInstance {
         self: Arc<Self>,
         client: Arc<Client>,
         request: &PlayCardRequest,
-    ) -> Result<(), GameLogicError> {
+    ) -> Result<PlayOutcome, GameLogicError> {
         let game_state = self.game_state.read().await;
+        self.play_card_in(&game_state, client, request).await
+    }
+
+    /// Plays a card on a game state the caller holds, see `play_card`.
+    async fn play_card_in(
+        &self,
+        game_state: &GameState,
+        client: Arc<Client>,
+        request: &PlayCardRequest,
+    ) -> Result<PlayOutcome, GameLogicError> {
+        let checkpoint = game_state.undo_checkpoint().await;
         let player_views = game_state.player_views.read().await;
 
         // Clone and lock the Client player object to compare identity and access full player data.
@@ -101,57 +365,1002 @@ impl GameInstance {
             return Err(GameLogicError::PlayerIdDoesNotMatch);
         }
 
-        //Confirm it is currently this player's turn.
-        if &player_view_guard.id != &request.actor_id {
-            return Err(GameLogicError::NotPlayerTurn);
-        }
+        // Confirm it is currently this player's turn, and that they are not stunned.
+        game_state.check_turn(&player_guard.id)?;
+        Self::check_not_stunned(&player_view_guard, &[])?;
 
         // Verifies if the card played is actually in the player's hand. This does not account for
         // out-of-hand plays from special interactions as they do not exist yet.
         let player_hand = player_view_guard.current_hand.iter();
         let card_view = player_hand
             .flatten()
-            .find(|c| c.id == request.card_id)
+            .find(|c| c.instance_id == request.instance_id)
             .ok_or_else(|| GameLogicError::CardPlayedIsNotInHand)?;
 
         // Verify that the requested card is in the player's current hand.
-        // Retrieve the full card details from game_cards. If not present, fetch it from external storage and add it to the shared card list.
-        let game_cards_lock = self.full_cards.read().await;
-        let full_card = match game_cards_lock.get(&card_view.id) {
+        // Retrieve the full card details from game_cards. If not present, get it from the card catalog and add it to the shared card list.
+        let known_card = self.full_cards.read().await.get(&card_view.id).cloned();
+        let full_card = match known_card {
             Some(card) => card,
             None => {
-                let card = Card::request_card(&card_view.id)
+                let card = self
+                    .catalog
+                    .card(&card_view.id)
                     .await
                     .map_err(|_| GameLogicError::UnableToGetCardDetails)?;
-                self.add_card(card).await;
-                game_cards_lock.get(&card_view.id).ok_or_else(|| {
-                    return GameLogicError::UnableToGetCardDetails;
-                })?
+                self.add_card(card.clone()).await;
+                card
             }
         };
 
-        // Iterate over the card’s on_play triggers, creating a Lua execution context for each.
-        for action in &full_card.on_play {
-            let lua_context = LuaContext::new(
-                Arc::clone(&self.game_state),
-                card_view,
+        // Decide where the card goes on the board before anything changes, so an invalid position
+        // rejects the play.
+        let placement = board::plan(
+            &player_view_guard,
+            &card_view.id,
+            full_card.card_type,
+            request.target_position.as_deref(),
+        )?;
+
+        // Resolve the target: an explicit target must be legal, a single legal target is picked
+        // automatically and several legal targets put the play on hold behind a prompt.
+        let mut views = Vec::with_capacity(player_views.len());
+        for view in player_views.values() {
+            if Arc::ptr_eq(view, &player_view_clone) {
+                views.push(player_view_guard.clone());
+            } else {
+                views.push(view.read().await.clone());
+            }
+        }
+
+        let legal = targeting::legal_targets(&full_card.targeting, &player_guard.id, &views);
+        let target_id = match targeting::resolve_target(
+            &full_card.targeting,
+            request.target_id.as_deref(),
+            legal,
+        )? {
+            TargetResolution::NoTarget => None,
+            TargetResolution::Resolved(target) => Some(target),
+            TargetResolution::Prompt(options) => {
+                let mut prompts = game_state.prompts.write().await;
+                let prompt = prompts.open(
+                    &player_guard.id,
+                    PromptKind::ChooseTarget,
+                    Some(card_view.id.clone()),
+                    options,
+                    PromptOrigin::PlayCard(request.clone()),
+                    self.config.get().prompt_timeout,
+                );
+                return Ok(PlayOutcome::Prompted(prompt));
+            }
+        };
+
+        // The card leaves the hand for its slot on the board, or the graveyard for a spell, before
+        // its on_play triggers run. A failing trigger puts it back, along with everything else.
+        let card_view = card_view.clone();
+        drop(player_view_guard);
+        let rollback = Checkpoint::capture(game_state).await;
+        {
+            let mut view = player_view_clone.write().await;
+            view.take_from_hand(&card_view.instance_id);
+            if let Some(placement) = placement {
+                board::place(&mut view, &card_view, placement);
+            } else {
+                let spell = CardRef {
+                    id: card_view.id.clone(),
+                    amount: 1,
+                    owner_id: None,
+                    instances: vec![card_view.instance_id.clone()],
+                };
+                graveyard::bury(&mut view, spell, full_card.card_type);
+            }
+        }
+
+        // Queue the card’s on_play triggers on the effect stack and resolve them, along with
+        // everything they trigger.
+        let effects = full_card
+            .on_play
+            .iter()
+            .map(|action| PendingEffect::new(&card_view, "on_play", action, target_id.clone()))
+            .collect();
+        let cinematic = Duration::from_millis(full_card.cinematic_ms);
+        let scripted = self.resolve_effects(game_state, effects, rollback).await?;
+
+        game_state
+            .record_event(GameEventKind::CardPlayed {
+                player_id: player_guard.id.clone(),
+                card_id: card_view.id.clone(),
+                target_id,
+            })
+            .await;
+        Self::pause_turn_timer(game_state, &player_guard.id, cinematic + scripted).await;
+        if let Some(checkpoint) = checkpoint {
+            game_state
+                .undo
+                .write()
+                .await
+                .record(&player_guard.id, checkpoint);
+        }
+        Ok(PlayOutcome::Resolved)
+    }
+
+    /// Activates the ability of a card on the board of the player.
+    ///
+    /// The activation limit of the card is enforced by the cooldown tracker before any script
+    /// runs. Abilities target like the card they belong to, but never open a prompt: with several
+    /// legal targets the request must name one.
+    ///
+    /// # Returns
+    /// * `Ok(PlayOutcome::Resolved)` - If the ability was activated.
+    /// * `Err(GameLogicError)` - If the card is not on the board, has no ability or no activation
+    ///   left, or the target is invalid.
+    pub async fn activate_ability(
+        self: Arc<Self>,
+        client: Arc<Client>,
+        request: &ActivateAbilityRequest,
+    ) -> Result<PlayOutcome, GameLogicError> {
+        let game_state = self.game_state.read().await;
+        let player_id = client.player.read().await.id.clone();
+        if player_id != request.actor_id {
+            return Err(GameLogicError::PlayerIdDoesNotMatch);
+        }
+        game_state.check_turn(&player_id)?;
+
+        let checkpoint = game_state.undo_checkpoint().await;
+        let views = game_state.snapshot_views().await;
+        let player_view = views
+            .get(&player_id)
+            .ok_or(GameLogicError::PlayerNotFound)?;
+        let card_id = match board::find(player_view, &request.instance_id) {
+            Some((_, card)) => card.id.clone(),
+            None => {
+                return Err(GameLogicError::CardNotOnBoard(
+                    request.instance_id.to_string(),
+                ))
+            }
+        };
+        Self::check_not_stunned(player_view, std::slice::from_ref(&request.instance_id))?;
+        let board_card = player_view.board.cards.get(&request.instance_id).cloned();
+
+        let full_cards = self.full_cards.read().await;
+        let full_card = full_cards
+            .get(&card_id)
+            .ok_or(GameLogicError::UnableToGetCardDetails)?;
+
+        let views: Vec<_> = views.into_values().collect();
+        let legal = targeting::legal_targets(&full_card.targeting, &player_id, &views);
+        let target_id = match targeting::resolve_target(
+            &full_card.targeting,
+            request.target_id.as_deref(),
+            legal,
+        )? {
+            TargetResolution::NoTarget => None,
+            TargetResolution::Resolved(target) => Some(target),
+            TargetResolution::Prompt(_) => return Err(GameLogicError::TargetRequired),
+        };
+
+        // The activation is only used up once its scripts resolved, so a failing script costs
+        // the player nothing.
+        let remaining = game_state
+            .cooldowns
+            .read()
+            .await
+            .check(&player_id, &card_id)?;
+
+        let mut card_view = board_card.unwrap_or_else(|| {
+            let mut card =
+                CardView::create_view(full_card, player_id.clone(), request.instance_id.clone());
+            card.in_board = true;
+            card
+        });
+        card_view.remaining_uses = Some(remaining - 1);
+        let effects = full_card
+            .on_activate
+            .iter()
+            .map(|action| PendingEffect::new(&card_view, "on_activate", action, target_id.clone()))
+            .collect();
+        let cinematic = Duration::from_millis(full_card.cinematic_ms);
+        drop(full_cards);
+        let rollback = Checkpoint::capture(&game_state).await;
+        let scripted = self.resolve_effects(&game_state, effects, rollback).await?;
+
+        let remaining = game_state
+            .cooldowns
+            .write()
+            .await
+            .activate(&player_id, &card_id)?;
+        game_state.refresh_remaining_uses(&player_id).await;
+        logger!(
+            DEBUG,
+            "[ABILITY] `{player_id}` activated `{}`, {remaining} use(s) left",
+            &request.instance_id
+        );
+
+        game_state
+            .record_event(GameEventKind::AbilityActivated {
+                player_id: player_id.clone(),
+                card_id: card_view.id.clone(),
+                target_id,
+            })
+            .await;
+        Self::pause_turn_timer(&game_state, &player_id, cinematic + scripted).await;
+        if let Some(checkpoint) = checkpoint {
+            game_state.undo.write().await.record(&player_id, checkpoint);
+        }
+        Ok(PlayOutcome::Resolved)
+    }
+
+    /// Attacks the opponent with creatures of the player's board.
+    ///
+    /// The `on_attack` scripts of the attackers resolve first. In direct-attack matches the attack
+    /// then resolves at once; with blockers, a window opens for the defender to declare blockers.
+    ///
+    /// # Returns
+    /// * `Ok(None)` - If the attack resolved.
+    /// * `Ok(Some(CombatWindow))` - The window to send to the defender.
+    /// * `Err(GameLogicError)` - If a creature cannot attack or another attack waits on blockers.
+    pub async fn declare_attackers(
+        &self,
+        client: Arc<Client>,
+        request: &DeclareAttackersRequest,
+    ) -> Result<Option<CombatWindow>, GameLogicError> {
+        let player_id = client.player.read().await.id.clone();
+        if player_id != request.actor_id {
+            return Err(GameLogicError::PlayerIdDoesNotMatch);
+        }
+
+        let game_state = self.game_state.read().await;
+        game_state.check_turn(&player_id)?;
+        let views = game_state.snapshot_views().await;
+        let view = views
+            .get(&player_id)
+            .ok_or(GameLogicError::PlayerNotFound)?;
+        for attacker in &request.attackers {
+            if !board::has_creature(view, attacker) {
+                return Err(GameLogicError::InvalidAttacker(attacker.to_string()));
+            }
+        }
+        Self::check_not_stunned(view, &request.attackers)?;
+        game_state
+            .combat
+            .read()
+            .await
+            .check_attackers(&request.attackers)?;
+
+        let defender_id = match &request.defender_id {
+            Some(defender_id) => defender_id.clone(),
+            None => game_state
+                .opponent_of(&player_id)
+                .cloned()
+                .ok_or(GameLogicError::PlayerNotFound)?,
+        };
+        if !game_state
+            .seating
+            .opponents_of(&player_id)
+            .contains(&&defender_id)
+        {
+            return Err(GameLogicError::InvalidDefender(defender_id.to_string()));
+        }
+
+        // The attack is only declared once the on_attack triggers resolved; a failing trigger
+        // rolls back the declaration along with the effects.
+        let rollback = Checkpoint::capture(&game_state).await;
+        game_state
+            .record_event(GameEventKind::AttackersDeclared {
+                player_id: player_id.clone(),
+                attackers: request.attackers.clone(),
+            })
+            .await;
+
+        let effects = {
+            let full_cards = self.full_cards.read().await;
+            let mut effects = Vec::new();
+            for attacker in &request.attackers {
+                let Some(card) = view.board.cards.get(attacker) else {
+                    continue;
+                };
+                let Some(full_card) = full_cards.get(&card.id) else {
+                    continue;
+                };
+
+                effects.extend(
+                    full_card
+                        .on_attack
+                        .iter()
+                        .map(|action| PendingEffect::new(card, "on_attack", action, None)),
+                );
+            }
+            effects
+        };
+        let scripted = self.resolve_effects(&game_state, effects, rollback).await?;
+        {
+            let mut combat = game_state.combat.write().await;
+            combat.check_attackers(&request.attackers)?;
+            combat.record_attackers(&request.attackers);
+        }
+        Self::pause_turn_timer(&game_state, &player_id, scripted).await;
+        match game_state.rules.combat {
+            CombatMode::DirectAttack => {
+                self.resolve_combat(&game_state, &defender_id, &request.attackers, &[])
+                    .await?;
+                Ok(None)
+            }
+            CombatMode::Blockers => {
+                let timeout = self.config.get().blockers_timeout;
+                let window = game_state.combat.write().await.open_window(
+                    &player_id,
+                    &defender_id,
+                    request.attackers.clone(),
+                    timeout,
+                );
+                logger!(
+                    DEBUG,
+                    "[COMBAT] `{defender_id}` may block until {}",
+                    window.deadline
+                );
+                Ok(Some(window))
+            }
+        }
+    }
+
+    /// Blocks the attackers of the opponent with creatures of the player's board, then resolves
+    /// the attack.
+    ///
+    /// # Returns
+    /// * `Ok(PlayOutcome::Resolved)` - Once the attack resolved.
+    /// * `Err(GameLogicError)` - If no attack waits on the player's blockers, a block is invalid
+    ///   or the attack could not be resolved; the window then stays open.
+    pub async fn declare_blockers(
+        &self,
+        client: Arc<Client>,
+        request: &DeclareBlockersRequest,
+    ) -> Result<PlayOutcome, GameLogicError> {
+        let player_id = client.player.read().await.id.clone();
+        if player_id != request.actor_id {
+            return Err(GameLogicError::PlayerIdDoesNotMatch);
+        }
+
+        let game_state = self.game_state.read().await;
+        let views = game_state.snapshot_views().await;
+        let view = views
+            .get(&player_id)
+            .ok_or(GameLogicError::PlayerNotFound)?;
+        {
+            let combat = game_state.combat.read().await;
+            let window = combat.window(&player_id)?;
+            combat::check_blocks(window, &request.blocks, |blocker| {
+                board::has_creature(view, blocker)
+            })?;
+        }
+        let blockers: Vec<_> = request.blocks.iter().map(|b| b.blocker.clone()).collect();
+        Self::check_not_stunned(view, &blockers)?;
+
+        // A failing resolution rolls back the damage dealt and opens the window again.
+        let rollback = Checkpoint::capture(&game_state).await;
+        let window = game_state.combat.write().await.take_window(&player_id)?;
+        game_state
+            .record_event(GameEventKind::BlockersDeclared {
+                player_id: player_id.clone(),
+                blocks: request.blocks.clone(),
+            })
+            .await;
+        let resolved = self
+            .resolve_combat(&game_state, &player_id, &window.attackers, &request.blocks)
+            .await;
+        if let Err(error) = resolved {
+            rollback.restore(&game_state).await;
+            return Err(error);
+        }
+        Ok(PlayOutcome::Resolved)
+    }
+
+    /// Resolves the attack whose blockers window closed without an answer, as if nothing blocked.
+    ///
+    /// # Returns
+    /// `true` if an attack was resolved.
+    pub async fn resolve_expired_combat(&self) -> bool {
+        let game_state = self.game_state.read().await;
+        let expired = game_state
+            .combat
+            .write()
+            .await
+            .take_expired(Utc::now().timestamp_millis());
+        let Some(window) = expired else {
+            return false;
+        };
+
+        logger!(
+            INFO,
+            "[COMBAT] `{}` declared no blockers in time, resolving the attack",
+            &window.defender_id
+        );
+        let resolved = self
+            .resolve_combat(&game_state, &window.defender_id, &window.attackers, &[])
+            .await;
+        if let Err(error) = resolved {
+            logger!(ERROR, "[COMBAT] Could not resolve the attack: {error}");
+        }
+        true
+    }
+
+    /// Applies game actions typed into the developer REPL the way the actions of a card script
+    /// are: the triggers they set off resolve, the board state invariants are checked in debug and
+    /// test builds, and the match goes back to where it was if the resolution fails.
+    ///
+    /// # Returns
+    /// * `Ok(Vec<GameEventKind>)` - The events the actions recorded.
+    /// * `Err(GameLogicError)` - If a triggered script failed; the match is back as it was.
+    #[cfg(feature = "dev-repl")]
+    pub async fn apply_repl_actions(
+        &self,
+        source: &CardDefId,
+        actions: Vec<GameAction>,
+    ) -> Result<Vec<GameEventKind>, GameLogicError> {
+        let game_state = self.game_state.read().await;
+        let rollback = Checkpoint::capture(&game_state).await;
+        let applied = game_state.apply_actions(source, actions).await;
+        let mut triggered = Vec::new();
+        for event in &applied {
+            triggered.extend(self.death_triggers(&game_state, event).await);
+        }
+
+        self.resolve_effects(&game_state, triggered, rollback)
+            .await?;
+        Ok(applied)
+    }
+
+    /// Ends the turn of the player and starts the turn of the next player in turn order.
+    ///
+    /// # Returns
+    /// * `Ok(PlayOutcome::Resolved)` - If the next turn started.
+    /// * `Err(GameLogicError)` - If it is not the player's turn, or their attack still waits on
+    ///   blockers.
+    pub async fn end_turn(&self, client: Arc<Client>) -> Result<PlayOutcome, GameLogicError> {
+        let player_id = client.player.read().await.id.clone();
+        let mut game_state = self.game_state.write().await;
+        game_state.check_turn(&player_id)?;
+        if game_state.combat.read().await.in_progress() {
+            return Err(GameLogicError::CombatInProgress);
+        }
+
+        self.advance_turn(&mut game_state).await;
+        Ok(PlayOutcome::Resolved)
+    }
+
+    /// Ends the turn of the player once they used up the turn time of the rules, the pauses for
+    /// long resolutions left out, as soon as no attack of theirs waits on blockers.
+    ///
+    /// # Returns
+    /// `true` if the next turn started.
+    pub async fn end_expired_turn(&self) -> bool {
+        let mut game_state = self.game_state.write().await;
+        let Some(limit) = game_state.rules.turn_limit() else {
+            return false;
+        };
+        let Some(player_id) = game_state.active_player.clone() else {
+            return false;
+        };
+        if game_state.combat.read().await.in_progress() {
+            return false;
+        }
+
+        let used = game_state.think_time.read().await.think_time(&player_id);
+        if u128::from(used.turn_millis) < limit.as_millis() {
+            return false;
+        }
+
+        logger!(INFO, "[GAME] `{player_id}` ran out of turn time");
+        self.advance_turn(&mut game_state).await;
+        true
+    }
+
+    /// Starts the next turn, the first one once the match starts, and tells the players and
+    /// spectators whose turn it is.
+    pub async fn start_next_turn(&self) {
+        let mut game_state = self.game_state.write().await;
+        self.advance_turn(&mut game_state).await;
+    }
+
+    async fn advance_turn(&self, game_state: &mut GameState) {
+        let Some(turn) = game_state.advance_turn().await else {
+            logger!(WARN, "[GAME] No player is left to take the next turn");
+            return;
+        };
+
+        // Creatures poison killed at the turn boundary set off their death triggers.
+        let mut triggered = Vec::new();
+        for event in &turn.deaths {
+            triggered.extend(self.death_triggers(game_state, event).await);
+        }
+        let rollback = Checkpoint::capture(game_state).await;
+        if let Err(error) = self.resolve_effects(game_state, triggered, rollback).await {
+            logger!(ERROR, "[GAME] Poison death triggers failed: {error}");
+        }
+
+        let player_id = turn.player_id;
+        logger!(DEBUG, "[GAME] Turn {} of `{player_id}`", game_state.rounds);
+        self.bus.publish(MatchEvent::TurnStarted(TurnStarted {
+            turn: game_state.rounds,
+            player_id,
+        }));
+    }
+
+    /// Deals the damage of an attack: unblocked attackers hit the defending player, blocked ones
+    /// and their blockers wound each other on their board state, and creatures left without
+    /// health die, queuing their death triggers. Failing triggers are rolled back, while the
+    /// damage of the attack stands.
+    async fn resolve_combat(
+        &self,
+        game_state: &GameState,
+        defender_id: &PlayerId,
+        attackers: &[CardInstanceId],
+        blocks: &[Block],
+    ) -> Result<(), GameLogicError> {
+        let views = game_state.snapshot_views().await;
+        let outcome = combat::resolve(attackers, blocks, |card| {
+            views
+                .values()
+                .find_map(|view| view.board.cards.get(card))
+                .map(|card| status_effect::attack(card.attack, &card.status_effects))
+        });
+
+        if outcome.player_damage > 0 {
+            game_state
+                .damage_player(defender_id, outcome.player_damage as i32)
+                .await;
+            game_state
+                .record_event(GameEventKind::DamageDealt {
+                    target: defender_id.to_string(),
+                    amount: outcome.player_damage,
+                })
+                .await;
+        }
+
+        let mut destroyed = Vec::new();
+        for (instance_id, amount) in &outcome.creature_damage {
+            if game_state.damage_creature(instance_id, *amount).await == Some(true) {
+                destroyed.push(instance_id);
+            }
+        }
+
+        let rollback = Checkpoint::capture(game_state).await;
+        let mut triggered = Vec::new();
+        for instance_id in destroyed {
+            match game_state.destroy_card(instance_id).await {
+                Ok(event) => {
+                    game_state.record_event(event.clone()).await;
+                    triggered.extend(self.death_triggers(game_state, &event).await);
+                }
+                Err(error) => logger!(DEBUG, "[COMBAT] Cannot destroy `{instance_id}`: {error}"),
+            }
+        }
+
+        self.resolve_effects(game_state, triggered, rollback)
+            .await?;
+        Ok(())
+    }
+
+    /// Resolves triggered effects one at a time through the effect stack. The triggers set off by
+    /// the actions of an effect are queued in turn, until none is left.
+    ///
+    /// Every action of a player and every turn boundary ends here, so in debug and test builds
+    /// the board state invariants are checked once the effects resolved, against `rollback`.
+    ///
+    /// # Arguments
+    /// * `rollback` - The state to go back to if the resolution fails, captured before the action
+    ///   setting off the effects changed anything.
+    ///
+    /// # Returns
+    /// * `Ok(Duration)` - Once every effect resolved, with the length of the cinematics they played.
+    /// * `Err(GameLogicError)` - If a script failed, or a chain of triggers grew deeper than
+    ///   `EFFECT_STACK_MAX_DEPTH`; the match is then back in the `rollback` state.
+    async fn resolve_effects(
+        &self,
+        game_state: &GameState,
+        effects: Vec<PendingEffect>,
+        rollback: Checkpoint,
+    ) -> Result<Duration, GameLogicError> {
+        let resolved = self.resolve_stack(game_state, effects).await;
+        match &resolved {
+            Ok(_) => Self::check_invariants(game_state, &rollback.views).await,
+            Err(error) => {
+                logger!(WARN, "[EFFECTS] Rolling back the resolution: {error}");
+                rollback.restore(game_state).await;
+            }
+        }
+        resolved
+    }
+
+    /// Panics in debug and test builds if the board state breaks an invariant, with a diff
+    /// against `before`.
+    async fn check_invariants(game_state: &GameState, before: &HashMap<PlayerId, PlayerView>) {
+        #[cfg(debug_assertions)]
+        invariants::assert_invariants(before, &game_state.snapshot_views().await);
+        #[cfg(not(debug_assertions))]
+        let _ = (game_state, before);
+    }
+
+    async fn resolve_stack(
+        &self,
+        game_state: &GameState,
+        effects: Vec<PendingEffect>,
+    ) -> Result<Duration, GameLogicError> {
+        let (order, max_depth) = SETTINGS
+            .get()
+            .map_or((ResolutionOrder::default(), 16), |s| {
+                (s.effect_resolution_order, s.effect_stack_max_depth)
+            });
+        let mut stack = EffectStack::new(order, max_depth);
+        stack.push_all(effects)?;
+        let mut cinematics = Duration::ZERO;
+
+        while let Some(effect) = stack.pop() {
+            if self
+                .scripts
+                .is_blocked(&effect.card.id, &effect.function)
+                .await
+            {
+                self.skip_script(game_state, &effect.card.id, &effect.event, &effect.function)
+                    .await;
+                continue;
+            }
+
+            let mut lua_context = LuaContext::new(
+                game_state,
+                &effect.card,
                 None,
-                "on_play".to_string(),
-                action.to_string(),
+                effect.event.clone(),
+                effect.function.clone(),
             )
             .await;
+            lua_context.target_id = effect.target_id.clone();
 
-            // Execute each script action using the ScriptManager and apply the resulting game actions to the state.
-            let script_manager_guard = self.script_manager.read().await;
-            let game_actions = script_manager_guard
-                .call_function_ctx(action, lua_context)
-                .await?;
+            // Execute the script and apply the resulting game actions to the state.
+            let started = Instant::now();
+            let game_actions = self
+                .scripts
+                .call_function_ctx(&effect.function, lua_context)
+                .await;
+            let elapsed = started.elapsed();
+            self.profiler.record_lua(elapsed);
+            METRICS.record_lua_call(elapsed);
+            game_state.record_draws(self.rng.take_draws());
+            let game_actions = game_actions?;
 
-            game_state.apply_actions(game_actions).await;
+            let mut triggered = Vec::new();
+            for event in game_state
+                .apply_actions(&effect.card.id, game_actions)
+                .await
+            {
+                if let GameEventKind::CinematicPlayed { duration_ms, .. } = &event {
+                    cinematics += Duration::from_millis(*duration_ms);
+                }
+                triggered.extend(self.death_triggers(game_state, &event).await);
+            }
+
+            let triggered = triggered
+                .into_iter()
+                .map(|trigger| trigger.nested(effect.depth))
+                .collect();
+            stack.push_all(triggered)?;
+        }
+
+        Ok(cinematics)
+    }
+
+    /// Fails with `Stunned` if the player, or one of the given cards of their board, is stunned.
+    fn check_not_stunned(
+        view: &PlayerView,
+        cards: &[CardInstanceId],
+    ) -> Result<(), GameLogicError> {
+        if status_effect::is_stunned(&view.status_effects) {
+            return Err(GameLogicError::Stunned(view.id.to_string()));
+        }
+
+        for instance_id in cards {
+            let stunned = view
+                .board
+                .cards
+                .get(instance_id)
+                .is_some_and(|card| status_effect::is_stunned(&card.status_effects));
+            if stunned {
+                return Err(GameLogicError::Stunned(instance_id.to_string()));
+            }
         }
 
         Ok(())
     }
+
+    /// Pauses the turn timer of a player while the animations of a resolution play on the
+    /// clients, within the bounds of the rules of the match.
+    ///
+    /// # Arguments
+    /// * `player_id` - The player whose play resolved.
+    /// * `requested` - The animation time of the card and of the cinematics its scripts played.
+    async fn pause_turn_timer(game_state: &GameState, player_id: &PlayerId, requested: Duration) {
+        if requested.is_zero() {
+            return;
+        }
+
+        let rules = &game_state.rules;
+        let granted = game_state.think_time.write().await.pause(
+            player_id,
+            rules.cinematic_pause(requested),
+            Duration::from_millis(rules.cinematic_pause_turn_ms),
+        );
+        logger!(
+            DEBUG,
+            "[GAME] Paused the turn timer of `{player_id}` for {}ms of {}ms requested",
+            granted.as_millis(),
+            requested.as_millis()
+        );
+        if !granted.is_zero() {
+            game_state
+                .record_event(GameEventKind::TurnTimerPaused {
+                    player_id: player_id.clone(),
+                    millis: granted.as_millis() as u64,
+                })
+                .await;
+        }
+    }
+
+    /// The triggers set off by the death of a creature: the `on_death` scripts of the creature,
+    /// then the `on_ally_death` scripts of the creatures of its owner and the `on_enemy_death`
+    /// scripts of the other creatures on the boards.
+    async fn death_triggers(
+        &self,
+        game_state: &GameState,
+        event: &GameEventKind,
+    ) -> Vec<PendingEffect> {
+        let GameEventKind::CreatureDied {
+            card_id,
+            instance_id,
+            owner_id,
+        } = event
+        else {
+            return Vec::new();
+        };
+
+        let full_cards = self.full_cards.read().await;
+        let views = game_state.snapshot_views().await;
+        let mut triggered = Vec::new();
+
+        // The copy that died, unless something already took it out of its owner's graveyard.
+        let buried = views
+            .get(owner_id)
+            .is_some_and(|view| graveyard::find(view, instance_id).is_some());
+        if let (true, Some(full_card)) = (buried, full_cards.get(card_id)) {
+            let mut card = CardView::create_view(full_card, owner_id.clone(), instance_id.clone());
+            card.in_graveyard = true;
+            triggered.extend(
+                full_card
+                    .on_death
+                    .iter()
+                    .map(|action| PendingEffect::new(&card, "on_death", action, None)),
+            );
+        }
+
+        let mut holders: Vec<_> = views.keys().collect();
+        holders.sort_by_key(|holder_id| (*holder_id != owner_id, *holder_id));
+        for holder_id in holders {
+            for stack in views[holder_id].board.creatures.iter().flatten() {
+                let Some(full_card) = full_cards.get(&stack.id) else {
+                    continue;
+                };
+                let (trigger, actions) = match holder_id == owner_id {
+                    true => ("on_ally_death", &full_card.on_ally_death),
+                    false => ("on_enemy_death", &full_card.on_enemy_death),
+                };
+
+                for instance_id in &stack.instances {
+                    let owner = stack.owner_or(holder_id).clone();
+                    let mut card = CardView::create_view(full_card, owner, instance_id.clone());
+                    card.controller_id = holder_id.clone();
+                    card.in_board = true;
+                    triggered.extend(
+                        actions
+                            .iter()
+                            .map(|action| PendingEffect::new(&card, trigger, action, None)),
+                    );
+                }
+            }
+        }
+
+        triggered
+    }
+
+    /// Resumes an action that was put on hold by a prompt, using the player's answer.
+    ///
+    /// # Arguments
+    /// * `client` - The client answering the prompt.
+    /// * `response` - The prompt id and the chosen option.
+    ///
+    /// # Returns
+    /// * `Ok(PlayOutcome)` - The outcome of the resumed action.
+    /// * `Err(GameLogicError)` - If the prompt or the choice is invalid, or the resumed action fails.
+    pub async fn answer_prompt(
+        self: Arc<Self>,
+        client: Arc<Client>,
+        response: &PromptResponse,
+    ) -> Result<PlayOutcome, GameLogicError> {
+        let game_state = self.game_state.read().await;
+        self.answer_prompt_in(&game_state, client, response).await
+    }
+
+    /// Answers a prompt on a game state the caller holds, see `answer_prompt`.
+    async fn answer_prompt_in(
+        &self,
+        game_state: &GameState,
+        client: Arc<Client>,
+        response: &PromptResponse,
+    ) -> Result<PlayOutcome, GameLogicError> {
+        let player_id = client.player.read().await.id.clone();
+        let pending = game_state.prompts.write().await.answer(
+            response.prompt_id,
+            &player_id,
+            &response.choice,
+        )?;
+
+        self.resume_prompt_in(game_state, client, pending, response.choice.clone())
+            .await
+    }
+
+    /// Resolves a batch of sub-actions atomically: either all of them are applied, or the state is
+    /// rolled back and the index of the first failing sub-action is reported.
+    ///
+    /// Sub-actions that would need a prompt fail the batch, since batches must carry every choice.
+    /// A committed batch is undone as a single play. The game state stays locked from the first
+    /// sub-action to the commit, so no other action lands in the middle of a batch.
+    pub async fn play_batch(
+        self: Arc<Self>,
+        client: Arc<Client>,
+        request: &ActionBatchRequest,
+    ) -> Result<PlayOutcome, GameLogicError> {
+        let game_state = self.game_state.write().await;
+        let batch = AtomicBatch::begin(&game_state, request.actions.len()).await?;
+        let undo = game_state.undo.read().await.clone();
+
+        for (index, action) in request.actions.iter().enumerate() {
+            let resolved = self
+                .resolve_batched(&game_state, client.clone(), action)
+                .await;
+            if let Err(error) = resolved {
+                *game_state.undo.write().await = undo;
+                return Err(batch.rollback(&game_state, index, error).await);
+            }
+        }
+
+        let checkpoint = batch.commit(&game_state).await;
+        if game_state.rules.allows_undo() {
+            let player_id = client.player.read().await.id.clone();
+            game_state.undo.write().await.record(&player_id, checkpoint);
+        }
+        Ok(PlayOutcome::Resolved)
+    }
+
+    /// Resolves a single sub-action of a batch.
+    async fn resolve_batched(
+        &self,
+        game_state: &GameState,
+        client: Arc<Client>,
+        action: &BatchedAction,
+    ) -> Result<(), GameLogicError> {
+        let outcome = match action {
+            BatchedAction::PlayCard(play) => self.play_card_in(game_state, client, play).await?,
+            BatchedAction::PromptResponse(response) => {
+                self.answer_prompt_in(game_state, client, response).await?
+            }
+        };
+
+        match outcome {
+            PlayOutcome::Resolved => Ok(()),
+            PlayOutcome::Prompted(prompt) => {
+                game_state.prompts.write().await.cancel(prompt.id);
+                Err(GameLogicError::BatchActionPrompted)
+            }
+        }
+    }
+
+    /// Resumes the action behind a prompt with the given choice.
+    pub async fn resume_prompt(
+        self: Arc<Self>,
+        client: Arc<Client>,
+        pending: PendingPrompt,
+        choice: String,
+    ) -> Result<PlayOutcome, GameLogicError> {
+        let game_state = self.game_state.read().await;
+        self.resume_prompt_in(&game_state, client, pending, choice)
+            .await
+    }
+
+    async fn resume_prompt_in(
+        &self,
+        game_state: &GameState,
+        client: Arc<Client>,
+        pending: PendingPrompt,
+        choice: String,
+    ) -> Result<PlayOutcome, GameLogicError> {
+        match pending.origin {
+            PromptOrigin::PlayCard(mut request) => {
+                request.target_id = Some(choice);
+                self.play_card_in(game_state, client, &request).await
+            }
+            PromptOrigin::Undo(player_id) => {
+                Self::resolve_undo(game_state, &player_id, &choice).await
+            }
+        }
+    }
+
+    /// Asks the opponent of a player to consent to undoing the player's last play.
+    ///
+    /// The play is only taken back once the opponent accepts the `ConfirmUndo` prompt; an
+    /// unanswered prompt declines.
+    ///
+    /// # Returns
+    /// * `Ok(Prompt)` - The prompt to send to the opponent.
+    /// * `Err(GameLogicError)` - If the rules of the match disable undo, the player has no undo
+    ///   left or their last play can no longer be undone.
+    pub async fn request_undo(&self, client: Arc<Client>) -> Result<Prompt, GameLogicError> {
+        let player_id = client.player.read().await.id.clone();
+        let game_state = self.game_state.read().await;
+        game_state
+            .undo
+            .read()
+            .await
+            .check(&player_id, game_state.rules.undo_limit)?;
+
+        let opponent = game_state
+            .opponent_of(&player_id)
+            .ok_or(GameLogicError::UndoConsentUnavailable)?;
+        let prompt = game_state.prompts.write().await.open(
+            opponent,
+            PromptKind::ConfirmUndo,
+            None,
+            vec![UNDO_ACCEPT.to_string(), UNDO_DECLINE.to_string()],
+            PromptOrigin::Undo(player_id.clone()),
+            self.config.get().prompt_timeout,
+        );
+
+        logger!(
+            DEBUG,
+            "[UNDO] `{player_id}` asked `{opponent}` to undo their last play"
+        );
+        Ok(prompt)
+    }
+
+    /// Applies the opponent's answer to an undo request, rolling the match back to the state
+    /// before the last play of `player_id` if they accepted.
+    async fn resolve_undo(
+        game_state: &GameState,
+        player_id: &PlayerId,
+        choice: &str,
+    ) -> Result<PlayOutcome, GameLogicError> {
+        if choice != UNDO_ACCEPT {
+            game_state
+                .record_event(GameEventKind::UndoDeclined {
+                    player_id: player_id.clone(),
+                })
+                .await;
+            return Ok(PlayOutcome::Resolved);
+        }
+
+        let checkpoint = game_state
+            .undo
+            .write()
+            .await
+            .take(player_id, game_state.rules.undo_limit)?;
+        let before = game_state.snapshot_views().await;
+        checkpoint.restore(game_state).await;
+        Self::check_invariants(game_state, &before).await;
+        game_state
+            .record_event(GameEventKind::PlayUndone {
+                player_id: player_id.clone(),
+            })
+            .await;
+
+        logger!(INFO, "[UNDO] The last play of `{player_id}` was undone");
+        Ok(PlayOutcome::Resolved)
+    }
 }
 
 // Card implementations
@@ -159,7 +1368,7 @@ impl GameInstance {
     /// Store a card in the game state.
     pub async fn add_card(&self, card: Card) {
         let mut card_vec = self.full_cards.write().await;
-        card_vec.insert(card.id.to_string(), card);
+        card_vec.insert(card.id.clone(), card);
     }
 }
 
@@ -1,44 +1,1091 @@
-use crate::game::entity::card::{Card, CardRef};
-use crate::game::entity::player::{Player, PlayerView, PublicPlayerView};
+use crate::game::entity::card::{CardRef, CardView};
+use crate::game::entity::player::PlayerView;
 use crate::logger;
 use crate::models::game_action::GameAction;
-use crate::utils::errors::{CardRequestError, GameLogicError};
+use crate::utils::errors::GameLogicError;
+use crate::utils::canonical;
 use crate::utils::logger::Logger;
 use std::{collections::HashMap, sync::Arc};
 use serde::Serialize;
 use tokio::sync::RwLock;
-use crate::game::lua_context::LuaContext;
-use crate::models::client_requests::PlayCardRequest;
-use crate::tcp::client::Client;
-use crate::tcp::server::ServerInstance;
+use crate::game::board::{self, Zone};
+use crate::game::graveyard;
+use crate::game::control::{self, ControlChange, ControlTracker, Transfer};
+use crate::game::checkpoint::Checkpoint;
+use crate::game::combat::CombatTracker;
+use crate::game::cooldown::CooldownTracker;
+use crate::game::event_log::{GameEventKind, GameEventLog};
+use crate::game::variables::MatchVariables;
+use crate::utils::replay::{ReplayRecord, ReplayWriter};
+use crate::game::rng::{MatchRng, RandomDraw};
+use crate::game::highlights::{HighlightDetector, HighlightSnapshot};
+use crate::game::prompt::PromptManager;
+use crate::game::redaction::{self, PublicPlayerView};
+use crate::game::rules::RulesProfile;
+use crate::game::seating::Seating;
+use crate::game::status_effect::{self, StatusEffect};
+use crate::game::think_time::{ThinkTime, ThinkTimeTracker};
+use crate::game::undo::UndoJournal;
+use crate::models::ids::{CardDefId, CardInstanceId, PlayerId};
+use crate::SETTINGS;
+use chrono::Utc;
+
+/// A turn that just started.
+pub struct TurnStart {
+    pub player_id: PlayerId,        // The player whose turn it is.
+    pub deaths: Vec<GameEventKind>, // Creatures poison killed at the turn boundary.
+}
 
 pub struct GameState {
     pub rounds: u32,
-    pub red_first: bool,
-    pub red_player: String,
-    pub blue_player: String,
+    pub active_player: Option<PlayerId>, // The player whose turn it is, once the first turn started.
+    pub seating: Seating,                // The players in seat order and their teams.
+    pub forfeited: Vec<PlayerId>,        // Players out of the match for not reconnecting in time.
     pub ongoing: Arc<RwLock<bool>>,
-    pub player_views: Arc<RwLock<HashMap<String, Arc<RwLock<PlayerView>>>>>
+    pub player_views: Arc<RwLock<HashMap<PlayerId, Arc<RwLock<PlayerView>>>>>,
+    pub highlights: Arc<RwLock<HighlightDetector>>, // Replay bookmarks computed from applied actions.
+    pub prompts: Arc<RwLock<PromptManager>>,        // Decisions waiting on a player's answer.
+    pub control: Arc<RwLock<ControlTracker>>,       // Control changes to revert when they expire.
+    pub events: Arc<RwLock<GameEventLog>>,          // History of the resolved actions.
+    pub variables: Arc<RwLock<MatchVariables>>,     // Values card scripts keep across turns.
+    pub replay: Option<Arc<ReplayWriter>>,          // Records the match when replays are enabled.
+    pub think_time: Arc<RwLock<ThinkTimeTracker>>,  // Time each player spends on their turns.
+    pub cooldowns: Arc<RwLock<CooldownTracker>>,    // Activations left for the abilities of cards.
+    pub card_instances: HashMap<CardInstanceId, CardView>, // Every card of the match, as instantiated.
+    pub rules: RulesProfile,                        // Rules specific to the kind of match.
+    pub undo: Arc<RwLock<UndoJournal>>,             // The last play, while it can still be undone.
+    pub combat: Arc<RwLock<CombatTracker>>,         // The attack waiting on blockers, and the attackers of the turn.
+    pub rng: Arc<MatchRng>,                         // Source of every random value, rewound on rollback.
 }
 
 impl GameState {
-    pub fn new_game(views: HashMap<String, Arc<RwLock<PlayerView>>>) -> Self {
+    pub fn new_game(views: HashMap<PlayerId, Arc<RwLock<PlayerView>>>) -> Self {
         Self {
             rounds: 0,
-            red_first: true,
-            red_player: String::new(),
-            blue_player: String::new(),
+            active_player: None,
+            forfeited: Vec::new(),
+            seating: Seating::default(),
             player_views: Arc::new(RwLock::new(views)),
             ongoing: Arc::new(RwLock::new(true)),
+            highlights: Arc::new(RwLock::new(HighlightDetector::default())),
+            prompts: Arc::new(RwLock::new(PromptManager::default())),
+            control: Arc::new(RwLock::new(ControlTracker::default())),
+            events: Arc::new(RwLock::new(GameEventLog::default())),
+            variables: Arc::new(RwLock::new(MatchVariables::default())),
+            replay: None,
+            think_time: Arc::new(RwLock::new(ThinkTimeTracker::default())),
+            cooldowns: Arc::new(RwLock::new(CooldownTracker::default())),
+            card_instances: HashMap::new(),
+            rules: RulesProfile::default(),
+            undo: Arc::new(RwLock::new(UndoJournal::default())),
+            combat: Arc::new(RwLock::new(CombatTracker::default())),
+            rng: Arc::new(MatchRng::new(0)),
+        }
+    }
+
+    /// Keeps the views of the cards of the match as they were instantiated, so cards coming back
+    /// to a hand start over from them.
+    pub fn with_card_instances(
+        mut self,
+        card_instances: HashMap<CardInstanceId, CardView>,
+    ) -> Self {
+        self.card_instances = card_instances;
+        self
+    }
+
+    /// Plays the match with the rules of its kind.
+    pub fn with_rules(mut self, rules: RulesProfile) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Records the resolved actions, and the packets that led to them, into a replay.
+    pub fn with_replay(mut self, replay: Option<Arc<ReplayWriter>>) -> Self {
+        self.replay = replay;
+        self
+    }
+
+    /// Draws the random values of the match from `rng`, so checkpoints can rewind it.
+    pub fn with_rng(mut self, rng: Arc<MatchRng>) -> Self {
+        self.rng = rng;
+        self
+    }
+
+    /// Records the random draws of a card script into the replay, if replays are enabled.
+    pub fn record_draws(&self, draws: Vec<RandomDraw>) {
+        if let Some(replay) = &self.replay {
+            for draw in draws {
+                replay.record(self.rounds, ReplayRecord::Draw { draw });
+            }
+        }
+    }
+
+    /// The player taking the first turn of the match.
+    pub fn first_player(&self) -> Option<&PlayerId> {
+        self.seating.first_player()
+    }
+
+    /// The opponent of a player: in a team match, the opponent seated after them.
+    pub fn opponent_of(&self, player_id: &PlayerId) -> Option<&PlayerId> {
+        self.seating.next_seat(player_id)
+    }
+
+    /// Deals damage to a player, and to the rest of their team when teammates share their health.
+    /// The shield of each player hit absorbs what it can first.
+    pub async fn damage_player(&self, player_id: &PlayerId, amount: i32) {
+        let player_views = self.player_views.read().await;
+        for target in self.health_pool(player_id) {
+            if let Some(view) = player_views.get(target) {
+                let mut view = view.write().await;
+                let left = status_effect::absorb(&mut view.status_effects, amount.max(0) as u32);
+                view.health = view.health.saturating_sub(left as i32);
+            }
+        }
+    }
+
+    /// The players whose health changes with the health of a player: the player, and the rest of
+    /// their team when teammates share their health.
+    fn health_pool<'a>(&'a self, player_id: &'a PlayerId) -> Vec<&'a PlayerId> {
+        let mut targets = vec![player_id];
+        if self.seating.shares_health() {
+            targets.extend(self.seating.teammates_of(player_id));
+        }
+        targets
+    }
+
+    /// Deals the damage of a card script to a player or to a creature on a board.
+    ///
+    /// # Returns
+    /// * `Ok(true)` - If the target is a creature left without health, which must die.
+    /// * `Ok(false)` - If the damage was dealt and the target survived.
+    /// * `Err(GameLogicError::InvalidTarget)` - If no player or creature matches the target.
+    pub async fn deal_damage(&self, target: &str, amount: u32) -> Result<bool, GameLogicError> {
+        let player_id = PlayerId::from(target);
+        if self.player_views.read().await.contains_key(&player_id) {
+            let amount = i32::try_from(amount).unwrap_or(i32::MAX);
+            self.damage_player(&player_id, amount).await;
+            return Ok(false);
+        }
+
+        self.damage_creature(&target.into(), amount)
+            .await
+            .ok_or_else(|| GameLogicError::InvalidTarget(target.to_string()))
+    }
+
+    /// Heals a player, or a creature on a board up to the health it was instantiated with.
+    ///
+    /// # Returns
+    /// `Err(GameLogicError::InvalidTarget)` if no player or creature matches the target.
+    pub async fn heal(&self, target: &str, amount: u32) -> Result<(), GameLogicError> {
+        let amount = i32::try_from(amount).unwrap_or(i32::MAX);
+        let player_views = self.player_views.read().await;
+        let player_id = PlayerId::from(target);
+        if player_views.contains_key(&player_id) {
+            for target in self.health_pool(&player_id) {
+                if let Some(view) = player_views.get(target) {
+                    let mut view = view.write().await;
+                    view.health = view.health.saturating_add(amount);
+                }
+            }
+            return Ok(());
+        }
+
+        let instance_id = CardInstanceId::from(target);
+        for view in player_views.values() {
+            let mut view = view.write().await;
+            if let Some(card) = view.board.cards.get_mut(&instance_id) {
+                let max = self
+                    .card_instances
+                    .get(&instance_id)
+                    .map_or(i32::MAX, |base| base.health.max(card.health));
+                card.health = card.health.saturating_add(amount).min(max);
+                return Ok(());
+            }
+        }
+
+        Err(GameLogicError::InvalidTarget(target.to_string()))
+    }
+
+    /// Deals damage to a creature on a board, once its shield absorbed what it can. The damage
+    /// stays on the board state of the creature until it leaves the board.
+    ///
+    /// # Returns
+    /// * `Some(true)` - If the creature has no health left and must die.
+    /// * `Some(false)` - If the creature survived.
+    /// * `None` - If the creature is on no board.
+    pub async fn damage_creature(&self, instance_id: &CardInstanceId, amount: u32) -> Option<bool> {
+        let player_views = self.player_views.read().await;
+        for view in player_views.values() {
+            let mut view = view.write().await;
+            if let Some(card) = view.board.cards.get_mut(instance_id) {
+                let left = status_effect::absorb(&mut card.status_effects, amount);
+                card.health = card
+                    .health
+                    .saturating_sub(i32::try_from(left).unwrap_or(i32::MAX));
+                return Some(card.health <= 0);
+            }
+        }
+        None
+    }
+
+    /// Captures the state before a play, if the rules of the match allow undoing plays.
+    pub async fn undo_checkpoint(&self) -> Option<Checkpoint> {
+        match self.rules.allows_undo() {
+            true => Some(Checkpoint::capture(self).await),
+            false => None,
+        }
+    }
+
+    /// Passes the turn to the next player in turn order who is not defeated, and starts their
+    /// turn on the next turn number. The first call starts the turn of the first player.
+    ///
+    /// # Returns
+    /// The turn that started, or `None` if no player is left to take it.
+    pub async fn advance_turn(&mut self) -> Option<TurnStart> {
+        let defeated = self.defeated_players().await;
+        let next = self
+            .seating
+            .next_turn(self.active_player.as_ref(), &defeated)?;
+
+        self.rounds += 1;
+        self.active_player = Some(next.clone());
+        self.events.write().await.start_turn(self.rounds);
+        let deaths = self.start_turn(&next).await;
+        Some(TurnStart {
+            player_id: next,
+            deaths,
+        })
+    }
+
+    /// Fails with `NotPlayerTurn` unless the turn being played is the turn of `player_id`.
+    pub fn check_turn(&self, player_id: &PlayerId) -> Result<(), GameLogicError> {
+        match self.active_player.as_ref() == Some(player_id) {
+            true => Ok(()),
+            false => Err(GameLogicError::NotPlayerTurn),
+        }
+    }
+
+    /// Starts the turn of a player: the last play of the previous turn can no longer be undone,
+    /// expired control effects end, creatures may attack again, poison deals its damage and
+    /// status effects count down, the per-turn abilities of the player are available again, then
+    /// the think time clock of the player starts on the current turn.
+    ///
+    /// # Returns
+    /// The deaths of the creatures poison killed.
+    async fn start_turn(&self, player_id: &PlayerId) -> Vec<GameEventKind> {
+        self.undo.write().await.clear();
+        self.revert_expired_control().await;
+        self.combat.write().await.start_turn();
+        let deaths = self.deal_poison_damage().await;
+        self.expire_status_effects().await;
+        self.cooldowns.write().await.start_turn(player_id);
+        self.refresh_remaining_uses(player_id).await;
+        self.think_time
+            .write()
+            .await
+            .start_turn(player_id, self.rounds);
+        deaths
+    }
+
+    /// Copies the activations a player has left into the views of the cards in their hand and on
+    /// their board.
+    pub async fn refresh_remaining_uses(&self, player_id: &PlayerId) {
+        let player_views = self.player_views.read().await;
+        let Some(view) = player_views.get(player_id) else {
+            return;
+        };
+
+        let cooldowns = self.cooldowns.read().await;
+        let mut view = view.write().await;
+        let view = &mut *view;
+        let cards = view
+            .current_hand
+            .iter_mut()
+            .flatten()
+            .chain(view.board.cards.values_mut());
+        for card in cards {
+            card.remaining_uses = cooldowns.remaining(player_id, &card.id);
         }
     }
 
+    /// Deals the damage of the poison on every player and on the creatures of their boards,
+    /// recording the damage, and sends the creatures it killed to the graveyard.
+    ///
+    /// # Returns
+    /// The deaths of the creatures poison killed.
+    async fn deal_poison_damage(&self) -> Vec<GameEventKind> {
+        let mut poisoned_players = Vec::new();
+        let mut poisoned_creatures = Vec::new();
+        {
+            let player_views = self.player_views.read().await;
+            let mut seats: Vec<_> = player_views.iter().collect();
+            seats.sort_by_key(|(player_id, _)| *player_id);
+            for (player_id, view) in seats {
+                let view = view.read().await;
+                let damage = status_effect::poison(&view.status_effects);
+                if damage > 0 {
+                    poisoned_players.push((player_id.clone(), damage));
+                }
+                for (instance_id, card) in &view.board.cards {
+                    let damage = status_effect::poison(&card.status_effects);
+                    if damage > 0 {
+                        poisoned_creatures.push((instance_id.clone(), damage));
+                    }
+                }
+            }
+        }
+
+        for (player_id, amount) in poisoned_players {
+            self.damage_player(&player_id, amount as i32).await;
+            self.record_event(GameEventKind::DamageDealt {
+                target: player_id.to_string(),
+                amount,
+            })
+            .await;
+        }
+
+        let mut deaths = Vec::new();
+        for (instance_id, amount) in poisoned_creatures {
+            let died = self.damage_creature(&instance_id, amount).await;
+            self.record_event(GameEventKind::DamageDealt {
+                target: instance_id.to_string(),
+                amount,
+            })
+            .await;
+            if died != Some(true) {
+                continue;
+            }
+            if let Ok(event) = self.destroy_card(&instance_id).await {
+                self.record_event(event.clone()).await;
+                deaths.push(event);
+            }
+        }
+        deaths
+    }
+
+    /// Counts down the status effects of every player and of the cards in their hands and on
+    /// their boards, recording the effects that expired.
+    async fn expire_status_effects(&self) {
+        let mut expired = Vec::new();
+        {
+            let player_views = self.player_views.read().await;
+            for view in player_views.values() {
+                let mut view = view.write().await;
+                let player_id = view.id.to_string();
+                for effect in status_effect::tick(&mut view.status_effects) {
+                    expired.push((player_id.clone(), effect.kind));
+                }
+
+                for card in view.current_hand.iter_mut().flatten() {
+                    for effect in status_effect::tick(&mut card.status_effects) {
+                        expired.push((card.instance_id.to_string(), effect.kind));
+                    }
+                }
+
+                for card in view.board.cards.values_mut() {
+                    for effect in status_effect::tick(&mut card.status_effects) {
+                        expired.push((card.instance_id.to_string(), effect.kind));
+                    }
+                }
+            }
+        }
+
+        for (target, kind) in expired {
+            self.record_event(GameEventKind::StatusEffectExpired { target, kind })
+                .await;
+        }
+    }
+
+    /// Attaches a status effect to a player, or to a card in a player's hand or on their board.
+    ///
+    /// # Returns
+    /// `false` if no player or card matches the target.
+    pub async fn apply_status_effect(&self, target: &str, effect: StatusEffect) -> bool {
+        let player_views = self.player_views.read().await;
+        for view in player_views.values() {
+            let mut view = view.write().await;
+            if view.id.to_string() == target {
+                status_effect::apply(&mut view.status_effects, effect);
+                return true;
+            }
+
+            let card = view
+                .current_hand
+                .iter_mut()
+                .flatten()
+                .find(|card| card.instance_id.to_string() == target);
+            if let Some(card) = card {
+                status_effect::apply(&mut card.status_effects, effect);
+                return true;
+            }
+
+            let card = view
+                .board
+                .cards
+                .values_mut()
+                .find(|card| card.instance_id.to_string() == target);
+            if let Some(card) = card {
+                status_effect::apply(&mut card.status_effects, effect);
+                return true;
+            }
+        }
+
+        false
+    }
+
     /// Wraps the game state into a byte array for transmission or storage.
     pub fn wrap_game_state(&self) -> Box<[u8]> {
         Box::new(b"Pretend this is the wrapped game state".to_owned())
     }
 
-    pub async fn apply_actions(&self, actions: Vec<GameAction>) {}
+    /// Applies the actions returned by a script of the card `source`, recording an event for each.
+    ///
+    /// # Returns
+    /// The recorded events, from which the triggers the actions set off are found.
+    pub async fn apply_actions(
+        &self,
+        source: &CardDefId,
+        actions: Vec<GameAction>,
+    ) -> Vec<GameEventKind> {
+        self.revert_expired_control().await;
+        let mut applied = Vec::new();
+        for action in actions {
+            if let Some(replay) = &self.replay {
+                let record = ReplayRecord::Action {
+                    source: source.clone(),
+                    action: action.clone(),
+                };
+                replay.record(self.rounds, record);
+            }
+
+            // Summons and draws are only recorded in the event log: the server keeps no library
+            // to draw from, nor the definitions of cards outside the decks of the match.
+            let event = match action {
+                GameAction::DealDamage { target, amount } => {
+                    let died = match self.deal_damage(&target, amount).await {
+                        Ok(died) => died,
+                        Err(error) => {
+                            logger!(DEBUG, "[GAME STATE] Cannot damage `{target}`: {error}");
+                            continue;
+                        }
+                    };
+                    let dealt = GameEventKind::DamageDealt {
+                        target: target.clone(),
+                        amount,
+                    };
+                    if !died {
+                        dealt
+                    } else {
+                        self.record_event(dealt.clone()).await;
+                        applied.push(dealt);
+                        match self.destroy_card(&target.as_str().into()).await {
+                            Ok(event) => event,
+                            Err(error) => {
+                                logger!(DEBUG, "[GAME STATE] Cannot destroy `{target}`: {error}");
+                                continue;
+                            }
+                        }
+                    }
+                }
+                GameAction::Heal { target, amount } => {
+                    if let Err(error) = self.heal(&target, amount).await {
+                        logger!(DEBUG, "[GAME STATE] Cannot heal `{target}`: {error}");
+                        continue;
+                    }
+                    GameEventKind::Healed { target, amount }
+                }
+                GameAction::Summon { id, position } => GameEventKind::Summoned {
+                    card_id: id.into(),
+                    position,
+                },
+                GameAction::DrawCard { player, count } => GameEventKind::CardsDrawn {
+                    player_id: player.into(),
+                    count,
+                },
+                GameAction::ChangeController {
+                    target,
+                    controller,
+                    turns,
+                } => {
+                    let transfer = self
+                        .change_controller(&target.into(), &controller.into(), turns)
+                        .await;
+                    logger!(DEBUG, "[GAME STATE] Control change: {transfer:?}");
+                    continue;
+                }
+                GameAction::SetVariable { key, value } => {
+                    self.variables.write().await.set(source, key, value);
+                    continue;
+                }
+                GameAction::DestroyCard { target } => {
+                    match self.destroy_card(&target.as_str().into()).await {
+                        Ok(event) => event,
+                        Err(error) => {
+                            logger!(DEBUG, "[GAME STATE] Cannot destroy `{target}`: {error}");
+                            continue;
+                        }
+                    }
+                }
+                GameAction::DiscardCard { target } => {
+                    match self.discard_card(&target.as_str().into()).await {
+                        Ok(event) => event,
+                        Err(error) => {
+                            logger!(DEBUG, "[GAME STATE] Cannot discard `{target}`: {error}");
+                            continue;
+                        }
+                    }
+                }
+                GameAction::ResurrectCard { target, position } => {
+                    let resurrected = self
+                        .resurrect_card(&target.as_str().into(), position.as_deref())
+                        .await;
+                    match resurrected {
+                        Ok(event) => event,
+                        Err(error) => {
+                            logger!(DEBUG, "[GAME STATE] Cannot resurrect `{target}`: {error}");
+                            continue;
+                        }
+                    }
+                }
+                GameAction::ReturnFromGraveyard { target } => {
+                    match self.return_from_graveyard(&target.as_str().into()).await {
+                        Ok(event) => event,
+                        Err(error) => {
+                            logger!(DEBUG, "[GAME STATE] Cannot return `{target}`: {error}");
+                            continue;
+                        }
+                    }
+                }
+                GameAction::ApplyStatusEffect {
+                    target,
+                    kind,
+                    magnitude,
+                    turns,
+                } => {
+                    let effect = StatusEffect {
+                        kind,
+                        magnitude,
+                        turns,
+                    };
+                    if !self.apply_status_effect(&target, effect).await {
+                        logger!(DEBUG, "[GAME STATE] No status effect target `{target}`");
+                        continue;
+                    }
+                    GameEventKind::StatusEffectApplied { target, kind }
+                }
+                GameAction::PlayCinematic { name, duration_ms } => GameEventKind::CinematicPlayed {
+                    card_id: source.clone(),
+                    name,
+                    duration_ms,
+                },
+            };
+
+            self.record_event(event.clone()).await;
+            applied.push(event);
+        }
+
+        self.record_highlights().await;
+        applied
+    }
+
+    /// Sends a card on a board to its owner's graveyard.
+    ///
+    /// # Returns
+    /// * `Ok(GameEventKind)` - The event to record: a creature died or a card was destroyed.
+    /// * `Err(GameLogicError)` - If the card is on no board.
+    pub async fn destroy_card(
+        &self,
+        instance_id: &CardInstanceId,
+    ) -> Result<GameEventKind, GameLogicError> {
+        let player_views = self.player_views.read().await;
+        for (holder_id, view) in player_views.iter() {
+            let (card, zone, owner_id) = {
+                let mut holder = view.write().await;
+                let Some((placement, stack)) = board::find(&holder, instance_id) else {
+                    continue;
+                };
+                let owner_id = stack.owner_or(holder_id).clone();
+                let card = board::remove(&mut holder, placement.zone, placement.slot, instance_id);
+                match card {
+                    Some(card) => (card, placement.zone, owner_id),
+                    None => continue,
+                }
+            };
+
+            let card_id = card.id.clone();
+            if let Some(owner) = player_views.get(&owner_id) {
+                graveyard::bury(&mut *owner.write().await, card, zone.card_type());
+            }
+
+            return Ok(match zone {
+                Zone::Creatures => GameEventKind::CreatureDied {
+                    card_id,
+                    instance_id: instance_id.clone(),
+                    owner_id,
+                },
+                _ => GameEventKind::CardDestroyed { card_id, owner_id },
+            });
+        }
+
+        Err(GameLogicError::CardNotOnBoard(instance_id.to_string()))
+    }
+
+    /// Sends a card from a hand to its owner's graveyard.
+    ///
+    /// # Returns
+    /// * `Ok(GameEventKind)` - The discard event to record.
+    /// * `Err(GameLogicError)` - If the card is in no hand.
+    pub async fn discard_card(
+        &self,
+        instance_id: &CardInstanceId,
+    ) -> Result<GameEventKind, GameLogicError> {
+        let player_views = self.player_views.read().await;
+        for view in player_views.values() {
+            let Some(card) = view.write().await.take_from_hand(instance_id) else {
+                continue;
+            };
+
+            let buried = CardRef {
+                id: card.id.clone(),
+                amount: 1,
+                owner_id: None,
+                instances: vec![instance_id.clone()],
+            };
+            if let Some(owner) = player_views.get(&card.owner_id) {
+                graveyard::bury(&mut *owner.write().await, buried, card.card_type);
+            }
+
+            return Ok(GameEventKind::CardDiscarded {
+                card_id: card.id,
+                owner_id: card.owner_id,
+            });
+        }
+
+        Err(GameLogicError::CardNotInHand(instance_id.to_string()))
+    }
+
+    /// Puts a card from a graveyard back onto its owner's board.
+    ///
+    /// # Arguments
+    /// * `instance_id` - The card to bring back.
+    /// * `position` - The board position, as for a played card; the first free slot if `None`.
+    ///
+    /// # Returns
+    /// * `Ok(GameEventKind)` - The resurrection event to record.
+    /// * `Err(GameLogicError)` - If the card is in no graveyard, is a spell, or the position is
+    ///   invalid.
+    pub async fn resurrect_card(
+        &self,
+        instance_id: &CardInstanceId,
+        position: Option<&str>,
+    ) -> Result<GameEventKind, GameLogicError> {
+        let player_views = self.player_views.read().await;
+        for view in player_views.values() {
+            let mut view = view.write().await;
+            let Some((card_id, card_type)) =
+                graveyard::find(&view, instance_id).map(|(card, t)| (card.id.clone(), t))
+            else {
+                continue;
+            };
+
+            let placement = board::plan(&view, &card_id, card_type, position)?
+                .ok_or_else(|| GameLogicError::NotAPermanent(card_type.to_string()))?;
+            // The card comes back as it was when the match started, its damage and effects gone.
+            let card = self
+                .card_instances
+                .get(instance_id)
+                .ok_or_else(|| GameLogicError::NotInGraveyard(instance_id.to_string()))?;
+            graveyard::exhume(&mut view, instance_id);
+            board::place(&mut view, card, placement);
+            return Ok(GameEventKind::CardResurrected {
+                card_id,
+                owner_id: view.id.clone(),
+            });
+        }
+
+        Err(GameLogicError::NotInGraveyard(instance_id.to_string()))
+    }
+
+    /// Returns a card from a graveyard to its owner's hand, as it was when the match started.
+    ///
+    /// # Returns
+    /// * `Ok(GameEventKind)` - The event to record.
+    /// * `Err(GameLogicError)` - If the card is in no graveyard or the hand is full.
+    pub async fn return_from_graveyard(
+        &self,
+        instance_id: &CardInstanceId,
+    ) -> Result<GameEventKind, GameLogicError> {
+        let not_found = || GameLogicError::NotInGraveyard(instance_id.to_string());
+        let mut card_view = self
+            .card_instances
+            .get(instance_id)
+            .cloned()
+            .ok_or_else(not_found)?;
+        card_view.in_hand = true;
+
+        let player_views = self.player_views.read().await;
+        for view in player_views.values() {
+            let mut view = view.write().await;
+            if graveyard::find(&view, instance_id).is_none() {
+                continue;
+            }
+
+            let slot = view
+                .current_hand
+                .iter()
+                .position(Option::is_none)
+                .ok_or_else(|| GameLogicError::HandFull(view.id.to_string()))?;
+            graveyard::exhume(&mut view, instance_id);
+            let event = GameEventKind::CardReturnedToHand {
+                card_id: card_view.id.clone(),
+                owner_id: view.id.clone(),
+            };
+            view.current_hand[slot] = Some(card_view);
+            view.hand_size += 1;
+            return Ok(event);
+        }
+
+        Err(not_found())
+    }
+
+    /// Moves one copy of a creature from another player's board to the board of `controller_id`.
+    ///
+    /// # Arguments
+    /// * `instance_id` - The copy of the creature to take control of.
+    /// * `controller_id` - The player taking control.
+    /// * `turns` - If set, the creature goes back to its owner once the game reaches
+    ///   `rounds + turns`; otherwise the change is permanent.
+    pub async fn change_controller(
+        &self,
+        instance_id: &CardInstanceId,
+        controller_id: &PlayerId,
+        turns: Option<u32>,
+    ) -> Transfer {
+        let player_views = self.player_views.read().await;
+        if !player_views.contains_key(controller_id) {
+            return Transfer::NotOnBoard;
+        }
+
+        let mut holder = None;
+        for (player_id, view) in player_views.iter().filter(|(id, _)| *id != controller_id) {
+            if let Some((_, card)) = board::find(&*view.read().await, instance_id) {
+                holder = Some((player_id, card.id.clone(), card.owner_or(player_id).clone()));
+                break;
+            }
+        }
+        let Some((holder_id, card_id, owner_id)) = holder else {
+            return Transfer::NotOnBoard;
+        };
+
+        let transfer =
+            Self::transfer_between(&player_views, holder_id, controller_id, instance_id).await;
+        drop(player_views);
+        self.record_transfer(&transfer, &card_id, instance_id, controller_id, &owner_id)
+            .await;
+        if let (Transfer::Moved(_), Some(turns)) = (&transfer, turns) {
+            if &owner_id != controller_id {
+                self.control.write().await.record(ControlChange {
+                    owner_id,
+                    card_id,
+                    instance_id: instance_id.clone(),
+                    controller_id: controller_id.clone(),
+                    expires_on_turn: self.rounds + turns,
+                });
+            }
+        }
+
+        transfer
+    }
+
+    /// Hands the creatures whose control effect expired back to their owners.
+    async fn revert_expired_control(&self) {
+        let expired = self.control.write().await.take_expired(self.rounds);
+        for change in expired {
+            let transfer = {
+                let player_views = self.player_views.read().await;
+                Self::transfer_between(
+                    &player_views,
+                    &change.controller_id,
+                    &change.owner_id,
+                    &change.instance_id,
+                )
+                .await
+            };
+            self.record_transfer(
+                &transfer,
+                &change.card_id,
+                &change.instance_id,
+                &change.owner_id,
+                &change.owner_id,
+            )
+            .await;
+            logger!(
+                DEBUG,
+                "[GAME STATE] Control of `{}` expired: {transfer:?}",
+                change.card_id
+            );
+        }
+    }
+
+    /// Moves a creature from the board of `from_id` to the board of `to_id`.
+    ///
+    /// Control changes lock the two views in player id order, whichever way the creature goes,
+    /// and no other lock is taken while they are held, so a change and a revert running at once
+    /// cannot deadlock.
+    async fn transfer_between(
+        player_views: &HashMap<PlayerId, Arc<RwLock<PlayerView>>>,
+        from_id: &PlayerId,
+        to_id: &PlayerId,
+        instance_id: &CardInstanceId,
+    ) -> Transfer {
+        let (Some(from), Some(to)) = (player_views.get(from_id), player_views.get(to_id)) else {
+            return Transfer::NotOnBoard;
+        };
+        if from_id == to_id {
+            return Transfer::NotOnBoard;
+        }
+
+        let (mut from, mut to) = match from_id < to_id {
+            true => {
+                let from = from.write().await;
+                (from, to.write().await)
+            }
+            false => {
+                let to = to.write().await;
+                (from.write().await, to)
+            }
+        };
+        control::transfer_creature(&mut from, &mut to, instance_id)
+    }
+
+    /// Appends an event to the event log, on the current turn.
+    pub async fn record_event(&self, kind: GameEventKind) -> u64 {
+        self.events.write().await.record(self.rounds, kind)
+    }
+
+    /// Records the outcome of a creature changing controller.
+    async fn record_transfer(
+        &self,
+        transfer: &Transfer,
+        card_id: &CardDefId,
+        instance_id: &CardInstanceId,
+        controller_id: &PlayerId,
+        owner_id: &PlayerId,
+    ) {
+        let event = match transfer {
+            Transfer::Moved(_) => GameEventKind::ControlChanged {
+                card_id: card_id.clone(),
+                controller_id: controller_id.clone(),
+            },
+            Transfer::Destroyed => GameEventKind::CreatureDied {
+                card_id: card_id.clone(),
+                instance_id: instance_id.clone(),
+                owner_id: owner_id.clone(),
+            },
+            Transfer::NotOnBoard => return,
+        };
+
+        self.record_event(event).await;
+    }
+
+    /// Clones the current view of every player, so it can be restored if an atomic operation fails.
+    pub async fn snapshot_views(&self) -> HashMap<PlayerId, PlayerView> {
+        let player_views = self.player_views.read().await;
+        let mut snapshot = HashMap::with_capacity(player_views.len());
+        for (player_id, view) in player_views.iter() {
+            snapshot.insert(player_id.clone(), view.read().await.clone());
+        }
+
+        snapshot
+    }
+
+    /// Restores player views previously taken with `snapshot_views`.
+    pub async fn restore_views(&self, snapshot: HashMap<PlayerId, PlayerView>) {
+        let player_views = self.player_views.read().await;
+        for (player_id, view) in snapshot {
+            if let Some(current) = player_views.get(&player_id) {
+                *current.write().await = view;
+            }
+        }
+    }
+
+    /// Lists the players whose health dropped to zero or below, and the players who forfeited.
+    pub async fn defeated_players(&self) -> Vec<PlayerId> {
+        let player_views = self.player_views.read().await;
+        let mut defeated = Vec::new();
+        for (player_id, view) in player_views.iter() {
+            if view.read().await.health <= 0 || self.forfeited.contains(player_id) {
+                defeated.push(player_id.clone());
+            }
+        }
+
+        defeated
+    }
+
+    /// Hashes the turn, every player view and the match variables with the canonical
+    /// serialization, so two servers (or a server and a replay) holding the same state always get
+    /// the same hash.
+    pub async fn state_hash(&self) -> Result<String, serde_cbor::Error> {
+        let variables = self.variables.read().await.clone();
+        canonical::canonical_hash(&(self.rounds, self.snapshot_views().await, variables))
+    }
+
+    /// Builds the full view of the match handed to Lua scripts, the players in seat order.
+    pub async fn private_view(&self) -> PrivateGameStateView {
+        let player_views = self.player_views.read().await;
+        let mut seats: Vec<_> = self.seating.seats().iter().collect();
+        if seats.len() < 2 {
+            seats = player_views.keys().collect();
+        }
+        let mut views = Vec::with_capacity(seats.len());
+        for player_id in seats {
+            views.push(player_views[player_id].read().await.clone());
+        }
+        let mut views = views.into_iter();
+        let mut next_view = || {
+            views
+                .next()
+                .unwrap_or_else(|| PlayerView::from_player(&PlayerId::default(), 0))
+        };
+        let red_player = next_view();
+        let blue_player = next_view();
+
+        PrivateGameStateView {
+            red_player,
+            blue_player,
+            partners: views.collect(),
+            turn: self.rounds,
+        }
+    }
+
+    /// Builds the view of the match streamed to spectators, without any hidden information.
+    ///
+    /// Think times are only included when `THINK_TIME_VISIBILITY` shows them to opponents.
+    pub async fn public_view(&self) -> PublicGameStateView {
+        let private_view = self.private_view().await;
+        let visibility = SETTINGS
+            .get()
+            .map(|s| s.think_time_visibility)
+            .unwrap_or_default();
+        let (red_think_time, blue_think_time) = match visibility.shows_opponent() {
+            true => {
+                let think_time = self.think_time.read().await;
+                (
+                    Some(think_time.think_time(&private_view.red_player.id)),
+                    Some(think_time.think_time(&private_view.blue_player.id)),
+                )
+            }
+            false => (None, None),
+        };
+
+        PublicGameStateView {
+            red_think_time,
+            blue_think_time,
+            ..redaction::redact_state(&private_view)
+        }
+    }
+
+    /// Builds the view of the match sent to one player: their own view in full and only the
+    /// public part of their opponent's, and in a team match of their teammates' and opponents'.
+    ///
+    /// Think times are included as allowed by `THINK_TIME_VISIBILITY`.
+    ///
+    /// # Returns
+    /// `None` if the player is not part of the match.
+    pub async fn player_view(&self, player_id: &PlayerId) -> Option<PlayerGameStateView> {
+        let player_views = self.player_views.read().await;
+        let player = player_views.get(player_id)?.read().await.clone();
+        let opponent_id = match self.opponent_of(player_id) {
+            Some(opponent_id) => opponent_id,
+            None => player_views.keys().find(|id| *id != player_id)?,
+        };
+        let opponent = redaction::redact(&*player_views.get(opponent_id)?.read().await);
+
+        let (mut teammates, mut opponents) = (Vec::new(), Vec::new());
+        if self.seating.is_team_match() {
+            for (ids, views) in [
+                (self.seating.teammates_of(player_id), &mut teammates),
+                (self.seating.opponents_of(player_id), &mut opponents),
+            ] {
+                for id in ids {
+                    if let Some(view) = player_views.get(id) {
+                        views.push(redaction::redact(&*view.read().await));
+                    }
+                }
+            }
+        }
+
+        let visibility = SETTINGS
+            .get()
+            .map(|s| s.think_time_visibility)
+            .unwrap_or_default();
+        let think_time = self.think_time.read().await;
+        let opponent_think_time = visibility
+            .shows_opponent()
+            .then(|| think_time.think_time(&opponent.id));
+
+        Some(PlayerGameStateView {
+            think_time: visibility
+                .shows_own()
+                .then(|| think_time.think_time(player_id)),
+            opponent_think_time,
+            turn: self.rounds,
+            player,
+            opponent,
+            teammates,
+            opponents,
+        })
+    }
+
+    /// Builds the snapshot a player resynchronizes from: its view of the match along with the
+    /// turn being played and its timer.
+    ///
+    /// The time taken by the running turn is only included when the think time of its player is
+    /// visible to `player_id`.
+    ///
+    /// # Returns
+    /// `None` if the player is not part of the match.
+    pub async fn resync_snapshot(&self, player_id: &PlayerId) -> Option<ResyncSnapshot> {
+        let state = self.player_view(player_id).await?;
+        let running = self.think_time.read().await.running_turn();
+        let last_event = self.events.read().await.last_sequence();
+
+        let visibility = SETTINGS
+            .get()
+            .map(|s| s.think_time_visibility)
+            .unwrap_or_default();
+        let turn_elapsed_millis = running
+            .as_ref()
+            .filter(|turn| match &turn.player_id == player_id {
+                true => visibility.shows_own(),
+                false => visibility.shows_opponent(),
+            })
+            .map(|turn| turn.millis);
+
+        Some(ResyncSnapshot {
+            active_player: running.map(|turn| turn.player_id),
+            turn_elapsed_millis,
+            last_event,
+            server_time: Utc::now().timestamp_millis(),
+            state,
+        })
+    }
+
+    /// Runs the replay bookmark heuristics against the current state of every player.
+    async fn record_highlights(&self) {
+        let player_views = self.player_views.read().await;
+        let mut views = Vec::with_capacity(player_views.len());
+        for view in player_views.values() {
+            views.push(view.read().await.clone());
+        }
+
+        let snapshot = HighlightSnapshot::from_views(&views);
+        let mut highlights = self.highlights.write().await;
+        let added = highlights.observe(self.rounds, snapshot);
+        if added > 0 {
+            logger!(DEBUG, "[GAME STATE] Bookmarked {added} highlight(s) on turn {}", self.rounds);
+        }
+    }
 }
 
 #[derive(Serialize, Clone)]
@@ -46,6 +1093,34 @@ pub struct PrivateGameStateView {
     pub turn: u32,
     pub red_player: PlayerView,
     pub blue_player: PlayerView,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub partners: Vec<PlayerView>, // The other seats of a team match, in seat order.
+}
+
+#[derive(Serialize, Clone)]
+pub struct PlayerGameStateView {
+    pub turn: u32,
+    pub player: PlayerView,
+    pub opponent: PublicPlayerView, // The opponent seated after the player.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub teammates: Vec<PublicPlayerView>, // The other players of the team, in a team match.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub opponents: Vec<PublicPlayerView>, // Every player of the other team, in a team match.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub think_time: Option<ThinkTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opponent_think_time: Option<ThinkTime>,
+}
+
+/// Payload of a `ResyncResponse`, replacing whatever the client missed while disconnected.
+#[derive(Serialize, Clone)]
+pub struct ResyncSnapshot {
+    pub state: PlayerGameStateView,       // The view of the match the player is allowed to see.
+    pub active_player: Option<PlayerId>,  // Whose turn is being played, if the match started.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub turn_elapsed_millis: Option<u64>, // Time taken by the running turn, when visible.
+    pub last_event: u64,                  // Sequence of the latest event, for `GetHistory`.
+    pub server_time: i64,                 // Unix timestamp in milliseconds of the snapshot.
 }
 
 #[derive(Serialize, Clone)]
@@ -53,4 +1128,280 @@ pub struct PublicGameStateView {
     pub turn: u32,
     pub red_player: PublicPlayerView,
     pub blue_player: PublicPlayerView,
-}
\ No newline at end of file
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub partners: Vec<PublicPlayerView>, // The other seats of a team match, in seat order.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub red_think_time: Option<ThinkTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blue_think_time: Option<ThinkTime>,
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::board::Placement;
+    use crate::game::cooldown::ActivationLimit;
+    use crate::game::match_format::TeamHealth;
+    use crate::game::status_effect::StatusKind;
+    use crate::test_support::fixtures::sample_card;
+
+    fn game_state(players: &[&str]) -> GameState {
+        let seats: Vec<PlayerId> = players.iter().map(|id| PlayerId::from(*id)).collect();
+        let views = seats
+            .iter()
+            .map(|id| {
+                (
+                    id.clone(),
+                    Arc::new(RwLock::new(PlayerView::from_player(id, 30))),
+                )
+            })
+            .collect();
+        let mut game_state = GameState::new_game(views);
+        game_state.seating = Seating::new(seats, TeamHealth::Separate);
+        game_state
+    }
+
+    #[tokio::test]
+    async fn test_turns_go_around_the_table() {
+        let (red, blue) = (PlayerId::from("red"), PlayerId::from("blue"));
+        let mut game_state = game_state(&["red", "blue"]);
+        let wolf = CardInstanceId::nth(7);
+
+        let turn = game_state.advance_turn().await.map(|turn| turn.player_id);
+        assert_eq!(Some(red.clone()), turn);
+        assert_eq!(
+            (1, Some(&red)),
+            (game_state.rounds, game_state.active_player.as_ref())
+        );
+        assert!(game_state.check_turn(&red).is_ok());
+        assert!(game_state.check_turn(&blue).is_err());
+        game_state
+            .combat
+            .write()
+            .await
+            .record_attackers(std::slice::from_ref(&wolf));
+
+        // Creatures may attack again on the next turn, logged even if nothing happens in it.
+        let turn = game_state.advance_turn().await.map(|turn| turn.player_id);
+        assert_eq!(Some(blue.clone()), turn);
+        assert_eq!(
+            (2, Some(&blue)),
+            (game_state.rounds, game_state.active_player.as_ref())
+        );
+        let combat = game_state.combat.read().await;
+        assert!(combat.check_attackers(&[wolf]).is_ok());
+        drop(combat);
+        let events = game_state.events.read().await.turn(2);
+        assert_eq!(GameEventKind::TurnChanged { turn: 2 }, events[0].kind);
+
+        let turn = game_state.advance_turn().await.map(|turn| turn.player_id);
+        assert_eq!((Some(red), 3), (turn, game_state.rounds));
+    }
+
+    #[tokio::test]
+    async fn test_control_ends_when_its_last_turn_is_over() {
+        let (red, blue) = (PlayerId::from("red"), PlayerId::from("blue"));
+        let mut game_state = game_state(&["red", "blue"]);
+        let wolf = CardInstanceId::nth(7);
+        {
+            let player_views = game_state.player_views.read().await;
+            let mut card = CardView::create_view(&sample_card("wolf"), blue.clone(), wolf.clone());
+            card.health = 1;
+            let placement = Placement {
+                zone: Zone::Creatures,
+                slot: 0,
+            };
+            board::place(&mut *player_views[&blue].write().await, &card, placement);
+        }
+
+        game_state.rounds = 1;
+        let moved = game_state.change_controller(&wolf, &red, Some(1)).await;
+        assert_eq!(Transfer::Moved(0), moved);
+        let views = game_state.snapshot_views().await;
+        assert_eq!(red, views[&red].board.cards[&wolf].controller_id);
+
+        // Control holds until the turn it expires on starts, then the creature goes back as it was.
+        game_state.start_turn(&red).await;
+        let views = game_state.snapshot_views().await;
+        assert!(board::has_creature(&views[&red], &wolf));
+        game_state.rounds = 2;
+        game_state.start_turn(&blue).await;
+        let views = game_state.snapshot_views().await;
+        let card = &views[&blue].board.cards[&wolf];
+        assert_eq!((blue.clone(), 1), (card.controller_id.clone(), card.health));
+        assert!(views[&red].board.cards.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_damage_stays_on_creatures_until_they_die() {
+        let blue = PlayerId::from("blue");
+        let game_state = game_state(&["red", "blue"]);
+        let wolf = CardInstanceId::nth(7);
+        {
+            let player_views = game_state.player_views.read().await;
+            let mut card = CardView::create_view(&sample_card("wolf"), blue.clone(), wolf.clone());
+            card.health = 3;
+            let placement = Placement {
+                zone: Zone::Creatures,
+                slot: 0,
+            };
+            board::place(&mut *player_views[&blue].write().await, &card, placement);
+        }
+
+        assert_eq!(Some(false), game_state.damage_creature(&wolf, 2).await);
+        let views = game_state.snapshot_views().await;
+        assert_eq!(1, views[&blue].board.cards[&wolf].health);
+        assert_eq!(Some(true), game_state.damage_creature(&wolf, 1).await);
+        let gone = CardInstanceId::nth(8);
+        assert_eq!(None, game_state.damage_creature(&gone, 1).await);
+    }
+
+    #[tokio::test]
+    async fn test_poison_deals_damage_at_each_turn_boundary() {
+        let (red, blue) = (PlayerId::from("red"), PlayerId::from("blue"));
+        let mut game_state = game_state(&["red", "blue"]);
+        let wolf = CardInstanceId::nth(7);
+        {
+            let player_views = game_state.player_views.read().await;
+            let mut card = CardView::create_view(&sample_card("wolf"), blue.clone(), wolf.clone());
+            card.health = 3;
+            let placement = Placement {
+                zone: Zone::Creatures,
+                slot: 0,
+            };
+            board::place(&mut *player_views[&blue].write().await, &card, placement);
+        }
+        let effect = |kind, magnitude| StatusEffect {
+            kind,
+            magnitude,
+            turns: Some(2),
+        };
+        let (poison, shield) = (effect(StatusKind::Poison, 2), effect(StatusKind::Shield, 1));
+        assert!(game_state.apply_status_effect("red", poison.clone()).await);
+        assert!(game_state.apply_status_effect("red", shield).await);
+        let target = wolf.to_string();
+        assert!(game_state.apply_status_effect(&target, poison).await);
+
+        // The shield absorbs part of the first tick; the creature survives it.
+        let turn = game_state.advance_turn().await.unwrap();
+        assert!(turn.deaths.is_empty());
+        let views = game_state.snapshot_views().await;
+        assert_eq!(29, views[&red].health);
+        assert_eq!(1, views[&blue].board.cards[&wolf].health);
+
+        let turn = game_state.advance_turn().await.unwrap();
+        assert_eq!(1, turn.deaths.len());
+        let views = game_state.snapshot_views().await;
+        assert_eq!(27, views[&red].health);
+        assert!(views[&red].status_effects.is_empty());
+        assert!(!board::has_creature(&views[&blue], &wolf));
+    }
+
+    #[tokio::test]
+    async fn test_script_damage_and_heals_change_health() {
+        let (red, blue) = (PlayerId::from("red"), PlayerId::from("blue"));
+        let wolf = CardInstanceId::nth(7);
+        let mut card = CardView::create_view(&sample_card("wolf"), blue.clone(), wolf.clone());
+        card.health = 3;
+        let game_state = game_state(&["red", "blue"])
+            .with_card_instances(HashMap::from([(wolf.clone(), card.clone())]));
+        {
+            let player_views = game_state.player_views.read().await;
+            let placement = Placement {
+                zone: Zone::Creatures,
+                slot: 0,
+            };
+            board::place(&mut *player_views[&blue].write().await, &card, placement);
+        }
+
+        let damage = |target: &str, amount| GameAction::DealDamage {
+            target: target.to_string(),
+            amount,
+        };
+        let heal = |target: &str, amount| GameAction::Heal {
+            target: target.to_string(),
+            amount,
+        };
+        let actions = vec![
+            damage("red", 5),
+            heal("red", 2),
+            damage("c7", 2),
+            heal("c7", 4),
+            damage("nobody", 1),
+        ];
+        let applied = game_state.apply_actions(&"spell".into(), actions).await;
+        assert_eq!(4, applied.len());
+        let views = game_state.snapshot_views().await;
+        assert_eq!(27, views[&red].health);
+        assert_eq!(3, views[&blue].board.cards[&wolf].health);
+
+        let applied = game_state
+            .apply_actions(&"spell".into(), vec![damage("c7", 3)])
+            .await;
+        assert!(matches!(
+            applied.as_slice(),
+            [
+                GameEventKind::DamageDealt { .. },
+                GameEventKind::CreatureDied { instance_id, .. },
+            ] if instance_id == &wolf
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_board_cards_show_the_activations_left() {
+        let red = PlayerId::from("red");
+        let mut game_state = game_state(&["red", "blue"]);
+        let totem = CardInstanceId::nth(3);
+        {
+            let player_views = game_state.player_views.read().await;
+            let card = CardView::create_view(&sample_card("totem"), red.clone(), totem.clone());
+            let placement = Placement {
+                zone: Zone::Artifacts,
+                slot: 0,
+            };
+            board::place(&mut *player_views[&red].write().await, &card, placement);
+        }
+        let limit = ActivationLimit::PerTurn { uses: 1 };
+        game_state
+            .cooldowns
+            .write()
+            .await
+            .register(&"totem".into(), limit);
+
+        game_state.advance_turn().await;
+        let remaining =
+            |views: &HashMap<PlayerId, PlayerView>| views[&red].board.cards[&totem].remaining_uses;
+        assert_eq!(Some(1), remaining(&game_state.snapshot_views().await));
+        game_state
+            .cooldowns
+            .write()
+            .await
+            .activate(&red, &"totem".into())
+            .unwrap();
+        game_state.refresh_remaining_uses(&red).await;
+        assert_eq!(Some(0), remaining(&game_state.snapshot_views().await));
+
+        // The use comes back on the next turn of the player.
+        game_state.advance_turn().await;
+        game_state.advance_turn().await;
+        assert_eq!(Some(1), remaining(&game_state.snapshot_views().await));
+    }
+
+    #[tokio::test]
+    async fn test_turns_go_around_the_table_skipping_defeated_players() {
+        let mut game_state = game_state(&["ann", "bob", "cid", "dan"]);
+        game_state.seating = game_state.seating.blue_first();
+        let mut turns = Vec::new();
+        for _ in 0..5 {
+            let turn = game_state.advance_turn().await.unwrap();
+            turns.push(turn.player_id.to_string());
+        }
+        assert_eq!(vec!["bob", "cid", "dan", "ann", "bob"], turns);
+
+        // A forfeited player and a player out of health no longer take turns.
+        game_state.forfeited.push(PlayerId::from("cid"));
+        let dan = game_state.player_views.read().await[&PlayerId::from("dan")].clone();
+        dan.write().await.health = 0;
+        let next = game_state.advance_turn().await.map(|turn| turn.player_id);
+        assert_eq!((Some(PlayerId::from("ann")), 6), (next, game_state.rounds));
+    }
+}
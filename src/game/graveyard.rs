@@ -0,0 +1,110 @@
+use crate::game::entity::card::{CardRef, CardType};
+use crate::game::entity::player::PlayerView;
+use crate::models::ids::CardInstanceId;
+
+/// Puts a card into a graveyard, in the pile of its type.
+///
+/// Cards are kept one per entry, in the order they arrived, and are no longer marked with an
+/// owner since a graveyard only ever holds the cards of its player.
+pub fn bury(view: &mut PlayerView, card: CardRef, card_type: CardType) {
+    let card = CardRef {
+        amount: 1,
+        owner_id: None,
+        ..card
+    };
+
+    pile_mut(view, card_type).push(card);
+    view.graveyard_size += 1;
+}
+
+/// Finds a card in a graveyard.
+///
+/// # Returns
+/// The card and its type, or `None` if the card is not in the graveyard.
+pub fn find<'a>(
+    view: &'a PlayerView,
+    instance_id: &CardInstanceId,
+) -> Option<(&'a CardRef, CardType)> {
+    CARD_TYPES.into_iter().find_map(|card_type| {
+        pile(view, card_type)
+            .iter()
+            .find(|card| card.instances.contains(instance_id))
+            .map(|card| (card, card_type))
+    })
+}
+
+/// Takes a card out of a graveyard.
+///
+/// # Returns
+/// The card and its type, or `None` if the card is not in the graveyard.
+pub fn exhume(view: &mut PlayerView, instance_id: &CardInstanceId) -> Option<(CardRef, CardType)> {
+    let card_type = find(view, instance_id)?.1;
+    let pile = pile_mut(view, card_type);
+    let index = pile
+        .iter()
+        .position(|card| card.instances.contains(instance_id))?;
+    let card = pile.remove(index);
+
+    view.graveyard_size -= 1;
+    Some((card, card_type))
+}
+
+const CARD_TYPES: [CardType; 4] = [
+    CardType::Creature,
+    CardType::Artifact,
+    CardType::Enchantment,
+    CardType::Spell,
+];
+
+fn pile(view: &PlayerView, card_type: CardType) -> &Vec<CardRef> {
+    match card_type {
+        CardType::Creature => &view.graveyard.creatures,
+        CardType::Artifact => &view.graveyard.artifacts,
+        CardType::Enchantment => &view.graveyard.enchantments,
+        CardType::Spell => &view.graveyard.spells,
+    }
+}
+
+fn pile_mut(view: &mut PlayerView, card_type: CardType) -> &mut Vec<CardRef> {
+    match card_type {
+        CardType::Creature => &mut view.graveyard.creatures,
+        CardType::Artifact => &mut view.graveyard.artifacts,
+        CardType::Enchantment => &mut view.graveyard.enchantments,
+        CardType::Spell => &mut view.graveyard.spells,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(id: &str, instance: u64) -> CardRef {
+        CardRef {
+            id: id.into(),
+            amount: 1,
+            owner_id: Some("blue".into()),
+            instances: vec![CardInstanceId::nth(instance)],
+        }
+    }
+
+    #[test]
+    fn test_cards_go_to_the_pile_of_their_type() {
+        let mut view = PlayerView::from_player(&"red".into(), 30);
+        bury(&mut view, card("wolf", 0), CardType::Creature);
+        bury(&mut view, card("fireball", 1), CardType::Spell);
+        bury(&mut view, card("wolf", 2), CardType::Creature);
+
+        assert_eq!(3, view.graveyard_size);
+        assert_eq!(2, view.graveyard.creatures.len());
+        assert_eq!(None, view.graveyard.creatures[0].owner_id);
+
+        let (fireball, card_type) = find(&view, &CardInstanceId::nth(1)).unwrap();
+        assert_eq!(CardType::Spell, card_type);
+        assert_eq!("fireball", fireball.id.to_string());
+
+        let (wolf, _) = exhume(&mut view, &CardInstanceId::nth(2)).unwrap();
+        assert_eq!(vec![CardInstanceId::nth(2)], wolf.instances);
+        assert_eq!(2, view.graveyard_size);
+        assert!(exhume(&mut view, &CardInstanceId::nth(2)).is_none());
+    }
+}
@@ -0,0 +1,173 @@
+use crate::game::entity::player::PlayerView;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::models::ids::PlayerId;
+
+/// Health lost by a single player within one turn for it to count as a big damage swing.
+const DAMAGE_SWING_THRESHOLD: i32 = 10;
+
+/// Creatures removed from a single player's board within one turn for it to count as a board clear.
+const BOARD_CLEAR_THRESHOLD: usize = 3;
+
+/// The kind of interesting moment a bookmark points at.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum BookmarkKind {
+    DamageSwing,
+    LethalTurn,
+    BoardClear,
+}
+
+/// A replay annotation marking an interesting moment of the match.
+///
+/// Highlight tooling can jump straight to `turn` instead of replaying the whole match.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Bookmark {
+    pub turn: u32,
+    pub kind: BookmarkKind,
+    /// The player who suffered the swing, died or had their board cleared.
+    pub player_id: PlayerId,
+    /// Health lost or creatures removed, depending on the kind.
+    pub magnitude: i32,
+}
+
+/// The slice of a player's state the heuristics care about.
+#[derive(Debug, Clone, Default)]
+pub struct HighlightSnapshot {
+    pub health: HashMap<PlayerId, i32>,
+    pub creatures: HashMap<PlayerId, usize>,
+}
+
+impl HighlightSnapshot {
+    pub fn from_views(views: &[PlayerView]) -> Self {
+        let mut snapshot = HighlightSnapshot::default();
+        for view in views {
+            let creatures = view.board.creatures.iter().flatten().count();
+            snapshot.health.insert(view.id.clone(), view.health);
+            snapshot.creatures.insert(view.id.clone(), creatures);
+        }
+
+        snapshot
+    }
+}
+
+/// Compares the state at the start of each turn with the state after every applied action batch
+/// and records bookmarks when one of the heuristics fires.
+///
+/// Each `(turn, kind, player)` is only bookmarked once; later batches in the same turn update the magnitude.
+#[derive(Debug, Default, Clone)]
+pub struct HighlightDetector {
+    turn_start: Option<(u32, HighlightSnapshot)>,
+    last: HighlightSnapshot,
+    pub bookmarks: Vec<Bookmark>,
+}
+
+impl HighlightDetector {
+    /// Feeds the state observed after an action batch into the detector.
+    ///
+    /// # Arguments
+    /// * `turn` - The turn the actions were applied in.
+    /// * `current` - A snapshot of the state after the actions were applied.
+    ///
+    /// # Returns
+    /// The number of bookmarks that were added by this observation.
+    pub fn observe(&mut self, turn: u32, current: HighlightSnapshot) -> usize {
+        let baseline = match &self.turn_start {
+            Some((start_turn, snapshot)) if *start_turn == turn => snapshot.clone(),
+            _ => {
+                let baseline = if self.turn_start.is_none() {
+                    current.clone()
+                } else {
+                    self.last.clone()
+                };
+                self.turn_start = Some((turn, baseline.clone()));
+                baseline
+            }
+        };
+
+        let before = self.bookmarks.len();
+        for (player_id, health) in &current.health {
+            let start_health = *baseline.health.get(player_id).unwrap_or(health);
+            let health_lost = start_health - health;
+
+            if start_health > 0 && *health <= 0 {
+                self.bookmark(turn, BookmarkKind::LethalTurn, player_id, health_lost);
+            }
+
+            if health_lost >= DAMAGE_SWING_THRESHOLD {
+                self.bookmark(turn, BookmarkKind::DamageSwing, player_id, health_lost);
+            }
+        }
+
+        for (player_id, creatures) in &current.creatures {
+            let start_creatures = *baseline.creatures.get(player_id).unwrap_or(creatures);
+            let removed = start_creatures.saturating_sub(*creatures);
+            if removed >= BOARD_CLEAR_THRESHOLD {
+                self.bookmark(turn, BookmarkKind::BoardClear, player_id, removed as i32);
+            }
+        }
+
+        self.last = current;
+        self.bookmarks.len() - before
+    }
+
+    fn bookmark(&mut self, turn: u32, kind: BookmarkKind, player_id: &PlayerId, magnitude: i32) {
+        let existing = self
+            .bookmarks
+            .iter_mut()
+            .find(|b| b.turn == turn && b.kind == kind && &b.player_id == player_id);
+
+        match existing {
+            Some(bookmark) => bookmark.magnitude = magnitude,
+            None => self.bookmarks.push(Bookmark {
+                turn,
+                kind,
+                magnitude,
+                player_id: player_id.clone(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(health: i32, creatures: usize) -> HighlightSnapshot {
+        let mut snapshot = HighlightSnapshot::default();
+        snapshot.health.insert("red".into(), health);
+        snapshot.creatures.insert("red".into(), creatures);
+        snapshot
+    }
+
+    #[test]
+    fn test_damage_swing_is_bookmarked_once_per_turn() {
+        let mut detector = HighlightDetector::default();
+        detector.observe(1, snapshot(30, 0));
+        detector.observe(2, snapshot(24, 0));
+        detector.observe(2, snapshot(18, 0));
+        detector.observe(2, snapshot(15, 0));
+
+        assert_eq!(1, detector.bookmarks.len());
+        assert_eq!(BookmarkKind::DamageSwing, detector.bookmarks[0].kind);
+        assert_eq!(15, detector.bookmarks[0].magnitude);
+    }
+
+    #[test]
+    fn test_lethal_turn_and_board_clear() {
+        let mut detector = HighlightDetector::default();
+        detector.observe(3, snapshot(4, 4));
+        detector.observe(4, snapshot(0, 0));
+
+        let kinds: Vec<_> = detector.bookmarks.iter().map(|b| b.kind.clone()).collect();
+        assert!(kinds.contains(&BookmarkKind::LethalTurn));
+        assert!(kinds.contains(&BookmarkKind::BoardClear));
+    }
+
+    #[test]
+    fn test_small_changes_are_ignored() {
+        let mut detector = HighlightDetector::default();
+        detector.observe(1, snapshot(30, 2));
+        detector.observe(1, snapshot(25, 1));
+        assert!(detector.bookmarks.is_empty());
+    }
+}
@@ -0,0 +1,423 @@
+use crate::game::entity::player::PlayerView;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use crate::models::ids::{CardDefId, CardInstanceId, PlayerId};
+
+/// Highest health a player can have.
+pub const MAX_PLAYER_HEALTH: i32 = 30;
+
+/// A broken rule of the board state, found by `check_view`.
+#[derive(Debug, PartialEq)]
+pub enum Violation {
+    /// Copies of a card with the same owner occupy several board slots instead of being stacked in one.
+    DuplicateBoardCard {
+        player_id: PlayerId,
+        card_id: CardDefId,
+    },
+    /// A board slot holds a stack of zero cards.
+    EmptyBoardStack {
+        player_id: PlayerId,
+        card_id: CardDefId,
+    },
+    /// `hand_size` does not match the number of occupied hand slots.
+    HandSizeMismatch {
+        player_id: PlayerId,
+        declared: usize,
+        actual: usize,
+    },
+    /// The player's health is above the maximum.
+    HealthOutOfBounds { player_id: PlayerId, health: i32 },
+    /// A card in hand is flagged as being in another zone.
+    HandCardOutOfZone {
+        player_id: PlayerId,
+        slot: usize,
+        card_id: CardDefId,
+    },
+    /// A copy of a card is in several places at once, in one player's zones or across players.
+    DuplicateInstance { instance_id: CardInstanceId },
+    /// The amount of a board stack does not match the copies it lists.
+    BoardStackMismatch {
+        player_id: PlayerId,
+        card_id: CardDefId,
+        amount: u32,
+        instances: usize,
+    },
+    /// A copy in a board slot has no board state in `board.cards`.
+    MissingBoardCard {
+        player_id: PlayerId,
+        instance_id: CardInstanceId,
+    },
+    /// `board.cards` holds the state of a copy that is in no board slot.
+    StrayBoardCard {
+        player_id: PlayerId,
+        instance_id: CardInstanceId,
+    },
+    /// The board state of a copy is not flagged as being on the board only.
+    BoardCardOutOfZone {
+        player_id: PlayerId,
+        instance_id: CardInstanceId,
+    },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::DuplicateBoardCard { player_id, card_id } => {
+                write!(f, "`{player_id}` has `{card_id}` in more than one board slot")
+            }
+            Violation::EmptyBoardStack { player_id, card_id } => {
+                write!(f, "`{player_id}` has an empty stack of `{card_id}` on the board")
+            }
+            Violation::HandSizeMismatch {
+                player_id,
+                declared,
+                actual,
+            } => write!(
+                f,
+                "`{player_id}` declares {declared} cards in hand but holds {actual}"
+            ),
+            Violation::HealthOutOfBounds { player_id, health } => write!(
+                f,
+                "`{player_id}` has {health} health (max {MAX_PLAYER_HEALTH})"
+            ),
+            Violation::HandCardOutOfZone {
+                player_id,
+                slot,
+                card_id,
+            } => write!(
+                f,
+                "`{player_id}` holds `{card_id}` in hand slot {slot}, but it is not flagged as in hand only"
+            ),
+            Violation::DuplicateInstance { instance_id } => {
+                write!(f, "`{instance_id}` is in more than one place")
+            }
+            Violation::BoardStackMismatch {
+                player_id,
+                card_id,
+                amount,
+                instances,
+            } => write!(
+                f,
+                "`{player_id}` has a stack of {amount} `{card_id}` listing {instances} copies"
+            ),
+            Violation::MissingBoardCard {
+                player_id,
+                instance_id,
+            } => write!(
+                f,
+                "`{player_id}` has `{instance_id}` in a board slot but no board state for it"
+            ),
+            Violation::StrayBoardCard {
+                player_id,
+                instance_id,
+            } => write!(
+                f,
+                "`{player_id}` has a board state for `{instance_id}`, which is in no board slot"
+            ),
+            Violation::BoardCardOutOfZone {
+                player_id,
+                instance_id,
+            } => write!(
+                f,
+                "`{player_id}` has `{instance_id}` on the board, but it is not flagged as on the board only"
+            ),
+        }
+    }
+}
+
+/// Checks the board state invariants of a single player.
+///
+/// # Returns
+/// Every violation found, empty if the view is consistent.
+pub fn check_view(view: &PlayerView) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let player_id = || view.id.clone();
+
+    let mut seen = HashSet::new();
+    let board = view
+        .board
+        .creatures
+        .iter()
+        .chain(view.board.artifacts.iter())
+        .chain(view.board.enchantments.iter())
+        .flatten();
+
+    let mut in_slots = HashSet::new();
+    for card in board {
+        if !seen.insert((&card.id, &card.owner_id)) {
+            violations.push(Violation::DuplicateBoardCard {
+                player_id: player_id(),
+                card_id: card.id.clone(),
+            });
+        }
+
+        if card.amount == 0 {
+            violations.push(Violation::EmptyBoardStack {
+                player_id: player_id(),
+                card_id: card.id.clone(),
+            });
+        }
+
+        if card.amount as usize != card.instances.len() {
+            violations.push(Violation::BoardStackMismatch {
+                player_id: player_id(),
+                card_id: card.id.clone(),
+                amount: card.amount,
+                instances: card.instances.len(),
+            });
+        }
+
+        for instance_id in &card.instances {
+            in_slots.insert(instance_id);
+            if !view.board.cards.contains_key(instance_id) {
+                violations.push(Violation::MissingBoardCard {
+                    player_id: player_id(),
+                    instance_id: instance_id.clone(),
+                });
+            }
+        }
+    }
+
+    for (instance_id, card) in &view.board.cards {
+        if !in_slots.contains(instance_id) {
+            violations.push(Violation::StrayBoardCard {
+                player_id: player_id(),
+                instance_id: instance_id.clone(),
+            });
+        }
+
+        if !card.in_board || card.in_hand || card.in_deck || card.in_graveyard {
+            violations.push(Violation::BoardCardOutOfZone {
+                player_id: player_id(),
+                instance_id: instance_id.clone(),
+            });
+        }
+    }
+
+    let held = view.current_hand.iter().flatten().count();
+    if held != view.hand_size {
+        violations.push(Violation::HandSizeMismatch {
+            player_id: player_id(),
+            declared: view.hand_size,
+            actual: held,
+        });
+    }
+
+    if view.health > MAX_PLAYER_HEALTH {
+        violations.push(Violation::HealthOutOfBounds {
+            player_id: player_id(),
+            health: view.health,
+        });
+    }
+
+    for (slot, card) in view.current_hand.iter().enumerate() {
+        if let Some(card) = card {
+            if !card.in_hand || card.in_board || card.in_deck || card.in_graveyard {
+                violations.push(Violation::HandCardOutOfZone {
+                    slot,
+                    player_id: player_id(),
+                    card_id: card.id.clone(),
+                });
+            }
+        }
+    }
+
+    violations
+}
+
+/// Checks the invariants of every player, and that no copy of a card is in two places at once:
+/// in two hand slots, board stacks or graveyard stacks, of one player or of two.
+///
+/// # Returns
+/// Every violation found, empty if the views are consistent.
+pub fn check_views(views: &HashMap<PlayerId, PlayerView>) -> Vec<Violation> {
+    let mut violations: Vec<Violation> = views.values().flat_map(check_view).collect();
+
+    let mut seen = HashSet::new();
+    for view in views.values() {
+        let board = view
+            .board
+            .creatures
+            .iter()
+            .chain(view.board.artifacts.iter())
+            .chain(view.board.enchantments.iter())
+            .flatten();
+        let graveyard = view
+            .graveyard
+            .creatures
+            .iter()
+            .chain(view.graveyard.artifacts.iter())
+            .chain(view.graveyard.enchantments.iter())
+            .chain(view.graveyard.spells.iter());
+        let hand = view.current_hand.iter().flatten().map(|c| &c.instance_id);
+
+        let placed = board.chain(graveyard).flat_map(|c| c.instances.iter());
+        for instance_id in hand.chain(placed) {
+            if !seen.insert(instance_id) {
+                violations.push(Violation::DuplicateInstance {
+                    instance_id: instance_id.clone(),
+                });
+            }
+        }
+    }
+
+    violations
+}
+
+/// Panics if any player view breaks a board state invariant.
+///
+/// The panic message lists the violations followed by a field-level diff of every view against
+/// `before`, so the action that corrupted the state can be told apart from the ones that did not.
+/// Batches check it on commit, and the actions of the players once they resolved.
+pub fn assert_invariants(
+    before: &HashMap<PlayerId, PlayerView>,
+    after: &HashMap<PlayerId, PlayerView>,
+) {
+    let violations: Vec<String> = check_views(after)
+        .iter()
+        .map(|v| format!("  - {v}"))
+        .collect();
+
+    if violations.is_empty() {
+        return;
+    }
+
+    let mut changes = Vec::new();
+    for (player_id, view) in after {
+        let old = before
+            .get(player_id)
+            .and_then(|v| serde_json::to_value(v).ok())
+            .unwrap_or(Value::Null);
+        let new = serde_json::to_value(view).unwrap_or(Value::Null);
+        diff_values(&player_id.to_string(), &old, &new, &mut changes);
+    }
+
+    panic!(
+        "Board state invariants broken:\n{}\nChanges since the action started:\n{}",
+        violations.join("\n"),
+        changes.join("\n")
+    );
+}
+
+/// Collects the leaves that differ between two JSON values, as `path: old -> new` lines.
+fn diff_values(path: &str, old: &Value, new: &Value, changes: &mut Vec<String>) {
+    match (old, new) {
+        (Value::Object(old_fields), Value::Object(new_fields)) => {
+            let keys: HashSet<&String> = old_fields.keys().chain(new_fields.keys()).collect();
+            let mut keys: Vec<&String> = keys.into_iter().collect();
+            keys.sort();
+            for key in keys {
+                let old = old_fields.get(key).unwrap_or(&Value::Null);
+                let new = new_fields.get(key).unwrap_or(&Value::Null);
+                diff_values(&format!("{path}.{key}"), old, new, changes);
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) => {
+            for index in 0..old_items.len().max(new_items.len()) {
+                let old = old_items.get(index).unwrap_or(&Value::Null);
+                let new = new_items.get(index).unwrap_or(&Value::Null);
+                diff_values(&format!("{path}[{index}]"), old, new, changes);
+            }
+        }
+        _ if old != new => changes.push(format!("  {path}: {old} -> {new}")),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entity::card::{CardRef, CardView};
+    use crate::test_support::fixtures::sample_card;
+
+    fn view() -> PlayerView {
+        PlayerView::from_player(&"red".into(), 30)
+    }
+
+    #[test]
+    fn test_fresh_view_is_consistent() {
+        assert!(check_view(&view()).is_empty());
+    }
+
+    #[test]
+    fn test_corrupted_view_is_reported() {
+        let mut corrupted = view();
+        corrupted.hand_size = 2;
+        corrupted.health = MAX_PLAYER_HEALTH + 1;
+        let wolf = CardRef {
+            id: "wolf".into(),
+            amount: 1,
+            owner_id: None,
+            instances: Vec::new(),
+        };
+        corrupted.board.creatures[0] = Some(wolf.clone());
+        corrupted.board.creatures[3] = Some(wolf);
+
+        // Both stacks also claim a copy they do not list.
+        let violations = check_view(&corrupted);
+        assert_eq!(5, violations.len());
+        assert!(violations.contains(&Violation::DuplicateBoardCard {
+            player_id: "red".into(),
+            card_id: "wolf".into(),
+        }));
+    }
+
+    #[test]
+    fn test_copies_are_in_one_place_with_their_board_state() {
+        let wolf = |instance: u64| CardRef {
+            id: "wolf".into(),
+            amount: 1,
+            owner_id: None,
+            instances: vec![CardInstanceId::nth(instance)],
+        };
+        let mut red = view();
+        let mut blue = PlayerView::from_player(&"blue".into(), 30);
+        red.board.creatures[0] = Some(wolf(0));
+        red.graveyard.creatures.push(wolf(1));
+        blue.board.creatures[0] = Some(wolf(1));
+        let state = |instance: u64| {
+            CardView::create_view(
+                &sample_card("wolf"),
+                "blue".into(),
+                CardInstanceId::nth(instance),
+            )
+        };
+        let mut placed = state(1);
+        placed.in_board = true;
+        blue.board.cards.insert(CardInstanceId::nth(1), placed);
+        blue.board.cards.insert(CardInstanceId::nth(2), state(2));
+
+        let views = HashMap::from([("red".into(), red), ("blue".into(), blue)]);
+        let violations = check_views(&views);
+        assert_eq!(4, violations.len());
+        for violation in [
+            Violation::DuplicateInstance {
+                instance_id: CardInstanceId::nth(1),
+            },
+            Violation::MissingBoardCard {
+                player_id: "red".into(),
+                instance_id: CardInstanceId::nth(0),
+            },
+            Violation::StrayBoardCard {
+                player_id: "blue".into(),
+                instance_id: CardInstanceId::nth(2),
+            },
+            Violation::BoardCardOutOfZone {
+                player_id: "blue".into(),
+                instance_id: CardInstanceId::nth(2),
+            },
+        ] {
+            assert!(violations.contains(&violation), "{violation}");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "red.health: 30 -> 31")]
+    fn test_assertion_reports_diff() {
+        let before = HashMap::from([("red".into(), view())]);
+        let mut after = before.clone();
+        after.get_mut(&"red".into()).unwrap().health = MAX_PLAYER_HEALTH + 1;
+        assert_invariants(&before, &after);
+    }
+}
@@ -1,27 +1,31 @@
 use mlua::LuaSerdeExt;
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
 use crate::game::entity::card::CardView;
+use crate::game::event_log::GameEvent;
 use super::game_state::{GameState, PrivateGameStateView};
+use crate::models::ids::CardDefId;
 
 #[derive(Serialize, Clone)]
 pub struct LuaContext {
     pub event: String,
     pub action_name: String,
 
-    pub actor_id: String,
+    pub actor_id: CardDefId,
     pub actor_view: CardView,
     pub target_id: Option<String>,
     pub target_view: Option<CardView>,
     pub game_state: PrivateGameStateView,
+    pub turn_events: Vec<GameEvent>, // Events of the current turn, for "this turn" effects.
+    pub vars: BTreeMap<String, serde_json::Value>, // Match variables of the actor's card.
 }
 
 impl LuaContext {
     /// Creates a new `LuaContext` instance.
     ///
     /// # Arguments
-    /// * `gs` - A thread-safe reference to the current game state.
+    /// * `game_state` - The current game state, locked by the caller.
     /// * `actor` - The `CardView` representing the actor performing the action.
     /// * `target` - An optional `CardView` representing the target of the action.
     /// * `event` - A string describing the event triggering this context.
@@ -30,42 +34,26 @@ impl LuaContext {
     /// # Returns
     /// A new `LuaContext` instance populated with the provided data and the current game state.
     pub async fn new(
-        game_state: Arc<RwLock<GameState>>,
+        game_state: &GameState,
         actor: &CardView,
         target: Option<CardView>,
         event: String,
         action: String,
     ) -> Self {
-        let game_state_guard = game_state.read().await;
-        let player_views_guard = game_state_guard.player_views.read().await;
-
-        let keys: Vec<_> = player_views_guard.keys().collect();
-        let red_player = player_views_guard[keys[0]]
-            .clone()
-            .read()
-            .await
-            .clone();
-        
-        let blue_player = player_views_guard[keys[1]]
-            .clone()
-            .read()
-            .await
-            .clone();
-
-        let private_game_state = PrivateGameStateView {
-            red_player,
-            blue_player,
-            turn: game_state_guard.rounds,
-        };
+        let private_game_state = game_state.private_view().await;
+        let turn_events = game_state.events.read().await.turn(game_state.rounds);
+        let vars = game_state.variables.read().await.namespace(&actor.id);
 
         LuaContext {
             event,
             game_state: private_game_state,
+            turn_events,
+            vars,
             action_name: action,
             actor_view: actor.clone(),
             actor_id: actor.id.clone(),
             target_id: match &target {
-                Some(t) => Some(t.id.clone()),
+                Some(t) => Some(t.id.to_string()),
                 None => None,
             },
             target_view: target,
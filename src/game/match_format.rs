@@ -0,0 +1,398 @@
+use crate::game::entity::deck::Deck;
+use crate::game::rules::{RulesProfile, BLOCKERS_SUFFIX};
+use crate::models::ids::CardDefId;
+use crate::utils::errors::DeckConstraintError;
+use crate::HTTP_SERVICE;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+/// Name of the format matches fall back to when their match type names no other.
+pub const STANDARD_FORMAT: &str = "standard";
+
+/// Limits on the decks a format accepts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeckRules {
+    pub min_size: u32, // Fewest cards a deck may hold.
+    pub max_size: u32, // Most cards a deck may hold.
+    #[serde(default)]
+    pub max_copies: Option<u32>, // Most copies of one card a deck may hold; unlimited if unset.
+    #[serde(default)]
+    pub banned: BTreeSet<CardDefId>, // Cards no deck of the format may hold.
+    #[serde(default)]
+    pub legal_cards: Option<BTreeSet<CardDefId>>, // The only cards decks may hold; every card if unset.
+}
+
+impl DeckRules {
+    /// Every rule a deck breaks: its size, then the copies, bans and legality of each card.
+    pub fn violations(&self, deck: &Deck) -> Vec<DeckConstraintError> {
+        let mut violations = Vec::new();
+        let size: u32 = deck.cards.iter().map(|card| card.amount).sum();
+        if size < self.min_size || size > self.max_size {
+            violations.push(DeckConstraintError::DeckSize(
+                size,
+                self.min_size,
+                self.max_size,
+            ));
+        }
+
+        // A card may be listed several times; its copies are counted together.
+        let mut copies: Vec<(&CardDefId, u32)> = Vec::new();
+        for card in &deck.cards {
+            match copies.iter_mut().find(|(id, _)| *id == &card.id) {
+                Some((_, amount)) => *amount += card.amount,
+                None => copies.push((&card.id, card.amount)),
+            }
+        }
+
+        for (card_id, amount) in copies {
+            if let Some(max_copies) = self.max_copies.filter(|max| amount > *max) {
+                violations.push(DeckConstraintError::TooManyCopies(
+                    card_id.to_string(),
+                    max_copies,
+                ));
+            }
+            if self.banned.contains(card_id) {
+                violations.push(DeckConstraintError::BannedCard(card_id.to_string()));
+            } else if self
+                .legal_cards
+                .as_ref()
+                .is_some_and(|legal| !legal.contains(card_id))
+            {
+                violations.push(DeckConstraintError::NotLegal(card_id.to_string()));
+            }
+        }
+
+        violations
+    }
+
+    /// Adds the bans and card list a legality service publishes for the format.
+    pub fn apply(&mut self, legality: FormatLegality) {
+        self.banned.extend(legality.banned);
+        if let Some(legal_cards) = legality.legal_cards {
+            self.legal_cards = Some(legal_cards);
+        }
+    }
+}
+
+/// The cards a legality service bans or allows in a format, so the ban list can change without
+/// editing the formats of every server.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct FormatLegality {
+    #[serde(default)]
+    pub banned: BTreeSet<CardDefId>, // Cards banned on top of those of the format.
+    #[serde(default)]
+    pub legal_cards: Option<BTreeSet<CardDefId>>, // Replaces the legal cards of the format if set.
+}
+
+impl FormatLegality {
+    /// Fetches the legality of a format, served at `<url>/<format>`.
+    ///
+    /// # Returns
+    /// * `Ok(FormatLegality)` - The cards banned and allowed in the format.
+    /// * `Err(String)` - If the service could not be reached or answered something else.
+    pub async fn fetch(url: &str, format: &str) -> Result<Self, String> {
+        let url = format!("{}/{format}", url.trim_end_matches('/'));
+        let response = HTTP_SERVICE
+            .client()
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("{url} answered {}", response.status()));
+        }
+
+        response.json().await.map_err(|e| e.to_string())
+    }
+}
+
+/// Which player takes the first turn.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FirstPlayer {
+    /// The first player listed by the matchmaker.
+    #[default]
+    Red,
+    /// Drawn from the match random number generator, so replays pick the same player.
+    Random,
+}
+
+/// How the turns of a format are played.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TurnStructure {
+    #[serde(default = "default_starting_mana")]
+    pub starting_mana: i32, // Mana each player starts the match with.
+    #[serde(default)]
+    pub first_player: FirstPlayer, // Who takes the first turn.
+}
+
+impl Default for TurnStructure {
+    fn default() -> Self {
+        Self {
+            starting_mana: default_starting_mana(),
+            first_player: FirstPlayer::default(),
+        }
+    }
+}
+
+/// How the health of teammates is kept in a team match.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TeamHealth {
+    /// Each player has their own health; a team is defeated once all of its players are.
+    #[default]
+    Separate,
+    /// Damage dealt to a player is dealt to their whole team, which is defeated as one.
+    Shared,
+}
+
+/// The two teams of a format played by more than two players, who take their turns in seat order
+/// with the teams alternating.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TeamRules {
+    pub size: u32, // Players in each team.
+    #[serde(default)]
+    pub health: TeamHealth,
+}
+
+impl TeamRules {
+    /// The players a match of the format seats.
+    pub fn seats(&self) -> usize {
+        self.size as usize * 2
+    }
+}
+
+/// A way of playing the game, picked from the match type when the match is created.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatchFormat {
+    pub name: String,
+    #[serde(default = "default_starting_health")]
+    pub starting_health: i32, // Health each player starts the match with.
+    pub deck: DeckRules,
+    #[serde(default)]
+    pub turns: TurnStructure,
+    #[serde(default = "default_best_of")]
+    pub best_of: u32, // Games in the series this match is part of; the matchmaker plays each one.
+    #[serde(default)]
+    pub rules: Option<RulesProfile>, // Rules of the format; the defaults of the match type otherwise.
+    #[serde(default)]
+    pub teams: Option<TeamRules>, // The teams of a team match; one player a side otherwise.
+}
+
+impl MatchFormat {
+    /// The standard format: 30 to 40 cards with at most 3 copies of each, 30 health, 1 mana.
+    pub fn standard() -> Self {
+        Self {
+            name: STANDARD_FORMAT.to_string(),
+            starting_health: default_starting_health(),
+            deck: DeckRules {
+                min_size: 30,
+                max_size: 40,
+                max_copies: Some(3),
+                banned: BTreeSet::new(),
+                legal_cards: None,
+            },
+            turns: TurnStructure::default(),
+            best_of: default_best_of(),
+            rules: None,
+            teams: None,
+        }
+    }
+
+    /// The rules a match of this format is played with when the matchmaker sends none.
+    pub fn rules_for(&self, match_type: &str) -> RulesProfile {
+        self.rules
+            .clone()
+            .unwrap_or_else(|| RulesProfile::for_match_type(match_type))
+    }
+}
+
+/// The formats a server knows, keyed by name.
+///
+/// Standard, best-of-three, draft, arena and 2v2 are built in; custom formats are read from a JSON
+/// file listing them, and replace built-in formats of the same name.
+#[derive(Debug, Clone)]
+pub struct MatchFormatRegistry {
+    formats: HashMap<String, MatchFormat>,
+}
+
+impl MatchFormatRegistry {
+    /// Reads the custom formats kept in a file on top of the built-in ones; a missing file only
+    /// leaves the built-in formats.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let mut registry = Self::default();
+        let custom: Vec<MatchFormat> = match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(error),
+        };
+
+        for format in custom {
+            registry.insert(format);
+        }
+        Ok(registry)
+    }
+
+    /// Adds a format, replacing any format of the same name.
+    pub fn insert(&mut self, format: MatchFormat) {
+        self.formats.insert(format.name.clone(), format);
+    }
+
+    /// The format of a match type: the format of the same name, ignoring the `-blockers` suffix,
+    /// or the standard format if none matches.
+    pub fn resolve(&self, match_type: &str) -> MatchFormat {
+        let base = match_type
+            .strip_suffix(BLOCKERS_SUFFIX)
+            .unwrap_or(match_type);
+        self.formats
+            .get(match_type)
+            .or_else(|| self.formats.get(base))
+            .or_else(|| self.formats.get(STANDARD_FORMAT))
+            .cloned()
+            .unwrap_or_else(MatchFormat::standard)
+    }
+}
+
+impl Default for MatchFormatRegistry {
+    fn default() -> Self {
+        let standard = MatchFormat::standard();
+        let best_of_three = MatchFormat {
+            name: "best-of-three".to_string(),
+            best_of: 3,
+            ..standard.clone()
+        };
+        let draft = MatchFormat {
+            name: "draft".to_string(),
+            deck: DeckRules {
+                min_size: 20,
+                max_size: 40,
+                max_copies: None,
+                banned: BTreeSet::new(),
+                legal_cards: None,
+            },
+            turns: TurnStructure {
+                first_player: FirstPlayer::Random,
+                ..TurnStructure::default()
+            },
+            ..standard.clone()
+        };
+        // Arena decks are drafted too, so they follow the same deck rules.
+        let arena = MatchFormat {
+            name: "arena".to_string(),
+            ..draft.clone()
+        };
+
+        let two_versus_two = MatchFormat {
+            name: "2v2".to_string(),
+            teams: Some(TeamRules {
+                size: 2,
+                health: TeamHealth::Separate,
+            }),
+            ..standard.clone()
+        };
+
+        let mut registry = Self {
+            formats: HashMap::new(),
+        };
+        for format in [standard, best_of_three, draft, arena, two_versus_two] {
+            registry.insert(format);
+        }
+        registry
+    }
+}
+
+fn default_starting_health() -> i32 {
+    30
+}
+
+fn default_starting_mana() -> i32 {
+    1
+}
+
+fn default_best_of() -> u32 {
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entity::card::CardRef;
+
+    fn deck(cards: &[(&str, u32)]) -> Deck {
+        Deck {
+            id: "deck".to_string(),
+            player_id: "red".into(),
+            name: "Deck".to_string(),
+            cards: cards
+                .iter()
+                .map(|(id, amount)| CardRef {
+                    id: (*id).into(),
+                    amount: *amount,
+                    owner_id: None,
+                    instances: Vec::new(),
+                })
+                .collect(),
+            sideboard: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_formats_resolve_and_validate_decks() {
+        let mut registry = MatchFormatRegistry::default();
+        registry.insert(MatchFormat {
+            name: "highlander".to_string(),
+            starting_health: 40,
+            deck: DeckRules {
+                min_size: 2,
+                max_size: 60,
+                max_copies: Some(1),
+                banned: BTreeSet::from(["wizard".into()]),
+                legal_cards: None,
+            },
+            ..MatchFormat::standard()
+        });
+
+        assert_eq!(3, registry.resolve("best-of-three-blockers").best_of);
+        assert_eq!(STANDARD_FORMAT, registry.resolve("ranked").name);
+        let highlander = registry.resolve("highlander");
+        assert_eq!(40, highlander.starting_health);
+
+        assert!(highlander
+            .deck
+            .violations(&deck(&[("wolf", 1), ("mage", 1)]))
+            .is_empty());
+        assert!(matches!(
+            highlander.deck.violations(&deck(&[("wolf", 2)])).as_slice(),
+            [DeckConstraintError::TooManyCopies(_, 1)]
+        ));
+        assert!(matches!(
+            registry
+                .resolve("draft")
+                .deck
+                .violations(&deck(&[("wolf", 10)]))
+                .as_slice(),
+            [DeckConstraintError::DeckSize(10, 20, 40)]
+        ));
+
+        let mut rules = highlander.deck.clone();
+        rules.apply(FormatLegality {
+            banned: BTreeSet::new(),
+            legal_cards: Some(BTreeSet::from(["wolf".into(), "wizard".into()])),
+        });
+        assert_eq!(
+            vec![
+                DeckConstraintError::DeckSize(1, 2, 60),
+                DeckConstraintError::BannedCard("wizard".to_string()),
+            ],
+            rules.violations(&deck(&[("wizard", 1)]))
+        );
+        assert_eq!(
+            vec![
+                DeckConstraintError::TooManyCopies("wolf".to_string(), 1),
+                DeckConstraintError::NotLegal("mage".to_string()),
+            ],
+            rules.violations(&deck(&[("wolf", 1), ("mage", 1), ("wolf", 1)]))
+        );
+    }
+}
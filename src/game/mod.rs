@@ -1,5 +1,41 @@
+pub mod batch;
+pub mod board;
+pub mod card_catalog;
+pub mod card_pool;
+pub mod checkpoint;
+pub mod combat;
+pub mod control;
+pub mod cooldown;
+pub mod draft;
+pub mod effect_stack;
 pub mod entity;
+pub mod event_bus;
+pub mod event_log;
 pub mod game_state;
+pub mod graveyard;
+pub mod highlights;
+pub mod invariants;
 pub mod lua_context;
+pub mod match_format;
+pub mod prompt;
+pub mod redaction;
+pub mod rewards;
+pub mod rng;
+pub mod rules;
+pub mod script_api;
+pub mod script_blocklist;
+pub mod script_harness;
+pub mod script_lint;
+pub mod script_manifest;
 pub mod script_manager;
+pub mod script_worker;
+pub mod seating;
+pub mod sideboard;
+pub mod start_barrier;
+pub mod status_effect;
+pub mod targeting;
+pub mod think_time;
+pub mod undo;
+pub mod variables;
+pub mod wager;
 pub mod game;
@@ -0,0 +1,213 @@
+use crate::models::client_requests::PlayCardRequest;
+use crate::utils::errors::GameLogicError;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::models::ids::{CardDefId, PlayerId};
+
+/// What the player is being asked to decide.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum PromptKind {
+    ChooseTarget,
+    /// The opponent must accept or decline taking back the last play of a player.
+    ConfirmUndo,
+}
+
+/// A decision the server is waiting on, sent to the client in a `PromptRequest` packet.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Prompt {
+    pub id: u32,
+    pub player_id: PlayerId,
+    pub kind: PromptKind,
+    /// The card that caused the prompt, if any.
+    pub card_id: Option<CardDefId>,
+    pub options: Vec<String>,
+    /// Unix timestamp (milliseconds) after which the default resolution is applied.
+    pub deadline: i64,
+}
+
+/// Option of a `ConfirmUndo` prompt letting the play be taken back.
+pub const UNDO_ACCEPT: &str = "accept";
+
+/// Option of a `ConfirmUndo` prompt keeping the play, also applied when the prompt expires.
+pub const UNDO_DECLINE: &str = "decline";
+
+/// The action to resume once the prompt is answered.
+#[derive(Debug, Clone)]
+pub enum PromptOrigin {
+    PlayCard(PlayCardRequest),
+    /// A player asked to undo their last play.
+    Undo(PlayerId),
+}
+
+#[derive(Debug, Clone)]
+pub struct PendingPrompt {
+    pub prompt: Prompt,
+    pub origin: PromptOrigin,
+}
+
+impl PendingPrompt {
+    /// The choice applied when the player fails to answer before the deadline.
+    pub fn default_choice(&self) -> Option<String> {
+        match self.prompt.kind {
+            PromptKind::ChooseTarget => self.prompt.options.first().cloned(),
+            PromptKind::ConfirmUndo => Some(UNDO_DECLINE.to_string()),
+        }
+    }
+}
+
+/// Keeps track of the prompts that are waiting on a player's answer.
+///
+/// Prompts live in the game state rather than on the client, so they survive disconnections.
+#[derive(Default, Clone)]
+pub struct PromptManager {
+    next_id: u32,
+    pending: HashMap<u32, PendingPrompt>,
+}
+
+impl PromptManager {
+    /// Opens a new prompt for a player and returns the client-facing part of it.
+    ///
+    /// The prompt expires `timeout_secs` seconds from now.
+    pub fn open(
+        &mut self,
+        player_id: &PlayerId,
+        kind: PromptKind,
+        card_id: Option<CardDefId>,
+        options: Vec<String>,
+        origin: PromptOrigin,
+        timeout_secs: u64,
+    ) -> Prompt {
+        self.next_id += 1;
+        let prompt = Prompt {
+            kind,
+            card_id,
+            options,
+            id: self.next_id,
+            deadline: Utc::now().timestamp_millis() + (timeout_secs * 1000) as i64,
+            player_id: player_id.clone(),
+        };
+
+        self.pending.insert(
+            prompt.id,
+            PendingPrompt {
+                origin,
+                prompt: prompt.clone(),
+            },
+        );
+
+        prompt
+    }
+
+    /// Removes and returns a pending prompt once its answer has been validated.
+    ///
+    /// # Arguments
+    /// * `prompt_id` - The prompt being answered.
+    /// * `player_id` - The player answering it.
+    /// * `choice` - The option picked by the player.
+    ///
+    /// # Returns
+    /// * `Ok(PendingPrompt)` - The prompt and the action to resume.
+    /// * `Err(GameLogicError)` - If the prompt does not exist, belongs to someone else or the choice is not an option.
+    pub fn answer(
+        &mut self,
+        prompt_id: u32,
+        player_id: &PlayerId,
+        choice: &str,
+    ) -> Result<PendingPrompt, GameLogicError> {
+        let pending = self
+            .pending
+            .get(&prompt_id)
+            .filter(|p| &p.prompt.player_id == player_id)
+            .ok_or(GameLogicError::PromptNotFound(prompt_id))?;
+
+        if !pending.prompt.options.iter().any(|o| o == choice) {
+            return Err(GameLogicError::InvalidPromptChoice(choice.to_string()));
+        }
+
+        self.pending
+            .remove(&prompt_id)
+            .ok_or(GameLogicError::PromptNotFound(prompt_id))
+    }
+
+    /// Goes back to the prompts of an earlier copy of the manager, when the match is rolled back.
+    /// Ids keep counting from the latest one given, so a prompt id is never used twice.
+    pub fn rewind(&mut self, earlier: PromptManager) {
+        let next_id = self.next_id.max(earlier.next_id);
+        *self = PromptManager { next_id, ..earlier };
+    }
+
+    /// Discards a pending prompt without resuming its action.
+    pub fn cancel(&mut self, prompt_id: u32) {
+        self.pending.remove(&prompt_id);
+    }
+
+    /// Removes and returns every prompt whose deadline is earlier than `now` (unix milliseconds).
+    pub fn take_expired(&mut self, now: i64) -> Vec<PendingPrompt> {
+        let expired: Vec<u32> = self
+            .pending
+            .values()
+            .filter(|p| p.prompt.deadline < now)
+            .map(|p| p.prompt.id)
+            .collect();
+
+        expired
+            .iter()
+            .filter_map(|id| self.pending.remove(id))
+            .collect()
+    }
+
+    /// Returns the pending prompts of a player, pushing their deadlines so at least
+    /// `grace_secs` seconds are left to answer them.
+    ///
+    /// Used to re-send prompts to a player who reconnected while they were pending.
+    pub fn refresh_for_player(&mut self, player_id: &PlayerId, grace_secs: u64) -> Vec<Prompt> {
+        let min_deadline = Utc::now().timestamp_millis() + (grace_secs * 1000) as i64;
+        self.pending
+            .values_mut()
+            .filter(|p| &p.prompt.player_id == player_id)
+            .map(|p| {
+                p.prompt.deadline = p.prompt.deadline.max(min_deadline);
+                p.prompt.clone()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_prompt(manager: &mut PromptManager, timeout_secs: u64) -> Prompt {
+        manager.open(
+            &"red".into(),
+            PromptKind::ChooseTarget,
+            Some("fireball".into()),
+            vec!["wolf".to_string(), "bear".to_string()],
+            PromptOrigin::PlayCard(PlayCardRequest::default()),
+            timeout_secs,
+        )
+    }
+
+    #[test]
+    fn test_expired_prompts_are_taken_with_default_choice() {
+        let mut manager = PromptManager::default();
+        let prompt = open_prompt(&mut manager, 0);
+        let expired = manager.take_expired(prompt.deadline + 1);
+
+        assert_eq!(1, expired.len());
+        assert_eq!(Some("wolf".to_string()), expired[0].default_choice());
+        assert!(manager.answer(prompt.id, &"red".into(), "wolf").is_err());
+    }
+
+    #[test]
+    fn test_refresh_extends_deadline_for_reconnecting_player() {
+        let mut manager = PromptManager::default();
+        let prompt = open_prompt(&mut manager, 0);
+        let refreshed = manager.refresh_for_player(&"red".into(), 60);
+
+        assert_eq!(1, refreshed.len());
+        assert!(refreshed[0].deadline > prompt.deadline);
+        assert!(manager.refresh_for_player(&"blue".into(), 60).is_empty());
+    }
+}
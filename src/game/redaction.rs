@@ -0,0 +1,180 @@
+use crate::game::entity::board::BoardView;
+use crate::game::entity::player::PlayerView;
+use crate::game::game_state::{PrivateGameStateView, PublicGameStateView};
+use crate::game::status_effect::StatusEffect;
+use crate::models::ids::PlayerId;
+use serde::Serialize;
+
+/// A player as their opponents, their teammates, spectators and card scripts see them.
+///
+/// Only `redact` builds it, so whatever is hidden from anyone but the player is removed in one
+/// place: the cards in the hand are reduced to their count and the deck, whose order is never
+/// part of a view, to its size.
+#[derive(Serialize, Clone, Debug)]
+pub struct PublicPlayerView {
+    pub id: PlayerId,
+    pub health: i32,
+    pub mana: i32,
+    pub hand_size: usize,
+    pub deck_size: usize,
+    pub graveyard_size: usize,
+    pub board: BoardView,
+    pub status_effects: Vec<StatusEffect>,
+}
+
+/// Keeps only what anyone but the player may see of their view.
+pub fn redact(view: &PlayerView) -> PublicPlayerView {
+    PublicPlayerView {
+        id: view.id.clone(),
+        health: view.health,
+        mana: view.mana,
+        hand_size: view.hand_size,
+        deck_size: view.deck_size,
+        graveyard_size: view.graveyard_size,
+        board: view.board.clone(),
+        status_effects: view.status_effects.clone(),
+    }
+}
+
+/// Keeps only what spectators may see of the match: every player is redacted. Think times are
+/// left out; the caller adds them when `THINK_TIME_VISIBILITY` shows them.
+pub fn redact_state(state: &PrivateGameStateView) -> PublicGameStateView {
+    PublicGameStateView {
+        turn: state.turn,
+        red_player: redact(&state.red_player),
+        blue_player: redact(&state.blue_player),
+        partners: state.partners.iter().map(redact).collect(),
+        red_think_time: None,
+        blue_think_time: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entity::card::{CardRef, CardView};
+    use crate::models::ids::{CardDefId, CardInstanceId};
+    use crate::test_support::fixtures::sample_card;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    /// Views checked by each property, each generated from its own seed.
+    const CASES: u64 = 200;
+
+    /// The fields a redacted player may be serialized with.
+    const PUBLIC_FIELDS: [&str; 8] = [
+        "id",
+        "health",
+        "mana",
+        "hand_size",
+        "deck_size",
+        "graveyard_size",
+        "board",
+        "status_effects",
+    ];
+
+    /// A card in a hand, every string of which is marked as hidden.
+    fn hidden_card(marker: &str, owner_id: &PlayerId) -> CardView {
+        let card = sample_card(marker);
+        let mut view = CardView::create_view(&card, owner_id.clone(), CardInstanceId::from(marker));
+        view.effects = vec![format!("{marker}-effect")];
+        view.in_hand = true;
+        view
+    }
+
+    /// A player with a random hand of hidden cards and a random board of public ones.
+    ///
+    /// # Returns
+    /// The view and the markers of its hidden cards.
+    fn random_view(rng: &mut ChaCha8Rng, seat: &str) -> (PlayerView, Vec<String>) {
+        let player_id = PlayerId::from(seat);
+        let mut view = PlayerView::from_player(&player_id, rng.gen_range(0..40));
+        view.health = rng.gen_range(-5..=30);
+        view.mana = rng.gen_range(0..=10);
+        view.graveyard_size = rng.gen_range(0..10);
+
+        let mut markers = Vec::new();
+        for slot in 0..view.current_hand.len() {
+            if rng.gen_bool(0.6) {
+                let marker = format!("hidden-{seat}-{slot}-{}", rng.gen::<u32>());
+                view.current_hand[slot] = Some(hidden_card(&marker, &player_id));
+                view.hand_size += 1;
+                markers.push(marker);
+            }
+        }
+        for slot in view.board.creatures.iter_mut() {
+            if rng.gen_bool(0.5) {
+                *slot = Some(CardRef {
+                    id: CardDefId::from(format!("board-{}", rng.gen::<u16>())),
+                    amount: 1,
+                    owner_id: None,
+                    instances: vec![CardInstanceId::nth(rng.gen_range(0..1000))],
+                });
+            }
+        }
+        (view, markers)
+    }
+
+    /// Fails if any hidden marker appears in the CBOR or JSON encoding of `value`.
+    fn assert_hidden<T: Serialize>(value: &T, markers: &[String], case: u64) {
+        let cbor = serde_cbor::to_vec(value).unwrap();
+        let json = serde_json::to_vec(value).unwrap();
+        for marker in markers {
+            for (encoding, bytes) in [("CBOR", &cbor), ("JSON", &json)] {
+                let leaked = bytes
+                    .windows(marker.len())
+                    .any(|window| window == marker.as_bytes());
+                assert!(!leaked, "case {case}: `{marker}` leaked in {encoding}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_redacted_players_never_reveal_their_hand() {
+        for case in 0..CASES {
+            let mut rng = ChaCha8Rng::seed_from_u64(case);
+            let (view, markers) = random_view(&mut rng, "red");
+            let public = redact(&view);
+
+            assert_hidden(&public, &markers, case);
+            assert_eq!(view.hand_size, public.hand_size, "case {case}");
+            assert_eq!(view.deck_size, public.deck_size, "case {case}");
+
+            let serde_json::Value::Object(fields) = serde_json::to_value(&public).unwrap() else {
+                panic!("case {case}: a redacted player is not a map");
+            };
+            for field in fields.keys() {
+                assert!(
+                    PUBLIC_FIELDS.contains(&field.as_str()),
+                    "case {case}: `{field}` is not a public field"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_spectators_never_see_a_hand() {
+        for case in 0..CASES {
+            let mut rng = ChaCha8Rng::seed_from_u64(case);
+            let mut markers = Vec::new();
+            let mut next_view = |seat| {
+                let (view, hidden) = random_view(&mut rng, seat);
+                markers.extend(hidden);
+                view
+            };
+            let state = PrivateGameStateView {
+                turn: case as u32,
+                red_player: next_view("red"),
+                blue_player: next_view("blue"),
+                partners: match case % 2 {
+                    0 => Vec::new(),
+                    _ => vec![next_view("green"), next_view("yellow")],
+                },
+            };
+
+            let public = redact_state(&state);
+            assert_hidden(&public, &markers, case);
+            assert_eq!(state.partners.len(), public.partners.len(), "case {case}");
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::game::event_log::{EventCounts, GameEvent};
+use crate::game::script_worker::ScriptWorker;
+use crate::models::ids::{MatchId, PlayerId};
+use crate::{logger, utils::logger::Logger};
+use serde::Serialize;
+
+/// Core script computing the rewards of a match. The hook is optional: without it, reports carry
+/// no rewards.
+pub const REWARDS_HOOK: &str = "core:match_rewards";
+
+/// What the rewards hook is given to work with.
+#[derive(Serialize, Debug, Clone)]
+pub struct RewardsInput {
+    pub match_id: MatchId,
+    pub match_type: String,
+    pub winner_id: Option<PlayerId>,
+    pub players: Vec<PlayerId>,
+    pub reason: String,
+    pub rounds: u32,
+    pub counts: EventCounts, // Events of each type over the whole match, overall and per player.
+    pub events: Vec<GameEvent>, // The latest events of the match, at most `MAX_EVENTS`, oldest first.
+}
+
+/// Runs the rewards hook on a match that ended.
+///
+/// Rewards never hold up the match report: when the hook fails, the failure is logged and the
+/// report is sent without rewards.
+///
+/// # Returns
+/// The value returned by the hook, or `None` if there is no hook or it failed.
+pub async fn compute_rewards(
+    scripts: &ScriptWorker,
+    input: &RewardsInput,
+) -> Option<serde_json::Value> {
+    match scripts.call_hook(REWARDS_HOOK, input).await {
+        Ok(rewards) => rewards,
+        Err(error) => {
+            logger!(
+                ERROR,
+                "[REWARDS] Could not compute the rewards of match `{}`: {error}",
+                &input.match_id
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::event_log::{GameEventKind, GameEventLog, MAX_EVENTS};
+    use crate::game::script_manager::ScriptManager;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_rewards_are_computed_from_the_event_log() {
+        let mut script_manager = ScriptManager::new_vm();
+        script_manager.load_scripts().unwrap();
+        script_manager.set_globals().await;
+
+        let card_played = |player_id: &str| GameEventKind::CardPlayed {
+            player_id: player_id.into(),
+            card_id: "fireball".into(),
+            target_id: None,
+        };
+        let mut log = GameEventLog::default();
+        for player_id in ["red", "red", "blue"] {
+            log.record(1, card_played(player_id));
+        }
+        let input = RewardsInput {
+            match_id: "match".into(),
+            match_type: "ranked".to_string(),
+            winner_id: Some("red".into()),
+            players: vec!["red".into(), "blue".into()],
+            reason: "A player was defeated".to_string(),
+            rounds: 1,
+            counts: log.counts().clone(),
+            events: log.since(0, MAX_EVENTS),
+        };
+
+        let scripts = ScriptWorker::spawn(script_manager);
+        let rewards = compute_rewards(&scripts, &input).await.unwrap();
+        assert_eq!(
+            json!({
+                "red": { "xp": 30, "won": true, "cards_played": 2 },
+                "blue": { "xp": 10, "won": false, "cards_played": 1 },
+            }),
+            rewards
+        );
+    }
+}
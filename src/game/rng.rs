@@ -0,0 +1,197 @@
+use mlua::{Lua, Table, Value};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// A value drawn by a card script, logged so a replay can check it draws the same values.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "function", rename_all = "snake_case")]
+pub enum RandomDraw {
+    /// `random_int(min, max)` returned `result`.
+    RandomInt { min: i64, max: i64, result: i64 },
+    /// `random_choice` picked the element at `index` (zero-based) of a list of `len` elements.
+    RandomChoice { len: usize, index: usize },
+    /// `shuffle` put the element at `order[i]` (zero-based) of the list in position `i`.
+    Shuffle { order: Vec<usize> },
+}
+
+/// The random number generator of a match.
+///
+/// Every random value of a match comes from this generator, so the seed alone is enough to replay
+/// it. ChaCha8 is used for its output being identical across platforms and releases.
+pub struct MatchRng {
+    rng: Mutex<ChaCha8Rng>,
+    draws: Mutex<Vec<RandomDraw>>, // Draws not yet written to the replay.
+}
+
+impl MatchRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Mutex::new(ChaCha8Rng::seed_from_u64(seed)),
+            draws: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Draws an integer between `min` and `max`, both included.
+    ///
+    /// # Returns
+    /// * `Ok(i64)` - The drawn integer.
+    /// * `Err(String)` - If `min` is greater than `max`.
+    pub fn random_int(&self, min: i64, max: i64) -> Result<i64, String> {
+        if min > max {
+            return Err(format!(
+                "random_int: min ({min}) is greater than max ({max})"
+            ));
+        }
+
+        let result = self.lock_rng().gen_range(min..=max);
+        self.log(RandomDraw::RandomInt { min, max, result });
+        Ok(result)
+    }
+
+    /// Draws the index of an element of a list of `len` elements.
+    ///
+    /// # Returns
+    /// * `Ok(usize)` - The zero-based index of the chosen element.
+    /// * `Err(String)` - If the list is empty.
+    pub fn random_choice(&self, len: usize) -> Result<usize, String> {
+        if len == 0 {
+            return Err(String::from("random_choice: the list is empty"));
+        }
+
+        let index = self.lock_rng().gen_range(0..len);
+        self.log(RandomDraw::RandomChoice { len, index });
+        Ok(index)
+    }
+
+    /// Shuffles a list in place.
+    pub fn shuffle<T>(&self, items: &mut Vec<T>) {
+        let mut order: Vec<usize> = (0..items.len()).collect();
+        order.shuffle(&mut *self.lock_rng());
+
+        let mut taken: Vec<Option<T>> = items.drain(..).map(Some).collect();
+        items.extend(order.iter().filter_map(|&i| taken[i].take()));
+        self.log(RandomDraw::Shuffle { order });
+    }
+
+    /// How far the generator has advanced since it was seeded.
+    pub fn cursor(&self) -> u128 {
+        self.lock_rng().get_word_pos()
+    }
+
+    /// Moves the generator back to a cursor, so the values drawn since are drawn again.
+    pub fn rewind(&self, cursor: u128) {
+        self.lock_rng().set_word_pos(cursor);
+    }
+
+    /// Removes and returns the draws made since the last call, oldest first.
+    pub fn take_draws(&self) -> Vec<RandomDraw> {
+        std::mem::take(&mut *self.draws.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Exposes `random_int(min, max)`, `random_choice(list)` and `shuffle(list)` to the scripts
+    /// run by a Lua VM. `shuffle` returns a shuffled copy and leaves the list untouched.
+    pub fn register(self: &Arc<Self>, lua: &Lua) -> Result<(), mlua::Error> {
+        let globals = lua.globals();
+
+        let rng = Arc::clone(self);
+        let random_int = lua.create_function(move |_, (min, max): (i64, i64)| {
+            rng.random_int(min, max).map_err(mlua::Error::RuntimeError)
+        })?;
+        globals.set("random_int", random_int)?;
+
+        let rng = Arc::clone(self);
+        let random_choice = lua.create_function(move |_, list: Table| {
+            let index = rng
+                .random_choice(list.raw_len())
+                .map_err(mlua::Error::RuntimeError)?;
+            list.raw_get::<Value>(index + 1)
+        })?;
+        globals.set("random_choice", random_choice)?;
+
+        let rng = Arc::clone(self);
+        let shuffle = lua.create_function(move |lua, list: Table| {
+            let mut items = list
+                .sequence_values::<Value>()
+                .collect::<Result<Vec<_>, _>>()?;
+            rng.shuffle(&mut items);
+            lua.create_sequence_from(items)
+        })?;
+        globals.set("shuffle", shuffle)?;
+
+        Ok(())
+    }
+
+    fn lock_rng(&self) -> std::sync::MutexGuard<'_, ChaCha8Rng> {
+        self.rng.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn log(&self, draw: RandomDraw) {
+        self.draws
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(draw);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_draws_the_same_values() {
+        let draw = |rng: &MatchRng| {
+            let mut deck: Vec<u32> = (0..10).collect();
+            rng.shuffle(&mut deck);
+            (
+                rng.random_int(1, 6).unwrap(),
+                rng.random_choice(4).unwrap(),
+                deck,
+            )
+        };
+
+        let (first, second) = (MatchRng::new(42), MatchRng::new(42));
+        assert_eq!(draw(&first), draw(&second));
+        assert_eq!(first.take_draws(), second.take_draws());
+        assert!(first.take_draws().is_empty());
+        assert!(first.random_int(3, 1).is_err());
+        assert!(first.random_choice(0).is_err());
+    }
+
+    #[test]
+    fn test_lua_functions_are_logged() {
+        let lua = Lua::new();
+        let rng = Arc::new(MatchRng::new(7));
+        rng.register(&lua).unwrap();
+
+        let (roll, pick, shuffled): (i64, String, Vec<String>) = lua
+            .load(
+                r#"
+                local list = { "a", "b", "c" }
+                return random_int(1, 6), random_choice(list), shuffle(list)
+                "#,
+            )
+            .eval()
+            .unwrap();
+
+        assert!((1..=6).contains(&roll));
+        assert!(["a", "b", "c"].contains(&pick.as_str()));
+        let mut sorted = shuffled.clone();
+        sorted.sort();
+        assert_eq!(vec!["a", "b", "c"], sorted);
+
+        let draws = rng.take_draws();
+        assert_eq!(3, draws.len());
+        assert_eq!(
+            RandomDraw::RandomInt {
+                min: 1,
+                max: 6,
+                result: roll
+            },
+            draws[0]
+        );
+        assert!(lua.load("return random_choice({})").exec().is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use crate::game::combat::CombatMode;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Undos each player may use in a friendly or casual match.
+pub const CASUAL_UNDO_LIMIT: u32 = 3;
+
+/// Suffix of the match types played with blockers, such as `ranked-blockers`.
+pub const BLOCKERS_SUFFIX: &str = "-blockers";
+
+/// Rules that differ between kinds of matches.
+///
+/// The matchmaker may send a profile with `InitServer`; otherwise it is picked from the match type.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RulesProfile {
+    #[serde(default)]
+    pub undo_limit: u32, // Plays each player may take back during the match; 0 disables undo.
+    #[serde(default)]
+    pub constrained_pool: bool, // Decks must be built from a card pool signed by the platform.
+    #[serde(default)]
+    pub combat: CombatMode, // Whether defenders may block attacking creatures.
+    #[serde(default = "default_cinematic_pause_ms")]
+    pub cinematic_pause_ms: u64, // Longest turn timer pause a single resolution may earn.
+    #[serde(default = "default_cinematic_pause_turn_ms")]
+    pub cinematic_pause_turn_ms: u64, // Longest total turn timer pause within one turn.
+    #[serde(default)]
+    pub disconnect_clock: DisconnectClock, // What the turn clock of a disconnected player does.
+    #[serde(default)]
+    pub turn_time_ms: u64, // Longest a turn may take, pauses left out; 0 leaves turns unlimited.
+}
+
+/// What happens to the turn clock of a player while they are disconnected.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DisconnectClock {
+    /// The clock keeps running, so leaving cannot be used to stall the turn.
+    #[default]
+    Run,
+    /// The clock stops until the player reconnects.
+    Pause,
+}
+
+impl Default for RulesProfile {
+    fn default() -> Self {
+        Self {
+            undo_limit: 0,
+            constrained_pool: false,
+            combat: CombatMode::default(),
+            cinematic_pause_ms: default_cinematic_pause_ms(),
+            cinematic_pause_turn_ms: default_cinematic_pause_turn_ms(),
+            disconnect_clock: DisconnectClock::default(),
+            turn_time_ms: 0,
+        }
+    }
+}
+
+impl RulesProfile {
+    /// The default rules of a match type: friendly and casual matches allow undoing plays and
+    /// pause the turn clock of disconnected players, and arena matches only accept decks built
+    /// from the pool of their run. Match types ending with `-blockers` are played with blockers.
+    pub fn for_match_type(match_type: &str) -> Self {
+        if let Some(base) = match_type.strip_suffix(BLOCKERS_SUFFIX) {
+            return Self {
+                combat: CombatMode::Blockers,
+                ..Self::for_match_type(base)
+            };
+        }
+
+        match match_type {
+            "friendly" | "casual" => Self {
+                undo_limit: CASUAL_UNDO_LIMIT,
+                disconnect_clock: DisconnectClock::Pause,
+                ..Self::default()
+            },
+            "arena" => Self {
+                constrained_pool: true,
+                ..Self::default()
+            },
+            _ => Self::default(),
+        }
+    }
+
+    /// Whether players may take back their plays at all.
+    pub fn allows_undo(&self) -> bool {
+        self.undo_limit > 0
+    }
+
+    /// How long a turn may take before the server ends it, if turns are timed at all.
+    pub fn turn_limit(&self) -> Option<Duration> {
+        match self.turn_time_ms {
+            0 => None,
+            millis => Some(Duration::from_millis(millis)),
+        }
+    }
+
+    /// The turn timer pause a resolution earns for the animations it plays on the clients.
+    pub fn cinematic_pause(&self, requested: Duration) -> Duration {
+        requested.min(Duration::from_millis(self.cinematic_pause_ms))
+    }
+}
+
+fn default_cinematic_pause_ms() -> u64 {
+    5_000
+}
+
+fn default_cinematic_pause_turn_ms() -> u64 {
+    15_000
+}
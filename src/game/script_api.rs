@@ -0,0 +1,384 @@
+use crate::game::entity::board::GraveyardView;
+use crate::game::game_state::PrivateGameStateView;
+use crate::game::redaction::{self, PublicPlayerView};
+use crate::models::game_action::GameAction;
+use crate::models::ids::PlayerId;
+use mlua::{Lua, LuaSerdeExt, Value};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// What a script sees and does through the `game` table during one call.
+pub struct ScriptSession {
+    players: Vec<PublicPlayerView>, // The players as the script sees them.
+    graveyards: Vec<(PlayerId, GraveyardView)>, // The graveyard of each player.
+    actions: Vec<GameAction>,       // Mutations requested so far, in order.
+}
+
+impl ScriptSession {
+    /// Starts a session on the game state a script is called with.
+    pub fn new(game_state: &PrivateGameStateView) -> Self {
+        let views: Vec<_> = [&game_state.red_player, &game_state.blue_player]
+            .into_iter()
+            .chain(&game_state.partners)
+            .collect();
+        Self {
+            players: views.iter().copied().map(redaction::redact).collect(),
+            graveyards: views
+                .iter()
+                .map(|view| (view.id.clone(), view.graveyard.clone()))
+                .collect(),
+            actions: Vec::new(),
+        }
+    }
+
+    /// Checks a mutation and queues it, reflecting its effect on player health and graveyards so
+    /// later queries of the same script see it.
+    ///
+    /// # Returns
+    /// * `Ok(())` - If the mutation was queued.
+    /// * `Err(String)` - Why the mutation is invalid.
+    fn queue(&mut self, action: GameAction) -> Result<(), String> {
+        match &action {
+            GameAction::DealDamage { target, amount } => {
+                self.check_target(target)?;
+                let amount = health_amount(*amount)?;
+                if let Some(player) = self.player_mut(target) {
+                    player.health = player.health.saturating_sub(amount);
+                }
+            }
+            GameAction::Heal { target, amount } => {
+                self.check_target(target)?;
+                let amount = health_amount(*amount)?;
+                if let Some(player) = self.player_mut(target) {
+                    player.health = player.health.saturating_add(amount);
+                }
+            }
+            GameAction::DestroyCard { target } if !self.on_board(target) => {
+                return Err(format!("`{target}` is not a card on the board"));
+            }
+            GameAction::ResurrectCard { target, .. }
+            | GameAction::ReturnFromGraveyard { target } => {
+                self.take_from_graveyard(target)?;
+            }
+            _ => {}
+        }
+
+        self.actions.push(action);
+        Ok(())
+    }
+
+    /// Checks that a target is a player or a card on a board, named by its instance id.
+    fn check_target(&self, target: &str) -> Result<(), String> {
+        let is_player = self
+            .players
+            .iter()
+            .any(|player| player.id.to_string() == target);
+        match is_player || self.on_board(target) {
+            true => Ok(()),
+            false => Err(format!(
+                "`{target}` is neither a player nor a card on the board"
+            )),
+        }
+    }
+
+    /// Whether a card instance is on a board.
+    fn on_board(&self, instance_id: &str) -> bool {
+        self.players.iter().any(|player| {
+            player
+                .board
+                .creatures
+                .iter()
+                .chain(&player.board.artifacts)
+                .chain(&player.board.enchantments)
+                .flatten()
+                .flat_map(|card| &card.instances)
+                .any(|id| id.to_string() == instance_id)
+        })
+    }
+
+    /// Removes a card instance from the graveyard holding it, so it cannot be brought back twice.
+    fn take_from_graveyard(&mut self, instance_id: &str) -> Result<(), String> {
+        for (_, graveyard) in &mut self.graveyards {
+            for pile in [
+                &mut graveyard.creatures,
+                &mut graveyard.artifacts,
+                &mut graveyard.enchantments,
+                &mut graveyard.spells,
+            ] {
+                let index = pile.iter().position(|card| {
+                    card.instances
+                        .iter()
+                        .any(|id| id.to_string() == instance_id)
+                });
+                if let Some(index) = index {
+                    pile.remove(index);
+                    return Ok(());
+                }
+            }
+        }
+
+        Err(format!("`{instance_id}` is not in a graveyard"))
+    }
+
+    fn player_mut(&mut self, player_id: &str) -> Option<&mut PublicPlayerView> {
+        self.players
+            .iter_mut()
+            .find(|player| player.id.to_string() == player_id)
+    }
+}
+
+/// Converts an amount of damage or healing to a change of health.
+fn health_amount(amount: u32) -> Result<i32, String> {
+    i32::try_from(amount).map_err(|_| format!("{amount} is too large an amount of health"))
+}
+
+/// The `game` table offered to card scripts, so they can interleave queries and mutations
+/// instead of returning every action at once.
+///
+/// Functions do not touch the game state: mutations are checked and queued as `GameAction`s,
+/// which are applied with the actions the script returns, through the same path.
+///
+/// * `game.deal_damage(target, amount)` and `game.heal(target, amount)` - A player or a creature.
+/// * `game.destroy(target)` and `game.discard(target)` - Send a card to its owner's graveyard.
+/// * `game.resurrect(target [, position])` and `game.return_to_hand(target)`
+/// * `game.query_board([player_id])` - One player, or every player when omitted.
+/// * `game.query_graveyard([player_id])` - One graveyard, or every graveyard by player id.
+#[derive(Default)]
+pub struct ScriptApi {
+    session: Mutex<Option<ScriptSession>>, // The session of the script running, if any.
+}
+
+impl ScriptApi {
+    /// Starts the session of a script about to run.
+    pub fn begin(&self, session: ScriptSession) {
+        *self.session.lock().unwrap_or_else(|e| e.into_inner()) = Some(session);
+    }
+
+    /// Ends the session of the script that ran.
+    ///
+    /// # Returns
+    /// The mutations the script requested, in order.
+    pub fn finish(&self) -> Vec<GameAction> {
+        self.session
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+            .map(|session| session.actions)
+            .unwrap_or_default()
+    }
+
+    /// Runs `f` on the session of the running script.
+    fn with_session<R>(
+        &self,
+        f: impl FnOnce(&mut ScriptSession) -> Result<R, String>,
+    ) -> mlua::Result<R> {
+        let mut session = self.session.lock().unwrap_or_else(|e| e.into_inner());
+        match session.as_mut() {
+            Some(session) => f(session).map_err(mlua::Error::RuntimeError),
+            None => Err(mlua::Error::RuntimeError(String::from(
+                "the game API is only available while a card script runs",
+            ))),
+        }
+    }
+
+    fn queue(&self, action: GameAction) -> mlua::Result<()> {
+        self.with_session(|session| session.queue(action))
+    }
+
+    /// Creates the `game` table in a Lua VM.
+    pub fn register(self: &Arc<Self>, lua: &Lua) -> Result<(), mlua::Error> {
+        let game = lua.create_table()?;
+
+        let api = Arc::clone(self);
+        let deal_damage = lua.create_function(move |_, (target, amount): (String, u32)| {
+            api.queue(GameAction::DealDamage { target, amount })
+        })?;
+        game.set("deal_damage", deal_damage)?;
+
+        let api = Arc::clone(self);
+        let heal = lua.create_function(move |_, (target, amount): (String, u32)| {
+            api.queue(GameAction::Heal { target, amount })
+        })?;
+        game.set("heal", heal)?;
+
+        let api = Arc::clone(self);
+        let destroy = lua.create_function(move |_, target: String| {
+            api.queue(GameAction::DestroyCard { target })
+        })?;
+        game.set("destroy", destroy)?;
+
+        let api = Arc::clone(self);
+        let discard = lua.create_function(move |_, target: String| {
+            api.queue(GameAction::DiscardCard { target })
+        })?;
+        game.set("discard", discard)?;
+
+        let api = Arc::clone(self);
+        let resurrect =
+            lua.create_function(move |_, (target, position): (String, Option<String>)| {
+                api.queue(GameAction::ResurrectCard { target, position })
+            })?;
+        game.set("resurrect", resurrect)?;
+
+        let api = Arc::clone(self);
+        let return_to_hand = lua.create_function(move |_, target: String| {
+            api.queue(GameAction::ReturnFromGraveyard { target })
+        })?;
+        game.set("return_to_hand", return_to_hand)?;
+
+        let api = Arc::clone(self);
+        let play_cinematic =
+            lua.create_function(move |_, (name, duration_ms): (String, u64)| {
+                api.queue(GameAction::PlayCinematic { name, duration_ms })
+            })?;
+        game.set("play_cinematic", play_cinematic)?;
+
+        let api = Arc::clone(self);
+        let query_board = lua.create_function(move |lua, player_id: Option<String>| {
+            let players = api.with_session(|session| Ok(session.players.clone()))?;
+            match player_id {
+                None => lua.to_value(&players),
+                Some(player_id) => match players.iter().find(|p| p.id.to_string() == player_id) {
+                    Some(player) => lua.to_value(player),
+                    None => Ok(Value::Nil),
+                },
+            }
+        })?;
+        game.set("query_board", query_board)?;
+
+        let api = Arc::clone(self);
+        let query_graveyard = lua.create_function(move |lua, player_id: Option<String>| {
+            let graveyards = api.with_session(|session| Ok(session.graveyards.clone()))?;
+            match player_id {
+                None => {
+                    let by_player: BTreeMap<_, _> = graveyards
+                        .iter()
+                        .map(|(id, graveyard)| (id.to_string(), graveyard))
+                        .collect();
+                    lua.to_value(&by_player)
+                }
+                Some(player_id) => match graveyards
+                    .iter()
+                    .find(|(id, _)| id.to_string() == player_id)
+                {
+                    Some((_, graveyard)) => lua.to_value(graveyard),
+                    None => Ok(Value::Nil),
+                },
+            }
+        })?;
+        game.set("query_graveyard", query_graveyard)?;
+
+        lua.globals().set("game", game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entity::card::{CardRef, CardType};
+    use crate::game::entity::player::PlayerView;
+    use crate::game::graveyard;
+    use crate::models::ids::CardInstanceId;
+
+    fn start(lua: &Lua) -> Arc<ScriptApi> {
+        let api = Arc::new(ScriptApi::default());
+        api.register(lua).unwrap();
+        api.begin(ScriptSession::new(&PrivateGameStateView {
+            turn: 1,
+            red_player: PlayerView::from_player(&"red".into(), 30),
+            blue_player: PlayerView::from_player(&"blue".into(), 30),
+            partners: Vec::new(),
+        }));
+        api
+    }
+
+    #[test]
+    fn test_queries_see_earlier_mutations() {
+        let lua = Lua::new();
+        let api = start(&lua);
+
+        let health: i32 = lua
+            .load(
+                r#"
+                game.deal_damage("blue", 5)
+                game.heal("red", 2)
+                return game.query_board("blue").health
+                "#,
+            )
+            .eval()
+            .unwrap();
+
+        assert_eq!(25, health);
+        assert_eq!(
+            vec![
+                GameAction::DealDamage {
+                    target: "blue".to_string(),
+                    amount: 5
+                },
+                GameAction::Heal {
+                    target: "red".to_string(),
+                    amount: 2
+                },
+            ],
+            api.finish()
+        );
+    }
+
+    #[test]
+    fn test_invalid_mutations_are_rejected() {
+        let lua = Lua::new();
+        let api = start(&lua);
+
+        assert!(lua.load(r#"game.deal_damage("nobody", 5)"#).exec().is_err());
+        assert!(lua.load(r#"game.heal("red", -3)"#).exec().is_err());
+        assert!(lua.load(r#"game.heal("green", 3)"#).exec().is_err());
+        let overflow = r#"game.deal_damage("red", 4294967295)"#;
+        assert!(lua.load(overflow).exec().is_err());
+        assert!(api.finish().is_empty());
+        assert!(lua.load(r#"game.query_board()"#).exec().is_err());
+    }
+
+    #[test]
+    fn test_cards_come_back_from_graveyards_once() {
+        let lua = Lua::new();
+        let api = Arc::new(ScriptApi::default());
+        api.register(&lua).unwrap();
+
+        let mut red = PlayerView::from_player(&"red".into(), 30);
+        let wolf = CardRef {
+            id: "wolf".into(),
+            amount: 1,
+            owner_id: None,
+            instances: vec![CardInstanceId::nth(4)],
+        };
+        graveyard::bury(&mut red, wolf, CardType::Creature);
+        api.begin(ScriptSession::new(&PrivateGameStateView {
+            turn: 1,
+            red_player: red,
+            blue_player: PlayerView::from_player(&"blue".into(), 30),
+            partners: Vec::new(),
+        }));
+
+        let left: usize = lua
+            .load(
+                r#"
+                local dead = game.query_graveyard("red").creatures[1]
+                game.resurrect(dead.instances[1], "creature:0")
+                return #game.query_graveyard().red.creatures
+                "#,
+            )
+            .eval()
+            .unwrap();
+
+        assert_eq!(0, left);
+        assert!(lua.load(r#"game.return_to_hand("c4")"#).exec().is_err());
+        assert!(lua.load(r#"game.destroy("c4")"#).exec().is_err());
+        assert_eq!(
+            vec![GameAction::ResurrectCard {
+                target: "c4".to_string(),
+                position: Some("creature:0".to_string()),
+            }],
+            api.finish()
+        );
+    }
+}
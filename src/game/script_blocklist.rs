@@ -0,0 +1,172 @@
+use crate::models::ids::CardDefId;
+use crate::HTTP_SERVICE;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::path::Path;
+
+/// An entry of the blocklist: every script of a card, or one script function wherever it is used.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockedScript {
+    Card(CardDefId),
+    Function(String),
+}
+
+impl BlockedScript {
+    /// Parses an entry typed by an operator, `card <card_id>` or `function <category:name>`.
+    pub fn parse(kind: &str, name: &str) -> Result<Self, String> {
+        match kind {
+            "card" => Ok(BlockedScript::Card(name.into())),
+            "function" => Ok(BlockedScript::Function(name.to_string())),
+            other => Err(format!("Expected `card` or `function`, got `{other}`")),
+        }
+    }
+}
+
+impl Display for BlockedScript {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockedScript::Card(card_id) => write!(f, "card `{card_id}`"),
+            BlockedScript::Function(function) => write!(f, "function `{function}`"),
+        }
+    }
+}
+
+/// Card scripts switched off by operators, so a script found to crash or be exploited can be
+/// disabled without a redeploy.
+///
+/// The triggers of a blocked card, and every call of a blocked function, are skipped as if the
+/// card had no script. The blocklist is kept in a JSON file so it survives restarts, and may also
+/// be fetched from a config service when a match is created.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ScriptBlocklist {
+    #[serde(default)]
+    pub cards: BTreeSet<CardDefId>, // Cards none of whose scripts run.
+    #[serde(default)]
+    pub functions: BTreeSet<String>, // Script functions, as `category:name`, that never run.
+}
+
+impl ScriptBlocklist {
+    /// Whether a script of a card must be skipped.
+    ///
+    /// # Arguments
+    /// * `card_id` - The card whose trigger runs the script.
+    /// * `function` - The script function, as `category:name`.
+    pub fn blocks(&self, card_id: &CardDefId, function: &str) -> bool {
+        self.cards.contains(card_id) || self.functions.contains(function)
+    }
+
+    /// Adds an entry.
+    ///
+    /// # Returns
+    /// `false` if the entry was already blocked.
+    pub fn insert(&mut self, entry: BlockedScript) -> bool {
+        match entry {
+            BlockedScript::Card(card_id) => self.cards.insert(card_id),
+            BlockedScript::Function(function) => self.functions.insert(function),
+        }
+    }
+
+    /// Removes an entry.
+    ///
+    /// # Returns
+    /// `false` if the entry was not blocked.
+    pub fn remove(&mut self, entry: &BlockedScript) -> bool {
+        match entry {
+            BlockedScript::Card(card_id) => self.cards.remove(card_id),
+            BlockedScript::Function(function) => self.functions.remove(function),
+        }
+    }
+
+    /// Adds every entry of another blocklist.
+    pub fn merge(&mut self, other: ScriptBlocklist) {
+        self.cards.extend(other.cards);
+        self.functions.extend(other.functions);
+    }
+
+    /// Reads the blocklist kept in a file; a missing file is an empty blocklist.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Writes the blocklist to a file, replacing its content.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    /// Fetches the blocklist published by a config service.
+    ///
+    /// # Returns
+    /// * `Ok(ScriptBlocklist)` - The blocklist served at `url`.
+    /// * `Err(String)` - If the service could not be reached or answered something else.
+    pub async fn fetch(url: &str) -> Result<Self, String> {
+        let response = HTTP_SERVICE
+            .client()
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("{url} answered {}", response.status()));
+        }
+
+        response.json().await.map_err(|e| e.to_string())
+    }
+}
+
+impl Display for ScriptBlocklist {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.cards.is_empty() && self.functions.is_empty() {
+            return write!(f, "no script is blocked");
+        }
+
+        let cards: Vec<_> = self.cards.iter().map(|id| id.to_string()).collect();
+        let functions: Vec<_> = self.functions.iter().cloned().collect();
+        write!(
+            f,
+            "cards: [{}], functions: [{}]",
+            cards.join(", "),
+            functions.join(", ")
+        )
+    }
+}
+
+/// Sent to every player in a `ScriptSkipped` packet when a blocked script did not run.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SkippedScript {
+    pub card_id: CardDefId, // The card whose trigger was skipped.
+    pub event: String,      // The trigger, such as `on_play`.
+    pub function: String,   // The blocked script function.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocklist_survives_restarts() {
+        let path = std::env::temp_dir().join(format!("blocklist-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(
+            ScriptBlocklist::default(),
+            ScriptBlocklist::load(&path).unwrap()
+        );
+
+        let mut blocklist = ScriptBlocklist::default();
+        assert!(blocklist.insert(BlockedScript::parse("card", "wolf").unwrap()));
+        assert!(blocklist.insert(BlockedScript::parse("function", "cards:fireball").unwrap()));
+        assert!(!blocklist.insert(BlockedScript::Card("wolf".into())));
+        blocklist.save(&path).unwrap();
+
+        let loaded = ScriptBlocklist::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.blocks(&"wolf".into(), "cards:howl"));
+        assert!(loaded.blocks(&"mage".into(), "cards:fireball"));
+        assert!(!loaded.blocks(&"mage".into(), "cards:frostbolt"));
+        assert!(BlockedScript::parse("spell", "fireball").is_err());
+    }
+}
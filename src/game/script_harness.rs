@@ -0,0 +1,298 @@
+use crate::game::entity::card::{Card, CardView};
+use crate::game::entity::player::PlayerView;
+use crate::game::event_log::GameEvent;
+use crate::game::game_state::PrivateGameStateView;
+use crate::game::lua_context::LuaContext;
+use crate::game::rng::MatchRng;
+use crate::game::script_manager::{ScriptLimits, ScriptManager};
+use crate::models::game_action::GameAction;
+use crate::models::ids::{CardInstanceId, PlayerId};
+use crate::utils::errors::ScriptTestError;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Usage of the `test-scripts` subcommand.
+pub const USAGE: &str = "usage: tcp-server test-scripts [<fixture.json|directory>...]";
+
+/// Where the fixtures are read from when none is given.
+pub const FIXTURES_DIR: &str = "./scripts/tests";
+
+/// A card and the cases its scripts are tested against, as written in a fixture file.
+///
+/// The card is written as the card service serves it, so its triggers name the scripts tested.
+#[derive(Debug, Deserialize)]
+pub struct ScriptFixture {
+    pub card: Card,
+    pub cases: Vec<ScriptCase>,
+}
+
+/// A call of a trigger of the card, in a synthetic match, and what its scripts should return.
+///
+/// The match is between `red`, who owns the card, and `blue`, both fresh unless given.
+#[derive(Debug, Deserialize)]
+pub struct ScriptCase {
+    pub name: String,
+    pub trigger: String, // The trigger of the card to call, such as `on_play`.
+    #[serde(default)]
+    pub target_id: Option<String>,
+    #[serde(default)]
+    pub actor: Option<CardView>, // The card running the scripts; a fresh copy of `red` if unset.
+    #[serde(default = "default_turn")]
+    pub turn: u32,
+    #[serde(default)]
+    pub red_player: Option<PlayerView>,
+    #[serde(default)]
+    pub blue_player: Option<PlayerView>,
+    #[serde(default)]
+    pub turn_events: Vec<GameEvent>,
+    #[serde(default)]
+    pub vars: BTreeMap<String, serde_json::Value>, // Match variables of the card.
+    #[serde(default)]
+    pub seed: u64, // Seed of `random_int`, `random_choice` and `shuffle`.
+    #[serde(default)]
+    pub expect: Vec<GameAction>, // Every action of the scripts of the trigger, in order.
+    #[serde(default)]
+    pub error: Option<String>, // Part of the error the scripts fail with, instead of `expect`.
+}
+
+fn default_turn() -> u32 {
+    1
+}
+
+impl ScriptCase {
+    /// Builds the context a script of the trigger is called with.
+    fn context(&self, card: &Card, function: &str) -> LuaContext {
+        let red = PlayerId::from("red");
+        let actor = self
+            .actor
+            .clone()
+            .unwrap_or_else(|| CardView::create_view(card, red.clone(), CardInstanceId::nth(1)));
+        let player = |view: &Option<PlayerView>, id: &PlayerId| {
+            view.clone()
+                .unwrap_or_else(|| PlayerView::from_player(id, 30))
+        };
+
+        LuaContext {
+            event: self.trigger.clone(),
+            action_name: function.to_string(),
+            actor_id: actor.id.clone(),
+            actor_view: actor,
+            target_id: self.target_id.clone(),
+            target_view: None,
+            game_state: PrivateGameStateView {
+                turn: self.turn,
+                red_player: player(&self.red_player, &red),
+                blue_player: player(&self.blue_player, &PlayerId::from("blue")),
+                partners: Vec::new(),
+            },
+            turn_events: self.turn_events.clone(),
+            vars: self.vars.clone(),
+        }
+    }
+
+    /// Calls the scripts of the trigger in the order the card lists them.
+    ///
+    /// Every script sees the same match: the actions of a script are not applied before the next
+    /// one runs.
+    ///
+    /// # Returns
+    /// * `Ok(Vec<GameAction>)` - The actions of every script.
+    /// * `Err(String)` - Why the first failing script failed.
+    async fn run(
+        &self,
+        script_manager: &ScriptManager,
+        card: &Card,
+    ) -> Result<Vec<GameAction>, String> {
+        let rng = Arc::new(MatchRng::new(self.seed));
+        rng.register(&script_manager.lua)
+            .map_err(|error| error.to_string())?;
+
+        let mut actions = Vec::new();
+        for function in card.trigger(&self.trigger).unwrap_or_default() {
+            let context = self.context(card, function);
+            let returned = script_manager.call_function_ctx(function, context).await;
+            actions.extend(returned.map_err(|error| error.to_string())?);
+        }
+        Ok(actions)
+    }
+
+    /// Runs the case and compares the outcome with the expected one.
+    ///
+    /// # Returns
+    /// * `Ok(())` - If the scripts returned the expected actions, or failed as expected.
+    /// * `Err(String)` - What the scripts did instead.
+    pub async fn check(&self, script_manager: &ScriptManager, card: &Card) -> Result<(), String> {
+        let json = |actions: &[GameAction]| serde_json::to_string(actions).unwrap_or_default();
+        match (self.run(script_manager, card).await, &self.error) {
+            (Ok(actions), None) if actions == self.expect => Ok(()),
+            (Ok(actions), None) => Err(format!(
+                "expected {}, got {}",
+                json(&self.expect),
+                json(&actions)
+            )),
+            (Ok(actions), Some(expected)) => Err(format!(
+                "expected an error containing `{expected}`, got {}",
+                json(&actions)
+            )),
+            (Err(error), Some(expected)) if error.contains(expected.as_str()) => Ok(()),
+            (Err(error), _) => Err(format!("failed: {error}")),
+        }
+    }
+}
+
+/// Reads the fixtures of a file, or of every `.json` file of a directory, in name order.
+///
+/// # Returns
+/// * `Ok(Vec<(String, ScriptFixture)>)` - The fixtures, with the file they were read from.
+/// * `Err(ScriptTestError)` - `InvalidFixture` if a file cannot be read or parsed, or a case calls
+///   a trigger cards do not have.
+pub fn load_fixtures(path: &Path) -> Result<Vec<(String, ScriptFixture)>, ScriptTestError> {
+    let invalid = |path: &Path, error: String| {
+        ScriptTestError::InvalidFixture(path.display().to_string(), error)
+    };
+
+    let files = match path.is_dir() {
+        true => {
+            let entries = fs::read_dir(path).map_err(|e| invalid(path, e.to_string()))?;
+            let mut files: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|file| {
+                    file.extension()
+                        .is_some_and(|extension| extension == "json")
+                })
+                .collect();
+            files.sort();
+            files
+        }
+        false => vec![path.to_path_buf()],
+    };
+
+    let mut fixtures = Vec::with_capacity(files.len());
+    for file in files {
+        let contents = fs::read_to_string(&file).map_err(|e| invalid(&file, e.to_string()))?;
+        let fixture: ScriptFixture =
+            serde_json::from_str(&contents).map_err(|e| invalid(&file, e.to_string()))?;
+        if let Some(case) = fixture
+            .cases
+            .iter()
+            .find(|case| fixture.card.trigger(&case.trigger).is_none())
+        {
+            let error = format!(
+                "case `{}` calls unknown trigger `{}`",
+                case.name, case.trigger
+            );
+            return Err(invalid(&file, error));
+        }
+        fixtures.push((file.display().to_string(), fixture));
+    }
+    Ok(fixtures)
+}
+
+/// Runs the `test-scripts` subcommand: loads the scripts of `./scripts` as a match does, runs
+/// every case of the fixtures given, or of `FIXTURES_DIR`, and prints the result of each.
+///
+/// # Returns
+/// * `Ok(())` - If every case passed.
+/// * `Err(ScriptTestError)` - `Failed` if some case failed, or why the tests could not run.
+pub async fn run(args: impl IntoIterator<Item = String>) -> Result<(), ScriptTestError> {
+    let mut paths: Vec<PathBuf> = args.into_iter().map(PathBuf::from).collect();
+    if paths.is_empty() {
+        paths.push(PathBuf::from(FIXTURES_DIR));
+    }
+
+    let mut fixtures = Vec::new();
+    for path in &paths {
+        let loaded = load_fixtures(path)?;
+        if loaded.is_empty() {
+            return Err(ScriptTestError::NoFixtures(path.display().to_string()));
+        }
+        fixtures.extend(loaded);
+    }
+
+    let mut script_manager = ScriptManager::with_limits(ScriptLimits::default());
+    script_manager
+        .load_scripts()
+        .map_err(|error| ScriptTestError::ScriptsUnavailable(error.to_string()))?;
+    script_manager.set_globals().await;
+
+    let (mut total, mut failed) = (0, 0);
+    for (file, fixture) in &fixtures {
+        println!("{file} ({})", fixture.card.id);
+        for case in &fixture.cases {
+            total += 1;
+            match case.check(&script_manager, &fixture.card).await {
+                Ok(()) => println!("  ok    {}", case.name),
+                Err(reason) => {
+                    failed += 1;
+                    println!("  FAIL  {}: {reason}", case.name);
+                }
+            }
+        }
+    }
+
+    println!("{} passed, {failed} failed", total - failed);
+    match failed {
+        0 => Ok(()),
+        failed => Err(ScriptTestError::Failed(failed, total)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(cases: serde_json::Value) -> ScriptFixture {
+        serde_json::from_value(serde_json::json!({
+            "card": {
+                "id": "tester", "name": "Tester", "description": "", "play_cost": 1,
+                "attack": 1, "health": 1, "rarity": 0, "on_play": ["core:test"], "on_draw": [],
+                "on_attack": [], "on_hit": [], "on_turn_start": [], "on_turn_end": [],
+                "on_death": [], "on_ally_death": [], "on_enemy_death": [],
+            },
+            "cases": cases,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cases_compare_the_actions_of_the_trigger() {
+        let mut script_manager = ScriptManager::with_limits(ScriptLimits::default());
+        script_manager.load_scripts().unwrap();
+        script_manager.set_globals().await;
+
+        let fixture = fixture(serde_json::json!([
+            {
+                "name": "passes",
+                "trigger": "on_play",
+                "expect": [
+                    { "type": "DealDamage", "target": "None", "amount": 10 },
+                    { "type": "Heal", "target": "None", "amount": 10 },
+                ],
+            },
+            {
+                "name": "expects other actions",
+                "trigger": "on_play",
+                "expect": [{ "type": "DealDamage", "target": "None", "amount": 10 }],
+            },
+            { "name": "runs nothing", "trigger": "on_draw" },
+            { "name": "expects an error", "trigger": "on_play", "error": "was not found" },
+        ]));
+
+        let mut results = Vec::new();
+        for case in &fixture.cases {
+            results.push(case.check(&script_manager, &fixture.card).await);
+        }
+        assert!(results[0].is_ok());
+        assert!(results[1].as_ref().unwrap_err().starts_with("expected"));
+        assert!(results[2].is_ok());
+        assert!(results[3].is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bundled_fixtures_pass() {
+        assert!(run(Vec::new()).await.is_ok());
+    }
+}
@@ -0,0 +1,239 @@
+use serde::Serialize;
+use std::fmt::Display;
+
+/// A global or library function scripts should no longer call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Deprecation {
+    pub name: &'static str,        // Dotted path of the deprecated function, such as `table.getn`.
+    pub replacement: &'static str, // What to use instead.
+}
+
+/// Every deprecated API known to the linter.
+///
+/// Lua 5.1 functions removed from Lua 5.4 come first; deprecated helpers of the card scripting
+/// API belong here as well when they are replaced.
+pub const DEPRECATIONS: &[Deprecation] = &[
+    Deprecation {
+        name: "unpack",
+        replacement: "`table.unpack`",
+    },
+    Deprecation {
+        name: "loadstring",
+        replacement: "`load`",
+    },
+    Deprecation {
+        name: "setfenv",
+        replacement: "an `_ENV` upvalue",
+    },
+    Deprecation {
+        name: "getfenv",
+        replacement: "an `_ENV` upvalue",
+    },
+    Deprecation {
+        name: "module",
+        replacement: "a table returned by the script",
+    },
+    Deprecation {
+        name: "table.getn",
+        replacement: "the `#` operator",
+    },
+    Deprecation {
+        name: "table.maxn",
+        replacement: "a loop over `pairs`",
+    },
+    Deprecation {
+        name: "math.pow",
+        replacement: "the `^` operator",
+    },
+    Deprecation {
+        name: "math.ldexp",
+        replacement: "`x * 2.0 ^ exp`",
+    },
+];
+
+/// A use of a deprecated API found in a script.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LintWarning {
+    pub file: String,
+    pub line: usize,
+    pub name: String,
+    pub replacement: String,
+}
+
+impl Display for LintWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: `{}` is deprecated, use {} instead",
+            self.file, self.line, self.name, self.replacement
+        )
+    }
+}
+
+/// Finds the uses of deprecated APIs in a script.
+///
+/// The script is scanned rather than run, so functions are reported even on branches that never
+/// execute. Strings and comments are skipped, and fields of other tables (`deck.unpack`) are not
+/// mistaken for the global of the same name.
+///
+/// # Arguments
+/// * `file` - The name of the script, for the warnings.
+/// * `code` - The source of the script.
+pub fn lint_script(file: &str, code: &str) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+    for (name, line) in identifier_chains(code) {
+        let deprecation = DEPRECATIONS.iter().find(|d| d.name == name);
+        if let Some(deprecation) = deprecation {
+            warnings.push(LintWarning {
+                file: file.to_string(),
+                line,
+                name,
+                replacement: deprecation.replacement.to_string(),
+            });
+        }
+    }
+    warnings
+}
+
+/// Splits Lua source into dotted identifier chains (`table.getn`, `deck`) with their line,
+/// skipping strings and comments.
+fn identifier_chains(code: &str) -> Vec<(String, usize)> {
+    let chars: Vec<char> = code.chars().collect();
+    let mut chains = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\n' => {
+                line += 1;
+                i += 1;
+            }
+            '-' if chars.get(i + 1) == Some(&'-') => {
+                i += 2;
+                match long_bracket_level(&chars, i) {
+                    Some(level) => i = skip_long_bracket(&chars, i, level, &mut line),
+                    None => {
+                        while i < chars.len() && chars[i] != '\n' {
+                            i += 1;
+                        }
+                    }
+                }
+            }
+            '[' => match long_bracket_level(&chars, i) {
+                Some(level) => i = skip_long_bracket(&chars, i, level, &mut line),
+                None => i += 1,
+            },
+            '"' | '\'' => {
+                i += 1;
+                while i < chars.len() && chars[i] != c && chars[i] != '\n' {
+                    if chars[i] == '\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                i += 1;
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                // Fields and methods of other values are not globals.
+                let is_field = chars[..i]
+                    .iter()
+                    .rev()
+                    .find(|c| !c.is_whitespace())
+                    .is_some_and(|c| *c == '.' || *c == ':');
+
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_ascii_alphanumeric()
+                        || chars[i] == '_'
+                        || (chars[i] == '.'
+                            && chars
+                                .get(i + 1)
+                                .is_some_and(|n| n.is_ascii_alphabetic() || *n == '_')))
+                {
+                    i += 1;
+                }
+
+                if !is_field {
+                    chains.push((chars[start..i].iter().collect(), line));
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    chains
+}
+
+/// The level of a long bracket (`[[`, `[==[`) opening at `i`, if there is one.
+fn long_bracket_level(chars: &[char], i: usize) -> Option<usize> {
+    if chars.get(i) != Some(&'[') {
+        return None;
+    }
+
+    let level = chars[i + 1..].iter().take_while(|c| **c == '=').count();
+    (chars.get(i + 1 + level) == Some(&'[')).then_some(level)
+}
+
+/// Skips a long bracket opening at `i`, returning the position after its closing bracket.
+fn skip_long_bracket(chars: &[char], i: usize, level: usize, line: &mut usize) -> usize {
+    let closing: Vec<char> = std::iter::once(']')
+        .chain(std::iter::repeat_n('=', level))
+        .chain(std::iter::once(']'))
+        .collect();
+
+    let mut i = i + level + 2;
+    while i < chars.len() {
+        if chars[i..].starts_with(&closing) {
+            return i + closing.len();
+        }
+        if chars[i] == '\n' {
+            *line += 1;
+        }
+        i += 1;
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deprecated_calls_are_reported_with_their_line() {
+        let code = r#"
+function on_play(ctx)
+    local a, b = unpack(ctx.targets)
+    local n = table.getn(ctx.hand)
+    return deck.unpack(a), math.floor(n)
+end
+"#;
+        let warnings = lint_script("on_play.lua", code);
+        let found: Vec<_> = warnings.iter().map(|w| (w.name.as_str(), w.line)).collect();
+
+        assert_eq!(vec![("unpack", 3), ("table.getn", 4)], found);
+        assert_eq!(
+            "on_play.lua:3: `unpack` is deprecated, use `table.unpack` instead",
+            warnings[0].to_string()
+        );
+    }
+
+    #[test]
+    fn test_strings_and_comments_are_skipped() {
+        let code = r#"
+-- unpack(old)
+--[[ loadstring("x")
+     math.pow(2, 3) ]]
+local message = "call unpack() here"
+local long = [==[ setfenv ]==]
+return loadstring
+"#;
+        let warnings = lint_script("core.lua", code);
+        assert_eq!(1, warnings.len());
+        assert_eq!(
+            ("loadstring", 7),
+            (warnings[0].name.as_str(), warnings[0].line)
+        );
+    }
+}
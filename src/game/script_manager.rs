@@ -2,61 +2,338 @@ use std::{
     collections::HashMap,
     ffi::OsStr,
     fs,
-    io::{BufRead, BufReader, Error},
-    path::PathBuf,
-    sync::Arc,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
 use crate::game::lua_context::LuaContext;
+use crate::game::script_api::{ScriptApi, ScriptSession};
+use crate::game::script_blocklist::ScriptBlocklist;
+use crate::models::ids::CardDefId;
+use crate::game::script_lint;
+use crate::game::script_manifest::{ScriptCategory, ScriptManifest, MANIFEST_PATH};
 use crate::logger;
 use crate::models::game_action::GameAction;
-use crate::utils::errors::GameLogicError;
+use crate::models::settings::Settings;
+use crate::utils::errors::{GameLogicError, ScriptLoadError};
 use crate::utils::logger::Logger;
-use mlua::{Function, Lua, LuaSerdeExt, Value};
-use tokio::sync::Mutex;
+use crate::SETTINGS;
+use mlua::{
+    Function, HookTriggers, IntoLuaMulti, Lua, LuaOptions, LuaSerdeExt, StdLib, Table,
+    ThreadStatus, Value, VmState,
+};
+use tokio::sync::{Mutex, RwLock};
+
+/// Instructions run between two checks of a script's limits.
+const HOOK_INTERVAL: u32 = 1000;
+
+/// Base library functions removed from the sandbox, since they reach the filesystem.
+const REMOVED_GLOBALS: [&str; 2] = ["dofile", "loadfile"];
+
+/// Folders of `./scripts` whose scripts define global functions, called as `<folder>:<name>`.
+const SHARED_FOLDERS: [&str; 3] = ["core", "effects", "triggers"];
+
+/// Folder of `./scripts` holding one script per card, named after the card id.
+const CARDS_FOLDER: &str = "cards";
+
+/// A script run in its own environment, whose functions are not registered yet.
+struct LoadedScript {
+    path: PathBuf,
+    card: bool, // Whether its functions stay in the namespace of its card.
+    env: Table, // The globals the script defined.
+}
+
+/// Resources a card script may use, so a buggy script cannot hang or exhaust the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptLimits {
+    pub instructions: u64,      // Lua instructions a single call may run.
+    pub timeout: Duration,      // Wall-clock time a single call may run.
+    pub memory: usize,          // Bytes the whole VM may allocate.
+    pub call_timeout: Duration, // Wall-clock time a caller waits for the worker, see `ScriptWorker`.
+}
+
+impl ScriptLimits {
+    /// Builds the limits from the settings, using the defaults when they are not loaded.
+    pub fn from_settings(settings: Option<&Settings>) -> Self {
+        match settings {
+            Some(settings) => Self {
+                instructions: settings.lua_instruction_limit,
+                timeout: Duration::from_millis(settings.lua_timeout),
+                memory: settings.lua_memory_limit,
+                call_timeout: Duration::from_millis(settings.script_call_timeout),
+            },
+            None => Self::default(),
+        }
+    }
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            instructions: 10_000_000,
+            timeout: Duration::from_millis(100),
+            memory: 64 * 1024 * 1024,
+            call_timeout: Duration::from_millis(1000),
+        }
+    }
+}
 
 pub struct ScriptManager {
     pub lua: Arc<Lua>,                              // Shared Lua VM instance
     pub core: Mutex<HashMap<String, Function>>,     // Core script functions
-    pub cards: Mutex<HashMap<String, Function>>,    // Card-related script functions
+    pub cards: Mutex<HashMap<CardDefId, Table>>,    // Namespaces of the card scripts
     pub effects: Mutex<HashMap<String, Function>>,  // Effect-related script functions
     pub triggers: Mutex<HashMap<String, Function>>, // Trigger-related script functions
+    pub limits: ScriptLimits,                       // Limits applied to every script call
+    pub api: Arc<ScriptApi>,                        // The `game` table offered to scripts
+    pub blocklist: Arc<RwLock<ScriptBlocklist>>,    // Scripts switched off by operators
+    pub manifest: ScriptManifest,                   // Functions of the scripts the server calls
 }
 
 impl ScriptManager {
     /// Creates a new instance of `ScriptManager` with an initialized Lua VM and empty function maps.
+    /// The VM is sandboxed with the limits from the settings.
     pub fn new_vm() -> Self {
-        let lua = Lua::new();
+        Self::with_limits(ScriptLimits::from_settings(SETTINGS.get()))
+    }
+
+    /// Creates a new instance of `ScriptManager` with a sandboxed Lua VM enforcing the given limits.
+    ///
+    /// The VM only has the safe standard libraries, without `dofile` and `loadfile`, and fails any
+    /// allocation past the memory limit.
+    pub fn with_limits(limits: ScriptLimits) -> Self {
+        // Card scripts get no `io`, `os`, `package` or `debug`.
+        let libs = StdLib::COROUTINE | StdLib::TABLE | StdLib::STRING | StdLib::UTF8 | StdLib::MATH;
+        let lua = Lua::new_with(libs, LuaOptions::default())
+            .expect("the sandboxed standard libraries should load");
+        let globals = lua.globals();
+        for name in REMOVED_GLOBALS {
+            let _ = globals.raw_remove(name);
+        }
+        if let Err(error) = lua.set_memory_limit(limits.memory) {
+            logger!(
+                ERROR,
+                "[SCRIPTS] Could not set the Lua memory limit: {error}"
+            );
+        }
+
+        let api = Arc::new(ScriptApi::default());
+        if let Err(error) = api.register(&lua) {
+            logger!(ERROR, "[SCRIPTS] Could not register the game API: {error}");
+        }
+
         Self {
+            api,
             lua: Arc::new(lua),
             core: Mutex::new(HashMap::new()),
             cards: Mutex::new(HashMap::new()),
             effects: Mutex::new(HashMap::new()),
             triggers: Mutex::new(HashMap::new()),
+            limits,
+            blocklist: Arc::new(RwLock::new(ScriptBlocklist::default())),
+            manifest: ScriptManifest::default(),
+        }
+    }
+
+    /// Loads the script blocklist kept in `SCRIPT_BLOCKLIST_PATH`, adding the one published at
+    /// `SCRIPT_BLOCKLIST_URL` when set. A blocklist that cannot be read or fetched is logged and
+    /// left out, so scripts are never blocked by a stale entry.
+    pub async fn load_blocklist(&self) {
+        let Some(settings) = SETTINGS.get() else {
+            return;
+        };
+
+        let mut blocklist = match ScriptBlocklist::load(Path::new(&settings.script_blocklist_path))
+        {
+            Ok(blocklist) => blocklist,
+            Err(error) => {
+                logger!(
+                    ERROR,
+                    "[SCRIPTS] Could not read the script blocklist: {error}"
+                );
+                ScriptBlocklist::default()
+            }
+        };
+        if let Some(url) = &settings.script_blocklist_url {
+            match ScriptBlocklist::fetch(url).await {
+                Ok(fetched) => blocklist.merge(fetched),
+                Err(error) => {
+                    logger!(
+                        ERROR,
+                        "[SCRIPTS] Could not fetch the script blocklist: {error}"
+                    )
+                }
+            }
+        }
+
+        if blocklist != ScriptBlocklist::default() {
+            logger!(WARN, "[SCRIPTS] Blocked scripts: {blocklist}");
+        }
+        *self.blocklist.write().await = blocklist;
+    }
+
+    /// Whether operators blocked the script a trigger of a card would run.
+    pub async fn is_blocked(&self, card_id: &CardDefId, action: &str) -> bool {
+        self.blocklist.read().await.blocks(card_id, action)
+    }
+
+    /// Calls a Lua function within the script limits.
+    ///
+    /// The function runs in its own coroutine, with a hook checking the instruction count and the
+    /// elapsed time every `HOOK_INTERVAL` instructions. Once a limit is exceeded the hook yields
+    /// the coroutine, which is then abandoned; unlike an error, a yield cannot be caught by the
+    /// script with `pcall`.
+    ///
+    /// # Returns
+    /// * `Ok(Some(Value))` - The value returned by the function.
+    /// * `Ok(None)` - If the call exceeded its instruction or time limit.
+    /// * `Err(mlua::Error)` - If the script failed, or yielded on its own.
+    fn resume_limited(
+        &self,
+        function: Function,
+        args: impl IntoLuaMulti,
+    ) -> mlua::Result<Option<Value>> {
+        let thread = self.lua.create_thread(function)?;
+
+        let exceeded = Arc::new(AtomicBool::new(false));
+        let hook_exceeded = Arc::clone(&exceeded);
+        let deadline = Instant::now() + self.limits.timeout;
+        let max_hooks = self.limits.instructions / HOOK_INTERVAL as u64;
+        let hooks = AtomicU64::new(0);
+        let triggers = HookTriggers::new().every_nth_instruction(HOOK_INTERVAL);
+        thread.set_hook(triggers, move |_, _| {
+            let over_budget = hooks.fetch_add(1, Ordering::Relaxed) >= max_hooks;
+            if over_budget || Instant::now() >= deadline {
+                hook_exceeded.store(true, Ordering::Relaxed);
+                return Ok(VmState::Yield);
+            }
+            Ok(VmState::Continue)
+        });
+
+        let value = thread.resume::<Value>(args);
+        if exceeded.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+
+        match thread.status() {
+            ThreadStatus::Resumable => Err(mlua::Error::CoroutineUnresumable),
+            _ => value.map(Some),
+        }
+    }
+
+    /// Calls a Lua function within the script limits, see `resume_limited`.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the script, for the errors.
+    /// * `function` - The function to call.
+    /// * `args` - The arguments of the call.
+    ///
+    /// # Returns
+    /// * `Ok(Value)` - The value returned by the function.
+    /// * `Err(GameLogicError)` - `ScriptTimeout` or `ScriptMemoryLimit` when a limit was exceeded,
+    ///   `FunctionNotCallable` when the script failed.
+    fn call_limited(
+        &self,
+        name: &str,
+        function: Function,
+        args: impl IntoLuaMulti,
+    ) -> Result<Value, GameLogicError> {
+        match self.resume_limited(function, args) {
+            Ok(Some(value)) => Ok(value),
+            Ok(None) => {
+                logger!(
+                    WARN,
+                    "[SCRIPTS] Script `{name}` exceeded its limits and was aborted"
+                );
+                Err(GameLogicError::ScriptTimeout(name.to_string()))
+            }
+            Err(mlua::Error::MemoryError(_)) => {
+                Err(GameLogicError::ScriptMemoryLimit(name.to_string()))
+            }
+            Err(_) => Err(GameLogicError::FunctionNotCallable(name.to_string())),
         }
     }
 
     /// Loads Lua scripts from the `./scripts` directory into the Lua VM.
     /// Only directories named "core", "cards", "effects", or "triggers" are processed.
+    ///
+    /// Every script runs in an environment of its own. The functions of the scripts of `core`,
+    /// `effects` and `triggers` are then made global, and the loading fails if two scripts define
+    /// the same global. The scripts of `cards` are named after a card id and keep their functions
+    /// in the namespace of that card, called as `<card_id>:<name>`, so two cards may both define
+    /// `on_play`.
+    ///
+    /// Scripts are linted for deprecated APIs as they load. In strict mode (`SCRIPT_LINT_STRICT`),
+    /// any deprecated use fails the loading, so staging environments catch them before production.
+    ///
+    /// The loading also fails if the script manifest (`scripts/manifest.json`) cannot be read,
+    /// lists a function twice or under an unknown category, or lists a function no script defines.
     pub fn load_scripts(&mut self) -> Result<(), Error> {
-        let folders = vec!["core", "cards", "effects", "triggers"];
+        let mut scripts = Vec::new();
+        let mut lint_warnings = 0;
         for entry in fs::read_dir("./scripts")? {
             let path = entry?.path();
             if path.is_dir() {
                 let name = path.file_name().and_then(|n| n.to_str()).unwrap();
-                if folders.contains(&name) {
+                if name == CARDS_FOLDER || SHARED_FOLDERS.contains(&name) {
                     logger!(DEBUG, "[SCRIPTS] Reading from: `{name}` directory");
-                    let _ = self.load_file(&path);
+                    lint_warnings += self
+                        .load_file(&path, name == CARDS_FOLDER, &mut scripts)
+                        .unwrap_or(0);
                 }
             }
         }
 
+        let strict = SETTINGS.get().is_some_and(|s| s.script_lint_strict);
+        if strict && lint_warnings > 0 {
+            logger!(
+                ERROR,
+                "[SCRIPTS] {lint_warnings} deprecated API uses found in strict mode"
+            );
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{lint_warnings} deprecated API uses found in card scripts"),
+            ));
+        }
+
+        // Sorted, so a collision always names the same script first.
+        scripts.sort_by(|a, b| a.path.cmp(&b.path));
+        if let Err(error) = self.register_scripts(scripts) {
+            logger!(ERROR, "[SCRIPTS] {error}");
+            return Err(Error::new(ErrorKind::InvalidData, error));
+        }
+
+        let manifest = ScriptManifest::load(Path::new(MANIFEST_PATH))
+            .and_then(|manifest| manifest.check_defined(&self.lua).map(|_| manifest));
+        match manifest {
+            Ok(manifest) => self.manifest = manifest,
+            Err(error) => {
+                logger!(ERROR, "[SCRIPTS] {error}");
+                return Err(Error::new(ErrorKind::InvalidData, error));
+            }
+        }
+
         Ok(())
     }
 
-    /// Loads individual Lua files from a given directory into the Lua VM.
-    /// Logs errors if a file cannot be read or executed.
-    fn load_file(&self, dir: &PathBuf) -> Result<(), Error> {
+    /// Runs the Lua files of a given directory, each in its own environment, see `run_isolated`.
+    /// Logs errors if a file cannot be read or executed, and a warning for every deprecated API
+    /// the file uses.
+    ///
+    /// # Returns
+    /// The number of deprecated API uses found in the directory.
+    fn load_file(
+        &self,
+        dir: &PathBuf,
+        card: bool,
+        scripts: &mut Vec<LoadedScript>,
+    ) -> Result<usize, Error> {
+        let mut lint_warnings = 0;
         for entry in fs::read_dir(dir)? {
             let path = entry?.path();
             if path.extension() == Some(OsStr::new("lua")) {
@@ -64,7 +341,19 @@ impl ScriptManager {
                 match fs::read_to_string(&path) {
                     Ok(code) => {
                         logger!(DEBUG, "[SCRIPTS] Loading script: `{name}`");
-                        let _ = self.lua.load(&code).exec();
+                        for warning in script_lint::lint_script(&path.display().to_string(), &code)
+                        {
+                            let structured = serde_json::to_string(&warning).unwrap_or_default();
+                            logger!(WARN, "[SCRIPTS] [LINT] {warning} {structured}");
+                            lint_warnings += 1;
+                        }
+
+                        match self.run_isolated(&name, &code) {
+                            Ok(env) => scripts.push(LoadedScript { path, card, env }),
+                            Err(error) => {
+                                logger!(ERROR, "[SCRIPTS] Couldn't run file `{name}`: {error}")
+                            }
+                        }
                     }
                     Err(e) => {
                         let error = e.to_string();
@@ -74,62 +363,102 @@ impl ScriptManager {
             }
         }
 
-        Ok(())
+        Ok(lint_warnings)
     }
 
-    /// Sets global Lua functions into categorized maps (`core`, `cards`, `effects`, `triggers`).
-    /// Reads function names from `.txt` files in the `./scripts` directory.
-    pub(crate) async fn set_globals(&mut self) {
+    /// Runs a script in an environment of its own, so the globals it defines cannot clobber those
+    /// of another script. Reads of globals the script did not define fall back to the shared ones.
+    ///
+    /// # Returns
+    /// * `Ok(Table)` - The environment, holding the globals the script defined.
+    /// * `Err(GameLogicError)` - If the script failed to compile or run.
+    fn run_isolated(&self, name: &str, code: &str) -> Result<Table, GameLogicError> {
+        let not_callable = |_| GameLogicError::FunctionNotCallable(name.to_string());
+        let env = self.lua.create_table().map_err(not_callable)?;
+        let fallback = self.lua.create_table().map_err(not_callable)?;
+        fallback
+            .set("__index", self.lua.globals())
+            .map_err(not_callable)?;
+        env.set_metatable(Some(fallback));
+
+        let chunk = self
+            .lua
+            .load(code)
+            .set_environment(env.clone())
+            .into_function()
+            .map_err(not_callable)?;
+        self.call_limited(name, chunk, ())?;
+        Ok(env)
+    }
+
+    /// Registers the functions of the scripts run by `load_file`: card scripts become the
+    /// namespace of their card, and the globals of the other scripts become shared globals.
+    ///
+    /// # Returns
+    /// * `Ok(())` - If every script was registered.
+    /// * `Err(ScriptLoadError)` - `Collision` if a script defines a global already defined, by
+    ///   another script or the server, `ReservedCardId` if a card script is named after a folder.
+    fn register_scripts(&mut self, scripts: Vec<LoadedScript>) -> Result<(), ScriptLoadError> {
         let globals = self.lua.globals();
-        if let Ok(files) = fs::read_dir("./scripts") {
-            for entry in files {
-                let path = entry.unwrap().path();
-                let file_name = path.file_name().unwrap().to_string_lossy().to_string();
-                if path.extension() == Some(OsStr::new("txt")) {
-                    let file = fs::File::open(path).unwrap();
-                    let reader = BufReader::new(file);
-                    for line in reader.lines() {
-                        let func_name = line.unwrap();
-                        match globals.get::<Function>(func_name.to_owned()) {
-                            Ok(function) => {
-                                if file_name.contains("core") {
-                                    logger!(DEBUG, "[SCRIPTS] [CORE] Setting function into map `{func_name}`");
-                                    let mut core_guard = self.core.lock().await;
-                                    core_guard.insert(func_name, function);
-                                } else if file_name.contains("card") {
-                                    logger!(DEBUG, "[SCRIPTS] [CARD] Setting function into map `{func_name}`");
-                                    let mut card_guard = self.cards.lock().await;
-                                    card_guard.insert(func_name, function);
-                                } else if file_name.contains("effect") {
-                                    logger!(DEBUG, "[SCRIPTS] [EFFECT] Setting function into map `{func_name}`");
-                                    let mut effects_guard = self.effects.lock().await;
-                                    effects_guard.insert(func_name, function);
-                                } else if file_name.contains("trigger") {
-                                    logger!(DEBUG, "[SCRIPTS] [TRIGGER] Setting function into map `{func_name}`");
-                                    let mut triggers_guard = self.triggers.lock().await;
-                                    triggers_guard.insert(func_name, function);
-                                }
-                            }
-                            Err(e) => {
-                                let error = e.to_string();
-                                logger!(ERROR, "[SCRIPTS] Unable to set function `{func_name}` ({error})");
-                            }
-                        }
-                    }
+        let mut defined_by: HashMap<String, String> = HashMap::new();
+        for script in scripts {
+            let name = script.path.display().to_string();
+            if script.card {
+                let stem = script.path.file_stem().unwrap_or_default();
+                let card_id = stem.to_string_lossy().to_string();
+                if card_id == CARDS_FOLDER || SHARED_FOLDERS.contains(&card_id.as_str()) {
+                    return Err(ScriptLoadError::ReservedCardId(name));
                 }
+                logger!(DEBUG, "[SCRIPTS] [cards] Setting namespace `{card_id}`");
+                self.cards.get_mut().insert(card_id.into(), script.env);
+                continue;
             }
+
+            for (key, value) in script.env.pairs::<String, Value>().flatten() {
+                if globals.contains_key(key.as_str()).unwrap_or(false) {
+                    let first = defined_by.get(&key).cloned();
+                    let first = first.unwrap_or_else(|| String::from("the server"));
+                    return Err(ScriptLoadError::Collision(key, first, name));
+                }
+                let _ = globals.raw_set(key.as_str(), value);
+                defined_by.insert(key, name.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the global Lua functions listed in the script manifest into categorized maps (`core`,
+    /// `effects`, `triggers`). The manifest was checked by `load_scripts`.
+    pub(crate) async fn set_globals(&mut self) {
+        let globals = self.lua.globals();
+        for (category, name) in self.manifest.functions() {
+            let Ok(function) = globals.get::<Function>(name) else {
+                continue;
+            };
+            logger!(DEBUG, "[SCRIPTS] [{category}] Setting function `{name}`");
+            let functions = match category {
+                ScriptCategory::Core => &self.core,
+                ScriptCategory::Effects => &self.effects,
+                ScriptCategory::Triggers => &self.triggers,
+            };
+            functions.lock().await.insert(name.to_string(), function);
         }
     }
 
     /// Retrieves a Lua function from the appropriate map based on the action prefix.
-    /// The action format is expected to be `<category>:<function_name>`.
+    /// The action format is expected to be `<category>:<function_name>`, or
+    /// `<card_id>:<function_name>` for a function of a card script.
     pub async fn get_function(&self, action: &str) -> Option<Function> {
         let action_parts: Vec<&str> = action.splitn(2, ":").collect();
         match action_parts.as_slice() {
-            ["cards", key] => self.cards.lock().await.get(*key).cloned(),
             ["core", key] => self.core.lock().await.get(*key).cloned(),
             ["effects", key] => self.effects.lock().await.get(*key).cloned(),
             ["triggers", key] => self.triggers.lock().await.get(*key).cloned(),
+            [card_id, key] => {
+                let cards = self.cards.lock().await;
+                let namespace = cards.get(&CardDefId::from(*card_id))?;
+                namespace.raw_get::<Option<Function>>(*key).ok().flatten()
+            }
             _ => None,
         }
     }
@@ -138,9 +467,7 @@ impl ScriptManager {
     /// Returns an error if the function is not callable, or the result is invalid.
     pub async fn call_function(&self, action: &str) -> Result<Vec<GameAction>, GameLogicError> {
         if let Some(function) = self.get_function(action).await {
-            let lua_value: Value = function
-                .call("")
-                .map_err(|_| GameLogicError::FunctionNotCallable(action.to_string()))?;
+            let lua_value = self.call_limited(action, function, "")?;
             let game_actions: Vec<GameAction> = self
                 .lua
                 .from_value(lua_value)
@@ -154,7 +481,8 @@ impl ScriptManager {
         ))
     }
 
-    /// Calls a Lua function with a `LuaContext` and returns a list of `GameAction` results.
+    /// Calls a Lua function with a `LuaContext` and returns a list of `GameAction` results: the
+    /// mutations requested through the `game` table, followed by the actions returned.
     /// Returns an error if the function is not callable, or the result is invalid.
     pub async fn call_function_ctx(
         &self,
@@ -163,13 +491,19 @@ impl ScriptManager {
     ) -> Result<Vec<GameAction>, GameLogicError> {
         let lua_table = ctx.to_table(self.lua.clone());
         if let Some(function) = self.get_function(action).await {
-            let lua_value: Value = function
-                .call(lua_table)
-                .map_err(|_| GameLogicError::FunctionNotCallable(action.to_string()))?;
-            let game_actions: Vec<GameAction> = self
-                .lua
-                .from_value(lua_value)
-                .map_err(|_| GameLogicError::InvalidGameActions)?;
+            // Mutations made through the `game` table come first, then the returned actions.
+            self.api.begin(ScriptSession::new(&ctx.game_state));
+            let lua_value = self.call_limited(action, function, lua_table);
+            let mut game_actions = self.api.finish();
+
+            let returned: Vec<GameAction> = match lua_value? {
+                Value::Nil => Vec::new(),
+                value => self
+                    .lua
+                    .from_value(value)
+                    .map_err(|_| GameLogicError::InvalidGameActions)?,
+            };
+            game_actions.extend(returned);
             return Ok(game_actions);
         }
 
@@ -178,6 +512,61 @@ impl ScriptManager {
             ctx.actor_id.to_string(),
         ))
     }
+
+    /// Calls a Lua function with a serialized input and returns whatever it returns, for hooks
+    /// whose result is passed along rather than applied to the game state.
+    ///
+    /// # Returns
+    /// * `Ok(Some(serde_json::Value))` - The value returned by the function.
+    /// * `Ok(None)` - If no function is registered under that name.
+    /// * `Err(GameLogicError)` - If the call failed or its result cannot be converted.
+    pub async fn call_hook<T: serde::Serialize>(
+        &self,
+        action: &str,
+        input: &T,
+    ) -> Result<Option<serde_json::Value>, GameLogicError> {
+        let Some(function) = self.get_function(action).await else {
+            return Ok(None);
+        };
+
+        let input = self
+            .lua
+            .to_value(input)
+            .map_err(|_| GameLogicError::FunctionNotCallable(action.to_string()))?;
+        let lua_value = self.call_limited(action, function, input)?;
+        self.lua
+            .from_value(lua_value)
+            .map(Some)
+            .map_err(|_| GameLogicError::InvalidHookResult(action.to_string()))
+    }
+
+    /// Evaluates a Lua snippet with `ctx` bound to the given context, for the developer REPL.
+    /// The snippet runs within the script limits, in its own environment falling back to the
+    /// globals, so neither `ctx` nor the globals it assigns outlive the evaluation.
+    ///
+    /// # Returns
+    /// * `Ok(serde_json::Value)` - The value the snippet evaluated to.
+    /// * `Err(mlua::Error)` - If the snippet fails to compile or run, or its result cannot be converted.
+    #[cfg(feature = "dev-repl")]
+    pub fn eval<T: serde::Serialize>(
+        &self,
+        code: &str,
+        ctx: &T,
+    ) -> Result<serde_json::Value, mlua::Error> {
+        let env = self.lua.create_table()?;
+        let fallback = self.lua.create_table()?;
+        fallback.set("__index", self.lua.globals())?;
+        env.set_metatable(Some(fallback));
+        env.set("ctx", self.lua.to_value(ctx)?)?;
+
+        let chunk = self.lua.load(code).set_environment(env).into_function()?;
+        match self.resume_limited(chunk, ())? {
+            Some(value) => self.lua.from_value(value),
+            None => Err(mlua::Error::RuntimeError(String::from(
+                "the snippet exceeded its instruction or time limit",
+            ))),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -206,4 +595,120 @@ mod tests {
             assert_eq!(2, actions.len());
         }
     }
+
+    /// Loads a snippet into a sandboxed VM and calls the `core:run` function it defines.
+    async fn run_sandboxed(code: &str) -> Result<Vec<GameAction>, GameLogicError> {
+        let sm = ScriptManager::with_limits(ScriptLimits {
+            instructions: 1_000_000,
+            timeout: Duration::from_millis(500),
+            memory: 4 * 1024 * 1024,
+            ..ScriptLimits::default()
+        });
+        sm.lua.load(code).exec().unwrap();
+        let function = sm.lua.globals().get::<Function>("run").unwrap();
+        sm.core.lock().await.insert("run".to_string(), function);
+        sm.call_function("core:run").await
+    }
+
+    #[tokio::test]
+    async fn test_card_scripts_keep_their_own_namespace() {
+        const FROSTBOLT: &str =
+            "function on_play() return { { type = 'DealDamage', target = 'None', amount = 3 } } end";
+        let mut sm = ScriptManager::with_limits(ScriptLimits::default());
+        let script = |sm: &ScriptManager, path: &str, card: bool, code: &str| LoadedScript {
+            path: PathBuf::from(path),
+            card,
+            env: sm.run_isolated(path, code).unwrap(),
+        };
+        let scripts = vec![
+            script(
+                &sm,
+                "cards/fireball.lua",
+                true,
+                "function on_play() return {} end",
+            ),
+            script(&sm, "cards/frostbolt.lua", true, FROSTBOLT),
+            script(&sm, "effects/burn.lua", false, "function burn() end"),
+        ];
+        sm.register_scripts(scripts).unwrap();
+
+        assert!(sm
+            .call_function("fireball:on_play")
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            1,
+            sm.call_function("frostbolt:on_play").await.unwrap().len()
+        );
+        assert!(sm.get_function("fireball:burn").await.is_none());
+        assert!(sm.lua.globals().get::<Function>("on_play").is_err());
+        assert!(sm.lua.globals().get::<Function>("burn").is_ok());
+
+        let collision = vec![
+            script(&sm, "effects/freeze.lua", false, "function freeze() end"),
+            script(&sm, "triggers/freeze.lua", false, "function freeze() end"),
+        ];
+        assert!(matches!(
+            sm.register_scripts(collision),
+            Err(ScriptLoadError::Collision(name, first, second))
+                if name == "freeze" && first == "effects/freeze.lua" && second == "triggers/freeze.lua"
+        ));
+        let builtin = vec![script(&sm, "core/print.lua", false, "function print() end")];
+        assert!(matches!(
+            sm.register_scripts(builtin),
+            Err(ScriptLoadError::Collision(_, first, _)) if first == "the server"
+        ));
+        let reserved = vec![script(&sm, "cards/core.lua", true, "")];
+        assert!(matches!(
+            sm.register_scripts(reserved),
+            Err(ScriptLoadError::ReservedCardId(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_runaway_scripts_are_aborted() {
+        let endless = run_sandboxed("function run() while true do end end").await;
+        assert!(matches!(endless, Err(GameLogicError::ScriptTimeout(_))));
+
+        let caught = run_sandboxed(
+            "function run() while true do pcall(function() while true do end end) end end",
+        )
+        .await;
+        assert!(matches!(caught, Err(GameLogicError::ScriptTimeout(_))));
+
+        let hungry = run_sandboxed(
+            "function run() local t = {} for i = 1, 1e8 do t[i] = string.rep('x', 64) .. i end end",
+        )
+        .await;
+        assert!(matches!(hungry, Err(GameLogicError::ScriptMemoryLimit(_))));
+
+        let fine = run_sandboxed("function run() return {} end").await;
+        assert!(fine.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sandbox_hides_unsafe_globals() {
+        let sm = ScriptManager::with_limits(ScriptLimits::default());
+        for name in [
+            "os", "io", "package", "debug", "dofile", "loadfile", "require",
+        ] {
+            let value = sm.lua.globals().get::<Value>(name).unwrap();
+            assert!(value.is_nil(), "`{name}` should not be available");
+        }
+    }
+
+    #[cfg(feature = "dev-repl")]
+    #[test]
+    fn test_eval_binds_context() {
+        let sm = ScriptManager::new_vm();
+        let value = sm.eval(
+            "leaked = true; return ctx.turn + 1",
+            &serde_json::json!({"turn": 4}),
+        );
+        assert_eq!(serde_json::json!(5), value.unwrap());
+        for name in ["ctx", "leaked"] {
+            assert!(sm.lua.globals().get::<Value>(name).unwrap().is_nil());
+        }
+    }
 }
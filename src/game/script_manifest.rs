@@ -0,0 +1,146 @@
+use crate::utils::errors::ScriptManifestError;
+use mlua::{Function, Lua};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::Path;
+
+/// The manifest listing the script functions the server calls.
+pub const MANIFEST_PATH: &str = "./scripts/manifest.json";
+
+/// What a shared script function is called for, which is the prefix of its action name
+/// (`effects:burn`). The functions of card scripts are not listed, see `ScriptManager::load_scripts`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptCategory {
+    Core,
+    Effects,
+    Triggers,
+}
+
+impl Display for ScriptCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScriptCategory::Core => write!(f, "core"),
+            ScriptCategory::Effects => write!(f, "effects"),
+            ScriptCategory::Triggers => write!(f, "triggers"),
+        }
+    }
+}
+
+/// The global functions defined by the scripts that the server calls, by category.
+///
+/// Categories other than these three are refused, so a typo cannot silently leave functions out.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptManifest {
+    #[serde(default)]
+    pub core: Vec<String>,
+    #[serde(default)]
+    pub effects: Vec<String>,
+    #[serde(default)]
+    pub triggers: Vec<String>,
+}
+
+impl ScriptManifest {
+    /// Reads a manifest file, see `parse`.
+    pub fn load(path: &Path) -> Result<Self, ScriptManifestError> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            ScriptManifestError::Unreadable(path.display().to_string(), e.to_string())
+        })?;
+        Self::parse(&content)
+    }
+
+    /// Parses a manifest.
+    ///
+    /// # Returns
+    /// * `Ok(ScriptManifest)` - The manifest, listing every function once.
+    /// * `Err(ScriptManifestError)` - `Invalid` if it is not valid JSON or names another category,
+    ///   `Duplicate` if a function is listed twice, even under different categories.
+    pub fn parse(content: &str) -> Result<Self, ScriptManifestError> {
+        let manifest: Self = serde_json::from_str(content)
+            .map_err(|e| ScriptManifestError::Invalid(e.to_string()))?;
+
+        let mut listed: HashMap<&str, ScriptCategory> = HashMap::new();
+        for (category, name) in manifest.functions() {
+            if let Some(first) = listed.insert(name, category) {
+                return Err(ScriptManifestError::Duplicate(
+                    format!("{first}:{name}"),
+                    format!("{category}:{name}"),
+                ));
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Every function of the manifest, with its category.
+    pub fn functions(&self) -> impl Iterator<Item = (ScriptCategory, &str)> {
+        [
+            (ScriptCategory::Core, &self.core),
+            (ScriptCategory::Effects, &self.effects),
+            (ScriptCategory::Triggers, &self.triggers),
+        ]
+        .into_iter()
+        .flat_map(|(category, names)| names.iter().map(move |name| (category, name.as_str())))
+    }
+
+    /// Checks that the scripts loaded into a VM define every function of the manifest.
+    ///
+    /// # Returns
+    /// * `Ok(())` - If every function is a global function of the VM.
+    /// * `Err(ScriptManifestError::Missing)` - Naming the first function that is not.
+    pub fn check_defined(&self, lua: &Lua) -> Result<(), ScriptManifestError> {
+        let globals = lua.globals();
+        match self
+            .functions()
+            .find(|(_, name)| globals.get::<Function>(*name).is_err())
+        {
+            Some((category, name)) => {
+                Err(ScriptManifestError::Missing(format!("{category}:{name}")))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_lists_each_function_once_in_a_known_category() {
+        let manifest =
+            ScriptManifest::parse(r#"{ "core": ["test"], "effects": ["burn"] }"#).unwrap();
+        let functions: Vec<_> = manifest.functions().collect();
+        assert_eq!(
+            vec![
+                (ScriptCategory::Core, "test"),
+                (ScriptCategory::Effects, "burn")
+            ],
+            functions
+        );
+
+        let duplicate = ScriptManifest::parse(r#"{ "effects": ["burn"], "triggers": ["burn"] }"#);
+        assert!(matches!(
+            duplicate,
+            Err(ScriptManifestError::Duplicate(first, second))
+                if first == "effects:burn" && second == "triggers:burn"
+        ));
+
+        let unknown = ScriptManifest::parse(r#"{ "cards": ["fireball"] }"#);
+        assert!(matches!(unknown, Err(ScriptManifestError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_listed_functions_must_be_defined() {
+        let lua = Lua::new();
+        lua.load("function burn() end").exec().unwrap();
+        let manifest = ScriptManifest::parse(r#"{ "effects": ["burn"] }"#).unwrap();
+        assert!(manifest.check_defined(&lua).is_ok());
+
+        let manifest = ScriptManifest::parse(r#"{ "effects": ["burn", "freeze"] }"#).unwrap();
+        assert!(matches!(
+            manifest.check_defined(&lua),
+            Err(ScriptManifestError::Missing(name)) if name == "effects:freeze"
+        ));
+    }
+}
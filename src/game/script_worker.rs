@@ -0,0 +1,258 @@
+use crate::game::lua_context::LuaContext;
+use crate::game::script_blocklist::ScriptBlocklist;
+use crate::game::script_manager::ScriptManager;
+use crate::logger;
+use crate::models::game_action::GameAction;
+use crate::models::ids::CardDefId;
+use crate::utils::errors::GameLogicError;
+use crate::utils::logger::Logger;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::{mpsc, oneshot, RwLock};
+
+/// Script calls waiting for the worker at most, before callers wait for room in the queue.
+const QUEUE_CAPACITY: usize = 64;
+
+type Reply<T> = oneshot::Sender<Result<T, GameLogicError>>;
+
+/// A script call for the worker, with where to send its result.
+enum ScriptRequest {
+    Call {
+        action: String,
+        context: LuaContext,
+        reply: Reply<Vec<GameAction>>,
+    },
+    Hook {
+        action: String,
+        input: serde_json::Value,
+        reply: Reply<Option<serde_json::Value>>,
+    },
+    #[cfg(feature = "dev-repl")]
+    Eval {
+        code: String,
+        context: serde_json::Value,
+        reply: Reply<Result<serde_json::Value, String>>,
+    },
+    Replace(Box<ScriptManager>), // Scripts reloaded, used from the next call on.
+}
+
+/// Runs the scripts of a match on a worker of its own, so protocol tasks never run Lua or hold a
+/// lock on the VM while they wait.
+///
+/// The worker owns the `ScriptManager` and runs the calls one at a time, in the order they were
+/// sent, on a thread of the blocking pool. Callers wait for the result at most `call_timeout` of
+/// the script limits, queueing included; a call whose caller stopped waiting is not run. The
+/// worker stops once every handle was dropped.
+#[derive(Clone)]
+pub struct ScriptWorker {
+    requests: mpsc::Sender<ScriptRequest>,
+    pub blocklist: Arc<RwLock<ScriptBlocklist>>, // Scripts switched off by operators
+    timeout: Duration,                           // How long a caller waits for a call
+}
+
+impl ScriptWorker {
+    /// Starts a worker running the scripts of a loaded `ScriptManager`.
+    pub fn spawn(script_manager: ScriptManager) -> Self {
+        let (requests, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        let worker = Self {
+            requests,
+            blocklist: Arc::clone(&script_manager.blocklist),
+            timeout: script_manager.limits.call_timeout,
+        };
+
+        let runtime = Handle::current();
+        tokio::task::spawn_blocking(move || Self::run(script_manager, receiver, runtime));
+        worker
+    }
+
+    /// Runs the calls sent to the worker until every handle was dropped. The `ScriptManager` is
+    /// only async for its function maps, which no one else holds, so its calls never wait.
+    fn run(
+        mut script_manager: ScriptManager,
+        mut receiver: mpsc::Receiver<ScriptRequest>,
+        runtime: Handle,
+    ) {
+        while let Some(request) = receiver.blocking_recv() {
+            match request {
+                ScriptRequest::Call {
+                    action,
+                    context,
+                    reply,
+                } if !reply.is_closed() => {
+                    let called = script_manager.call_function_ctx(&action, context);
+                    let _ = reply.send(runtime.block_on(called));
+                }
+                ScriptRequest::Hook {
+                    action,
+                    input,
+                    reply,
+                } if !reply.is_closed() => {
+                    let called = script_manager.call_hook(&action, &input);
+                    let _ = reply.send(runtime.block_on(called));
+                }
+                #[cfg(feature = "dev-repl")]
+                ScriptRequest::Eval {
+                    code,
+                    context,
+                    reply,
+                } if !reply.is_closed() => {
+                    let evaluated = script_manager.eval(&code, &context);
+                    let _ = reply.send(Ok(evaluated.map_err(|error| error.to_string())));
+                }
+                ScriptRequest::Replace(replacement) => script_manager = *replacement,
+                _ => {} // The caller stopped waiting.
+            }
+        }
+    }
+
+    /// Sends a request to the worker and waits for its result.
+    ///
+    /// # Returns
+    /// * `Ok(T)` - The result of the call.
+    /// * `Err(GameLogicError)` - `ScriptTimeout` if the result did not come within the timeout,
+    ///   `ScriptWorkerStopped` if the worker is gone, or why the call failed.
+    async fn request<T>(
+        &self,
+        action: &str,
+        request: impl FnOnce(Reply<T>) -> ScriptRequest,
+    ) -> Result<T, GameLogicError> {
+        let (reply, result) = oneshot::channel();
+        let called = async {
+            self.requests
+                .send(request(reply))
+                .await
+                .map_err(|_| GameLogicError::ScriptWorkerStopped)?;
+            result
+                .await
+                .map_err(|_| GameLogicError::ScriptWorkerStopped)?
+        };
+
+        match tokio::time::timeout(self.timeout, called).await {
+            Ok(result) => result,
+            Err(_) => {
+                logger!(
+                    WARN,
+                    "[SCRIPTS] Script `{action}` got no result within {:?}",
+                    self.timeout
+                );
+                Err(GameLogicError::ScriptTimeout(action.to_string()))
+            }
+        }
+    }
+
+    /// Whether operators blocked the script a trigger of a card would run.
+    pub async fn is_blocked(&self, card_id: &CardDefId, action: &str) -> bool {
+        self.blocklist.read().await.blocks(card_id, action)
+    }
+
+    /// Calls a script with a `LuaContext` on the worker, see `ScriptManager::call_function_ctx`.
+    pub async fn call_function_ctx(
+        &self,
+        action: &str,
+        context: LuaContext,
+    ) -> Result<Vec<GameAction>, GameLogicError> {
+        self.request(action, |reply| ScriptRequest::Call {
+            action: action.to_string(),
+            context,
+            reply,
+        })
+        .await
+    }
+
+    /// Calls a hook on the worker, see `ScriptManager::call_hook`.
+    pub async fn call_hook<T: Serialize>(
+        &self,
+        action: &str,
+        input: &T,
+    ) -> Result<Option<serde_json::Value>, GameLogicError> {
+        let input = serde_json::to_value(input)
+            .map_err(|_| GameLogicError::FunctionNotCallable(action.to_string()))?;
+        self.request(action, |reply| ScriptRequest::Hook {
+            action: action.to_string(),
+            input,
+            reply,
+        })
+        .await
+    }
+
+    /// Evaluates a snippet of the developer REPL on the worker, see `ScriptManager::eval`.
+    ///
+    /// # Returns
+    /// * `Ok(serde_json::Value)` - The value the snippet evaluated to.
+    /// * `Err(String)` - Why the snippet failed, or got no result.
+    #[cfg(feature = "dev-repl")]
+    pub async fn eval<T: Serialize>(
+        &self,
+        code: &str,
+        context: &T,
+    ) -> Result<serde_json::Value, String> {
+        let context = serde_json::to_value(context).map_err(|error| error.to_string())?;
+        let evaluated = self.request("repl", |reply| ScriptRequest::Eval {
+            code: code.to_string(),
+            context,
+            reply,
+        });
+        evaluated.await.map_err(|error| error.to_string())?
+    }
+
+    /// Replaces the scripts of the worker with reloaded ones. The calls sent before still run with
+    /// the previous scripts.
+    pub async fn replace(&self, script_manager: ScriptManager) -> Result<(), GameLogicError> {
+        self.requests
+            .send(ScriptRequest::Replace(Box::new(script_manager)))
+            .await
+            .map_err(|_| GameLogicError::ScriptWorkerStopped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::script_manager::ScriptLimits;
+    use mlua::Function;
+
+    /// A worker whose only script is `core:run`, defined by a snippet.
+    async fn worker(code: &str, limits: ScriptLimits) -> ScriptWorker {
+        let script_manager = ScriptManager::with_limits(limits);
+        script_manager.lua.load(code).exec().unwrap();
+        let function = script_manager.lua.globals().get::<Function>("run").unwrap();
+        script_manager
+            .core
+            .lock()
+            .await
+            .insert("run".to_string(), function);
+        ScriptWorker::spawn(script_manager)
+    }
+
+    #[tokio::test]
+    async fn test_hooks_run_on_the_worker() {
+        let worker = worker(
+            "function run(input) return input.turn + 1 end",
+            ScriptLimits::default(),
+        )
+        .await;
+        let turn = worker
+            .call_hook("core:run", &serde_json::json!({ "turn": 4 }))
+            .await;
+        assert_eq!(Some(serde_json::json!(5)), turn.unwrap());
+
+        let replacement = ScriptManager::with_limits(ScriptLimits::default());
+        worker.replace(replacement).await.unwrap();
+        let missing = worker.call_hook("core:run", &serde_json::json!({})).await;
+        assert_eq!(None, missing.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_callers_stop_waiting_after_the_call_timeout() {
+        let limits = ScriptLimits {
+            timeout: Duration::from_millis(500),
+            call_timeout: Duration::from_millis(50),
+            ..ScriptLimits::default()
+        };
+        let worker = worker("function run() while true do end end", limits).await;
+        let endless = worker.call_hook("core:run", &serde_json::json!({})).await;
+        assert!(matches!(endless, Err(GameLogicError::ScriptTimeout(_))));
+    }
+}
@@ -0,0 +1,208 @@
+use crate::game::match_format::TeamHealth;
+use crate::models::ids::PlayerId;
+
+/// Teams of every match: red and blue.
+pub const TEAMS: usize = 2;
+
+/// The seats of a match, in the order the matchmaker listed the players.
+///
+/// The teams alternate seats, so the red team holds the even seats and the blue team the odd ones;
+/// a match between two players seats one player a team. The turns go around the table in seat
+/// order, from the first seat of the team that plays first.
+#[derive(Debug, Clone, Default)]
+pub struct Seating {
+    seats: Vec<PlayerId>,
+    first: usize,       // The seat taking the first turn: 0 for red, 1 for blue.
+    health: TeamHealth, // Whether teammates share their health.
+}
+
+impl Seating {
+    pub fn new(seats: Vec<PlayerId>, health: TeamHealth) -> Self {
+        Self {
+            seats,
+            first: 0,
+            health,
+        }
+    }
+
+    /// Gives the first turn to the first player of the blue team instead of the red one.
+    pub fn blue_first(mut self) -> Self {
+        self.first = 1;
+        self
+    }
+
+    pub fn seats(&self) -> &[PlayerId] {
+        &self.seats
+    }
+
+    /// Whether the teams have more than one player each.
+    pub fn is_team_match(&self) -> bool {
+        self.seats.len() > TEAMS
+    }
+
+    /// Whether damage dealt to a player is dealt to their whole team.
+    pub fn shares_health(&self) -> bool {
+        self.is_team_match() && self.health == TeamHealth::Shared
+    }
+
+    fn seat_of(&self, player_id: &PlayerId) -> Option<usize> {
+        self.seats.iter().position(|seat| seat == player_id)
+    }
+
+    /// The team of a player: 0 for red, 1 for blue.
+    pub fn team_of(&self, player_id: &PlayerId) -> Option<usize> {
+        self.seat_of(player_id).map(|seat| seat % TEAMS)
+    }
+
+    /// The players of a team, in seat order.
+    pub fn team(&self, team: usize) -> Vec<&PlayerId> {
+        self.seats.iter().skip(team).step_by(TEAMS).collect()
+    }
+
+    /// The other players of the team of a player.
+    pub fn teammates_of(&self, player_id: &PlayerId) -> Vec<&PlayerId> {
+        let Some(team) = self.team_of(player_id) else {
+            return Vec::new();
+        };
+        self.team(team)
+            .into_iter()
+            .filter(|teammate| *teammate != player_id)
+            .collect()
+    }
+
+    /// The players of the other team, in seat order.
+    pub fn opponents_of(&self, player_id: &PlayerId) -> Vec<&PlayerId> {
+        match self.team_of(player_id) {
+            Some(team) => self.team((team + 1) % TEAMS),
+            None => Vec::new(),
+        }
+    }
+
+    /// The player taking their turn after a player: the next seat, always an opponent.
+    pub fn next_seat(&self, player_id: &PlayerId) -> Option<&PlayerId> {
+        let seat = self.seat_of(player_id)?;
+        self.seats.get((seat + 1) % self.seats.len())
+    }
+
+    /// The player taking the first turn of the match.
+    pub fn first_player(&self) -> Option<&PlayerId> {
+        self.seats.get(self.first)
+    }
+
+    /// Every player in the order they take their turns, from the first player.
+    pub fn turn_order(&self) -> Vec<PlayerId> {
+        let mut order = self.seats.clone();
+        let first = self.first.min(order.len());
+        order.rotate_left(first);
+        order
+    }
+
+    /// The player taking the turn after the turn of `current`: the next player in turn order who
+    /// is not `defeated`, or the first player who is not when no turn was played yet.
+    ///
+    /// # Returns
+    /// The player, or `None` if every player is defeated.
+    pub fn next_turn(&self, current: Option<&PlayerId>, defeated: &[PlayerId]) -> Option<PlayerId> {
+        let order = self.turn_order();
+        let after = current
+            .and_then(|current| order.iter().position(|seat| seat == current))
+            .map_or(0, |seat| seat + 1);
+        (0..order.len())
+            .map(|offset| &order[(after + offset) % order.len()])
+            .find(|player_id| !defeated.contains(player_id))
+            .cloned()
+    }
+
+    /// The teams with a player still standing once `defeated` are out.
+    pub fn standing_teams(&self, defeated: &[PlayerId]) -> Vec<usize> {
+        (0..TEAMS)
+            .filter(|team| {
+                self.team(*team)
+                    .into_iter()
+                    .any(|player_id| !defeated.contains(player_id))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(players: &[&str]) -> Vec<PlayerId> {
+        players
+            .iter()
+            .map(|player| PlayerId::from(*player))
+            .collect()
+    }
+
+    #[test]
+    fn test_teams_alternate_seats_and_turns() {
+        let seating = Seating::new(ids(&["ann", "bob", "cid", "dan"]), TeamHealth::Shared);
+        let (ann, bob, cid, dan) = (
+            PlayerId::from("ann"),
+            PlayerId::from("bob"),
+            PlayerId::from("cid"),
+            PlayerId::from("dan"),
+        );
+
+        assert!(seating.is_team_match());
+        assert!(seating.shares_health());
+        assert_eq!(vec![&ann, &cid], seating.team(0));
+        assert_eq!(vec![&dan], seating.teammates_of(&bob));
+        assert_eq!(vec![&bob, &dan], seating.opponents_of(&cid));
+        assert_eq!(Some(&ann), seating.next_seat(&dan));
+        assert_eq!(Some(&ann), seating.first_player());
+
+        let seating = seating.blue_first();
+        assert_eq!(Some(&bob), seating.first_player());
+        assert_eq!(ids(&["bob", "cid", "dan", "ann"]), seating.turn_order());
+
+        // A team stands while one of its players does.
+        assert_eq!(
+            vec![0, 1],
+            seating.standing_teams(&[ann.clone(), bob.clone()])
+        );
+        assert_eq!(vec![1], seating.standing_teams(&[ann, cid, bob]));
+    }
+
+    #[test]
+    fn test_turns_skip_defeated_players() {
+        let seating = Seating::new(ids(&["ann", "bob", "cid", "dan"]), TeamHealth::Separate);
+        let (ann, bob, cid, dan) = (
+            PlayerId::from("ann"),
+            PlayerId::from("bob"),
+            PlayerId::from("cid"),
+            PlayerId::from("dan"),
+        );
+
+        assert_eq!(Some(ann.clone()), seating.next_turn(None, &[]));
+        assert_eq!(Some(bob.clone()), seating.next_turn(Some(&ann), &[]));
+        assert_eq!(Some(ann.clone()), seating.next_turn(Some(&dan), &[]));
+        assert_eq!(
+            Some(dan.clone()),
+            seating.next_turn(Some(&bob), std::slice::from_ref(&cid))
+        );
+        assert_eq!(
+            Some(cid.clone()),
+            seating.next_turn(None, &[ann.clone(), bob.clone()])
+        );
+        assert_eq!(
+            None,
+            seating.next_turn(Some(&ann), &ids(&["ann", "bob", "cid", "dan"]))
+        );
+    }
+
+    #[test]
+    fn test_duels_seat_one_player_a_team() {
+        let seating = Seating::new(ids(&["red", "blue"]), TeamHealth::Shared);
+        assert!(!seating.is_team_match());
+        assert!(!seating.shares_health());
+        assert_eq!(
+            Some(&PlayerId::from("blue")),
+            seating.next_seat(&"red".into())
+        );
+        assert!(seating.teammates_of(&"red".into()).is_empty());
+        assert_eq!(None, seating.next_seat(&"green".into()));
+    }
+}
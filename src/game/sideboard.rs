@@ -0,0 +1,170 @@
+use crate::game::entity::card::CardRef;
+use crate::game::entity::deck::Deck;
+use crate::game::match_format::DeckRules;
+use crate::models::ids::CardDefId;
+use crate::utils::errors::SideboardError;
+use serde::{Deserialize, Serialize};
+
+/// A card of the deck traded for a card of the sideboard between two games of a series.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SideboardSwap {
+    pub remove: CardDefId, // The card taken out of the deck, into the sideboard.
+    pub add: CardDefId,    // The card taken out of the sideboard, into the deck.
+}
+
+/// Sent to every player of a series in a `Sideboard` packet before each game after the first.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SideboardView {
+    pub game: u32,     // The game about to be played, counting from 1.
+    pub deck: Deck,    // The deck of the player, with its registered sideboard.
+    pub deadline: i64, // Unix timestamp (milliseconds) after which the deck is played unchanged.
+}
+
+/// Applies the swaps of a player to their deck.
+///
+/// # Arguments
+/// * `deck` - The deck and sideboard the player registered.
+/// * `swaps` - The cards the player trades, applied in order.
+/// * `rules` - The deck rules of the format of the series.
+///
+/// # Returns
+/// The deck with the swapped cards, or an error if a card to remove is not in the deck, a card to
+/// add is not in the sideboard, or the deck breaks the rules of the format once swapped.
+pub fn apply(
+    deck: &Deck,
+    swaps: &[SideboardSwap],
+    rules: &DeckRules,
+) -> Result<Deck, SideboardError> {
+    let mut sideboarded = deck.clone();
+    for swap in swaps {
+        if !take(&mut sideboarded.cards, &swap.remove) {
+            return Err(SideboardError::NotInDeck(swap.remove.to_string()));
+        }
+        if !take(&mut sideboarded.sideboard, &swap.add) {
+            return Err(SideboardError::NotInSideboard(swap.add.to_string()));
+        }
+        put(&mut sideboarded.cards, &swap.add);
+        put(&mut sideboarded.sideboard, &swap.remove);
+    }
+
+    let violations = rules.violations(&sideboarded);
+    if !violations.is_empty() {
+        return Err(SideboardError::DeckIllegal(violations));
+    }
+    Ok(sideboarded)
+}
+
+/// Takes one copy of a card out of a list, dropping its entry once no copy is left.
+fn take(cards: &mut Vec<CardRef>, card_id: &CardDefId) -> bool {
+    let Some(index) = cards
+        .iter()
+        .position(|card| &card.id == card_id && card.amount > 0)
+    else {
+        return false;
+    };
+
+    cards[index].amount -= 1;
+    if cards[index].amount == 0 {
+        cards.remove(index);
+    }
+    true
+}
+
+/// Puts one copy of a card in a list.
+fn put(cards: &mut Vec<CardRef>, card_id: &CardDefId) {
+    match cards.iter_mut().find(|card| &card.id == card_id) {
+        Some(card) => card.amount += 1,
+        None => cards.push(CardRef {
+            id: card_id.clone(),
+            amount: 1,
+            owner_id: None,
+            instances: Vec::new(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::match_format::MatchFormat;
+
+    fn cards(cards: &[(&str, u32)]) -> Vec<CardRef> {
+        cards
+            .iter()
+            .map(|(id, amount)| CardRef {
+                id: (*id).into(),
+                amount: *amount,
+                owner_id: None,
+                instances: Vec::new(),
+            })
+            .collect()
+    }
+
+    fn swap(remove: &str, add: &str) -> SideboardSwap {
+        SideboardSwap {
+            remove: remove.into(),
+            add: add.into(),
+        }
+    }
+
+    fn amounts(cards: &[CardRef]) -> Vec<(String, u32)> {
+        cards.iter().map(|c| (c.id.to_string(), c.amount)).collect()
+    }
+
+    #[test]
+    fn test_swaps_trade_cards_between_deck_and_sideboard() {
+        let rules = MatchFormat::standard().deck;
+        let deck = Deck {
+            id: "deck".to_string(),
+            player_id: "red".into(),
+            name: "Deck".to_string(),
+            cards: cards(&[
+                ("wolf", 3),
+                ("mage", 3),
+                ("bear", 3),
+                ("imp", 3),
+                ("elf", 3),
+                ("orc", 3),
+                ("owl", 3),
+                ("bat", 3),
+                ("rat", 3),
+                ("ant", 3),
+            ]),
+            sideboard: cards(&[("fireball", 2), ("wall", 1)]),
+        };
+
+        let sideboarded = apply(
+            &deck,
+            &[swap("ant", "wall"), swap("wolf", "fireball")],
+            &rules,
+        )
+        .unwrap();
+        assert!(amounts(&sideboarded.cards).contains(&("wall".to_string(), 1)));
+        assert!(amounts(&sideboarded.cards).contains(&("wolf".to_string(), 2)));
+        assert!(amounts(&sideboarded.cards).contains(&("ant".to_string(), 2)));
+        assert_eq!(
+            vec![
+                ("fireball".to_string(), 1),
+                ("ant".to_string(), 1),
+                ("wolf".to_string(), 1)
+            ],
+            amounts(&sideboarded.sideboard)
+        );
+
+        assert_eq!(
+            Err(SideboardError::NotInDeck("fireball".to_string())),
+            apply(&deck, &[swap("fireball", "wall")], &rules).map(|_| ())
+        );
+        assert_eq!(
+            Err(SideboardError::NotInSideboard("wall".to_string())),
+            apply(&deck, &[swap("ant", "wall"), swap("imp", "wall")], &rules).map(|_| ())
+        );
+        // A fourth copy of a card breaks the standard rules.
+        let mut fourth = deck.clone();
+        fourth.sideboard = cards(&[("wolf", 1)]);
+        assert!(matches!(
+            apply(&fourth, &[swap("ant", "wolf")], &rules),
+            Err(SideboardError::DeckIllegal(_))
+        ));
+    }
+}
@@ -0,0 +1,183 @@
+use crate::game::game_state::PlayerGameStateView;
+use crate::models::ids::PlayerId;
+use crate::utils::errors::GameLogicError;
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// Sent to every player in a `MatchStart` packet once all of them are ready.
+#[derive(Serialize, Clone)]
+pub struct MatchStart {
+    pub countdown_ms: u64,          // Time left before the first turn starts.
+    pub starts_at: i64,             // Unix timestamp (milliseconds) of the start of the first turn.
+    pub first_player: PlayerId,     // The player taking the first turn.
+    pub state: PlayerGameStateView, // The initial game state, as seen by the receiving player.
+}
+
+/// Sent to every player and spectator in a `MatchCountdown` packet while a scheduled match waits
+/// for its start time.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MatchCountdown {
+    pub scheduled_at: i64,    // Unix timestamp (milliseconds) the match is scheduled to start at.
+    pub remaining_ms: u64,    // Time left before the scheduled start.
+    pub ready: Vec<PlayerId>, // The players who are already ready.
+}
+
+/// Holds the match back until every player is connected and ready, and until its scheduled
+/// start time if it has one.
+///
+/// Player actions are refused until the countdown that follows the last `Ready` has elapsed.
+pub struct StartBarrier {
+    players: Vec<PlayerId>,
+    ready: HashSet<PlayerId>,
+    scheduled_at: Option<i64>,  // Unix timestamp (milliseconds) before which the match does not start.
+    starts_at: Option<Instant>, // When the first turn starts, once every player is ready.
+}
+
+impl StartBarrier {
+    pub fn new(players: Vec<PlayerId>) -> Self {
+        Self {
+            players,
+            ready: HashSet::new(),
+            scheduled_at: None,
+            starts_at: None,
+        }
+    }
+
+    /// Holds the match back until a scheduled start time, given as a Unix timestamp in
+    /// milliseconds.
+    pub fn scheduled(mut self, scheduled_at: Option<i64>) -> Self {
+        self.scheduled_at = scheduled_at;
+        self
+    }
+
+    /// Marks a player as ready.
+    ///
+    /// # Arguments
+    /// * `player_id` - The player who sent `Ready`.
+    /// * `countdown` - The delay between the last player getting ready and the first turn; the
+    ///   first turn still waits for the scheduled start time.
+    ///
+    /// # Returns
+    /// * `Ok(true)` - If this was the last player to get ready; the countdown is now running.
+    /// * `Ok(false)` - If some players are still not ready, or the match already started.
+    /// * `Err(GameLogicError)` - If the player is not part of the match.
+    pub fn mark_ready(
+        &mut self,
+        player_id: &PlayerId,
+        countdown: Duration,
+    ) -> Result<bool, GameLogicError> {
+        if !self.players.contains(player_id) {
+            return Err(GameLogicError::PlayerNotFound);
+        }
+
+        self.ready.insert(player_id.clone());
+        if self.starts_at.is_some() || self.players.iter().any(|id| !self.ready.contains(id)) {
+            return Ok(false);
+        }
+
+        let wait = countdown.max(self.until_scheduled().unwrap_or_default());
+        self.starts_at = Some(Instant::now() + wait);
+        Ok(true)
+    }
+
+    /// Time left before the scheduled start, if the match is scheduled.
+    pub fn until_scheduled(&self) -> Option<Duration> {
+        let remaining = self.scheduled_at? - Utc::now().timestamp_millis();
+        Some(Duration::from_millis(remaining.max(0) as u64))
+    }
+
+    /// The countdown to the scheduled start, while the match waits for it.
+    pub fn countdown(&self) -> Option<MatchCountdown> {
+        let remaining = self.until_scheduled().filter(|left| !left.is_zero())?;
+        Some(MatchCountdown {
+            scheduled_at: self.scheduled_at?,
+            remaining_ms: remaining.as_millis() as u64,
+            ready: self
+                .players
+                .iter()
+                .filter(|id| self.ready.contains(*id))
+                .cloned()
+                .collect(),
+        })
+    }
+
+    /// The players who have not sent `Ready`.
+    pub fn missing(&self) -> Vec<PlayerId> {
+        self.players
+            .iter()
+            .filter(|id| !self.ready.contains(*id))
+            .cloned()
+            .collect()
+    }
+
+    /// Time left before the first turn, once every player is ready.
+    pub fn starts_in(&self) -> Option<Duration> {
+        self.starts_at
+            .map(|starts_at| starts_at.saturating_duration_since(Instant::now()))
+    }
+
+    /// Whether every player got ready, even if the countdown is still running.
+    pub fn all_ready(&self) -> bool {
+        self.starts_at.is_some()
+    }
+
+    /// Whether the countdown elapsed and players may act.
+    pub fn started(&self) -> bool {
+        self.starts_at
+            .is_some_and(|starts_at| Instant::now() >= starts_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_starts_once_everyone_is_ready() {
+        let mut barrier = StartBarrier::new(vec!["red".into(), "blue".into()]);
+        assert!(matches!(
+            barrier.mark_ready(&"green".into(), Duration::ZERO),
+            Err(GameLogicError::PlayerNotFound)
+        ));
+
+        assert!(!barrier.mark_ready(&"red".into(), Duration::ZERO).unwrap());
+        assert!(!barrier.all_ready());
+        assert!(barrier.mark_ready(&"blue".into(), Duration::ZERO).unwrap());
+        assert!(barrier.started());
+
+        // Readying again does not restart the match.
+        assert!(!barrier.mark_ready(&"blue".into(), Duration::ZERO).unwrap());
+    }
+
+    #[test]
+    fn test_actions_wait_for_the_countdown() {
+        let mut barrier = StartBarrier::new(vec!["red".into()]);
+        assert!(barrier
+            .mark_ready(&"red".into(), Duration::from_secs(60))
+            .unwrap());
+        assert!(barrier.all_ready());
+        assert!(!barrier.started());
+    }
+
+    #[test]
+    fn test_scheduled_match_waits_for_its_start_time() {
+        let scheduled_at = Utc::now().timestamp_millis() + 60_000;
+        let mut barrier =
+            StartBarrier::new(vec!["red".into(), "blue".into()]).scheduled(Some(scheduled_at));
+        barrier.mark_ready(&"red".into(), Duration::ZERO).unwrap();
+
+        let countdown = barrier.countdown().unwrap();
+        assert_eq!(vec![PlayerId::from("red")], countdown.ready);
+        assert!(countdown.remaining_ms > 50_000);
+        assert_eq!(vec![PlayerId::from("blue")], barrier.missing());
+
+        assert!(barrier.mark_ready(&"blue".into(), Duration::ZERO).unwrap());
+        assert!(!barrier.started());
+        assert!(barrier.starts_in().unwrap() > Duration::from_secs(50));
+
+        let past = StartBarrier::new(vec![]).scheduled(Some(scheduled_at - 120_000));
+        assert!(past.countdown().is_none());
+    }
+}
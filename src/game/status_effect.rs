@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+
+/// The kinds of status effects a card or a player can carry.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusKind {
+    /// Takes `magnitude` damage at each turn boundary.
+    Poison,
+    /// Cannot play, activate, attack or block while it lasts.
+    Stun,
+    /// Absorbs up to `magnitude` damage in total, then breaks.
+    Shield,
+    /// Gains `magnitude` attack in combat.
+    AttackBuff,
+    /// Loses `magnitude` attack in combat.
+    AttackDebuff,
+}
+
+/// How a new effect combines with an effect of the same kind already on the target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stacking {
+    /// Magnitudes add up, and the longest duration is kept.
+    Intensify,
+    /// A single instance keeps the strongest magnitude and the longest duration.
+    Refresh,
+    /// Every application is its own instance, expiring on its own.
+    Independent,
+}
+
+impl StatusKind {
+    pub fn stacking(&self) -> Stacking {
+        match self {
+            StatusKind::Poison => Stacking::Intensify,
+            StatusKind::Stun | StatusKind::Shield => Stacking::Refresh,
+            StatusKind::AttackBuff | StatusKind::AttackDebuff => Stacking::Independent,
+        }
+    }
+}
+
+/// A status effect attached to a card or a player.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub magnitude: u32,
+    /// Turn boundaries the effect still lasts for; `None` lasts until removed.
+    pub turns: Option<u32>,
+}
+
+/// Attaches an effect to the effects of a target, following the stacking rule of its kind.
+pub fn apply(effects: &mut Vec<StatusEffect>, effect: StatusEffect) {
+    let stacking = effect.kind.stacking();
+    let existing = effects.iter_mut().find(|e| e.kind == effect.kind);
+    match (stacking, existing) {
+        (Stacking::Independent, _) | (_, None) => effects.push(effect),
+        (Stacking::Intensify, Some(existing)) => {
+            existing.magnitude += effect.magnitude;
+            existing.turns = longest(existing.turns, effect.turns);
+        }
+        (Stacking::Refresh, Some(existing)) => {
+            existing.magnitude = existing.magnitude.max(effect.magnitude);
+            existing.turns = longest(existing.turns, effect.turns);
+        }
+    }
+}
+
+/// Counts down the effects of a target at a turn boundary.
+///
+/// # Returns
+/// The effects that expired, which are removed from the target.
+pub fn tick(effects: &mut Vec<StatusEffect>) -> Vec<StatusEffect> {
+    for effect in effects.iter_mut() {
+        if let Some(turns) = &mut effect.turns {
+            *turns = turns.saturating_sub(1);
+        }
+    }
+
+    let (expired, active) = std::mem::take(effects)
+        .into_iter()
+        .partition(|e| e.turns == Some(0));
+    *effects = active;
+    expired
+}
+
+/// Whether a target carrying these effects is stunned and cannot act.
+pub fn is_stunned(effects: &[StatusEffect]) -> bool {
+    effects.iter().any(|e| e.kind == StatusKind::Stun)
+}
+
+/// The damage the poison on a target deals at a turn boundary.
+pub fn poison(effects: &[StatusEffect]) -> u32 {
+    effects
+        .iter()
+        .filter(|e| e.kind == StatusKind::Poison)
+        .map(|e| e.magnitude)
+        .sum()
+}
+
+/// The attack of a creature once its attack buffs and debuffs apply, never below zero.
+pub fn attack(base: i32, effects: &[StatusEffect]) -> i32 {
+    let modifier: i64 = effects
+        .iter()
+        .map(|e| match e.kind {
+            StatusKind::AttackBuff => i64::from(e.magnitude),
+            StatusKind::AttackDebuff => -i64::from(e.magnitude),
+            _ => 0,
+        })
+        .sum();
+    (i64::from(base) + modifier).clamp(0, i64::from(i32::MAX)) as i32
+}
+
+/// Lets the shield of a target absorb damage. The shield loses what it absorbed, and is removed
+/// once it has nothing left to absorb.
+///
+/// # Returns
+/// The damage the shield did not absorb.
+pub fn absorb(effects: &mut Vec<StatusEffect>, damage: u32) -> u32 {
+    let mut left = damage;
+    for shield in effects.iter_mut().filter(|e| e.kind == StatusKind::Shield) {
+        let absorbed = left.min(shield.magnitude);
+        shield.magnitude -= absorbed;
+        left -= absorbed;
+    }
+
+    effects.retain(|e| e.kind != StatusKind::Shield || e.magnitude > 0);
+    left
+}
+
+fn longest(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effect(kind: StatusKind, magnitude: u32, turns: Option<u32>) -> StatusEffect {
+        StatusEffect {
+            kind,
+            magnitude,
+            turns,
+        }
+    }
+
+    #[test]
+    fn test_stacking_rules() {
+        let mut effects = Vec::new();
+        apply(&mut effects, effect(StatusKind::Poison, 2, Some(3)));
+        apply(&mut effects, effect(StatusKind::Poison, 1, Some(1)));
+        apply(&mut effects, effect(StatusKind::Shield, 5, Some(1)));
+        apply(&mut effects, effect(StatusKind::Shield, 3, None));
+        apply(&mut effects, effect(StatusKind::AttackBuff, 1, Some(2)));
+        apply(&mut effects, effect(StatusKind::AttackBuff, 1, Some(2)));
+
+        assert_eq!(
+            vec![
+                effect(StatusKind::Poison, 3, Some(3)),
+                effect(StatusKind::Shield, 5, None),
+                effect(StatusKind::AttackBuff, 1, Some(2)),
+                effect(StatusKind::AttackBuff, 1, Some(2)),
+            ],
+            effects
+        );
+    }
+
+    #[test]
+    fn test_effects_expire_at_turn_boundaries() {
+        let mut effects = vec![
+            effect(StatusKind::Stun, 1, Some(1)),
+            effect(StatusKind::Poison, 2, Some(2)),
+            effect(StatusKind::Shield, 4, None),
+        ];
+
+        assert_eq!(
+            vec![effect(StatusKind::Stun, 1, Some(0))],
+            tick(&mut effects)
+        );
+        assert_eq!(2, effects.len());
+        assert_eq!(StatusKind::Poison, tick(&mut effects)[0].kind);
+        assert_eq!(vec![effect(StatusKind::Shield, 4, None)], effects);
+    }
+
+    #[test]
+    fn test_effects_change_attack_and_absorb_damage() {
+        let mut effects = vec![
+            effect(StatusKind::AttackBuff, 2, Some(1)),
+            effect(StatusKind::AttackDebuff, 5, None),
+            effect(StatusKind::Shield, 3, None),
+            effect(StatusKind::Poison, 2, Some(2)),
+        ];
+
+        assert_eq!(0, attack(2, &effects));
+        assert_eq!(4, attack(7, &effects));
+        assert_eq!(2, poison(&effects));
+        assert!(!is_stunned(&effects));
+
+        assert_eq!(0, absorb(&mut effects, 2));
+        assert_eq!(3, absorb(&mut effects, 4));
+        assert!(effects.iter().all(|e| e.kind != StatusKind::Shield));
+    }
+}
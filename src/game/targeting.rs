@@ -0,0 +1,144 @@
+use crate::game::entity::player::PlayerView;
+use crate::utils::errors::GameLogicError;
+use serde::{Deserialize, Serialize};
+use crate::models::ids::PlayerId;
+
+/// Describes what a card may target when it is played.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub enum TargetRule {
+    #[default]
+    None,
+    AnyCreature,
+    AllyCreature,
+    EnemyCreature,
+    AnyPlayer,
+    EnemyPlayer,
+    AnyCharacter,
+}
+
+/// The outcome of validating the target of a play.
+#[derive(Debug, PartialEq)]
+pub enum TargetResolution {
+    /// The card does not take a target.
+    NoTarget,
+    /// The target was supplied by the client or was the only legal one.
+    Resolved(String),
+    /// Several targets are legal and the client did not pick one; the player must be prompted.
+    Prompt(Vec<String>),
+}
+
+/// Lists the ids of every legal target for a rule from the point of view of `actor_id`.
+///
+/// Players are identified by their player id and creatures by the instance id of each copy on the
+/// board.
+pub fn legal_targets(rule: &TargetRule, actor_id: &PlayerId, views: &[PlayerView]) -> Vec<String> {
+    let mut targets = Vec::new();
+    for view in views {
+        let is_ally = &view.id == actor_id;
+        let creatures = view
+            .board
+            .creatures
+            .iter()
+            .flatten()
+            .flat_map(|c| c.instances.iter().map(|id| id.to_string()));
+
+        match rule {
+            TargetRule::None => {}
+            TargetRule::AnyCreature => targets.extend(creatures),
+            TargetRule::AllyCreature if is_ally => targets.extend(creatures),
+            TargetRule::EnemyCreature if !is_ally => targets.extend(creatures),
+            TargetRule::AnyPlayer => targets.push(view.id.to_string()),
+            TargetRule::EnemyPlayer if !is_ally => targets.push(view.id.to_string()),
+            TargetRule::AnyCharacter => {
+                targets.push(view.id.to_string());
+                targets.extend(creatures);
+            }
+            _ => {}
+        }
+    }
+
+    targets
+}
+
+/// Validates the requested target against the legal ones.
+///
+/// # Arguments
+/// * `rule` - The targeting rule of the card being played.
+/// * `requested` - The target id sent by the client, if any.
+/// * `legal` - The legal targets, as returned by `legal_targets`.
+///
+/// # Returns
+/// * `Ok(TargetResolution)` - The resolved target, or the options the player must choose from.
+/// * `Err(GameLogicError)` - If the requested target is illegal or there is nothing to target.
+pub fn resolve_target(
+    rule: &TargetRule,
+    requested: Option<&str>,
+    legal: Vec<String>,
+) -> Result<TargetResolution, GameLogicError> {
+    if *rule == TargetRule::None {
+        return Ok(TargetResolution::NoTarget);
+    }
+
+    match requested {
+        Some(target) if legal.iter().any(|t| t == target) => {
+            Ok(TargetResolution::Resolved(target.to_string()))
+        }
+        Some(target) => Err(GameLogicError::InvalidTarget(target.to_string())),
+        None => match legal.len() {
+            0 => Err(GameLogicError::NoLegalTargets),
+            1 => Ok(TargetResolution::Resolved(legal[0].clone())),
+            _ => Ok(TargetResolution::Prompt(legal)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entity::card::CardRef;
+    use crate::models::ids::CardInstanceId;
+
+    fn views() -> Vec<PlayerView> {
+        let mut red = PlayerView::from_player(&"red".into(), 30);
+        let blue = PlayerView::from_player(&"blue".into(), 30);
+        red.board.creatures[0] = Some(CardRef {
+            id: "wolf".into(),
+            amount: 1,
+            owner_id: None,
+            instances: vec![CardInstanceId::nth(0)],
+        });
+        red.board.creatures[1] = Some(CardRef {
+            id: "bear".into(),
+            amount: 1,
+            owner_id: None,
+            instances: vec![CardInstanceId::nth(1)],
+        });
+        vec![red, blue]
+    }
+
+    #[test]
+    fn test_single_legal_target_is_auto_resolved() {
+        let legal = legal_targets(&TargetRule::EnemyPlayer, &"red".into(), &views());
+        let resolution = resolve_target(&TargetRule::EnemyPlayer, None, legal).unwrap();
+        assert_eq!(TargetResolution::Resolved("blue".to_string()), resolution);
+    }
+
+    #[test]
+    fn test_several_legal_targets_prompt_the_player() {
+        let legal = legal_targets(&TargetRule::EnemyCreature, &"blue".into(), &views());
+        let resolution = resolve_target(&TargetRule::EnemyCreature, None, legal).unwrap();
+        assert_eq!(
+            TargetResolution::Prompt(vec!["c0".to_string(), "c1".to_string()]),
+            resolution
+        );
+    }
+
+    #[test]
+    fn test_illegal_or_missing_targets_are_rejected() {
+        let legal = legal_targets(&TargetRule::AllyCreature, &"red".into(), &views());
+        assert!(resolve_target(&TargetRule::AllyCreature, Some("blue"), legal).is_err());
+
+        let legal = legal_targets(&TargetRule::EnemyCreature, &"red".into(), &views());
+        assert!(resolve_target(&TargetRule::EnemyCreature, None, legal).is_err());
+    }
+}
@@ -0,0 +1,281 @@
+use crate::models::ids::PlayerId;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Who is sent the think time of a player, see `THINK_TIME_VISIBILITY`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThinkTimeVisibility {
+    /// Both players and the spectators.
+    All,
+    /// Only the player it belongs to.
+    #[default]
+    Own,
+    /// Nobody; think time is still tracked for the analytics.
+    None,
+}
+
+impl ThinkTimeVisibility {
+    /// Whether the think time of a player is sent to their opponent and the spectators.
+    pub fn shows_opponent(&self) -> bool {
+        *self == ThinkTimeVisibility::All
+    }
+
+    /// Whether the think time of a player is sent to the player themselves.
+    pub fn shows_own(&self) -> bool {
+        *self != ThinkTimeVisibility::None
+    }
+}
+
+/// The think time of a player as sent to clients.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ThinkTime {
+    pub turn_millis: u64,  // Time used on the current turn, `0` when it is not their turn.
+    pub total_millis: u64, // Time used over the whole match, the current turn included.
+}
+
+/// Time a player spent on one of their turns, for the analytics.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TurnThinkTime {
+    pub turn: u32,
+    pub player_id: PlayerId,
+    pub millis: u64,
+}
+
+/// The turn whose clock is running.
+#[derive(Clone)]
+struct RunningTurn {
+    turn: u32,
+    player_id: PlayerId,
+    started: Instant,
+    paused: Duration,           // Time not counted, while long resolutions played on the clients.
+    suspended: Option<Instant>, // Since when the clock is stopped, while its player is disconnected.
+}
+
+/// Measures how long each player takes on their turns.
+///
+/// The clock of a player runs from the start of their turn until the next turn starts or the
+/// match ends, prompts included, minus the pauses granted for long resolutions.
+#[derive(Default, Clone)]
+pub struct ThinkTimeTracker {
+    running: Option<RunningTurn>,  // The turn being played, if any.
+    completed: Vec<TurnThinkTime>, // Every finished turn, in order.
+}
+
+impl ThinkTimeTracker {
+    /// Stops the clock of the running turn and starts the clock of `player_id`.
+    ///
+    /// # Arguments
+    /// * `player_id` - The player whose turn starts.
+    /// * `turn` - The number of the turn.
+    pub fn start_turn(&mut self, player_id: &PlayerId, turn: u32) {
+        self.start_turn_at(player_id, turn, Instant::now());
+    }
+
+    /// Pauses the clock of a player for a long resolution, by leaving time out of their turn.
+    ///
+    /// # Arguments
+    /// * `player_id` - The player whose clock pauses; only the clock of the running turn can.
+    /// * `requested` - How long the resolution plays on the clients.
+    /// * `turn_cap` - The most the clock may pause over the whole turn.
+    ///
+    /// # Returns
+    /// The pause granted, cut down to what is left of `turn_cap`.
+    pub fn pause(
+        &mut self,
+        player_id: &PlayerId,
+        requested: Duration,
+        turn_cap: Duration,
+    ) -> Duration {
+        let Some(running) = self.running_of(player_id) else {
+            return Duration::ZERO;
+        };
+
+        let granted = requested.min(turn_cap.saturating_sub(running.paused));
+        running.paused += granted;
+        granted
+    }
+
+    /// Stops the clock of a player until `resume`, while they are disconnected.
+    ///
+    /// Does nothing unless the running turn is theirs.
+    pub fn suspend(&mut self, player_id: &PlayerId) {
+        self.suspend_at(player_id, Instant::now());
+    }
+
+    /// Starts the clock of a player again once they reconnect, leaving the time they were away
+    /// out of their turn.
+    pub fn resume(&mut self, player_id: &PlayerId) {
+        self.resume_at(player_id, Instant::now());
+    }
+
+    /// Stops the clock of the running turn, when the match ends.
+    pub fn stop(&mut self) {
+        self.stop_at(Instant::now());
+    }
+
+    /// The think time of a player, up to now.
+    pub fn think_time(&self, player_id: &PlayerId) -> ThinkTime {
+        self.think_time_at(player_id, Instant::now())
+    }
+
+    /// The turn being played with the time it has taken so far, if any.
+    pub fn running_turn(&self) -> Option<TurnThinkTime> {
+        self.running_at(Instant::now())
+    }
+
+    /// Every turn played so far with the time it took, the running turn included.
+    pub fn turns(&self) -> Vec<TurnThinkTime> {
+        let mut turns = self.completed.clone();
+        turns.extend(self.running_at(Instant::now()));
+        turns
+    }
+
+    fn start_turn_at(&mut self, player_id: &PlayerId, turn: u32, now: Instant) {
+        self.stop_at(now);
+        self.running = Some(RunningTurn {
+            turn,
+            player_id: player_id.clone(),
+            started: now,
+            paused: Duration::ZERO,
+            suspended: None,
+        });
+    }
+
+    fn suspend_at(&mut self, player_id: &PlayerId, now: Instant) {
+        if let Some(running) = self.running_of(player_id) {
+            running.suspended.get_or_insert(now);
+        }
+    }
+
+    fn resume_at(&mut self, player_id: &PlayerId, now: Instant) {
+        if let Some(running) = self.running_of(player_id) {
+            if let Some(since) = running.suspended.take() {
+                running.paused += now.saturating_duration_since(since);
+            }
+        }
+    }
+
+    fn running_of(&mut self, player_id: &PlayerId) -> Option<&mut RunningTurn> {
+        self.running
+            .as_mut()
+            .filter(|running| &running.player_id == player_id)
+    }
+
+    fn stop_at(&mut self, now: Instant) {
+        if let Some(turn) = self.running_at(now) {
+            self.completed.push(turn);
+        }
+        self.running = None;
+    }
+
+    fn think_time_at(&self, player_id: &PlayerId, now: Instant) -> ThinkTime {
+        let turn_millis = self
+            .running_at(now)
+            .filter(|turn| &turn.player_id == player_id)
+            .map_or(0, |turn| turn.millis);
+        let completed_millis: u64 = self
+            .completed
+            .iter()
+            .filter(|turn| &turn.player_id == player_id)
+            .map(|turn| turn.millis)
+            .sum();
+
+        ThinkTime {
+            turn_millis,
+            total_millis: completed_millis + turn_millis,
+        }
+    }
+
+    fn running_at(&self, now: Instant) -> Option<TurnThinkTime> {
+        self.running.as_ref().map(|running| TurnThinkTime {
+            turn: running.turn,
+            player_id: running.player_id.clone(),
+            millis: running
+                .suspended
+                .unwrap_or(now)
+                .min(now)
+                .saturating_duration_since(running.started)
+                .saturating_sub(running.paused)
+                .as_millis() as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_think_time_accumulates_per_player() {
+        let (red, blue) = (PlayerId::from("red"), PlayerId::from("blue"));
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let mut tracker = ThinkTimeTracker::default();
+        tracker.start_turn_at(&red, 1, at(0));
+        tracker.start_turn_at(&blue, 2, at(40));
+        tracker.start_turn_at(&red, 3, at(55));
+
+        let red_time = tracker.think_time_at(&red, at(150));
+        assert_eq!(95_000, red_time.turn_millis);
+        assert_eq!(135_000, red_time.total_millis);
+
+        let blue_time = tracker.think_time_at(&blue, at(150));
+        assert_eq!(0, blue_time.turn_millis);
+        assert_eq!(15_000, blue_time.total_millis);
+
+        tracker.stop_at(at(60));
+        let turns: Vec<_> = tracker.turns().iter().map(|t| (t.turn, t.millis)).collect();
+        assert_eq!(vec![(1, 40_000), (2, 15_000), (3, 5_000)], turns);
+    }
+
+    #[test]
+    fn test_pauses_are_capped_per_turn() {
+        let (red, blue) = (PlayerId::from("red"), PlayerId::from("blue"));
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let cap = Duration::from_secs(15);
+
+        let mut tracker = ThinkTimeTracker::default();
+        tracker.start_turn_at(&red, 1, at(0));
+        assert_eq!(
+            Duration::ZERO,
+            tracker.pause(&blue, Duration::from_secs(5), cap)
+        );
+        assert_eq!(
+            Duration::from_secs(10),
+            tracker.pause(&red, Duration::from_secs(10), cap)
+        );
+        assert_eq!(
+            Duration::from_secs(5),
+            tracker.pause(&red, Duration::from_secs(10), cap)
+        );
+        assert_eq!(
+            Duration::ZERO,
+            tracker.pause(&red, Duration::from_secs(1), cap)
+        );
+        assert_eq!(25_000, tracker.think_time_at(&red, at(40)).turn_millis);
+    }
+
+    #[test]
+    fn test_clock_stops_while_disconnected() {
+        let (red, blue) = (PlayerId::from("red"), PlayerId::from("blue"));
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let mut tracker = ThinkTimeTracker::default();
+        tracker.start_turn_at(&red, 1, at(0));
+        tracker.suspend_at(&blue, at(5));
+        tracker.suspend_at(&red, at(10));
+        assert_eq!(10_000, tracker.think_time_at(&red, at(30)).turn_millis);
+
+        tracker.resume_at(&red, at(40));
+        assert_eq!(15_000, tracker.think_time_at(&red, at(45)).turn_millis);
+
+        tracker.suspend_at(&red, at(50));
+        tracker.start_turn_at(&blue, 2, at(60));
+        let turns: Vec<_> = tracker.turns().iter().map(|t| (t.turn, t.millis)).collect();
+        assert_eq!((1, 20_000), turns[0]);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::game::checkpoint::Checkpoint;
+use crate::models::ids::PlayerId;
+use crate::utils::errors::GameLogicError;
+use std::collections::HashMap;
+
+/// Keeps the state before the last play of the match, so the player who made it can take it back.
+///
+/// Only the last play can be undone, and only until the opponent acts: the checkpoint is replaced
+/// by every new play and forgotten when the turn passes.
+#[derive(Default, Clone)]
+pub struct UndoJournal {
+    last: Option<(PlayerId, Checkpoint)>, // The player who made the last play and the state before it.
+    used: HashMap<PlayerId, u32>,         // Undos each player already used.
+}
+
+impl UndoJournal {
+    /// Remembers the state before a play of a player, replacing the previous play.
+    pub fn record(&mut self, player_id: &PlayerId, checkpoint: Checkpoint) {
+        self.last = Some((player_id.clone(), checkpoint));
+    }
+
+    /// Forgets the last play, which can no longer be undone.
+    pub fn clear(&mut self) {
+        self.last = None;
+    }
+
+    /// Checks that a player may undo the last play.
+    ///
+    /// # Arguments
+    /// * `player_id` - The player asking to undo.
+    /// * `limit` - Undos each player may use during the match.
+    ///
+    /// # Returns
+    /// * `Ok(())` - If the last play is the player's and they have an undo left.
+    /// * `Err(GameLogicError)` - If undo is disabled, the player has none left or made no play to undo.
+    pub fn check(&self, player_id: &PlayerId, limit: u32) -> Result<(), GameLogicError> {
+        if limit == 0 {
+            return Err(GameLogicError::UndoDisabled);
+        }
+
+        if self.used.get(player_id).copied().unwrap_or(0) >= limit {
+            return Err(GameLogicError::UndoLimitReached(limit));
+        }
+
+        match &self.last {
+            Some((last_player, _)) if last_player == player_id => Ok(()),
+            _ => Err(GameLogicError::NothingToUndo),
+        }
+    }
+
+    /// Takes the state before the last play of a player, counting it against their undos.
+    ///
+    /// # Returns
+    /// The checkpoint to restore, or an error if `check` fails.
+    pub fn take(&mut self, player_id: &PlayerId, limit: u32) -> Result<Checkpoint, GameLogicError> {
+        self.check(player_id, limit)?;
+        let (_, checkpoint) = self.last.take().ok_or(GameLogicError::NothingToUndo)?;
+        *self.used.entry(player_id.clone()).or_default() += 1;
+        Ok(checkpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entity::player::PlayerView;
+    use crate::game::game_state::GameState;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    #[tokio::test]
+    async fn test_only_the_last_play_of_a_player_can_be_undone() {
+        let red: PlayerId = "red".into();
+        let blue: PlayerId = "blue".into();
+        let mut views = HashMap::new();
+        views.insert(
+            red.clone(),
+            Arc::new(RwLock::new(PlayerView::from_player(&red, 30))),
+        );
+        let state = GameState::new_game(views);
+
+        let mut journal = UndoJournal::default();
+        assert!(matches!(
+            journal.check(&red, 0),
+            Err(GameLogicError::UndoDisabled)
+        ));
+        assert!(matches!(
+            journal.check(&red, 1),
+            Err(GameLogicError::NothingToUndo)
+        ));
+
+        journal.record(&red, Checkpoint::capture(&state).await);
+        assert!(matches!(
+            journal.check(&blue, 1),
+            Err(GameLogicError::NothingToUndo)
+        ));
+        state.player_views.read().await[&red].write().await.health = 12;
+        journal.take(&red, 1).unwrap().restore(&state).await;
+        assert_eq!(
+            30,
+            state.player_views.read().await[&red].read().await.health
+        );
+
+        journal.record(&red, Checkpoint::capture(&state).await);
+        assert!(matches!(
+            journal.take(&red, 1),
+            Err(GameLogicError::UndoLimitReached(1))
+        ));
+        journal.clear();
+        assert!(journal.check(&red, 2).is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use crate::models::ids::CardDefId;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Key-value store scoped to the match, giving card scripts memory across turns.
+///
+/// Every card definition writes to its own namespace, so two cards using the same key (such as
+/// `corpses_consumed`) never overwrite each other. Ordered maps keep the canonical encoding, and
+/// so the state hash, independent of insertion order.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct MatchVariables {
+    namespaces: BTreeMap<CardDefId, BTreeMap<String, Value>>,
+}
+
+impl MatchVariables {
+    /// Returns a copy of every variable of a namespace, empty if the card never stored anything.
+    pub fn namespace(&self, namespace: &CardDefId) -> BTreeMap<String, Value> {
+        self.namespaces.get(namespace).cloned().unwrap_or_default()
+    }
+
+    /// Stores a variable in a namespace; storing `null` removes it.
+    pub fn set(&mut self, namespace: &CardDefId, key: String, value: Value) {
+        if value.is_null() {
+            if let Some(variables) = self.namespaces.get_mut(namespace) {
+                variables.remove(&key);
+                if variables.is_empty() {
+                    self.namespaces.remove(namespace);
+                }
+            }
+            return;
+        }
+
+        self.namespaces
+            .entry(namespace.clone())
+            .or_default()
+            .insert(key, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_namespaces_do_not_collide() {
+        let mut variables = MatchVariables::default();
+        variables.set(&"ghoul".into(), "consumed".to_string(), json!(2));
+        variables.set(&"vulture".into(), "consumed".to_string(), json!(5));
+
+        assert_eq!(json!(2), variables.namespace(&"ghoul".into())["consumed"]);
+        assert_eq!(json!(5), variables.namespace(&"vulture".into())["consumed"]);
+        assert!(variables.namespace(&"wolf".into()).is_empty());
+    }
+
+    #[test]
+    fn test_null_removes_the_variable() {
+        let mut variables = MatchVariables::default();
+        variables.set(&"ghoul".into(), "consumed".to_string(), json!(2));
+        variables.set(&"ghoul".into(), "consumed".to_string(), Value::Null);
+
+        assert_eq!(MatchVariables::default(), variables);
+    }
+}
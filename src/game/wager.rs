@@ -0,0 +1,152 @@
+use crate::models::client_requests::ConfirmStakeRequest;
+use crate::models::ids::PlayerId;
+use crate::models::init_server::Stake;
+use crate::utils::errors::GameLogicError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// How the stake of a wagered match was settled, included in the match report.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StakeSettlement {
+    pub amount: u64,
+    pub currency: String,
+    pub outcome: SettlementOutcome,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum SettlementOutcome {
+    /// The winner takes the stake of every player.
+    Won { winner_id: PlayerId },
+    /// The match was a draw; every player gets their stake back.
+    Draw,
+    /// The match ended before every player confirmed the stake; nothing changes hands.
+    Refunded { unconfirmed: Vec<PlayerId> },
+}
+
+/// The stake of a wagered match and the players who acknowledged it.
+///
+/// Player actions are refused until every player sent a matching `ConfirmStake`.
+pub struct Wager {
+    pub stake: Stake,
+    players: Vec<PlayerId>,
+    confirmed: HashSet<PlayerId>,
+}
+
+impl Wager {
+    pub fn new(stake: Stake, players: Vec<PlayerId>) -> Self {
+        Self {
+            stake,
+            players,
+            confirmed: HashSet::new(),
+        }
+    }
+
+    /// Records a player's acknowledgement of the stake.
+    ///
+    /// # Returns
+    /// * `Ok(bool)` - Whether every player has now confirmed.
+    /// * `Err(GameLogicError)` - If the player is not part of the match or acknowledged another
+    ///   stake.
+    pub fn confirm(
+        &mut self,
+        player_id: &PlayerId,
+        request: &ConfirmStakeRequest,
+    ) -> Result<bool, GameLogicError> {
+        if !self.players.contains(player_id) {
+            return Err(GameLogicError::PlayerNotFound);
+        }
+
+        if request.amount != self.stake.amount || request.currency != self.stake.currency {
+            return Err(GameLogicError::StakeMismatch);
+        }
+
+        self.confirmed.insert(player_id.clone());
+        Ok(self.is_confirmed())
+    }
+
+    /// Whether every player confirmed the stake.
+    pub fn is_confirmed(&self) -> bool {
+        self.players.iter().all(|id| self.confirmed.contains(id))
+    }
+
+    /// Settles the stake once the match ended.
+    ///
+    /// # Arguments
+    /// * `winner_id` - The winning player, or `None` for a draw.
+    pub fn settle(&self, winner_id: Option<&PlayerId>) -> StakeSettlement {
+        let outcome = match (self.is_confirmed(), winner_id) {
+            (false, _) => SettlementOutcome::Refunded {
+                unconfirmed: self
+                    .players
+                    .iter()
+                    .filter(|id| !self.confirmed.contains(*id))
+                    .cloned()
+                    .collect(),
+            },
+            (true, Some(winner_id)) => SettlementOutcome::Won {
+                winner_id: winner_id.clone(),
+            },
+            (true, None) => SettlementOutcome::Draw,
+        };
+
+        StakeSettlement {
+            amount: self.stake.amount,
+            currency: self.stake.currency.clone(),
+            outcome,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wager() -> Wager {
+        let stake = Stake {
+            amount: 100,
+            currency: String::from("gold"),
+        };
+        Wager::new(stake, vec!["red".into(), "blue".into()])
+    }
+
+    fn confirmation(amount: u64) -> ConfirmStakeRequest {
+        ConfirmStakeRequest {
+            amount,
+            currency: String::from("gold"),
+        }
+    }
+
+    #[test]
+    fn test_stake_is_settled_once_confirmed() {
+        let mut wager = wager();
+        assert!(matches!(
+            wager.confirm(&"red".into(), &confirmation(50)),
+            Err(GameLogicError::StakeMismatch)
+        ));
+        assert!(!wager.confirm(&"red".into(), &confirmation(100)).unwrap());
+        assert!(wager.confirm(&"blue".into(), &confirmation(100)).unwrap());
+
+        let settlement = wager.settle(Some(&"blue".into()));
+        assert_eq!(
+            SettlementOutcome::Won {
+                winner_id: "blue".into()
+            },
+            settlement.outcome
+        );
+        assert_eq!(SettlementOutcome::Draw, wager.settle(None).outcome);
+    }
+
+    #[test]
+    fn test_unconfirmed_stake_is_refunded() {
+        let mut wager = wager();
+        wager.confirm(&"red".into(), &confirmation(100)).unwrap();
+
+        assert_eq!(
+            SettlementOutcome::Refunded {
+                unconfirmed: vec!["blue".into()]
+            },
+            wager.settle(Some(&"red".into())).outcome
+        );
+    }
+}
@@ -0,0 +1,497 @@
+use crate::models::client_requests::ConnectionRequest;
+use crate::tcp::encoding::PayloadEncoding;
+use crate::tcp::handshake::{HandshakeRequest, PROTOCOL_VERSION, SERVER_FEATURES};
+use crate::tcp::header::HeaderType;
+use crate::tcp::packet::Packet;
+use crate::utils::errors::LoadTestError;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::TcpStream;
+use tokio::sync::oneshot;
+
+pub const USAGE: &str = "usage: tcp-server loadtest --address <host:port> [--connections <count>] \
+[--players <id,...>] [--script <file.json>] [--iterations <count>] [--timeout <ms>] \
+[--max-p99 <ms>] [--max-loss <percent>]";
+
+/// A step of the script every connection replays, as written in the script file.
+#[derive(Debug, Deserialize)]
+pub struct ScriptStep {
+    pub header: String, // Name of the packet type, as logged: `PING`, `PLAY_CARD`...
+    #[serde(default)]
+    pub payload: serde_json::Value, // Sent in CBOR.
+    #[serde(default)]
+    pub delay: u64, // Milliseconds waited before the step is sent.
+    #[serde(default = "default_reply")]
+    pub reply: bool, // Whether the server answers the step; only answered steps are timed.
+}
+
+fn default_reply() -> bool {
+    true
+}
+
+/// A step of the script, ready to be sent.
+#[derive(Debug, Clone)]
+struct Step {
+    header_type: HeaderType,
+    payload: Vec<u8>,
+    delay: Duration,
+    reply: bool,
+}
+
+impl Step {
+    fn resolve(step: &ScriptStep) -> Result<Self, LoadTestError> {
+        let header_type = (0..=u8::MAX)
+            .filter_map(|byte| HeaderType::try_from(byte).ok())
+            .find(|header_type| header_type.to_string().eq_ignore_ascii_case(&step.header))
+            .ok_or_else(|| {
+                LoadTestError::InvalidScript(format!("unknown packet type `{}`", step.header))
+            })?;
+        let payload = serde_cbor::to_vec(&step.payload)
+            .map_err(|error| LoadTestError::InvalidScript(error.to_string()))?;
+        Ok(Self {
+            header_type,
+            payload,
+            delay: Duration::from_millis(step.delay),
+            reply: step.reply,
+        })
+    }
+}
+
+/// What a load test does, read from the command line.
+#[derive(Debug)]
+pub struct LoadTestOptions {
+    address: String,
+    connections: usize,    // Connections opened at once.
+    players: Vec<String>,  // Players the connections connect as, in turn.
+    script: Vec<Step>,     // Replayed by every connection once connected.
+    iterations: usize,     // Times each connection replays the script.
+    timeout: Duration,     // Time an answer may take before its request counts as lost.
+    max_p99: Option<u64>,  // Milliseconds; the test fails if the 99th percentile is higher.
+    max_loss: Option<f64>, // Percent; the test fails if more requests are lost.
+}
+
+impl LoadTestOptions {
+    /// Reads the options following the `loadtest` subcommand.
+    ///
+    /// Without `--script`, every connection pings the server. Connections connect with
+    /// `mock:<player id>` tokens, which the server only accepts with `MOCK_AUTH` set.
+    ///
+    /// # Returns
+    /// * `Ok(LoadTestOptions)` - The options, with the script loaded.
+    /// * `Err(LoadTestError)` - If an option is unknown or invalid, or the script cannot be read.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, LoadTestError> {
+        let mut options = Self {
+            address: String::new(),
+            connections: 10,
+            players: Vec::new(),
+            script: vec![Step {
+                header_type: HeaderType::Ping,
+                payload: Vec::new(),
+                delay: Duration::ZERO,
+                reply: true,
+            }],
+            iterations: 100,
+            timeout: Duration::from_secs(2),
+            max_p99: None,
+            max_loss: None,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(option) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| LoadTestError::MissingValue(option.clone()))?;
+            let invalid = |error: &dyn std::fmt::Display| {
+                LoadTestError::InvalidValue(option.clone(), error.to_string())
+            };
+            match option.as_str() {
+                "--address" => options.address = value,
+                "--connections" => options.connections = value.parse().map_err(|e| invalid(&e))?,
+                "--players" => {
+                    options.players = value.split(',').map(str::to_string).collect();
+                }
+                "--script" => {
+                    let file = std::fs::read_to_string(&value).map_err(|e| invalid(&e))?;
+                    let steps: Vec<ScriptStep> = serde_json::from_str(&file)
+                        .map_err(|e| LoadTestError::InvalidScript(e.to_string()))?;
+                    options.script = steps.iter().map(Step::resolve).collect::<Result<_, _>>()?;
+                }
+                "--iterations" => options.iterations = value.parse().map_err(|e| invalid(&e))?,
+                "--timeout" => {
+                    let millis = value.parse().map_err(|e| invalid(&e))?;
+                    options.timeout = Duration::from_millis(millis);
+                }
+                "--max-p99" => options.max_p99 = Some(value.parse().map_err(|e| invalid(&e))?),
+                "--max-loss" => options.max_loss = Some(value.parse().map_err(|e| invalid(&e))?),
+                _ => return Err(LoadTestError::UnknownOption(option)),
+            }
+        }
+
+        if options.address.is_empty() {
+            return Err(LoadTestError::MissingValue(String::from("--address")));
+        }
+        if options.players.is_empty() {
+            options.players = (1..=options.connections)
+                .map(|index| format!("loadtest-{index}"))
+                .collect();
+        }
+        Ok(options)
+    }
+}
+
+/// What one connection of a load test measured.
+#[derive(Debug, Default)]
+struct ConnectionReport {
+    connected: bool,
+    connect_latency: Option<Duration>, // From opening the connection to the `ConnectAck`.
+    latencies: Vec<Duration>,          // Of the answered requests of the script.
+    sent: usize,                       // Requests of the script expecting an answer.
+    lost: usize,                       // Requests left unanswered within the timeout.
+}
+
+/// The results of a load test, over every connection.
+#[derive(Debug, Default)]
+pub struct LoadTestReport {
+    pub connections: usize,
+    pub connected: usize,
+    pub connect_latencies: Vec<Duration>, // Sorted.
+    pub latencies: Vec<Duration>,         // Sorted.
+    pub sent: usize,
+    pub lost: usize,
+}
+
+impl LoadTestReport {
+    fn collect(reports: Vec<ConnectionReport>) -> Self {
+        let mut report = Self {
+            connections: reports.len(),
+            ..Self::default()
+        };
+        for connection in reports {
+            report.connected += usize::from(connection.connected);
+            report.connect_latencies.extend(connection.connect_latency);
+            report.latencies.extend(connection.latencies);
+            report.sent += connection.sent;
+            report.lost += connection.lost;
+        }
+        report.connect_latencies.sort();
+        report.latencies.sort();
+        report
+    }
+
+    /// The latency under which `percentile` percent of the sorted `samples` fall.
+    pub fn percentile(samples: &[Duration], percentile: f64) -> Option<Duration> {
+        let last = samples.len().checked_sub(1)?;
+        let rank = (percentile / 100.0 * last as f64).round() as usize;
+        samples.get(rank.min(last)).copied()
+    }
+
+    /// Percent of the requests left unanswered.
+    pub fn loss(&self) -> f64 {
+        match self.sent {
+            0 => 0.0,
+            sent => self.lost as f64 * 100.0 / sent as f64,
+        }
+    }
+
+    fn summary(samples: &[Duration]) -> String {
+        let millis = |percentile| {
+            Self::percentile(samples, percentile).map_or(String::from("-"), |d| {
+                format!("{:.2}ms", d.as_secs_f64() * 1000.0)
+            })
+        };
+        format!(
+            "p50 {}  p90 {}  p99 {}  max {}  ({} samples)",
+            millis(50.0),
+            millis(90.0),
+            millis(99.0),
+            millis(100.0),
+            samples.len()
+        )
+    }
+
+    /// Fails if the report goes over a threshold of the options.
+    fn check(&self, options: &LoadTestOptions) -> Result<(), LoadTestError> {
+        let p99 = Self::percentile(&self.latencies, 99.0).unwrap_or_default();
+        if let Some(max_p99) = options.max_p99.filter(|max| p99.as_millis() > *max as u128) {
+            return Err(LoadTestError::ThresholdExceeded(format!(
+                "p99 latency {}ms is over {max_p99}ms",
+                p99.as_millis()
+            )));
+        }
+        if let Some(max_loss) = options.max_loss.filter(|max| self.loss() > *max) {
+            return Err(LoadTestError::ThresholdExceeded(format!(
+                "packet loss {:.2}% is over {max_loss}%",
+                self.loss()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for LoadTestReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "connections  {}/{} connected",
+            self.connected, self.connections
+        )?;
+        writeln!(f, "connect      {}", Self::summary(&self.connect_latencies))?;
+        writeln!(f, "requests     {}", Self::summary(&self.latencies))?;
+        write!(
+            f,
+            "packet loss  {}/{} ({:.2}%)",
+            self.lost,
+            self.sent,
+            self.loss()
+        )
+    }
+}
+
+/// Requests waiting for their answer, by sequence number.
+type Pending = Arc<Mutex<HashMap<u16, oneshot::Sender<Packet>>>>;
+
+/// A connection of a load test. Answers are matched to their request by the sequence number the
+/// server echoes; the pings of the server are answered so the connection is kept alive.
+struct LoadTestConnection {
+    writer: Arc<tokio::sync::Mutex<OwnedWriteHalf>>,
+    pending: Pending,
+    sequence: AtomicU16,
+}
+
+impl LoadTestConnection {
+    async fn open(address: &str) -> std::io::Result<Self> {
+        let (mut read, write) = TcpStream::connect(address).await?.into_split();
+        let writer = Arc::new(tokio::sync::Mutex::new(write));
+        let pending: Pending = Arc::default();
+
+        let (reader_writer, reader_pending) = (Arc::clone(&writer), Arc::clone(&pending));
+        tokio::spawn(async move {
+            while let Ok(Some(packet)) = Packet::read_from(&mut read).await {
+                if packet.header.header_type == HeaderType::Ping {
+                    let pong = Packet::reply_to(&packet, HeaderType::Pong, &packet.payload);
+                    let _ = pong.write_to(&mut *reader_writer.lock().await).await;
+                    continue;
+                }
+
+                let waiting = reader_pending
+                    .lock()
+                    .unwrap()
+                    .remove(&packet.header.sequence);
+                if let Some(waiting) = waiting {
+                    let _ = waiting.send(packet);
+                }
+            }
+            // Requests still waiting are lost with the connection.
+            reader_pending.lock().unwrap().clear();
+        });
+
+        Ok(Self {
+            writer,
+            pending,
+            sequence: AtomicU16::new(1),
+        })
+    }
+
+    /// Sends a request and waits for its answer.
+    ///
+    /// # Returns
+    /// The answer, or `None` if none came within the timeout.
+    async fn request(&self, step: &Step, timeout: Duration) -> Option<Packet> {
+        // Packets the server sends on its own carry the sequence number 0.
+        let mut sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        if sequence == 0 {
+            sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        }
+        let (answered, answer) = oneshot::channel();
+        self.pending.lock().unwrap().insert(sequence, answered);
+
+        let mut packet = Packet::new(step.header_type.clone(), &step.payload);
+        packet.header.sequence = sequence;
+        if self.send(&packet).await.is_err() {
+            self.pending.lock().unwrap().remove(&sequence);
+            return None;
+        }
+
+        let answer = tokio::time::timeout(timeout, answer).await;
+        self.pending.lock().unwrap().remove(&sequence);
+        answer.ok()?.ok()
+    }
+
+    async fn send(&self, packet: &Packet) -> std::io::Result<()> {
+        let mut writer = self.writer.lock().await;
+        packet.write_to(&mut *writer).await.map(|_| ())
+    }
+
+    async fn close(&self) {
+        let _ = self.writer.lock().await.shutdown().await;
+    }
+}
+
+/// Connects as a player, then replays the script.
+async fn run_connection(options: Arc<LoadTestOptions>, index: usize) -> ConnectionReport {
+    let mut report = ConnectionReport::default();
+    let player_id = &options.players[index % options.players.len()];
+
+    let started = Instant::now();
+    let Ok(connection) = LoadTestConnection::open(&options.address).await else {
+        return report;
+    };
+    let handshake = HandshakeRequest {
+        version: PROTOCOL_VERSION,
+        features: SERVER_FEATURES,
+        checksums: Vec::new(),
+        compression: Vec::new(),
+        encoding: PayloadEncoding::Cbor,
+    };
+    let connect = ConnectionRequest {
+        player_id: player_id.as_str().into(),
+        auth_token: format!("mock:{player_id}"),
+        current_deck_id: String::new(),
+        match_id: None,
+    };
+    let opening = [
+        (HeaderType::Handshake, serde_cbor::to_vec(&handshake)),
+        (HeaderType::Connect, serde_cbor::to_vec(&connect)),
+    ];
+    for (header_type, payload) in opening {
+        let step = Step {
+            header_type,
+            payload: payload.unwrap_or_default(),
+            delay: Duration::ZERO,
+            reply: true,
+        };
+        match connection.request(&step, options.timeout).await {
+            Some(answer)
+                if matches!(
+                    answer.header.header_type,
+                    HeaderType::Handshake | HeaderType::ConnectAck
+                ) => {}
+            _ => {
+                connection.close().await;
+                return report;
+            }
+        }
+    }
+    report.connected = true;
+    report.connect_latency = Some(started.elapsed());
+
+    for step in (0..options.iterations).flat_map(|_| options.script.iter()) {
+        tokio::time::sleep(step.delay).await;
+        if !step.reply {
+            let _ = connection
+                .send(&Packet::new(step.header_type.clone(), &step.payload))
+                .await;
+            continue;
+        }
+
+        report.sent += 1;
+        let sent = Instant::now();
+        match connection.request(step, options.timeout).await {
+            Some(_) => report.latencies.push(sent.elapsed()),
+            None => report.lost += 1,
+        }
+    }
+
+    connection.close().await;
+    report
+}
+
+/// Runs the `loadtest` subcommand: opens the connections at once, replays the script on each and
+/// prints the latency percentiles and the packet loss.
+///
+/// # Returns
+/// * `Ok(())` - If the test ran within its thresholds.
+/// * `Err(LoadTestError)` - If the options are invalid or a threshold was exceeded.
+pub async fn run(args: impl IntoIterator<Item = String>) -> Result<(), LoadTestError> {
+    let options = Arc::new(LoadTestOptions::parse(args)?);
+    println!(
+        "Load testing `{}` with {} connections, {} iterations of {} steps",
+        options.address,
+        options.connections,
+        options.iterations,
+        options.script.len()
+    );
+
+    let connections: Vec<_> = (0..options.connections)
+        .map(|index| tokio::spawn(run_connection(Arc::clone(&options), index)))
+        .collect();
+    let mut reports = Vec::with_capacity(connections.len());
+    for connection in connections {
+        reports.push(connection.await.unwrap_or_default());
+    }
+
+    let report = LoadTestReport::collect(reports);
+    println!("{report}");
+    report.check(&options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_options_default_to_pinging_as_generated_players() {
+        let options =
+            LoadTestOptions::parse(args(&["--address", "127.0.0.1:8000", "--connections", "3"]))
+                .unwrap();
+        assert_eq!(
+            vec!["loadtest-1", "loadtest-2", "loadtest-3"],
+            options.players
+        );
+        assert_eq!(HeaderType::Ping, options.script[0].header_type);
+
+        assert!(matches!(
+            LoadTestOptions::parse(args(&["--connections", "3"])),
+            Err(LoadTestError::MissingValue(_))
+        ));
+        assert!(matches!(
+            LoadTestOptions::parse(args(&["--address", "a", "--speed", "1"])),
+            Err(LoadTestError::UnknownOption(_))
+        ));
+    }
+
+    #[test]
+    fn test_script_steps_name_packet_types() {
+        let step: ScriptStep =
+            serde_json::from_str(r#"{ "header": "play_card", "delay": 5 }"#).unwrap();
+        let step = Step::resolve(&step).unwrap();
+        assert_eq!(HeaderType::PlayCard, step.header_type);
+        assert!(step.reply);
+
+        let step: ScriptStep = serde_json::from_str(r#"{ "header": "NOT_A_PACKET" }"#).unwrap();
+        assert!(Step::resolve(&step).is_err());
+    }
+
+    #[test]
+    fn test_report_percentiles_and_thresholds() {
+        let reports = (1..=100)
+            .map(|millis| ConnectionReport {
+                connected: true,
+                connect_latency: None,
+                latencies: vec![Duration::from_millis(millis)],
+                sent: 2,
+                lost: 1,
+            })
+            .collect();
+        let report = LoadTestReport::collect(reports);
+        let percentile = |p| LoadTestReport::percentile(&report.latencies, p).unwrap();
+        assert_eq!(Duration::from_millis(51), percentile(50.0));
+        assert_eq!(Duration::from_millis(99), percentile(99.0));
+        assert_eq!(50.0, report.loss());
+
+        let mut options = LoadTestOptions::parse(args(&["--address", "a"])).unwrap();
+        options.max_p99 = Some(100);
+        assert!(report.check(&options).is_ok());
+        options.max_loss = Some(10.0);
+        assert!(report.check(&options).is_err());
+    }
+}
@@ -1,21 +1,96 @@
+// The relay build leaves out most of what the game server uses.
+#![cfg_attr(not(feature = "game"), allow(dead_code))]
+
 use config::{Config, File};
+#[cfg(feature = "game")]
 use models::settings::Settings;
-use std::{io::Error, sync::Arc};
+use std::{io::Error, path::Path, sync::Arc};
+#[cfg(feature = "game")]
+use std::time::Duration;
 use std::sync::LazyLock;
-use tcp::server::ServerInstance;
+#[cfg(feature = "game")]
+use tcp::match_registry::MatchRegistry;
+#[cfg(feature = "game")]
 use tokio::sync::OnceCell;
-use crate::tcp::server::UninitializedServer;
+#[cfg(feature = "game")]
+use crate::tcp::runtime::ServerRuntime;
+use crate::utils::logger::{self, Logger};
+#[cfg(feature = "game")]
+use crate::utils::http_service::HttpService;
+#[cfg(feature = "game")]
+use crate::utils::lifecycle::Lifecycle;
+#[cfg(feature = "game")]
+use crate::utils::metrics::Metrics;
+#[cfg(feature = "game")]
+use crate::utils::runtime_config::{ConfigWatcher, SharedConfig};
+use crate::utils::ready;
+#[cfg(not(feature = "game"))]
+use crate::utils::ready::ReadySignal;
+use crate::utils::runtime_flags::RuntimeFlags;
 
+#[cfg(feature = "game")]
+mod admin;
+#[cfg(feature = "game")]
 mod game;
+#[cfg(feature = "game")]
+mod loadtest;
 mod models;
+#[cfg(not(feature = "game"))]
+mod relay;
 mod tcp;
+#[cfg(all(test, feature = "game"))]
+mod test_support;
 mod utils;
 
+/// The settings file, watched for changes to the tunable settings.
+#[cfg(feature = "game")]
+const CONFIG_PATH: &str = "config.toml";
+
+#[cfg(feature = "game")]
 static SETTINGS: OnceCell<Settings> = OnceCell::const_new();
-static SERVER_INSTANCE: OnceCell<ServerInstance> = OnceCell::const_new();
+#[cfg(feature = "game")]
+static MATCHES: LazyLock<MatchRegistry> = LazyLock::new(MatchRegistry::default);
+#[cfg(feature = "game")]
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+#[cfg(feature = "game")]
+static LIFECYCLE: LazyLock<Lifecycle> = LazyLock::new(Lifecycle::default);
+#[cfg(feature = "game")]
+static HTTP_SERVICE: LazyLock<HttpService> = LazyLock::new(HttpService::default);
+#[cfg(feature = "game")]
+static RUNTIME_CONFIG: LazyLock<SharedConfig> = LazyLock::new(SharedConfig::from_settings);
+#[cfg(feature = "game")]
+static CONFIG_WATCHER: OnceCell<Arc<ConfigWatcher>> = OnceCell::const_new();
+static RUNTIME_FLAGS: LazyLock<RuntimeFlags> = LazyLock::new(RuntimeFlags::default);
 
+#[cfg(feature = "game")]
 #[tokio::main]
 async fn main() -> Result<(), Error> {
+    if std::env::args().nth(1).as_deref() == Some("loadtest") {
+        return loadtest::run(std::env::args().skip(2))
+            .await
+            .map_err(|error| {
+                eprintln!("{error}\n{}", loadtest::USAGE);
+                Error::other(error.to_string())
+            });
+    }
+
+    if std::env::args().nth(1).as_deref() == Some("protocol-spec") {
+        let path = std::env::args().nth(2);
+        return tcp::protocol::spec::write(path.as_deref().map(Path::new)).map_err(|error| {
+            eprintln!("{error}\n{}", tcp::protocol::spec::USAGE);
+            error
+        });
+    }
+
+    if std::env::args().nth(1).as_deref() == Some("test-scripts") {
+        return game::script_harness::run(std::env::args().skip(2))
+            .await
+            .map_err(|error| {
+                eprintln!("{error}\n{}", game::script_harness::USAGE);
+                Error::other(error.to_string())
+            });
+    }
+
     SETTINGS
         .set(
             Config::builder()
@@ -26,16 +101,105 @@ async fn main() -> Result<(), Error> {
                 .unwrap(),
         )
         .unwrap();
+    if let Some(settings) = SETTINGS.get() {
+        logger::init(settings.log_level, settings.log_format);
+        if settings.mock_auth {
+            logger!(
+                WARN,
+                "[SERVER] `MOCK_AUTH` is set: players are not authenticated"
+            );
+        }
+    }
+
+    match ConfigWatcher::new(Path::new(CONFIG_PATH), RUNTIME_CONFIG.clone()) {
+        Ok(watcher) => {
+            let watcher = Arc::new(watcher);
+            let _ = CONFIG_WATCHER.set(Arc::clone(&watcher));
+            let interval = SETTINGS.get().map_or(5, |s| s.config_reload_interval);
+            if interval > 0 {
+                tokio::spawn(watcher.watch(Duration::from_secs(interval)));
+            }
+        }
+        Err(error) => logger!(ERROR, "[CONFIG] Could not watch `{CONFIG_PATH}`: {error}"),
+    }
+
+    let ready_file = SETTINGS
+        .get()
+        .and_then(|s| s.ready_file.as_deref())
+        .map(Path::new);
+    if let Some(path) = ready_file {
+        ready::clear_ready_file(path)?;
+    }
 
-    let port = 8000;
-    
-    if let Ok(uninitialized) = UninitializedServer::create_instance(port).await {
-        let server_arc = Arc::new(uninitialized);
-        if let Ok(initialized_server) = Arc::clone(&server_arc).await_for_initialization().await {
-            let initialized_clone = Arc::new(initialized_server);
-            initialized_clone.listen().await;
+    if let Some(address) = SETTINGS.get().and_then(|s| s.health_address) {
+        match admin::health::bind(address).await {
+            Ok(listener) => {
+                tokio::spawn(admin::health::serve(listener));
+            }
+            Err(error) => logger!(ERROR, "[HEALTH] Could not listen on `{address}`: {error}"),
         }
     }
 
+    if let Some(address) = SETTINGS.get().and_then(|s| s.metrics_address) {
+        match admin::prometheus::bind(address).await {
+            Ok(listener) => {
+                tokio::spawn(admin::prometheus::serve(listener));
+            }
+            Err(error) => logger!(ERROR, "[METRICS] Could not listen on `{address}`: {error}"),
+        }
+    }
+
+    let runtime = ServerRuntime::new(ready_file.map(Path::to_path_buf));
+    let status = runtime.run().await;
+    std::process::exit(status.code)
+}
+
+/// Runs the packet relay built without the `game` feature: no scripts, no service calls and no
+/// game rules, only the packet framing.
+#[cfg(not(feature = "game"))]
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let settings = Config::builder()
+        .add_source(File::with_name("config"))
+        .build()
+        .and_then(|config| config.try_deserialize::<relay::RelaySettings>());
+    let settings = match settings {
+        Ok(settings) => {
+            logger::init(settings.log_level, settings.log_format);
+            settings
+        }
+        Err(error) => {
+            let settings = relay::RelaySettings::default();
+            logger::init(settings.log_level, settings.log_format);
+            logger!(
+                WARN,
+                "[RELAY] Could not read the settings, using the defaults: {error}"
+            );
+            settings
+        }
+    };
+
+    let ready_file = settings.ready_file.as_deref().map(Path::new);
+    if let Some(path) = ready_file {
+        ready::clear_ready_file(path)?;
+    }
+
+    let relay = Arc::new(relay::Relay::bind(&settings)?);
+    let addresses = relay.addresses();
+    let quic_addresses = relay.quic_addresses();
+    logger!(
+        INFO,
+        "[RELAY] tcp-server v{} relaying packets on {addresses:?}",
+        env!("CARGO_PKG_VERSION")
+    );
+    if !quic_addresses.is_empty() {
+        logger!(INFO, "[RELAY] Relaying QUIC clients on {quic_addresses:?}");
+    }
+    let signal = ReadySignal::new(addresses).with_quic(quic_addresses);
+    if let Err(error) = signal.announce(ready_file) {
+        logger!(ERROR, "[RELAY] Could not signal readiness: {error}");
+    }
+
+    relay.run().await;
     Ok(())
 }
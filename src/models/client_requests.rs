@@ -1,22 +1,183 @@
+use crate::game::combat::Block;
+use crate::game::sideboard::SideboardSwap;
 use serde::{Deserialize, Serialize};
+use crate::models::ids::{CardDefId, CardInstanceId, MatchId, PlayerId};
+use crate::tcp::payload::VersionedRequest;
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct ConnectionRequest {
-    pub player_id: String,
+    pub player_id: PlayerId,
     pub auth_token: String,
     pub current_deck_id: String,
+    #[serde(default)]
+    pub match_id: Option<MatchId>, // The match to join, needed when the process hosts several.
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct ReconnectionRequest {
-    pub player_id: String,
-    pub auth_token: String,
+    pub player_id: PlayerId,
+    #[serde(default)]
+    pub auth_token: String, // Checked by the auth server when no valid session token is sent.
+    #[serde(default)]
+    pub session_token: Option<String>, // The token of the last `ConnectAck`, checked locally.
+    #[serde(default)]
+    pub match_id: Option<MatchId>, // The match to rejoin, needed when the process hosts several.
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+/// The fields of a `Connect`, `Reconnect` or `Spectate` request telling which of the matches
+/// hosted by the process it is for.
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct MatchTarget {
+    #[serde(default)]
+    pub match_id: Option<MatchId>,
+    #[serde(default)]
+    pub player_id: Option<PlayerId>, // Routes to the match seating the player without a match id.
+}
+
+/// The credentials of a `Spectate` request, checked when the match restricts its viewers.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SpectateRequest {
+    #[serde(default)]
+    pub auth_token: String, // The token of the viewer's account; unused when the match is open.
+}
+
+/// Asks for the replay of a match, which the viewer must be allowed to watch.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct GetReplayRequest {
+    pub match_id: MatchId,
+    #[serde(default)]
+    pub auth_token: String, // The token of the viewer's account; unused when the match is open.
+}
+
+/// The join code of a `JoinPrivateMatch` request, which also carries the fields of a
+/// `ConnectionRequest`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct JoinPrivateMatchRequest {
+    pub join_code: String, // The code of the `PrivateMatchCreated` sent to the player who opened it.
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct DraftPickRequest {
+    pub card_id: CardDefId, // A card of the open pack.
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SideboardRequest {
+    pub swaps: Vec<SideboardSwap>, // Applied in order; none plays the deck unchanged.
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct EmoteRequest {
+    pub emote_id: String, // One of the predefined emotes.
+    #[serde(default)]
+    pub ping: Option<BoardPing>, // A spot of the board the emote points at.
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct BoardPing {
+    pub player_id: PlayerId, // Whose side of the board.
+    pub position: String,    // A `<zone>:<slot>` or `<slot>` position.
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ChatRequest {
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct MuteRequest {
+    pub player_id: PlayerId, // The player whose chat to mute.
+    #[serde(default = "default_muted")]
+    pub muted: bool, // `false` to unmute the player.
+}
+
+fn default_muted() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct PlayCardRequest {
-    pub actor_id: String,
-    pub card_id: String,
+    pub actor_id: PlayerId,
+    pub instance_id: CardInstanceId,
     pub target_id: Option<String>,
     pub target_position: Option<String>,
-}
\ No newline at end of file
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PromptResponse {
+    pub prompt_id: u32,
+    pub choice: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type")]
+pub enum BatchedAction {
+    PlayCard(PlayCardRequest),
+    PromptResponse(PromptResponse),
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ActionBatchRequest {
+    pub actions: Vec<BatchedAction>,
+}
+
+/// Activates the ability of a card on the board of the actor.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ActivateAbilityRequest {
+    pub actor_id: PlayerId,
+    pub instance_id: CardInstanceId,
+    pub target_id: Option<String>,
+}
+
+/// Attacks the opponent with creatures of the actor's board.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct DeclareAttackersRequest {
+    pub actor_id: PlayerId,
+    pub attackers: Vec<CardInstanceId>,
+    #[serde(default)]
+    pub defender_id: Option<PlayerId>, // The opponent attacked in a team match; the next seat if unset.
+}
+
+/// Blocks the attackers of the opponent; attackers left out are unblocked.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct DeclareBlockersRequest {
+    pub actor_id: PlayerId,
+    #[serde(default)]
+    pub blocks: Vec<Block>,
+}
+
+/// Acknowledges the stake of a wagered match; must repeat the stake sent in `InitServer`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ConfirmStakeRequest {
+    pub amount: u64,
+    pub currency: String,
+}
+
+/// Asks for the events recorded after `since`, at most `limit` of them (the latest ones).
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct HistoryRequest {
+    #[serde(default)]
+    pub since: u64,
+    pub limit: Option<usize>,
+}
+
+// Every request still has its first schema; see `payload::VersionedRequest`.
+impl VersionedRequest for ConnectionRequest {}
+impl VersionedRequest for ReconnectionRequest {}
+impl VersionedRequest for MatchTarget {}
+impl VersionedRequest for SpectateRequest {}
+impl VersionedRequest for GetReplayRequest {}
+impl VersionedRequest for JoinPrivateMatchRequest {}
+impl VersionedRequest for DraftPickRequest {}
+impl VersionedRequest for SideboardRequest {}
+impl VersionedRequest for EmoteRequest {}
+impl VersionedRequest for ChatRequest {}
+impl VersionedRequest for MuteRequest {}
+impl VersionedRequest for PlayCardRequest {}
+impl VersionedRequest for PromptResponse {}
+impl VersionedRequest for ActionBatchRequest {}
+impl VersionedRequest for ActivateAbilityRequest {}
+impl VersionedRequest for DeclareAttackersRequest {}
+impl VersionedRequest for DeclareBlockersRequest {}
+impl VersionedRequest for ConfirmStakeRequest {}
+impl VersionedRequest for HistoryRequest {}
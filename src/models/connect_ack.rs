@@ -0,0 +1,44 @@
+use crate::game::game_state::PlayerGameStateView;
+use crate::game::match_format::{TeamRules, TurnStructure};
+use crate::game::rules::RulesProfile;
+use crate::game::think_time::ThinkTimeVisibility;
+use crate::models::ids::{MatchId, PlayerId};
+use serde::Serialize;
+
+/// Payload of a `ConnectAck`, sent once a player is connected or reconnected, so the client can
+/// render the match straight away instead of waiting for the first `GameState`.
+#[derive(Serialize, Clone)]
+pub struct ConnectAck {
+    pub session_token: String, // The token to present in `Reconnect`.
+    pub expires_at: i64,       // Unix timestamp (milliseconds) after which the token is refused.
+    pub match_id: MatchId,
+    pub match_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<PlayerGameStateView>, // The view of the match the player is allowed to see.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opponent: Option<OpponentProfile>,
+    pub rules: MatchRules,
+}
+
+/// The public profile of the opponent.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct OpponentProfile {
+    pub player_id: PlayerId,
+    pub username: String,
+    pub level: u32,
+}
+
+/// How the match is played, as far as the client needs to know.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MatchRules {
+    pub format: String,                  // The name of the match format.
+    pub best_of: u32,                    // Games in the series this match is part of.
+    pub starting_health: i32,            // Health each player starts the match with.
+    pub turns: TurnStructure,            // Starting mana and who takes the first turn.
+    pub rules: RulesProfile,             // Undo, combat and turn clock rules of the match.
+    pub think_time: ThinkTimeVisibility, // Whose think time is sent in game states.
+    pub disconnect_grace_ms: u64,        // Time a player who dropped has to reconnect.
+    pub turn_order: Vec<PlayerId>,       // Every player in the order they take their turns.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub teams: Option<TeamRules>, // The teams of a team match.
+}
@@ -1,12 +1,42 @@
-#[derive(Default)]
+use crate::utils::errors::{GameInstanceError, ServerInstanceError};
+
+#[derive(Default, Debug, Clone, PartialEq)]
 pub struct ExitStatus {
     pub code: i32,
     pub reason: String,
 }
 
+impl ExitStatus {
+    pub fn new(code: ExitCode, reason: impl Into<String>) -> Self {
+        Self {
+            code: code as i32,
+            reason: reason.into(),
+        }
+    }
+}
+
 #[repr(i32)]
 pub enum ExitCode {
     MatchEnded = 00,
-    
+
     CardRequestFailed = 10,
-}
\ No newline at end of file
+
+    PlayersNoShow = 20,
+    EndedByOperator = 21,
+    PlayerForfeited = 22,
+
+    ListenFailed = 30,
+    InitializationFailed = 31,
+}
+
+/// The code a process hosting a single match exits with when the match could not be hosted.
+impl From<&ServerInstanceError> for ExitCode {
+    fn from(error: &ServerInstanceError) -> Self {
+        match error {
+            ServerInstanceError::GameInstance(GameInstanceError::CardsUnavailable { .. }) => {
+                ExitCode::CardRequestFailed
+            }
+            _ => ExitCode::InitializationFailed,
+        }
+    }
+}
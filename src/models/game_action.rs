@@ -1,9 +1,70 @@
+use crate::game::status_effect::StatusKind;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum GameAction {
-    DealDamage { target: String, amount: u32 },
-    Heal { target: String, amount: u32 },
-    Summon { id: String, position: String }
-}
\ No newline at end of file
+    DealDamage {
+        target: String,
+        amount: u32,
+    },
+    Heal {
+        target: String,
+        amount: u32,
+    },
+    Summon {
+        id: String,
+        position: String,
+    },
+    /// Draws `count` cards from the deck of `player`.
+    DrawCard {
+        player: String,
+        count: u32,
+    },
+    /// Moves one copy of a creature to the board of `controller`, handing it back after `turns`
+    /// turns if set.
+    ChangeController {
+        target: String,
+        controller: String,
+        turns: Option<u32>,
+    },
+    /// Stores a value in the match variables of the card running the script; a missing or `nil`
+    /// value removes the variable.
+    SetVariable {
+        key: String,
+        #[serde(default)]
+        value: serde_json::Value,
+    },
+    /// Sends a card on the board to its owner's graveyard.
+    DestroyCard {
+        target: String,
+    },
+    /// Sends a card from a hand to its owner's graveyard.
+    DiscardCard {
+        target: String,
+    },
+    /// Puts a card from a graveyard back onto its owner's board, at `position` if set.
+    ResurrectCard {
+        target: String,
+        #[serde(default)]
+        position: Option<String>,
+    },
+    /// Returns a card from a graveyard to its owner's hand.
+    ReturnFromGraveyard {
+        target: String,
+    },
+    /// Attaches a status effect to a player or a card, lasting `turns` turn boundaries if set.
+    ApplyStatusEffect {
+        target: String,
+        kind: StatusKind,
+        #[serde(default)]
+        magnitude: u32,
+        turns: Option<u32>,
+    },
+    /// Plays a long animation on the clients; the turn timer of the acting player pauses for
+    /// `duration_ms`, within the bounds of the rules of the match.
+    PlayCinematic {
+        name: String,
+        duration_ms: u64,
+    },
+}
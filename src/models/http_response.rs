@@ -1,16 +1,17 @@
 use serde::{Deserialize, Serialize};
 use crate::game::entity::card::Card;
+use crate::models::ids::PlayerId;
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct PartialPlayerProfile {
-    pub id: String,
+    pub id: PlayerId,
     pub level: u32,
     pub username: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct PreloadedPlayer {
-    pub id: String,
+    pub id: PlayerId,
     pub level: u32,
     pub username: String,
 }
@@ -18,10 +19,13 @@ pub struct PreloadedPlayer {
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct AuthenticatedPlayer {
     #[serde(alias = "playerId")]
-    pub player_id: String,
+    pub player_id: PlayerId,
     pub username: String,
     #[serde(alias = "isBanned")]
-    pub is_banned: bool
+    pub is_banned: bool,
+    /// The token the player authenticated with; not part of the auth server response.
+    #[serde(skip)]
+    pub session_token: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
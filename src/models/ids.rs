@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// Declares a string-backed identifier type.
+///
+/// Each type serializes as a plain string, so payloads keep their shape, but the different kinds
+/// of ids cannot be mixed up at compile time.
+macro_rules! id_type {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+        #[serde(transparent)]
+        pub struct $name(String);
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(id: String) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(id: &str) -> Self {
+                Self(id.to_string())
+            }
+        }
+    };
+}
+
+id_type!(
+    /// Identifies a player account.
+    PlayerId
+);
+
+id_type!(
+    /// Identifies a card definition in the catalog, shared by every copy of the card.
+    CardDefId
+);
+
+id_type!(
+    /// Identifies a match hosted by this server.
+    MatchId
+);
+
+id_type!(
+    /// Identifies one copy of a card in a match, so copies of the same card can be told apart.
+    CardInstanceId
+);
+
+impl CardInstanceId {
+    /// The id of the `n`th card instantiated in the match.
+    pub fn nth(n: u64) -> Self {
+        Self(format!("c{n}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_serialize_as_plain_strings() {
+        let id = PlayerId::from("red");
+        let encoded = serde_cbor::to_vec(&id).unwrap();
+
+        assert_eq!(serde_cbor::to_vec(&"red").unwrap(), encoded);
+        assert_eq!(id, serde_cbor::from_slice::<PlayerId>(&encoded).unwrap());
+        assert_eq!("red", id.to_string());
+    }
+}
@@ -1,14 +1,103 @@
+use crate::game::card_pool::SignedPool;
+use crate::game::draft::DraftSettings;
+use crate::game::entity::deck::Deck;
+use crate::game::rules::RulesProfile;
+use crate::models::http_response::PreloadedPlayer;
+use crate::tcp::payload::VersionedRequest;
+use crate::tcp::viewers::ViewerAccess;
 use serde::{Deserialize, Serialize};
+use crate::models::ids::{MatchId, PlayerId};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InitServerRequest {
-    pub match_id: String,
+    pub match_id: MatchId,
     pub match_type: String,
-    pub players: Vec<PreloadPlayer>
+    pub players: Vec<PreloadPlayer>,
+    /// Whether clients may join the match as spectators.
+    #[serde(default)]
+    pub spectatable: bool,
+    /// Who may spectate the match and fetch its replay. When omitted, anyone may spectate a
+    /// spectatable match and only its players may fetch the replay.
+    #[serde(default)]
+    pub viewers: Option<ViewerAccess>,
+    /// Seed of the match random number generator. A random seed is picked when omitted.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Makes the match a wagered one: both players must confirm the stake before acting.
+    #[serde(default)]
+    pub stake: Option<Stake>,
+    /// Rules of the match; the defaults of the match type are used when omitted.
+    #[serde(default)]
+    pub rules: Option<RulesProfile>,
+    /// Unix timestamp (milliseconds) before which the match does not start, for tournament rounds.
+    #[serde(default)]
+    pub scheduled_start: Option<i64>,
+    /// Drafts the decks of a limited match before it starts; the decks of `deck_id` are not used.
+    #[serde(default)]
+    pub draft: Option<DraftSettings>,
+    /// Game of its series the match is, counting from 1; the players sideboard before every game
+    /// after the first.
+    #[serde(default = "default_game")]
+    pub game: u32,
+}
+
+impl VersionedRequest for InitServerRequest {}
+
+fn default_game() -> u32 {
+    1
+}
+
+/// What each player puts at stake in a wagered match.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Stake {
+    pub amount: u64,
+    pub currency: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PreloadPlayer {
-    pub id: String,
+    pub id: PlayerId,
     pub deck_id: String,
+    /// The cards offered to the player during their arena run, which the deck must be built from.
+    #[serde(default)]
+    pub pool: Option<SignedPool>,
+    /// Seats a bot played by the server instead of a client; its deck is still fetched from the
+    /// deck server, but it has no account on the auth server.
+    #[serde(default)]
+    pub bot: Option<BotProfile>,
+    /// The deck the player drafted, used instead of fetching `deck_id` from the deck server.
+    #[serde(skip)]
+    pub deck: Option<Deck>,
+}
+
+/// A bot taking a seat of the match.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BotProfile {
+    /// The name shown to the opponent; `Bot` when omitted.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub strategy: BotStrategy,
+}
+
+impl BotProfile {
+    /// The profile of the bot, standing in for the one the auth server keeps for players.
+    pub fn profile(&self, player_id: &PlayerId) -> PreloadedPlayer {
+        PreloadedPlayer {
+            id: player_id.clone(),
+            level: 1,
+            username: self.name.clone().unwrap_or_else(|| String::from("Bot")),
+        }
+    }
+}
+
+/// How a bot picks its actions.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BotStrategy {
+    /// Plays the most expensive cards its mana allows, then attacks with every creature.
+    #[default]
+    Heuristic,
+    /// Asks a core script, e.g. `core:bot_turn`, for the plan of each turn.
+    Script(String),
 }
\ No newline at end of file
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::game::wager::StakeSettlement;
+use crate::models::ids::{MatchId, PlayerId};
+use crate::tcp::audit::ViolationSummary;
+
+/// The result of a match, reported to the platform once the match ends.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatchReport {
+    /// Unique per report, so the platform can discard duplicates of a re-sent report.
+    pub report_id: Uuid,
+    pub match_id: MatchId,
+    pub match_type: String,
+    pub winner_id: Option<PlayerId>,
+    pub players: Vec<PlayerId>,
+    pub reason: String,
+    /// Unix timestamp (milliseconds) of the end of the match.
+    pub ended_at: i64,
+    /// Rewards and quest progress inputs computed by the `match_rewards` core script, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rewards: Option<serde_json::Value>,
+    /// How the stake was settled, for wagered matches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stake: Option<StakeSettlement>,
+    /// Games in the series the match is part of, for formats such as best-of-three.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub best_of: Option<u32>,
+    /// How well each player stayed connected, so losses caused by the connection can be told
+    /// apart from losses caused by the game.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub connection_quality: Vec<ConnectionQualityReport>,
+    /// Players who never got ready, when the match was aborted because they did not show up.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub no_shows: Vec<PlayerId>,
+    /// Actions of each player the server rejected as violations, and whether the player was
+    /// disconnected and flagged for them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<ViolationSummary>,
+}
+
+/// Connection quality of one player over a match.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConnectionQualityReport {
+    pub player_id: PlayerId,
+    pub disconnects: u32,            // Times the player's connection dropped.
+    pub reconnect_ms: u64,           // Total time spent disconnected.
+    pub average_rtt_ms: Option<u64>, // Average keepalive round trip, if any was measured.
+    pub packets_resent: u64,         // Packets sent again after the player missed them.
+}
@@ -1,6 +1,16 @@
+#[cfg(feature = "game")]
 pub mod client_requests;
+#[cfg(feature = "game")]
+pub mod connect_ack;
+#[cfg(feature = "game")]
 pub mod http_response;
+#[cfg(feature = "game")]
 pub mod settings;
+#[cfg(feature = "game")]
 pub mod game_action;
 pub mod exit_code;
+#[cfg(feature = "game")]
 pub mod init_server;
+#[cfg(feature = "game")]
+pub mod match_report;
+pub mod ids;
@@ -1,4 +1,15 @@
-use serde::Deserialize;
+use crate::game::effect_stack::ResolutionOrder;
+use crate::game::think_time::ThinkTimeVisibility;
+use crate::tcp::listener::DEFAULT_LISTEN_ADDRESS;
+use crate::tcp::transport::Transport;
+use crate::utils::logger::LogFormat;
+use crate::utils::runtime_flags::LogLevel;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use std::net::SocketAddr;
+
+/// Shortest `ADMIN_SECRET` accepted, so the key admin commands are signed with cannot be guessed.
+pub const MIN_ADMIN_SECRET_LEN: usize = 32;
 
 #[derive(Debug, Deserialize)]
 pub struct Settings {
@@ -8,4 +19,501 @@ pub struct Settings {
     pub card_server: String,
     #[serde(rename = "DECK_SERVER")]
     pub deck_server: String,
+    #[serde(rename = "RESULT_SERVER", default)]
+    pub result_server: Option<String>, // Platform service match results are reported to; not reported if unset.
+    #[serde(rename = "PROMPT_TIMEOUT", default = "default_prompt_timeout")]
+    pub prompt_timeout: u64, // Seconds a player has to answer a prompt.
+    #[serde(
+        rename = "PROMPT_RECONNECT_GRACE",
+        default = "default_prompt_reconnect_grace"
+    )]
+    pub prompt_reconnect_grace: u64, // Minimum seconds left on a prompt re-sent after reconnecting.
+    #[serde(
+        rename = "SLOW_HANDLER_THRESHOLD",
+        default = "default_slow_handler_threshold"
+    )]
+    pub slow_handler_threshold: u64, // Milliseconds after which a packet handler is reported as slow.
+    #[serde(rename = "LEGACY_CHECKSUM", default = "default_legacy_checksum")]
+    pub legacy_checksum: bool, // Whether clients may still use the XOR checksum (required by legacy clients).
+    #[serde(rename = "DEAD_LETTER_PATH", default = "default_dead_letter_path")]
+    pub dead_letter_path: String, // File holding match reports that could not be delivered.
+    #[serde(
+        rename = "DEAD_LETTER_RETRY_INTERVAL",
+        default = "default_dead_letter_retry_interval"
+    )]
+    pub dead_letter_retry_interval: u64, // Seconds between automatic retries of undelivered reports.
+    #[serde(rename = "HEARTBEAT_INTERVAL", default = "default_heartbeat_interval")]
+    pub heartbeat_interval: u64, // Seconds of silence after which a client is pinged.
+    #[serde(
+        rename = "HEARTBEAT_MAX_MISSED",
+        default = "default_heartbeat_max_missed"
+    )]
+    pub heartbeat_max_missed: u32, // Unanswered pings after which a client is marked as disconnected.
+    #[serde(rename = "ARTIFACTS_PATH", default = "default_artifacts_path")]
+    pub artifacts_path: String, // Directory receiving one artifact bundle per match.
+    #[serde(rename = "MAX_SPECTATORS", default = "default_max_spectators")]
+    pub max_spectators: usize, // Spectators allowed at once in a spectatable match.
+    #[serde(rename = "TCP_NODELAY", default = "default_tcp_nodelay")]
+    pub tcp_nodelay: bool, // Whether small packets are sent without waiting to be coalesced.
+    #[serde(rename = "TCP_KEEPALIVE", default = "default_tcp_keepalive")]
+    pub tcp_keepalive: bool, // Whether OS keepalive probes follow the heartbeat settings.
+    #[serde(rename = "TCP_LINGER", default)]
+    pub tcp_linger: Option<u64>, // Seconds a closing socket waits for unsent data; unset keeps the OS default.
+    #[serde(rename = "REPLAY_ENABLED", default)]
+    pub replay_enabled: bool, // Whether matches are recorded into replay files in their artifact bundle.
+    #[serde(rename = "REPLAY_ROTATE_SIZE", default = "default_replay_rotate_size")]
+    pub replay_rotate_size: u64, // Bytes after which a replay continues in a new file.
+    #[serde(
+        rename = "LUA_INSTRUCTION_LIMIT",
+        default = "default_lua_instruction_limit"
+    )]
+    pub lua_instruction_limit: u64, // Lua instructions a single script call may run.
+    #[serde(rename = "LUA_TIMEOUT", default = "default_lua_timeout")]
+    pub lua_timeout: u64, // Milliseconds a single script call may run.
+    #[serde(rename = "LUA_MEMORY_LIMIT", default = "default_lua_memory_limit")]
+    pub lua_memory_limit: usize, // Bytes the Lua VM may allocate in total.
+    #[serde(
+        rename = "SCRIPT_CALL_TIMEOUT",
+        default = "default_script_call_timeout"
+    )]
+    pub script_call_timeout: u64, // Milliseconds a script call may wait for the Lua worker, queueing included.
+    #[serde(rename = "SCRIPT_LINT_STRICT", default)]
+    pub script_lint_strict: bool, // Whether deprecated API uses in card scripts fail initialization.
+    #[serde(rename = "BANDWIDTH_WINDOW", default = "default_bandwidth_window")]
+    pub bandwidth_window: u64, // Seconds over which the bandwidth caps are measured.
+    #[serde(rename = "BANDWIDTH_SOFT_CAP", default)]
+    pub bandwidth_soft_cap: Option<u64>, // Bytes a client may send per window before a warning is logged.
+    #[serde(rename = "BANDWIDTH_HARD_CAP", default)]
+    pub bandwidth_hard_cap: Option<u64>, // Bytes a client may send per window before being disconnected.
+    #[serde(rename = "READY_FILE", default)]
+    pub ready_file: Option<String>, // File written with the readiness line once the server is ready for InitServer.
+    #[serde(rename = "LISTEN_ADDRESSES", default = "default_listen_addresses")]
+    pub listen_addresses: Vec<SocketAddr>, // Addresses the server listens on, IPv4 or IPv6.
+    #[serde(rename = "LISTEN_DUAL_STACK", default)]
+    pub listen_dual_stack: bool, // Whether IPv6 listeners also accept IPv4 clients.
+    #[serde(rename = "TRANSPORT", default)]
+    pub transport: Transport, // `tcp`, `quic` or `both`; QUIC is experimental and needs the `quic` feature.
+    #[serde(rename = "TLS", default)]
+    pub tls: bool, // Whether TCP clients are served over TLS; needs the `tls` feature.
+    #[serde(rename = "TLS_CERTIFICATE", default)]
+    pub tls_certificate: Option<String>, // PEM certificate chain served to TLS and QUIC clients, self-signed if unset.
+    #[serde(rename = "TLS_PRIVATE_KEY", default)]
+    pub tls_private_key: Option<String>, // PEM private key of `TLS_CERTIFICATE`.
+    #[serde(rename = "THINK_TIME_VISIBILITY", default)]
+    pub think_time_visibility: ThinkTimeVisibility, // Who is sent each player's think time: `all`, `own` or `none`.
+    #[serde(
+        rename = "THINK_TIME_ANALYTICS",
+        default = "default_think_time_analytics"
+    )]
+    pub think_time_analytics: bool, // Whether per-turn think times are included in the match profile.
+    #[serde(
+        rename = "STAKE_CONFIRM_TIMEOUT",
+        default = "default_stake_confirm_timeout"
+    )]
+    pub stake_confirm_timeout: u64, // Seconds players of a wagered match have to confirm the stake.
+    #[serde(rename = "READY_TIMEOUT", default = "default_ready_timeout")]
+    pub ready_timeout: u64, // Seconds players have to connect and send `Ready` before the match is aborted.
+    #[serde(rename = "DRAFT_PICK_TIMEOUT", default = "default_draft_pick_timeout")]
+    pub draft_pick_timeout: u64, // Seconds a drafting player has to pick before the first card of the pack is picked for them.
+    #[serde(rename = "SIDEBOARD_TIMEOUT", default = "default_sideboard_timeout")]
+    pub sideboard_timeout: u64, // Seconds the players of a series have to sideboard before the next game is created.
+    #[serde(
+        rename = "MATCH_START_COUNTDOWN",
+        default = "default_match_start_countdown"
+    )]
+    pub match_start_countdown: u64, // Seconds between `MatchStart` and the first turn.
+    #[serde(
+        rename = "SCHEDULE_COUNTDOWN_INTERVAL",
+        default = "default_schedule_countdown_interval"
+    )]
+    pub schedule_countdown_interval: u64, // Seconds between the countdowns sent while a scheduled match waits.
+    #[serde(
+        rename = "SCRIPT_BLOCKLIST_PATH",
+        default = "default_script_blocklist_path"
+    )]
+    pub script_blocklist_path: String, // File keeping the card scripts blocked by operators.
+    #[serde(rename = "SCRIPT_BLOCKLIST_URL", default)]
+    pub script_blocklist_url: Option<String>, // Config service publishing more blocked scripts.
+    #[serde(rename = "EFFECT_RESOLUTION_ORDER", default)]
+    pub effect_resolution_order: ResolutionOrder, // Order triggered effects resolve in: `lifo` or `fifo`.
+    #[serde(
+        rename = "EFFECT_STACK_MAX_DEPTH",
+        default = "default_effect_stack_max_depth"
+    )]
+    pub effect_stack_max_depth: u32, // Longest chain of triggers before it is stopped as a loop.
+    #[serde(rename = "ARENA_POOL_SECRET", default)]
+    pub arena_pool_secret: Option<String>, // Secret shared with the platform to verify arena card pools.
+    #[serde(rename = "BLOCKERS_TIMEOUT", default = "default_blockers_timeout")]
+    pub blockers_timeout: u64, // Seconds the defender has to declare blockers before the attack resolves.
+    #[serde(rename = "MATCH_FORMATS_PATH", default = "default_match_formats_path")]
+    pub match_formats_path: String, // File listing custom match formats, on top of the built-in ones.
+    #[serde(
+        rename = "SHUTDOWN_HOOK_TIMEOUT",
+        default = "default_shutdown_hook_timeout"
+    )]
+    pub shutdown_hook_timeout: u64, // Seconds each shutdown hook may run before the process exits anyway.
+    #[serde(rename = "HEALTH_ADDRESS", default)]
+    pub health_address: Option<SocketAddr>, // Address of the HTTP health endpoint; off if unset.
+    #[serde(
+        rename = "OUTBOUND_QUEUE_CAPACITY",
+        default = "default_outbound_queue_capacity"
+    )]
+    pub outbound_queue_capacity: usize, // Packets queued for a client before its oldest state packet is dropped.
+    #[serde(
+        rename = "MISSED_PACKETS_LIMIT",
+        default = "default_missed_packets_limit"
+    )]
+    pub missed_packets_limit: usize, // Notices kept for a disconnected client; turn changes and the match end are always kept.
+    #[serde(
+        rename = "DISCONNECT_GRACE_PERIOD",
+        default = "default_disconnect_grace_period"
+    )]
+    pub disconnect_grace_period: u64, // Seconds a player who dropped mid-match has to reconnect before forfeiting.
+    #[serde(rename = "SESSION_TOKEN_TTL", default = "default_session_token_ttl")]
+    pub session_token_ttl: u64, // Seconds a session token issued in `ConnectAck` is accepted for reconnecting.
+    #[serde(rename = "EMOTE_RATE_LIMIT", default = "default_emote_rate_limit")]
+    pub emote_rate_limit: u32, // Emotes a player may send within `EMOTE_RATE_WINDOW`.
+    #[serde(rename = "EMOTE_RATE_WINDOW", default = "default_emote_rate_window")]
+    pub emote_rate_window: u64, // Seconds over which the emote allowance of a player refills.
+    #[serde(rename = "HTTP_TIMEOUT", default = "default_http_timeout")]
+    pub http_timeout: u64, // Milliseconds a request to a platform service may take.
+    #[serde(rename = "HTTP_RETRIES", default = "default_http_retries")]
+    pub http_retries: u32, // Attempts made after a failed request to the auth, deck or card service.
+    #[serde(rename = "HTTP_RETRY_BACKOFF", default = "default_http_retry_backoff")]
+    pub http_retry_backoff: u64, // Milliseconds before the first retry, doubled (with jitter) for every next one.
+    #[serde(
+        rename = "CIRCUIT_BREAKER_THRESHOLD",
+        default = "default_circuit_breaker_threshold"
+    )]
+    pub circuit_breaker_threshold: u32, // Failed requests in a row after which a service is paused.
+    #[serde(
+        rename = "CIRCUIT_BREAKER_COOLDOWN",
+        default = "default_circuit_breaker_cooldown"
+    )]
+    pub circuit_breaker_cooldown: u64, // Seconds a paused service is not sent requests.
+    #[serde(rename = "CARD_CACHE_TTL", default = "default_card_cache_ttl")]
+    pub card_cache_ttl: u64, // Seconds a card definition is used before it is fetched again.
+    #[serde(rename = "CARD_CACHE_PATH", default)]
+    pub card_cache_path: Option<String>, // File keeping the card definitions across matches; in memory only if unset.
+    #[serde(rename = "CHAT_MAX_LENGTH", default = "default_chat_max_length")]
+    pub chat_max_length: usize, // Characters a chat message may hold at most.
+    #[serde(rename = "CHAT_RATE_LIMIT", default = "default_chat_rate_limit")]
+    pub chat_rate_limit: u32, // Chat messages a player may send within `CHAT_RATE_WINDOW`.
+    #[serde(rename = "CHAT_RATE_WINDOW", default = "default_chat_rate_window")]
+    pub chat_rate_window: u64, // Seconds over which the chat allowance of a player refills.
+    #[serde(rename = "CHAT_TO_SPECTATORS", default)]
+    pub chat_to_spectators: bool, // Whether the spectators are relayed the chat of the players.
+    #[serde(rename = "PROFANITY_WORDLIST_PATH", default)]
+    pub profanity_wordlist_path: Option<String>, // Words masked in chat messages, one per line; no filter if unset.
+    #[serde(
+        rename = "AUDIT_VIOLATION_LIMIT",
+        default = "default_audit_violation_limit"
+    )]
+    pub audit_violation_limit: u32, // Rejected actions a player may send within `AUDIT_VIOLATION_WINDOW` before being disconnected and flagged; 0 never disconnects.
+    #[serde(
+        rename = "AUDIT_VIOLATION_WINDOW",
+        default = "default_audit_violation_window"
+    )]
+    pub audit_violation_window: u64, // Seconds over which the rejected actions of a player are counted.
+    #[serde(
+        rename = "MAX_MALFORMED_PACKETS",
+        default = "default_max_malformed_packets"
+    )]
+    pub max_malformed_packets: u32, // Undecodable packets or bad checksums a client may send over the match before being disconnected; 0 never disconnects.
+    #[serde(rename = "BOT_THINK_TIME", default = "default_bot_think_time")]
+    pub bot_think_time: u64, // Milliseconds a bot waits before each of its actions.
+    #[serde(
+        rename = "CONFIG_RELOAD_INTERVAL",
+        default = "default_config_reload_interval"
+    )]
+    pub config_reload_interval: u64, // Seconds between checks of `config.toml` for changed tunable settings; 0 disables them.
+    #[serde(rename = "LOBBY_MODE", default)]
+    pub lobby_mode: bool, // Pairs the players connecting before any match is initialized, without a matchmaker.
+    #[serde(rename = "LOBBY_MATCH_TYPE", default = "default_lobby_match_type")]
+    pub lobby_match_type: String, // The `match_type` of the matches created in the lobby.
+    #[serde(rename = "PRIVATE_MATCH_TYPE", default = "default_private_match_type")]
+    pub private_match_type: String, // The `match_type` of the private matches created with a join code.
+    #[serde(rename = "PRIVATE_MATCH_TTL", default = "default_private_match_ttl")]
+    pub private_match_ttl: u64, // Seconds a join code stays valid while its private match waits for a second player.
+    #[serde(rename = "MAX_MATCHES", default = "default_max_matches")]
+    pub max_matches: usize, // Matches hosted by the process at once; with 1 the process exits when its match ends.
+    #[serde(rename = "MOCK_AUTH", default)]
+    pub mock_auth: bool, // Accepts `mock:<player id>` tokens without the auth server; for load tests only.
+    #[serde(rename = "JSON_PAYLOADS", default)]
+    pub json_payloads: bool, // Lets clients negotiate JSON payloads instead of CBOR at handshake; for debugging only.
+    #[serde(rename = "METRICS_ADDRESS", default)]
+    pub metrics_address: Option<SocketAddr>, // Address of the Prometheus `/metrics` endpoint; off if unset.
+    #[serde(rename = "ADMIN_ADDRESS", default)]
+    pub admin_address: Option<SocketAddr>, // Address of the admin channel; off unless `ADMIN_SECRET` is set too.
+    #[serde(rename = "ADMIN_SECRET", default, deserialize_with = "admin_secret")]
+    pub admin_secret: Option<String>, // Secret admin commands are signed with.
+    #[serde(rename = "DECK_LEGALITY_URL", default)]
+    pub deck_legality_url: Option<String>, // Service publishing the banned and legal cards of each format.
+    #[serde(rename = "LOG_LEVEL", default = "default_log_level")]
+    pub log_level: LogLevel, // Lowest level logged at startup: `debug`, `info`, `warn` or `error`.
+    #[serde(rename = "LOG_FORMAT", default)]
+    pub log_format: LogFormat, // `text` for readable lines, `json` for log aggregators.
+}
+
+/// Refuses an `ADMIN_SECRET` shorter than `MIN_ADMIN_SECRET_LEN`, an empty one included, so the
+/// server does not start with an admin channel anyone could sign commands for.
+fn admin_secret<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let secret = Option::<String>::deserialize(deserializer)?;
+    match &secret {
+        Some(secret) if secret.len() < MIN_ADMIN_SECRET_LEN => Err(D::Error::custom(format!(
+            "ADMIN_SECRET must be at least {MIN_ADMIN_SECRET_LEN} bytes long"
+        ))),
+        _ => Ok(secret),
+    }
+}
+
+fn default_prompt_timeout() -> u64 {
+    30
+}
+
+fn default_prompt_reconnect_grace() -> u64 {
+    15
+}
+
+fn default_slow_handler_threshold() -> u64 {
+    250
+}
+
+fn default_legacy_checksum() -> bool {
+    true
+}
+
+fn default_dead_letter_path() -> String {
+    String::from("dead_letters.jsonl")
+}
+
+fn default_dead_letter_retry_interval() -> u64 {
+    300
+}
+
+fn default_listen_addresses() -> Vec<SocketAddr> {
+    vec![DEFAULT_LISTEN_ADDRESS]
+}
+
+fn default_heartbeat_interval() -> u64 {
+    5
+}
+
+fn default_heartbeat_max_missed() -> u32 {
+    3
+}
+
+fn default_artifacts_path() -> String {
+    String::from("artifacts")
+}
+
+fn default_max_spectators() -> usize {
+    16
+}
+
+fn default_tcp_nodelay() -> bool {
+    true
+}
+
+fn default_tcp_keepalive() -> bool {
+    true
+}
+
+fn default_replay_rotate_size() -> u64 {
+    16 * 1024 * 1024
+}
+
+fn default_bandwidth_window() -> u64 {
+    10
+}
+
+fn default_lua_instruction_limit() -> u64 {
+    10_000_000
+}
+
+fn default_lua_timeout() -> u64 {
+    100
+}
+
+fn default_lua_memory_limit() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_script_call_timeout() -> u64 {
+    1000
+}
+
+fn default_think_time_analytics() -> bool {
+    true
+}
+
+fn default_stake_confirm_timeout() -> u64 {
+    60
+}
+
+fn default_draft_pick_timeout() -> u64 {
+    30
+}
+
+fn default_sideboard_timeout() -> u64 {
+    60
+}
+
+fn default_ready_timeout() -> u64 {
+    60
+}
+
+fn default_shutdown_hook_timeout() -> u64 {
+    10
+}
+
+fn default_outbound_queue_capacity() -> usize {
+    64
+}
+
+fn default_missed_packets_limit() -> usize {
+    30
+}
+
+fn default_disconnect_grace_period() -> u64 {
+    90
+}
+
+fn default_session_token_ttl() -> u64 {
+    600
+}
+
+fn default_emote_rate_limit() -> u32 {
+    3
+}
+
+fn default_emote_rate_window() -> u64 {
+    10
+}
+
+fn default_http_timeout() -> u64 {
+    5000
+}
+
+fn default_http_retries() -> u32 {
+    2
+}
+
+fn default_http_retry_backoff() -> u64 {
+    200
+}
+
+fn default_circuit_breaker_threshold() -> u32 {
+    5
+}
+
+fn default_circuit_breaker_cooldown() -> u64 {
+    30
+}
+
+fn default_card_cache_ttl() -> u64 {
+    3600
+}
+
+fn default_chat_max_length() -> usize {
+    200
+}
+
+fn default_chat_rate_limit() -> u32 {
+    5
+}
+
+fn default_chat_rate_window() -> u64 {
+    10
+}
+
+fn default_audit_violation_limit() -> u32 {
+    10
+}
+
+fn default_audit_violation_window() -> u64 {
+    60
+}
+
+fn default_max_malformed_packets() -> u32 {
+    5
+}
+
+fn default_bot_think_time() -> u64 {
+    800
+}
+
+fn default_config_reload_interval() -> u64 {
+    5
+}
+
+fn default_max_matches() -> usize {
+    1
+}
+
+fn default_lobby_match_type() -> String {
+    String::from("casual")
+}
+
+fn default_private_match_type() -> String {
+    String::from("friendly")
+}
+
+fn default_private_match_ttl() -> u64 {
+    300
+}
+
+fn default_log_level() -> LogLevel {
+    LogLevel::Debug
+}
+
+fn default_schedule_countdown_interval() -> u64 {
+    10
+}
+
+fn default_match_start_countdown() -> u64 {
+    3
+}
+
+fn default_script_blocklist_path() -> String {
+    String::from("script_blocklist.json")
+}
+
+fn default_effect_stack_max_depth() -> u32 {
+    16
+}
+
+fn default_blockers_timeout() -> u64 {
+    20
+}
+
+fn default_match_formats_path() -> String {
+    String::from("match_formats.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(admin_secret: &str) -> Result<Settings, serde_json::Error> {
+        serde_json::from_value(serde_json::json!({
+            "AUTH_SERVER": "http://localhost",
+            "CARD_SERVER": "http://localhost",
+            "DECK_SERVER": "http://localhost",
+            "RESULT_SERVER": "http://localhost",
+            "ADMIN_SECRET": admin_secret,
+        }))
+    }
+
+    #[test]
+    fn test_short_admin_secrets_are_refused() {
+        assert!(settings("").is_err());
+        assert!(settings("hunter2").is_err());
+
+        let secret = "s".repeat(MIN_ADMIN_SECRET_LEN);
+        assert_eq!(
+            Some(secret.clone()),
+            settings(&secret).unwrap().admin_secret
+        );
+    }
 }
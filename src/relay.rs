@@ -0,0 +1,303 @@
+use crate::tcp::compat::WireFormat;
+use crate::tcp::encoding::PayloadEncoding;
+use crate::tcp::handshake::{self, HandshakeRequest, HandshakeResponse, NegotiatedProtocol};
+use crate::tcp::header::HeaderType;
+use crate::tcp::listener::{ListenerSet, TransportOptions, DEFAULT_LISTEN_ADDRESS};
+use crate::tcp::packet::Packet;
+use crate::tcp::transport::{ClientStream, Transport, WriteHalf};
+use crate::utils::checksum::{Checksum, ChecksumKind};
+use crate::utils::compression::Compression;
+use crate::utils::logger::LogFormat;
+use crate::utils::runtime_flags::LogLevel;
+use crate::{logger, utils::logger::Logger};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+
+/// The settings the relay reads from `config.toml`; the game settings are ignored.
+#[derive(Deserialize, Debug)]
+pub struct RelaySettings {
+    #[serde(rename = "LISTEN_ADDRESSES", default = "default_listen_addresses")]
+    pub listen_addresses: Vec<SocketAddr>, // Addresses the relay listens on, IPv4 or IPv6.
+    #[serde(rename = "LISTEN_DUAL_STACK", default)]
+    pub listen_dual_stack: bool, // Whether IPv6 listeners also accept IPv4 clients.
+    #[serde(rename = "TRANSPORT", default)]
+    pub transport: Transport, // `tcp`, `quic` or `both`; QUIC needs the `quic` feature.
+    #[serde(rename = "TLS", default)]
+    pub tls: bool, // Whether TCP clients are served over TLS; needs the `tls` feature.
+    #[serde(rename = "TLS_CERTIFICATE", default)]
+    pub tls_certificate: Option<String>, // PEM certificate chain served to TLS and QUIC clients.
+    #[serde(rename = "TLS_PRIVATE_KEY", default)]
+    pub tls_private_key: Option<String>, // PEM private key of `TLS_CERTIFICATE`.
+    #[serde(rename = "READY_FILE", default)]
+    pub ready_file: Option<String>, // File written with the readiness line once the relay is up.
+    #[serde(rename = "LOG_LEVEL", default = "default_log_level")]
+    pub log_level: LogLevel, // Lowest level logged: `debug`, `info`, `warn` or `error`.
+    #[serde(rename = "LOG_FORMAT", default)]
+    pub log_format: LogFormat, // `text` for readable lines, `json` for log aggregators.
+}
+
+impl Default for RelaySettings {
+    fn default() -> Self {
+        Self {
+            listen_addresses: default_listen_addresses(),
+            listen_dual_stack: false,
+            transport: Transport::default(),
+            tls: false,
+            tls_certificate: None,
+            tls_private_key: None,
+            ready_file: None,
+            log_level: default_log_level(),
+            log_format: LogFormat::default(),
+        }
+    }
+}
+
+fn default_listen_addresses() -> Vec<SocketAddr> {
+    vec![DEFAULT_LISTEN_ADDRESS]
+}
+
+fn default_log_level() -> LogLevel {
+    LogLevel::Debug
+}
+
+/// A client of the relay.
+struct Peer {
+    wire_format: RwLock<WireFormat>,     // Framing agreed at handshake, legacy without one.
+    checksum: RwLock<Box<dyn Checksum>>, // Seals the packets the relay answers with itself.
+    writer: Mutex<WriteHalf>,
+}
+
+impl Peer {
+    /// Forwards a packet as it was received, in the framing of this peer.
+    async fn send(&self, packet: &Packet) -> io::Result<usize> {
+        let wire_format = *self.wire_format.read().await;
+        let mut writer = self.writer.lock().await;
+        wire_format.write_packet(packet, &mut *writer).await
+    }
+
+    /// Sends a packet of the relay's own, sealed with the checksum of this peer.
+    async fn reply(&self, packet: &Packet) -> io::Result<usize> {
+        let sealed = packet.sealed(&**self.checksum.read().await, Compression::None);
+        self.send(&sealed).await
+    }
+}
+
+/// A packet relay speaking the full packet framing, without scripts, service calls or game
+/// rules, for testing the network layer of clients.
+///
+/// Clients negotiate the protocol like with the game server, and get `Ping` and `Disconnect`
+/// answered. Every other packet is forwarded to the other connected clients, or echoed back to
+/// its sender when it is alone.
+pub struct Relay {
+    listeners: ListenerSet,
+    peers: RwLock<HashMap<SocketAddr, Arc<Peer>>>,
+}
+
+impl Relay {
+    /// Binds the listen addresses of the settings.
+    pub fn bind(settings: &RelaySettings) -> io::Result<Self> {
+        Ok(Self {
+            listeners: ListenerSet::bind_transport(
+                &settings.listen_addresses,
+                settings.listen_dual_stack,
+                &TransportOptions {
+                    transport: settings.transport,
+                    tls: settings.tls,
+                    certificate: settings.tls_certificate.as_deref(),
+                    private_key: settings.tls_private_key.as_deref(),
+                },
+            )?,
+            peers: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// The TCP addresses the relay listens on.
+    pub fn addresses(&self) -> &[SocketAddr] {
+        self.listeners.addresses()
+    }
+
+    /// The UDP addresses the relay accepts QUIC clients on.
+    pub fn quic_addresses(&self) -> &[SocketAddr] {
+        self.listeners.quic_addresses()
+    }
+
+    /// Accepts clients and relays their packets. Runs until every listener stops.
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            match self.listeners.accept().await {
+                Ok((stream, addr)) => {
+                    logger!(INFO, "[RELAY] Accepted `{addr}`");
+                    tokio::spawn(Arc::clone(&self).serve(stream, addr));
+                }
+                Err(error) if error.kind() == io::ErrorKind::Other => {
+                    logger!(ERROR, "[RELAY] {error}");
+                    return;
+                }
+                Err(error) => logger!(WARN, "[RELAY] Failed to accept a client: {error}"),
+            }
+        }
+    }
+
+    /// Reads the packets of a client until it disconnects.
+    async fn serve(self: Arc<Self>, mut stream: ClientStream, addr: SocketAddr) {
+        // Current clients open with a `Handshake`; anything else is a legacy client.
+        let mut first = [0u8; 1];
+        let wire_format = match stream.peek(&mut first).await {
+            Ok(1) if first[0] != HeaderType::Handshake as u8 => {
+                WireFormat::for_protocol(&NegotiatedProtocol::legacy())
+            }
+            _ => WireFormat::Current,
+        };
+
+        let (mut reader, writer) = stream.into_split();
+        let peer = Arc::new(Peer {
+            wire_format: RwLock::new(wire_format),
+            checksum: RwLock::new(ChecksumKind::Xor.build(None)),
+            writer: Mutex::new(writer),
+        });
+        self.peers.write().await.insert(addr, Arc::clone(&peer));
+
+        loop {
+            let wire_format = *peer.wire_format.read().await;
+            let packet = match wire_format.read_packet(&mut reader).await {
+                Ok(Some(packet)) => packet,
+                Ok(None) => break,
+                Err(error) => {
+                    logger!(WARN, "[RELAY] Invalid packet from `{addr}` ({error})");
+                    break;
+                }
+            };
+
+            if !self.handle(addr, &peer, packet).await {
+                break;
+            }
+        }
+
+        self.peers.write().await.remove(&addr);
+        logger!(INFO, "[RELAY] `{addr}` disconnected");
+    }
+
+    /// Answers the packets the relay handles itself and forwards the others.
+    ///
+    /// # Returns
+    /// Whether the connection stays open.
+    async fn handle(&self, addr: SocketAddr, peer: &Peer, packet: Packet) -> bool {
+        let answered = match packet.header.header_type {
+            HeaderType::Handshake => return Self::handshake(peer, &packet).await,
+            HeaderType::Ping => {
+                let pong = Packet::reply_to(&packet, HeaderType::Pong, &packet.payload);
+                peer.reply(&pong).await
+            }
+            HeaderType::Disconnect => {
+                let _ = peer
+                    .reply(&Packet::reply_to(&packet, HeaderType::Disconnect, b""))
+                    .await;
+                return false;
+            }
+            _ => return self.forward(addr, peer, &packet).await,
+        };
+
+        answered.is_ok()
+    }
+
+    /// Negotiates the protocol like the game server, without optional features, JSON payloads,
+    /// which the relay forwards untouched, or the HMAC checksum, which needs a session token.
+    ///
+    /// # Returns
+    /// `true` if the client's version is supported.
+    async fn handshake(peer: &Peer, packet: &Packet) -> bool {
+        let Ok(mut request) = serde_cbor::from_slice::<HandshakeRequest>(&packet.payload) else {
+            let response = Packet::reply_to(packet, HeaderType::InvalidPacketPayload, b"");
+            let _ = peer.send(&response).await;
+            return false;
+        };
+        request
+            .checksums
+            .retain(|kind| *kind != ChecksumKind::HmacSha256);
+
+        match handshake::negotiate(&request, true) {
+            Ok(mut negotiated) => {
+                negotiated.features = 0;
+                negotiated.encoding = PayloadEncoding::Cbor;
+                *peer.wire_format.write().await = WireFormat::for_protocol(&negotiated);
+                *peer.checksum.write().await = negotiated.checksum.build(None);
+                let Ok(payload) = serde_cbor::to_vec(&HandshakeResponse::from(negotiated)) else {
+                    return false;
+                };
+                let response = Packet::reply_to(packet, HeaderType::Handshake, &payload);
+                peer.send(&response).await.is_ok()
+            }
+            Err(supported) => {
+                if let Ok(payload) = serde_cbor::to_vec(&supported) {
+                    let response = Packet::reply_to(packet, HeaderType::VersionMismatch, &payload);
+                    let _ = peer.send(&response).await;
+                }
+                false
+            }
+        }
+    }
+
+    /// Forwards a packet to every other client, or echoes it back when its sender is alone.
+    ///
+    /// # Returns
+    /// Whether the sender is still reachable.
+    async fn forward(&self, from: SocketAddr, sender: &Peer, packet: &Packet) -> bool {
+        let others: Vec<Arc<Peer>> = self
+            .peers
+            .read()
+            .await
+            .iter()
+            .filter(|(addr, _)| **addr != from)
+            .map(|(_, peer)| Arc::clone(peer))
+            .collect();
+        if others.is_empty() {
+            return sender.send(packet).await.is_ok();
+        }
+
+        for peer in others {
+            let _ = peer.send(packet).await;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use tokio::net::TcpStream;
+
+    #[tokio::test]
+    async fn test_relay_negotiates_and_echoes() {
+        let settings = RelaySettings {
+            listen_addresses: vec![SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0)],
+            ..RelaySettings::default()
+        };
+        let relay = Arc::new(Relay::bind(&settings).unwrap());
+        let address = relay.addresses()[0];
+        tokio::spawn(Arc::clone(&relay).run());
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = HandshakeRequest {
+            version: handshake::PROTOCOL_VERSION,
+            ..HandshakeRequest::default()
+        };
+        let handshake = Packet::new(
+            HeaderType::Handshake,
+            &serde_cbor::to_vec(&request).unwrap(),
+        );
+        handshake.write_to(&mut stream).await.unwrap();
+        let response = Packet::read_from(&mut stream).await.unwrap().unwrap();
+        assert_eq!(HeaderType::Handshake, response.header.header_type);
+
+        let play = Packet::new(HeaderType::PlayCard, b"card");
+        play.write_to(&mut stream).await.unwrap();
+        let echoed = Packet::read_from(&mut stream).await.unwrap().unwrap();
+        assert_eq!(HeaderType::PlayCard, echoed.header.header_type);
+        assert_eq!(play.payload, echoed.payload);
+    }
+}
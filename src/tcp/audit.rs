@@ -0,0 +1,277 @@
+use crate::models::ids::PlayerId;
+use crate::models::settings::Settings;
+use crate::utils::errors::GameLogicError;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Violations kept in the trail of a player; older ones are only counted.
+const MAX_TRAIL: usize = 256;
+
+/// What was wrong with an action the server rejected.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ViolationKind {
+    /// The player acted outside of their turn.
+    WrongTurn,
+    /// The player acted before the match started or before the stake was confirmed.
+    NotStarted,
+    /// The card played is not in the player's hand.
+    CardNotInHand,
+    /// The card used is not on the player's board, or cannot act.
+    CardNotOnBoard,
+    /// The target, defender, blocker or board position is not a legal one.
+    InvalidTarget,
+    /// The action was made in the name of another player.
+    WrongPlayer,
+    /// The payload of the action could not be decoded.
+    MalformedPayload,
+}
+
+impl ViolationKind {
+    /// The violation an error rejecting an action stands for.
+    ///
+    /// # Returns
+    /// `None` for rejections an honest client runs into as well, such as a full hand or a failing
+    /// script.
+    pub fn of(error: &GameLogicError) -> Option<Self> {
+        match error {
+            GameLogicError::NotPlayerTurn => Some(Self::WrongTurn),
+            GameLogicError::MatchNotStarted | GameLogicError::StakeNotConfirmed => {
+                Some(Self::NotStarted)
+            }
+            GameLogicError::CardPlayedIsNotInHand | GameLogicError::CardNotInHand(_) => {
+                Some(Self::CardNotInHand)
+            }
+            GameLogicError::CardNotOnBoard(_) | GameLogicError::InvalidAttacker(_) => {
+                Some(Self::CardNotOnBoard)
+            }
+            GameLogicError::InvalidTarget(_)
+            | GameLogicError::InvalidDefender(_)
+            | GameLogicError::InvalidBlock(_)
+            | GameLogicError::InvalidPosition(_)
+            | GameLogicError::InvalidPromptChoice(_) => Some(Self::InvalidTarget),
+            GameLogicError::PlayerIdDoesNotMatch => Some(Self::WrongPlayer),
+            GameLogicError::BatchActionFailed(_, error) => Self::of(error),
+            _ => None,
+        }
+    }
+}
+
+/// A rejected action of a player.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    pub action: String,  // Header type of the rejected action, e.g. `PLAY_CARD`.
+    pub details: String, // Why the action was rejected.
+    pub at: i64,         // Unix timestamp (milliseconds) of the rejection.
+}
+
+/// The violations of a player over a match, sent in the match report.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ViolationSummary {
+    pub player_id: PlayerId,
+    pub total: u32,                          // Actions rejected as violations.
+    pub kinds: BTreeMap<ViolationKind, u32>, // Violations of each kind.
+    pub flagged: bool,                       // Whether the player was disconnected.
+    pub first_at: i64,                       // Unix timestamp (ms) of the first violation.
+    pub last_at: i64,                        // Unix timestamp (ms) of the last violation.
+}
+
+/// How many violations a player may make before being disconnected and flagged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AuditLimits {
+    pub max_violations: u32, // Violations a player may make within `window`; 0 never disconnects.
+    pub window: Duration,    // Time over which the violations are counted.
+}
+
+impl AuditLimits {
+    pub fn from_settings(settings: Option<&Settings>) -> Self {
+        Self {
+            max_violations: settings.map_or(10, |s| s.audit_violation_limit),
+            window: Duration::from_secs(settings.map_or(60, |s| s.audit_violation_window)),
+        }
+    }
+}
+
+/// The violations of one player.
+#[derive(Default)]
+struct Trail {
+    violations: VecDeque<Violation>, // The latest violations, up to `MAX_TRAIL`.
+    kinds: BTreeMap<ViolationKind, u32>,
+    total: u32,
+    first_at: i64,
+    recent: VecDeque<Instant>, // Violations within the window of the limits.
+    flagged: bool,
+}
+
+/// Records the actions the server rejected, per player, so clients sending actions the server
+/// would never accept can be told apart from players misclicking.
+///
+/// A player making more violations than the limits allow is flagged and should be disconnected.
+/// The flag stays for the rest of the match, and is reported with the violation summary.
+#[derive(Default)]
+pub struct ActionAudit {
+    trails: Mutex<HashMap<PlayerId, Trail>>,
+}
+
+impl ActionAudit {
+    /// Records a rejected action of a player.
+    ///
+    /// # Returns
+    /// `true` if the player has now made more violations than the limits allow, in which case
+    /// they are flagged and their count within the window starts over.
+    pub fn record(
+        &self,
+        player_id: &PlayerId,
+        kind: ViolationKind,
+        action: &str,
+        details: &str,
+        limits: &AuditLimits,
+    ) -> bool {
+        let violation = Violation {
+            kind,
+            action: action.to_string(),
+            details: details.to_string(),
+            at: Utc::now().timestamp_millis(),
+        };
+        self.record_at(player_id, violation, limits, Instant::now())
+    }
+
+    fn record_at(
+        &self,
+        player_id: &PlayerId,
+        violation: Violation,
+        limits: &AuditLimits,
+        now: Instant,
+    ) -> bool {
+        let mut trails = self.trails.lock().unwrap_or_else(|e| e.into_inner());
+        let trail = trails.entry(player_id.clone()).or_default();
+
+        if trail.total == 0 {
+            trail.first_at = violation.at;
+        }
+        trail.total += 1;
+        *trail.kinds.entry(violation.kind).or_default() += 1;
+        if trail.violations.len() == MAX_TRAIL {
+            trail.violations.pop_front();
+        }
+        trail.violations.push_back(violation);
+
+        if limits.max_violations == 0 {
+            return false;
+        }
+        while trail
+            .recent
+            .front()
+            .is_some_and(|at| now.saturating_duration_since(*at) >= limits.window)
+        {
+            trail.recent.pop_front();
+        }
+        trail.recent.push_back(now);
+
+        if trail.recent.len() > limits.max_violations as usize {
+            trail.recent.clear();
+            trail.flagged = true;
+            return true;
+        }
+        false
+    }
+
+    /// Whether the player was flagged for their violations.
+    pub fn is_flagged(&self, player_id: &PlayerId) -> bool {
+        let trails = self.trails.lock().unwrap_or_else(|e| e.into_inner());
+        trails.get(player_id).is_some_and(|trail| trail.flagged)
+    }
+
+    /// The latest violations of a player, oldest first.
+    pub fn trail(&self, player_id: &PlayerId) -> Vec<Violation> {
+        let trails = self.trails.lock().unwrap_or_else(|e| e.into_inner());
+        trails
+            .get(player_id)
+            .map(|trail| trail.violations.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The violations of every player who made any, by player.
+    pub fn summaries(&self) -> Vec<ViolationSummary> {
+        let trails = self.trails.lock().unwrap_or_else(|e| e.into_inner());
+        let mut summaries: Vec<_> = trails
+            .iter()
+            .map(|(player_id, trail)| ViolationSummary {
+                player_id: player_id.clone(),
+                total: trail.total,
+                kinds: trail.kinds.clone(),
+                flagged: trail.flagged,
+                first_at: trail.first_at,
+                last_at: trail.violations.back().map_or(trail.first_at, |v| v.at),
+            })
+            .collect();
+        summaries.sort_by(|a, b| a.player_id.cmp(&b.player_id));
+        summaries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeated_violations_flag_the_player() {
+        let (red, blue) = (PlayerId::from("red"), PlayerId::from("blue"));
+        let start = Instant::now();
+        let limits = AuditLimits {
+            max_violations: 2,
+            window: Duration::from_secs(10),
+        };
+        let audit = ActionAudit::default();
+        let record = |player_id, kind, secs, at| {
+            let violation = Violation {
+                kind,
+                action: String::from("PLAY_CARD"),
+                details: String::from("rejected"),
+                at,
+            };
+            audit.record_at(
+                player_id,
+                violation,
+                &limits,
+                start + Duration::from_secs(secs),
+            )
+        };
+
+        assert!(!record(&red, ViolationKind::WrongTurn, 0, 1_000));
+        assert!(!record(&red, ViolationKind::WrongTurn, 1, 2_000));
+        // The first violation left the window.
+        assert!(!record(&red, ViolationKind::CardNotInHand, 10, 11_000));
+        assert!(!audit.is_flagged(&red));
+        assert!(record(&red, ViolationKind::InvalidTarget, 11, 12_000));
+        assert!(audit.is_flagged(&red));
+        assert!(!record(&blue, ViolationKind::WrongTurn, 11, 12_000));
+
+        let summaries = audit.summaries();
+        assert_eq!(2, summaries.len());
+        let summary = summaries.iter().find(|s| s.player_id == red).unwrap();
+        assert_eq!(4, summary.total);
+        assert_eq!(Some(&2), summary.kinds.get(&ViolationKind::WrongTurn));
+        assert_eq!((1_000, 12_000), (summary.first_at, summary.last_at));
+        assert!(summary.flagged);
+        assert_eq!(4, audit.trail(&red).len());
+    }
+
+    #[test]
+    fn test_only_violations_are_classified() {
+        let batch = GameLogicError::BatchActionFailed(1, Box::new(GameLogicError::NotPlayerTurn));
+        assert_eq!(Some(ViolationKind::WrongTurn), ViolationKind::of(&batch));
+        assert_eq!(
+            Some(ViolationKind::InvalidTarget),
+            ViolationKind::of(&GameLogicError::InvalidTarget("c1".to_string()))
+        );
+        assert_eq!(
+            None,
+            ViolationKind::of(&GameLogicError::HandFull("red".to_string()))
+        );
+    }
+}
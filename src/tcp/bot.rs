@@ -0,0 +1,462 @@
+use crate::game::board;
+use crate::game::entity::card::CardType;
+use crate::game::entity::player::{Player, PlayerView};
+use crate::game::event_bus::MatchEvent;
+use crate::game::game::PlayOutcome;
+use crate::models::client_requests::{
+    DeclareAttackersRequest, DeclareBlockersRequest, PlayCardRequest, PromptResponse,
+};
+use crate::models::ids::{CardInstanceId, PlayerId};
+use crate::models::init_server::{BotProfile, BotStrategy};
+use crate::tcp::client::Client;
+use crate::tcp::encoding::PayloadEncoding;
+use crate::tcp::handshake::{NegotiatedProtocol, PROTOCOL_VERSION, SERVER_FEATURES};
+use crate::tcp::protocol::Protocol;
+use crate::tcp::transport::{ClientStream, Connection};
+use crate::utils::checksum::ChecksumKind;
+use crate::utils::compression::Compression;
+use crate::utils::errors::GameLogicError;
+use crate::{logger, utils::logger::Logger, SETTINGS};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::RwLock;
+
+/// Bytes buffered in the pipe between a bot and its client.
+const LOOPBACK_BUFFER: usize = 64 * 1024;
+
+/// How often a bot waiting for its attack to resolve tries to end its turn again.
+const COMBAT_POLL: Duration = Duration::from_millis(250);
+
+/// What a bot does on its turn, in order: the cards it plays, then the creatures it attacks with.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct BotPlan {
+    #[serde(default)]
+    pub plays: Vec<BotPlay>,
+    #[serde(default)]
+    pub attackers: Vec<CardInstanceId>, // Creatures not on the board once the cards are played are left out.
+}
+
+/// A card a bot plays from its hand.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct BotPlay {
+    pub instance_id: CardInstanceId,
+    #[serde(default)]
+    pub target_id: Option<String>,
+    #[serde(default)]
+    pub target_position: Option<String>,
+}
+
+impl BotPlan {
+    /// Plays the most expensive cards of the hand the mana of the player allows, then attacks with
+    /// every creature of the board, the ones just played included.
+    pub fn heuristic(view: &PlayerView) -> Self {
+        let mut hand: Vec<_> = view.current_hand.iter().flatten().collect();
+        hand.sort_by_key(|card| std::cmp::Reverse(card.play_cost));
+
+        let mut plan = BotPlan {
+            plays: Vec::new(),
+            attackers: board_creatures(view),
+        };
+        let mut mana = view.mana;
+        for card in hand {
+            if card.play_cost > mana {
+                continue;
+            }
+
+            mana -= card.play_cost;
+            if card.card_type == CardType::Creature {
+                plan.attackers.push(card.instance_id.clone());
+            }
+            plan.plays.push(BotPlay {
+                instance_id: card.instance_id.clone(),
+                target_id: None,
+                target_position: None,
+            });
+        }
+
+        plan
+    }
+}
+
+/// The copies of the creatures on the board of a player.
+fn board_creatures(view: &PlayerView) -> Vec<CardInstanceId> {
+    view.board
+        .creatures
+        .iter()
+        .flatten()
+        .flat_map(|creature| creature.instances.iter().cloned())
+        .collect()
+}
+
+/// A seat of the match played by the server, for practice matches and load tests.
+///
+/// The bot follows the match on the event bus rather than through packets, and makes its actions
+/// straight on the game instance, in the name of a client of its own. It marks itself ready when
+/// it takes its seat, plays its turns as its strategy plans them, and lets attacks through
+/// unblocked. Prompts its plays open are answered with their first option.
+pub struct Bot {
+    protocol: Arc<Protocol>,
+    client: Arc<Client>, // The client the actions of the bot are made in the name of.
+    player_id: PlayerId,
+    profile: BotProfile,
+    think_time: Duration, // Delay before each action, so the opponent can follow them.
+}
+
+impl Bot {
+    /// Seats a bot in the match and plays its turns until the match ends.
+    ///
+    /// # Arguments
+    /// * `protocol` - The protocol of the match, which the actions of the bot go through.
+    /// * `player_id` - The seat of the bot, preloaded with the other players.
+    /// * `profile` - How the bot plays.
+    pub async fn join(protocol: Arc<Protocol>, player_id: PlayerId, profile: BotProfile) {
+        let player = protocol
+            .game_instance
+            .connected_players
+            .read()
+            .await
+            .get(&player_id)
+            .cloned();
+        let Some(player) = player else {
+            logger!(ERROR, "[BOT] `{player_id}` has no seat in the match");
+            return;
+        };
+
+        let client = match Self::loopback_client(&protocol, player) {
+            Ok(client) => client,
+            Err(error) => {
+                logger!(
+                    ERROR,
+                    "[BOT] Could not create the client of `{player_id}`: {error}"
+                );
+                return;
+            }
+        };
+
+        let think_time = SETTINGS.get().map_or(800, |s| s.bot_think_time);
+        let bot = Bot {
+            protocol,
+            client: Arc::new(client),
+            player_id,
+            profile,
+            think_time: Duration::from_millis(think_time),
+        };
+        bot.play().await;
+    }
+
+    /// A client over an in-memory pipe: game actions are made in the name of a client, but the bot
+    /// never reads from the connection, and what the match sends it is drained and dropped.
+    fn loopback_client(
+        protocol: &Arc<Protocol>,
+        player: Arc<RwLock<Player>>,
+    ) -> std::io::Result<Client> {
+        let (stream, mut peer) = tokio::io::duplex(LOOPBACK_BUFFER);
+        let addr = Connection::peer_addr(&stream)?;
+        tokio::spawn(async move {
+            let _ = tokio::io::copy(&mut peer, &mut tokio::io::sink()).await;
+        });
+
+        let (read, write) = ClientStream::new(stream).into_split();
+        let negotiated = NegotiatedProtocol {
+            version: PROTOCOL_VERSION,
+            features: SERVER_FEATURES,
+            checksum: ChecksumKind::Crc32c,
+            compression: Compression::None,
+            encoding: PayloadEncoding::Cbor,
+        };
+        let client = Client::new(
+            read,
+            write,
+            addr,
+            Arc::clone(protocol),
+            player,
+            negotiated,
+            "",
+        );
+        Ok(client)
+    }
+
+    /// Marks the bot ready, then reacts to the events of the match until it ends.
+    async fn play(&self) {
+        // Subscribing first, so the turn of the bot cannot start unnoticed.
+        let mut events = self.protocol.game_instance.bus.subscribe();
+        if let Err(error) = self.protocol.mark_ready(&self.player_id).await {
+            logger!(
+                ERROR,
+                "[BOT] `{}` could not get ready: {error}",
+                &self.player_id
+            );
+            return;
+        }
+        logger!(
+            INFO,
+            "[BOT] `{}` took its seat ({:?})",
+            &self.player_id,
+            &self.profile.strategy
+        );
+
+        loop {
+            match events.recv().await {
+                Ok(MatchEvent::TurnStarted(turn)) if turn.player_id == self.player_id => {
+                    self.take_turn().await;
+                    self.end_turn().await;
+                }
+                Ok(MatchEvent::StateChanged) => self.let_attack_through().await,
+                Ok(MatchEvent::MatchEnded(_)) | Err(RecvError::Closed) => break,
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+            }
+        }
+        logger!(INFO, "[BOT] `{}` left the match", &self.player_id);
+    }
+
+    /// Plays the turn of the bot as its strategy plans it.
+    async fn take_turn(&self) {
+        let Some(view) = self.view().await else {
+            return;
+        };
+        let plan = self.plan(&view).await;
+
+        for play in plan.plays {
+            tokio::time::sleep(self.think_time).await;
+            if self.match_over().await {
+                return;
+            }
+
+            let request = PlayCardRequest {
+                actor_id: self.player_id.clone(),
+                instance_id: play.instance_id,
+                target_id: play.target_id,
+                target_position: play.target_position,
+            };
+            let mut outcome = self
+                .protocol
+                .game_instance
+                .clone()
+                .play_card(self.client.clone(), &request)
+                .await;
+            while let Ok(PlayOutcome::Prompted(prompt)) = &outcome {
+                let response = PromptResponse {
+                    prompt_id: prompt.id,
+                    choice: prompt.options.first().cloned().unwrap_or_default(),
+                };
+                outcome = self
+                    .protocol
+                    .game_instance
+                    .clone()
+                    .answer_prompt(self.client.clone(), &response)
+                    .await;
+            }
+            self.settle("play", outcome.map(|_| ())).await;
+        }
+
+        // The cards played may have changed the board, or ended the match.
+        let Some(view) = self.view().await else {
+            return;
+        };
+        let attackers: Vec<_> = plan
+            .attackers
+            .into_iter()
+            .filter(|attacker| board::has_creature(&view, attacker))
+            .collect();
+        if attackers.is_empty() {
+            return;
+        }
+
+        tokio::time::sleep(self.think_time).await;
+        if self.match_over().await {
+            return;
+        }
+        let request = DeclareAttackersRequest {
+            actor_id: self.player_id.clone(),
+            attackers,
+            defender_id: None,
+        };
+        let declared = self
+            .protocol
+            .game_instance
+            .declare_attackers(self.client.clone(), &request)
+            .await;
+        if let Ok(Some(window)) = &declared {
+            self.protocol.notify_defender(window).await;
+        }
+        self.settle("attack", declared.map(|_| ())).await;
+    }
+
+    /// Ends the turn of the bot once its attack, if any, is no longer waiting on blockers.
+    async fn end_turn(&self) {
+        loop {
+            if self.match_over().await {
+                return;
+            }
+
+            let ended = self
+                .protocol
+                .game_instance
+                .end_turn(self.client.clone())
+                .await;
+            match ended {
+                Err(GameLogicError::CombatInProgress) => tokio::time::sleep(COMBAT_POLL).await,
+                ended => return self.settle("end its turn", ended.map(|_| ())).await,
+            }
+        }
+    }
+
+    /// Answers an attack waiting on the blockers of the bot without blocking, so the opponent
+    /// does not wait for the blockers window to close.
+    async fn let_attack_through(&self) {
+        let waiting = {
+            let game_state = self.protocol.game_instance.game_state.read().await;
+            let combat = game_state.combat.read().await;
+            combat.window(&self.player_id).is_ok()
+        };
+        if !waiting {
+            return;
+        }
+
+        tokio::time::sleep(self.think_time).await;
+        let request = DeclareBlockersRequest {
+            actor_id: self.player_id.clone(),
+            blocks: Vec::new(),
+        };
+        let blocked = self
+            .protocol
+            .game_instance
+            .declare_blockers(self.client.clone(), &request)
+            .await;
+        self.settle("block", blocked.map(|_| ())).await;
+    }
+
+    /// The plan of the turn, from the script of the bot if it has one and it answers.
+    async fn plan(&self, view: &PlayerView) -> BotPlan {
+        let BotStrategy::Script(hook) = &self.profile.strategy else {
+            return BotPlan::heuristic(view);
+        };
+
+        let planned = {
+            let game_state = self.protocol.game_instance.game_state.read().await;
+            let Some(state) = game_state.player_view(&self.player_id).await else {
+                return BotPlan::heuristic(view);
+            };
+            let scripts = &self.protocol.game_instance.scripts;
+            scripts.call_hook(hook, &state).await
+        };
+
+        let plan = match planned {
+            Ok(Some(plan)) => serde_json::from_value::<BotPlan>(plan)
+                .map_err(|_| GameLogicError::InvalidHookResult(hook.to_string())),
+            Ok(None) => Err(GameLogicError::FunctionNotCallable(hook.to_string())),
+            Err(error) => Err(error),
+        };
+        plan.unwrap_or_else(|error| {
+            logger!(
+                WARN,
+                "[BOT] `{}` falls back to the heuristic: {error}",
+                &self.player_id
+            );
+            BotPlan::heuristic(view)
+        })
+    }
+
+    /// Publishes the outcome of an action of the bot, like the protocol does for the actions of
+    /// clients.
+    async fn settle(&self, action: &str, outcome: Result<(), GameLogicError>) {
+        match outcome {
+            Ok(()) => {
+                self.protocol.server_instance.check_match_end().await;
+                self.protocol.publish_state().await;
+            }
+            Err(error) => logger!(
+                DEBUG,
+                "[BOT] `{}` could not {action}: {error}",
+                &self.player_id
+            ),
+        }
+    }
+
+    async fn view(&self) -> Option<PlayerView> {
+        let game_state = self.protocol.game_instance.game_state.read().await;
+        let player_views = game_state.player_views.read().await;
+        let view = player_views.get(&self.player_id)?.read().await.clone();
+        Some(view)
+    }
+
+    async fn match_over(&self) -> bool {
+        self.protocol.server_instance.report.read().await.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entity::card::{Card, CardRef, CardView};
+    use crate::game::script_manager::ScriptManager;
+
+    fn card_view(id: &str, card_type: &str, play_cost: i32, instance: u64) -> CardView {
+        let card: Card = serde_json::from_value(serde_json::json!({
+            "id": id, "name": id, "description": "", "play_cost": play_cost, "attack": 1,
+            "health": 1, "rarity": 0, "card_type": card_type, "on_play": [], "on_draw": [],
+            "on_attack": [], "on_hit": [], "on_turn_start": [], "on_turn_end": [],
+            "on_death": [], "on_ally_death": [], "on_enemy_death": [],
+        }))
+        .unwrap();
+        CardView::create_view(&card, "bot".into(), CardInstanceId::nth(instance))
+    }
+
+    fn sample_view() -> PlayerView {
+        let mut view = PlayerView::from_player(&"bot".into(), 30);
+        view.mana = 5;
+        view.current_hand[0] = Some(card_view("wolf", "creature", 1, 1));
+        view.current_hand[1] = Some(card_view("fireball", "spell", 3, 2));
+        view.current_hand[2] = Some(card_view("ogre", "creature", 4, 3));
+        view.current_hand[3] = Some(card_view("dragon", "creature", 8, 4));
+        view.board.creatures[0] = Some(CardRef {
+            id: "bear".into(),
+            amount: 1,
+            owner_id: None,
+            instances: vec![CardInstanceId::nth(0)],
+        });
+        view
+    }
+
+    #[test]
+    fn test_heuristic_spends_the_mana_and_attacks_with_every_creature() {
+        let plan = BotPlan::heuristic(&sample_view());
+        let played: Vec<_> = plan
+            .plays
+            .iter()
+            .map(|play| play.instance_id.clone())
+            .collect();
+        assert_eq!(vec![CardInstanceId::nth(3), CardInstanceId::nth(1)], played);
+        assert_eq!(
+            vec![
+                CardInstanceId::nth(0),
+                CardInstanceId::nth(3),
+                CardInstanceId::nth(1)
+            ],
+            plan.attackers
+        );
+    }
+
+    #[tokio::test]
+    async fn test_core_script_plans_the_cheapest_cards_first() {
+        let mut script_manager = ScriptManager::new_vm();
+        script_manager.load_scripts().unwrap();
+        script_manager.set_globals().await;
+        let state = serde_json::json!({ "turn": 1, "player": sample_view() });
+
+        let plan = script_manager.call_hook("core:bot_turn", &state).await;
+        let plan: BotPlan = serde_json::from_value(plan.unwrap().unwrap()).unwrap();
+        let played: Vec<_> = plan
+            .plays
+            .iter()
+            .map(|play| play.instance_id.clone())
+            .collect();
+        assert_eq!(vec![CardInstanceId::nth(1), CardInstanceId::nth(2)], played);
+        assert_eq!(
+            vec![CardInstanceId::nth(0), CardInstanceId::nth(1)],
+            plan.attackers
+        );
+    }
+}
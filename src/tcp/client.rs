@@ -1,17 +1,45 @@
 use super::protocol::Protocol;
+use crate::game::event_bus::{Audience, MatchEvent};
 use crate::game::entity::player::Player;
+use crate::tcp::compat::WireFormat;
+use crate::tcp::encoding::PayloadEncoding;
+use crate::tcp::error_response::{ErrorCode, ErrorResponse};
+use crate::tcp::handshake::{
+    self, HandshakeRequest, HandshakeResponse, NegotiatedProtocol, FEATURE_MATCH_START,
+};
 use crate::tcp::header::HeaderType;
+use crate::tcp::missed_packets::MissedPacketQueue;
+use crate::tcp::outbound::OutboundQueue;
 use crate::tcp::packet::Packet;
-use crate::{logger, utils::logger::Logger};
-use std::{collections::VecDeque, net::SocketAddr, sync::Arc};
+use crate::tcp::payload;
+use crate::tcp::rejection::{Rejection, RejectionReason, NOT_INITIALIZED_RETRY_AFTER};
+use crate::tcp::transport::{ClientStream, ReadHalf, WriteHalf};
+use crate::tcp::draft::DraftRoom;
+use crate::tcp::sideboard::SideboardRoom;
+use crate::tcp::lobby::Lobby;
+use crate::tcp::server::MatchHost;
+use crate::tcp::viewers::{self, ReplayResponse};
+use crate::utils::bandwidth::{BandwidthMeter, CountingReader};
+use crate::utils::checksum::Checksum;
+use crate::utils::connection_quality::ConnectionQuality;
+use crate::utils::errors::ProtocolError;
+use crate::utils::socket::SocketTuning;
+use crate::models::client_requests::{GetReplayRequest, MatchTarget};
+use crate::{
+    logger,
+    utils::logger::{match_span, packet_span, Logger},
+    MATCHES, RUNTIME_CONFIG, RUNTIME_FLAGS, SETTINGS,
+};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::{
-    io::AsyncReadExt,
-    net::{
-        tcp::{OwnedReadHalf, OwnedWriteHalf},
-        TcpStream,
-    },
-    sync::RwLock,
+    io::AsyncWriteExt,
+    sync::{broadcast::error::RecvError, Notify, RwLock},
 };
+use chrono::Utc;
+use tracing::Instrument;
+
+/// How long closing a client waits for its queued packets to be written.
+const CLOSE_FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// Represents a connected client in the game server.
 ///
@@ -22,179 +50,377 @@ pub struct Client {
     pub player: Arc<RwLock<Player>>,
     pub connected: Arc<RwLock<bool>>,
     pub addr: Arc<RwLock<SocketAddr>>,
-    pub read_stream: Arc<RwLock<OwnedReadHalf>>,
-    pub write_stream: Arc<RwLock<OwnedWriteHalf>>,
-    pub missed_packets: Arc<RwLock<VecDeque<Packet>>>,
+    pub read_stream: Arc<RwLock<ReadHalf>>,
+    pub write_stream: Arc<RwLock<WriteHalf>>,
+    pub outbound: Arc<OutboundQueue>, // Packets waiting for the writer task, see `write_outbound`.
+    pub missed_packets: Arc<RwLock<MissedPacketQueue>>, // Packets kept while the client is disconnected.
+    pub negotiated: Arc<RwLock<NegotiatedProtocol>>, // Protocol version and features agreed at handshake.
+    pub checksum: Arc<RwLock<Box<dyn Checksum>>>, // Negotiated checksum, keyed by the session token.
+    pub last_seen: Arc<RwLock<i64>>, // Unix timestamp (milliseconds) of the last packet received.
+    pub missed_pongs: Arc<RwLock<u32>>, // Keepalive pings sent since the client was last heard from.
+    pub malformed_packets: Arc<RwLock<u32>>, // Packets received over the match that could not be decoded.
+    pub shutdown: Arc<Notify>, // Wakes the read loop up when the client is marked as disconnected.
+    pub bandwidth: Arc<BandwidthMeter>, // Bytes exchanged with the client over the match.
+    pub quality: Arc<ConnectionQuality>, // Disconnects, round trips and resent packets over the match.
 }
 
 impl Client {
-    /// Creates a new `Client` instance from a TCP stream and address.
+    /// Creates a new `Client` instance from a connection and address.
     ///
     /// Splits the stream into read/write halves and wraps all fields
     /// in thread-safe containers for async access.
     ///
     /// # Arguments
-    /// - `stream`: The TCP stream from the accepted connection.
+    /// - `stream`: The accepted connection, over TCP or QUIC.
     /// - `addr`: The client's socket address.
-    /// - `rx`: A broadcast receiver for incoming packets.
+    /// - `negotiated`: The protocol version and features agreed during the handshake.
+    /// - `session_token`: The token the player authenticated with, keying the HMAC checksum.
     ///
     /// # Returns
     /// An `Arc<Client>` ready for use in async tasks.
     pub fn new(
-        read_stream: OwnedReadHalf,
-        write_stream: OwnedWriteHalf,
+        read_stream: ReadHalf,
+        write_stream: WriteHalf,
         addr: SocketAddr,
         protocol: Arc<Protocol>,
         player: Arc<RwLock<Player>>,
+        negotiated: NegotiatedProtocol,
+        session_token: &str,
     ) -> Self {
         Self {
+            checksum: Arc::new(RwLock::new(negotiated.checksum.build(Some(session_token)))),
             player,
             protocol,
+            negotiated: Arc::new(RwLock::new(negotiated)),
             addr: Arc::new(RwLock::new(addr)),
             connected: Arc::new(RwLock::new(true)),
             read_stream: Arc::new(RwLock::new(read_stream)),
             write_stream: Arc::new(RwLock::new(write_stream)),
-            missed_packets: Arc::new(RwLock::new(VecDeque::new())),
+            outbound: Arc::new(OutboundQueue::new(
+                RUNTIME_CONFIG.get().outbound_queue_capacity,
+            )),
+            missed_packets: Arc::new(RwLock::new(MissedPacketQueue::new(
+                RUNTIME_CONFIG.get().missed_packets_limit,
+            ))),
+            last_seen: Arc::new(RwLock::new(Utc::now().timestamp_millis())),
+            missed_pongs: Arc::new(RwLock::new(0)),
+            malformed_packets: Arc::new(RwLock::new(0)),
+            shutdown: Arc::new(Notify::new()),
+            bandwidth: Arc::new(BandwidthMeter::default()),
+            quality: Arc::new(ConnectionQuality::default()),
         }
     }
 
     /// Handles the main lifecycle of a connected client.
     ///
-    /// - Logs connection and spawns a background game state update task.
-    /// - Reads data from the client in a loop, parses packets, and handles them.
-    /// - Verifies checksums and sends error responses if validation fails.
-    ///
-    /// Exits the loop (and drops the client) if the connection is closed, or an error occurs.
+    /// - Logs connection and spawns a background task relaying the events of the match.
+    /// - Spawns the writer task sending the queued packets (see `write_outbound`).
+    /// - Reads packets from the client until it disconnects (see `read_packets`).
     pub async fn connect(self: Arc<Self>) {
-        let addr = self.addr.read().await;
+        let addr = *self.addr.read().await;
         logger!(DEBUG, "[CLIENT] Listening to `{addr}` (Authenticated)");
 
-        tokio::spawn({
-            let self_clone = Arc::clone(&self);
-            async move {
-                self_clone.listen_to_game_state().await;
+        tokio::spawn(Arc::clone(&self).listen_to_events().in_current_span());
+        tokio::spawn(Arc::clone(&self).write_outbound().in_current_span());
+
+        self.read_packets().await;
+    }
+
+    /// Closes the connection for good, waking the read loop up so it stops.
+    ///
+    /// The packets already queued are written first, for at most `CLOSE_FLUSH_TIMEOUT`.
+    pub async fn close(&self) {
+        self.outbound.close();
+        *self.connected.write().await = false;
+        self.shutdown.notify_waiters();
+        let _ = tokio::time::timeout(CLOSE_FLUSH_TIMEOUT, self.outbound.finished()).await;
+        let _ = self.write_stream.write().await.shutdown().await;
+    }
+
+    /// Reads packets from the client in a loop and handles them.
+    ///
+    /// Every packet refreshes the client's last-seen timestamp for the keepalive task.
+    ///
+    /// Exits the loop if the connection is closed, the client is marked as disconnected, or an
+    /// error occurs. A malformed header also ends the loop, since the packet boundaries can no
+    /// longer be trusted, while a packet read whole whose payload cannot be decoded is answered
+    /// with `InvalidPacketPayload` and counted against the client (see `Protocol::malformed_packet`).
+    pub async fn read_packets(self: Arc<Self>) {
+        let addr = *self.addr.read().await;
+        loop {
+            let shutdown = self.shutdown.notified();
+            tokio::pin!(shutdown);
+            shutdown.as_mut().enable();
+            if !*self.connected.read().await {
+                break;
             }
-        });
 
-        let mut buffer = [0; 1024];
-        while *self.connected.read().await {
+            let wire_format = WireFormat::for_protocol(&*self.negotiated.read().await);
             let mut read_stream_guard = self.read_stream.write().await;
-            let bytes_read = match read_stream_guard.read(&mut buffer).await {
-                Ok(0) => break,
-                Ok(n) => n,
-                Err(_) => break,
+            let mut reader = CountingReader::new(&mut *read_stream_guard);
+            let read_result = tokio::select! {
+                result = wire_format.read_packet(&mut reader) => result,
+                _ = &mut shutdown => break,
             };
+            let received = reader.count();
+            drop(read_stream_guard);
+
+            let within_caps = self
+                .protocol
+                .account_received(Arc::clone(&self), received)
+                .await;
+            if !within_caps {
+                break;
+            }
 
+            let packet = match read_result {
+                Ok(Some(packet)) => packet,
+                Ok(None) => break,
+                Err(ProtocolError::CorruptPayload(error)) => {
+                    logger!(
+                        WARN,
+                        "[CLIENT] Could not decode a packet from `{addr}` ({error})"
+                    );
+                    let response = ErrorResponse::new(ErrorCode::InvalidPayload, error)
+                        .packet(HeaderType::InvalidPacketPayload);
+                    let client = Arc::clone(&self);
+                    if self.protocol.malformed_packet(client, &response).await {
+                        break;
+                    }
+                    continue;
+                }
+                Err(error) => {
+                    logger!(ERROR, "[CLIENT] Stopped reading from `{addr}` ({error})");
+                    break;
+                }
+            };
+
+            *self.last_seen.write().await = Utc::now().timestamp_millis();
+            *self.missed_pongs.write().await = 0;
+            let span = packet_span(&packet.header.header_type);
             self.protocol
-                .handle_incoming(Arc::clone(&self), &buffer[..bytes_read])
+                .handle_incoming(Arc::clone(&self), packet)
+                .instrument(span)
                 .await;
         }
     }
 
-    /// Listens to game state updates and sends them to the client.
+    /// Writes the packets of the outbound queue to the connection, in order, until the client is
+    /// closed for good.
     ///
-    /// - If the client is disconnected, queues the game state packets.
-    /// - Sends missed packets if any are queued.
-    /// - Sends the current game state to the client.
-    ///
-    /// This function runs in a loop and exits when the receiver is dropped.
-    async fn listen_to_game_state(self: Arc<Self>) {
-        let protocol_clone = Arc::clone(&self.protocol);
-        let transmitter_clone = Arc::clone(&protocol_clone.transmitter);
-        let mut receiver = transmitter_clone.lock().await.subscribe();
-        while let Ok(game_state) = receiver.recv().await {
-            if !*self.connected.read().await {
-                let addr = self.addr.read().await;
-                let mut missed_packets = self.missed_packets.write().await;
-                missed_packets.push_back(game_state);
-
-                if missed_packets.len() > 30 {
-                    missed_packets.pop_front();
-                }
-
+    /// The task outlives reconnections, writing to whichever connection the client has. A failed
+    /// write drops the packet and marks the client as disconnected, if it was not already.
+    async fn write_outbound(self: Arc<Self>) {
+        while let Some(packet) = self.outbound.pop().await {
+            if let Err(error) = self.protocol.write_packet(&self, &packet).await {
                 logger!(
-                    WARN,
-                    "[CLIENT] `{addr}` has {} game state packets in queue",
-                    &missed_packets.len()
+                    DEBUG,
+                    "[CLIENT] Could not write to `{}`: {error}",
+                    &self.addr.read().await
                 );
+                if *self.connected.read().await {
+                    self.protocol.disconnect(Arc::clone(&self)).await;
+                }
+            }
+        }
+        self.outbound.finish();
+    }
 
+    /// Listens to the events of the match and sends the client the packets they call for.
+    ///
+    /// State changes are handled by `send_game_state`. The other events are sent as they are,
+    /// countdowns only if the client negotiated the match start, and queued as missed packets
+    /// while the client is disconnected.
+    ///
+    /// Lagging behind is harmless, since the next state sent carries the latest state anyway.
+    /// This function runs in a loop and exits when the bus is dropped.
+    async fn listen_to_events(self: Arc<Self>) {
+        let mut receiver = self.protocol.game_instance.bus.subscribe();
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(_)) => MatchEvent::StateChanged,
+                Err(RecvError::Closed) => break,
+            };
+            let player_id = self.player.read().await.id.clone();
+            if !event.visible_to(Audience::Player) || event.is_about(&player_id) {
                 continue;
             }
+            if let MatchEvent::Chat(chat) = &event {
+                let social = &self.protocol.server_instance.social;
+                if social.is_muted(&player_id, &chat.player_id) {
+                    continue;
+                }
+            }
 
-            if self.missed_packets.read().await.len() > 0 {
-                let client_clone = Arc::clone(&self);
-                self.protocol.send_missed_packets(client_clone).await;
+            match &event {
+                MatchEvent::StateChanged => Arc::clone(&self).send_game_state().await,
+                _ => {
+                    let negotiated = *self.negotiated.read().await;
+                    if matches!(event, MatchEvent::Countdown(_))
+                        && !negotiated.supports(FEATURE_MATCH_START)
+                    {
+                        continue;
+                    }
+                    if let Some(packet) = Protocol::event_packet(&event) {
+                        Arc::clone(&self).send_or_queue(packet).await;
+                    }
+                }
             }
+        }
+    }
+
+    /// Sends the client its own view of the new game state.
+    ///
+    /// Builds and serializes the view of the player behind this client, so the opponent's hand
+    /// never leaves the server, then sends it with `send_or_queue`.
+    async fn send_game_state(self: Arc<Self>) {
+        let player_id = self.player.read().await.id.clone();
+        if let Some(game_state) = self.protocol.player_state_packet(&player_id).await {
+            self.send_or_queue(game_state).await;
+        }
+    }
 
+    /// Sends a packet to the client, or keeps it for later while the client is disconnected.
+    ///
+    /// - If the client is disconnected, queues the packet as missed, evicting what its retention
+    ///   calls for (see `MissedPacketQueue`).
+    /// - Sends missed packets if any are queued.
+    /// - Sends the packet to the client.
+    async fn send_or_queue(self: Arc<Self>, packet: Packet) {
+        if !*self.connected.read().await {
+            let addr = self.addr.read().await;
+            let mut missed_packets = self.missed_packets.write().await;
+            missed_packets.set_limit(RUNTIME_CONFIG.get().missed_packets_limit);
+            let evicted = missed_packets.push(packet);
+
+            logger!(
+                WARN,
+                "[CLIENT] `{addr}` has {} missed packets in queue ({evicted} evicted)",
+                missed_packets.len()
+            );
+
+            return;
+        }
+
+        if !self.missed_packets.read().await.is_empty() {
             let client_clone = Arc::clone(&self);
-            let _ = self.protocol.send_packet(client_clone, &game_state).await;
+            self.protocol.send_missed_packets(client_clone).await;
         }
+
+        let client_clone = Arc::clone(&self);
+        let _ = self.protocol.send_packet(client_clone, &packet).await;
     }
 
     /// Reconnects a client using a temporary client instance.
     ///
-    /// - Updates the client's read/write streams, address, and connection status.
+    /// - Stops the read loop of the previous connection, if it is still running.
+    /// - Updates the client's read/write streams, address, negotiated protocol and connection status.
+    ///
+    /// The caller is responsible for starting a new read loop with `read_packets`.
     ///
     /// # Arguments
     /// - `temporary_client`: A `TemporaryClient` instance containing the new connection details.
-    pub async fn reconnect(self: Arc<Self>, temporary_client: TemporaryClient) {
+    pub async fn reconnect(
+        self: Arc<Self>,
+        temporary_client: TemporaryClient,
+        session_token: &str,
+    ) {
         let (read, write) = temporary_client.stream.into_split();
 
+        *self.connected.write().await = false;
+        self.shutdown.notify_waiters();
+
         let mut write_stream = self.write_stream.write().await;
         let mut read_stream = self.read_stream.write().await;
         let mut addr = self.addr.write().await;
         let mut connected = self.connected.write().await;
 
+        if let Some(negotiated) = temporary_client.negotiated {
+            *self.negotiated.write().await = negotiated;
+            *self.checksum.write().await = negotiated.checksum.build(Some(session_token));
+        }
+
         *write_stream = write;
         *read_stream = read;
         *addr = temporary_client.addr;
         *connected = true;
+        *self.last_seen.write().await = Utc::now().timestamp_millis();
+        *self.missed_pongs.write().await = 0;
+        self.quality.record_reconnect();
     }
 }
 
 /// Represents a temporary client used during the authentication or reconnection process.
 ///
 /// This struct holds the necessary information for handling a temporary client connection,
-/// such as the client's socket address, the TCP stream, and the protocol instance.
+/// such as the client's socket address, the connection, and the host that accepted it.
 ///
-/// Temporary clients are used to authenticate new connections or handle reconnection requests
-/// before they are fully integrated into the main client management system.
+/// Temporary clients are used to initialize matches, authenticate new connections or handle
+/// reconnection requests before they are routed to their match and fully integrated into the
+/// main client management system.
 pub struct TemporaryClient {
     /// The socket address of the temporary client.
     pub addr: SocketAddr,
-    /// The protocol instance used to handle communication with the client.
-    pub protocol: Arc<Protocol>,
-    /// The TCP stream associated with the temporary client.
-    pub stream: TcpStream,
+    /// The host that accepted the connection, hosting the matches it may be routed to.
+    pub host: Arc<MatchHost>,
+    /// The connection of the temporary client, over TCP or QUIC.
+    pub stream: ClientStream,
+    /// The protocol agreed during the handshake, if it already happened.
+    pub negotiated: Option<NegotiatedProtocol>,
 }
 
 impl TemporaryClient {
     /// Creates a new `TemporaryClient` instance.
     ///
+    /// Applies the socket tuning from the settings (`TCP_NODELAY`, keepalive and linger) to TCP
+    /// connections, which the `Client` or `Spectator` built from them keeps. QUIC connections are
+    /// left as they are.
     /// # Arguments
-    /// - `stream`: The TCP stream for the temporary client.
+    /// - `stream`: The connection of the temporary client.
     /// - `addr`: The socket address of the temporary client.
-    /// - `protocol`: The protocol instance to handle client communication.
+    /// - `host`: The host that accepted the connection.
     ///
     /// # Returns
     /// A new `TemporaryClient` instance.
-    pub async fn new(stream: TcpStream, addr: SocketAddr, protocol: Arc<Protocol>) -> Self {
+    pub async fn new(stream: ClientStream, addr: SocketAddr, host: Arc<MatchHost>) -> Self {
+        if let Some(socket) = stream.tcp_stream() {
+            if let Err(error) = SocketTuning::from_settings(SETTINGS.get()).apply(socket) {
+                logger!(
+                    WARN,
+                    "[CLIENT] Could not tune the socket of `{addr}`: {error}"
+                );
+            }
+        }
+
         TemporaryClient {
             addr,
             stream,
-            protocol,
+            host,
+            negotiated: None,
         }
     }
 
     /// Handles the lifecycle of a temporary client.
     ///
     /// - Reads data from the client for authentication.
-    /// - Parses the packet and determines if it's a `Connect` or `Reconnect` request.
-    /// - Calls the appropriate protocol handler for authentication.
+    /// - Hosts the match of an `InitServer` packet from the matchmaker, then drops the connection.
+    /// - Negotiates the protocol version when a `Handshake` packet arrives.
+    /// - Hands `CreatePrivateMatch` and `JoinPrivateMatch` requests over to the lobby.
+    /// - Answers `GetReplay` requests, see `handle_get_replay`.
+    /// - Parses the packet and determines if it's a `Connect`, `Reconnect` or `Spectate` request.
+    /// - Routes the request to its match and calls the appropriate protocol handler for
+    ///   authentication.
+    ///
+    /// Clients that speak an unsupported version receive a `VersionMismatch` packet and are dropped.
+    /// Clients that start with anything other than a `Handshake` or an `InitServer` are served as
+    /// legacy clients. Requests for a match that is not hosted are rejected as `NotInitialized`,
+    /// except the `Connect` requests of players drafting their decks, see `DraftRoom`,
+    /// sideboarding between two games of a series, see `SideboardRoom`, or waiting in the lobby,
+    /// see `Lobby`.
     ///
     /// Exits if the client sends invalid data or an error occurs.
     pub async fn handle_temp_client(mut self) {
-        let mut buffer = [0; 1024];
         let addr = self.addr.clone();
         logger!(
             DEBUG,
@@ -202,31 +428,90 @@ impl TemporaryClient {
         );
 
         loop {
-            let bytes = match self.stream.read(&mut buffer).await {
-                Ok(0) => return,
-                Err(_) => return,
-                Ok(n) => n,
+            let wire_format = match self.negotiated {
+                Some(negotiated) => WireFormat::for_protocol(&negotiated),
+                None => match self.detect_wire_format().await {
+                    Some(wire_format) => wire_format,
+                    None => return,
+                },
             };
 
-            match Packet::parse(&buffer[..bytes]) {
-                Ok(packet) => {
+            match self.read_packet(wire_format).await {
+                Ok(None) => return,
+                Ok(Some(packet)) => {
+                    if packet.header.header_type == HeaderType::Handshake {
+                        if !self.handle_handshake(&packet).await {
+                            return;
+                        }
+                        continue;
+                    }
+
+                    if packet.header.header_type == HeaderType::InitServer {
+                        let host = Arc::clone(&self.host);
+                        let _ = host.handle_init_server(&mut self.stream, &packet).await;
+                        return;
+                    }
+
+                    if packet.header.header_type == HeaderType::CreatePrivateMatch {
+                        let host = Arc::clone(&self.host);
+                        host.lobby.create_private(self, packet).await;
+                        return;
+                    }
+                    if packet.header.header_type == HeaderType::JoinPrivateMatch {
+                        let host = Arc::clone(&self.host);
+                        host.lobby.join_private(self, packet).await;
+                        return;
+                    }
+                    if packet.header.header_type == HeaderType::GetReplay {
+                        self.handle_get_replay(&packet).await;
+                        continue;
+                    }
+
+                    if !matches!(
+                        packet.header.header_type,
+                        HeaderType::Connect | HeaderType::Reconnect | HeaderType::Spectate
+                    ) {
+                        continue;
+                    }
+                    let Some(protocol) = self.route(&packet).await else {
+                        if let Some(draft) = self.route_draft(&packet) {
+                            draft.join(self, packet).await;
+                            return;
+                        }
+                        if let Some(sideboard) = self.route_sideboard(&packet) {
+                            sideboard.join(self, packet).await;
+                            return;
+                        }
+                        if Self::waits_in_lobby(&packet) {
+                            let host = Arc::clone(&self.host);
+                            host.lobby.join(self, packet).await;
+                            return;
+                        }
+                        self.reject_unrouted(&packet).await;
+                        continue;
+                    };
+                    let span = match_span(&protocol.server_instance.match_id);
+
+                    let temp_arc = Arc::new(self);
                     if packet.header.header_type == HeaderType::Connect {
-                        let temp_arc = Arc::new(self);
-                        let protocol = Arc::clone(&temp_arc.protocol);
-                        if let Err(error) = protocol.handle_connect(temp_arc, &packet).await {
+                        let connect = protocol.handle_connect(temp_arc, &packet);
+                        if let Err(error) = connect.instrument(span).await {
                             logger!(ERROR, "[CLIENT] Could not authenticate `{addr}` ({error})");
                         };
-                        break;
                     } else if packet.header.header_type == HeaderType::Reconnect {
-                        let temp_arc = Arc::new(self);
-                        let protocol = Arc::clone(&temp_arc.protocol);
-                        if let Err(error) = protocol.handle_reconnect(temp_arc, &packet).await {
+                        let reconnect = protocol.handle_reconnect(temp_arc, &packet);
+                        if let Err(error) = reconnect.instrument(span).await {
                             logger!(ERROR, "[CLIENT] Could not authenticate `{addr}` ({error})");
                         } else {
                             logger!(INFO, "[CLIENT] `{addr}` has been reconnected as `todo`")
                         }
-                        break;
+                    } else {
+                        let spectate = protocol.handle_spectate(temp_arc, &packet);
+                        if let Err(error) = spectate.instrument(span).await {
+                            logger!(WARN, "[CLIENT] `{addr}` could not spectate ({error})");
+                        }
                     }
+                    break;
                 }
                 Err(error) => {
                     logger!(ERROR, "[CLIENT] Invalid packet from `{addr}` ({error})");
@@ -235,4 +520,271 @@ impl TemporaryClient {
             }
         }
     }
+
+    /// Peeks at the first byte sent by the client to tell current clients from legacy ones.
+    ///
+    /// Current clients always open with a `Handshake`; anything else is a version 1 client, which
+    /// is given the legacy protocol without negotiation.
+    ///
+    /// # Returns
+    /// The wire format to read with, or `None` if the client is legacy and the legacy checksum is
+    /// disabled.
+    async fn detect_wire_format(&mut self) -> Option<WireFormat> {
+        let mut first = [0u8; 1];
+        match self.stream.peek(&mut first).await {
+            // The matchmaker does not handshake.
+            Ok(1) if first[0] == HeaderType::InitServer as u8 => Some(WireFormat::Current),
+            Ok(1) if first[0] != HeaderType::Handshake as u8 => {
+                if !legacy_checksum_allowed() {
+                    logger!(
+                        WARN,
+                        "[CLIENT] `{}` did not handshake and legacy clients are disabled",
+                        &self.addr
+                    );
+                    return None;
+                }
+
+                logger!(
+                    INFO,
+                    "[CLIENT] `{}` did not handshake, using the legacy protocol",
+                    &self.addr
+                );
+                let legacy = NegotiatedProtocol::legacy();
+                self.negotiated = Some(legacy);
+                Some(WireFormat::for_protocol(&legacy))
+            }
+            _ => Some(WireFormat::Current),
+        }
+    }
+
+    /// Negotiates the protocol version with the client and replies with the outcome.
+    ///
+    /// # Returns
+    /// `true` if the client's version is supported and the connection can continue.
+    async fn handle_handshake(&mut self, packet: &Packet) -> bool {
+        // Debugging clients may handshake in JSON too, so netcat needs no CBOR at all.
+        let translated = match json_payloads_allowed() && packet.payload.first() == Some(&b'{') {
+            true => PayloadEncoding::Json.incoming(packet.clone()).ok(),
+            false => None,
+        };
+        let payload = translated
+            .as_ref()
+            .map_or(&packet.payload, |json| &json.payload);
+        let request = match payload::decode::<HandshakeRequest>(&HeaderType::Handshake, payload) {
+            Ok(request) => request,
+            Err(error) => {
+                let response = Packet::reply_to(
+                    packet,
+                    HeaderType::InvalidPacketPayload,
+                    error.to_string().as_bytes(),
+                );
+                let _ = response.write_to(&mut self.stream).await;
+                return false;
+            }
+        };
+
+        match handshake::negotiate(&request, legacy_checksum_allowed()) {
+            Ok(mut negotiated) => {
+                negotiated.features &= RUNTIME_FLAGS.enabled_features();
+                if !json_payloads_allowed() {
+                    negotiated.encoding = PayloadEncoding::Cbor;
+                }
+                logger!(
+                    DEBUG,
+                    "[CLIENT] `{}` negotiated protocol v{} (features: {:#b}, checksum: {:?}, encoding: {:?})",
+                    &self.addr,
+                    negotiated.version,
+                    negotiated.features,
+                    negotiated.checksum,
+                    negotiated.encoding
+                );
+                self.negotiated = Some(negotiated);
+                if let Ok(payload) = serde_cbor::to_vec(&HandshakeResponse::from(negotiated)) {
+                    let response = Packet::reply_to(packet, HeaderType::Handshake, &payload);
+                    let response = negotiated.encoding.outgoing(&response);
+                    let _ = response.write_to(&mut self.stream).await;
+                }
+                true
+            }
+            Err(_) => {
+                logger!(
+                    WARN,
+                    "[CLIENT] `{}` uses unsupported protocol v{}",
+                    &self.addr,
+                    request.version
+                );
+                self.reject_version(packet).await;
+                false
+            }
+        }
+    }
+
+    /// Sends a `ConnectionRejected` packet explaining why the client is refused, in the wire
+    /// format of the client.
+    ///
+    /// # Returns
+    /// The error the client was refused with, for the caller to return.
+    pub async fn reject<E>(&mut self, request: &Packet, error: E) -> Result<(), E>
+    where
+        for<'a> Rejection: From<&'a E>,
+    {
+        self.send_rejection(request, Rejection::from(&error)).await;
+        Err(error)
+    }
+
+    async fn send_rejection(&mut self, request: &Packet, rejection: Rejection) {
+        self.send(&rejection.packet(Some(request))).await;
+    }
+
+    /// Sends the replay of a match in a `Replay` packet, to a client allowed to fetch it.
+    ///
+    /// The replay is read from the artifact bundle of the match, so it does not matter whether
+    /// the match is still hosted. Refused requests are answered with a `ConnectionRejected`
+    /// packet, and the client may send another request.
+    async fn handle_get_replay(&mut self, request: &Packet) {
+        let replay_request =
+            match payload::decode::<GetReplayRequest>(&HeaderType::GetReplay, &request.payload) {
+                Ok(replay_request) => replay_request,
+                Err(error) => {
+                    let header_type = error.reply_header(HeaderType::ERROR);
+                    let answer = ErrorResponse::from(&error).reply(request, header_type);
+                    self.send(&answer).await;
+                    return;
+                }
+            };
+
+        let match_id = replay_request.match_id;
+        match viewers::fetch_replay(&match_id, &replay_request.auth_token).await {
+            Ok(entries) => {
+                let replay = ReplayResponse { match_id, entries };
+                let payload = serde_cbor::to_vec(&replay).unwrap_or_default();
+                self.send(&Packet::reply_to(request, HeaderType::Replay, &payload))
+                    .await;
+            }
+            Err(error) => {
+                logger!(
+                    WARN,
+                    "[CLIENT] `{}` could not fetch the replay of `{match_id}` ({error})",
+                    &self.addr
+                );
+                let _ = self.reject(request, error).await;
+            }
+        }
+    }
+
+    /// Sends a packet in the wire format and payload encoding of the client.
+    pub async fn send(&mut self, packet: &Packet) {
+        let wire_format = match self.negotiated {
+            Some(negotiated) => WireFormat::for_protocol(&negotiated),
+            None => WireFormat::Current,
+        };
+        let negotiated = self.negotiated.unwrap_or_else(NegotiatedProtocol::legacy);
+        let packet = negotiated.outgoing(packet);
+        let _ = self.stream.write_packet(wire_format, &packet).await;
+    }
+
+    /// Reads the next packet of the client, translated back from its payload encoding.
+    ///
+    /// # Returns
+    /// - `Ok(Some(Packet))`: The next packet, with a CBOR payload.
+    /// - `Ok(None)`: If the client closed the connection.
+    /// - `Err(ProtocolError)`: If the packet or its payload could not be read.
+    pub async fn read_packet(
+        &mut self,
+        wire_format: WireFormat,
+    ) -> Result<Option<Packet>, ProtocolError> {
+        let packet = self.stream.read_packet(wire_format).await?;
+        let encoding = self.encoding();
+        packet.map(|packet| encoding.incoming(packet)).transpose()
+    }
+
+    /// The payload encoding agreed with the client; CBOR before the handshake.
+    fn encoding(&self) -> PayloadEncoding {
+        self.negotiated
+            .map(|negotiated| negotiated.encoding)
+            .unwrap_or_default()
+    }
+
+    /// Finds the match a `Connect`, `Reconnect` or `Spectate` request is for, see
+    /// `MatchRegistry::route`.
+    ///
+    /// In lobby mode, a `Connect` without a match id is only routed to a match seating its
+    /// player; the others wait in the lobby.
+    ///
+    /// # Returns
+    /// The protocol of the match, or `None` if it is not hosted or no longer accepts clients.
+    async fn route(&self, request: &Packet) -> Option<Arc<Protocol>> {
+        let target = payload::decode::<MatchTarget>(&request.header.header_type, &request.payload)
+            .unwrap_or_default();
+        let protocol = MATCHES.route(&target).await?;
+        if !*protocol.server_instance.listening.read().await {
+            return None;
+        }
+
+        if Self::waits_in_lobby(request) {
+            let player_id = target.player_id.unwrap_or_default();
+            let players = protocol.game_instance.connected_players.read().await;
+            if !players.contains_key(&player_id) {
+                return None;
+            }
+        }
+        Some(protocol)
+    }
+
+    /// Finds the draft a `Connect` request is for, while its match is not hosted yet.
+    fn route_draft(&self, request: &Packet) -> Option<Arc<DraftRoom>> {
+        self.host.drafts.route(&Self::connect_target(request)?)
+    }
+
+    /// Finds the sideboarding a `Connect` request is for, while its match is not hosted yet.
+    fn route_sideboard(&self, request: &Packet) -> Option<Arc<SideboardRoom>> {
+        self.host.sideboards.route(&Self::connect_target(request)?)
+    }
+
+    /// The match a `Connect` request names, if it is one.
+    fn connect_target(request: &Packet) -> Option<MatchTarget> {
+        if request.header.header_type != HeaderType::Connect {
+            return None;
+        }
+        let target = payload::decode::<MatchTarget>(&HeaderType::Connect, &request.payload)
+            .unwrap_or_default();
+        Some(target)
+    }
+
+    /// Whether a request may wait in the lobby: a `Connect` without a match id, in lobby mode.
+    fn waits_in_lobby(request: &Packet) -> bool {
+        if request.header.header_type != HeaderType::Connect || !Lobby::enabled() {
+            return false;
+        }
+        payload::decode::<MatchTarget>(&HeaderType::Connect, &request.payload)
+            .is_ok_and(|target| target.match_id.is_none())
+    }
+
+    /// Tells a client whose match is not hosted, or not yet, to come back shortly.
+    async fn reject_unrouted(&mut self, request: &Packet) {
+        let rejection = Rejection::new(
+            RejectionReason::NotInitialized,
+            "The match is not initialized yet",
+        )
+        .retry_after(NOT_INITIALIZED_RETRY_AFTER);
+        self.send_rejection(request, rejection).await;
+    }
+
+    /// Sends a `VersionMismatch` packet describing the protocol versions this server accepts.
+    async fn reject_version(&mut self, packet: &Packet) {
+        if let Ok(payload) = serde_cbor::to_vec(&handshake::supported()) {
+            let response = Packet::reply_to(packet, HeaderType::VersionMismatch, &payload);
+            let _ = response.write_to(&mut self.stream).await;
+        }
+    }
+}
+
+/// Whether clients may still use the legacy XOR checksum.
+fn legacy_checksum_allowed() -> bool {
+    SETTINGS.get().is_none_or(|s| s.legacy_checksum)
+}
+
+/// Whether clients may negotiate JSON payloads, for debugging.
+fn json_payloads_allowed() -> bool {
+    SETTINGS.get().is_some_and(|s| s.json_payloads)
 }
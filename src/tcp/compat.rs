@@ -0,0 +1,262 @@
+use crate::tcp::handshake::{NegotiatedProtocol, LEGACY_PROTOCOL_VERSION};
+use crate::tcp::header::{Header, HeaderType, MAX_PAYLOAD_SIZE};
+use crate::tcp::packet::{read_payload, Packet};
+use crate::tcp::rejection::Rejection;
+use crate::utils::checksum::{Checksum, XorChecksum};
+use crate::utils::errors::ProtocolError;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Size of the version 1 header: `[type, payload_len (2 bytes), checksum (2 bytes), 0x0A]`.
+pub const LEGACY_HEADER_SIZE: usize = 6;
+
+/// How packets are laid out on the wire for a given client.
+///
+/// Handlers always work with the current `Packet`; this is the only place that knows about older
+/// encodings, translating packets on their way in and out of the socket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WireFormat {
+    /// The current framing, with a 4-byte payload length and a sequence number.
+    Current,
+    /// The version 1 framing, with a 2-byte payload length, a 16-bit XOR checksum and no
+    /// sequence number.
+    Legacy,
+}
+
+impl WireFormat {
+    /// Picks the wire format matching a negotiated protocol version. The payload length is only
+    /// framed on 4 bytes for clients that negotiated a version past the legacy one.
+    pub fn for_protocol(negotiated: &NegotiatedProtocol) -> Self {
+        if negotiated.version <= LEGACY_PROTOCOL_VERSION {
+            WireFormat::Legacy
+        } else {
+            WireFormat::Current
+        }
+    }
+
+    /// The largest payload the length field of this format can declare.
+    pub fn max_payload(&self) -> usize {
+        match self {
+            WireFormat::Current => MAX_PAYLOAD_SIZE as usize,
+            WireFormat::Legacy => u16::MAX as usize,
+        }
+    }
+
+    /// Reads exactly one packet from a stream, translating it into the current representation.
+    ///
+    /// # Returns
+    /// - `Ok(Some(Packet))`: The next packet on the stream.
+    /// - `Ok(None)`: If the peer closed the connection before a new packet started.
+    /// - `Err(ProtocolError)`: If the header is invalid or the stream failed mid-packet.
+    pub async fn read_packet<R: AsyncRead + Unpin>(
+        &self,
+        reader: &mut R,
+    ) -> Result<Option<Packet>, ProtocolError> {
+        match self {
+            WireFormat::Current => Packet::read_from(reader).await,
+            WireFormat::Legacy => read_legacy(reader).await,
+        }
+    }
+
+    /// Writes a packet to a stream in this format.
+    ///
+    /// Packets a legacy client would not understand are translated to their closest legacy
+    /// equivalent, or silently dropped when there is none.
+    ///
+    /// # Returns
+    /// * `Ok(usize)` - The number of bytes written (`0` if the packet was dropped).
+    /// * `Err(std::io::Error)` - If the payload is larger than the length field of this format
+    ///   can declare, or the stream failed.
+    pub async fn write_packet<W: AsyncWrite + Unpin>(
+        &self,
+        packet: &Packet,
+        writer: &mut W,
+    ) -> std::io::Result<usize> {
+        if packet.payload.len() > self.max_payload() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "A payload of {} bytes is too large for a {self:?} client",
+                    packet.payload.len()
+                ),
+            ));
+        }
+
+        match self {
+            WireFormat::Current => packet.write_to(writer).await,
+            WireFormat::Legacy => match downgrade(packet) {
+                Some(packet) => write_legacy(&packet, writer).await,
+                None => Ok(0),
+            },
+        }
+    }
+}
+
+/// Whether a header type already existed in version 1 of the protocol.
+fn is_legacy_type(header_type: &HeaderType) -> bool {
+    matches!(
+        header_type,
+        HeaderType::Disconnect
+            | HeaderType::Connect
+            | HeaderType::Ping
+            | HeaderType::Reconnect
+            | HeaderType::GameState
+            | HeaderType::PlayCard
+            | HeaderType::AttackPlayer
+            | HeaderType::InitServer
+            | HeaderType::InvalidHeader
+            | HeaderType::AlreadyConnected
+            | HeaderType::InvalidPlayerData
+            | HeaderType::InvalidChecksum
+            | HeaderType::FailedToConnectPlayer
+            | HeaderType::InvalidPacketPayload
+            | HeaderType::ERROR
+    )
+}
+
+/// Translates an outgoing packet for a legacy client.
+///
+/// Rejections become generic `ERROR` packets, carrying the message of a refused connection, and
+/// pongs become pings; acknowledgements and prompts have no legacy equivalent and are dropped.
+fn downgrade(packet: &Packet) -> Option<Packet> {
+    match packet.header.header_type {
+        HeaderType::ActionRejected => Some(Packet::new(HeaderType::ERROR, &packet.payload)),
+        HeaderType::ConnectionRejected => {
+            let rejection: Rejection = serde_cbor::from_slice(&packet.payload).ok()?;
+            Some(Packet::new(HeaderType::ERROR, rejection.message.as_bytes()))
+        }
+        HeaderType::Pong => Some(Packet::new(HeaderType::Ping, &packet.payload)),
+        ref header_type if is_legacy_type(header_type) => Some(packet.clone()),
+        _ => None,
+    }
+}
+
+async fn read_legacy<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<Option<Packet>, ProtocolError> {
+    let mut bytes = [0u8; LEGACY_HEADER_SIZE];
+    match reader.read_exact(&mut bytes).await {
+        Ok(_) => {}
+        Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(ProtocolError::StreamError(error.to_string())),
+    }
+
+    if bytes[LEGACY_HEADER_SIZE - 1] != 0x0A {
+        return Err(ProtocolError::InvalidHeaderError(format!(
+            "Format invalid: {:?}",
+            bytes
+        )));
+    }
+
+    let header_type = HeaderType::try_from(bytes[0])
+        .ok()
+        .filter(is_legacy_type)
+        .ok_or(ProtocolError::InvalidHeaderError(
+            "Invalid message type.".to_string(),
+        ))?;
+
+    let payload_length = u16::from_be_bytes([bytes[1], bytes[2]]);
+    let payload = read_payload(reader, payload_length as usize).await?;
+
+    Ok(Some(Packet {
+        header: Header {
+            header_type,
+            payload_length: payload_length as u32,
+            checksum: u16::from_be_bytes([bytes[3], bytes[4]]) as u32,
+            sequence: 0,
+            flags: 0,
+        },
+        payload: payload.into_boxed_slice(),
+    }))
+}
+
+async fn write_legacy<W: AsyncWrite + Unpin>(
+    packet: &Packet,
+    writer: &mut W,
+) -> std::io::Result<usize> {
+    let payload_length = (packet.payload.len() as u16).to_be_bytes();
+    let checksum = (XorChecksum.compute(&packet.payload) as u16).to_be_bytes();
+    let header = [
+        packet.header.header_type.to_owned() as u8,
+        payload_length[0],
+        payload_length[1],
+        checksum[0],
+        checksum[1],
+        0x0A,
+    ];
+
+    writer.write_all(&header).await?;
+    writer.write_all(&packet.payload).await?;
+    writer.flush().await?;
+    Ok(LEGACY_HEADER_SIZE + packet.payload.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tcp::header::HEADER_SIZE;
+
+    #[tokio::test]
+    async fn test_legacy_packets_round_trip() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let packet = Packet::new(HeaderType::PlayCard, b"card");
+
+        let written = WireFormat::Legacy
+            .write_packet(&packet, &mut client)
+            .await
+            .unwrap();
+        let received = WireFormat::Legacy
+            .read_packet(&mut server)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(LEGACY_HEADER_SIZE + 4, written);
+        assert_eq!(HeaderType::PlayCard, received.header.header_type);
+        assert_eq!(b"card", &*received.payload);
+    }
+
+    #[tokio::test]
+    async fn test_newer_packets_are_downgraded_for_legacy_clients() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let accepted = Packet::new(HeaderType::ActionAccepted, b"");
+        let rejected = Packet::new(HeaderType::ActionRejected, b"nope");
+
+        assert_eq!(
+            0,
+            WireFormat::Legacy
+                .write_packet(&accepted, &mut client)
+                .await
+                .unwrap()
+        );
+        WireFormat::Legacy
+            .write_packet(&rejected, &mut client)
+            .await
+            .unwrap();
+
+        let received = WireFormat::Legacy
+            .read_packet(&mut server)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(HeaderType::ERROR, received.header.header_type);
+        assert_eq!(b"nope", &*received.payload);
+    }
+
+    #[tokio::test]
+    async fn test_payload_width_follows_the_protocol_version() {
+        let (mut client, _server) = tokio::io::duplex(1 << 20);
+        let state = Packet::new(HeaderType::GameState, &vec![0xAB; 70_000]);
+
+        assert!(WireFormat::Legacy
+            .write_packet(&state, &mut client)
+            .await
+            .is_err());
+        assert_eq!(
+            HEADER_SIZE + 70_000,
+            WireFormat::Current
+                .write_packet(&state, &mut client)
+                .await
+                .unwrap()
+        );
+    }
+}
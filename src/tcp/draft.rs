@@ -0,0 +1,477 @@
+use crate::game::card_catalog::CardCatalog;
+use crate::game::draft::{Draft, DraftSettings};
+use crate::game::entity::player::Player;
+use crate::game::rng::MatchRng;
+use crate::models::client_requests::{DraftPickRequest, MatchTarget};
+use crate::models::exit_code::{ExitCode, ExitStatus};
+use crate::models::ids::{CardDefId, MatchId, PlayerId};
+use crate::models::init_server::InitServerRequest;
+use crate::tcp::client::TemporaryClient;
+use crate::tcp::compat::WireFormat;
+use crate::tcp::error_response::ErrorResponse;
+use crate::tcp::header::HeaderType;
+use crate::tcp::lobby::Seat;
+use crate::tcp::packet::Packet;
+use crate::tcp::payload;
+use crate::tcp::rejection::{Rejection, RejectionReason};
+use crate::tcp::server::MatchHost;
+use crate::utils::errors::{DraftError, PlayerConnectionError, ServerInstanceError};
+use crate::utils::http_service::Upstream;
+use crate::{
+    logger,
+    utils::logger::{match_span, Logger},
+    HTTP_SERVICE, LIFECYCLE, MATCHES, SETTINGS,
+};
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+use tracing::Instrument;
+
+/// What the players of a draft wait for.
+#[derive(Clone)]
+enum DraftPhase {
+    Waiting,      // Every player to connect.
+    Picking,      // Their picks; the draft changed since the players last got it.
+    Hosted(Seat), // Their match, created with the drafted decks, or why it could not be.
+}
+
+/// The picks of a draft and the players connected to it.
+struct DraftState {
+    draft: Draft,
+    joined: HashSet<PlayerId>,
+    picks: u64,         // Picks made so far, auto-picks included.
+    pick_deadline: i64, // Unix timestamp (milliseconds) the running pick is made at.
+}
+
+/// The pick phase of a limited match, before the match is hosted.
+///
+/// The players connect with their `Connect` request as they would to the match. Once all of them
+/// are connected, each receives `DraftPack` whenever the draft moves on and sends `DraftPick`
+/// when it is their pick; a player who does not pick within `DRAFT_PICK_TIMEOUT` seconds gets
+/// the first card of the pack. When every pack is empty, the match is hosted with the decks the
+/// players drafted, and each of them receives `DraftComplete` with their deck and is connected to
+/// the match, which then starts as usual.
+pub struct DraftRoom {
+    match_id: MatchId,
+    players: Vec<PlayerId>,
+    request: Mutex<Option<InitServerRequest>>, // Taken to host the match once the draft is complete.
+    state: Mutex<DraftState>,
+    phase: watch::Sender<DraftPhase>,
+    picked: Notify, // Wakes the pick timer up when a player picks.
+}
+
+impl DraftRoom {
+    fn state(&self) -> MutexGuard<'_, DraftState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn pick_timeout() -> Duration {
+        Duration::from_secs(SETTINGS.get().map_or(30, |s| s.draft_pick_timeout))
+    }
+
+    fn next_deadline() -> i64 {
+        Utc::now().timestamp_millis() + Self::pick_timeout().as_millis() as i64
+    }
+
+    /// Keeps a drafting player connected to the draft until they are connected to their match or
+    /// leave. A player who leaves may connect again while the draft goes on.
+    ///
+    /// # Arguments
+    /// * `client` - The client, which must have completed the handshake.
+    /// * `connect` - Its `Connect` request, used again to connect it to the match.
+    pub async fn join(self: Arc<Self>, mut client: TemporaryClient, connect: Packet) {
+        let Some(negotiated) = client.negotiated else {
+            let _ = client
+                .reject(&connect, PlayerConnectionError::HandshakeRequired)
+                .await;
+            return;
+        };
+        let player_id = match Player::new_connection(&connect.payload).await {
+            Ok(player) => player.player_id,
+            Err(error) => {
+                let _ = client.reject(&connect, error).await;
+                return;
+            }
+        };
+        if !self.players.contains(&player_id) {
+            let _ = client
+                .reject(&connect, PlayerConnectionError::PlayerNotConnected)
+                .await;
+            return;
+        }
+
+        let everyone = {
+            let mut state = self.state();
+            state.joined.insert(player_id.clone());
+            state.joined.len() == self.players.len()
+        };
+        logger!(INFO, "[DRAFT] `{player_id}` joined the draft");
+        if everyone && matches!(*self.phase.borrow(), DraftPhase::Waiting) {
+            self.state().pick_deadline = Self::next_deadline();
+            self.phase.send_replace(DraftPhase::Picking);
+        }
+
+        let wire_format = WireFormat::for_protocol(&negotiated);
+        let mut phase = self.phase.subscribe();
+        phase.mark_changed();
+        loop {
+            // Peeking does not consume anything, so a packet is never read halfway when the
+            // client is connected to its match.
+            let mut first = [0u8; 1];
+            let packet = tokio::select! {
+                changed = phase.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                    let current = phase.borrow_and_update().clone();
+                    match current {
+                        DraftPhase::Waiting => {}
+                        DraftPhase::Picking => self.send_pack(&mut client, &connect, &player_id).await,
+                        DraftPhase::Hosted(seat) => {
+                            self.take_seat(client, &connect, &player_id, seat).await;
+                            return;
+                        }
+                    }
+                    continue;
+                }
+                peeked = client.stream.peek(&mut first) => match peeked {
+                    Ok(read) if read > 0 => client.read_packet(wire_format).await,
+                    _ => Ok(None),
+                },
+            };
+
+            match packet {
+                Ok(Some(packet)) if packet.header.header_type == HeaderType::DraftPick => {
+                    let request = payload::decode::<DraftPickRequest>(
+                        &HeaderType::DraftPick,
+                        &packet.payload,
+                    );
+                    let answer = match request {
+                        Ok(request) => match self.pick(&player_id, &request.card_id) {
+                            Ok(()) => Packet::reply_to(&packet, HeaderType::ActionAccepted, b""),
+                            Err(error) => ErrorResponse::from(&error)
+                                .reply(&packet, HeaderType::ActionRejected),
+                        },
+                        Err(error) => ErrorResponse::from(&error)
+                            .reply(&packet, error.reply_header(HeaderType::ActionRejected)),
+                    };
+                    client.send(&answer).await;
+                }
+                Ok(Some(packet)) if packet.header.header_type == HeaderType::Ping => {
+                    client
+                        .send(&Packet::reply_to(&packet, HeaderType::Pong, &[]))
+                        .await;
+                }
+                Ok(Some(_)) => continue,
+                Ok(None) | Err(_) => {
+                    self.state().joined.remove(&player_id);
+                    logger!(INFO, "[DRAFT] `{player_id}` left the draft");
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Picks a card for a player, telling every player about the new state of the draft.
+    fn pick(&self, player_id: &PlayerId, card_id: &CardDefId) -> Result<(), DraftError> {
+        let complete = {
+            let mut state = self.state();
+            state.draft.pick(player_id, card_id)?;
+            state.picks += 1;
+            state.pick_deadline = Self::next_deadline();
+            state.draft.is_complete()
+        };
+
+        logger!(DEBUG, "[DRAFT] `{player_id}` picked `{card_id}`");
+        self.picked.notify_one();
+        if !complete {
+            self.phase.send_replace(DraftPhase::Picking);
+        }
+        Ok(())
+    }
+
+    async fn send_pack(
+        &self,
+        client: &mut TemporaryClient,
+        request: &Packet,
+        player_id: &PlayerId,
+    ) {
+        let view = {
+            let state = self.state();
+            state.draft.view(player_id, state.pick_deadline)
+        };
+        if let Ok(payload) = serde_cbor::to_vec(&view) {
+            let packet = Packet::reply_to(request, HeaderType::DraftPack, &payload);
+            client.send(&packet).await;
+        }
+    }
+
+    /// Sends a player the deck they drafted and connects them to their match, or tells them why
+    /// it could not be created.
+    async fn take_seat(
+        &self,
+        mut client: TemporaryClient,
+        connect: &Packet,
+        player_id: &PlayerId,
+        seat: Seat,
+    ) {
+        let protocol = match seat {
+            Ok(protocol) => protocol,
+            Err(rejection) => {
+                client.send(&rejection.packet(Some(connect))).await;
+                return;
+            }
+        };
+
+        let deck = self.state().draft.deck(player_id);
+        if let Ok(payload) = serde_cbor::to_vec(&deck) {
+            let packet = Packet::reply_to(connect, HeaderType::DraftComplete, &payload);
+            client.send(&packet).await;
+        }
+        let span = match_span(&protocol.server_instance.match_id);
+        let connection = protocol.handle_connect(Arc::new(client), connect);
+        if let Err(error) = connection.instrument(span).await {
+            logger!(
+                ERROR,
+                "[DRAFT] Could not connect `{player_id}` to their match ({error})"
+            );
+        }
+    }
+
+    /// Runs the draft: waits for the players, picks for those whose timer runs out, then hosts
+    /// the match with the drafted decks.
+    ///
+    /// Players who are not all connected within `READY_TIMEOUT` seconds abort the draft.
+    async fn run(self: Arc<Self>, host: Arc<MatchHost>) {
+        let ready_timeout = Duration::from_secs(SETTINGS.get().map_or(60, |s| s.ready_timeout));
+        let mut phase = self.phase.subscribe();
+        let started = phase.wait_for(|phase| !matches!(phase, DraftPhase::Waiting));
+        if !matches!(
+            tokio::time::timeout(ready_timeout, started).await,
+            Ok(Ok(_))
+        ) {
+            self.abort(&host);
+            return;
+        }
+        drop(phase);
+        logger!(
+            INFO,
+            "[DRAFT] Every player joined, the draft of `{}` starts",
+            self.match_id
+        );
+
+        loop {
+            let (picks, deadline) = {
+                let state = self.state();
+                if state.draft.is_complete() {
+                    break;
+                }
+                (state.picks, state.pick_deadline)
+            };
+
+            let left = (deadline - Utc::now().timestamp_millis()).max(0) as u64;
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_millis(left)) => {}
+                _ = self.picked.notified() => continue,
+            }
+            let auto_pick = {
+                let mut state = self.state();
+                if state.picks != picks {
+                    continue;
+                }
+                state.draft.auto_pick()
+            };
+            if let Some((player_id, card_id)) = auto_pick {
+                logger!(
+                    INFO,
+                    "[DRAFT] `{player_id}` ran out of time, picked `{card_id}` for them"
+                );
+                let mut state = self.state();
+                state.picks += 1;
+                state.pick_deadline = Self::next_deadline();
+                if !state.draft.is_complete() {
+                    self.phase.send_replace(DraftPhase::Picking);
+                }
+            }
+        }
+
+        self.host_match(&host).await;
+    }
+
+    /// Hosts the match with the drafted decks and takes the players to it.
+    async fn host_match(&self, host: &Arc<MatchHost>) {
+        let request = self
+            .request
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let Some(mut request) = request else {
+            return;
+        };
+        {
+            let state = self.state();
+            for player in &mut request.players {
+                player.deck = Some(state.draft.deck(&player.id));
+            }
+        }
+
+        let seat = host.host(request).await.map_err(|error| {
+            logger!(
+                ERROR,
+                "[DRAFT] Could not create the drafted match `{}`: {error}",
+                self.match_id
+            );
+            if !MATCHES.hosts_many() {
+                LIFECYCLE.exit(ExitStatus::new(ExitCode::from(&error), error.to_string()));
+            }
+            Rejection::from(&error)
+        });
+        host.drafts.close(&self.match_id);
+        self.phase.send_replace(DraftPhase::Hosted(seat));
+    }
+
+    /// Gives up on a draft whose players did not all connect in time.
+    fn abort(&self, host: &Arc<MatchHost>) {
+        let reason = format!(
+            "Not every player of `{}` joined the draft in time",
+            self.match_id
+        );
+        logger!(WARN, "[DRAFT] {reason}");
+        host.drafts.close(&self.match_id);
+        let rejection = Rejection::new(RejectionReason::NotInMatch, reason.clone());
+        self.phase.send_replace(DraftPhase::Hosted(Err(rejection)));
+        if !MATCHES.hosts_many() {
+            LIFECYCLE.exit(ExitStatus::new(ExitCode::PlayersNoShow, reason));
+        }
+    }
+}
+
+/// The drafts of the limited matches the matchmaker initialized, by match id, until their match
+/// is hosted.
+#[derive(Default)]
+pub struct Drafts {
+    rooms: Mutex<HashMap<MatchId, Arc<DraftRoom>>>,
+}
+
+impl Drafts {
+    fn rooms(&self) -> MutexGuard<'_, HashMap<MatchId, Arc<DraftRoom>>> {
+        self.rooms.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Deals the packs of a match initialized with `draft` and waits for its players to draft.
+    ///
+    /// # Returns
+    /// An error if the match is already hosted or drafted, there is no room for it, or no packs
+    /// could be dealt.
+    pub async fn open(
+        &self,
+        host: Arc<MatchHost>,
+        mut request: InitServerRequest,
+    ) -> Result<(), ServerInstanceError> {
+        let Some(settings) = request.draft.take() else {
+            return Err(ServerInstanceError::DraftFailed(String::from(
+                "the match has no draft",
+            )));
+        };
+        let match_id = request.match_id.clone();
+        MATCHES.admits(&match_id).await?;
+        if self.rooms().contains_key(&match_id) {
+            return Err(ServerInstanceError::AlreadyInitialized(match_id));
+        }
+
+        let seed = *request.seed.get_or_insert_with(rand::random);
+        let players: Vec<PlayerId> = request.players.iter().map(|p| p.id.clone()).collect();
+        let packs = Self::deal(&settings, players.len(), seed).await?;
+        let room = Arc::new(DraftRoom {
+            match_id: match_id.clone(),
+            players: players.clone(),
+            request: Mutex::new(Some(request)),
+            state: Mutex::new(DraftState {
+                draft: Draft::new(players, packs),
+                joined: HashSet::new(),
+                picks: 0,
+                pick_deadline: 0,
+            }),
+            phase: watch::Sender::new(DraftPhase::Waiting),
+            picked: Notify::new(),
+        });
+
+        self.rooms().insert(match_id.clone(), Arc::clone(&room));
+        let span = match_span(&match_id);
+        tokio::spawn(room.run(host).instrument(span));
+        logger!(
+            INFO,
+            "[DRAFT] Drafting match `{match_id}`: {} packs of {} cards",
+            settings.packs,
+            settings.pack_size
+        );
+        Ok(())
+    }
+
+    /// Deals the packs of a draft: packs of its set from the card server, or dealt from the local
+    /// catalog when it has no set or the card server fails.
+    async fn deal(
+        settings: &DraftSettings,
+        players: usize,
+        seed: u64,
+    ) -> Result<Vec<Vec<CardDefId>>, ServerInstanceError> {
+        let count = settings.pack_count(players);
+        let size = settings.pack_size as usize;
+        if let Some(set) = &settings.set {
+            match Self::fetch_packs(set, count, size).await {
+                Ok(packs) => return Ok(packs),
+                Err(error) => logger!(
+                    WARN,
+                    "[DRAFT] Dealing packs from the local catalog, the card server failed: {error}"
+                ),
+            }
+        }
+
+        let cards = CardCatalog::default().card_ids();
+        Draft::deal(&cards, count, size, &MatchRng::new(seed))
+            .map_err(|error| ServerInstanceError::DraftFailed(error.to_string()))
+    }
+
+    /// Fetches packs of a card set, served at `<CARD_SERVER>/api/draft/<set>/packs`.
+    async fn fetch_packs(
+        set: &str,
+        count: usize,
+        size: usize,
+    ) -> Result<Vec<Vec<CardDefId>>, String> {
+        let card_server = SETTINGS.get().map_or("", |s| &s.card_server);
+        let url = format!("{card_server}/api/draft/{set}/packs?count={count}&size={size}");
+        let response = HTTP_SERVICE
+            .send(Upstream::Card, |client| client.get(&url))
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("{url} answered {}", response.status()));
+        }
+
+        let packs: Vec<Vec<CardDefId>> = response.json().await.map_err(|e| e.to_string())?;
+        if packs.len() != count || packs.iter().any(|pack| pack.is_empty()) {
+            return Err(format!("expected {count} packs, got {}", packs.len()));
+        }
+        Ok(packs)
+    }
+
+    /// Finds the draft a `Connect` request is for: the draft it names, else the draft of its
+    /// player.
+    pub fn route(&self, target: &MatchTarget) -> Option<Arc<DraftRoom>> {
+        let rooms = self.rooms();
+        if let Some(match_id) = &target.match_id {
+            return rooms.get(match_id).cloned();
+        }
+        let player_id = target.player_id.as_ref()?;
+        rooms
+            .values()
+            .find(|room| room.players.contains(player_id))
+            .cloned()
+    }
+
+    fn close(&self, match_id: &MatchId) {
+        self.rooms().remove(match_id);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::tcp::handshake::PAYLOAD_VERSION;
+use crate::tcp::header::HeaderType;
+use crate::tcp::packet::Packet;
+use crate::utils::errors::ProtocolError;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// How the structured payloads of a client are encoded on the wire, agreed at handshake.
+///
+/// Handlers always work with CBOR payloads; packets are translated when they are sealed for a
+/// client and after their checksum is verified, so checksums always cover the bytes on the wire.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadEncoding {
+    #[default]
+    Cbor,
+    /// JSON text, so the server can be poked with netcat or scripts during development. Only
+    /// granted when `JSON_PAYLOADS` is set.
+    Json,
+}
+
+impl PayloadEncoding {
+    /// Whether the payloads of a header type are CBOR, and so are translated. Text and raw
+    /// payloads are sent as they are in every encoding.
+    pub fn translates(header_type: &HeaderType) -> bool {
+        !matches!(
+            header_type,
+            HeaderType::Ping
+                | HeaderType::Pong
+                | HeaderType::InvalidPlayerData
+                | HeaderType::FailedToConnectPlayer
+        )
+    }
+
+    /// Returns the packet as it is sent to a client of this encoding, before it is sealed.
+    ///
+    /// The packet is only copied if it needs to change. A CBOR payload that has no JSON
+    /// equivalent is sent as it is.
+    pub fn outgoing<'a>(self, packet: &'a Packet) -> Cow<'a, Packet> {
+        if self == PayloadEncoding::Cbor
+            || packet.payload.is_empty()
+            || !Self::translates(&packet.header.header_type)
+        {
+            return Cow::Borrowed(packet);
+        }
+
+        let json = serde_cbor::from_slice::<serde_cbor::Value>(&packet.payload)
+            .ok()
+            .and_then(|value| serde_json::to_vec(&value).ok());
+        match json {
+            Some(json) => {
+                let mut translated = Packet::new(packet.header.header_type.clone(), &json);
+                translated.header.flags = packet.header.flags;
+                translated.header.sequence = packet.header.sequence;
+                Cow::Owned(translated)
+            }
+            None => Cow::Borrowed(packet),
+        }
+    }
+
+    /// Translates a packet received from a client of this encoding back to CBOR, once its
+    /// checksum was verified.
+    ///
+    /// JSON payloads are written against the current schema, so they are given the
+    /// `PAYLOAD_VERSION` byte (see `payload::decode`).
+    ///
+    /// # Returns
+    /// * `Ok(Packet)` - The packet, with a CBOR payload.
+    /// * `Err(ProtocolError::CorruptPayload)` - If the payload is not valid JSON.
+    pub fn incoming(self, packet: Packet) -> Result<Packet, ProtocolError> {
+        if self == PayloadEncoding::Cbor
+            || packet.payload.is_empty()
+            || !Self::translates(&packet.header.header_type)
+        {
+            return Ok(packet);
+        }
+
+        let value: serde_json::Value = serde_json::from_slice(&packet.payload)
+            .map_err(|e| ProtocolError::CorruptPayload(e.to_string()))?;
+        let mut payload = vec![PAYLOAD_VERSION];
+        serde_cbor::to_writer(&mut payload, &value)
+            .map_err(|e| ProtocolError::CorruptPayload(e.to_string()))?;
+
+        let mut translated = Packet::new(packet.header.header_type, &payload);
+        translated.header.flags = packet.header.flags;
+        translated.header.sequence = packet.header.sequence;
+        Ok(translated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_cbor::Value;
+
+    #[test]
+    fn test_json_payloads_are_translated_both_ways() {
+        let mut map = std::collections::BTreeMap::new();
+        map.insert(Value::Text("actor_id".into()), Value::Text("red".into()));
+        map.insert(Value::Text("amount".into()), Value::Integer(3));
+        let cbor = serde_cbor::to_vec(&Value::Map(map)).unwrap();
+
+        let mut sent = Packet::new(HeaderType::ConfirmStake, &cbor);
+        sent.header.sequence = 42;
+        let wire = PayloadEncoding::Json.outgoing(&sent);
+        let json: serde_json::Value = serde_json::from_slice(&wire.payload).unwrap();
+        assert_eq!(serde_json::json!({ "actor_id": "red", "amount": 3 }), json);
+        assert_eq!(42, wire.header.sequence);
+
+        let received = PayloadEncoding::Json.incoming(wire.into_owned()).unwrap();
+        assert_eq!(PAYLOAD_VERSION, received.payload[0]);
+        let decoded: Value = serde_cbor::from_slice(&received.payload[1..]).unwrap();
+        assert_eq!(serde_cbor::from_slice::<Value>(&cbor).unwrap(), decoded);
+        assert_eq!(42, received.header.sequence);
+        assert_eq!(
+            received.payload.len() as u32,
+            received.header.payload_length
+        );
+    }
+
+    #[test]
+    fn test_text_payloads_and_cbor_clients_are_left_alone() {
+        let failed = Packet::new(HeaderType::FailedToConnectPlayer, b"Deck not found");
+        assert!(matches!(
+            PayloadEncoding::Json.outgoing(&failed),
+            Cow::Borrowed(_)
+        ));
+
+        let cbor = serde_cbor::to_vec(&Value::Integer(1)).unwrap();
+        let state = Packet::new(HeaderType::GameState, &cbor);
+        assert!(matches!(
+            PayloadEncoding::Cbor.outgoing(&state),
+            Cow::Borrowed(_)
+        ));
+
+        let garbage = Packet::new(HeaderType::PlayCard, b"{\"actor_id\":");
+        assert!(matches!(
+            PayloadEncoding::Json.incoming(garbage),
+            Err(ProtocolError::CorruptPayload(_))
+        ));
+    }
+}
@@ -0,0 +1,411 @@
+use crate::tcp::header::HeaderType;
+use crate::tcp::packet::Packet;
+use crate::utils::errors::{
+    DraftError, GameLogicError, PayloadError, ProtocolError, SideboardError, SocialError,
+};
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+
+/// Why a request failed, as a stable number client UIs can localize and branch on.
+///
+/// Codes are grouped by hundreds and never reused; new failures get new codes.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// The server failed while handling the request; retrying may succeed.
+    Internal = 100,
+    InvalidPayload = 101,
+    PayloadOutOfLimits = 102,
+    UnsupportedPayloadVersion = 103,
+    NotNegotiated = 104,
+
+    MatchNotStarted = 200,
+    StakeNotConfirmed = 201,
+    StakeMismatch = 202,
+    NoStake = 203,
+    NotPlayerTurn = 204,
+    PlayerNotFound = 205,
+    PlayerMismatch = 206,
+    Stunned = 207,
+
+    CardNotInHand = 300,
+    CardNotOnBoard = 301,
+    CardNotInGraveyard = 302,
+    CardUnavailable = 303,
+    InvalidPosition = 304,
+    SlotOccupied = 305,
+    WrongZone = 306,
+    ZoneFull = 307,
+    NotAPermanent = 308,
+    HandFull = 309,
+    NoActivatedAbility = 310,
+    AbilityExhausted = 311,
+
+    TargetRequired = 400,
+    InvalidTarget = 401,
+    NoLegalTargets = 402,
+    PromptNotFound = 403,
+    InvalidPromptChoice = 404,
+    TooManyPrompts = 405,
+
+    BatchTooLarge = 500,
+    BatchActionFailed = 501,
+    BatchActionPrompted = 502,
+    UndoDisabled = 510,
+    NothingToUndo = 511,
+    UndoLimitReached = 512,
+    UndoConsentUnavailable = 513,
+
+    CombatInProgress = 600,
+    NoAttackers = 601,
+    InvalidAttacker = 602,
+    InvalidDefender = 603,
+    InvalidBlock = 604,
+    NoCombatToBlock = 605,
+
+    /// A card script failed; the play is refused rather than half applied.
+    ScriptFailed = 700,
+    ScriptTimeout = 701,
+    ScriptMemoryLimit = 702,
+    EffectLoop = 703,
+
+    EmotesMuted = 800,
+    UnknownEmote = 801,
+    InvalidPing = 802,
+    ChatRateLimited = 803,
+    EmptyMessage = 804,
+    MessageTooLong = 805,
+    MessageFiltered = 806,
+
+    NoDraftCards = 900,
+    NotYourPick = 901,
+    CardNotInPack = 902,
+    DraftComplete = 903,
+    NotInDeck = 910,
+    NotInSideboard = 911,
+    SideboardedDeckIllegal = 912,
+    SwapsAlreadySubmitted = 913,
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(*self as u16)
+    }
+}
+
+/// Payload of the `ActionRejected`, `InvalidPacketPayload` and `ERROR` packets sent to clients
+/// that negotiated error codes; other clients are sent the message alone, as text.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ErrorResponse {
+    pub code: ErrorCode, // Machine-readable reason of the failure.
+    pub message: String, // Human-readable details, in English, for logs and fallback UIs.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub context: BTreeMap<String, String>, // What the failure is about, such as the `card_id`.
+}
+
+impl ErrorResponse {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            context: BTreeMap::new(),
+        }
+    }
+
+    /// Names what the failure is about.
+    pub fn with(mut self, key: &str, value: impl ToString) -> Self {
+        self.context.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Builds the `header_type` packet answering `request`.
+    pub fn reply(&self, request: &Packet, header_type: HeaderType) -> Packet {
+        Packet::reply_to(request, header_type, &self.payload())
+    }
+
+    /// Builds a `header_type` packet answering no request.
+    pub fn packet(&self, header_type: HeaderType) -> Packet {
+        Packet::new(header_type, &self.payload())
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        serde_cbor::to_vec(self).unwrap_or_else(|_| self.message.clone().into_bytes())
+    }
+
+    /// Whether packets of a header type carry an `ErrorResponse`.
+    pub fn carried_by(header_type: &HeaderType) -> bool {
+        matches!(
+            header_type,
+            HeaderType::ActionRejected | HeaderType::InvalidPacketPayload | HeaderType::ERROR
+        )
+    }
+
+    /// Returns an error packet as it is sent to a client that did not negotiate error codes: with
+    /// the message of its `ErrorResponse` as text, as the server sent it before error codes.
+    ///
+    /// # Returns
+    /// `None` if the packet carries no `ErrorResponse`.
+    pub fn plain(packet: &Packet) -> Option<Packet> {
+        if !Self::carried_by(&packet.header.header_type) || packet.payload.is_empty() {
+            return None;
+        }
+
+        #[derive(serde::Deserialize)]
+        struct Message {
+            message: String,
+        }
+        let Message { message } = serde_cbor::from_slice(&packet.payload).ok()?;
+        let mut plain = Packet::new(packet.header.header_type.clone(), message.as_bytes());
+        plain.header.flags = packet.header.flags;
+        plain.header.sequence = packet.header.sequence;
+        Some(plain)
+    }
+}
+
+impl From<&GameLogicError> for ErrorResponse {
+    fn from(error: &GameLogicError) -> Self {
+        let response = |code| ErrorResponse::new(code, error.to_string());
+        match error {
+            GameLogicError::MatchNotStarted => response(ErrorCode::MatchNotStarted),
+            GameLogicError::StakeNotConfirmed => response(ErrorCode::StakeNotConfirmed),
+            GameLogicError::StakeMismatch => response(ErrorCode::StakeMismatch),
+            GameLogicError::NoStake => response(ErrorCode::NoStake),
+            GameLogicError::NotPlayerTurn => response(ErrorCode::NotPlayerTurn),
+            GameLogicError::PlayerNotFound => response(ErrorCode::PlayerNotFound),
+            GameLogicError::PlayerIdDoesNotMatch => response(ErrorCode::PlayerMismatch),
+            GameLogicError::Stunned(target) => response(ErrorCode::Stunned).with("target", target),
+
+            GameLogicError::CardPlayedIsNotInHand => response(ErrorCode::CardNotInHand),
+            GameLogicError::CardNotInHand(card) => {
+                response(ErrorCode::CardNotInHand).with("card_id", card)
+            }
+            GameLogicError::CardNotOnBoard(card) => {
+                response(ErrorCode::CardNotOnBoard).with("card_id", card)
+            }
+            GameLogicError::NotInGraveyard(card) => {
+                response(ErrorCode::CardNotInGraveyard).with("card_id", card)
+            }
+            GameLogicError::UnableToGetCardDetails => response(ErrorCode::CardUnavailable),
+            GameLogicError::InvalidPosition(position) => {
+                response(ErrorCode::InvalidPosition).with("position", position)
+            }
+            GameLogicError::SlotOccupied(position) => {
+                response(ErrorCode::SlotOccupied).with("position", position)
+            }
+            GameLogicError::WrongZone(card_type, position) => response(ErrorCode::WrongZone)
+                .with("card_type", card_type)
+                .with("position", position),
+            GameLogicError::ZoneFull(zone) => response(ErrorCode::ZoneFull).with("zone", zone),
+            GameLogicError::NotAPermanent(card_type) => {
+                response(ErrorCode::NotAPermanent).with("card_type", card_type)
+            }
+            GameLogicError::HandFull(player) => {
+                response(ErrorCode::HandFull).with("player_id", player)
+            }
+            GameLogicError::NoActivatedAbility(card) => {
+                response(ErrorCode::NoActivatedAbility).with("card_id", card)
+            }
+            GameLogicError::AbilityExhausted(card) => {
+                response(ErrorCode::AbilityExhausted).with("card_id", card)
+            }
+
+            GameLogicError::TargetRequired => response(ErrorCode::TargetRequired),
+            GameLogicError::InvalidTarget(target) => {
+                response(ErrorCode::InvalidTarget).with("target_id", target)
+            }
+            GameLogicError::NoLegalTargets => response(ErrorCode::NoLegalTargets),
+            GameLogicError::PromptNotFound(prompt) => {
+                response(ErrorCode::PromptNotFound).with("prompt_id", prompt)
+            }
+            GameLogicError::InvalidPromptChoice(choice) => {
+                response(ErrorCode::InvalidPromptChoice).with("choice", choice)
+            }
+            GameLogicError::TooManyPrompts(answered) => {
+                response(ErrorCode::TooManyPrompts).with("answered", answered)
+            }
+
+            GameLogicError::BatchTooLarge(size) => {
+                response(ErrorCode::BatchTooLarge).with("size", size)
+            }
+            // The failing action keeps its own context, along with its index and code.
+            GameLogicError::BatchActionFailed(index, cause) => {
+                let cause = ErrorResponse::from(&**cause);
+                ErrorResponse {
+                    code: ErrorCode::BatchActionFailed,
+                    message: error.to_string(),
+                    context: cause.context,
+                }
+                .with("index", index)
+                .with("cause", cause.code as u16)
+            }
+            GameLogicError::BatchActionPrompted => response(ErrorCode::BatchActionPrompted),
+            GameLogicError::UndoDisabled => response(ErrorCode::UndoDisabled),
+            GameLogicError::NothingToUndo => response(ErrorCode::NothingToUndo),
+            GameLogicError::UndoLimitReached(limit) => {
+                response(ErrorCode::UndoLimitReached).with("limit", limit)
+            }
+            GameLogicError::UndoConsentUnavailable => response(ErrorCode::UndoConsentUnavailable),
+
+            GameLogicError::CombatInProgress => response(ErrorCode::CombatInProgress),
+            GameLogicError::NoAttackers => response(ErrorCode::NoAttackers),
+            GameLogicError::InvalidAttacker(card) => {
+                response(ErrorCode::InvalidAttacker).with("card_id", card)
+            }
+            GameLogicError::InvalidDefender(defender) => {
+                response(ErrorCode::InvalidDefender).with("player_id", defender)
+            }
+            GameLogicError::InvalidBlock(card) => {
+                response(ErrorCode::InvalidBlock).with("card_id", card)
+            }
+            GameLogicError::NoCombatToBlock => response(ErrorCode::NoCombatToBlock),
+
+            GameLogicError::FunctionNotFound(function, card) => response(ErrorCode::ScriptFailed)
+                .with("function", function)
+                .with("card_id", card),
+            GameLogicError::FunctionNotCallable(function) => {
+                response(ErrorCode::ScriptFailed).with("function", function)
+            }
+            GameLogicError::InvalidGameActions => response(ErrorCode::ScriptFailed),
+            GameLogicError::InvalidHookResult(hook) => {
+                response(ErrorCode::ScriptFailed).with("hook", hook)
+            }
+            GameLogicError::ScriptTimeout(script) => {
+                response(ErrorCode::ScriptTimeout).with("script", script)
+            }
+            GameLogicError::ScriptMemoryLimit(script) => {
+                response(ErrorCode::ScriptMemoryLimit).with("script", script)
+            }
+            GameLogicError::ScriptWorkerStopped => response(ErrorCode::Internal),
+            GameLogicError::EffectLoopDetected(card, limit) => response(ErrorCode::EffectLoop)
+                .with("card_id", card)
+                .with("limit", limit),
+        }
+    }
+}
+
+impl From<&PayloadError> for ErrorResponse {
+    fn from(error: &PayloadError) -> Self {
+        let code = match error {
+            PayloadError::OverBudget(..)
+            | PayloadError::TooDeep(_)
+            | PayloadError::CollectionTooLarge(..)
+            | PayloadError::TooManyValues(_) => ErrorCode::PayloadOutOfLimits,
+            PayloadError::UnsupportedPayloadVersion(..) => ErrorCode::UnsupportedPayloadVersion,
+            PayloadError::Malformed(_) | PayloadError::Decode(_) => ErrorCode::InvalidPayload,
+        };
+        ErrorResponse::new(code, error.to_string())
+    }
+}
+
+impl From<&ProtocolError> for ErrorResponse {
+    fn from(error: &ProtocolError) -> Self {
+        let code = match error {
+            ProtocolError::PayloadTooLarge(_) => ErrorCode::PayloadOutOfLimits,
+            ProtocolError::InvalidHeaderError(_)
+            | ProtocolError::InvalidPacketError(_)
+            | ProtocolError::CorruptPayload(_) => ErrorCode::InvalidPayload,
+            ProtocolError::StreamError(_) => ErrorCode::Internal,
+        };
+        ErrorResponse::new(code, error.to_string())
+    }
+}
+
+impl From<&SocialError> for ErrorResponse {
+    fn from(error: &SocialError) -> Self {
+        let response = |code| ErrorResponse::new(code, error.to_string());
+        match error {
+            SocialError::EmotesMuted => response(ErrorCode::EmotesMuted),
+            SocialError::UnknownEmote(emote) => {
+                response(ErrorCode::UnknownEmote).with("emote", emote)
+            }
+            SocialError::InvalidPing(position) => {
+                response(ErrorCode::InvalidPing).with("position", position)
+            }
+            SocialError::RateLimited => response(ErrorCode::ChatRateLimited),
+            SocialError::EmptyMessage => response(ErrorCode::EmptyMessage),
+            SocialError::MessageTooLong(limit) => {
+                response(ErrorCode::MessageTooLong).with("limit", limit)
+            }
+            SocialError::MessageFiltered => response(ErrorCode::MessageFiltered),
+        }
+    }
+}
+
+impl From<&DraftError> for ErrorResponse {
+    fn from(error: &DraftError) -> Self {
+        let response = |code| ErrorResponse::new(code, error.to_string());
+        match error {
+            DraftError::NoCards => response(ErrorCode::NoDraftCards),
+            DraftError::NotYourPick => response(ErrorCode::NotYourPick),
+            DraftError::CardNotInPack(card) => {
+                response(ErrorCode::CardNotInPack).with("card_id", card)
+            }
+            DraftError::DraftComplete => response(ErrorCode::DraftComplete),
+        }
+    }
+}
+
+impl From<&SideboardError> for ErrorResponse {
+    fn from(error: &SideboardError) -> Self {
+        let response = |code| ErrorResponse::new(code, error.to_string());
+        match error {
+            SideboardError::NotInDeck(card) => response(ErrorCode::NotInDeck).with("card_id", card),
+            SideboardError::NotInSideboard(card) => {
+                response(ErrorCode::NotInSideboard).with("card_id", card)
+            }
+            SideboardError::DeckIllegal(_) => response(ErrorCode::SideboardedDeckIllegal),
+            SideboardError::AlreadySubmitted => response(ErrorCode::SwapsAlreadySubmitted),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_carry_a_code_and_their_context() {
+        let error = GameLogicError::WrongZone("creature".to_string(), "artifact:1".to_string());
+        let response = ErrorResponse::from(&error);
+        assert_eq!(ErrorCode::WrongZone, response.code);
+        assert_eq!(error.to_string(), response.message);
+        assert_eq!("artifact:1", response.context["position"]);
+
+        let batch = GameLogicError::BatchActionFailed(
+            2,
+            Box::new(GameLogicError::CardNotOnBoard("c7".to_string())),
+        );
+        let response = ErrorResponse::from(&batch);
+        assert_eq!(ErrorCode::BatchActionFailed, response.code);
+        assert_eq!("2", response.context["index"]);
+        assert_eq!("301", response.context["cause"]);
+        assert_eq!("c7", response.context["card_id"]);
+
+        let request = Packet::new(HeaderType::PlayCard, b"");
+        let packet = response.reply(&request, HeaderType::ActionRejected);
+        let payload: serde_cbor::Value = serde_cbor::from_slice(&packet.payload).unwrap();
+        let serde_cbor::Value::Map(payload) = payload else {
+            panic!("ErrorResponse is not a map");
+        };
+        let code = &payload[&serde_cbor::Value::Text(String::from("code"))];
+        assert_eq!(&serde_cbor::Value::Integer(501), code);
+    }
+
+    #[test]
+    fn test_clients_without_error_codes_get_the_message() {
+        let mut request = Packet::new(HeaderType::PlayCard, b"");
+        request.header.sequence = 7;
+        let response = ErrorResponse::from(&GameLogicError::NotPlayerTurn);
+        let packet = response.reply(&request, HeaderType::ActionRejected);
+
+        let plain = ErrorResponse::plain(&packet).unwrap();
+        assert_eq!(HeaderType::ActionRejected, plain.header.header_type);
+        assert_eq!(7, plain.header.sequence);
+        assert_eq!(response.message.as_bytes(), &plain.payload[..]);
+
+        let state = Packet::new(HeaderType::GameState, &packet.payload);
+        assert!(ErrorResponse::plain(&state).is_none());
+        let text = Packet::new(HeaderType::ERROR, b"already text");
+        assert!(ErrorResponse::plain(&text).is_none());
+    }
+}
@@ -0,0 +1,273 @@
+use crate::tcp::encoding::PayloadEncoding;
+use crate::tcp::error_response::ErrorResponse;
+use crate::tcp::packet::Packet;
+use crate::utils::checksum::ChecksumKind;
+use crate::utils::compression::Compression;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// The protocol version spoken by this server.
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// The oldest protocol version this server still accepts.
+pub const MIN_PROTOCOL_VERSION: u16 = 1;
+
+/// The protocol spoken by clients that predate the handshake; served through `compat`.
+pub const LEGACY_PROTOCOL_VERSION: u16 = 1;
+
+/// The newest schema version of the request payloads this server decodes.
+pub const PAYLOAD_VERSION: u8 = 1;
+
+/// The oldest schema version of the request payloads this server still decodes.
+pub const MIN_PAYLOAD_VERSION: u8 = 1;
+
+/// Optional protocol features, negotiated as a bit set.
+pub const FEATURE_PROMPTS: u32 = 1 << 0;
+pub const FEATURE_ACTION_BATCH: u32 = 1 << 1;
+pub const FEATURE_MATCH_START: u32 = 1 << 2;
+/// Error packets carry an `ErrorResponse` instead of a text message.
+pub const FEATURE_ERROR_CODES: u32 = 1 << 3;
+
+/// Every feature this server supports.
+pub const SERVER_FEATURES: u32 =
+    FEATURE_PROMPTS | FEATURE_ACTION_BATCH | FEATURE_MATCH_START | FEATURE_ERROR_CODES;
+
+/// Sent by the client in a `Handshake` packet before `Connect` or `Reconnect`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct HandshakeRequest {
+    pub version: u16,
+    pub features: u32,
+    /// The checksum algorithms the client implements. Empty means CRC32C only.
+    #[serde(default)]
+    pub checksums: Vec<ChecksumKind>,
+    /// The payload compression algorithms the client implements.
+    #[serde(default)]
+    pub compression: Vec<Compression>,
+    /// The encoding the client wants its payloads in; JSON is only granted when `JSON_PAYLOADS`
+    /// is set.
+    #[serde(default)]
+    pub encoding: PayloadEncoding,
+}
+
+#[cfg(feature = "game")]
+impl crate::tcp::payload::VersionedRequest for HandshakeRequest {}
+
+/// Sent back in a `Handshake` packet on success, or in a `VersionMismatch` packet on failure.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct HandshakeResponse {
+    pub version: u16,
+    pub min_version: u16,
+    pub features: u32,
+    pub checksum: ChecksumKind,
+    pub compression: Compression,
+    /// The newest request payload schema the server decodes, see `payload::decode`.
+    #[serde(default = "default_payload_version")]
+    pub payload_version: u8,
+    #[serde(default = "default_payload_version")]
+    pub min_payload_version: u8,
+    #[serde(default)]
+    pub encoding: PayloadEncoding,
+}
+
+fn default_payload_version() -> u8 {
+    MIN_PAYLOAD_VERSION
+}
+
+/// The protocol version and features agreed with a client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NegotiatedProtocol {
+    pub version: u16,
+    pub features: u32,
+    pub checksum: ChecksumKind,
+    pub compression: Compression,
+    pub encoding: PayloadEncoding,
+}
+
+impl NegotiatedProtocol {
+    /// The protocol assumed for clients that connect without a handshake.
+    pub fn legacy() -> Self {
+        Self {
+            version: LEGACY_PROTOCOL_VERSION,
+            features: 0,
+            checksum: ChecksumKind::Xor,
+            compression: Compression::None,
+            encoding: PayloadEncoding::Cbor,
+        }
+    }
+
+    pub fn supports(&self, feature: u32) -> bool {
+        self.features & feature == feature
+    }
+
+    /// Returns a packet as it is sent to this client, before it is sealed: error packets carry
+    /// their message alone unless error codes were negotiated, and structured payloads are in the
+    /// negotiated encoding.
+    pub fn outgoing<'a>(&self, packet: &'a Packet) -> Cow<'a, Packet> {
+        if !self.supports(FEATURE_ERROR_CODES) {
+            if let Some(plain) = ErrorResponse::plain(packet) {
+                return Cow::Owned(plain);
+            }
+        }
+        self.encoding.outgoing(packet)
+    }
+}
+
+/// Describes what this server supports, sent to clients in a `VersionMismatch` packet.
+pub fn supported() -> HandshakeResponse {
+    HandshakeResponse {
+        version: PROTOCOL_VERSION,
+        min_version: MIN_PROTOCOL_VERSION,
+        features: SERVER_FEATURES,
+        checksum: ChecksumKind::default(),
+        compression: Compression::Zstd,
+        payload_version: PAYLOAD_VERSION,
+        min_payload_version: MIN_PAYLOAD_VERSION,
+        encoding: PayloadEncoding::Cbor,
+    }
+}
+
+/// Agrees on a protocol version and feature set with a client.
+///
+/// The version used is the lowest of both sides, and the features are the ones both sides support.
+/// Legacy clients never get optional features, whatever they ask for. The strongest checksum both
+/// sides implement is picked, XOR only being considered when `allow_xor` is set. Zstd is preferred
+/// over deflate for game state compression.
+///
+/// # Arguments
+/// * `request` - The handshake sent by the client.
+/// * `allow_xor` - Whether the legacy XOR checksum is still accepted.
+///
+/// # Returns
+/// * `Ok(NegotiatedProtocol)` - If the client's version is supported.
+/// * `Err(HandshakeResponse)` - The server's supported range, to be sent in a `VersionMismatch` packet.
+pub fn negotiate(
+    request: &HandshakeRequest,
+    allow_xor: bool,
+) -> Result<NegotiatedProtocol, HandshakeResponse> {
+    let rejection = supported();
+    if request.version < MIN_PROTOCOL_VERSION {
+        return Err(rejection);
+    }
+
+    let version = request.version.min(PROTOCOL_VERSION);
+    if version <= LEGACY_PROTOCOL_VERSION {
+        return match allow_xor {
+            true => Ok(NegotiatedProtocol::legacy()),
+            false => Err(rejection),
+        };
+    }
+
+    let offered = |kind: ChecksumKind| request.checksums.contains(&kind);
+    let checksum = if offered(ChecksumKind::HmacSha256) {
+        ChecksumKind::HmacSha256
+    } else if request.checksums.is_empty() || offered(ChecksumKind::Crc32c) {
+        ChecksumKind::Crc32c
+    } else if allow_xor && offered(ChecksumKind::Xor) {
+        ChecksumKind::Xor
+    } else {
+        return Err(rejection);
+    };
+
+    let compression = [Compression::Zstd, Compression::Deflate]
+        .into_iter()
+        .find(|c| request.compression.contains(c))
+        .unwrap_or_default();
+
+    Ok(NegotiatedProtocol {
+        version,
+        checksum,
+        compression,
+        features: request.features & SERVER_FEATURES,
+        encoding: request.encoding,
+    })
+}
+
+impl From<NegotiatedProtocol> for HandshakeResponse {
+    fn from(negotiated: NegotiatedProtocol) -> Self {
+        HandshakeResponse {
+            version: negotiated.version,
+            min_version: MIN_PROTOCOL_VERSION,
+            features: negotiated.features,
+            checksum: negotiated.checksum,
+            compression: negotiated.compression,
+            payload_version: PAYLOAD_VERSION,
+            min_payload_version: MIN_PAYLOAD_VERSION,
+            encoding: negotiated.encoding,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiation_keeps_common_features() {
+        let request = HandshakeRequest {
+            version: PROTOCOL_VERSION + 3,
+            features: FEATURE_PROMPTS | 1 << 20,
+            checksums: vec![],
+            compression: vec![],
+            encoding: PayloadEncoding::Cbor,
+        };
+        let negotiated = negotiate(&request, true).unwrap();
+
+        assert_eq!(PROTOCOL_VERSION, negotiated.version);
+        assert!(negotiated.supports(FEATURE_PROMPTS));
+        assert!(!negotiated.supports(FEATURE_ACTION_BATCH));
+        assert!(!negotiated.supports(1 << 20));
+        assert_eq!(ChecksumKind::Crc32c, negotiated.checksum);
+        assert_eq!(Compression::None, negotiated.compression);
+    }
+
+    #[test]
+    fn test_legacy_client_gets_no_optional_features() {
+        let request = HandshakeRequest {
+            version: LEGACY_PROTOCOL_VERSION,
+            features: SERVER_FEATURES,
+            checksums: vec![],
+            compression: vec![],
+            encoding: PayloadEncoding::Json,
+        };
+        assert_eq!(
+            NegotiatedProtocol::legacy(),
+            negotiate(&request, true).unwrap()
+        );
+        assert!(negotiate(&request, false).is_err());
+    }
+
+    #[test]
+    fn test_strongest_common_checksum_is_picked() {
+        let mut request = HandshakeRequest {
+            version: PROTOCOL_VERSION,
+            features: 0,
+            checksums: vec![ChecksumKind::Xor, ChecksumKind::HmacSha256],
+            compression: vec![Compression::Deflate],
+            encoding: PayloadEncoding::Json,
+        };
+        let negotiated = negotiate(&request, true).unwrap();
+        assert_eq!(ChecksumKind::HmacSha256, negotiated.checksum);
+        assert_eq!(Compression::Deflate, negotiated.compression);
+        assert_eq!(PayloadEncoding::Json, negotiated.encoding);
+
+        request.checksums = vec![ChecksumKind::Xor];
+        assert_eq!(
+            ChecksumKind::Xor,
+            negotiate(&request, true).unwrap().checksum
+        );
+        assert!(negotiate(&request, false).is_err());
+    }
+
+    #[test]
+    fn test_outdated_client_is_rejected() {
+        let request = HandshakeRequest {
+            version: MIN_PROTOCOL_VERSION - 1,
+            features: SERVER_FEATURES,
+            checksums: vec![],
+            compression: vec![],
+            encoding: PayloadEncoding::Cbor,
+        };
+        let rejection = negotiate(&request, true).unwrap_err();
+        assert_eq!(MIN_PROTOCOL_VERSION, rejection.min_version);
+    }
+}
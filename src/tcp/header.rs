@@ -1,25 +1,95 @@
-use crate::utils::checksum::Checksum;
+use crate::utils::checksum::{Checksum, Crc32cChecksum};
+use crate::utils::compression::{Compression, COMPRESSION_MASK};
 use crate::utils::errors::ProtocolError;
 use std::fmt::Display;
 
+/// Size in bytes of a serialized `Header`.
+pub const HEADER_SIZE: usize = 13;
+
+/// Largest payload accepted in a single packet.
+pub const MAX_PAYLOAD_SIZE: u32 = 4 * 1024 * 1024;
+
 /// Represents the type of message in a protocol packet.
 ///
 /// Each variant maps to a specific `u8` value used during transmission.
 ///
 /// # Variants
 ///
-/// ## General (0x00–0x03):
+/// ## General (0x00–0x0E):
 /// - `Disconnect` - Client is disconnecting.
 /// - `Connect` - Client is initiating a connection.
-/// - `Ping` - Client is sending a ping to the server.
+/// - `Ping` - Keepalive probe, sent by either side.
+/// - `Pong` - Answer to a `Ping`.
 /// - `Reconnect` - Client is attempting to reconnect.
+/// - `Handshake` - Protocol version and feature negotiation, sent before `Connect`/`Reconnect`.
+/// - `Spectate` - Client is joining the match as a spectator.
+/// - `ConfirmStake` - Player acknowledges the stake of a wagered match.
+/// - `Ready` - Player is ready for the match to start.
+/// - `MatchStart` - Server is starting the match once every player is ready.
+/// - `MatchCountdown` - Server is counting down to the scheduled start of the match.
+/// - `ResyncRequest` - Client is asking for a full snapshot of the match, usually after reconnecting.
+/// - `ResyncResponse` - Server is sending the snapshot, with the current turn and its timer.
+/// - `ConnectAck` - Server accepted a `Connect` or `Reconnect`, with a session token and the initial view of the match.
+/// - `LobbyStatus` - Server is telling a client waiting in the lobby how many players wait and are ready.
 ///
 /// ## Game State (0x10):
 /// - `GameState` - Server is sending the current game state.
 ///
-/// ## Actions (0x11–0x12):
+/// ## Actions (0x11–0x19):
 /// - `PlayCard` - Client is playing a card.
 /// - `AttackPlayer` - Client is attacking another player.
+/// - `InitServer` - Matchmaker is initializing the match.
+/// - `ActionBatch` - Client is submitting several actions to resolve atomically.
+/// - `ActivateAbility` - Client is activating the ability of a card on its board.
+/// - `RequestUndo` - Client is asking to take back its last play, pending the opponent's consent.
+/// - `DeclareAttackers` - Client is attacking with creatures; sent by the server to open the blockers window.
+/// - `DeclareBlockers` - Client is blocking the declared attackers.
+/// - `EndTurn` - Client is ending its turn.
+///
+/// ## Action Responses (0x20–0x21):
+/// - `ActionAccepted` - The action identified by the header sequence was applied.
+/// - `ActionRejected` - The action identified by the header sequence was refused.
+///
+/// ## Prompts (0x30–0x31):
+/// - `PromptRequest` - Server is asking the player to make a choice.
+/// - `PromptResponse` - Client is answering a prompt.
+///
+/// ## History (0x40–0x43):
+/// - `GetHistory` - Client is asking for the recent events of the match.
+/// - `History` - Server is sending the requested events.
+/// - `GetReplay` - Client is asking for the replay of a match it may watch.
+/// - `Replay` - Server is sending the requested replay.
+///
+/// ## Notices (0x50–0x54):
+/// - `ScriptSkipped` - Server skipped a card script blocked by operators.
+/// - `TurnStarted` - Server started the turn of a player.
+/// - `MatchEnded` - Server ended the match, naming the winner if any.
+/// - `OpponentDisconnected` - The opponent dropped and forfeits unless they reconnect in time.
+/// - `OpponentReconnected` - The opponent is back.
+///
+/// ## Admin (0x60–0x61):
+/// - `AdminCommand` - Operator tool is running a signed admin command.
+/// - `AdminResponse` - Server is answering an admin command.
+///
+/// ## Social (0x70):
+/// - `Emote` - Client is sending an emote; relayed by the server to the opponent and spectators.
+/// - `Chat` - Client is sending a chat message; relayed by the server to the opponent, and to the
+///   spectators when `CHAT_TO_SPECTATORS` is set.
+/// - `MuteChat` - Client is muting or unmuting the chat of another player.
+///
+/// ## Private Matches (0x80–0x82):
+/// - `CreatePrivateMatch` - Client is opening a private match to play with a friend.
+/// - `PrivateMatchCreated` - Server is sending the join code of the private match.
+/// - `JoinPrivateMatch` - Client is joining a private match with its join code.
+///
+/// ## Draft (0x90–0x92):
+/// - `DraftPack` - Server is sending the open pack of the draft and whose pick it is.
+/// - `DraftPick` - Client is picking a card of the open pack.
+/// - `DraftComplete` - Server is sending the deck the player drafted, before connecting them to the match.
+///
+/// ## Sideboard (0xA0–0xA1):
+/// - `Sideboard` - Server is sending the deck and sideboard of the player before a game of a series.
+/// - `SideboardRequest` - Client is swapping cards between its deck and its sideboard.
 ///
 /// ## Errors (0xFA–0xFF):
 /// - `InvalidHeader` - Malformed or unrecognized header.
@@ -28,6 +98,9 @@ use std::fmt::Display;
 /// - `InvalidChecksum` - Payload failed checksum validation.
 /// - `FailedToConnectPlayer` - Server failed to connect the player.
 /// - `InvalidPacketPayload` - Packet payload is invalid.
+/// - `VersionMismatch` - Client protocol version is not supported.
+/// - `ConnectionRejected` - Server refused the connection, with a reason and a retry hint.
+/// - `DeckIllegal` - Matchmaker is told the match was not initialized because of an illegal deck.
 /// - `ERROR` - Generic error.
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq)]
@@ -36,12 +109,64 @@ pub enum HeaderType {
     Connect = 0x01,
     Ping = 0x02,
     Reconnect = 0x03,
-    
+    Handshake = 0x04,
+    Pong = 0x05,
+    Spectate = 0x06,
+    ConfirmStake = 0x07,
+    Ready = 0x08,
+    MatchStart = 0x09,
+    MatchCountdown = 0x0A,
+    ResyncRequest = 0x0B,
+    ResyncResponse = 0x0C,
+    ConnectAck = 0x0D,
+    LobbyStatus = 0x0E,
+
     GameState = 0x10,
 
     PlayCard = 0x11,
     AttackPlayer = 0x12,
     InitServer = 0x13,
+    ActionBatch = 0x14,
+    ActivateAbility = 0x15,
+    RequestUndo = 0x16,
+    DeclareAttackers = 0x17,
+    DeclareBlockers = 0x18,
+    EndTurn = 0x19,
+
+    ActionAccepted = 0x20,
+    ActionRejected = 0x21,
+
+    PromptRequest = 0x30,
+    PromptResponse = 0x31,
+
+    GetHistory = 0x40,
+    History = 0x41,
+    GetReplay = 0x42,
+    Replay = 0x43,
+
+    ScriptSkipped = 0x50,
+    TurnStarted = 0x51,
+    MatchEnded = 0x52,
+    OpponentDisconnected = 0x53,
+    OpponentReconnected = 0x54,
+
+    AdminCommand = 0x60,
+    AdminResponse = 0x61,
+
+    Emote = 0x70,
+    Chat = 0x71,
+    MuteChat = 0x72,
+
+    CreatePrivateMatch = 0x80,
+    PrivateMatchCreated = 0x81,
+    JoinPrivateMatch = 0x82,
+
+    DraftPack = 0x90,
+    DraftPick = 0x91,
+    DraftComplete = 0x92,
+
+    Sideboard = 0xA0,
+    SideboardRequest = 0xA1,
 
     InvalidHeader = 0xFA,
     AlreadyConnected = 0xFB,
@@ -49,6 +174,9 @@ pub enum HeaderType {
     InvalidChecksum = 0xFD,
     FailedToConnectPlayer = 0xF0,
     InvalidPacketPayload = 0xF1,
+    VersionMismatch = 0xF2,
+    ConnectionRejected = 0xF3,
+    DeckIllegal = 0xF4,
     ERROR = 0xFE,
 }
 
@@ -66,6 +194,17 @@ impl Display for HeaderType {
             HeaderType::Connect => String::from("CONNECT"),
             HeaderType::Reconnect => String::from("RECONNECT"),
             HeaderType::Ping => String::from("PING"),
+            HeaderType::Pong => String::from("PONG"),
+            HeaderType::Handshake => String::from("HANDSHAKE"),
+            HeaderType::Spectate => String::from("SPECTATE"),
+            HeaderType::ConfirmStake => String::from("CONFIRM_STAKE"),
+            HeaderType::Ready => String::from("READY"),
+            HeaderType::MatchStart => String::from("MATCH_START"),
+            HeaderType::MatchCountdown => String::from("MATCH_COUNTDOWN"),
+            HeaderType::ResyncRequest => String::from("RESYNC_REQUEST"),
+            HeaderType::ResyncResponse => String::from("RESYNC_RESPONSE"),
+            HeaderType::ConnectAck => String::from("CONNECT_ACK"),
+            HeaderType::LobbyStatus => String::from("LOBBY_STATUS"),
 
             HeaderType::PlayCard => String::from("PLAY_CARD"),
             HeaderType::AttackPlayer => String::from("ATTACK_PLAYER"),
@@ -76,8 +215,52 @@ impl Display for HeaderType {
             HeaderType::InvalidChecksum => String::from("INVALID_CHECKSUM"),
             HeaderType::FailedToConnectPlayer => String::from("FAILED_TO_CONNECT_PLAYER"),
             HeaderType::InvalidPacketPayload => String::from("INVALID_PACKET_PAYLOAD"),
+            HeaderType::VersionMismatch => String::from("VERSION_MISMATCH"),
+            HeaderType::ConnectionRejected => String::from("CONNECTION_REJECTED"),
+            HeaderType::DeckIllegal => String::from("DECK_ILLEGAL"),
             HeaderType::ERROR => String::from("ERROR"),
             HeaderType::InitServer => String::from("INIT_SERVER"),
+            HeaderType::ActionBatch => String::from("ACTION_BATCH"),
+            HeaderType::ActivateAbility => String::from("ACTIVATE_ABILITY"),
+            HeaderType::RequestUndo => String::from("REQUEST_UNDO"),
+            HeaderType::DeclareAttackers => String::from("DECLARE_ATTACKERS"),
+            HeaderType::DeclareBlockers => String::from("DECLARE_BLOCKERS"),
+            HeaderType::EndTurn => String::from("END_TURN"),
+
+            HeaderType::ActionAccepted => String::from("ACTION_ACCEPTED"),
+            HeaderType::ActionRejected => String::from("ACTION_REJECTED"),
+
+            HeaderType::PromptRequest => String::from("PROMPT_REQUEST"),
+            HeaderType::PromptResponse => String::from("PROMPT_RESPONSE"),
+
+            HeaderType::GetHistory => String::from("GET_HISTORY"),
+            HeaderType::History => String::from("HISTORY"),
+            HeaderType::GetReplay => String::from("GET_REPLAY"),
+            HeaderType::Replay => String::from("REPLAY"),
+
+            HeaderType::ScriptSkipped => String::from("SCRIPT_SKIPPED"),
+            HeaderType::TurnStarted => String::from("TURN_STARTED"),
+            HeaderType::MatchEnded => String::from("MATCH_ENDED"),
+            HeaderType::OpponentDisconnected => String::from("OPPONENT_DISCONNECTED"),
+            HeaderType::OpponentReconnected => String::from("OPPONENT_RECONNECTED"),
+
+            HeaderType::AdminCommand => String::from("ADMIN_COMMAND"),
+            HeaderType::AdminResponse => String::from("ADMIN_RESPONSE"),
+
+            HeaderType::Emote => String::from("EMOTE"),
+            HeaderType::Chat => String::from("CHAT"),
+            HeaderType::MuteChat => String::from("MUTE_CHAT"),
+
+            HeaderType::CreatePrivateMatch => String::from("CREATE_PRIVATE_MATCH"),
+            HeaderType::PrivateMatchCreated => String::from("PRIVATE_MATCH_CREATED"),
+            HeaderType::JoinPrivateMatch => String::from("JOIN_PRIVATE_MATCH"),
+
+            HeaderType::DraftPack => String::from("DRAFT_PACK"),
+            HeaderType::DraftPick => String::from("DRAFT_PICK"),
+            HeaderType::DraftComplete => String::from("DRAFT_COMPLETE"),
+
+            HeaderType::Sideboard => String::from("SIDEBOARD"),
+            HeaderType::SideboardRequest => String::from("SIDEBOARD_REQUEST"),
 
             HeaderType::GameState => String::from("GAME_STATE"),
         };
@@ -105,11 +288,63 @@ impl TryFrom<u8> for HeaderType {
             0x01 => Ok(HeaderType::Connect),
             0x02 => Ok(HeaderType::Ping),
             0x03 => Ok(HeaderType::Reconnect),
+            0x04 => Ok(HeaderType::Handshake),
+            0x05 => Ok(HeaderType::Pong),
+            0x06 => Ok(HeaderType::Spectate),
+            0x07 => Ok(HeaderType::ConfirmStake),
+            0x08 => Ok(HeaderType::Ready),
+            0x09 => Ok(HeaderType::MatchStart),
+            0x0A => Ok(HeaderType::MatchCountdown),
+            0x0B => Ok(HeaderType::ResyncRequest),
+            0x0C => Ok(HeaderType::ResyncResponse),
+            0x0D => Ok(HeaderType::ConnectAck),
+            0x0E => Ok(HeaderType::LobbyStatus),
 
             0x10 => Ok(HeaderType::GameState),
             0x11 => Ok(HeaderType::PlayCard),
             0x12 => Ok(HeaderType::AttackPlayer),
             0x13 => Ok(HeaderType::InitServer),
+            0x14 => Ok(HeaderType::ActionBatch),
+            0x15 => Ok(HeaderType::ActivateAbility),
+            0x16 => Ok(HeaderType::RequestUndo),
+            0x17 => Ok(HeaderType::DeclareAttackers),
+            0x18 => Ok(HeaderType::DeclareBlockers),
+            0x19 => Ok(HeaderType::EndTurn),
+
+            0x20 => Ok(HeaderType::ActionAccepted),
+            0x21 => Ok(HeaderType::ActionRejected),
+
+            0x30 => Ok(HeaderType::PromptRequest),
+            0x31 => Ok(HeaderType::PromptResponse),
+
+            0x40 => Ok(HeaderType::GetHistory),
+            0x41 => Ok(HeaderType::History),
+            0x42 => Ok(HeaderType::GetReplay),
+            0x43 => Ok(HeaderType::Replay),
+
+            0x50 => Ok(HeaderType::ScriptSkipped),
+            0x51 => Ok(HeaderType::TurnStarted),
+            0x52 => Ok(HeaderType::MatchEnded),
+            0x53 => Ok(HeaderType::OpponentDisconnected),
+            0x54 => Ok(HeaderType::OpponentReconnected),
+
+            0x60 => Ok(HeaderType::AdminCommand),
+            0x61 => Ok(HeaderType::AdminResponse),
+
+            0x70 => Ok(HeaderType::Emote),
+            0x71 => Ok(HeaderType::Chat),
+            0x72 => Ok(HeaderType::MuteChat),
+
+            0x80 => Ok(HeaderType::CreatePrivateMatch),
+            0x81 => Ok(HeaderType::PrivateMatchCreated),
+            0x82 => Ok(HeaderType::JoinPrivateMatch),
+
+            0x90 => Ok(HeaderType::DraftPack),
+            0x91 => Ok(HeaderType::DraftPick),
+            0x92 => Ok(HeaderType::DraftComplete),
+
+            0xA0 => Ok(HeaderType::Sideboard),
+            0xA1 => Ok(HeaderType::SideboardRequest),
 
             0xFA => Ok(HeaderType::InvalidHeader),
             0xFB => Ok(HeaderType::AlreadyConnected),
@@ -117,6 +352,9 @@ impl TryFrom<u8> for HeaderType {
             0xFD => Ok(HeaderType::InvalidChecksum),
             0xF0 => Ok(HeaderType::FailedToConnectPlayer),
             0xF1 => Ok(HeaderType::InvalidPacketPayload),
+            0xF2 => Ok(HeaderType::VersionMismatch),
+            0xF3 => Ok(HeaderType::ConnectionRejected),
+            0xF4 => Ok(HeaderType::DeckIllegal),
             0xFE => Ok(HeaderType::ERROR),
             _ => Err(()),
         }
@@ -125,19 +363,23 @@ impl TryFrom<u8> for HeaderType {
 
 /// Represents a fixed-size protocol header for game packet transmission.
 ///
-/// Contains the message type, payload length, and a checksum for validation.
-/// Serialized as 6 bytes total when sent over the network.
+/// Contains the message type, flags (such as the payload compression), payload length, a checksum
+/// for validation and a sequence number used to correlate responses with the request that caused them.
+/// Serialized as 13 bytes total when sent over the network.
 #[derive(Clone)]
 pub struct Header {
-    pub checksum: i16,
-    pub payload_length: i16,
+    pub checksum: u32,
+    pub payload_length: u32,
     pub header_type: HeaderType,
+    pub sequence: u16,
+    pub flags: u8,
 }
 
 impl Header {
     /// Creates a new `PacketHeader` from the given message type and payload.
     ///
-    /// Calculates the checksum and payload length automatically.
+    /// Calculates the checksum (CRC32C) and payload length automatically. Packets sent to a client
+    /// are re-sealed with the checksum negotiated with it.
     ///
     /// # Arguments
     /// - `header_type`: The type of the message (e.g., `Connect`, `Disconnect`).
@@ -147,36 +389,47 @@ impl Header {
     /// A new `Header` instance with the calculated checksum and payload length.
     pub fn new(header_type: HeaderType, payload: &[u8]) -> Self {
         Self {
-            checksum: Checksum::new(payload) as i16,
-            payload_length: payload.len() as i16,
+            checksum: Crc32cChecksum.compute(payload),
+            payload_length: payload.len() as u32,
             header_type,
+            sequence: 0,
+            flags: 0,
         }
     }
 
     /// Serializes the header into a fixed-size byte array.
     ///
-    /// Format: `[type, payload_len (2 bytes), checksum (2 bytes), 0x0A]`.
+    /// Format: `[type, flags, payload_len (4 bytes), checksum (4 bytes), sequence (2 bytes), 0x0A]`.
     ///
     /// # Returns
     /// A boxed array of bytes representing the serialized header.
     pub fn wrap_header(&self) -> Box<[u8]> {
-        let checksum: u16 = self.checksum as u16;
-        let payload_length: u16 = self.payload_length as u16;
+        let checksum = self.checksum.to_be_bytes();
+        let payload_length = self.payload_length.to_be_bytes();
         let header_type: u8 = self.header_type.to_owned() as u8;
+        let sequence = self.sequence.to_be_bytes();
 
         Box::new([
             header_type,
-            ((payload_length >> 8) & 0xFF) as u8,
-            (payload_length & 0xFF) as u8,
-            ((checksum >> 8) & 0xFF) as u8,
-            (checksum & 0xFF) as u8,
+            self.flags,
+            payload_length[0],
+            payload_length[1],
+            payload_length[2],
+            payload_length[3],
+            checksum[0],
+            checksum[1],
+            checksum[2],
+            checksum[3],
+            sequence[0],
+            sequence[1],
             0x0A,
         ])
     }
 
     /// Parses a `PacketHeader` from a byte slice.
     ///
-    /// Validates the format and extracts the header fields.
+    /// Validates the format, the declared payload length and the flags, and extracts the header
+    /// fields.
     ///
     /// # Arguments
     /// - `bytes`: A byte slice containing the serialized header.
@@ -185,7 +438,7 @@ impl Header {
     /// - `Ok(Header)`: If the byte slice is valid and contains a recognizable header.
     /// - `Err(ProtocolError)`: If the byte slice is invalid or has an unrecognized type.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProtocolError> {
-        if bytes.len() != 6 || bytes[5] != 0x0A {
+        if bytes.len() != HEADER_SIZE || bytes[HEADER_SIZE - 1] != 0x0A {
             return Err(ProtocolError::InvalidHeaderError(format!(
                 "Format invalid: {:?}",
                 bytes
@@ -197,15 +450,93 @@ impl Header {
                 "Invalid message type.".to_string(),
             )),
             Ok(header_type) => {
-                let checksum: i16 = u16::from_be_bytes([bytes[3], bytes[4]]) as i16;
-                let payload_length: i16 = u16::from_be_bytes([bytes[1], bytes[2]]) as i16;
+                let flags = bytes[1];
+                let payload_length = u32::from_be_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]);
+                let checksum = u32::from_be_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]);
+                let sequence: u16 = u16::from_be_bytes([bytes[10], bytes[11]]);
+
+                if payload_length > MAX_PAYLOAD_SIZE {
+                    return Err(ProtocolError::PayloadTooLarge(payload_length));
+                }
+                if flags & !COMPRESSION_MASK != 0 {
+                    return Err(ProtocolError::InvalidHeaderError(format!(
+                        "Unknown flags: {flags:#04x}"
+                    )));
+                }
+                Compression::from_flags(flags)?;
 
                 Ok(Self {
                     header_type,
                     payload_length,
                     checksum,
+                    sequence,
+                    flags,
                 })
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_round_trip_keeps_sequence() {
+        let mut header = Header::new(HeaderType::PlayCard, b"payload");
+        header.sequence = 0xBEEF;
+        let parsed = Header::from_bytes(&header.wrap_header()).unwrap();
+
+        assert_eq!(HeaderType::PlayCard, parsed.header_type);
+        assert_eq!(0xBEEF, parsed.sequence);
+        assert_eq!(7, parsed.payload_length);
+    }
+
+    #[test]
+    fn test_spectate_header_type() {
+        assert_eq!(Ok(HeaderType::Spectate), HeaderType::try_from(0x06));
+        assert_eq!("SPECTATE", HeaderType::Spectate.to_string());
+    }
+
+    #[test]
+    fn test_replay_header_types() {
+        assert_eq!(Ok(HeaderType::GetReplay), HeaderType::try_from(0x42));
+        assert_eq!("REPLAY", HeaderType::Replay.to_string());
+    }
+
+    #[test]
+    fn test_resync_header_types() {
+        assert_eq!(Ok(HeaderType::ResyncRequest), HeaderType::try_from(0x0B));
+        assert_eq!(Ok(HeaderType::ResyncResponse), HeaderType::try_from(0x0C));
+        assert_eq!("RESYNC_RESPONSE", HeaderType::ResyncResponse.to_string());
+    }
+
+    #[test]
+    fn test_header_rejects_missing_delimiter() {
+        let bytes = [
+            0x11, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00,
+        ];
+        assert!(Header::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_header_supports_large_payloads() {
+        let payload = vec![0xAB; 70_000];
+        let parsed =
+            Header::from_bytes(&Header::new(HeaderType::GameState, &payload).wrap_header());
+        assert_eq!(70_000, parsed.unwrap().payload_length);
+
+        let mut oversized = Header::new(HeaderType::GameState, b"").wrap_header();
+        oversized[2] = 0xFF;
+        assert!(Header::from_bytes(&oversized).is_err());
+    }
+
+    #[test]
+    fn test_header_rejects_unknown_flags() {
+        for flags in [0b0000_0011, 0b1000_0000] {
+            let mut bytes = Header::new(HeaderType::GameState, b"").wrap_header();
+            bytes[1] = flags;
+            assert!(Header::from_bytes(&bytes).is_err(), "{flags:#04x}");
+        }
+    }
+}
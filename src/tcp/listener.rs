@@ -0,0 +1,346 @@
+#[cfg(feature = "game")]
+use crate::models::settings::Settings;
+#[cfg(feature = "quic")]
+use crate::tcp::quic;
+#[cfg(feature = "tls")]
+use crate::tcp::tls;
+#[cfg(any(feature = "tls", feature = "quic"))]
+use crate::tcp::tls::TlsIdentity;
+use crate::tcp::transport::{ClientStream, Transport};
+use crate::{logger, utils::logger::Logger};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+#[cfg(test)]
+use std::sync::atomic::{AtomicU16, Ordering};
+#[cfg(test)]
+use tokio::io::DuplexStream;
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, Mutex};
+
+/// Address the server listens on when the settings name none.
+pub const DEFAULT_LISTEN_ADDRESS: SocketAddr =
+    SocketAddr::new(std::net::IpAddr::V4(Ipv4Addr::LOCALHOST), 8000);
+
+/// Connections waiting in the accept pipeline before the listeners stop accepting more.
+const ACCEPT_BACKLOG: usize = 64;
+
+/// Bytes an in-memory connection buffers each way before its writer waits for the reader.
+#[cfg(test)]
+const LOOPBACK_BUFFER: usize = 1024 * 1024;
+
+pub(crate) type Accepted = io::Result<(ClientStream, SocketAddr)>;
+
+/// How clients connect to the listeners, from the settings.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TransportOptions<'a> {
+    pub transport: Transport,         // Whether TCP listeners, QUIC endpoints or both are bound.
+    pub tls: bool,                    // Whether TCP clients are served over TLS.
+    pub certificate: Option<&'a str>, // PEM certificate chain served to TLS and QUIC clients.
+    pub private_key: Option<&'a str>, // PEM private key of the certificate.
+}
+
+impl TransportOptions<'_> {
+    /// Fails for the transports this build leaves out.
+    fn check_supported(&self) -> io::Result<()> {
+        let missing = if self.tls && cfg!(not(feature = "tls")) {
+            "tls"
+        } else if self.transport.quic() && cfg!(not(feature = "quic")) {
+            "quic"
+        } else {
+            return Ok(());
+        };
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("the {missing} transport needs a build with the `{missing}` feature"),
+        ))
+    }
+}
+
+/// Every socket the server listens on, feeding one accept pipeline.
+///
+/// Each listener accepts on its own task and hands its connections over a channel, so the server
+/// accepts from IPv4 and IPv6 addresses, on any number of interfaces and over every transport, as
+/// if from one socket.
+pub struct ListenerSet {
+    addresses: Vec<SocketAddr>,                // TCP addresses actually bound, with the ports picked by the OS.
+    quic_addresses: Vec<SocketAddr>,           // QUIC endpoints actually bound.
+    accepted: Mutex<mpsc::Receiver<Accepted>>, // Connections accepted by any of the listeners.
+    #[cfg(test)]
+    loopback: Option<mpsc::Sender<Accepted>>, // Keeps the pipeline of in-memory listeners open.
+}
+
+impl ListenerSet {
+    /// Binds every address and starts accepting on each of them.
+    ///
+    /// # Arguments
+    /// * `addresses` - The addresses to listen on; none binds the default address.
+    /// * `dual_stack` - Whether IPv6 listeners also accept IPv4 clients. When off, IPv4 and IPv6
+    ///   listeners may share a port.
+    ///
+    /// # Returns
+    /// An error if any of the addresses could not be bound.
+    pub fn bind(addresses: &[SocketAddr], dual_stack: bool) -> io::Result<Self> {
+        Self::bind_transport(addresses, dual_stack, &TransportOptions::default())
+    }
+
+    /// Binds every address over the chosen transports and starts accepting on each of them.
+    ///
+    /// # Returns
+    /// An error if any of the addresses could not be bound, or if a transport was asked of a
+    /// build without its feature.
+    pub fn bind_transport(
+        addresses: &[SocketAddr],
+        dual_stack: bool,
+        options: &TransportOptions,
+    ) -> io::Result<Self> {
+        options.check_supported()?;
+        let addresses = match addresses.is_empty() {
+            true => &[DEFAULT_LISTEN_ADDRESS][..],
+            false => addresses,
+        };
+        let could_not_bind = |address: &SocketAddr, error: io::Error| {
+            io::Error::new(error.kind(), format!("could not bind `{address}`: {error}"))
+        };
+
+        #[cfg(any(feature = "tls", feature = "quic"))]
+        let identity = match options.tls || options.transport.quic() {
+            true => Some(TlsIdentity::load(options.certificate, options.private_key)?),
+            false => None,
+        };
+        #[cfg(feature = "tls")]
+        let acceptor = match (&identity, options.tls) {
+            (Some(identity), true) => Some(identity.acceptor()?),
+            _ => None,
+        };
+
+        let mut listeners = Vec::with_capacity(addresses.len());
+        if options.transport.tcp() {
+            for address in addresses {
+                let listener = bind_one(*address, dual_stack)
+                    .map_err(|error| could_not_bind(address, error))?;
+                listeners.push(listener);
+            }
+        }
+
+        #[cfg(feature = "quic")]
+        let mut endpoints = Vec::with_capacity(addresses.len());
+        #[cfg(feature = "quic")]
+        if let (Some(identity), true) = (&identity, options.transport.quic()) {
+            for address in addresses {
+                let endpoint = quic::bind(*address, dual_stack, identity)
+                    .map_err(|error| could_not_bind(address, error))?;
+                endpoints.push(endpoint);
+            }
+        }
+
+        let (sender, receiver) = mpsc::channel(ACCEPT_BACKLOG);
+        let mut bound = Vec::with_capacity(listeners.len());
+        for listener in listeners {
+            bound.push(listener.local_addr()?);
+            #[cfg(feature = "tls")]
+            if let Some(acceptor) = &acceptor {
+                tokio::spawn(tls::accept_loop(listener, acceptor.clone(), sender.clone()));
+                continue;
+            }
+            tokio::spawn(accept_loop(listener, sender.clone()));
+        }
+        #[allow(unused_mut)]
+        let mut quic_bound = Vec::new();
+        #[cfg(feature = "quic")]
+        for endpoint in endpoints {
+            quic_bound.push(endpoint.local_addr()?);
+            tokio::spawn(quic::accept_loop(endpoint, sender.clone()));
+        }
+
+        Ok(Self {
+            addresses: bound,
+            quic_addresses: quic_bound,
+            accepted: Mutex::new(receiver),
+            #[cfg(test)]
+            loopback: None,
+        })
+    }
+
+    /// Listens on no socket: clients connect through the returned `Loopback`, over in-memory
+    /// pipes, so tests exercise the whole server without binding a port.
+    #[cfg(test)]
+    pub fn loopback() -> (Self, Loopback) {
+        let (sender, receiver) = mpsc::channel(ACCEPT_BACKLOG);
+        let listeners = Self {
+            addresses: Vec::new(),
+            quic_addresses: Vec::new(),
+            accepted: Mutex::new(receiver),
+            loopback: Some(sender.clone()),
+        };
+        let loopback = Loopback {
+            sender,
+            next_port: AtomicU16::new(1),
+        };
+        (listeners, loopback)
+    }
+
+    /// Binds the addresses listed in the settings, over the transports they select.
+    #[cfg(feature = "game")]
+    pub fn from_settings(settings: Option<&Settings>) -> io::Result<Self> {
+        match settings {
+            Some(settings) => Self::bind_transport(
+                &settings.listen_addresses,
+                settings.listen_dual_stack,
+                &TransportOptions {
+                    transport: settings.transport,
+                    tls: settings.tls,
+                    certificate: settings.tls_certificate.as_deref(),
+                    private_key: settings.tls_private_key.as_deref(),
+                },
+            ),
+            None => Self::bind(&[], false),
+        }
+    }
+
+    /// The TCP addresses the server listens on.
+    pub fn addresses(&self) -> &[SocketAddr] {
+        &self.addresses
+    }
+
+    /// The UDP addresses the server accepts QUIC clients on.
+    pub fn quic_addresses(&self) -> &[SocketAddr] {
+        &self.quic_addresses
+    }
+
+    /// Waits for a connection on any of the listeners.
+    pub async fn accept(&self) -> Accepted {
+        self.accepted
+            .lock()
+            .await
+            .recv()
+            .await
+            .unwrap_or_else(|| Err(io::Error::other("every listener stopped accepting")))
+    }
+}
+
+/// Connects clients to the in-memory listeners of `ListenerSet::loopback`.
+#[cfg(test)]
+pub struct Loopback {
+    sender: mpsc::Sender<Accepted>,
+    next_port: AtomicU16, // Tells the clients apart in the logs, as TCP ports would.
+}
+
+#[cfg(test)]
+impl Loopback {
+    /// Opens a connection to the listeners.
+    ///
+    /// # Returns
+    /// The end of the client, or an error if the listeners were dropped.
+    pub async fn connect(&self) -> io::Result<DuplexStream> {
+        let (client, server) = tokio::io::duplex(LOOPBACK_BUFFER);
+        let port = self.next_port.fetch_add(1, Ordering::Relaxed);
+        let address = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port);
+        self.sender
+            .send(Ok((ClientStream::new(server), address)))
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::ConnectionRefused))?;
+        Ok(client)
+    }
+}
+
+/// Binds one listener; IPv6 listeners only take IPv6 clients unless `dual_stack` is set.
+fn bind_one(address: SocketAddr, dual_stack: bool) -> io::Result<TcpListener> {
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    if address.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+    socket.listen(1024)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// Accepts connections on one listener until the accept pipeline is dropped.
+async fn accept_loop(listener: TcpListener, sender: mpsc::Sender<Accepted>) {
+    loop {
+        let accepted = listener
+            .accept()
+            .await
+            .map(|(stream, address)| (ClientStream::new(stream), address));
+        if sender.send(accepted).await.is_err() {
+            break;
+        }
+    }
+
+    if let Ok(address) = listener.local_addr() {
+        logger!(DEBUG, "[SERVER] Stopped accepting on `{address}`");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tcp::header::HeaderType;
+    use crate::tcp::packet::Packet;
+    use std::net::Ipv6Addr;
+    use tokio::net::TcpStream;
+
+    #[tokio::test]
+    async fn test_listeners_feed_one_pipeline() {
+        let addresses = [
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0),
+            SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 0),
+        ];
+        // Hosts without IPv6 still exercise the IPv4 listener.
+        let listeners = ListenerSet::bind(&addresses, false)
+            .or_else(|_| ListenerSet::bind(&addresses[..1], false))
+            .unwrap();
+
+        for address in listeners.addresses() {
+            let client = TcpStream::connect(address).await.unwrap();
+            let (connection, peer) = listeners.accept().await.unwrap();
+            assert_eq!(client.local_addr().unwrap(), peer);
+            assert!(connection.tcp_stream().is_some());
+        }
+        assert!(listeners.quic_addresses().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_loopback_clients_bind_no_port() {
+        let (listeners, loopback) = ListenerSet::loopback();
+        assert!(listeners.addresses().is_empty());
+
+        let mut client = loopback.connect().await.unwrap();
+        let (mut connection, first) = listeners.accept().await.unwrap();
+        assert!(connection.tcp_stream().is_none());
+        Packet::new(HeaderType::Ping, b"loopback")
+            .write_to(&mut client)
+            .await
+            .unwrap();
+        let ping = Packet::read_from(&mut connection).await.unwrap().unwrap();
+        assert_eq!(&b"loopback"[..], &ping.payload[..]);
+
+        loopback.connect().await.unwrap();
+        let (_, second) = listeners.accept().await.unwrap();
+        assert_ne!(first, second);
+
+        drop(listeners);
+        let error = loopback.connect().await.err().unwrap();
+        assert_eq!(io::ErrorKind::ConnectionRefused, error.kind());
+    }
+
+    #[cfg(not(feature = "quic"))]
+    #[tokio::test]
+    async fn test_quic_needs_the_quic_feature() {
+        let address = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0);
+        let options = TransportOptions {
+            transport: Transport::Both,
+            ..TransportOptions::default()
+        };
+        let error = ListenerSet::bind_transport(&[address], false, &options)
+            .err()
+            .unwrap();
+        assert_eq!(io::ErrorKind::Unsupported, error.kind());
+    }
+}
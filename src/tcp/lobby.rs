@@ -0,0 +1,690 @@
+use crate::game::entity::player::Player;
+use crate::models::client_requests::{ConnectionRequest, JoinPrivateMatchRequest};
+use crate::models::ids::{MatchId, PlayerId};
+use crate::models::init_server::{InitServerRequest, PreloadPlayer};
+use crate::tcp::client::TemporaryClient;
+use crate::tcp::compat::WireFormat;
+use crate::tcp::header::HeaderType;
+use crate::tcp::packet::Packet;
+use crate::tcp::payload;
+use crate::tcp::protocol::Protocol;
+use crate::tcp::rejection::Rejection;
+use crate::tcp::server::MatchHost;
+use crate::utils::errors::{LobbyError, PlayerConnectionError};
+use crate::{
+    logger,
+    utils::logger::{match_span, Logger},
+    SETTINGS,
+};
+use chrono::Utc;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tracing::Instrument;
+
+/// How many players a lobby match seats.
+const LOBBY_MATCH_PLAYERS: usize = 2;
+
+/// Characters of join codes, leaving out the ones easily mistaken for one another (`0`, `O`, `1`
+/// and `I`).
+const JOIN_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// Length of a join code.
+const JOIN_CODE_LENGTH: usize = 6;
+
+/// Payload of a `LobbyStatus` packet, sent to a client when it enters the lobby and when it gets
+/// ready.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LobbyStatus {
+    pub waiting: usize, // Players in the room of the client, the client included.
+    pub ready: usize,   // Players of the room who sent `Ready`.
+}
+
+/// Payload of a `PrivateMatchCreated` packet, answering `CreatePrivateMatch`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PrivateMatchCreated {
+    pub join_code: String, // Given by the player to the friend they want to play with.
+    pub expires_at: i64,   // Unix timestamp (milliseconds) after which the code is refused.
+}
+
+/// The match a player of the lobby was seated in, or why it could not be created.
+pub type Seat = Result<Arc<Protocol>, Rejection>;
+
+/// A player waiting in the lobby.
+struct LobbyEntry {
+    player_id: PlayerId,
+    deck_id: String,
+    ready: bool,
+    seat: oneshot::Sender<Seat>, // Takes the player to their match once it is hosted.
+}
+
+/// Where a player of the lobby waits for their opponent.
+#[derive(Debug, Clone, PartialEq)]
+enum Room {
+    Public,          // Paired with the first other player to get ready.
+    Private(String), // Paired with the player joining with this join code.
+}
+
+/// A private match waiting for its players.
+struct PrivateRoom {
+    players: Vec<LobbyEntry>,
+    expires_at: i64, // Unix timestamp (milliseconds) after which the join code is refused.
+}
+
+impl PrivateRoom {
+    fn expired(&self) -> bool {
+        Utc::now().timestamp_millis() >= self.expires_at
+    }
+}
+
+/// The players of the lobby, by room.
+#[derive(Default)]
+struct Rooms {
+    public: Vec<LobbyEntry>,               // In the order they entered.
+    private: HashMap<String, PrivateRoom>, // By join code.
+}
+
+impl Rooms {
+    fn contains(&self, player_id: &PlayerId) -> bool {
+        let private = self.private.values().flat_map(|room| room.players.iter());
+        self.public
+            .iter()
+            .chain(private)
+            .any(|entry| &entry.player_id == player_id)
+    }
+
+    fn players(&mut self, room: &Room) -> Option<&mut Vec<LobbyEntry>> {
+        match room {
+            Room::Public => Some(&mut self.public),
+            Room::Private(join_code) => self.private.get_mut(join_code).map(|r| &mut r.players),
+        }
+    }
+}
+
+/// Pairs the players who connect while no match seats them when `LOBBY_MODE` is set.
+///
+/// A player enters the lobby with their `Connect` request and sends `Ready` once they want to
+/// play. As soon as two players are ready, the server creates their match itself, as the
+/// matchmaker would with `InitServer`, then connects them to it with their `Connect` request and
+/// marks them ready, which starts the match.
+///
+/// Friends play together through a private match instead: one of them sends `CreatePrivateMatch`
+/// and gets a join code, valid for `PRIVATE_MATCH_TTL` seconds, that the other sends in
+/// `JoinPrivateMatch`. Their match is created once both of them are ready.
+#[derive(Default)]
+pub struct Lobby {
+    rooms: Mutex<Rooms>,
+}
+
+impl Lobby {
+    /// Whether players without a seat wait in the lobby instead of being rejected.
+    pub fn enabled() -> bool {
+        SETTINGS.get().is_some_and(|s| s.lobby_mode)
+    }
+
+    fn rooms(&self) -> MutexGuard<'_, Rooms> {
+        self.rooms.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Keeps a client in the lobby until it is seated in a match or leaves.
+    ///
+    /// # Arguments
+    /// * `client` - The client, which must have completed the handshake.
+    /// * `connect` - Its `Connect` request, used again to connect it to its match.
+    pub async fn join(&self, mut client: TemporaryClient, connect: Packet) {
+        let Some((entry, seated)) = Self::admit(&mut client, &connect).await else {
+            return;
+        };
+        let player_id = entry.player_id.clone();
+        if let Err(error) = self.enter(&Room::Public, entry) {
+            Self::refuse(&mut client, &connect, error).await;
+            return;
+        }
+
+        logger!(INFO, "[LOBBY] `{player_id}` is waiting for an opponent");
+        self.send_status(&mut client, &connect, &Room::Public).await;
+        self.wait(client, &connect, &player_id, Room::Public, seated)
+            .await;
+    }
+
+    /// Opens a private match for the player of a `CreatePrivateMatch` request, answering with its
+    /// join code, then keeps the client waiting in it.
+    ///
+    /// # Arguments
+    /// * `client` - The client, which must have completed the handshake.
+    /// * `request` - Its request, carrying the fields of a `Connect` request.
+    pub async fn create_private(&self, mut client: TemporaryClient, request: Packet) {
+        let Some((entry, seated)) = Self::admit(&mut client, &request).await else {
+            return;
+        };
+        let player_id = entry.player_id.clone();
+        let created = match self.open_private(entry) {
+            Ok(created) => created,
+            Err(error) => {
+                Self::refuse(&mut client, &request, error).await;
+                return;
+            }
+        };
+
+        let join_code = created.join_code.clone();
+        logger!(
+            INFO,
+            "[LOBBY] `{player_id}` opened the private match `{join_code}`"
+        );
+        if let Ok(payload) = serde_cbor::to_vec(&created) {
+            let packet = Packet::reply_to(&request, HeaderType::PrivateMatchCreated, &payload);
+            client.send(&packet).await;
+        }
+        let room = Room::Private(join_code);
+        self.wait(client, &request, &player_id, room, seated).await;
+    }
+
+    /// Seats the player of a `JoinPrivateMatch` request in the private match of its join code,
+    /// then keeps the client waiting in it.
+    ///
+    /// # Arguments
+    /// * `client` - The client, which must have completed the handshake.
+    /// * `request` - Its request, carrying the join code and the fields of a `Connect` request.
+    pub async fn join_private(&self, mut client: TemporaryClient, request: Packet) {
+        let join_code = payload::decode::<JoinPrivateMatchRequest>(
+            &request.header.header_type,
+            &request.payload,
+        )
+        .map(|request| request.join_code.trim().to_ascii_uppercase())
+        .unwrap_or_default();
+        let Some((entry, seated)) = Self::admit(&mut client, &request).await else {
+            return;
+        };
+        let player_id = entry.player_id.clone();
+        let room = Room::Private(join_code.clone());
+        if let Err(error) = self.enter(&room, entry) {
+            Self::refuse(&mut client, &request, error).await;
+            return;
+        }
+
+        logger!(
+            INFO,
+            "[LOBBY] `{player_id}` joined the private match `{join_code}`"
+        );
+        self.send_status(&mut client, &request, &room).await;
+        self.wait(client, &request, &player_id, room, seated).await;
+    }
+
+    /// Authenticates a client entering the lobby with a `Connect` request, or a request carrying
+    /// the same fields.
+    ///
+    /// # Returns
+    /// The entry of the player in the lobby and the receiver of their seat, or `None` if the
+    /// client was rejected.
+    async fn admit(
+        client: &mut TemporaryClient,
+        request: &Packet,
+    ) -> Option<(LobbyEntry, oneshot::Receiver<Seat>)> {
+        if !Self::enabled() {
+            let _ = client.reject(request, LobbyError::LobbyDisabled).await;
+            return None;
+        }
+        if client.negotiated.is_none() {
+            let _ = client
+                .reject(request, PlayerConnectionError::HandshakeRequired)
+                .await;
+            return None;
+        }
+        let player = match Player::new_connection(&request.payload).await {
+            Ok(player) => player,
+            Err(error) => {
+                let _ = client.reject(request, error).await;
+                return None;
+            }
+        };
+
+        let deck_id = payload::decode::<ConnectionRequest>(&HeaderType::Connect, &request.payload)
+            .map(|request| request.current_deck_id)
+            .unwrap_or_default();
+        let (seat, seated) = oneshot::channel();
+        let entry = LobbyEntry {
+            player_id: player.player_id,
+            deck_id,
+            ready: false,
+            seat,
+        };
+        Some((entry, seated))
+    }
+
+    /// Tells a client why it could not enter the lobby.
+    async fn refuse(client: &mut TemporaryClient, request: &Packet, error: LobbyError) {
+        match error {
+            LobbyError::AlreadyWaiting(_) => {
+                let answer = Packet::reply_to(request, HeaderType::AlreadyConnected, &[]);
+                client.send(&answer).await;
+            }
+            error => {
+                let _ = client.reject(request, error).await;
+            }
+        }
+    }
+
+    /// Keeps a client of the lobby waiting in its room until it is seated in a match, it leaves
+    /// or its private match expires.
+    async fn wait(
+        &self,
+        mut client: TemporaryClient,
+        request: &Packet,
+        player_id: &PlayerId,
+        room: Room,
+        mut seated: oneshot::Receiver<Seat>,
+    ) {
+        let wire_format = client
+            .negotiated
+            .map_or(WireFormat::Current, |n| WireFormat::for_protocol(&n));
+        loop {
+            // Peeking does not consume anything, so a packet is never read halfway when the
+            // client is seated.
+            let mut first = [0u8; 1];
+            let packet = tokio::select! {
+                seat = &mut seated => {
+                    if let Ok(seat) = seat {
+                        Self::take_seat(client, request, player_id, seat).await;
+                    }
+                    return;
+                }
+                join_code = self.expiry(&room) => {
+                    logger!(INFO, "[LOBBY] The private match `{join_code}` expired");
+                    let _ = client.reject(request, LobbyError::JoinCodeExpired(join_code)).await;
+                    return;
+                }
+                peeked = client.stream.peek(&mut first) => match peeked {
+                    Ok(read) if read > 0 => client.read_packet(wire_format).await,
+                    _ => Ok(None),
+                },
+            };
+
+            match packet {
+                Ok(Some(packet)) if packet.header.header_type == HeaderType::Ready => {
+                    logger!(INFO, "[LOBBY] `{player_id}` is ready");
+                    if let Some(players) = self.mark_ready(&room, player_id) {
+                        let host = Arc::clone(&client.host);
+                        let hosting = Self::host_match(host, room.clone(), players);
+                        tokio::spawn(hosting.in_current_span());
+                    }
+                    self.send_status(&mut client, &packet, &room).await;
+                }
+                Ok(Some(packet)) if packet.header.header_type == HeaderType::Ping => {
+                    client
+                        .send(&Packet::reply_to(&packet, HeaderType::Pong, &[]))
+                        .await;
+                }
+                Ok(Some(_)) => continue,
+                Ok(None) | Err(_) => {
+                    self.leave(&room, player_id);
+                    logger!(INFO, "[LOBBY] `{player_id}` left the lobby");
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Adds a player to a room of the lobby.
+    ///
+    /// # Returns
+    /// An error if the player is already waiting in the lobby, or the private match is unknown,
+    /// expired or full.
+    fn enter(&self, room: &Room, entry: LobbyEntry) -> Result<(), LobbyError> {
+        let mut rooms = self.rooms();
+        if rooms.contains(&entry.player_id) {
+            return Err(LobbyError::AlreadyWaiting(entry.player_id));
+        }
+
+        match room {
+            Room::Public => rooms.public.push(entry),
+            Room::Private(join_code) => {
+                let private = rooms
+                    .private
+                    .get_mut(join_code)
+                    .ok_or_else(|| LobbyError::UnknownJoinCode(join_code.clone()))?;
+                if private.expired() {
+                    return Err(LobbyError::JoinCodeExpired(join_code.clone()));
+                }
+                if private.players.len() >= LOBBY_MATCH_PLAYERS {
+                    return Err(LobbyError::PrivateMatchFull(join_code.clone()));
+                }
+                private.players.push(entry);
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens a private match seating the player, under a new join code.
+    fn open_private(&self, entry: LobbyEntry) -> Result<PrivateMatchCreated, LobbyError> {
+        let ttl = SETTINGS.get().map_or(300, |s| s.private_match_ttl);
+        let expires_at =
+            Utc::now().timestamp_millis() + Duration::from_secs(ttl).as_millis() as i64;
+
+        let mut rooms = self.rooms();
+        if rooms.contains(&entry.player_id) {
+            return Err(LobbyError::AlreadyWaiting(entry.player_id));
+        }
+        let join_code = loop {
+            let join_code = Self::join_code();
+            if !rooms.private.contains_key(&join_code) {
+                break join_code;
+            }
+        };
+        let room = PrivateRoom {
+            players: vec![entry],
+            expires_at,
+        };
+        rooms.private.insert(join_code.clone(), room);
+        Ok(PrivateMatchCreated {
+            join_code,
+            expires_at,
+        })
+    }
+
+    fn join_code() -> String {
+        let mut rng = rand::thread_rng();
+        (0..JOIN_CODE_LENGTH)
+            .map(|_| JOIN_CODE_ALPHABET[rng.gen_range(0..JOIN_CODE_ALPHABET.len())] as char)
+            .collect()
+    }
+
+    /// Waits for the private match of `room` to expire while it still misses a player, then
+    /// closes it. Never completes for the public room, nor once the private match has its players.
+    ///
+    /// # Returns
+    /// The join code of the expired private match.
+    async fn expiry(&self, room: &Room) -> String {
+        let Room::Private(join_code) = room else {
+            return std::future::pending().await;
+        };
+        loop {
+            let expires_at = self.rooms().private.get(join_code).map(|p| p.expires_at);
+            let Some(expires_at) = expires_at else {
+                return std::future::pending().await;
+            };
+            let left = expires_at - Utc::now().timestamp_millis();
+            if left > 0 {
+                tokio::time::sleep(Duration::from_millis(left as u64)).await;
+                continue;
+            }
+
+            if !self.close_expired(join_code) {
+                return std::future::pending().await;
+            }
+            return join_code.clone();
+        }
+    }
+
+    /// Closes a private match whose join code expired before it got its players.
+    ///
+    /// # Returns
+    /// `true` if the private match was closed.
+    fn close_expired(&self, join_code: &str) -> bool {
+        let mut rooms = self.rooms();
+        let closed = rooms.private.get(join_code).is_some_and(|private| {
+            private.expired() && private.players.len() < LOBBY_MATCH_PLAYERS
+        });
+        if closed {
+            rooms.private.remove(join_code);
+        }
+        closed
+    }
+
+    fn leave(&self, room: &Room, player_id: &PlayerId) {
+        let mut rooms = self.rooms();
+        if let Some(players) = rooms.players(room) {
+            players.retain(|entry| &entry.player_id != player_id);
+            if let (Room::Private(join_code), true) = (room, players.is_empty()) {
+                rooms.private.remove(join_code);
+            }
+        }
+    }
+
+    /// Marks a player of the lobby as ready.
+    ///
+    /// # Returns
+    /// The players to seat in a new match, taken out of the lobby, once enough of them are ready
+    /// in the room.
+    fn mark_ready(&self, room: &Room, player_id: &PlayerId) -> Option<Vec<LobbyEntry>> {
+        let mut rooms = self.rooms();
+        let players = rooms.players(room)?;
+        if let Some(entry) = players.iter_mut().find(|e| &e.player_id == player_id) {
+            entry.ready = true;
+        }
+
+        if players.iter().filter(|e| e.ready).count() < LOBBY_MATCH_PLAYERS {
+            return None;
+        }
+        let mut seated = Vec::with_capacity(LOBBY_MATCH_PLAYERS);
+        let mut index = 0;
+        while seated.len() < LOBBY_MATCH_PLAYERS {
+            match players[index].ready {
+                true => seated.push(players.remove(index)),
+                false => index += 1,
+            }
+        }
+        if let Room::Private(join_code) = room {
+            rooms.private.remove(join_code);
+        }
+        Some(seated)
+    }
+
+    fn status(&self, room: &Room) -> LobbyStatus {
+        let mut rooms = self.rooms();
+        let players = rooms.players(room).map_or(&[][..], |players| &players[..]);
+        LobbyStatus {
+            waiting: players.len(),
+            ready: players.iter().filter(|e| e.ready).count(),
+        }
+    }
+
+    async fn send_status(&self, client: &mut TemporaryClient, request: &Packet, room: &Room) {
+        if let Ok(payload) = serde_cbor::to_vec(&self.status(room)) {
+            let packet = Packet::reply_to(request, HeaderType::LobbyStatus, &payload);
+            client.send(&packet).await;
+        }
+    }
+
+    /// Creates the match of players of the lobby, as if the matchmaker sent `InitServer`, and
+    /// takes each of them to their seat.
+    async fn host_match(host: Arc<MatchHost>, room: Room, players: Vec<LobbyEntry>) {
+        let settings = SETTINGS.get();
+        let (prefix, match_type) = match room {
+            Room::Public => (
+                "lobby",
+                settings.map_or("casual", |s| s.lobby_match_type.as_str()),
+            ),
+            Room::Private(_) => (
+                "private",
+                settings.map_or("friendly", |s| s.private_match_type.as_str()),
+            ),
+        };
+        let request = InitServerRequest {
+            match_id: MatchId::from(format!("{prefix}-{}", uuid::Uuid::new_v4())),
+            match_type: match_type.to_string(),
+            players: players
+                .iter()
+                .map(|entry| PreloadPlayer {
+                    id: entry.player_id.clone(),
+                    deck_id: entry.deck_id.clone(),
+                    pool: None,
+                    bot: None,
+                    deck: None,
+                })
+                .collect(),
+            spectatable: false,
+            viewers: None,
+            seed: None,
+            stake: None,
+            rules: None,
+            scheduled_start: None,
+            draft: None,
+            game: 1,
+        };
+        let match_id = request.match_id.clone();
+
+        let seat = host.host(request).await.map_err(|error| {
+            logger!(
+                ERROR,
+                "[LOBBY] Could not create the match `{match_id}` of the lobby: {error}"
+            );
+            Rejection::from(&error)
+        });
+        for entry in players {
+            let _ = entry.seat.send(seat.clone());
+        }
+    }
+
+    /// Connects a player of the lobby to the match they were seated in and marks them ready, or
+    /// tells them why the match could not be created.
+    async fn take_seat(
+        mut client: TemporaryClient,
+        connect: &Packet,
+        player_id: &PlayerId,
+        seat: Seat,
+    ) {
+        let protocol = match seat {
+            Ok(protocol) => protocol,
+            Err(rejection) => {
+                client.send(&rejection.packet(Some(connect))).await;
+                return;
+            }
+        };
+
+        let span = match_span(&protocol.server_instance.match_id);
+        let connect = Arc::clone(&protocol).handle_connect(Arc::new(client), connect);
+        if let Err(error) = connect.instrument(span.clone()).await {
+            logger!(
+                ERROR,
+                "[LOBBY] Could not connect `{player_id}` to their match ({error})"
+            );
+            return;
+        }
+        if let Err(error) = protocol.mark_ready(player_id).instrument(span).await {
+            logger!(ERROR, "[LOBBY] Could not get `{player_id}` ready ({error})");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(player_id: &str) -> (LobbyEntry, oneshot::Receiver<Seat>) {
+        let (seat, seated) = oneshot::channel();
+        let entry = LobbyEntry {
+            player_id: PlayerId::from(player_id),
+            deck_id: format!("{player_id}-deck"),
+            ready: false,
+            seat,
+        };
+        (entry, seated)
+    }
+
+    #[test]
+    fn test_ready_players_are_paired_in_order() {
+        let lobby = Lobby::default();
+        let mut receivers = Vec::new();
+        for player_id in ["red", "blue", "green"] {
+            let (entry, seated) = entry(player_id);
+            assert!(lobby.enter(&Room::Public, entry).is_ok());
+            receivers.push(seated);
+        }
+        assert_eq!(
+            Err(LobbyError::AlreadyWaiting(PlayerId::from("red"))),
+            lobby.enter(&Room::Public, entry("red").0)
+        );
+
+        assert!(lobby
+            .mark_ready(&Room::Public, &PlayerId::from("green"))
+            .is_none());
+        assert_eq!(
+            LobbyStatus {
+                waiting: 3,
+                ready: 1
+            },
+            lobby.status(&Room::Public)
+        );
+
+        let players = lobby
+            .mark_ready(&Room::Public, &PlayerId::from("red"))
+            .unwrap();
+        let paired: Vec<_> = players.iter().map(|e| e.player_id.to_string()).collect();
+        assert_eq!(vec!["red", "green"], paired);
+        assert_eq!(
+            LobbyStatus {
+                waiting: 1,
+                ready: 0
+            },
+            lobby.status(&Room::Public)
+        );
+
+        lobby.leave(&Room::Public, &PlayerId::from("blue"));
+        assert_eq!(
+            LobbyStatus {
+                waiting: 0,
+                ready: 0
+            },
+            lobby.status(&Room::Public)
+        );
+    }
+
+    #[test]
+    fn test_private_matches_pair_the_players_of_their_join_code() {
+        let lobby = Lobby::default();
+        let created = lobby.open_private(entry("red").0).unwrap();
+        assert_eq!(JOIN_CODE_LENGTH, created.join_code.len());
+        let room = Room::Private(created.join_code.clone());
+
+        assert!(lobby.enter(&Room::Public, entry("blue").0).is_ok());
+        assert_eq!(
+            Err(LobbyError::UnknownJoinCode(String::from("NOPE42"))),
+            lobby.enter(&Room::Private(String::from("NOPE42")), entry("green").0)
+        );
+        assert!(lobby.enter(&room, entry("green").0).is_ok());
+        assert_eq!(
+            Err(LobbyError::PrivateMatchFull(created.join_code.clone())),
+            lobby.enter(&room, entry("yellow").0)
+        );
+
+        // Ready players of the public room are not paired with the private match.
+        assert!(lobby
+            .mark_ready(&Room::Public, &PlayerId::from("blue"))
+            .is_none());
+        assert!(lobby.mark_ready(&room, &PlayerId::from("red")).is_none());
+        let players = lobby.mark_ready(&room, &PlayerId::from("green")).unwrap();
+        assert_eq!(2, players.len());
+        assert!(lobby.rooms().private.is_empty());
+    }
+
+    #[test]
+    fn test_expired_join_codes_are_refused() {
+        let lobby = Lobby::default();
+        let created = lobby.open_private(entry("red").0).unwrap();
+        let join_code = created.join_code;
+        assert!(!lobby.close_expired(&join_code));
+
+        lobby
+            .rooms()
+            .private
+            .get_mut(&join_code)
+            .unwrap()
+            .expires_at = 0;
+        let room = Room::Private(join_code.clone());
+        assert_eq!(
+            Err(LobbyError::JoinCodeExpired(join_code.clone())),
+            lobby.enter(&room, entry("blue").0)
+        );
+        assert!(lobby.close_expired(&join_code));
+        assert_eq!(
+            LobbyStatus {
+                waiting: 0,
+                ready: 0
+            },
+            lobby.status(&room)
+        );
+    }
+}
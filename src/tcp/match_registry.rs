@@ -0,0 +1,126 @@
+use crate::models::client_requests::MatchTarget;
+use crate::models::ids::MatchId;
+use crate::tcp::protocol::Protocol;
+use crate::tcp::server::ServerInstance;
+use crate::utils::errors::ServerInstanceError;
+use crate::SETTINGS;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{Notify, RwLock};
+
+/// The matches hosted by the process, by id, each with the protocol its clients are served by.
+///
+/// Hosts up to `MAX_MATCHES` matches. With 1 the process hosts a single match and exits when it
+/// ends; with more, the matchmaker may send an `InitServer` for every match and a match leaves the
+/// registry when it ends.
+#[derive(Default)]
+pub struct MatchRegistry {
+    matches: RwLock<HashMap<MatchId, Arc<Protocol>>>, // The matches being played.
+    hosted: Notify,                                   // Woken when a match is hosted.
+}
+
+impl MatchRegistry {
+    /// The most matches hosted at once.
+    fn capacity() -> usize {
+        SETTINGS.get().map_or(1, |s| s.max_matches).max(1)
+    }
+
+    /// Whether the process hosts several matches instead of exiting when its match ends.
+    pub fn hosts_many(&self) -> bool {
+        Self::capacity() > 1
+    }
+
+    /// Checks that a match could be hosted, before the work of initializing it.
+    ///
+    /// # Returns
+    /// * `Ok(())` - The match is not hosted yet and there is room for it.
+    /// * `Err(ServerInstanceError)` - The match is already hosted or the registry is full.
+    pub async fn admits(&self, match_id: &MatchId) -> Result<(), ServerInstanceError> {
+        Self::check(&*self.matches.read().await, match_id)
+    }
+
+    fn check(
+        matches: &HashMap<MatchId, Arc<Protocol>>,
+        match_id: &MatchId,
+    ) -> Result<(), ServerInstanceError> {
+        if matches.contains_key(match_id) {
+            return Err(ServerInstanceError::AlreadyInitialized(match_id.clone()));
+        }
+        if matches.len() >= Self::capacity() {
+            return Err(ServerInstanceError::CapacityReached(Self::capacity()));
+        }
+        Ok(())
+    }
+
+    /// Hosts the match served by `protocol`.
+    ///
+    /// # Returns
+    /// An error if the match is already hosted or the registry is full.
+    pub async fn insert(&self, protocol: Arc<Protocol>) -> Result<(), ServerInstanceError> {
+        let match_id = protocol.server_instance.match_id.clone();
+        let mut matches = self.matches.write().await;
+        Self::check(&matches, &match_id)?;
+        matches.insert(match_id, protocol);
+        self.hosted.notify_waiters();
+        Ok(())
+    }
+
+    /// Waits until at least one match is hosted.
+    pub async fn wait_hosted(&self) {
+        loop {
+            // Created before the check, so a match hosted in between still wakes it.
+            let hosted = self.hosted.notified();
+            if !self.matches.read().await.is_empty() {
+                return;
+            }
+            hosted.await;
+        }
+    }
+
+    /// Stops hosting a match.
+    ///
+    /// # Returns
+    /// The protocol of the match, if it was hosted.
+    pub async fn remove(&self, match_id: &MatchId) -> Option<Arc<Protocol>> {
+        self.matches.write().await.remove(match_id)
+    }
+
+    /// The server instances of the hosted matches, ordered by match id.
+    pub async fn servers(&self) -> Vec<Arc<ServerInstance>> {
+        let mut servers: Vec<_> = self
+            .matches
+            .read()
+            .await
+            .values()
+            .map(|protocol| Arc::clone(&protocol.server_instance))
+            .collect();
+        servers.sort_by(|a, b| a.match_id.cmp(&b.match_id));
+        servers
+    }
+
+    /// Finds the match a `Connect`, `Reconnect` or `Spectate` request is for: the match it names,
+    /// else the match seating its player, else the only match hosted.
+    ///
+    /// # Returns
+    /// The protocol of the match, or `None` if no hosted match fits the request.
+    pub async fn route(&self, target: &MatchTarget) -> Option<Arc<Protocol>> {
+        let matches = self.matches.read().await;
+        if let Some(match_id) = &target.match_id {
+            return matches.get(match_id).cloned();
+        }
+
+        if let Some(player_id) = &target.player_id {
+            for protocol in matches.values() {
+                let players = protocol.game_instance.connected_players.read().await;
+                if players.contains_key(player_id) {
+                    return Some(Arc::clone(protocol));
+                }
+            }
+        }
+
+        match matches.len() {
+            1 => matches.values().next().cloned(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use crate::tcp::header::HeaderType;
+use crate::tcp::packet::Packet;
+use std::collections::VecDeque;
+
+/// How long a packet queued for a disconnected client is kept.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Retention {
+    /// Kept until the client reconnects, whatever the size of the queue.
+    Critical,
+    /// Only the latest packet of its type is kept, since it supersedes the previous ones.
+    Latest,
+    /// Kept while fewer than `limit` such packets are queued, the oldest being evicted first.
+    Bounded,
+}
+
+impl Retention {
+    /// The retention of the packets of a type.
+    pub fn of(header_type: &HeaderType) -> Self {
+        match header_type {
+            HeaderType::TurnStarted | HeaderType::MatchEnded => Retention::Critical,
+            HeaderType::GameState | HeaderType::MatchCountdown => Retention::Latest,
+            _ => Retention::Bounded,
+        }
+    }
+}
+
+/// Packets kept for a disconnected client until it reconnects, oldest first.
+///
+/// Packets are retained according to their type (see `Retention`): turn changes and the end of
+/// the match are never evicted, each new game state replaces the one queued before, and the other
+/// packets are bounded by `MISSED_PACKETS_LIMIT`.
+pub struct MissedPacketQueue {
+    packets: VecDeque<Packet>, // Packets kept, in the order they were sent.
+    limit: usize,              // Bounded packets kept at most.
+}
+
+impl MissedPacketQueue {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            packets: VecDeque::new(),
+            limit,
+        }
+    }
+
+    /// Changes the number of bounded packets kept, taking effect with the next packet queued.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    /// Queues a packet, evicting the packets its retention calls for.
+    ///
+    /// # Returns
+    /// The number of packets evicted.
+    pub fn push(&mut self, packet: Packet) -> usize {
+        let before = self.packets.len();
+        match Retention::of(&packet.header.header_type) {
+            Retention::Critical => {}
+            Retention::Latest => {
+                let header_type = packet.header.header_type.clone();
+                self.packets
+                    .retain(|queued| queued.header.header_type != header_type);
+            }
+            Retention::Bounded => {
+                if self.limit == 0 {
+                    return 1;
+                }
+                let bounded = self
+                    .packets
+                    .iter()
+                    .filter(|queued| Self::is_bounded(queued))
+                    .count();
+                let mut excess = (bounded + 1).saturating_sub(self.limit);
+                self.packets.retain(|queued| {
+                    let evict = excess > 0 && Self::is_bounded(queued);
+                    excess -= evict as usize;
+                    !evict
+                });
+            }
+        }
+        let evicted = before - self.packets.len();
+        self.packets.push_back(packet);
+        evicted
+    }
+
+    /// Takes every packet queued, oldest first.
+    pub fn drain(&mut self) -> Vec<Packet> {
+        self.packets.drain(..).collect()
+    }
+
+    /// Drops every packet queued.
+    pub fn clear(&mut self) {
+        self.packets.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    fn is_bounded(packet: &Packet) -> bool {
+        Retention::of(&packet.header.header_type) == Retention::Bounded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retention_per_packet_type() {
+        let mut queue = MissedPacketQueue::new(2);
+        let state = |turn: u8| Packet::new(HeaderType::GameState, &[turn]);
+        let notice = |id: u8| Packet::new(HeaderType::ScriptSkipped, &[id]);
+
+        assert_eq!(0, queue.push(state(1)));
+        assert_eq!(0, queue.push(Packet::new(HeaderType::TurnStarted, &[1])));
+        assert_eq!(0, queue.push(notice(1)));
+        assert_eq!(1, queue.push(state(2)));
+        assert_eq!(0, queue.push(notice(2)));
+        assert_eq!(1, queue.push(notice(3)));
+        assert_eq!(0, queue.push(Packet::new(HeaderType::MatchEnded, b"")));
+        assert_eq!(5, queue.len());
+
+        let queued: Vec<_> = queue
+            .drain()
+            .into_iter()
+            .map(|packet| (packet.header.header_type, packet.payload.to_vec()))
+            .collect();
+        assert_eq!(
+            vec![
+                (HeaderType::TurnStarted, vec![1]),
+                (HeaderType::GameState, vec![2]),
+                (HeaderType::ScriptSkipped, vec![2]),
+                (HeaderType::ScriptSkipped, vec![3]),
+                (HeaderType::MatchEnded, vec![]),
+            ],
+            queued
+        );
+        assert!(queue.is_empty());
+    }
+}
@@ -1,5 +1,47 @@
+#[cfg(feature = "game")]
+pub mod audit;
+#[cfg(feature = "game")]
+pub mod bot;
+#[cfg(feature = "game")]
 pub mod client;
+pub mod compat;
+#[cfg(feature = "game")]
+pub mod draft;
+pub mod encoding;
+pub mod error_response;
+pub mod handshake;
+pub mod listener;
+#[cfg(feature = "game")]
+pub mod lobby;
+#[cfg(feature = "game")]
+pub mod match_registry;
+#[cfg(feature = "game")]
+pub mod missed_packets;
+#[cfg(feature = "game")]
+pub mod outbound;
+#[cfg(feature = "game")]
+pub mod payload;
+#[cfg(feature = "game")]
 pub mod protocol;
+#[cfg(feature = "quic")]
+pub mod quic;
+pub mod rejection;
+#[cfg(feature = "game")]
+pub mod runtime;
+#[cfg(feature = "game")]
 pub mod server;
+#[cfg(feature = "game")]
+pub mod session;
+#[cfg(feature = "game")]
+pub mod sideboard;
+#[cfg(feature = "game")]
+pub mod social;
+#[cfg(feature = "game")]
+pub mod spectator;
+#[cfg(any(feature = "tls", feature = "quic"))]
+pub mod tls;
+pub mod transport;
+#[cfg(feature = "game")]
+pub mod viewers;
 pub mod header;
-mod packet;
+pub(crate) mod packet;
@@ -0,0 +1,195 @@
+use crate::tcp::header::HeaderType;
+use crate::tcp::packet::Packet;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tokio::sync::{watch, Notify};
+
+/// What happened to a packet pushed to an `OutboundQueue`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Enqueued {
+    /// The packet was queued.
+    Queued,
+    /// The queue was full: the oldest queued state packet was dropped to make room.
+    DroppedOldest,
+    /// The queue was closed; the packet will never be written.
+    Closed,
+}
+
+/// Packets waiting to be written to a client by its writer task.
+///
+/// The queue is bounded. When it is full, the oldest state packet queued is dropped, since every
+/// state packet supersedes the previous ones; other packets, such as the responses to actions,
+/// are never dropped, and pushing them waits for the writer to make room instead, which slows
+/// down the task sending to a slow client without blocking the rest of the match.
+pub struct OutboundQueue {
+    packets: Mutex<VecDeque<Packet>>, // Packets not written yet, oldest first.
+    capacity: usize,                  // Packets queued at most.
+    closed: Mutex<bool>,              // Set once the client is closed for good.
+    pushed: Notify,                   // Wakes the writer up when a packet is queued.
+    popped: Notify,                   // Wakes the senders up when room is made.
+    finished: watch::Sender<bool>,    // Set by the writer once it wrote the last packet.
+}
+
+impl OutboundQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            packets: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity: capacity.max(1),
+            closed: Mutex::new(false),
+            pushed: Notify::new(),
+            popped: Notify::new(),
+            finished: watch::Sender::new(false),
+        }
+    }
+
+    /// Whether the packet only carries state a later packet supersedes, so it may be dropped
+    /// when the client falls behind.
+    pub fn is_droppable(packet: &Packet) -> bool {
+        matches!(
+            packet.header.header_type,
+            HeaderType::GameState | HeaderType::MatchCountdown
+        )
+    }
+
+    /// Queues a packet, waiting for room if the queue is full of packets that cannot be dropped.
+    pub async fn push(&self, packet: Packet) -> Enqueued {
+        loop {
+            let popped = self.popped.notified();
+            tokio::pin!(popped);
+            popped.as_mut().enable();
+
+            if self.is_closed() {
+                return Enqueued::Closed;
+            }
+
+            {
+                let mut packets = self.lock();
+                let enqueued = match packets.len() < self.capacity {
+                    true => Some(Enqueued::Queued),
+                    false => packets
+                        .iter()
+                        .position(Self::is_droppable)
+                        .and_then(|oldest| packets.remove(oldest))
+                        .map(|_| Enqueued::DroppedOldest),
+                };
+                if let Some(enqueued) = enqueued {
+                    packets.push_back(packet);
+                    drop(packets);
+                    self.pushed.notify_one();
+                    return enqueued;
+                }
+            }
+
+            popped.await;
+        }
+    }
+
+    /// Takes the oldest packet, waiting for one to be queued.
+    ///
+    /// # Returns
+    /// `None` once the queue is closed and every packet queued before was taken.
+    pub async fn pop(&self) -> Option<Packet> {
+        loop {
+            let pushed = self.pushed.notified();
+            tokio::pin!(pushed);
+            pushed.as_mut().enable();
+
+            if let Some(packet) = self.lock().pop_front() {
+                self.popped.notify_waiters();
+                return Some(packet);
+            }
+            if self.is_closed() {
+                return None;
+            }
+
+            pushed.await;
+        }
+    }
+
+    /// Packets waiting to be written.
+    pub fn depth(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Refuses every packet from now on; the writer still writes the ones already queued.
+    pub fn close(&self) {
+        *self.closed.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        self.pushed.notify_one();
+        self.popped.notify_waiters();
+    }
+
+    /// Called by the writer after `pop` returned `None` and the last packet was written.
+    pub fn finish(&self) {
+        self.finished.send_replace(true);
+    }
+
+    /// Waits until the writer wrote every packet queued before the queue was closed.
+    pub async fn finished(&self) {
+        let _ = self
+            .finished
+            .subscribe()
+            .wait_for(|finished| *finished)
+            .await;
+    }
+
+    fn is_closed(&self) -> bool {
+        *self.closed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<Packet>> {
+        self.packets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_full_queue_drops_oldest_state_packet_only() {
+        let queue = OutboundQueue::new(3);
+        let state = |turn: u8| Packet::new(HeaderType::GameState, &[turn]);
+        assert_eq!(Enqueued::Queued, queue.push(state(1)).await);
+        assert_eq!(
+            Enqueued::Queued,
+            queue
+                .push(Packet::new(HeaderType::ActionAccepted, b""))
+                .await
+        );
+        assert_eq!(Enqueued::Queued, queue.push(state(2)).await);
+        assert_eq!(Enqueued::DroppedOldest, queue.push(state(3)).await);
+
+        let types: Vec<_> = [queue.pop().await, queue.pop().await]
+            .into_iter()
+            .flatten()
+            .map(|packet| (packet.header.header_type, packet.payload.to_vec()))
+            .collect();
+        assert_eq!(
+            vec![
+                (HeaderType::ActionAccepted, vec![]),
+                (HeaderType::GameState, vec![2]),
+            ],
+            types
+        );
+
+        // A queue full of responses makes the sender wait for the writer.
+        let queue = OutboundQueue::new(1);
+        queue
+            .push(Packet::new(HeaderType::ActionAccepted, b""))
+            .await;
+        let blocked = tokio::time::timeout(
+            std::time::Duration::from_millis(20),
+            queue.push(Packet::new(HeaderType::ActionRejected, b"")),
+        )
+        .await;
+        assert!(blocked.is_err());
+
+        queue.close();
+        assert!(queue.pop().await.is_some());
+        assert!(queue.pop().await.is_none());
+        assert_eq!(
+            Enqueued::Closed,
+            queue.push(Packet::new(HeaderType::GameState, b"")).await
+        );
+    }
+}
@@ -1,7 +1,14 @@
 use crate::logger;
-use crate::tcp::header::{Header, HeaderType};
+use crate::tcp::header::{Header, HeaderType, HEADER_SIZE, MAX_PAYLOAD_SIZE};
+use crate::utils::checksum::Checksum;
+use crate::utils::compression::{Compression, COMPRESSION_MASK, COMPRESSION_THRESHOLD};
 use crate::utils::errors::ProtocolError;
 use crate::utils::logger::Logger;
+use std::borrow::Cow;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Size of the chunks large payloads are streamed in.
+const CHUNK_SIZE: usize = 16 * 1024;
 
 /// Represents a complete network packet with a protocol header and payload.
 ///
@@ -17,7 +24,8 @@ pub struct Packet {
 impl Packet {
     /// Parses a raw byte slice into a `Packet`.
     ///
-    /// Expects a 5-byte header followed by the payload (skips byte 5: delimiter).
+    /// Expects a `HEADER_SIZE`-byte header (the last byte being the delimiter) followed by the payload.
+    /// Compressed payloads are decompressed according to the header flags.
     ///
     /// # Arguments
     /// - `protocol`: A byte slice containing the serialized packet data.
@@ -26,16 +34,75 @@ impl Packet {
     /// - `Ok(Packet)`: If the byte slice is valid and contains a recognizable packet.
     /// - `Err(ProtocolError)`: If the byte slice is invalid or the header cannot be parsed.
     pub fn parse(protocol: &[u8]) -> Result<Self, ProtocolError> {
-        if protocol.len() < 6 {
+        if protocol.len() < HEADER_SIZE {
             logger!(ERROR, "[PROTOCOL] Not enough bytes for a valid packet");
             return Err(ProtocolError::InvalidPacketError(
                 "Not enough bytes for a valid packet".to_string(),
             ));
         }
 
-        let header = Header::from_bytes(&protocol[..6])?;
-        let payload = protocol[6..].to_owned().into_boxed_slice();
-        Ok(Self { header, payload })
+        let header = Header::from_bytes(&protocol[..HEADER_SIZE])?;
+        let payload = protocol[HEADER_SIZE..].to_owned().into_boxed_slice();
+        if payload.len() != header.payload_length as usize {
+            return Err(ProtocolError::InvalidPacketError(format!(
+                "Declared payload length {} does not match the {} bytes received",
+                header.payload_length,
+                payload.len()
+            )));
+        }
+
+        Self::decode(header, payload.into_vec())
+    }
+
+    /// Reads exactly one packet from a stream.
+    ///
+    /// Reads the fixed-size header first, then as many bytes as it declares, so payloads can be
+    /// larger than a single read and several packets can arrive in one read. Compressed payloads are
+    /// decompressed according to the header flags.
+    ///
+    /// A payload that cannot be decompressed is read whole before failing with `CorruptPayload`,
+    /// so the stream can still be read after it.
+    ///
+    /// # Arguments
+    /// - `reader`: The stream to read from.
+    ///
+    /// # Returns
+    /// - `Ok(Some(Packet))`: The next packet on the stream.
+    /// - `Ok(None)`: If the peer closed the connection before a new packet started.
+    /// - `Err(ProtocolError)`: If the header is invalid or the stream failed mid-packet.
+    pub async fn read_from<R: AsyncRead + Unpin>(
+        reader: &mut R,
+    ) -> Result<Option<Self>, ProtocolError> {
+        let mut header_bytes = [0u8; HEADER_SIZE];
+        match reader.read_exact(&mut header_bytes).await {
+            Ok(_) => {}
+            Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(ProtocolError::StreamError(error.to_string())),
+        }
+
+        let header = Header::from_bytes(&header_bytes)?;
+        let payload = read_payload(reader, header.payload_length as usize).await?;
+        Self::decode(header, payload).map(Some)
+    }
+
+    /// Writes the packet to a stream, sending the payload in chunks.
+    ///
+    /// The payload is compressed first if the header flags ask for it.
+    ///
+    /// # Arguments
+    /// - `writer`: The stream to write to.
+    ///
+    /// # Returns
+    /// The number of bytes written.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> std::io::Result<usize> {
+        let (header, payload) = self.encode();
+        writer.write_all(&header.wrap_header()).await?;
+        for chunk in payload.chunks(CHUNK_SIZE) {
+            writer.write_all(chunk).await?;
+        }
+
+        writer.flush().await?;
+        Ok(HEADER_SIZE + payload.len())
     }
 
     /// Creates a new `Packet` from a message type and payload.
@@ -54,19 +121,232 @@ impl Packet {
         Self { header, payload }
     }
 
+    /// Creates a response `Packet` that echoes the sequence number of the request it answers.
+    ///
+    /// # Arguments
+    /// - `request`: The packet being answered.
+    /// - `header_type`: The type of the response message.
+    /// - `payload`: The payload data for the response.
+    ///
+    /// # Returns
+    /// A new `Packet` carrying the same sequence number as `request`.
+    pub fn reply_to(request: &Packet, header_type: HeaderType, payload: &[u8]) -> Self {
+        let mut packet = Packet::new(header_type, payload);
+        packet.header.sequence = request.header.sequence;
+        packet
+    }
+
+    /// Marks the payload to be compressed with the given algorithm when the packet is written.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.header.flags = (self.header.flags & !COMPRESSION_MASK) | compression.flag();
+        self
+    }
+
+    /// Returns the packet as it is sent to a peer: sealed with the peer's checksum and, for large
+    /// `GameState` payloads, marked for compression with the algorithm the peer negotiated.
+    ///
+    /// The packet is only copied if it needs to change.
+    pub fn sealed(&self, checksum: &dyn Checksum, compression: Compression) -> Cow<'_, Packet> {
+        let checksum = checksum.compute(&self.payload);
+        let compression = match self.header.header_type {
+            HeaderType::GameState if self.payload.len() >= COMPRESSION_THRESHOLD => compression,
+            _ => Compression::None,
+        };
+
+        if checksum == self.header.checksum && compression == Compression::None {
+            return Cow::Borrowed(self);
+        }
+
+        let mut sealed = self.clone().with_compression(compression);
+        sealed.header.checksum = checksum;
+        Cow::Owned(sealed)
+    }
+
+    /// Describes the packet in full for the packet dump: header fields and the payload in hex.
+    pub fn dump(&self) -> String {
+        let payload: String = self.payload.iter().map(|b| format!("{b:02x}")).collect();
+        format!(
+            "{{ type: {}, flags: {:#04x}, sequence: {}, checksum: {:#010x}, length: {}, payload: {payload} }}",
+            self.header.header_type,
+            self.header.flags,
+            self.header.sequence,
+            self.header.checksum,
+            self.payload.len()
+        )
+    }
+
     /// Serializes the packet into a byte slice.
     ///
-    /// Combines the header and payload into a single buffer for transmission.
+    /// Combines the header and payload into a single buffer for transmission, compressing the
+    /// payload if the header flags ask for it.
     ///
     /// # Returns
     /// A boxed array of bytes representing the serialized packet.
     pub fn wrap_packet(&self) -> Box<[u8]> {
-        let header = self.header.wrap_header();
-        let mut packet = Vec::with_capacity(header.len() + self.payload.len());
+        let (header, payload) = self.encode();
+        let header = header.wrap_header();
+        let mut packet = Vec::with_capacity(header.len() + payload.len());
 
         packet.extend_from_slice(&header);
-        packet.extend_from_slice(&self.payload);
+        packet.extend_from_slice(&payload);
 
         packet.into_boxed_slice()
     }
+
+    /// Returns the header and payload as they are sent on the wire.
+    ///
+    /// If compression fails the payload is sent uncompressed, with the flag cleared.
+    fn encode(&self) -> (Header, Cow<'_, [u8]>) {
+        let compression = Compression::from_flags(self.header.flags).unwrap_or_default();
+        if compression == Compression::None {
+            return (self.header.clone(), Cow::Borrowed(&self.payload));
+        }
+
+        let mut header = self.header.clone();
+        match compression.compress(&self.payload) {
+            Ok(compressed) => {
+                header.payload_length = compressed.len() as u32;
+                (header, Cow::Owned(compressed))
+            }
+            Err(_) => {
+                header.flags &= !COMPRESSION_MASK;
+                (header, Cow::Borrowed(&self.payload))
+            }
+        }
+    }
+
+    /// Builds a packet from a header and payload read from the wire, decompressing the payload.
+    ///
+    /// The compression flag is kept, while the payload length is updated to the decompressed size.
+    fn decode(mut header: Header, payload: Vec<u8>) -> Result<Self, ProtocolError> {
+        let payload = match Compression::from_flags(header.flags)? {
+            Compression::None => payload,
+            compression => compression
+                .decompress(&payload, MAX_PAYLOAD_SIZE as usize)
+                .map_err(|e| ProtocolError::CorruptPayload(e.to_string()))?,
+        };
+
+        header.payload_length = payload.len() as u32;
+        Ok(Self {
+            header,
+            payload: payload.into_boxed_slice(),
+        })
+    }
+}
+
+/// Reads a payload of the length declared by its header.
+///
+/// The buffer grows as the bytes arrive instead of being allocated for the declared length up
+/// front, so a header claiming a large payload costs nothing until the payload is actually sent.
+///
+/// # Returns
+/// - `Ok(Vec<u8>)`: The payload, exactly `length` bytes long.
+/// - `Err(ProtocolError)`: If the stream failed or closed before `length` bytes were read.
+pub async fn read_payload<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    length: usize,
+) -> Result<Vec<u8>, ProtocolError> {
+    let mut payload = Vec::with_capacity(length.min(CHUNK_SIZE));
+    let read = (&mut *reader)
+        .take(length as u64)
+        .read_to_end(&mut payload)
+        .await
+        .map_err(|e| ProtocolError::StreamError(e.to_string()))?;
+    if read < length {
+        return Err(ProtocolError::StreamError(format!(
+            "Connection closed after {read} of the {length} bytes declared"
+        )));
+    }
+
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_large_packet_round_trips_through_stream() {
+        let payload: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let packet = Packet::new(HeaderType::GameState, &payload);
+        let (mut client, mut server) = tokio::io::duplex(4096);
+
+        let writer = tokio::spawn(async move {
+            packet.write_to(&mut client).await.unwrap();
+            Packet::new(HeaderType::Ping, b"")
+                .write_to(&mut client)
+                .await
+                .unwrap();
+        });
+
+        let received = Packet::read_from(&mut server).await.unwrap().unwrap();
+        let next = Packet::read_from(&mut server).await.unwrap().unwrap();
+        writer.await.unwrap();
+
+        assert_eq!(payload.as_slice(), &*received.payload);
+        assert_eq!(HeaderType::Ping, next.header.header_type);
+        assert!(Packet::read_from(&mut server).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_compressed_packet_round_trips() {
+        let payload = b"{\"hand\": [null, null, null]}".repeat(100);
+        for compression in [Compression::Deflate, Compression::Zstd] {
+            let packet = Packet::new(HeaderType::GameState, &payload).with_compression(compression);
+            let bytes = packet.wrap_packet();
+            let parsed = Packet::parse(&bytes).unwrap();
+
+            assert!(bytes.len() < payload.len());
+            assert_eq!(payload.as_slice(), &*parsed.payload);
+            assert_eq!(packet.header.checksum, parsed.header.checksum);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compressed_packet_round_trips_through_stream() {
+        let payload = vec![7u8; 50_000];
+        let packet =
+            Packet::new(HeaderType::GameState, &payload).with_compression(Compression::Zstd);
+        let (mut client, mut server) = tokio::io::duplex(4096);
+
+        let written = packet.write_to(&mut client).await.unwrap();
+        drop(client);
+        let received = Packet::read_from(&mut server).await.unwrap().unwrap();
+
+        assert!(written < payload.len());
+        assert_eq!(payload.as_slice(), &*received.payload);
+    }
+
+    #[test]
+    fn test_parse_rejects_length_mismatch() {
+        let mut bytes = Packet::new(HeaderType::Ping, b"abc").wrap_packet().to_vec();
+        bytes.pop();
+        assert!(Packet::parse(&bytes).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_truncated_payload_fails_the_read() {
+        let mut header = Header::new(HeaderType::GameState, b"");
+        header.payload_length = MAX_PAYLOAD_SIZE;
+        let mut bytes = header.wrap_header().to_vec();
+        bytes.extend_from_slice(b"abc");
+
+        let result = Packet::read_from(&mut bytes.as_slice()).await;
+        assert!(matches!(result, Err(ProtocolError::StreamError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_payload_keeps_the_stream_readable() {
+        let mut corrupt = Packet::new(HeaderType::GameState, &[0xAB; 64]);
+        corrupt.header.flags = Compression::Zstd.flag();
+        let mut bytes = corrupt.header.wrap_header().to_vec();
+        bytes.extend_from_slice(&corrupt.payload);
+        bytes.extend_from_slice(&Packet::new(HeaderType::Ping, b"next").wrap_packet());
+        let mut reader = bytes.as_slice();
+
+        let result = Packet::read_from(&mut reader).await;
+        assert!(matches!(result, Err(ProtocolError::CorruptPayload(_))));
+        let next = Packet::read_from(&mut reader).await.unwrap().unwrap();
+        assert_eq!(b"next", &*next.payload);
+    }
 }
@@ -0,0 +1,393 @@
+use crate::tcp::handshake::{MIN_PAYLOAD_VERSION, PAYLOAD_VERSION};
+use crate::tcp::header::{HeaderType, MAX_PAYLOAD_SIZE};
+use crate::utils::errors::PayloadError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// CBOR item that closes an indefinite-length string or collection.
+const BREAK: u8 = 0xFF;
+
+/// First bytes read as a schema version rather than CBOR. A request payload is always a CBOR
+/// map, which never starts with one of them.
+const VERSION_BYTES: std::ops::RangeInclusive<u8> = 0x00..=0x17;
+
+/// A request decoded from a versioned payload.
+///
+/// Every supported payload version decodes into the current request struct. A request whose
+/// schema changes overrides `decode_version` to read the payloads of older versions into it, so
+/// clients can be upgraded after the server.
+pub trait VersionedRequest: DeserializeOwned {
+    /// Decodes the CBOR of a payload of a supported schema version.
+    fn decode_version(version: u8, payload: &[u8]) -> Result<Self, PayloadError> {
+        let _ = version;
+        Ok(serde_cbor::from_slice(payload)?)
+    }
+}
+
+/// Bounds a CBOR payload must stay within before it is deserialized.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct PayloadLimits {
+    pub max_bytes: usize,    // Size of the whole payload.
+    pub max_depth: usize,    // Arrays, maps and tags nested in one another.
+    pub max_collection: u64, // Entries of a single array or map.
+    pub max_values: u64,     // Values in the whole payload, nested ones included.
+}
+
+impl PayloadLimits {
+    /// The limits of the payload of a request type, sized for what a legitimate client sends.
+    pub fn for_request(header_type: &HeaderType) -> Self {
+        match header_type {
+            HeaderType::Handshake
+            | HeaderType::GetHistory
+            | HeaderType::ConfirmStake
+            | HeaderType::Ready
+            | HeaderType::ResyncRequest
+            | HeaderType::Emote
+            | HeaderType::MuteChat
+            | HeaderType::RequestUndo
+            | HeaderType::EndTurn
+            | HeaderType::DraftPick => Self {
+                max_bytes: 1024,
+                max_depth: 4,
+                max_collection: 32,
+                max_values: 128,
+            },
+            HeaderType::Connect
+            | HeaderType::Reconnect
+            | HeaderType::Chat
+            | HeaderType::CreatePrivateMatch
+            | HeaderType::JoinPrivateMatch
+            | HeaderType::GetReplay => Self {
+                max_bytes: 8 * 1024,
+                max_depth: 4,
+                max_collection: 16,
+                max_values: 64,
+            },
+            HeaderType::PlayCard
+            | HeaderType::PromptResponse
+            | HeaderType::ActivateAbility
+            | HeaderType::DeclareAttackers
+            | HeaderType::DeclareBlockers
+            | HeaderType::SideboardRequest => Self {
+                max_bytes: 16 * 1024,
+                max_depth: 8,
+                max_collection: 64,
+                max_values: 1024,
+            },
+            HeaderType::ActionBatch => Self {
+                max_bytes: 64 * 1024,
+                max_depth: 8,
+                max_collection: 64,
+                max_values: 4096,
+            },
+            _ => Self {
+                max_bytes: MAX_PAYLOAD_SIZE as usize,
+                max_depth: 16,
+                max_collection: 4096,
+                max_values: 65536,
+            },
+        }
+    }
+
+    /// Walks the CBOR items of a payload without allocating anything, checking them against the
+    /// limits.
+    ///
+    /// # Returns
+    /// * `Ok(())` - If the payload is within the limits.
+    /// * `Err(PayloadError)` - The first limit exceeded, or `Malformed` if the items are invalid.
+    pub fn check(&self, payload: &[u8]) -> Result<(), PayloadError> {
+        if payload.len() > self.max_bytes {
+            return Err(PayloadError::OverBudget(payload.len(), self.max_bytes));
+        }
+
+        let mut scanner = Scanner {
+            limits: self,
+            bytes: payload,
+            position: 0,
+            values: 0,
+        };
+        scanner.value(0)
+    }
+}
+
+/// Decodes the payload of a request, refusing payloads outside the limits of its type before
+/// serde gets to allocate anything for them.
+///
+/// A payload may start with the byte of its schema version, followed by the CBOR of the request.
+/// Payloads without one predate the versioning and are read as `MIN_PAYLOAD_VERSION`.
+///
+/// # Arguments
+/// * `header_type` - The type of the request, which selects the limits.
+/// * `payload` - The CBOR payload of the request, after its schema version if any.
+///
+/// # Returns
+/// * `Ok(T)` - The request, upgraded to the current schema.
+/// * `Err(PayloadError)` - `UnsupportedPayloadVersion` if the server does not decode the schema
+///   version of the payload, or why the payload could not be decoded.
+pub fn decode<T: VersionedRequest>(
+    header_type: &HeaderType,
+    payload: &[u8],
+) -> Result<T, PayloadError> {
+    let (version, payload) = match payload.split_first() {
+        Some((version, cbor)) if VERSION_BYTES.contains(version) => (*version, cbor),
+        _ => (MIN_PAYLOAD_VERSION, payload),
+    };
+    if !(MIN_PAYLOAD_VERSION..=PAYLOAD_VERSION).contains(&version) {
+        return Err(PayloadError::UnsupportedPayloadVersion(
+            version,
+            MIN_PAYLOAD_VERSION,
+            PAYLOAD_VERSION,
+        ));
+    }
+
+    PayloadLimits::for_request(header_type).check(payload)?;
+    T::decode_version(version, payload)
+}
+
+/// Encodes a request the way a client of the current payload schema sends it.
+pub fn encode<T: Serialize>(request: &T) -> Result<Vec<u8>, serde_cbor::Error> {
+    let mut payload = vec![PAYLOAD_VERSION];
+    serde_cbor::to_writer(&mut payload, request)?;
+    Ok(payload)
+}
+
+impl PayloadError {
+    /// The header type to reply with: payloads outside the limits are always answered with
+    /// `InvalidPacketPayload`, other decoding errors with the usual rejection of the handler.
+    pub fn reply_header(&self, rejection: HeaderType) -> HeaderType {
+        match self {
+            PayloadError::Decode(_) => rejection,
+            _ => HeaderType::InvalidPacketPayload,
+        }
+    }
+}
+
+/// Cursor over the CBOR items of a payload.
+struct Scanner<'a> {
+    limits: &'a PayloadLimits,
+    bytes: &'a [u8],
+    position: usize,
+    values: u64, // Values met so far.
+}
+
+impl Scanner<'_> {
+    fn value(&mut self, depth: usize) -> Result<(), PayloadError> {
+        if depth > self.limits.max_depth {
+            return Err(PayloadError::TooDeep(self.limits.max_depth));
+        }
+
+        self.values += 1;
+        if self.values > self.limits.max_values {
+            return Err(PayloadError::TooManyValues(self.limits.max_values));
+        }
+
+        let initial = self.byte()?;
+        let (major, info) = (initial >> 5, initial & 0x1F);
+        match (major, info) {
+            (0 | 1, _) => {
+                self.argument(info)?;
+            }
+            (2 | 3, 31) => self.chunks(major)?,
+            (2 | 3, _) => {
+                let length = self.argument(info)?;
+                self.skip(length)?;
+            }
+            (4 | 5, 31) => {
+                let mut entries = 0;
+                while self.peek()? != BREAK {
+                    entries += 1;
+                    self.check_collection(entries)?;
+                    self.entry(major, depth)?;
+                }
+                self.position += 1;
+            }
+            (4 | 5, _) => {
+                let entries = self.argument(info)?;
+                self.check_collection(entries)?;
+                for _ in 0..entries {
+                    self.entry(major, depth)?;
+                }
+            }
+            (6, _) => {
+                self.argument(info)?;
+                self.value(depth + 1)?;
+            }
+            (_, 0..=23) => {}
+            (_, 24..=27) => {
+                self.skip(1 << (info - 24))?;
+            }
+            _ => {
+                return Err(PayloadError::Malformed(format!(
+                    "unexpected simple value or break at byte {}",
+                    self.position - 1
+                )))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Scans one entry of an array, or one key and value of a map.
+    fn entry(&mut self, major: u8, depth: usize) -> Result<(), PayloadError> {
+        self.value(depth + 1)?;
+        if major == 5 {
+            self.value(depth + 1)?;
+        }
+        Ok(())
+    }
+
+    /// Scans the chunks of an indefinite-length string, which must be strings of the same type.
+    fn chunks(&mut self, major: u8) -> Result<(), PayloadError> {
+        while self.peek()? != BREAK {
+            let initial = self.byte()?;
+            if initial >> 5 != major || initial & 0x1F == 31 {
+                return Err(PayloadError::Malformed(format!(
+                    "invalid string chunk at byte {}",
+                    self.position - 1
+                )));
+            }
+            let length = self.argument(initial & 0x1F)?;
+            self.skip(length)?;
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn check_collection(&self, entries: u64) -> Result<(), PayloadError> {
+        match entries > self.limits.max_collection {
+            true => Err(PayloadError::CollectionTooLarge(
+                entries,
+                self.limits.max_collection,
+            )),
+            false => Ok(()),
+        }
+    }
+
+    /// Reads the argument following an initial byte: a length, a count or an integer.
+    fn argument(&mut self, info: u8) -> Result<u64, PayloadError> {
+        let size = match info {
+            0..=23 => return Ok(info as u64),
+            24..=27 => 1 << (info - 24),
+            _ => {
+                return Err(PayloadError::Malformed(format!(
+                    "invalid argument at byte {}",
+                    self.position - 1
+                )))
+            }
+        };
+
+        let start = self.position;
+        self.skip(size)?;
+        Ok(self.bytes[start..self.position]
+            .iter()
+            .fold(0, |argument, byte| argument << 8 | *byte as u64))
+    }
+
+    fn byte(&mut self) -> Result<u8, PayloadError> {
+        let byte = self.peek()?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn peek(&self) -> Result<u8, PayloadError> {
+        self.bytes
+            .get(self.position)
+            .copied()
+            .ok_or_else(|| PayloadError::Malformed(String::from("unexpected end of payload")))
+    }
+
+    fn skip(&mut self, length: u64) -> Result<(), PayloadError> {
+        let remaining = (self.bytes.len() - self.position) as u64;
+        if length > remaining {
+            return Err(PayloadError::Malformed(format!(
+                "item of {length} bytes runs past the end of the payload"
+            )));
+        }
+
+        self.position += length as usize;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::client_requests::{ActionBatchRequest, PlayCardRequest};
+    use crate::models::ids::CardInstanceId;
+
+    #[test]
+    fn test_valid_requests_are_decoded() {
+        let request = PlayCardRequest {
+            actor_id: "red".into(),
+            instance_id: CardInstanceId::nth(7),
+            target_id: Some(String::from("blue")),
+            target_position: None,
+        };
+        let payload = serde_cbor::to_vec(&request).unwrap();
+
+        let decoded: PlayCardRequest = decode(&HeaderType::PlayCard, &payload).unwrap();
+        assert_eq!(request.instance_id, decoded.instance_id);
+    }
+
+    #[test]
+    fn test_pathological_payloads_are_refused() {
+        // An array claiming four billion entries, in five bytes.
+        let huge = [0x9A, 0xFF, 0xFF, 0xFF, 0xFF];
+        assert!(matches!(
+            decode::<ActionBatchRequest>(&HeaderType::ActionBatch, &huge),
+            Err(PayloadError::CollectionTooLarge(0xFFFF_FFFF, 64))
+        ));
+
+        // Arrays nested in one another, well within the byte budget.
+        let deep = [0x81; 32];
+        assert!(matches!(
+            decode::<ActionBatchRequest>(&HeaderType::ActionBatch, &deep),
+            Err(PayloadError::TooDeep(8))
+        ));
+
+        // A text string longer than the payload.
+        let truncated = [0x78, 0x40, b'a'];
+        let error = decode::<PlayCardRequest>(&HeaderType::PlayCard, &truncated).unwrap_err();
+        assert!(matches!(error, PayloadError::Malformed(_)));
+        assert_eq!(
+            HeaderType::InvalidPacketPayload,
+            error.reply_header(HeaderType::ActionRejected)
+        );
+
+        let oversized = vec![0x40; 2048];
+        assert!(matches!(
+            decode::<PlayCardRequest>(&HeaderType::Handshake, &oversized),
+            Err(PayloadError::OverBudget(2048, 1024))
+        ));
+    }
+
+    #[test]
+    fn test_payload_versions_are_checked() {
+        let request = PlayCardRequest {
+            actor_id: "red".into(),
+            instance_id: CardInstanceId::nth(7),
+            ..Default::default()
+        };
+        let unversioned = serde_cbor::to_vec(&request).unwrap();
+        let versioned = encode(&request).unwrap();
+        assert_eq!(PAYLOAD_VERSION, versioned[0]);
+        assert_eq!(&unversioned[..], &versioned[1..]);
+
+        for payload in [unversioned, versioned.clone()] {
+            let decoded: PlayCardRequest = decode(&HeaderType::PlayCard, &payload).unwrap();
+            assert_eq!(request.instance_id, decoded.instance_id);
+        }
+
+        let mut unsupported = versioned;
+        unsupported[0] = PAYLOAD_VERSION + 1;
+        let error = decode::<PlayCardRequest>(&HeaderType::PlayCard, &unsupported).unwrap_err();
+        assert!(matches!(
+            error,
+            PayloadError::UnsupportedPayloadVersion(_, MIN_PAYLOAD_VERSION, PAYLOAD_VERSION)
+        ));
+        assert_eq!(
+            HeaderType::InvalidPacketPayload,
+            error.reply_header(HeaderType::ActionRejected)
+        );
+    }
+}
@@ -1,116 +1,274 @@
 use super::client::{Client, TemporaryClient};
-use crate::game::entity::player::{Player, PlayerView};
+use crate::game::entity::player::Player;
+use crate::game::combat::CombatWindow;
 use crate::game::game::GameInstance;
-use crate::models::client_requests::PlayCardRequest;
-use crate::models::exit_code::ExitCode;
+use crate::game::game::PlayOutcome;
+use crate::game::event_bus::{
+    Audience, ChatSent, EmoteSent, MatchEvent, PlayerDisconnected, PlayerReconnected,
+};
+use crate::game::rules::DisconnectClock;
+use crate::game::event_log::MAX_EVENTS;
+use crate::game::start_barrier::MatchStart;
+use crate::models::client_requests::{
+    ActionBatchRequest, ActivateAbilityRequest, ChatRequest, ConfirmStakeRequest,
+    DeclareAttackersRequest, DeclareBlockersRequest, EmoteRequest, HistoryRequest, MuteRequest,
+    PlayCardRequest, PromptResponse, SpectateRequest,
+};
+use crate::models::connect_ack::{ConnectAck, MatchRules, OpponentProfile};
+use crate::models::ids::PlayerId;
+use crate::tcp::audit::{AuditLimits, ViolationKind};
 use crate::tcp::header::HeaderType;
-use crate::tcp::header::HeaderType::PlayCard;
+use crate::tcp::compat::WireFormat;
+use crate::tcp::error_response::{ErrorCode, ErrorResponse};
+use crate::tcp::handshake::{FEATURE_ACTION_BATCH, FEATURE_MATCH_START, FEATURE_PROMPTS};
+use crate::tcp::outbound::Enqueued;
 use crate::tcp::packet::Packet;
+use crate::tcp::payload;
+use crate::tcp::rejection::{Rejection, RejectionReason};
 use crate::tcp::server::ServerInstance;
-use crate::utils::errors::{NetworkError, PlayerConnectionError};
-use crate::{
-    logger,
-    utils::{checksum::Checksum, logger::Logger},
-};
+use crate::tcp::session::SessionToken;
+use crate::tcp::spectator::{self, Spectator};
+use crate::utils::bandwidth::{BandwidthCaps, CapStatus};
+use crate::utils::metrics::PacketDirection;
+use crate::utils::replay::ReplayRecord;
+use crate::utils::errors::{GameLogicError, NetworkError, PlayerConnectionError, SpectatorError};
+use crate::{logger, utils::logger::{player_span, Logger}, METRICS, RUNTIME_FLAGS, SETTINGS};
+use chrono::Utc;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::io::AsyncWriteExt;
-use tokio::sync::broadcast::Sender;
-use tokio::sync::{broadcast, Mutex, RwLock};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::Instrument;
+
+pub mod spec;
+
+/// Prompts answered with their default choice for a client without prompt support before the
+/// action is given up, so a script prompting again and again cannot hold the connection forever.
+const MAX_DEFAULT_ANSWERS: usize = 8;
 
 /// The Protocol struct handles the communication protocol for the server, managing client connections and packet processing.
 pub struct Protocol {
     pub game_instance: Arc<GameInstance>,
     pub server_instance: Arc<ServerInstance>,
-    pub transmitter: Arc<Mutex<Sender<Packet>>>, // The transmitter for broadcasting packets to clients.
 }
 
 impl Protocol {
     pub fn new(server_instance: Arc<ServerInstance>, game_instance: Arc<GameInstance>) -> Self {
-        let (tx, _) = broadcast::channel::<Packet>(10);
         Protocol {
             game_instance,
             server_instance,
-            transmitter: Arc::new(Mutex::new(tx)),
         }
     }
 
     /// Handles incoming packets from a client.
     ///
-    /// - Parses the packet from the provided buffer.
-    /// - Validates the packet's checksum.
+    /// - Validates the packet's checksum with the algorithm negotiated with the client.
     /// - Logs the packet details.
+    /// - Translates the payload back to CBOR for clients that negotiated JSON payloads, answering
+    ///   payloads that are not valid JSON with `InvalidPacketPayload`.
     /// - If the packet is valid, it calls `handle_packet` to process it.
-    /// - If the checksum is invalid, it sends an `InvalidChecksum` packet to the client and disconnects.
+    /// - If the checksum is invalid, it sends an `InvalidChecksum` packet to the client and counts
+    ///   the packet as malformed (see `malformed_packet`).
     ///
     /// # Arguments
     /// * `client` - The client that sent the packet.
-    /// * `buffer` - The byte buffer containing the incoming packet data.
+    /// * `packet` - The packet read from the client's stream.
     ///
     /// # Returns
     /// * None if the packet is processed successfully.
-    /// * Sends an `InvalidChecksum` packet and disconnects the client if the checksum is invalid.
+    /// * Sends an `InvalidChecksum` packet if the checksum is invalid, disconnecting clients that
+    ///   keep sending malformed packets.
     ///
     /// Log all outcomes, including errors and successful packet processing.
-    pub async fn handle_incoming(&self, client: Arc<Client>, buffer: &[u8]) {
-        match Packet::parse(&buffer) {
-            Err(error) => logger!(ERROR, "{}", error.to_string()),
-            Ok(packet) => {
-                logger!(
-                    DEBUG,
-                    "[PROTOCOL] Received packet: {{ type: {}, size: {} }}",
-                    packet.header.header_type.to_string(),
-                    packet.header.payload_length
-                );
+    pub async fn handle_incoming(&self, client: Arc<Client>, packet: Packet) {
+        logger!(
+            DEBUG,
+            "[PROTOCOL] Received packet: {{ type: {}, size: {} }}",
+            packet.header.header_type.to_string(),
+            packet.header.payload_length
+        );
+        METRICS.record_packet(
+            PacketDirection::Received,
+            &packet.header.header_type.to_string(),
+        );
+        if RUNTIME_FLAGS.packet_dump() {
+            logger!(
+                INFO,
+                "[DUMP] In from `{}`: {}",
+                &client.addr.read().await,
+                packet.dump()
+            );
+        }
 
-                if !Checksum::check(&packet.header.checksum, &packet.payload) {
-                    logger!(WARN, "[PROTOCOL] Invalid checksum value");
-                    let packet = Packet::new(HeaderType::InvalidChecksum, b"");
-                    self.send_or_disconnect(client, &packet).await;
-                    return;
-                }
-                self.handle_packet(client, &packet).await
-            }
+        let valid = client
+            .checksum
+            .read()
+            .await
+            .verify(packet.header.checksum, &packet.payload);
+        if !valid {
+            logger!(WARN, "[PROTOCOL] Invalid checksum value");
+            let response = Packet::reply_to(&packet, HeaderType::InvalidChecksum, b"");
+            self.malformed_packet(client, &response).await;
+            return;
         }
+
+        let encoding = client.negotiated.read().await.encoding;
+        let packet = match encoding.incoming(packet) {
+            Ok(packet) => packet,
+            Err(error) => {
+                logger!(WARN, "[PROTOCOL] Could not translate a payload ({error})");
+                let response = ErrorResponse::from(&error).packet(HeaderType::InvalidPacketPayload);
+                self.malformed_packet(client, &response).await;
+                return;
+            }
+        };
+        self.handle_packet(client, &packet).await
     }
 
-    /// Sends a packet to the client, retrying up to 3 times if the sending fails.
+    /// Queues a packet on the outbound queue of the client, for its writer task to send.
+    ///
+    /// The packet is re-sealed with the client's negotiated checksum when it differs from the default,
+    /// and large `GameState` payloads are compressed if the client negotiated compression. Its
+    /// payload is translated first for clients that negotiated JSON payloads.
     ///
-    /// If all attempts fail, it disconnects the client and returns an error.
+    /// Only waits when the queue of a slow client is full of packets that cannot be dropped; a full
+    /// queue otherwise drops its oldest state packet (see `OutboundQueue`).
     ///
     /// # Arguments
     /// * `client` - The client to which the packet should be sent.
     /// * `packet` - The packet to send.
     ///
     /// # Returns
-    /// * `Ok(())` if the packet was sent successfully.
-    /// * `Err(NetworkError)` if the packet could not be sent after 3 attempts.
+    /// * `Ok(())` if the packet was queued.
+    /// * `Err(NetworkError)` if the connection of the client was closed for good.
     pub async fn send_packet(
         &self,
         client: Arc<Client>,
         packet: &Packet,
     ) -> Result<(), NetworkError> {
-        let mut tries = 0;
-        while tries < 3 {
-            let addr = client.addr.read().await;
-            let packet_data = packet.wrap_packet();
-            let mut stream_guard = client.write_stream.write().await;
-            if stream_guard.write_all(&packet_data).await.is_err() {
-                tokio::time::sleep(Duration::from_millis(500)).await;
-                tries += 1;
-                continue;
-            }
-
+        let started = Instant::now();
+        let negotiated = *client.negotiated.read().await;
+        let packet = negotiated
+            .outgoing(packet)
+            .sealed(&**client.checksum.read().await, negotiated.compression)
+            .into_owned();
+        let profiler = &self.game_instance.profiler;
+        profiler.record_serialization(started.elapsed());
+        if RUNTIME_FLAGS.packet_dump() {
             logger!(
-                DEBUG,
-                "[PROTOCOL] Sent packet {{ type: {}, size: {} }} to `{addr}`",
-                packet.header.header_type.to_string(),
-                packet_data.len()
+                INFO,
+                "[DUMP] Out to `{}`: {}",
+                &client.addr.read().await,
+                packet.dump()
             );
-            return Ok(());
         }
 
-        Err(NetworkError::PackageWriteError("Unknown error".to_string()))
+        match client.outbound.push(packet).await {
+            Enqueued::Queued => Ok(()),
+            Enqueued::DroppedOldest => {
+                METRICS.record_outbound_drop();
+                logger!(
+                    DEBUG,
+                    "[PROTOCOL] Outbound queue of `{}` is full, dropped its oldest state packet",
+                    &client.addr.read().await
+                );
+                Ok(())
+            }
+            Enqueued::Closed => Err(NetworkError::PackageWriteError(String::from(
+                "The connection is closed",
+            ))),
+        }
+    }
+
+    /// Encodes a payload as CBOR, adding the time it took to the serialization time of the match.
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, serde_cbor::Error> {
+        let started = Instant::now();
+        let payload = serde_cbor::to_vec(value);
+        self.game_instance
+            .profiler
+            .record_serialization(started.elapsed());
+        payload
+    }
+
+    /// Writes a packet taken from the outbound queue of the client to its connection.
+    ///
+    /// Called by the writer task of the client only, so a slow connection never holds up the
+    /// tasks sending to it.
+    ///
+    /// # Arguments
+    /// * `client` - The client to which the packet is written.
+    /// * `packet` - The packet, already sealed by `send_packet`.
+    pub async fn write_packet(&self, client: &Client, packet: &Packet) -> Result<(), NetworkError> {
+        let addr = *client.addr.read().await;
+        let wire_format = WireFormat::for_protocol(&*client.negotiated.read().await);
+        let mut stream_guard = client.write_stream.write().await;
+        let packet_size = wire_format
+            .write_packet(packet, &mut *stream_guard)
+            .await
+            .map_err(|error| NetworkError::PackageWriteError(error.to_string()))?;
+        drop(stream_guard);
+
+        logger!(
+            DEBUG,
+            "[PROTOCOL] Sent packet {{ type: {}, size: {} }} to `{addr}`",
+            packet.header.header_type.to_string(),
+            packet_size
+        );
+        let profiler = &self.game_instance.profiler;
+        profiler.record_bytes_sent(&client.player.read().await.id, packet_size);
+        client.bandwidth.record_sent(packet_size as u64);
+        METRICS.record_bandwidth(&self.server_instance.match_id, packet_size as u64, 0);
+        METRICS.record_packet(
+            PacketDirection::Sent,
+            &packet.header.header_type.to_string(),
+        );
+        Ok(())
+    }
+
+    /// Records the bytes read from a client and enforces the bandwidth caps.
+    ///
+    /// Crossing the soft cap logs a warning; crossing the hard cap sends the client an ERROR
+    /// packet and disconnects it.
+    ///
+    /// # Arguments
+    /// * `client` - The client the bytes were read from.
+    /// * `bytes` - The bytes read, as they were on the wire.
+    ///
+    /// # Returns
+    /// `false` if the client was disconnected for exceeding the hard cap.
+    pub async fn account_received(&self, client: Arc<Client>, bytes: u64) -> bool {
+        if bytes == 0 {
+            return true;
+        }
+
+        METRICS.record_bandwidth(&self.server_instance.match_id, 0, bytes);
+        let caps = BandwidthCaps::from_settings(SETTINGS.get());
+        match client.bandwidth.record_received(bytes, &caps) {
+            CapStatus::Within => true,
+            CapStatus::SoftExceeded(received) => {
+                logger!(
+                    WARN,
+                    "[PROTOCOL] `{}` sent {received} bytes in {}s, over the soft cap",
+                    client.addr.read().await,
+                    caps.window.as_secs()
+                );
+                true
+            }
+            CapStatus::HardExceeded(received) => {
+                logger!(
+                    WARN,
+                    "[PROTOCOL] `{}` sent {received} bytes in {}s, over the hard cap, disconnecting",
+                    client.addr.read().await,
+                    caps.window.as_secs()
+                );
+                let error = NetworkError::BandwidthCapExceeded(received, caps.window.as_secs());
+                let rejection = Rejection::new(RejectionReason::RateLimited, error.to_string())
+                    .retry_after(caps.window);
+                self.send_and_disconnect(client, &rejection.packet(None))
+                    .await;
+                false
+            }
+        }
     }
 
     /// Disconnects a client by setting its connected state to false and logging the disconnection.
@@ -121,11 +279,65 @@ impl Protocol {
     /// This function updates the client's connection status and logs the disconnection event.
     ///
     /// It does not send any packets to the client; it simply marks the client as disconnected.
-    async fn disconnect(&self, client: Arc<Client>) {
-        let addr = client.addr.read().await;
-        logger!(INFO, "[PROTOCOL] Client `{addr}` disconnected");
-        let mut connected_guard = client.connected.write().await;
-        *connected_guard = false;
+    ///
+    /// A player dropping for the first time since they were last connected starts their grace
+    /// period (see `start_disconnect_grace`).
+    pub async fn disconnect(&self, client: Arc<Client>) {
+        logger!(
+            INFO,
+            "[PROTOCOL] Client `{}` disconnected",
+            &client.addr.read().await
+        );
+        *client.connected.write().await = false;
+        let dropped = client.quality.record_disconnect();
+        client.shutdown.notify_waiters();
+
+        if dropped {
+            self.start_disconnect_grace(&client).await;
+        }
+    }
+
+    /// Starts the grace period of a player who dropped during the match.
+    ///
+    /// - Stops the turn clock of the player if the rules of the match say so.
+    /// - Tells the opponent with an `OpponentDisconnected` packet.
+    /// - Forfeits the player if they are still away after `DISCONNECT_GRACE_PERIOD` seconds.
+    ///
+    /// Does nothing before the match started, which `READY_TIMEOUT` covers, or after it ended.
+    async fn start_disconnect_grace(&self, client: &Client) {
+        let server = &self.server_instance;
+        if !server.start_barrier.read().await.started() || server.exit_status.read().await.is_some()
+        {
+            return;
+        }
+
+        let player_id = client.player.read().await.id.clone();
+        let grace = Duration::from_secs(SETTINGS.get().map_or(90, |s| s.disconnect_grace_period));
+        let clock_paused = {
+            let game_state = self.game_instance.game_state.read().await;
+            let paused = game_state.rules.disconnect_clock == DisconnectClock::Pause;
+            if paused {
+                game_state.think_time.write().await.suspend(&player_id);
+            }
+            paused
+        };
+
+        logger!(
+            INFO,
+            "[PROTOCOL] `{player_id}` forfeits unless back within {} seconds",
+            grace.as_secs()
+        );
+        self.game_instance
+            .bus
+            .publish(MatchEvent::PlayerDisconnected(PlayerDisconnected {
+                player_id: player_id.clone(),
+                forfeit_at: Utc::now().timestamp_millis() + grace.as_millis() as i64,
+                clock_paused,
+            }));
+
+        let forfeit =
+            Arc::clone(server).forfeit_after_grace(player_id, client.quality.disconnects(), grace);
+        tokio::spawn(forfeit.in_current_span());
     }
 
     /// Sends a packet to the client, and if it fails, it attempts to disconnect the client.
@@ -152,15 +364,94 @@ impl Protocol {
     }
 
     /// Handles a packet received from a client based on its header type.
+    ///
+    /// Execution time is recorded per header type, and handlers slower than the configured
+    /// threshold are reported with a warning.
     async fn handle_packet(&self, client: Arc<Client>, packet: &Packet) {
+        let started = Instant::now();
+        self.dispatch_packet(client, packet).await;
+        let elapsed = started.elapsed();
+
+        let header_type = packet.header.header_type.to_string();
+        let threshold = SETTINGS.get().map_or(250, |s| s.slow_handler_threshold);
+        let slow = elapsed > Duration::from_millis(threshold);
+        let stats = METRICS.record_handler(&header_type, elapsed, slow);
+
+        if slow {
+            logger!(
+                WARN,
+                "[PROTOCOL] Slow handler: {{ type: {header_type}, elapsed_ms: {}, threshold_ms: {threshold}, avg_ms: {}, max_ms: {}, slow_calls: {}/{} }}",
+                elapsed.as_millis(),
+                stats.average_micros() / 1000,
+                stats.max_micros / 1000,
+                stats.slow_calls,
+                stats.calls
+            );
+        }
+    }
+
+    /// Routes a packet to the handler of its header type.
+    ///
+    /// Actions are rejected until the match started, and, in a wagered match, until every player
+    /// confirmed the stake.
+    async fn dispatch_packet(&self, client: Arc<Client>, packet: &Packet) {
         let message_type = &packet.header.header_type;
+        if matches!(
+            message_type,
+            HeaderType::PlayCard
+                | HeaderType::PromptResponse
+                | HeaderType::ActionBatch
+                | HeaderType::ActivateAbility
+                | HeaderType::RequestUndo
+                | HeaderType::DeclareAttackers
+                | HeaderType::DeclareBlockers
+                | HeaderType::EndTurn
+        ) {
+            let refusal = if !self.server_instance.stake_confirmed().await {
+                Some(GameLogicError::StakeNotConfirmed)
+            } else if !self.server_instance.start_barrier.read().await.started() {
+                Some(GameLogicError::MatchNotStarted)
+            } else {
+                None
+            };
+
+            if let Some(error) = refusal {
+                let rejection = ErrorResponse::from(&error);
+                let response = rejection.reply(packet, HeaderType::ActionRejected);
+                let _ = self.send_packet(client.clone(), &response).await;
+                if let Some(kind) = ViolationKind::of(&error) {
+                    self.audit_rejection(client, packet, kind, &rejection.message)
+                        .await;
+                }
+                return;
+            }
+
+            self.record_replay_packet(&client, packet).await;
+        }
+
         match message_type {
-            HeaderType::Disconnect => self.handle_disconnect(client).await,
-            HeaderType::PlayCard => self.handle_play_card(client, &packet).await,
+            HeaderType::Disconnect => self.handle_disconnect(client, packet).await,
+            HeaderType::Ping => self.handle_ping(client, packet).await,
+            HeaderType::Pong => client.quality.record_pong(), // The read loop already refreshed the last-seen time.
+            HeaderType::PlayCard => self.handle_play_card(client, packet).await,
+            HeaderType::PromptResponse => self.handle_prompt_response(client, packet).await,
+            HeaderType::ActionBatch => self.handle_action_batch(client, packet).await,
+            HeaderType::ActivateAbility => self.handle_activate_ability(client, packet).await,
+            HeaderType::RequestUndo => self.handle_request_undo(client, packet).await,
+            HeaderType::DeclareAttackers => self.handle_declare_attackers(client, packet).await,
+            HeaderType::DeclareBlockers => self.handle_declare_blockers(client, packet).await,
+            HeaderType::EndTurn => self.handle_end_turn(client, packet).await,
+            HeaderType::GetHistory => self.handle_get_history(client, packet).await,
+            HeaderType::ResyncRequest => self.handle_resync(client, packet).await,
+            HeaderType::Emote => self.handle_emote(client, packet).await,
+            HeaderType::Chat => self.handle_chat(client, packet).await,
+            HeaderType::MuteChat => self.handle_mute_chat(client, packet).await,
+            HeaderType::ConfirmStake => self.handle_confirm_stake(client, packet).await,
+            HeaderType::Ready => self.handle_ready(client, packet).await,
             _ => {
                 logger!(WARN, "[PROTOCOL] Invalid header");
-                let packet = Packet::new(HeaderType::InvalidHeader, b"");
-                self.send_or_disconnect(client, &packet).await;
+                let response = Packet::reply_to(packet, HeaderType::InvalidHeader, b"");
+                self.send_or_disconnect(client, &response).await;
             }
         }
     }
@@ -182,11 +473,17 @@ impl Protocol {
         temp_client: Arc<TemporaryClient>,
         packet: &Packet,
     ) -> Result<(), PlayerConnectionError> {
-        let player_authentication = Player::new_connection(&packet.payload).await?;
+        let mut temp = Arc::try_unwrap(temp_client).map_err(|_| {
+            PlayerConnectionError::InternalError("Unable to unwrap temporary client".to_string())
+        })?;
+        let player_authentication = match Player::new_connection(&packet.payload).await {
+            Ok(player_authentication) => player_authentication,
+            Err(error) => return temp.reject(packet, error).await,
+        };
         logger!(
             INFO,
             "[PROTOCOL] Client `{}` has been authenticated as player `{}`.",
-            &temp_client.addr,
+            &temp.addr,
             &player_authentication.username
         );
 
@@ -197,34 +494,350 @@ impl Protocol {
             .read()
             .await;
 
-        if let Some(connected_player) = connected_players.get(&player_authentication.player_id) {
-            match Arc::try_unwrap(temp_client) {
-                Ok(temp) => {
-                    let (read, write) = temp.stream.into_split();
-                    let client = Arc::new(Client::new(
-                        read,
-                        write,
-                        temp.addr,
-                        self.clone(),
-                        connected_player.clone(),
-                    ));
-                    let mut clients_guard = self.server_instance.connected_clients.write().await;
-                    clients_guard.insert(player_authentication.player_id, client.clone());
-
-                    tokio::spawn({
-                        async move {
-                            client.clone().connect().await;
-                        }
-                    });
+        let Some(connected_player) = connected_players.get(&player_authentication.player_id) else {
+            return temp
+                .reject(packet, PlayerConnectionError::PlayerNotConnected)
+                .await;
+        };
+        let Some(negotiated) = temp.negotiated else {
+            return temp
+                .reject(packet, PlayerConnectionError::HandshakeRequired)
+                .await;
+        };
+
+        let (read, write) = temp.stream.into_split();
+        let client = Arc::new(Client::new(
+            read,
+            write,
+            temp.addr,
+            self.clone(),
+            connected_player.clone(),
+            negotiated,
+            &player_authentication.session_token,
+        ));
+        let mut clients_guard = self.server_instance.connected_clients.write().await;
+        clients_guard.insert(player_authentication.player_id.clone(), client.clone());
+        drop(clients_guard);
+
+        let span = player_span(&player_authentication.player_id);
+        tokio::spawn(client.clone().connect().instrument(span));
+        self.acknowledge_connection(client.clone(), packet).await;
+
+        // Clients unaware of the match start are ready as soon as they connect.
+        if !negotiated.supports(FEATURE_MATCH_START) {
+            let _ = self.mark_ready(&player_authentication.player_id).await;
+        }
+
+        Ok(())
+    }
+
+    /// Answers an accepted `Connect` or `Reconnect` with a `ConnectAck` carrying a new session
+    /// token, which the player presents to reconnect without contacting the auth server, along
+    /// with the view of the match, the opponent and the rules the client needs to render it.
+    async fn acknowledge_connection(&self, client: Arc<Client>, packet: &Packet) {
+        let (player_id, token) = {
+            let player = client.player.read().await;
+            let token = self
+                .server_instance
+                .sessions
+                .issue(&player.id, &player.username);
+            (player.id.clone(), token)
+        };
+        let ack = self.connect_ack(&player_id, token).await;
+        match self.encode(&ack) {
+            Ok(payload) => {
+                let response = Packet::reply_to(packet, HeaderType::ConnectAck, &payload);
+                self.send_or_disconnect(client, &response).await;
+            }
+            Err(error) => logger!(
+                ERROR,
+                "[PROTOCOL] Could not serialize the `ConnectAck` of `{player_id}`: {error}"
+            ),
+        }
+    }
+
+    /// Builds the `ConnectAck` of a player.
+    async fn connect_ack(&self, player_id: &PlayerId, token: SessionToken) -> ConnectAck {
+        let game_state = self.game_instance.game_state.read().await;
+        let opponent_id = game_state
+            .opponent_of(player_id)
+            .cloned()
+            .unwrap_or_default();
+        let opponent = match self
+            .game_instance
+            .connected_players
+            .read()
+            .await
+            .get(&opponent_id)
+        {
+            Some(opponent) => {
+                let opponent = opponent.read().await;
+                Some(OpponentProfile {
+                    player_id: opponent.id.clone(),
+                    username: opponent.username.clone(),
+                    level: opponent.level,
+                })
+            }
+            None => None,
+        };
+
+        let format = &self.game_instance.format;
+        let settings = SETTINGS.get();
+        ConnectAck {
+            session_token: token.session_token,
+            expires_at: token.expires_at,
+            match_id: self.server_instance.match_id.clone(),
+            match_type: self.server_instance.match_type.clone(),
+            state: game_state.player_view(player_id).await,
+            opponent,
+            rules: MatchRules {
+                format: format.name.clone(),
+                best_of: format.best_of,
+                starting_health: format.starting_health,
+                turns: format.turns.clone(),
+                rules: game_state.rules.clone(),
+                think_time: settings
+                    .map(|s| s.think_time_visibility)
+                    .unwrap_or_default(),
+                disconnect_grace_ms: settings.map_or(90, |s| s.disconnect_grace_period) * 1000,
+                teams: format.teams.clone(),
+                turn_order: game_state.seating.turn_order(),
+            },
+        }
+    }
+
+    /// Handles a request from a temporary client to watch the match.
+    ///
+    /// The client must have completed the handshake, the match must be spectatable and the
+    /// spectator list must not be full. Unless the match is open to anyone, the auth token of the
+    /// request must belong to an account the match allows, see `ViewerAccess`. Rejected clients
+    /// receive a `ConnectionRejected` packet with the reason.
+    /// Accepted spectators are sent the current public game state, once the spectator delay has
+    /// passed like every later state.
+    ///
+    /// # Arguments
+    /// * `temp_client` - The temporary client that wants to spectate.
+    /// * `packet` - The `Spectate` packet, whose sequence number is echoed.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the client was added to the spectators.
+    /// * `Err(SpectatorError)` if the client was rejected.
+    pub async fn handle_spectate(
+        self: Arc<Self>,
+        temp_client: Arc<TemporaryClient>,
+        packet: &Packet,
+    ) -> Result<(), SpectatorError> {
+        let mut temp = Arc::try_unwrap(temp_client).map_err(|_| {
+            SpectatorError::InternalError("Unable to unwrap temporary client".to_string())
+        })?;
+
+        let max_spectators = SETTINGS.get().map_or(16, |s| s.max_spectators);
+        let admitted = match temp.negotiated {
+            None => Err(SpectatorError::HandshakeRequired),
+            Some(negotiated) if WireFormat::for_protocol(&negotiated) == WireFormat::Legacy => {
+                Err(SpectatorError::HandshakeRequired)
+            }
+            Some(_) if !self.server_instance.viewers.admits_anyone() => {
+                Err(SpectatorError::SpectatingDisabled)
+            }
+            Some(negotiated) => Ok(negotiated),
+        };
+
+        let negotiated = match admitted {
+            Ok(negotiated) => negotiated,
+            Err(error) => return temp.reject(packet, error).await,
+        };
+
+        let request = payload::decode::<SpectateRequest>(&HeaderType::Spectate, &packet.payload)
+            .unwrap_or_default();
+        let viewers = &self.server_instance.viewers;
+        let viewer = match viewers.authorize(&request.auth_token).await {
+            Ok(viewer) => viewer,
+            Err(error) => return temp.reject(packet, error).await,
+        };
+        if let Some(viewer) = viewer {
+            logger!(DEBUG, "[PROTOCOL] `{}` spectates as `{viewer}`", &temp.addr);
+        }
 
-                    Ok(())
+        // The slot is reserved under one guard, so clients spectating at the same time cannot go
+        // past the limit; the guard is released before the first state is sent.
+        let spectator = {
+            let mut spectators = self.server_instance.spectators.write().await;
+            if spectators.len() >= max_spectators {
+                drop(spectators);
+                return temp
+                    .reject(packet, SpectatorError::SpectatorsFull(max_spectators))
+                    .await;
+            }
+            let spectator = Arc::new(Spectator::new(temp.stream, temp.addr, negotiated));
+            spectators.push(Arc::clone(&spectator));
+            spectator
+        };
+
+        if let Some(state) = self.public_state_packet().await {
+            let delay = RUNTIME_FLAGS.spectator_delay();
+            if delay.is_zero() {
+                if let Err(error) = spectator.send(&state).await {
+                    // The spectator never got the match; give its slot back.
+                    self.server_instance
+                        .spectators
+                        .write()
+                        .await
+                        .retain(|other| !Arc::ptr_eq(other, &spectator));
+                    return Err(SpectatorError::InternalError(error.to_string()));
                 }
-                Err(_) => Err(PlayerConnectionError::InternalError(
-                    "Unable to unwrap temporary client".to_string(),
-                )),
+            } else {
+                // Like every later state, the first one lags the match by the spectator delay.
+                let spectator = Arc::clone(&spectator);
+                let send = async move {
+                    tokio::time::sleep(delay).await;
+                    if let Err(error) = spectator.send(&state).await {
+                        logger!(
+                            WARN,
+                            "[PROTOCOL] Could not send the game state to `{}`: {error}",
+                            &spectator.addr
+                        );
+                    }
+                };
+                tokio::spawn(send.in_current_span());
+            }
+        }
+
+        logger!(INFO, "[PROTOCOL] `{}` is now spectating", &spectator.addr);
+        Ok(())
+    }
+
+    /// Tells every client and spectator that the game state changed.
+    pub async fn publish_state(&self) {
+        self.game_instance.bus.publish(MatchEvent::StateChanged);
+    }
+
+    /// Builds the packet telling a player or spectator about an event of the bus, other than a
+    /// state change, whose packet depends on who receives it.
+    pub fn event_packet(event: &MatchEvent) -> Option<Packet> {
+        let (header_type, payload) = match event {
+            MatchEvent::StateChanged => return None,
+            MatchEvent::ScriptSkipped(script) => {
+                (HeaderType::ScriptSkipped, serde_cbor::to_vec(script))
             }
+            MatchEvent::Countdown(countdown) => {
+                (HeaderType::MatchCountdown, serde_cbor::to_vec(countdown))
+            }
+            MatchEvent::TurnStarted(turn) => (HeaderType::TurnStarted, serde_cbor::to_vec(turn)),
+            MatchEvent::MatchEnded(ended) => (HeaderType::MatchEnded, serde_cbor::to_vec(ended)),
+            MatchEvent::PlayerDisconnected(notice) => {
+                (HeaderType::OpponentDisconnected, serde_cbor::to_vec(notice))
+            }
+            MatchEvent::PlayerReconnected(notice) => {
+                (HeaderType::OpponentReconnected, serde_cbor::to_vec(notice))
+            }
+            MatchEvent::Emote(emote) => (HeaderType::Emote, serde_cbor::to_vec(emote)),
+            MatchEvent::Chat(chat) => (HeaderType::Chat, serde_cbor::to_vec(chat)),
+        };
+        match payload {
+            Ok(payload) => Some(Packet::new(header_type, &payload)),
+            Err(error) => {
+                logger!(
+                    ERROR,
+                    "[PROTOCOL] Could not serialize a `{header_type}` packet: {error}"
+                );
+                None
+            }
+        }
+    }
+
+    /// Relays the events of the bus to the spectators until the bus is dropped.
+    ///
+    /// A spectator that lagged behind is sent the latest public state, which carries everything
+    /// it missed.
+    pub async fn relay_to_spectators(self: Arc<Self>) {
+        let mut receiver = self.game_instance.bus.subscribe();
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(_)) => MatchEvent::StateChanged,
+                Err(RecvError::Closed) => break,
+            };
+            if !event.visible_to(Audience::Spectator) {
+                continue;
+            }
+
+            match Self::event_packet(&event) {
+                None => self.broadcast_to_spectators().await,
+                Some(packet) => {
+                    let spectators = &self.server_instance.spectators;
+                    spectator::broadcast_and_prune(spectators, Arc::new(packet)).await;
+                }
+            }
+        }
+    }
+
+    /// Builds a `GameState` packet carrying the view of the match a player is allowed to see.
+    pub async fn player_state_packet(&self, player_id: &PlayerId) -> Option<Packet> {
+        let view = self
+            .game_instance
+            .game_state
+            .read()
+            .await
+            .player_view(player_id)
+            .await?;
+        match self.encode(&view) {
+            Ok(payload) => Some(Packet::new(HeaderType::GameState, &payload)),
+            Err(error) => {
+                logger!(
+                    ERROR,
+                    "[PROTOCOL] Could not serialize the game state of `{player_id}`: {error}"
+                );
+                None
+            }
+        }
+    }
+
+    /// Sends the public game state to every spectator, dropping the ones that can no longer be
+    /// reached.
+    ///
+    /// With a spectator delay set, the state is captured now and sent once the delay has passed,
+    /// so spectators cannot relay live information to a player.
+    pub async fn broadcast_to_spectators(&self) {
+        if self.server_instance.spectators.read().await.is_empty() {
+            return;
+        }
+
+        let Some(state) = self.public_state_packet().await else {
+            return;
+        };
+
+        let spectators = Arc::clone(&self.server_instance.spectators);
+        let delay = RUNTIME_FLAGS.spectator_delay();
+        if delay.is_zero() {
+            spectator::broadcast_and_prune(&spectators, Arc::new(state)).await;
         } else {
-            Err(PlayerConnectionError::PlayerNotConnected)
+            let broadcast = async move {
+                tokio::time::sleep(delay).await;
+                spectator::broadcast_and_prune(&spectators, Arc::new(state)).await;
+            };
+            tokio::spawn(broadcast.in_current_span());
+        }
+    }
+
+    /// Builds a `GameState` packet carrying the public view of the match.
+    async fn public_state_packet(&self) -> Option<Packet> {
+        let view = self
+            .game_instance
+            .game_state
+            .read()
+            .await
+            .public_view()
+            .await;
+        match self.encode(&view) {
+            Ok(payload) => Some(Packet::new(HeaderType::GameState, &payload)),
+            Err(error) => {
+                logger!(
+                    ERROR,
+                    "[PROTOCOL] Could not serialize the public game state: {error}"
+                );
+                None
+            }
         }
     }
 
@@ -253,42 +866,123 @@ impl Protocol {
             &temp_client.addr
         );
 
-        let authenticated_player = Player::reconnection(&packet.payload).await?;
+        let mut temp = Arc::try_unwrap(temp_client).map_err(|_| {
+            PlayerConnectionError::InternalError("Unable to unwrap temporary client".to_string())
+        })?;
+        let authenticated_player =
+            match Player::reconnection(&packet.payload, &self.server_instance.sessions).await {
+                Ok(authenticated_player) => authenticated_player,
+                Err(error) => return temp.reject(packet, error).await,
+            };
         logger!(
             INFO,
             "[PROTOCOL] Client `{}` has been authenticated as player `{}`.",
-            &temp_client.addr,
+            &temp.addr,
             &authenticated_player.username
         );
 
         let players_map = self.server_instance.connected_clients.read().await;
-        if let Some(client) = players_map.get(&authenticated_player.player_id) {
-            match Arc::try_unwrap(temp_client) {
-                Err(_) => Err(PlayerConnectionError::InternalError(
-                    "Unable to unwrap temporary client".to_string(),
-                )),
+        let Some(client) = players_map.get(&authenticated_player.player_id) else {
+            return temp
+                .reject(packet, PlayerConnectionError::PlayerNotConnected)
+                .await;
+        };
 
-                Ok(temp) => {
-                    logger!(
-                        INFO,
-                        "[PROTOCOL] Attempting to reconnect player `{}`",
-                        &client.player.read().await.username
-                    );
+        logger!(
+            INFO,
+            "[PROTOCOL] Attempting to reconnect player `{}`",
+            &client.player.read().await.username
+        );
+
+        let client_clone = Arc::clone(&client);
+        client_clone
+            .reconnect(temp, &authenticated_player.session_token)
+            .await;
+        let span = player_span(&authenticated_player.player_id);
+        tokio::spawn(Arc::clone(client).read_packets().instrument(span));
+        self.acknowledge_connection(Arc::clone(client), packet)
+            .await;
 
-                    let client_clone = Arc::clone(&client);
-                    client_clone.reconnect(temp).await;
+        let player_id = authenticated_player.player_id;
+        self.game_instance
+            .game_state
+            .read()
+            .await
+            .think_time
+            .write()
+            .await
+            .resume(&player_id);
+        self.game_instance
+            .bus
+            .publish(MatchEvent::PlayerReconnected(PlayerReconnected {
+                player_id,
+            }));
+        self.resend_prompts(Arc::clone(client)).await;
+
+        Ok(())
+    }
+
+    async fn handle_ping(&self, client: Arc<Client>, packet: &Packet) {
+        let response = Packet::reply_to(packet, HeaderType::Pong, &packet.payload);
+        self.send_or_disconnect(client, &response).await;
+    }
+
+    /// Pings idle clients and marks unresponsive ones as disconnected. Runs indefinitely.
+    ///
+    /// A client is idle once nothing was received from it for a whole heartbeat interval. It is
+    /// then pinged once per interval, and disconnected after the configured number of unanswered
+    /// pings; the game state packets it misses from then on are queued until it reconnects.
+    pub async fn keepalive(self: Arc<Self>) {
+        let (interval, max_missed) = SETTINGS
+            .get()
+            .map_or((5, 3), |s| (s.heartbeat_interval, s.heartbeat_max_missed));
 
-                    Ok(())
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            let clients: Vec<Arc<Client>> = self
+                .server_instance
+                .connected_clients
+                .read()
+                .await
+                .values()
+                .cloned()
+                .collect();
+
+            for client in clients {
+                if !*client.connected.read().await {
+                    continue;
+                }
+
+                let idle_ms = Utc::now().timestamp_millis() - *client.last_seen.read().await;
+                if idle_ms < (interval * 1000) as i64 {
+                    continue;
+                }
+
+                let mut missed = client.missed_pongs.write().await;
+                if *missed >= max_missed {
+                    drop(missed);
+                    logger!(
+                        WARN,
+                        "[PROTOCOL] `{}` missed {max_missed} pings, marking it as disconnected",
+                        client.addr.read().await
+                    );
+                    self.disconnect(client).await;
+                    continue;
                 }
+
+                *missed += 1;
+                drop(missed);
+                client.quality.record_ping();
+                let _ = self
+                    .send_packet(client, &Packet::new(HeaderType::Ping, b""))
+                    .await;
             }
-        } else {
-            Err(PlayerConnectionError::PlayerNotConnected)
         }
     }
 
-    async fn handle_disconnect(&self, client: Arc<Client>) {
-        let packet = Packet::new(HeaderType::Disconnect, b"");
-        self.send_and_disconnect(client, &packet).await;
+    async fn handle_disconnect(&self, client: Arc<Client>, packet: &Packet) {
+        let response = Packet::reply_to(packet, HeaderType::Disconnect, b"");
+        self.send_and_disconnect(client, &response).await;
     }
 
     /// Handles a play card action from a client during a game turn.
@@ -305,26 +999,21 @@ impl Protocol {
     /// * `client` - The client attempting to play the card.
     /// * `request` - The play card request containing the player and card ID.
     ///
-    /// # Returns
-    /// * `Ok(())` if the action is successful.
-    /// * `Err(GameLogicError)` if any validation or execution step fails.
+    /// Replies with `ActionAccepted` on success or `ActionRejected` carrying the error message,
+    /// both echoing the request's sequence number. If the card needs a target the client did not
+    /// provide, a `PromptRequest` listing the legal targets is sent instead.
     async fn handle_play_card(&self, client: Arc<Client>, packet: &Packet) {
         logger!(DEBUG, "Handle play card ended");
-        match serde_cbor::from_slice::<PlayCardRequest>(&packet.payload) {
+        match payload::decode::<PlayCardRequest>(&HeaderType::PlayCard, &packet.payload) {
             Ok(request) => {
-                if let Err(error) = self
+                let started = Instant::now();
+                let outcome = self
                     .game_instance
                     .clone()
                     .play_card(client.clone(), &request)
-                    .await
-                {
-                    let error_message = error.to_string();
-                    logger!(ERROR, "Play Card Request: {}", error_message.clone());
-                    let error_packet = Packet::new(HeaderType::PlayCard, error_message.as_bytes());
-                    let _ = self.send_packet(client, &error_packet).await;
-                } else {
-                    logger!(INFO, "Play card request was finished successfully");
-                }
+                    .await;
+                self.game_instance.profiler.record_action(started.elapsed());
+                self.send_play_outcome(client, packet, outcome).await;
             }
             Err(error) => {
                 let error_message = error.to_string();
@@ -333,8 +1022,841 @@ impl Protocol {
                     "[PROTOCOL] Play card request: {}",
                     error_message.clone()
                 );
-                let error_packet = Packet::new(HeaderType::PlayCard, error_message.as_bytes());
-                let _ = self.send_packet(client, &error_packet).await;
+                let header_type = error.reply_header(HeaderType::ActionRejected);
+                let error_packet = ErrorResponse::from(&error).reply(packet, header_type);
+                let _ = self.send_packet(client.clone(), &error_packet).await;
+                self.audit_rejection(
+                    client,
+                    packet,
+                    ViolationKind::MalformedPayload,
+                    &error_message,
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Handles the activation of the ability of a card on the player's board.
+    async fn handle_activate_ability(&self, client: Arc<Client>, packet: &Packet) {
+        let request = payload::decode::<ActivateAbilityRequest>(
+            &HeaderType::ActivateAbility,
+            &packet.payload,
+        );
+        match request {
+            Ok(request) => {
+                let started = Instant::now();
+                let outcome = self
+                    .game_instance
+                    .clone()
+                    .activate_ability(client.clone(), &request)
+                    .await;
+                self.game_instance.profiler.record_action(started.elapsed());
+                self.send_play_outcome(client, packet, outcome).await;
+            }
+            Err(error) => {
+                let error_message = error.to_string();
+                logger!(
+                    ERROR,
+                    "[PROTOCOL] Activate ability request: {error_message}"
+                );
+                let header_type = error.reply_header(HeaderType::ActionRejected);
+                let error_packet = ErrorResponse::from(&error).reply(packet, header_type);
+                let _ = self.send_packet(client.clone(), &error_packet).await;
+                self.audit_rejection(
+                    client,
+                    packet,
+                    ViolationKind::MalformedPayload,
+                    &error_message,
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Handles the attack of a player.
+    ///
+    /// In matches with blockers, the defender is sent a `DeclareAttackers` packet carrying the
+    /// blockers window; the attack resolves when they answer or when the window closes.
+    async fn handle_declare_attackers(&self, client: Arc<Client>, packet: &Packet) {
+        let request = match payload::decode::<DeclareAttackersRequest>(
+            &HeaderType::DeclareAttackers,
+            &packet.payload,
+        ) {
+            Ok(request) => request,
+            Err(error) => {
+                let error_message = error.to_string();
+                logger!(
+                    ERROR,
+                    "[PROTOCOL] Declare attackers request: {error_message}"
+                );
+                let header_type = error.reply_header(HeaderType::ActionRejected);
+                let error_packet = ErrorResponse::from(&error).reply(packet, header_type);
+                let _ = self.send_packet(client.clone(), &error_packet).await;
+                self.audit_rejection(
+                    client,
+                    packet,
+                    ViolationKind::MalformedPayload,
+                    &error_message,
+                )
+                .await;
+                return;
+            }
+        };
+
+        let started = Instant::now();
+        let declared = self
+            .game_instance
+            .declare_attackers(client.clone(), &request)
+            .await;
+        self.game_instance.profiler.record_action(started.elapsed());
+
+        let outcome = match declared {
+            Ok(Some(window)) => {
+                self.notify_defender(&window).await;
+                Ok(PlayOutcome::Resolved)
+            }
+            Ok(None) => Ok(PlayOutcome::Resolved),
+            Err(error) => Err(error),
+        };
+        self.send_play_outcome(client, packet, outcome).await;
+    }
+
+    /// Sends the defender of an attack a `DeclareAttackers` packet carrying the blockers window,
+    /// if they are connected.
+    pub async fn notify_defender(&self, window: &CombatWindow) {
+        let defender = self
+            .server_instance
+            .connected_clients
+            .read()
+            .await
+            .get(&window.defender_id)
+            .cloned();
+        if let (Some(defender), Ok(payload)) = (defender, self.encode(window)) {
+            let notice = Packet::new(HeaderType::DeclareAttackers, &payload);
+            self.send_or_disconnect(defender, &notice).await;
+        }
+    }
+
+    /// Handles the blockers declared by the defending player and resolves the attack.
+    async fn handle_declare_blockers(&self, client: Arc<Client>, packet: &Packet) {
+        match payload::decode::<DeclareBlockersRequest>(
+            &HeaderType::DeclareBlockers,
+            &packet.payload,
+        ) {
+            Ok(request) => {
+                let started = Instant::now();
+                let outcome = self
+                    .game_instance
+                    .declare_blockers(client.clone(), &request)
+                    .await;
+                self.game_instance.profiler.record_action(started.elapsed());
+                self.send_play_outcome(client, packet, outcome).await;
+            }
+            Err(error) => {
+                let error_message = error.to_string();
+                logger!(
+                    ERROR,
+                    "[PROTOCOL] Declare blockers request: {error_message}"
+                );
+                let header_type = error.reply_header(HeaderType::ActionRejected);
+                let error_packet = ErrorResponse::from(&error).reply(packet, header_type);
+                let _ = self.send_packet(client.clone(), &error_packet).await;
+                self.audit_rejection(
+                    client,
+                    packet,
+                    ViolationKind::MalformedPayload,
+                    &error_message,
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Handles a player ending their turn; the next player's turn starts before the reply.
+    async fn handle_end_turn(&self, client: Arc<Client>, packet: &Packet) {
+        let started = Instant::now();
+        let outcome = self.game_instance.end_turn(client.clone()).await;
+        self.game_instance.profiler.record_action(started.elapsed());
+        self.send_play_outcome(client, packet, outcome).await;
+    }
+
+    /// Handles a player's request to undo their last play.
+    ///
+    /// The opponent is sent a `ConfirmUndo` prompt and the request is accepted once the prompt is
+    /// out; the play is only taken back when the opponent accepts. Opponents whose client cannot
+    /// answer prompts, or who are disconnected, cannot consent and the request is rejected.
+    async fn handle_request_undo(&self, client: Arc<Client>, packet: &Packet) {
+        let prompt = match self.game_instance.request_undo(client.clone()).await {
+            Ok(prompt) => prompt,
+            Err(error) => {
+                let response =
+                    ErrorResponse::from(&error).reply(packet, HeaderType::ActionRejected);
+                let _ = self.send_packet(client, &response).await;
+                return;
+            }
+        };
+
+        let opponent = self
+            .server_instance
+            .connected_clients
+            .read()
+            .await
+            .get(&prompt.player_id)
+            .cloned();
+        let mut sent = false;
+        if let Some(opponent) = opponent {
+            if opponent.negotiated.read().await.supports(FEATURE_PROMPTS) {
+                if let Ok(payload) = self.encode(&prompt) {
+                    let request = Packet::new(HeaderType::PromptRequest, &payload);
+                    sent = self.send_packet(opponent, &request).await.is_ok();
+                }
+            }
+        }
+
+        let response = match sent {
+            true => Packet::reply_to(packet, HeaderType::ActionAccepted, b""),
+            false => {
+                let game_state = self.game_instance.game_state.read().await;
+                game_state.prompts.write().await.cancel(prompt.id);
+                let error = ErrorResponse::from(&GameLogicError::UndoConsentUnavailable);
+                error.reply(packet, HeaderType::ActionRejected)
+            }
+        };
+        let _ = self.send_packet(client, &response).await;
+    }
+
+    /// Handles a player's answer to a pending prompt and resumes the action that opened it.
+    async fn handle_prompt_response(&self, client: Arc<Client>, packet: &Packet) {
+        match payload::decode::<PromptResponse>(&HeaderType::PromptResponse, &packet.payload) {
+            Ok(response) => {
+                let started = Instant::now();
+                let outcome = self
+                    .game_instance
+                    .clone()
+                    .answer_prompt(client.clone(), &response)
+                    .await;
+                self.game_instance.profiler.record_action(started.elapsed());
+                self.send_play_outcome(client, packet, outcome).await;
+            }
+            Err(error) => {
+                let error_message = error.to_string();
+                logger!(
+                    ERROR,
+                    "[PROTOCOL] Prompt response: {}",
+                    error_message.clone()
+                );
+                let header_type = error.reply_header(HeaderType::ActionRejected);
+                let error_packet = ErrorResponse::from(&error).reply(packet, header_type);
+                let _ = self.send_packet(client.clone(), &error_packet).await;
+                self.audit_rejection(
+                    client,
+                    packet,
+                    ViolationKind::MalformedPayload,
+                    &error_message,
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Records an action packet into the match replay, if replays are enabled.
+    async fn record_replay_packet(&self, client: &Client, packet: &Packet) {
+        let game_state = self.game_instance.game_state.read().await;
+        if let Some(replay) = &game_state.replay {
+            let record = ReplayRecord::Packet {
+                player_id: client.player.read().await.id.clone(),
+                header_type: packet.header.header_type.clone() as u8,
+                sequence: packet.header.sequence,
+                payload: packet.payload.to_vec(),
+            };
+            replay.record(game_state.rounds, record);
+        }
+    }
+
+    /// Answers a `GetHistory` request with the requested events of the event log.
+    ///
+    /// An empty payload asks for the latest events, up to the size of the log.
+    async fn handle_get_history(&self, client: Arc<Client>, packet: &Packet) {
+        let request = match packet.payload.is_empty() {
+            true => Ok(HistoryRequest::default()),
+            false => payload::decode::<HistoryRequest>(&HeaderType::GetHistory, &packet.payload),
+        };
+
+        let response = match request {
+            Ok(request) => {
+                let events = {
+                    let game_state = self.game_instance.game_state.read().await;
+                    let events = game_state.events.read().await;
+                    events.since(request.since, request.limit.unwrap_or(MAX_EVENTS))
+                };
+                match self.encode(&events) {
+                    Ok(payload) => Packet::reply_to(packet, HeaderType::History, &payload),
+                    Err(error) => ErrorResponse::new(ErrorCode::Internal, error.to_string())
+                        .reply(packet, HeaderType::ERROR),
+                }
+            }
+            Err(error) => {
+                ErrorResponse::from(&error).reply(packet, HeaderType::InvalidPacketPayload)
+            }
+        };
+
+        let _ = self.send_packet(client, &response).await;
+    }
+
+    /// Answers a `ResyncRequest` with a full snapshot of the match as the player sees it.
+    ///
+    /// The packets queued while the player was disconnected are dropped first: the snapshot
+    /// supersedes them, and some notices may already have been evicted from the queue.
+    async fn handle_resync(&self, client: Arc<Client>, packet: &Packet) {
+        let dropped = {
+            let mut missed_packets = client.missed_packets.write().await;
+            let dropped = missed_packets.len();
+            missed_packets.clear();
+            dropped
+        };
+
+        let player_id = client.player.read().await.id.clone();
+        let snapshot = self
+            .game_instance
+            .game_state
+            .read()
+            .await
+            .resync_snapshot(&player_id)
+            .await;
+        let response = match snapshot.map(|snapshot| self.encode(&snapshot)) {
+            Some(Ok(payload)) => Packet::reply_to(packet, HeaderType::ResyncResponse, &payload),
+            Some(Err(error)) => ErrorResponse::new(ErrorCode::Internal, error.to_string())
+                .reply(packet, HeaderType::ERROR),
+            None => ErrorResponse::from(&GameLogicError::PlayerNotFound)
+                .reply(packet, HeaderType::ERROR),
+        };
+
+        logger!(
+            INFO,
+            "[PROTOCOL] Resynchronized `{player_id}`, dropping {dropped} missed packet(s)"
+        );
+        self.send_or_disconnect(client, &response).await;
+    }
+
+    /// Relays an emote to the opponent and the spectators.
+    ///
+    /// Nothing is sent back for a relayed emote; a refused one is answered with `ActionRejected`
+    /// and the reason.
+    async fn handle_emote(&self, client: Arc<Client>, packet: &Packet) {
+        let request = match payload::decode::<EmoteRequest>(&HeaderType::Emote, &packet.payload) {
+            Ok(request) => request,
+            Err(error) => {
+                let header_type = error.reply_header(HeaderType::ActionRejected);
+                let response = ErrorResponse::from(&error).reply(packet, header_type);
+                let _ = self.send_packet(client, &response).await;
+                return;
+            }
+        };
+
+        let player_id = client.player.read().await.id.clone();
+        if let Err(error) = self
+            .server_instance
+            .social
+            .check_emote(&player_id, &request)
+        {
+            logger!(DEBUG, "[PROTOCOL] Emote of `{player_id}` refused: {error}");
+            let response = ErrorResponse::from(&error).reply(packet, HeaderType::ActionRejected);
+            let _ = self.send_packet(client, &response).await;
+            return;
+        }
+
+        self.game_instance.bus.publish(MatchEvent::Emote(EmoteSent {
+            player_id,
+            emote_id: request.emote_id,
+            ping: request.ping,
+        }));
+    }
+
+    /// Relays a chat message to the opponent, and to the spectators when `CHAT_TO_SPECTATORS` is
+    /// set, after it went through the profanity filter.
+    ///
+    /// Nothing is sent back for a relayed message; a refused one is answered with
+    /// `ActionRejected` and the reason.
+    async fn handle_chat(&self, client: Arc<Client>, packet: &Packet) {
+        let request = match payload::decode::<ChatRequest>(&HeaderType::Chat, &packet.payload) {
+            Ok(request) => request,
+            Err(error) => {
+                let header_type = error.reply_header(HeaderType::ActionRejected);
+                let response = ErrorResponse::from(&error).reply(packet, header_type);
+                let _ = self.send_packet(client, &response).await;
+                return;
+            }
+        };
+
+        let player_id = client.player.read().await.id.clone();
+        let social = &self.server_instance.social;
+        let message = match social.check_chat(&player_id, &request) {
+            Ok(message) => message,
+            Err(error) => {
+                logger!(
+                    DEBUG,
+                    "[PROTOCOL] Chat message of `{player_id}` refused: {error}"
+                );
+                let response =
+                    ErrorResponse::from(&error).reply(packet, HeaderType::ActionRejected);
+                let _ = self.send_packet(client, &response).await;
+                return;
+            }
+        };
+
+        self.game_instance.bus.publish(MatchEvent::Chat(ChatSent {
+            player_id,
+            message,
+            public: social.chat_to_spectators(),
+        }));
+    }
+
+    /// Mutes or unmutes the chat of another player for the sender, answering `ActionAccepted`.
+    async fn handle_mute_chat(&self, client: Arc<Client>, packet: &Packet) {
+        let response = match payload::decode::<MuteRequest>(&HeaderType::MuteChat, &packet.payload)
+        {
+            Ok(request) => {
+                let player_id = client.player.read().await.id.clone();
+                let social = &self.server_instance.social;
+                social.mute(&player_id, &request.player_id, request.muted);
+                Packet::reply_to(packet, HeaderType::ActionAccepted, b"")
+            }
+            Err(error) => {
+                let header_type = error.reply_header(HeaderType::ActionRejected);
+                ErrorResponse::from(&error).reply(packet, header_type)
+            }
+        };
+        let _ = self.send_packet(client, &response).await;
+    }
+
+    /// Handles a player's acknowledgement of the stake of a wagered match.
+    ///
+    /// Replies with `ActionAccepted`, or `ActionRejected` if the match has no stake or the client
+    /// acknowledged a different one.
+    async fn handle_confirm_stake(&self, client: Arc<Client>, packet: &Packet) {
+        let request =
+            payload::decode::<ConfirmStakeRequest>(&HeaderType::ConfirmStake, &packet.payload);
+        let request = match request {
+            Ok(request) => request,
+            Err(error) => {
+                let header_type = error.reply_header(HeaderType::ActionRejected);
+                let response = ErrorResponse::from(&error).reply(packet, header_type);
+                let _ = self.send_packet(client, &response).await;
+                return;
+            }
+        };
+
+        let player_id = client.player.read().await.id.clone();
+        let confirmed = match &self.server_instance.wager {
+            Some(wager) => wager.write().await.confirm(&player_id, &request),
+            None => Err(GameLogicError::NoStake),
+        };
+
+        let response = match confirmed {
+            Ok(all_confirmed) => {
+                logger!(INFO, "[PROTOCOL] Player `{player_id}` confirmed the stake");
+                if all_confirmed {
+                    logger!(INFO, "[PROTOCOL] Every player confirmed the stake");
+                }
+                Packet::reply_to(packet, HeaderType::ActionAccepted, b"")
+            }
+            Err(error) => ErrorResponse::from(&error).reply(packet, HeaderType::ActionRejected),
+        };
+
+        let _ = self.send_packet(client, &response).await;
+    }
+
+    /// Handles a `Ready` packet, starting the match if every player is now ready.
+    async fn handle_ready(&self, client: Arc<Client>, packet: &Packet) {
+        let player_id = client.player.read().await.id.clone();
+        let response = match self.mark_ready(&player_id).await {
+            Ok(()) => Packet::reply_to(packet, HeaderType::ActionAccepted, b""),
+            Err(error) => ErrorResponse::from(&error).reply(packet, HeaderType::ActionRejected),
+        };
+
+        let _ = self.send_packet(client, &response).await;
+    }
+
+    /// Marks a player as ready for the match to start.
+    ///
+    /// Once the last player is ready, every player is sent a `MatchStart` packet and the first
+    /// turn starts when the `MATCH_START_COUNTDOWN` elapsed.
+    ///
+    /// # Returns
+    /// * `Ok(())` - If the player is now ready.
+    /// * `Err(GameLogicError)` - If the player is not part of the match.
+    pub async fn mark_ready(&self, player_id: &PlayerId) -> Result<(), GameLogicError> {
+        let countdown = SETTINGS.get().map_or(3, |s| s.match_start_countdown);
+        let countdown = Duration::from_secs(countdown);
+        let starts_in = {
+            let mut start_barrier = self.server_instance.start_barrier.write().await;
+            match start_barrier.mark_ready(player_id, countdown)? {
+                true => start_barrier.starts_in(),
+                false => None,
+            }
+        };
+
+        logger!(INFO, "[PROTOCOL] Player `{player_id}` is ready");
+        if let Some(starts_in) = starts_in {
+            self.start_match(starts_in).await;
+        }
+        Ok(())
+    }
+
+    /// Publishes the countdown of the match every `SCHEDULE_COUNTDOWN_INTERVAL` seconds, for the
+    /// spectators and the players that negotiated the match start, until the scheduled start time
+    /// or until every player is ready. Returns straight away for matches that are not scheduled.
+    pub async fn count_down_to_schedule(self: Arc<Self>) {
+        let interval = SETTINGS.get().map_or(10, |s| s.schedule_countdown_interval);
+        let interval = Duration::from_secs(interval.max(1));
+        loop {
+            let countdown = {
+                let start_barrier = self.server_instance.start_barrier.read().await;
+                match start_barrier.all_ready() {
+                    true => None,
+                    false => start_barrier.countdown(),
+                }
+            };
+            let Some(countdown) = countdown else {
+                return;
+            };
+
+            let remaining = Duration::from_millis(countdown.remaining_ms);
+            self.game_instance
+                .bus
+                .publish(MatchEvent::Countdown(countdown));
+            tokio::time::sleep(interval.min(remaining)).await;
+        }
+    }
+
+    /// Sends every player that negotiated the match start a `MatchStart` packet with its view of
+    /// the initial state, then starts the turn of the first player once the countdown elapsed.
+    async fn start_match(&self, countdown: Duration) {
+        let starts_at = Utc::now().timestamp_millis() + countdown.as_millis() as i64;
+        let clients: Vec<_> = self
+            .server_instance
+            .connected_clients
+            .read()
+            .await
+            .iter()
+            .map(|(id, client)| (id.clone(), Arc::clone(client)))
+            .collect();
+
+        let game_state = self.game_instance.game_state.read().await;
+        let first_player = game_state.first_player().cloned().unwrap_or_default();
+        let mut packets = Vec::with_capacity(clients.len());
+        for (player_id, client) in clients {
+            if !client.negotiated.read().await.supports(FEATURE_MATCH_START) {
+                continue;
+            }
+
+            let Some(state) = game_state.player_view(&player_id).await else {
+                continue;
+            };
+            let match_start = MatchStart {
+                countdown_ms: countdown.as_millis() as u64,
+                starts_at,
+                first_player: first_player.clone(),
+                state,
+            };
+            match self.encode(&match_start) {
+                Ok(payload) => {
+                    packets.push((client, Packet::new(HeaderType::MatchStart, &payload)))
+                }
+                Err(error) => logger!(
+                    ERROR,
+                    "[PROTOCOL] Could not serialize the match start of `{player_id}`: {error}"
+                ),
+            }
+        }
+        drop(game_state);
+
+        logger!(
+            INFO,
+            "[PROTOCOL] Every player is ready, `{first_player}` starts in {}ms",
+            countdown.as_millis()
+        );
+        for (client, packet) in packets {
+            self.send_or_disconnect(client, &packet).await;
+        }
+
+        let game_instance = Arc::clone(&self.game_instance);
+        let start_turn = async move {
+            tokio::time::sleep(countdown).await;
+            game_instance.start_next_turn().await;
+        };
+        tokio::spawn(start_turn.in_current_span());
+    }
+
+    /// Handles a batch of actions, replying once for the whole batch.
+    ///
+    /// A rejection names the index of the first failing sub-action; none of the batch is applied.
+    /// Clients that did not negotiate the action batch feature have their batches rejected.
+    async fn handle_action_batch(&self, client: Arc<Client>, packet: &Packet) {
+        if !client
+            .negotiated
+            .read()
+            .await
+            .supports(FEATURE_ACTION_BATCH)
+        {
+            let error_packet = ErrorResponse::new(
+                ErrorCode::NotNegotiated,
+                "Action batches were not negotiated",
+            )
+            .reply(packet, HeaderType::ActionRejected);
+            let _ = self.send_packet(client, &error_packet).await;
+            return;
+        }
+
+        match payload::decode::<ActionBatchRequest>(&HeaderType::ActionBatch, &packet.payload) {
+            Ok(request) => {
+                let started = Instant::now();
+                let outcome = self
+                    .game_instance
+                    .clone()
+                    .play_batch(client.clone(), &request)
+                    .await;
+                self.game_instance.profiler.record_action(started.elapsed());
+                self.send_play_outcome(client, packet, outcome).await;
+            }
+            Err(error) => {
+                let error_message = error.to_string();
+                logger!(ERROR, "[PROTOCOL] Action batch: {}", error_message.clone());
+                let header_type = error.reply_header(HeaderType::ActionRejected);
+                let error_packet = ErrorResponse::from(&error).reply(packet, header_type);
+                let _ = self.send_packet(client.clone(), &error_packet).await;
+                self.audit_rejection(
+                    client,
+                    packet,
+                    ViolationKind::MalformedPayload,
+                    &error_message,
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Replies to an action request according to its outcome.
+    ///
+    /// # Arguments
+    /// * `client` - The client that sent the request.
+    /// * `request` - The request packet, whose sequence number is echoed.
+    /// * `outcome` - The result of handling the action.
+    async fn send_play_outcome(
+        &self,
+        client: Arc<Client>,
+        request: &Packet,
+        outcome: Result<PlayOutcome, GameLogicError>,
+    ) {
+        let mut outcome = outcome;
+        if !client.negotiated.read().await.supports(FEATURE_PROMPTS) {
+            // Clients without prompt support get the default choice straight away.
+            let mut answered = 0;
+            while let Ok(PlayOutcome::Prompted(prompt)) = &outcome {
+                if answered == MAX_DEFAULT_ANSWERS {
+                    let game_state = self.game_instance.game_state.read().await;
+                    game_state.prompts.write().await.cancel(prompt.id);
+                    outcome = Err(GameLogicError::TooManyPrompts(answered));
+                    break;
+                }
+
+                answered += 1;
+                let response = PromptResponse {
+                    prompt_id: prompt.id,
+                    choice: prompt.options.first().cloned().unwrap_or_default(),
+                };
+                outcome = self
+                    .game_instance
+                    .clone()
+                    .answer_prompt(client.clone(), &response)
+                    .await;
+            }
+        }
+
+        let resolved = matches!(outcome, Ok(PlayOutcome::Resolved));
+        let response = match outcome {
+            Ok(PlayOutcome::Resolved) => {
+                logger!(INFO, "Play card request was finished successfully");
+                self.server_instance.check_match_end().await;
+                Packet::reply_to(request, HeaderType::ActionAccepted, b"")
+            }
+            Ok(PlayOutcome::Prompted(prompt)) => {
+                logger!(
+                    DEBUG,
+                    "[PROTOCOL] Prompting player `{}` ({:?})",
+                    &prompt.player_id,
+                    &prompt.kind
+                );
+                match self.encode(&prompt) {
+                    Ok(payload) => Packet::reply_to(request, HeaderType::PromptRequest, &payload),
+                    Err(error) => ErrorResponse::new(ErrorCode::Internal, error.to_string())
+                        .reply(request, HeaderType::ERROR),
+                }
+            }
+            Err(error) => {
+                let rejection = ErrorResponse::from(&error);
+                logger!(ERROR, "Play Card Request: {}", &rejection.message);
+                let response = rejection.reply(request, HeaderType::ActionRejected);
+                let _ = self.send_packet(client.clone(), &response).await;
+                if let Some(kind) = ViolationKind::of(&error) {
+                    self.audit_rejection(client, request, kind, &rejection.message)
+                        .await;
+                }
+                return;
+            }
+        };
+
+        let _ = self.send_packet(client, &response).await;
+        // The acting player hears back first; the new state reaches the players and the
+        // spectators from their own relay tasks.
+        if resolved {
+            self.publish_state().await;
+        }
+    }
+
+    /// Records a rejected action of a player in the audit trail of the match.
+    ///
+    /// A player making more violations than `AUDIT_VIOLATION_LIMIT` allows within
+    /// `AUDIT_VIOLATION_WINDOW` is flagged and disconnected; the flag is reported with the match
+    /// result.
+    async fn audit_rejection(
+        &self,
+        client: Arc<Client>,
+        request: &Packet,
+        kind: ViolationKind,
+        details: &str,
+    ) {
+        let player_id = client.player.read().await.id.clone();
+        let limits = AuditLimits::from_settings(SETTINGS.get());
+        let action = request.header.header_type.to_string();
+        let audit = &self.server_instance.audit;
+        if !audit.record(&player_id, kind, &action, details, &limits) {
+            return;
+        }
+
+        logger!(
+            WARN,
+            "[PROTOCOL] `{player_id}` made more than {} rejected actions in {}s, flagged and disconnecting",
+            limits.max_violations,
+            limits.window.as_secs()
+        );
+        let error = NetworkError::TooManyViolations(limits.max_violations, limits.window.as_secs());
+        let rejection = Rejection::new(RejectionReason::Flagged, error.to_string());
+        self.send_and_disconnect(client, &rejection.packet(None))
+            .await;
+    }
+
+    /// Answers a packet the client sent that could not be decoded, and counts it against the
+    /// client. A client sending more malformed packets over the match than `MAX_MALFORMED_PACKETS`
+    /// allows is sent a `Malformed` rejection and disconnected.
+    ///
+    /// # Arguments
+    /// * `client` - The client that sent the packet.
+    /// * `response` - The packet answering the malformed one.
+    ///
+    /// # Returns
+    /// `true` if the client is being disconnected.
+    pub async fn malformed_packet(&self, client: Arc<Client>, response: &Packet) -> bool {
+        let limit = SETTINGS.get().map_or(5, |s| s.max_malformed_packets);
+        let count = {
+            let mut count = client.malformed_packets.write().await;
+            *count += 1;
+            *count
+        };
+        if limit == 0 || count <= limit {
+            self.send_or_disconnect(client, response).await;
+            return false;
+        }
+
+        logger!(
+            WARN,
+            "[PROTOCOL] `{}` sent more than {limit} malformed packets, disconnecting",
+            &client.addr.read().await
+        );
+        let error = NetworkError::TooManyMalformedPackets(limit);
+        let rejection = Rejection::new(RejectionReason::Malformed, error.to_string());
+        self.send_and_disconnect(client, &rejection.packet(None))
+            .await;
+        true
+    }
+
+    /// Re-sends the prompts that were pending when a client disconnected, with their deadlines
+    /// pushed back so the player has time to answer.
+    async fn resend_prompts(&self, client: Arc<Client>) {
+        let player_id = client.player.read().await.id.clone();
+        let grace = SETTINGS.get().map_or(15, |s| s.prompt_reconnect_grace);
+        let prompts = {
+            let game_state = self.game_instance.game_state.read().await;
+            let mut prompts_guard = game_state.prompts.write().await;
+            prompts_guard.refresh_for_player(&player_id, grace)
+        };
+
+        for prompt in prompts {
+            if let Ok(payload) = self.encode(&prompt) {
+                let packet = Packet::new(HeaderType::PromptRequest, &payload);
+                self.send_or_disconnect(Arc::clone(&client), &packet).await;
+            }
+        }
+    }
+
+    /// Applies the default resolution to prompts whose deadline has passed, resolves attacks
+    /// whose blockers window closed unanswered, and ends turns that ran out of time.
+    ///
+    /// Runs indefinitely, checking once per second.
+    pub async fn expire_prompts(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            if self.game_instance.resolve_expired_combat().await {
+                self.server_instance.check_match_end().await;
+                self.publish_state().await;
+            }
+            if self.game_instance.end_expired_turn().await {
+                self.publish_state().await;
+            }
+
+            let expired = {
+                let game_state = self.game_instance.game_state.read().await;
+                let mut prompts = game_state.prompts.write().await;
+                prompts.take_expired(Utc::now().timestamp_millis())
+            };
+
+            for pending in expired {
+                let player_id = pending.prompt.player_id.clone();
+                let client = self
+                    .server_instance
+                    .connected_clients
+                    .read()
+                    .await
+                    .get(&player_id)
+                    .cloned();
+                match (client, pending.default_choice()) {
+                    (Some(client), Some(choice)) => {
+                        logger!(
+                            INFO,
+                            "[PROTOCOL] Prompt `{}` expired, resolving with `{choice}`",
+                            pending.prompt.id
+                        );
+                        let outcome = self
+                            .game_instance
+                            .clone()
+                            .resume_prompt(client, pending, choice)
+                            .await;
+                        match outcome {
+                            Ok(PlayOutcome::Resolved) => self.publish_state().await,
+                            Ok(PlayOutcome::Prompted(_)) => {}
+                            Err(error) => logger!(
+                                ERROR,
+                                "[PROTOCOL] Default prompt resolution failed: {error}"
+                            ),
+                        }
+                    }
+                    _ => logger!(
+                        WARN,
+                        "[PROTOCOL] Prompt `{}` expired without a default resolution",
+                        pending.prompt.id
+                    ),
+                }
             }
         }
     }
@@ -348,16 +1870,13 @@ impl Protocol {
     /// * `client` - The client to which the missed packets should be sent.
     pub async fn send_missed_packets(&self, client: Arc<Client>) {
         let mut packets_lock = client.missed_packets.write().await;
-        loop {
-            if let Some(packet) = packets_lock.pop_front() {
-                let client_clone = Arc::clone(&client);
-                self.send_or_disconnect(client_clone, &packet).await;
-                tokio::time::interval(Duration::from_micros(30))
-                    .tick()
-                    .await;
-            } else {
-                break;
-            }
+        for packet in packets_lock.drain() {
+            let client_clone = Arc::clone(&client);
+            self.send_or_disconnect(client_clone, &packet).await;
+            client.quality.record_resent(1);
+            tokio::time::interval(Duration::from_micros(30))
+                .tick()
+                .await;
         }
         logger!(
             INFO,
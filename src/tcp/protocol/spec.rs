@@ -0,0 +1,884 @@
+use crate::admin::remote::{AdminRequest, AdminResponse};
+use crate::game::combat::{Block, CombatWindow};
+use crate::game::draft::DraftView;
+use crate::game::entity::deck::Deck;
+use crate::game::event_bus::{
+    ChatSent, EmoteSent, MatchEnded, PlayerDisconnected, PlayerReconnected, TurnStarted,
+};
+use crate::game::event_log::GameEvent;
+use crate::game::game_state::{PlayerGameStateView, ResyncSnapshot};
+use crate::game::prompt::Prompt;
+use crate::game::script_blocklist::SkippedScript;
+use crate::game::sideboard::{SideboardSwap, SideboardView};
+use crate::game::start_barrier::{MatchCountdown, MatchStart};
+use crate::models::client_requests::{
+    ActionBatchRequest, ActivateAbilityRequest, BatchedAction, BoardPing, ChatRequest,
+    ConfirmStakeRequest, ConnectionRequest, DeclareAttackersRequest, DeclareBlockersRequest,
+    DraftPickRequest, EmoteRequest, GetReplayRequest, HistoryRequest, JoinPrivateMatchRequest,
+    MuteRequest, PlayCardRequest, PromptResponse, ReconnectionRequest, SideboardRequest,
+    SpectateRequest,
+};
+use crate::models::connect_ack::ConnectAck;
+use crate::models::ids::{CardDefId, CardInstanceId, MatchId, PlayerId};
+use crate::models::init_server::{InitServerRequest, PreloadPlayer};
+use crate::tcp::error_response::{ErrorCode, ErrorResponse};
+use crate::tcp::handshake::{
+    self, HandshakeRequest, MIN_PAYLOAD_VERSION, MIN_PROTOCOL_VERSION, PAYLOAD_VERSION,
+    PROTOCOL_VERSION, SERVER_FEATURES,
+};
+use crate::tcp::header::{HeaderType, HEADER_SIZE, MAX_PAYLOAD_SIZE};
+use crate::tcp::lobby::{LobbyStatus, PrivateMatchCreated};
+use crate::tcp::payload::{self, PayloadLimits, VersionedRequest};
+use crate::tcp::rejection::{Rejection, RejectionReason};
+use crate::tcp::viewers::ReplayResponse;
+use crate::utils::errors::{DeckIllegal, GameLogicError, PayloadError};
+use serde::Serialize;
+use std::path::Path;
+
+/// Usage of the `protocol-spec` subcommand.
+pub const USAGE: &str = "usage: tcp-server protocol-spec [<output.json>]";
+
+/// Who sends a packet.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    ClientToServer,
+    ServerToClient,
+    Both,
+}
+
+/// How the payload of a packet is encoded.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// No payload; whatever is sent is ignored.
+    Empty,
+    /// UTF-8 text, meant for logs.
+    Text,
+    /// Bytes with no structure, echoed back as they are.
+    Raw,
+    /// A CBOR map, described by its schema.
+    Cbor,
+}
+
+/// A byte range of the packet header.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct HeaderField {
+    pub name: &'static str,
+    pub offset: usize,
+    pub size: usize, // In bytes; integers are big-endian.
+}
+
+/// A top-level field of a CBOR payload.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldSpec {
+    pub name: String,
+    pub kind: &'static str, // The JSON kind of its value in the example; `optional` when unset.
+}
+
+/// Decodes a payload as a client request and encodes it again.
+type RoundTrip = fn(&HeaderType, &[u8]) -> Result<serde_cbor::Value, String>;
+
+/// The payload of a packet, sent one way.
+#[derive(Serialize, Debug, Clone)]
+pub struct PayloadSpec {
+    pub encoding: Encoding,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>, // The server type of the payload.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldSpec>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub example: Option<serde_json::Value>,
+    #[serde(skip)]
+    pub sample: Option<Vec<u8>>, // The CBOR of the example.
+    #[serde(skip)]
+    round_trip: Option<RoundTrip>,
+}
+
+impl PayloadSpec {
+    fn new(encoding: Encoding) -> Self {
+        Self {
+            encoding,
+            schema: None,
+            fields: Vec::new(),
+            example: None,
+            sample: None,
+            round_trip: None,
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self::new(Encoding::Empty)
+    }
+
+    pub fn text() -> Self {
+        Self::new(Encoding::Text)
+    }
+
+    pub fn raw() -> Self {
+        Self::new(Encoding::Raw)
+    }
+
+    /// A CBOR payload too large to give an example of, described by its type only.
+    pub fn opaque<T: ?Sized>() -> Self {
+        Self {
+            schema: Some(schema_name::<T>()),
+            ..Self::new(Encoding::Cbor)
+        }
+    }
+
+    /// A CBOR payload sent by the server, described by an example.
+    pub fn cbor<T: Serialize>(example: &T) -> Self {
+        let json = serde_json::to_value(example).unwrap_or_default();
+        let fields = match &json {
+            serde_json::Value::Object(map) => map
+                .iter()
+                .map(|(name, value)| FieldSpec {
+                    name: name.clone(),
+                    kind: kind_of(value),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Self {
+            schema: Some(schema_name::<T>()),
+            fields,
+            example: Some(json),
+            sample: serde_cbor::to_vec(example).ok(),
+            ..Self::new(Encoding::Cbor)
+        }
+    }
+
+    /// A CBOR payload sent by clients, described by an example the server must decode. The
+    /// sample leaves out the schema version byte, which clients may prepend.
+    pub fn request<T: Serialize + VersionedRequest>(example: &T) -> Self {
+        Self {
+            round_trip: Some(round_trip::<T>),
+            ..Self::cbor(example)
+        }
+    }
+
+    /// Decodes the example the way the server decodes requests of the packet, and encodes the
+    /// request again.
+    ///
+    /// # Returns
+    /// `None` if the payload is not a client request with an example.
+    pub fn round_trip(
+        &self,
+        header_type: &HeaderType,
+    ) -> Option<Result<serde_cbor::Value, String>> {
+        let (round_trip, sample) = (self.round_trip?, self.sample.as_ref()?);
+        Some(round_trip(header_type, sample))
+    }
+}
+
+/// A packet type: who sends it and what its payloads are.
+#[derive(Serialize, Debug, Clone)]
+pub struct PacketSpec {
+    pub code: u8,
+    pub name: String,
+    pub direction: Direction,
+    pub summary: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_payload: Option<PayloadSpec>, // What clients send.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_payload: Option<PayloadSpec>, // What the server sends.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limits: Option<PayloadLimits>, // Bounds of the CBOR payloads clients send.
+    #[serde(skip)]
+    pub header_type: HeaderType,
+}
+
+impl PacketSpec {
+    fn new(header_type: HeaderType, summary: &'static str) -> Self {
+        Self {
+            code: header_type.clone() as u8,
+            name: header_type.to_string(),
+            direction: Direction::ServerToClient,
+            summary,
+            client_payload: None,
+            server_payload: None,
+            limits: None,
+            header_type,
+        }
+    }
+
+    fn client(mut self, payload: PayloadSpec) -> Self {
+        if payload.encoding == Encoding::Cbor {
+            self.limits = Some(PayloadLimits::for_request(&self.header_type));
+        }
+        self.client_payload = Some(payload);
+        self.direction = match self.server_payload {
+            Some(_) => Direction::Both,
+            None => Direction::ClientToServer,
+        };
+        self
+    }
+
+    fn server(mut self, payload: PayloadSpec) -> Self {
+        self.server_payload = Some(payload);
+        self.direction = match self.client_payload {
+            Some(_) => Direction::Both,
+            None => Direction::ServerToClient,
+        };
+        self
+    }
+}
+
+/// The binary protocol, as external client implementations need it.
+#[derive(Serialize, Debug, Clone)]
+pub struct ProtocolSpec {
+    pub version: u16,
+    pub min_version: u16,
+    pub features: u32,
+    pub payload_version: u8, // Schema version byte that may start client CBOR payloads.
+    pub min_payload_version: u8,
+    pub header_size: usize,
+    pub max_payload_size: u32,
+    pub header: Vec<HeaderField>,
+    pub packets: Vec<PacketSpec>, // By code.
+}
+
+/// The layout of the packet header, as written by `Header::wrap_header`.
+pub fn header_layout() -> Vec<HeaderField> {
+    let field = |name, offset, size| HeaderField { name, offset, size };
+    vec![
+        field("header_type", 0, 1),
+        field("flags", 1, 1),
+        field("payload_length", 2, 4),
+        field("checksum", 6, 4),
+        field("sequence", 10, 2),
+        field("delimiter", 12, 1), // Always `0x0A`.
+    ]
+}
+
+/// Describes every packet type of the protocol.
+pub fn spec() -> ProtocolSpec {
+    let red = PlayerId::from("red");
+    let blue = PlayerId::from("blue");
+    let match_id = MatchId::from("match-1");
+    let play_card = PlayCardRequest {
+        actor_id: red.clone(),
+        instance_id: CardInstanceId::nth(7),
+        target_id: Some(blue.to_string()),
+        target_position: None,
+    };
+    let connect = ConnectionRequest {
+        player_id: red.clone(),
+        auth_token: String::from("auth-token"),
+        current_deck_id: String::from("deck-1"),
+        match_id: Some(match_id.clone()),
+    };
+    let preload = |id: &PlayerId| PreloadPlayer {
+        id: id.clone(),
+        deck_id: String::from("deck-1"),
+        pool: None,
+        bot: None,
+        deck: None,
+    };
+    let rejected = ErrorResponse::from(&GameLogicError::CardNotInHand(
+        CardInstanceId::nth(7).to_string(),
+    ));
+
+    let mut packets = vec![
+        // General
+        PacketSpec::new(
+            HeaderType::Disconnect,
+            "Either side is closing the connection.",
+        )
+        .client(PayloadSpec::empty())
+        .server(PayloadSpec::empty()),
+        PacketSpec::new(HeaderType::Connect, "Client is joining the match as a player.")
+            .client(PayloadSpec::request(&connect)),
+        PacketSpec::new(
+            HeaderType::Ping,
+            "Keepalive probe, sent by either side and echoed in a `PONG`.",
+        )
+        .client(PayloadSpec::raw())
+        .server(PayloadSpec::raw()),
+        PacketSpec::new(
+            HeaderType::Reconnect,
+            "Client is rejoining the match it dropped from.",
+        )
+        .client(PayloadSpec::request(&ReconnectionRequest {
+            player_id: red.clone(),
+            auth_token: String::new(),
+            session_token: Some(String::from("session-token")),
+            match_id: Some(match_id.clone()),
+        })),
+        PacketSpec::new(
+            HeaderType::Handshake,
+            "Protocol version and feature negotiation, sent before `CONNECT` or `RECONNECT`.",
+        )
+        .client(PayloadSpec::request(&HandshakeRequest {
+            version: PROTOCOL_VERSION,
+            features: SERVER_FEATURES,
+            ..Default::default()
+        }))
+        .server(PayloadSpec::cbor(&handshake::supported())),
+        PacketSpec::new(HeaderType::Pong, "Answer to a `PING`, echoing its payload.")
+            .client(PayloadSpec::raw())
+            .server(PayloadSpec::raw()),
+        PacketSpec::new(
+            HeaderType::Spectate,
+            "Client is joining the match as a spectator; `match_id` or `player_id` pick the match.",
+        )
+        .client(PayloadSpec::request(&SpectateRequest {
+            auth_token: String::from("auth-token"),
+        })),
+        PacketSpec::new(
+            HeaderType::ConfirmStake,
+            "Player acknowledges the stake of a wagered match.",
+        )
+        .client(PayloadSpec::request(&ConfirmStakeRequest {
+            amount: 100,
+            currency: String::from("gold"),
+        })),
+        PacketSpec::new(HeaderType::Ready, "Player is ready for the match to start.")
+            .client(PayloadSpec::empty()),
+        PacketSpec::new(
+            HeaderType::MatchStart,
+            "Server is starting the match once every player is ready.",
+        )
+        .server(PayloadSpec::opaque::<MatchStart>()),
+        PacketSpec::new(
+            HeaderType::MatchCountdown,
+            "Server is counting down to the scheduled start of the match.",
+        )
+        .server(PayloadSpec::cbor(&MatchCountdown {
+            scheduled_at: 1_700_000_060_000,
+            remaining_ms: 60_000,
+            ready: vec![red.clone()],
+        })),
+        PacketSpec::new(
+            HeaderType::ResyncRequest,
+            "Client is asking for a full snapshot of the match.",
+        )
+        .client(PayloadSpec::empty()),
+        PacketSpec::new(
+            HeaderType::ResyncResponse,
+            "Server is sending the snapshot, with the current turn and its timer.",
+        )
+        .server(PayloadSpec::opaque::<ResyncSnapshot>()),
+        PacketSpec::new(
+            HeaderType::ConnectAck,
+            "Server accepted a `CONNECT` or `RECONNECT`, with a session token and the initial view.",
+        )
+        .server(PayloadSpec::opaque::<ConnectAck>()),
+        PacketSpec::new(
+            HeaderType::LobbyStatus,
+            "Server is telling a client waiting in the lobby how many players wait and are ready.",
+        )
+        .server(PayloadSpec::cbor(&LobbyStatus {
+            waiting: 2,
+            ready: 1,
+        })),
+        // Game state
+        PacketSpec::new(
+            HeaderType::GameState,
+            "Server is sending the game state; spectators get a `PublicGameStateView`.",
+        )
+        .server(PayloadSpec::opaque::<PlayerGameStateView>()),
+        // Actions
+        PacketSpec::new(HeaderType::PlayCard, "Client is playing a card.")
+            .client(PayloadSpec::request(&play_card)),
+        PacketSpec::new(
+            HeaderType::AttackPlayer,
+            "Reserved; answered with `INVALID_HEADER`.",
+        )
+        .client(PayloadSpec::empty()),
+        PacketSpec::new(HeaderType::InitServer, "Matchmaker is initializing the match.")
+            .client(PayloadSpec::request(&InitServerRequest {
+                match_id: match_id.clone(),
+                match_type: String::from("ranked"),
+                players: vec![preload(&red), preload(&blue)],
+                spectatable: true,
+                viewers: None,
+                seed: Some(42),
+                stake: None,
+                rules: None,
+                scheduled_start: None,
+                draft: None,
+                game: 1,
+            })),
+        PacketSpec::new(
+            HeaderType::ActionBatch,
+            "Client is submitting several actions to resolve atomically.",
+        )
+        .client(PayloadSpec::request(&ActionBatchRequest {
+            actions: vec![
+                BatchedAction::PlayCard(play_card.clone()),
+                BatchedAction::PromptResponse(PromptResponse {
+                    prompt_id: 1,
+                    choice: String::from("c1"),
+                }),
+            ],
+        })),
+        PacketSpec::new(
+            HeaderType::ActivateAbility,
+            "Client is activating the ability of a card on its board.",
+        )
+        .client(PayloadSpec::request(&ActivateAbilityRequest {
+            actor_id: red.clone(),
+            instance_id: CardInstanceId::nth(3),
+            target_id: None,
+        })),
+        PacketSpec::new(
+            HeaderType::RequestUndo,
+            "Client is asking to take back its last play, pending the opponent's consent.",
+        )
+        .client(PayloadSpec::empty()),
+        PacketSpec::new(
+            HeaderType::DeclareAttackers,
+            "Client is attacking with creatures; sent by the server to open the blockers window.",
+        )
+        .client(PayloadSpec::request(&DeclareAttackersRequest {
+            actor_id: red.clone(),
+            attackers: vec![CardInstanceId::nth(3)],
+            defender_id: Some(blue.clone()),
+        }))
+        .server(PayloadSpec::cbor(&CombatWindow {
+            attacker_id: red.clone(),
+            defender_id: blue.clone(),
+            attackers: vec![CardInstanceId::nth(3)],
+            deadline: 1_700_000_030_000,
+        })),
+        PacketSpec::new(
+            HeaderType::DeclareBlockers,
+            "Client is blocking the declared attackers.",
+        )
+        .client(PayloadSpec::request(&DeclareBlockersRequest {
+            actor_id: blue.clone(),
+            blocks: vec![Block {
+                blocker: CardInstanceId::nth(11),
+                attacker: CardInstanceId::nth(3),
+            }],
+        })),
+        PacketSpec::new(
+            HeaderType::EndTurn,
+            "Client is ending its turn; the next player in turn order who is not defeated starts \
+             theirs. Answered with ActionAccepted, or ActionRejected when it is not the \
+             client's turn or its attack still waits on blockers.",
+        )
+        .client(PayloadSpec::empty()),
+        // Action responses
+        PacketSpec::new(
+            HeaderType::ActionAccepted,
+            "The action identified by the header sequence was applied.",
+        )
+        .server(PayloadSpec::empty()),
+        PacketSpec::new(
+            HeaderType::ActionRejected,
+            "The action identified by the header sequence was refused, and why. Text without the \
+             error codes feature.",
+        )
+        .server(PayloadSpec::cbor(&rejected)),
+        // Prompts
+        PacketSpec::new(
+            HeaderType::PromptRequest,
+            "Server is asking the player to make a choice.",
+        )
+        .server(PayloadSpec::opaque::<Prompt>()),
+        PacketSpec::new(HeaderType::PromptResponse, "Client is answering a prompt.").client(
+            PayloadSpec::request(&PromptResponse {
+                prompt_id: 1,
+                choice: String::from("c1"),
+            }),
+        ),
+        // History
+        PacketSpec::new(
+            HeaderType::GetHistory,
+            "Client is asking for the recent events of the match; the payload may be empty.",
+        )
+        .client(PayloadSpec::request(&HistoryRequest {
+            since: 10,
+            limit: Some(50),
+        })),
+        PacketSpec::new(HeaderType::History, "Server is sending the requested events.")
+            .server(PayloadSpec::opaque::<Vec<GameEvent>>()),
+        PacketSpec::new(
+            HeaderType::GetReplay,
+            "Client is asking for the replay of a match it may watch.",
+        )
+        .client(PayloadSpec::request(&GetReplayRequest {
+            match_id: match_id.clone(),
+            auth_token: String::from("auth-token"),
+        })),
+        PacketSpec::new(HeaderType::Replay, "Server is sending the requested replay.")
+            .server(PayloadSpec::opaque::<ReplayResponse>()),
+        // Notices
+        PacketSpec::new(
+            HeaderType::ScriptSkipped,
+            "Server skipped a card script blocked by operators.",
+        )
+        .server(PayloadSpec::cbor(&SkippedScript {
+            card_id: CardDefId::from("fireball"),
+            event: String::from("on_play"),
+            function: String::from("on_play"),
+        })),
+        PacketSpec::new(
+            HeaderType::TurnStarted,
+            "Server started the turn of a player.",
+        )
+        .server(PayloadSpec::cbor(&TurnStarted {
+            turn: 3,
+            player_id: red.clone(),
+        })),
+        PacketSpec::new(
+            HeaderType::MatchEnded,
+            "Server ended the match, naming the winner if any.",
+        )
+        .server(PayloadSpec::cbor(&MatchEnded {
+            winner_id: Some(red.clone()),
+            reason: String::from("blue ran out of health"),
+        })),
+        PacketSpec::new(
+            HeaderType::OpponentDisconnected,
+            "The opponent dropped and forfeits unless they reconnect in time.",
+        )
+        .server(PayloadSpec::cbor(&PlayerDisconnected {
+            player_id: blue.clone(),
+            forfeit_at: 1_700_000_090_000,
+            clock_paused: true,
+        })),
+        PacketSpec::new(HeaderType::OpponentReconnected, "The opponent is back.").server(
+            PayloadSpec::cbor(&PlayerReconnected {
+                player_id: blue.clone(),
+            }),
+        ),
+        // Admin
+        PacketSpec::new(
+            HeaderType::AdminCommand,
+            "Operator tool is running a signed admin command.",
+        )
+        .client(PayloadSpec::request(&AdminRequest {
+            command: String::from("status"),
+            issued_at: 1_700_000_000_000,
+            signature: String::from("hex-hmac-sha256"),
+        })),
+        PacketSpec::new(
+            HeaderType::AdminResponse,
+            "Server is answering an admin command.",
+        )
+        .server(PayloadSpec::cbor(&AdminResponse {
+            ok: true,
+            output: String::from("turn 3, red to play"),
+        })),
+        // Social
+        PacketSpec::new(
+            HeaderType::Emote,
+            "Client is sending an emote; relayed to the opponent and spectators.",
+        )
+        .client(PayloadSpec::request(&EmoteRequest {
+            emote_id: String::from("well_played"),
+            ping: Some(BoardPing {
+                player_id: blue.clone(),
+                position: String::from("front:2"),
+            }),
+        }))
+        .server(PayloadSpec::cbor(&EmoteSent {
+            player_id: red.clone(),
+            emote_id: String::from("well_played"),
+            ping: None,
+        })),
+        PacketSpec::new(
+            HeaderType::Chat,
+            "Client is sending a chat message; relayed to the opponent, and to spectators when \
+             `CHAT_TO_SPECTATORS` is set.",
+        )
+        .client(PayloadSpec::request(&ChatRequest {
+            message: String::from("good luck"),
+        }))
+        .server(PayloadSpec::cbor(&ChatSent {
+            player_id: red.clone(),
+            message: String::from("good luck"),
+            public: false,
+        })),
+        PacketSpec::new(
+            HeaderType::MuteChat,
+            "Client is muting or unmuting the chat of another player.",
+        )
+        .client(PayloadSpec::request(&MuteRequest {
+            player_id: blue.clone(),
+            muted: true,
+        })),
+        // Private matches
+        PacketSpec::new(
+            HeaderType::CreatePrivateMatch,
+            "Client is opening a private match to play with a friend.",
+        )
+        .client(PayloadSpec::request(&connect)),
+        PacketSpec::new(
+            HeaderType::PrivateMatchCreated,
+            "Server is sending the join code of the private match.",
+        )
+        .server(PayloadSpec::cbor(&PrivateMatchCreated {
+            join_code: String::from("K7QX2M"),
+            expires_at: 1_700_000_600_000,
+        })),
+        PacketSpec::new(
+            HeaderType::JoinPrivateMatch,
+            "Client is joining a private match with its join code, along with the fields of \
+             `CONNECT`.",
+        )
+        .client(PayloadSpec::request(&JoinPrivateMatchRequest {
+            join_code: String::from("K7QX2M"),
+        })),
+        // Draft
+        PacketSpec::new(
+            HeaderType::DraftPack,
+            "Server is sending the open pack of the draft and whose pick it is.",
+        )
+        .server(PayloadSpec::opaque::<DraftView>()),
+        PacketSpec::new(
+            HeaderType::DraftPick,
+            "Client is picking a card of the open pack.",
+        )
+        .client(PayloadSpec::request(&DraftPickRequest {
+            card_id: CardDefId::from("fireball"),
+        })),
+        PacketSpec::new(
+            HeaderType::DraftComplete,
+            "Server is sending the deck the player drafted, before connecting them to the match.",
+        )
+        .server(PayloadSpec::opaque::<Deck>()),
+        // Sideboard
+        PacketSpec::new(
+            HeaderType::Sideboard,
+            "Server is sending the deck and sideboard of the player before a game of a series.",
+        )
+        .server(PayloadSpec::opaque::<SideboardView>()),
+        PacketSpec::new(
+            HeaderType::SideboardRequest,
+            "Client is swapping cards between its deck and its sideboard.",
+        )
+        .client(PayloadSpec::request(&SideboardRequest {
+            swaps: vec![SideboardSwap {
+                remove: CardDefId::from("fireball"),
+                add: CardDefId::from("frostbolt"),
+            }],
+        })),
+        // Errors
+        PacketSpec::new(
+            HeaderType::InvalidHeader,
+            "Malformed or unrecognized header.",
+        )
+        .server(PayloadSpec::empty()),
+        PacketSpec::new(HeaderType::AlreadyConnected, "Client is already connected.")
+            .server(PayloadSpec::empty()),
+        PacketSpec::new(
+            HeaderType::InvalidPlayerData,
+            "Legacy; no longer sent by the server.",
+        )
+        .server(PayloadSpec::text()),
+        PacketSpec::new(
+            HeaderType::InvalidChecksum,
+            "Payload failed checksum validation.",
+        )
+        .server(PayloadSpec::empty()),
+        PacketSpec::new(
+            HeaderType::FailedToConnectPlayer,
+            "Legacy; no longer sent by the server.",
+        )
+        .server(PayloadSpec::text()),
+        PacketSpec::new(
+            HeaderType::InvalidPacketPayload,
+            "Packet payload is invalid or outside the limits of its type, and why. Text without the \
+             error codes feature.",
+        )
+        .server(PayloadSpec::cbor(&ErrorResponse::from(&PayloadError::TooDeep(64)))),
+        PacketSpec::new(
+            HeaderType::VersionMismatch,
+            "Client protocol version is not supported; carries what the server supports.",
+        )
+        .server(PayloadSpec::cbor(&handshake::supported())),
+        PacketSpec::new(
+            HeaderType::ConnectionRejected,
+            "Server refused the connection, with a reason and a retry hint.",
+        )
+        .server(PayloadSpec::cbor(&Rejection {
+            retry_after_ms: Some(5_000),
+            ..Rejection::new(RejectionReason::RateLimited, "too many packets")
+        })),
+        PacketSpec::new(
+            HeaderType::DeckIllegal,
+            "Matchmaker is told the match was not initialized because of an illegal deck.",
+        )
+        .server(PayloadSpec::opaque::<DeckIllegal>()),
+        PacketSpec::new(
+            HeaderType::ERROR,
+            "Generic error. Text without the error codes feature, and for the matchmaker.",
+        )
+        .server(PayloadSpec::cbor(&ErrorResponse::new(
+            ErrorCode::Internal,
+            "could not encode the snapshot",
+        ))),
+    ];
+    packets.sort_by_key(|packet| packet.code);
+
+    ProtocolSpec {
+        version: PROTOCOL_VERSION,
+        min_version: MIN_PROTOCOL_VERSION,
+        features: SERVER_FEATURES,
+        payload_version: PAYLOAD_VERSION,
+        min_payload_version: MIN_PAYLOAD_VERSION,
+        header_size: HEADER_SIZE,
+        max_payload_size: MAX_PAYLOAD_SIZE,
+        header: header_layout(),
+        packets,
+    }
+}
+
+/// Writes the spec as JSON to a file, or to the standard output without one.
+pub fn write(path: Option<&Path>) -> std::io::Result<()> {
+    let json = serde_json::to_string_pretty(&spec())?;
+    match path {
+        Some(path) => std::fs::write(path, json + "\n"),
+        None => {
+            println!("{json}");
+            Ok(())
+        }
+    }
+}
+
+fn round_trip<T: Serialize + VersionedRequest>(
+    header_type: &HeaderType,
+    bytes: &[u8],
+) -> Result<serde_cbor::Value, String> {
+    let request: T = payload::decode(header_type, bytes).map_err(|e| e.to_string())?;
+    serde_cbor::value::to_value(&request).map_err(|e| e.to_string())
+}
+
+/// The name of a type without its module paths, e.g. `Vec<GameEvent>`.
+fn schema_name<T: ?Sized>() -> String {
+    std::any::type_name::<T>()
+        .split_inclusive(['<', '>', ',', ' ', '[', ']', ';'])
+        .map(|part| part.rsplit("::").next().unwrap_or(part))
+        .collect()
+}
+
+fn kind_of(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "optional",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Number(number) if number.is_f64() => "number",
+        serde_json::Value::Number(_) => "integer",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "map",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tcp::encoding::PayloadEncoding;
+    use crate::tcp::packet::Packet;
+    use crate::utils::checksum::{Checksum, Crc32cChecksum};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_every_header_type_is_documented_once() {
+        let spec = spec();
+        let mut codes = HashSet::new();
+        for packet in &spec.packets {
+            assert!(
+                codes.insert(packet.code),
+                "{} is documented twice",
+                packet.name
+            );
+            assert_eq!(packet.code, packet.header_type.clone() as u8);
+            assert!(
+                packet.client_payload.is_some() || packet.server_payload.is_some(),
+                "{} has no payload",
+                packet.name
+            );
+        }
+
+        for code in 0..=u8::MAX {
+            if let Ok(header_type) = HeaderType::try_from(code) {
+                assert!(codes.contains(&code), "{header_type} is not documented");
+            }
+        }
+        assert_eq!(
+            HEADER_SIZE,
+            spec.header.iter().map(|field| field.size).sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn test_every_documented_packet_round_trips() {
+        for packet in spec().packets {
+            let payloads = [&packet.client_payload, &packet.server_payload];
+            for payload in payloads.into_iter().flatten() {
+                let sample = payload.sample.clone().unwrap_or_default();
+                let mut sent = Packet::new(packet.header_type.clone(), &sample);
+                sent.header.sequence = 0x1234;
+
+                let bytes = sent.wrap_packet();
+                assert_eq!(packet.code, bytes[0], "{}", packet.name);
+                assert_eq!(
+                    [0x12, 0x34, 0x0A],
+                    bytes[10..HEADER_SIZE],
+                    "{}",
+                    packet.name
+                );
+
+                let received = Packet::parse(&bytes).unwrap();
+                assert_eq!(packet.header_type, received.header.header_type);
+                assert_eq!(0x1234, received.header.sequence);
+                assert_eq!(Crc32cChecksum.compute(&sample), received.header.checksum);
+                assert_eq!(&sample[..], &received.payload[..], "{}", packet.name);
+
+                if let Some(decoded) = payload.round_trip(&received.header.header_type) {
+                    let decoded = decoded.unwrap_or_else(|e| panic!("{}: {e}", packet.name));
+                    let sent: serde_cbor::Value = serde_cbor::from_slice(&sample).unwrap();
+                    assert_eq!(sent, decoded, "{} changed on the way", packet.name);
+
+                    let versioned = [&[PAYLOAD_VERSION][..], &sample].concat();
+                    let payload = PayloadSpec {
+                        sample: Some(versioned),
+                        ..payload.clone()
+                    };
+                    let decoded = payload.round_trip(&packet.header_type).unwrap();
+                    assert_eq!(Ok(sent), decoded, "{} is versioned", packet.name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_only_cbor_payloads_are_translated_to_json() {
+        for packet in spec().packets {
+            let payloads = [&packet.client_payload, &packet.server_payload];
+            for payload in payloads.into_iter().flatten() {
+                let translated = PayloadEncoding::translates(&packet.header_type);
+                match payload.encoding {
+                    Encoding::Cbor => assert!(translated, "{} is not translated", packet.name),
+                    Encoding::Text | Encoding::Raw => {
+                        assert!(!translated, "{} is translated", packet.name)
+                    }
+                    Encoding::Empty => {}
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_spec_is_described_in_json() {
+        let json = serde_json::to_value(spec()).unwrap();
+        let play_card = json["packets"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|packet| packet["name"] == "PLAY_CARD")
+            .unwrap();
+
+        assert_eq!(Some(0x11), play_card["code"].as_u64());
+        assert_eq!("client_to_server", play_card["direction"]);
+        assert_eq!("PlayCardRequest", play_card["client_payload"]["schema"]);
+        assert_eq!(Some(16 * 1024), play_card["limits"]["max_bytes"].as_u64());
+        assert_eq!("Vec<GameEvent>", schema_name::<Vec<GameEvent>>());
+    }
+}
@@ -0,0 +1,199 @@
+use crate::tcp::listener::Accepted;
+use crate::tcp::tls::TlsIdentity;
+use crate::tcp::transport::{ClientStream, Connection, WriteHalf};
+use crate::{logger, utils::logger::Logger};
+use quinn::crypto::rustls::QuicServerConfig;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::mpsc;
+
+fn server_config(identity: &TlsIdentity) -> io::Result<quinn::ServerConfig> {
+    let mut tls = identity.server_config()?;
+    // Clients resuming a session may send their first packets with the TLS handshake, which saves
+    // a reconnecting client a round trip.
+    tls.max_early_data_size = u32::MAX;
+
+    let tls = QuicServerConfig::try_from(tls).map_err(io::Error::other)?;
+    Ok(quinn::ServerConfig::with_crypto(Arc::new(tls)))
+}
+
+/// Binds one QUIC endpoint; IPv6 endpoints only take IPv6 clients unless `dual_stack` is set.
+pub fn bind(
+    address: SocketAddr,
+    dual_stack: bool,
+    identity: &TlsIdentity,
+) -> io::Result<quinn::Endpoint> {
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::DGRAM,
+        Some(Protocol::UDP),
+    )?;
+    if address.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+    socket.bind(&address.into())?;
+
+    quinn::Endpoint::new(
+        quinn::EndpointConfig::default(),
+        Some(server_config(identity)?),
+        socket.into(),
+        Arc::new(quinn::TokioRuntime),
+    )
+}
+
+/// Accepts QUIC clients on one endpoint until the accept pipeline is dropped.
+///
+/// Every client completes its handshake on its own task, so a slow one does not hold the others
+/// back, and joins the pipeline once it opens the stream carrying its packets; clients failing the
+/// handshake are dropped.
+pub async fn accept_loop(endpoint: quinn::Endpoint, sender: mpsc::Sender<Accepted>) {
+    while let Some(incoming) = endpoint.accept().await {
+        if sender.is_closed() {
+            break;
+        }
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            let addr = incoming.remote_address();
+            match open(incoming).await {
+                Ok(stream) => {
+                    let _ = sender.send(Ok((stream, addr))).await;
+                }
+                // A failed handshake only concerns its client, not the accept pipeline.
+                Err(error) => logger!(WARN, "[SERVER] QUIC handshake of `{addr}` failed: {error}"),
+            }
+        });
+    }
+
+    if let Ok(address) = endpoint.local_addr() {
+        logger!(DEBUG, "[SERVER] Stopped accepting QUIC on `{address}`");
+    }
+}
+
+/// Completes the handshake of a client and waits for its stream.
+async fn open(incoming: quinn::Incoming) -> io::Result<ClientStream> {
+    let connecting = incoming.accept()?;
+    // Resumed clients are read from before the handshake completes, see `server_config`.
+    let connection = match connecting.into_0rtt() {
+        Ok((connection, _)) => connection,
+        Err(connecting) => connecting.await?,
+    };
+    let (sender, receiver) = connection.accept_bi().await?;
+    let addr = connection.remote_address();
+    let stream = QuicStream {
+        receiver,
+        sender,
+        addr,
+    };
+    Ok(ClientStream::new(stream))
+}
+
+/// The first bidirectional stream opened by a QUIC client, carrying all of its packets.
+pub struct QuicStream {
+    receiver: quinn::RecvStream,
+    sender: quinn::SendStream,
+    addr: SocketAddr, // Address of the client, which may change as QUIC connections migrate.
+}
+
+impl Connection for QuicStream {
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn split(self: Box<Self>) -> (Box<dyn AsyncRead + Send + Sync + Unpin>, WriteHalf) {
+        (Box::new(self.receiver), Box::new(self.sender))
+    }
+}
+
+impl AsyncRead for QuicStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        AsyncRead::poll_read(Pin::new(&mut self.get_mut().receiver), cx, buf)
+    }
+}
+
+impl AsyncWrite for QuicStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        AsyncWrite::poll_write(Pin::new(&mut self.get_mut().sender), cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().sender).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().sender).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tcp::header::HeaderType;
+    use crate::tcp::packet::Packet;
+    use crate::tcp::tls::ALPN;
+    use quinn::rustls;
+    use std::net::Ipv4Addr;
+
+    /// A QUIC client trusting the certificate of the server.
+    fn client(identity: &TlsIdentity) -> quinn::Endpoint {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(identity.certificate().clone()).unwrap();
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut tls = rustls::ClientConfig::builder_with_provider(provider)
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        tls.alpn_protocols = vec![ALPN.to_vec()];
+        let tls = quinn::crypto::rustls::QuicClientConfig::try_from(tls).unwrap();
+
+        let mut endpoint = quinn::Endpoint::client((Ipv4Addr::LOCALHOST, 0).into()).unwrap();
+        endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(tls)));
+        endpoint
+    }
+
+    #[tokio::test]
+    async fn test_packets_travel_over_a_quic_stream() {
+        let identity = TlsIdentity::self_signed().unwrap();
+        let address = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0);
+        let endpoint = bind(address, false, &identity).unwrap();
+        let address = endpoint.local_addr().unwrap();
+        let (accepted, mut pipeline) = mpsc::channel(1);
+        tokio::spawn(accept_loop(endpoint, accepted));
+
+        let client = client(&identity);
+        let connection = client.connect(address, "localhost").unwrap().await.unwrap();
+        let (mut send, mut receive) = connection.open_bi().await.unwrap();
+        let ping = Packet::new(HeaderType::Ping, b"quic");
+        ping.write_to(&mut send).await.unwrap();
+
+        let (mut server, peer) = pipeline.recv().await.unwrap().unwrap();
+        assert_eq!(client.local_addr().unwrap().port(), peer.port());
+        let mut first = [0u8; 1];
+        assert_eq!(1, server.peek(&mut first).await.unwrap());
+        assert_eq!(HeaderType::Ping as u8, first[0]);
+
+        let received = Packet::read_from(&mut server).await.unwrap().unwrap();
+        assert_eq!(&b"quic"[..], &received.payload[..]);
+        let (_, mut write) = server.into_split();
+        Packet::new(HeaderType::Pong, &received.payload)
+            .write_to(&mut write)
+            .await
+            .unwrap();
+
+        let answer = Packet::read_from(&mut receive).await.unwrap().unwrap();
+        assert_eq!(HeaderType::Pong, answer.header.header_type);
+    }
+}
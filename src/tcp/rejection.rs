@@ -0,0 +1,211 @@
+use crate::tcp::header::HeaderType;
+use crate::tcp::packet::Packet;
+use crate::utils::errors::{
+    GameInstanceError, LobbyError, PlayerConnectionError, ServerInstanceError, SpectatorError,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How long clients are told to wait before retrying a server that is not initialized yet.
+pub const NOT_INITIALIZED_RETRY_AFTER: Duration = Duration::from_secs(2);
+
+/// How long clients are told to wait before retrying after the server failed to admit them.
+pub const INTERNAL_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// How long clients are told to wait before retrying a match whose spectator list is full.
+pub const SPECTATORS_FULL_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Why the server refused a connection, so client UIs can show a meaningful message.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectionReason {
+    /// The server is still waiting for the matchmaker to initialize the match.
+    NotInitialized,
+    /// The player is not part of this match.
+    NotInMatch,
+    /// The spectator list of the match is full.
+    MatchFull,
+    /// The match is not open to spectators.
+    SpectatingDisabled,
+    /// The player is banned.
+    Banned,
+    /// The client sent too much data and must back off before reconnecting.
+    RateLimited,
+    /// The player's credentials or deck were refused.
+    Unauthorized,
+    /// The client must complete the protocol handshake first.
+    HandshakeRequired,
+    /// The server failed while admitting the client; retrying may succeed.
+    Internal,
+    /// A platform service the server relies on is failing; retrying later may succeed.
+    ServiceUnavailable,
+    /// No pending private match has the join code, or it expired.
+    InvalidJoinCode,
+    /// The account may not spectate the match or fetch its replay.
+    NotAllowed,
+    /// The match has no replay to fetch.
+    ReplayNotFound,
+    /// The player kept sending actions the server rejects, and was flagged for it.
+    Flagged,
+    /// The client kept sending packets the server could not decode.
+    Malformed,
+}
+
+/// Sent in a `ConnectionRejected` packet when the server refuses a connection.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Rejection {
+    pub reason: RejectionReason, // Machine-readable reason of the rejection.
+    pub message: String,         // Human-readable details, for logs and fallback UIs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>, // How long to wait before retrying; no retry is expected if unset.
+}
+
+impl Rejection {
+    pub fn new(reason: RejectionReason, message: impl Into<String>) -> Self {
+        Self {
+            reason,
+            message: message.into(),
+            retry_after_ms: None,
+        }
+    }
+
+    /// Tells the client when it may try again.
+    pub fn retry_after(mut self, delay: Duration) -> Self {
+        self.retry_after_ms = Some(delay.as_millis() as u64);
+        self
+    }
+
+    /// Builds the `ConnectionRejected` packet answering `request`, or sent on its own without one.
+    pub fn packet(&self, request: Option<&Packet>) -> Packet {
+        let payload = serde_cbor::to_vec(self).unwrap_or_default();
+        match request {
+            Some(request) => Packet::reply_to(request, HeaderType::ConnectionRejected, &payload),
+            None => Packet::new(HeaderType::ConnectionRejected, &payload),
+        }
+    }
+}
+
+impl From<&PlayerConnectionError> for Rejection {
+    fn from(error: &PlayerConnectionError) -> Self {
+        let reason = match error {
+            PlayerConnectionError::BannedPlayer(_) => RejectionReason::Banned,
+            PlayerConnectionError::PlayerNotConnected => RejectionReason::NotInMatch,
+            PlayerConnectionError::HandshakeRequired => RejectionReason::HandshakeRequired,
+            PlayerConnectionError::PlayerDiscrepancy
+            | PlayerConnectionError::UnauthorizedPlayerError
+            | PlayerConnectionError::InvalidPlayerPayload(_)
+            | PlayerConnectionError::DeckNotFound
+            | PlayerConnectionError::InvalidDeckFormat
+            | PlayerConnectionError::UnauthorizedDeckError => RejectionReason::Unauthorized,
+            PlayerConnectionError::InvalidResponseBody(_)
+            | PlayerConnectionError::UnexpectedPlayerError(_)
+            | PlayerConnectionError::UnexpectedDeckError(_)
+            | PlayerConnectionError::InternalError(_) => {
+                return Rejection::new(RejectionReason::Internal, error.to_string())
+                    .retry_after(INTERNAL_RETRY_AFTER);
+            }
+            #[cfg(feature = "game")]
+            PlayerConnectionError::Upstream(_) => {
+                return Rejection::new(RejectionReason::ServiceUnavailable, error.to_string())
+                    .retry_after(INTERNAL_RETRY_AFTER);
+            }
+        };
+
+        Rejection::new(reason, error.to_string())
+    }
+}
+
+impl From<&SpectatorError> for Rejection {
+    fn from(error: &SpectatorError) -> Self {
+        let reason = match error {
+            SpectatorError::SpectatingDisabled => RejectionReason::SpectatingDisabled,
+            SpectatorError::HandshakeRequired => RejectionReason::HandshakeRequired,
+            SpectatorError::SpectatorsFull(_) => {
+                return Rejection::new(RejectionReason::MatchFull, error.to_string())
+                    .retry_after(SPECTATORS_FULL_RETRY_AFTER);
+            }
+            SpectatorError::NotAllowed(_) => RejectionReason::NotAllowed,
+            SpectatorError::ReplayNotFound(_) => RejectionReason::ReplayNotFound,
+            SpectatorError::Unauthenticated(error) => return Rejection::from(error),
+            SpectatorError::InternalError(_) => RejectionReason::Internal,
+        };
+
+        Rejection::new(reason, error.to_string())
+    }
+}
+
+/// Why the server could not create the match of players it seats itself, after the lobby or a
+/// draft.
+impl From<&ServerInstanceError> for Rejection {
+    fn from(error: &ServerInstanceError) -> Self {
+        match error {
+            ServerInstanceError::DeckIllegal(_)
+            | ServerInstanceError::GameInstance(
+                GameInstanceError::DeckIllegal(_) | GameInstanceError::DeckRejected(..),
+            ) => Rejection::new(RejectionReason::Unauthorized, error.to_string()),
+            ServerInstanceError::GameInstance(
+                GameInstanceError::ProfileUnavailable { source, .. }
+                | GameInstanceError::DeckUnavailable { source, .. },
+            )
+            | ServerInstanceError::SideboardFailed { source, .. } => Rejection {
+                message: error.to_string(),
+                ..Rejection::from(source)
+            },
+            _ => Rejection::new(RejectionReason::Internal, error.to_string())
+                .retry_after(INTERNAL_RETRY_AFTER),
+        }
+    }
+}
+
+impl From<&LobbyError> for Rejection {
+    fn from(error: &LobbyError) -> Self {
+        let reason = match error {
+            LobbyError::LobbyDisabled => RejectionReason::NotInitialized,
+            LobbyError::AlreadyWaiting(_) => RejectionReason::Unauthorized,
+            LobbyError::UnknownJoinCode(_) | LobbyError::JoinCodeExpired(_) => {
+                RejectionReason::InvalidJoinCode
+            }
+            LobbyError::PrivateMatchFull(_) => RejectionReason::MatchFull,
+        };
+
+        Rejection::new(reason, error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejections_carry_reason_and_retry_hint() {
+        let full = Rejection::from(&SpectatorError::SpectatorsFull(16));
+        assert_eq!(RejectionReason::MatchFull, full.reason);
+        assert_eq!(Some(30_000), full.retry_after_ms);
+
+        let banned = Rejection::from(&PlayerConnectionError::BannedPlayer("red".to_string()));
+        assert_eq!(RejectionReason::Banned, banned.reason);
+        assert_eq!(None, banned.retry_after_ms);
+
+        let unauthenticated = Rejection::from(&SpectatorError::Unauthenticated(
+            PlayerConnectionError::UnauthorizedPlayerError,
+        ));
+        assert_eq!(RejectionReason::Unauthorized, unauthenticated.reason);
+        let not_allowed = Rejection::from(&SpectatorError::NotAllowed("stranger".to_string()));
+        assert_eq!(RejectionReason::NotAllowed, not_allowed.reason);
+
+        // Failures of the players' accounts keep their reason, with the player in the message.
+        let unavailable = ServerInstanceError::GameInstance(GameInstanceError::DeckUnavailable {
+            player_id: "red".into(),
+            deck_id: "red-deck".to_string(),
+            source: PlayerConnectionError::DeckNotFound,
+        });
+        let rejected = Rejection::from(&unavailable);
+        assert_eq!(RejectionReason::Unauthorized, rejected.reason);
+        assert!(rejected.message.contains("`red`"));
+
+        let packet = banned.packet(None);
+        assert_eq!(HeaderType::ConnectionRejected, packet.header.header_type);
+        let decoded: Rejection = serde_cbor::from_slice(&packet.payload).unwrap();
+        assert_eq!(banned, decoded);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::models::exit_code::{ExitCode, ExitStatus};
+use crate::tcp::server::MatchHost;
+use crate::utils::ready::ReadySignal;
+use crate::{logger, utils::logger::Logger, LIFECYCLE, MATCHES, SETTINGS};
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Where the process is in its life, from binding its listeners to exiting.
+pub enum RuntimePhase {
+    Binding,              // Binding the listen addresses of the settings.
+    AwaitingInit,         // Listening, until the matchmaker sends a first `InitServer`.
+    Running,              // Hosting matches, until the process is told to exit.
+    Finished(ExitStatus), // Exiting with this status once the shutdown hooks ran.
+}
+
+impl Display for RuntimePhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimePhase::Binding => write!(f, "binding"),
+            RuntimePhase::AwaitingInit => write!(f, "awaiting init"),
+            RuntimePhase::Running => write!(f, "running"),
+            RuntimePhase::Finished(_) => write!(f, "finished"),
+        }
+    }
+}
+
+/// Runs the game server through its phases: binds its listeners once, announces it is ready,
+/// serves the matches the matchmaker initializes and, once an exit status is set, runs the
+/// shutdown hooks.
+pub struct ServerRuntime {
+    phase: RuntimePhase,
+    ready_file: Option<PathBuf>, // Written once the listeners are bound.
+}
+
+impl ServerRuntime {
+    pub fn new(ready_file: Option<PathBuf>) -> Self {
+        Self {
+            phase: RuntimePhase::Binding,
+            ready_file,
+        }
+    }
+
+    /// Runs the phases until the process finished.
+    ///
+    /// # Returns
+    /// The exit status of the process, set by a finished match, a failed initialization or a
+    /// fatal error.
+    pub async fn run(mut self) -> ExitStatus {
+        loop {
+            let phase = std::mem::replace(&mut self.phase, RuntimePhase::Binding);
+            self.phase = self.advance(phase).await;
+            logger!(DEBUG, "[SERVER] Now {}", self.phase);
+            if let RuntimePhase::Finished(status) = &self.phase {
+                return Self::shut_down(status.clone()).await;
+            }
+        }
+    }
+
+    /// Moves from a phase to the next one.
+    async fn advance(&self, phase: RuntimePhase) -> RuntimePhase {
+        match phase {
+            RuntimePhase::Binding => match MatchHost::create_instance().await {
+                Err(error) => RuntimePhase::Finished(ExitStatus::new(
+                    ExitCode::ListenFailed,
+                    error.to_string(),
+                )),
+                Ok(host) => {
+                    self.start(host);
+                    RuntimePhase::AwaitingInit
+                }
+            },
+            RuntimePhase::AwaitingInit => tokio::select! {
+                _ = MATCHES.wait_hosted() => RuntimePhase::Running,
+                status = LIFECYCLE.wait() => RuntimePhase::Finished(status),
+            },
+            RuntimePhase::Running => RuntimePhase::Finished(LIFECYCLE.wait().await),
+            RuntimePhase::Finished(status) => RuntimePhase::Finished(status),
+        }
+    }
+
+    /// Announces that the bound host is ready for initialization and starts its listen loop.
+    fn start(&self, host: MatchHost) {
+        let addresses = host.listeners.addresses();
+        let quic_addresses = host.listeners.quic_addresses();
+        logger!(
+            INFO,
+            "[SERVER] tcp-server v{} ready for initialization on {addresses:?}",
+            env!("CARGO_PKG_VERSION")
+        );
+        if !quic_addresses.is_empty() {
+            logger!(
+                INFO,
+                "[SERVER] Accepting QUIC clients on {quic_addresses:?}"
+            );
+        }
+        let signal = ReadySignal::new(addresses).with_quic(quic_addresses);
+        if let Err(error) = signal.announce(self.ready_file.as_deref()) {
+            logger!(ERROR, "[SERVER] Could not signal readiness: {error}");
+        }
+        if MATCHES.hosts_many() {
+            logger!(
+                INFO,
+                "[SERVER] Hosting up to {} matches",
+                SETTINGS.get().map_or(1, |s| s.max_matches)
+            );
+        }
+
+        // The operator channels serve every match of the process.
+        crate::admin::start();
+
+        let host = Arc::new(host);
+        host.register_shutdown_hooks();
+        tokio::spawn(host.listen());
+    }
+
+    /// Sets the exit status, unless a finished match or a failed initialization already set its
+    /// own, and runs the shutdown hooks.
+    ///
+    /// # Returns
+    /// The exit status the process exits with.
+    async fn shut_down(status: ExitStatus) -> ExitStatus {
+        LIFECYCLE.exit(status);
+        let status = LIFECYCLE.status().unwrap_or_default();
+        logger!(
+            INFO,
+            "[SERVER] Shutting down with exit code {}: {}",
+            status.code,
+            &status.reason
+        );
+        let timeout = SETTINGS.get().map_or(10, |s| s.shutdown_hook_timeout);
+        LIFECYCLE.shutdown(Duration::from_secs(timeout)).await;
+        status
+    }
+}
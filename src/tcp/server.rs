@@ -1,179 +1,824 @@
 use super::client::Client;
+use crate::game::event_bus::{MatchEnded, MatchEvent};
+use crate::game::event_log::MAX_EVENTS;
 use crate::game::game::GameInstance;
-use crate::models::exit_code::ExitStatus;
-use crate::models::init_server::InitServerRequest;
+use crate::game::rewards::{self, RewardsInput};
+use crate::game::match_format::{FormatLegality, MatchFormat, MatchFormatRegistry};
+use crate::game::start_barrier::StartBarrier;
+use crate::game::wager::Wager;
+use crate::models::exit_code::{ExitCode, ExitStatus};
+use crate::models::init_server::{BotProfile, InitServerRequest};
+use crate::models::match_report::{ConnectionQualityReport, MatchReport};
+use crate::tcp::audit::ActionAudit;
+use crate::tcp::bot::Bot;
 use crate::tcp::client::TemporaryClient;
 use crate::tcp::header::HeaderType;
+use crate::tcp::listener::ListenerSet;
+use crate::tcp::draft::Drafts;
+use crate::tcp::lobby::Lobby;
 use crate::tcp::packet::Packet;
+use crate::tcp::payload;
 use crate::tcp::protocol::Protocol;
-use crate::utils::errors::ServerInstanceError;
-use crate::{logger, utils::logger::Logger, SERVER_INSTANCE};
+use crate::tcp::session::SessionTokens;
+use crate::tcp::sideboard::Sideboards;
+use crate::tcp::social::Social;
+use crate::tcp::spectator::Spectator;
+use crate::tcp::transport::ClientStream;
+use crate::tcp::viewers::{ViewerAccess, VIEWERS_FILE};
+use crate::utils::artifacts::ArtifactBundle;
+use crate::utils::dead_letter::DeadLetterQueue;
+use crate::utils::errors::{GameInstanceError, ServerInstanceError};
+use crate::utils::logger::{match_span, Logger};
+use crate::utils::replay::ReplayWriter;
+use crate::utils::result_reporter::ResultReporter;
+use crate::{logger, LIFECYCLE, MATCHES, METRICS, SETTINGS};
+use chrono::Utc;
 use std::collections::HashMap;
-use std::{io::Error, net::Ipv4Addr, sync::Arc};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
-use tokio::{net::TcpListener, sync::RwLock};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::{io::Error, sync::Arc};
+use tokio::sync::RwLock;
+use tokio::task::AbortHandle;
+use tracing::Instrument;
+use crate::models::ids::{MatchId, PlayerId};
 
-static HOST: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 1);
-
-/// Represents the main server instance.
+/// Represents a match hosted by the server.
 ///
-/// Manages the TCP listener, game state, Lua scripts, connected players, and packet broadcasting.
+/// Manages the game state, Lua scripts, connected players, and packet broadcasting of the match;
+/// the `MatchHost` accepts its connections.
 pub struct ServerInstance {
-    pub socket: TcpListener, // The TCP listener for accepting incoming client connections.
-    pub listening: Arc<RwLock<bool>>, // Whether the server listen loop is running.
+    pub addresses: Vec<SocketAddr>, // The addresses the clients of the match connect to.
+    pub match_id: MatchId,
+    pub match_type: String,
+    pub listening: Arc<RwLock<bool>>, // Whether the match still accepts clients.
+    pub reporter: Arc<ResultReporter>, // Reports the match result, keeping undelivered ones as dead letters.
     pub game_instance: Arc<GameInstance>,
     pub exit_status: Arc<RwLock<Option<ExitStatus>>>, // The exit status of the server.
-    pub connected_clients: Arc<RwLock<HashMap<String, Arc<Client>>>>, // A map of connected players, identified by their unique IDs.
+    pub report: Arc<RwLock<Option<MatchReport>>>, // The result of the match once it ended, sent by the `report` shutdown hook.
+    pub connected_clients: Arc<RwLock<HashMap<PlayerId, Arc<Client>>>>, // A map of connected players, identified by their unique IDs.
+    pub viewers: ViewerAccess, // Who may join the match as spectators.
+    pub spectators: Arc<RwLock<Vec<Arc<Spectator>>>>, // Clients watching the public game state.
+    pub wager: Option<Arc<RwLock<Wager>>>, // Stake of a wagered match and the players who confirmed it.
+    pub start_barrier: Arc<RwLock<StartBarrier>>, // Players who are ready for the match to start.
+    pub sessions: SessionTokens, // Session tokens issued to the players, checked on reconnection.
+    pub social: Social,          // Emotes and chat relayed between the players and to the spectators.
+    pub audit: ActionAudit,      // The actions of each player the server rejected, see `audit`.
+    pub bots: HashMap<PlayerId, BotProfile>, // Seats played by the server, see `bot`.
+    tasks: Mutex<Vec<AbortHandle>>, // The background tasks of the match, stopped when it is closed.
 }
 
 impl ServerInstance {
+    /// Creates the match requested by the matchmaker, with the game instance of its players.
+    ///
+    /// # Arguments
+    /// * `host` - The host the match is played on, whose listeners its clients connect to.
+    /// * `request` - The `InitServer` request of the match.
     pub async fn init_server(
-        uninitialized: Arc<UninitializedServer>,
+        host: &MatchHost,
         request: InitServerRequest,
     ) -> Result<ServerInstance, ServerInstanceError> {
-        match SERVER_INSTANCE.initialized() {
-            true => Err(ServerInstanceError::AlreadyInitialized),
-            false => {
-                if let Ok(server) = Arc::try_unwrap(uninitialized) {
-                    match GameInstance::create_instance(request.players).await {
-                        Ok(game_instance) => Ok(ServerInstance {
-                            socket: server.socket,
-                            game_instance: Arc::new(game_instance),
-                            exit_status: Arc::new(RwLock::new(None)),
-                            listening: Arc::new(RwLock::new(false)),
-                            connected_clients: Arc::new(RwLock::new(HashMap::new())),
-                        }),
-                        Err(error) => Err(ServerInstanceError::GameInstanceFail(error.to_string())),
-                    }
-                } else {
-                    Err(ServerInstanceError::UnwrapFailed)
-                }
+        let player_ids: Vec<_> = request.players.iter().map(|p| p.id.clone()).collect();
+        let replay = SETTINGS.get().filter(|s| s.replay_enabled).map(|s| {
+            let bundle = ArtifactBundle::new(&s.artifacts_path, &request.match_id);
+            let viewers = ViewerAccess::replay(request.viewers.as_ref(), &player_ids);
+            if let Err(error) = bundle.write_json(VIEWERS_FILE, &viewers) {
+                logger!(ERROR, "[SERVER] Could not keep the replay viewers: {error}");
+            }
+            Arc::new(ReplayWriter::start(bundle.dir(), s.replay_rotate_size))
+        });
+
+        let seed = request.seed.unwrap_or_else(rand::random);
+        logger!(
+            INFO,
+            "[SERVER] Match `{}` random seed: {seed}",
+            &request.match_id
+        );
+
+        let start_barrier =
+            StartBarrier::new(player_ids.clone()).scheduled(request.scheduled_start);
+        let wager = request
+            .stake
+            .map(|stake| Arc::new(RwLock::new(Wager::new(stake, player_ids))));
+
+        let format = Self::match_format(&request.match_type).await;
+        logger!(
+            INFO,
+            "[SERVER] Match `{}` is played in the `{}` format",
+            &request.match_id,
+            &format.name
+        );
+        let rules = request
+            .rules
+            .unwrap_or_else(|| format.rules_for(&request.match_type));
+        let bots = request
+            .players
+            .iter()
+            .filter_map(|p| Some((p.id.clone(), p.bot.clone()?)))
+            .collect();
+
+        match GameInstance::create_instance(request.players, seed, format, rules, replay).await {
+            Ok(game_instance) => Ok(ServerInstance {
+                addresses: host.listeners.addresses().to_vec(),
+                match_id: request.match_id,
+                match_type: request.match_type,
+                reporter: Arc::clone(&host.reporter),
+                game_instance: Arc::new(game_instance),
+                exit_status: Arc::new(RwLock::new(None)),
+                report: Arc::new(RwLock::new(None)),
+                listening: Arc::new(RwLock::new(true)),
+                connected_clients: Arc::new(RwLock::new(HashMap::new())),
+                viewers: ViewerAccess::spectators(request.spectatable, request.viewers.as_ref()),
+                spectators: Arc::new(RwLock::new(Vec::new())),
+                wager,
+                start_barrier: Arc::new(RwLock::new(start_barrier)),
+                sessions: SessionTokens::new(Duration::from_secs(
+                    SETTINGS.get().map_or(600, |s| s.session_token_ttl),
+                )),
+                social: Social::default(),
+                audit: ActionAudit::default(),
+                bots,
+                tasks: Mutex::new(Vec::new()),
+            }),
+            Err(GameInstanceError::DeckIllegal(illegal)) => {
+                Err(ServerInstanceError::DeckIllegal(illegal))
             }
+            Err(error) => Err(error.into()),
         }
     }
 
-    /// Starts the main server loop and handles incoming client connections.
-    ///
-    /// - Spawns a background task to broadcast game state updates.
-    /// - Accepts new TCP clients, logs them, registers them, and spawns their handling task.
-    ///
-    /// Runs indefinitely. Requires `self` as `Arc` for shared access.
-    pub async fn listen(self: Arc<Self>) {
-        let protocol = Arc::new(Protocol::new(self.clone(), self.game_instance.clone()));
+    /// The format of a match type, with the bans and legal cards published by the legality
+    /// service at `DECK_LEGALITY_URL`.
+    pub async fn match_format(match_type: &str) -> MatchFormat {
+        let mut format = Self::match_formats().resolve(match_type);
+        if let Some(url) = SETTINGS.get().and_then(|s| s.deck_legality_url.as_ref()) {
+            match FormatLegality::fetch(url, &format.name).await {
+                Ok(legality) => format.deck.apply(legality),
+                Err(error) => logger!(
+                    ERROR,
+                    "[SERVER] Could not fetch the legality of the `{}` format: {error}",
+                    &format.name
+                ),
+            }
+        }
+        format
+    }
+
+    /// The built-in match formats, with the custom ones listed in `MATCH_FORMATS_PATH`.
+    fn match_formats() -> MatchFormatRegistry {
+        let Some(settings) = SETTINGS.get() else {
+            return MatchFormatRegistry::default();
+        };
+
+        MatchFormatRegistry::load(Path::new(&settings.match_formats_path)).unwrap_or_else(|error| {
+            logger!(
+                ERROR,
+                "[SERVER] Could not read the custom match formats: {error}"
+            );
+            MatchFormatRegistry::default()
+        })
+    }
+
+    /// Starts the background tasks of the match, served by `protocol`.
+    pub fn start(self: Arc<Self>, protocol: Arc<Protocol>) {
+        // Spawn a background task applying default resolutions to expired prompts.
+        self.spawn(Arc::clone(&protocol).expire_prompts());
+
+        // Spawn a background task pinging idle clients and detecting dead connections.
+        self.spawn(Arc::clone(&protocol).keepalive());
+
+        // Spawn a background task aborting a wagered match whose stake is not confirmed in time.
+        if self.wager.is_some() {
+            self.spawn(self.clone().enforce_stake_deadline());
+        }
+
+        // Spawn a background task aborting the match if some player does not get ready in time.
+        self.spawn(self.clone().enforce_ready_deadline());
+
+        // Spawn a background task relaying the events of the match to the spectators.
+        self.spawn(Arc::clone(&protocol).relay_to_spectators());
+
+        // Spawn a background task counting down to the start of a scheduled match.
+        self.spawn(Arc::clone(&protocol).count_down_to_schedule());
+
+        // Spawn a task playing each bot seat of the match.
+        for (player_id, profile) in &self.bots {
+            self.spawn(Bot::join(
+                Arc::clone(&protocol),
+                player_id.clone(),
+                profile.clone(),
+            ));
+        }
 
         // Spawn a background task to handle game state updates.
         // tokio::spawn({
         //     let protocol_clone = Arc::clone(&protocol);
         //     async move { protocol_clone.cycle_game_state().await }
         // });
+    }
+
+    /// Spawns a background task of the match, in the current span, to be aborted by `close`.
+    fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(task.in_current_span());
+        self.tasks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(handle.abort_handle());
+    }
+
+    /// Whether player actions may be resolved: always, unless the match is wagered and some player
+    /// has not confirmed the stake yet.
+    pub async fn stake_confirmed(&self) -> bool {
+        match &self.wager {
+            Some(wager) => wager.read().await.is_confirmed(),
+            None => true,
+        }
+    }
+
+    /// Aborts a wagered match, refunding the stake, if some player has not confirmed the stake
+    /// within `STAKE_CONFIRM_TIMEOUT` seconds.
+    async fn enforce_stake_deadline(self: Arc<Self>) {
+        let timeout = SETTINGS.get().map_or(60, |s| s.stake_confirm_timeout);
+        tokio::time::sleep(Duration::from_secs(timeout)).await;
+        if !self.stake_confirmed().await {
+            logger!(
+                WARN,
+                "[SERVER] Stake of match `{}` was not confirmed within {timeout} seconds",
+                &self.match_id
+            );
+            self.finish_match(None, "The stake was not confirmed in time")
+                .await;
+        }
+    }
+
+    /// Aborts the match if some player has not connected and sent `Ready` within `READY_TIMEOUT`
+    /// seconds, counted from the scheduled start for scheduled matches.
+    async fn enforce_ready_deadline(self: Arc<Self>) {
+        let timeout = SETTINGS.get().map_or(60, |s| s.ready_timeout);
+        let until_scheduled = self.start_barrier.read().await.until_scheduled();
+        tokio::time::sleep(until_scheduled.unwrap_or_default() + Duration::from_secs(timeout))
+            .await;
+
+        let no_shows = {
+            let start_barrier = self.start_barrier.read().await;
+            if start_barrier.all_ready() {
+                return;
+            }
+            start_barrier.missing()
+        };
+        logger!(
+            WARN,
+            "[SERVER] Players {no_shows:?} of match `{}` were not ready within {timeout} seconds",
+            &self.match_id
+        );
+        self.end_match(
+            ExitCode::PlayersNoShow,
+            None,
+            "A player did not get ready in time",
+            no_shows,
+        )
+        .await;
+    }
+
+    /// Forfeits a player who dropped during the match if they are still away once the grace
+    /// period has passed. The player is defeated: their opponent wins, or in a team match the
+    /// match goes on without them while a teammate stands, their turns being skipped.
+    ///
+    /// # Arguments
+    /// * `player_id` - The player who dropped.
+    /// * `disconnects` - The disconnects of the player when they dropped, so a player who came
+    ///   back and dropped again is only forfeited by the timer of their latest disconnect.
+    /// * `grace` - How long the player has to reconnect.
+    pub async fn forfeit_after_grace(
+        self: Arc<Self>,
+        player_id: PlayerId,
+        disconnects: u32,
+        grace: Duration,
+    ) {
+        tokio::time::sleep(grace).await;
+
+        let still_away = match self.connected_clients.read().await.get(&player_id) {
+            Some(client) => {
+                client.quality.disconnects() == disconnects
+                    && client.quality.disconnected_for().is_some()
+            }
+            None => false,
+        };
+        if !still_away {
+            return;
+        }
+
+        let their_turn = {
+            let mut game_state = self.game_instance.game_state.write().await;
+            game_state.forfeited.push(player_id.clone());
+            game_state.active_player.as_ref() == Some(&player_id)
+        };
+        logger!(
+            WARN,
+            "[SERVER] `{player_id}` did not reconnect within {} seconds and forfeits",
+            grace.as_secs()
+        );
+
+        // A teammate still standing plays on for the team.
+        let Some((winner_id, _)) = self.match_outcome().await else {
+            if their_turn {
+                self.game_instance.start_next_turn().await;
+            }
+            self.game_instance.bus.publish(MatchEvent::StateChanged);
+            return;
+        };
+        self.end_match(
+            ExitCode::PlayerForfeited,
+            winner_id,
+            &format!("`{player_id}` did not reconnect in time"),
+            Vec::new(),
+        )
+        .await;
+    }
+
+    /// Ends the match if a player has been defeated.
+    pub async fn check_match_end(&self) {
+        if let Some((winner_id, reason)) = self.match_outcome().await {
+            self.finish_match(winner_id, reason).await;
+        }
+    }
+
+    /// Whether the defeated players, forfeits included, end the match.
+    ///
+    /// The remaining player wins; if every player was defeated at once the match is a draw. In a
+    /// team match the match goes on while both teams have a player standing, then the first seat
+    /// of the remaining team is reported as the winner.
+    ///
+    /// # Returns
+    /// * `None` - If the match goes on.
+    /// * `Some((winner_id, reason))` - The winner, `None` on a draw, and why the match ended.
+    async fn match_outcome(&self) -> Option<(Option<PlayerId>, &'static str)> {
+        let (defeated, seating) = {
+            let game_state = self.game_instance.game_state.read().await;
+            (
+                game_state.defeated_players().await,
+                game_state.seating.clone(),
+            )
+        };
+
+        if defeated.is_empty() {
+            return None;
+        }
+
+        if seating.is_team_match() {
+            let winner_id = match seating.standing_teams(&defeated)[..] {
+                [_, _] => return None,
+                [team] => seating.team(team).first().map(|id| (*id).clone()),
+                _ => None,
+            };
+            return Some((winner_id, "A team was defeated"));
+        }
+
+        let players = self.game_instance.connected_players.read().await;
+        let mut survivors = players.keys().filter(|id| !defeated.contains(id));
+        let winner_id = match (survivors.next(), survivors.next()) {
+            (Some(winner), None) => Some(winner.clone()),
+            _ => None,
+        };
+        Some((winner_id, "A player was defeated"))
+    }
+
+    /// Registers the work done before the process exits, in order: reporting the result of the
+    /// match, flushing the replay, then closing the connections of players and spectators.
+    pub fn register_shutdown_hooks(self: &Arc<Self>) {
+        let server = Arc::clone(self);
+        LIFECYCLE.on_shutdown("report", move || async move { server.send_report().await });
+
+        let server = Arc::clone(self);
+        LIFECYCLE.on_shutdown(
+            "replay",
+            move || async move { server.finish_replay().await },
+        );
+
+        let server = Arc::clone(self);
+        LIFECYCLE.on_shutdown("connections", move || async move {
+            server.close_connections().await
+        });
+    }
+
+    /// Does the work of the shutdown hooks for a match ending while the process keeps hosting
+    /// others, then stops the background tasks of the match.
+    pub async fn close(&self) {
+        self.send_report().await;
+        self.finish_replay().await;
+        self.close_connections().await;
+        for task in self
+            .tasks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .drain(..)
+        {
+            task.abort();
+        }
+        METRICS.forget_match(&self.match_id);
+    }
+
+    /// Sends the report of the match, once it ended.
+    async fn send_report(&self) {
+        let report = self.report.read().await.clone();
+        if let Some(report) = report {
+            self.reporter.report(&report).await;
+        }
+    }
+
+    async fn finish_replay(&self) {
+        if let Some(replay) = &self.game_instance.game_state.read().await.replay {
+            replay.finish().await;
+        }
+    }
+
+    /// Stops accepting clients and closes the connections of the players and spectators.
+    async fn close_connections(&self) {
+        *self.listening.write().await = false;
+        let clients: Vec<_> = self
+            .connected_clients
+            .read()
+            .await
+            .values()
+            .cloned()
+            .collect();
+        for client in clients {
+            client.close().await;
+        }
+        for spectator in self.spectators.write().await.drain(..) {
+            spectator.close().await;
+        }
+    }
+
+    /// Ends the match: records the exit status and the match report (settling the stake of a
+    /// wagered match), emits the performance report and stops accepting connections, then lets
+    /// the process exit; the shutdown hooks send the report. A process hosting several matches
+    /// closes the match instead, and keeps running. Only the first call has any effect.
+    ///
+    /// # Arguments
+    /// * `winner_id` - The winning player, or `None` for a draw.
+    /// * `reason` - Why the match ended.
+    pub async fn finish_match(&self, winner_id: Option<PlayerId>, reason: &str) {
+        self.end_match(ExitCode::MatchEnded, winner_id, reason, Vec::new())
+            .await;
+    }
+
+    /// Ends the match like `finish_match`, with the exit code of the server and the players
+    /// reported as not having shown up.
+    pub async fn end_match(
+        &self,
+        exit_code: ExitCode,
+        winner_id: Option<PlayerId>,
+        reason: &str,
+        no_shows: Vec<PlayerId>,
+    ) {
+        {
+            let mut exit_status = self.exit_status.write().await;
+            if exit_status.is_some() {
+                return;
+            }
+
+            *exit_status = Some(ExitStatus::new(exit_code, reason));
+        }
+
+        let players: Vec<PlayerId> = self
+            .game_instance
+            .connected_players
+            .read()
+            .await
+            .keys()
+            .cloned()
+            .collect();
+        let rewards = self
+            .compute_rewards(winner_id.clone(), players.clone(), reason)
+            .await;
+        let stake = match &self.wager {
+            Some(wager) => Some(wager.read().await.settle(winner_id.as_ref())),
+            None => None,
+        };
+
+        let connection_quality = self.connection_quality(&players).await;
+        let report = MatchReport {
+            winner_id,
+            connection_quality,
+            no_shows,
+            violations: self.audit.summaries(),
+            players,
+            rewards,
+            stake,
+            best_of: (self.game_instance.format.best_of > 1)
+                .then_some(self.game_instance.format.best_of),
+            report_id: uuid::Uuid::new_v4(),
+            match_id: self.match_id.clone(),
+            match_type: self.match_type.clone(),
+            reason: reason.to_string(),
+            ended_at: Utc::now().timestamp_millis(),
+        };
+
+        logger!(INFO, "[SERVER] Match `{}` ended: {reason}", &self.match_id);
+        self.game_instance
+            .bus
+            .publish(MatchEvent::MatchEnded(MatchEnded {
+                winner_id: report.winner_id.clone(),
+                reason: reason.to_string(),
+            }));
+        self.game_instance
+            .game_state
+            .read()
+            .await
+            .think_time
+            .write()
+            .await
+            .stop();
+        self.emit_audit(&report).await;
+        *self.report.write().await = Some(report);
+        self.emit_profile().await;
+        *self.listening.write().await = false;
+
+        // A process hosting several matches keeps running, closing only this one.
+        if MATCHES.hosts_many() {
+            if let Some(protocol) = MATCHES.remove(&self.match_id).await {
+                let server = Arc::clone(&protocol.server_instance);
+                tokio::spawn(async move { server.close().await }.in_current_span());
+            }
+            return;
+        }
+        if let Some(exit_status) = self.exit_status.read().await.clone() {
+            LIFECYCLE.exit(exit_status);
+        }
+    }
+
+    /// Summarizes the connection quality of every player who connected during the match.
+    async fn connection_quality(&self, players: &[PlayerId]) -> Vec<ConnectionQualityReport> {
+        let clients = self.connected_clients.read().await;
+        players
+            .iter()
+            .filter_map(|player_id| {
+                let client = clients.get(player_id)?;
+                Some(client.quality.report(player_id.clone()))
+            })
+            .collect()
+    }
+
+    /// Runs the rewards hook over the event log of the match, see `rewards::compute_rewards`.
+    async fn compute_rewards(
+        &self,
+        winner_id: Option<PlayerId>,
+        players: Vec<PlayerId>,
+        reason: &str,
+    ) -> Option<serde_json::Value> {
+        let input = {
+            let game_state = self.game_instance.game_state.read().await;
+            let events = game_state.events.read().await;
+            RewardsInput {
+                winner_id,
+                players,
+                counts: events.counts().clone(),
+                events: events.since(0, MAX_EVENTS),
+                match_id: self.match_id.clone(),
+                match_type: self.match_type.clone(),
+                reason: reason.to_string(),
+                rounds: game_state.rounds,
+            }
+        };
+
+        rewards::compute_rewards(&self.game_instance.scripts, &input).await
+    }
+
+    /// Writes the match performance report, with the think time of every turn unless
+    /// `THINK_TIME_ANALYTICS` is disabled, to the artifact bundle and the metrics registry.
+    async fn emit_profile(&self) {
+        let mut profile = self.game_instance.profiler.report();
+        if SETTINGS.get().is_none_or(|s| s.think_time_analytics) {
+            let game_state = self.game_instance.game_state.read().await;
+            profile.think_time = game_state.think_time.read().await.turns();
+        }
+
+        logger!(
+            INFO,
+            "[SERVER] Match `{}` profile: {profile}",
+            &self.match_id
+        );
+
+        let root = SETTINGS.get().map_or("artifacts", |s| &s.artifacts_path);
+        let bundle = ArtifactBundle::new(root, &self.match_id);
+        let written = profile.clone();
+        let write =
+            tokio::task::spawn_blocking(move || bundle.write_json("profile.json", &written));
+        match write.await {
+            Ok(Ok(_)) => {}
+            Ok(Err(error)) => logger!(ERROR, "[SERVER] Could not write the match profile: {error}"),
+            Err(error) => logger!(ERROR, "[SERVER] Writing the match profile failed: {error}"),
+        }
+
+        METRICS.record_match_profile(&self.match_id, profile);
+    }
+
+    /// Writes the trail of the rejected actions of every player who made any to the artifact
+    /// bundle, for moderators reviewing the players flagged in the match report.
+    async fn emit_audit(&self, report: &MatchReport) {
+        if report.violations.is_empty() {
+            return;
+        }
+
+        let trails: HashMap<_, _> = report
+            .violations
+            .iter()
+            .map(|summary| {
+                let player_id = summary.player_id.clone();
+                let trail = self.audit.trail(&player_id);
+                (player_id, trail)
+            })
+            .collect();
+        let root = SETTINGS.get().map_or("artifacts", |s| &s.artifacts_path);
+        let bundle = ArtifactBundle::new(root, &self.match_id);
+        let write = tokio::task::spawn_blocking(move || bundle.write_json("audit.json", &trails));
+        match write.await {
+            Ok(Ok(_)) => {}
+            Ok(Err(error)) => logger!(ERROR, "[SERVER] Could not write the audit trail: {error}"),
+            Err(error) => logger!(ERROR, "[SERVER] Writing the audit trail failed: {error}"),
+        }
+    }
+}
+
+/// Binds the listen addresses and serves every match of the process: the matchmaker sends an
+/// `InitServer` packet for each match to host, and the clients are routed to their match through
+/// `MATCHES`.
+pub struct MatchHost {
+    pub listeners: ListenerSet, // The TCP listeners and QUIC endpoints accepting incoming client connections.
+    pub listening: Arc<RwLock<bool>>, // Whether the listen loop is running.
+    pub reporter: Arc<ResultReporter>, // Reports the results of the matches, shared so the dead letters are retried once.
+    pub lobby: Lobby,                  // Players waiting for an opponent when `LOBBY_MODE` is set.
+    pub drafts: Drafts,                // Limited matches whose players are drafting their decks.
+    pub sideboards: Sideboards,        // Games of a series whose players are sideboarding.
+}
+
+impl MatchHost {
+    /// Binds every listen address of the settings.
+    ///
+    /// # Returns
+    /// An error if any of the addresses could not be bound.
+    pub async fn create_instance() -> Result<Self, Error> {
+        let listeners = ListenerSet::from_settings(SETTINGS.get()).inspect_err(|error| {
+            logger!(ERROR, "[SERVER] Could not listen: {error}");
+        })?;
+        for address in listeners.addresses() {
+            logger!(INFO, "[SERVER] Listening on `{address}`");
+        }
+        Ok(Self::with_listeners(listeners))
+    }
+
+    /// A host accepting the clients of listeners bound elsewhere, such as the in-memory
+    /// listeners of the tests.
+    pub fn with_listeners(listeners: ListenerSet) -> Self {
+        let dead_letters = SETTINGS
+            .get()
+            .map_or("dead_letters.jsonl", |s| &s.dead_letter_path);
+        Self {
+            listeners,
+            listening: Arc::new(RwLock::new(true)),
+            reporter: Arc::new(ResultReporter::new(DeadLetterQueue::new(dead_letters))),
+            lobby: Lobby::default(),
+            drafts: Drafts::default(),
+            sideboards: Sideboards::default(),
+        }
+    }
+
+    /// Accepts the connections of the matchmaker and of the clients, each handled as a
+    /// `TemporaryClient` until it is routed.
+    ///
+    /// Runs indefinitely. Requires `self` as `Arc` for shared access.
+    pub async fn listen(self: Arc<Self>) {
+        // Spawn a background task retrying the match reports that could not be delivered.
+        let reporter = Arc::clone(&self.reporter);
+        tokio::spawn(async move { reporter.retry_dead_letters().await }.in_current_span());
 
-        // Main loop to accept and handle incoming client connections.
         while *self.listening.read().await {
-            match self.socket.accept().await {
+            match self.listeners.accept().await {
                 Err(error) => logger!(INFO, "[SERVER] Failed to accept client connection: {error}"),
                 Ok((stream, addr)) => {
                     logger!(INFO, "[CONNECTION] Accepted request from `{addr}`");
-                    let protocol_clone = Arc::clone(&protocol);
+                    let host = Arc::clone(&self);
 
                     // Spawn a task to handle the temporary client.
-                    tokio::spawn(async move {
-                        let temp_client = TemporaryClient::new(stream, addr, protocol_clone).await;
+                    let handle_client = async move {
+                        let temp_client = TemporaryClient::new(stream, addr, host).await;
                         temp_client.handle_temp_client().await;
-                    });
+                    };
+                    tokio::spawn(handle_client.in_current_span());
                 }
             }
         }
     }
-}
 
-pub struct UninitializedServer {
-    pub socket: TcpListener,
-    pub listening: Arc<RwLock<bool>>,
-}
+    /// Hosts the match of an `InitServer` packet, or opens its draft if it has one or its
+    /// sideboarding if it is not the first game of its series, answering the matchmaker if it
+    /// could not be.
+    ///
+    /// A process hosting a single match exits when its initialization fails.
+    ///
+    /// # Returns
+    /// * `Ok(())` - The match, its draft or its sideboarding accepts players.
+    /// * `Err(ServerInstanceError)` - Why the match could not be hosted.
+    pub async fn handle_init_server(
+        self: &Arc<Self>,
+        stream: &mut ClientStream,
+        packet: &Packet,
+    ) -> Result<(), ServerInstanceError> {
+        let request =
+            payload::decode::<InitServerRequest>(&HeaderType::InitServer, &packet.payload);
+        let result = match request {
+            Err(error) => Err(ServerInstanceError::from(error)),
+            Ok(request) if request.draft.is_some() => {
+                self.drafts.open(Arc::clone(self), request).await
+            }
+            Ok(request) if request.game > 1 => {
+                self.sideboards.open(Arc::clone(self), request).await
+            }
+            Ok(request) => self.host(request).await.map(|_| ()),
+        };
 
-impl UninitializedServer {
-    pub async fn create_instance(port: u16) -> Result<Self, Error> {
-        match TcpListener::bind((HOST, port)).await {
-            Ok(listener) => {
-                logger!(INFO, "[SERVER] Listening on port `{port}`");
-                Ok(Self {
-                    socket: listener,
-                    listening: Arc::new(RwLock::new(false)),
-                })
+        if let Err(error) = &result {
+            logger!(ERROR, "[SERVER] Could not initialize a match: {error}");
+            let _ = error.reply(packet).write_to(stream).await;
+            let hosting = matches!(
+                error,
+                ServerInstanceError::AlreadyInitialized(_)
+                    | ServerInstanceError::CapacityReached(_)
+            );
+            if !hosting && !MATCHES.hosts_many() {
+                LIFECYCLE.exit(ExitStatus::new(ExitCode::from(error), error.to_string()));
             }
-            Err(error) => Err(error),
         }
+        result
     }
 
-    pub async fn await_for_initialization(
-        self: Arc<Self>,
-    ) -> Result<ServerInstance, ServerInstanceError> {
-        while *self.listening.read().await {
-            return match self.socket.accept().await {
-                Err(error) => {
-                    logger!(INFO, "[SERVER] Failed to accept client connection: {error}");
-                    Err(ServerInstanceError::PlaceHolderError)
-                }
-                Ok((stream, _)) => {
-                    let me = self.clone();
-                    me.listen_to_connection(stream).await
-                }
-            }
+    /// Creates a match, adds it to `MATCHES` and starts its background tasks. A process hosting
+    /// a single match registers the shutdown hooks of the match.
+    ///
+    /// # Returns
+    /// The protocol serving the match, or an error if it is already hosted, there is no room for
+    /// it or it could not be created.
+    pub async fn host(
+        &self,
+        request: InitServerRequest,
+    ) -> Result<Arc<Protocol>, ServerInstanceError> {
+        MATCHES.admits(&request.match_id).await?;
+        let server = Arc::new(ServerInstance::init_server(self, request).await?);
+        let protocol = Arc::new(Protocol::new(
+            Arc::clone(&server),
+            Arc::clone(&server.game_instance),
+        ));
+        MATCHES.insert(Arc::clone(&protocol)).await?;
+
+        if !MATCHES.hosts_many() {
+            server.register_shutdown_hooks();
         }
-        
-        Err(ServerInstanceError::PlaceHolderError)
+        match_span(&server.match_id).in_scope(|| Arc::clone(&server).start(Arc::clone(&protocol)));
+        logger!(INFO, "[SERVER] Hosting match `{}`", &server.match_id);
+        Ok(protocol)
     }
 
-    pub async fn listen_to_connection(
-        self: Arc<Self>,
-        mut stream: TcpStream,
-    ) -> Result<ServerInstance, ServerInstanceError> {
-        let mut buffer = [0; 1024];
-        while *self.listening.read().await {
-            let read_bytes = match stream.read(&mut buffer).await {
-                Ok(0) => return Err(ServerInstanceError::PlaceHolderError),
-                Err(_) => return Err(ServerInstanceError::PlaceHolderError),
-                Ok(n) => n,
-            };
-
-            let mut send_packet = async |packet: Packet| {
-                let _ = stream.write(&packet.wrap_packet()).await;
-            };
+    /// Registers the work done before the process exits: stopping the listen loop and, in a
+    /// process hosting several matches, closing the matches still being played.
+    pub fn register_shutdown_hooks(self: &Arc<Self>) {
+        let host = Arc::clone(self);
+        LIFECYCLE.on_shutdown("listeners", move || async move {
+            *host.listening.write().await = false;
+        });
 
-            match Packet::parse(&buffer[..read_bytes]) {
-                Ok(packet) => {
-                    if packet.header.header_type == HeaderType::InitServer {
-                        return match serde_cbor::from_slice::<InitServerRequest>(&packet.payload) {
-                            Err(error) => {
-                                let packet =
-                                    Packet::new(HeaderType::ERROR, error.to_string().as_bytes());
-                                send_packet(packet).await;
-                                Err(ServerInstanceError::PlaceHolderError)
-                            }
-                            Ok(request) => {
-                                match ServerInstance::init_server(self.clone(), request).await {
-                                    Ok(server) => Ok(server),
-                                    Err(error) => {
-                                        let packet = Packet::new(
-                                            HeaderType::ERROR,
-                                            error.to_string().as_bytes(),
-                                        );
-                                        send_packet(packet).await;
-                                        Err(ServerInstanceError::PlaceHolderError)
-                                    }
-                                }
-                            }
-                        };
-                    }
+        if MATCHES.hosts_many() {
+            LIFECYCLE.on_shutdown("matches", || async {
+                for server in MATCHES.servers().await {
+                    server.close().await;
                 }
-                Err(error) => {
-                    let packet = Packet::new(HeaderType::ERROR, error.to_string().as_bytes());
-                    send_packet(packet).await;
-                }
-            }
+            });
         }
+    }
+}
 
-        Err(ServerInstanceError::PlaceHolderError)
+impl ServerInstanceError {
+    /// The packet answering the matchmaker's `InitServer` request: an illegal deck is answered
+    /// with every violation, to tell the player what to fix, a request that could not be decoded
+    /// as other invalid payloads are, and other failures with an `ERROR` packet.
+    pub fn reply(&self, request: &Packet) -> Packet {
+        match self {
+            ServerInstanceError::DeckIllegal(illegal)
+            | ServerInstanceError::GameInstance(GameInstanceError::DeckIllegal(illegal)) => {
+                let payload = serde_cbor::to_vec(illegal).unwrap_or_default();
+                Packet::reply_to(request, HeaderType::DeckIllegal, &payload)
+            }
+            ServerInstanceError::InvalidRequest(error) => Packet::reply_to(
+                request,
+                error.reply_header(HeaderType::ERROR),
+                self.to_string().as_bytes(),
+            ),
+            _ => Packet::reply_to(request, HeaderType::ERROR, self.to_string().as_bytes()),
+        }
     }
 }
@@ -0,0 +1,142 @@
+use crate::models::ids::PlayerId;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Random bytes in a session token.
+const TOKEN_BYTES: usize = 32;
+
+/// Issued to a player in a `ConnectAck` packet once they are connected, so they can reconnect
+/// without going through the auth server again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionToken {
+    pub session_token: String, // The token to present in `Reconnect`.
+    pub expires_at: i64,       // Unix timestamp (milliseconds) after which the token is refused.
+}
+
+/// Why a session token was refused, in which case the player must authenticate again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionRefusal {
+    /// No token was issued to the player, or the token is not theirs.
+    Unknown,
+    /// The token is past its lifetime.
+    Expired,
+}
+
+/// A token issued to a player.
+struct Ticket {
+    token: String,
+    username: String,
+    expires: Instant,
+}
+
+/// The session tokens issued by this server, one per player.
+///
+/// Tokens never leave the server except to the player they were issued to, and a new token
+/// replaces the previous one of the player.
+pub struct SessionTokens {
+    tickets: Mutex<HashMap<PlayerId, Ticket>>, // The latest token issued to each player.
+    lifetime: Duration,                        // How long a token is accepted after it is issued.
+}
+
+impl SessionTokens {
+    pub fn new(lifetime: Duration) -> Self {
+        Self {
+            tickets: Mutex::new(HashMap::new()),
+            lifetime,
+        }
+    }
+
+    /// Issues a new token to a player, replacing the previous one.
+    ///
+    /// # Arguments
+    /// * `player_id` - The player the token identifies.
+    /// * `username` - The username of the player, reported back when the token is presented.
+    pub fn issue(&self, player_id: &PlayerId, username: &str) -> SessionToken {
+        let token: String = (0..TOKEN_BYTES)
+            .map(|_| format!("{:02x}", rand::random::<u8>()))
+            .collect();
+        self.lock().insert(
+            player_id.clone(),
+            Ticket {
+                token: token.clone(),
+                username: username.to_string(),
+                expires: Instant::now() + self.lifetime,
+            },
+        );
+
+        SessionToken {
+            session_token: token,
+            expires_at: Utc::now().timestamp_millis() + self.lifetime.as_millis() as i64,
+        }
+    }
+
+    /// Checks a token presented by a player.
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The username of the player, if the token is the latest issued to them.
+    /// * `Err(SessionRefusal)` - Why the token was refused.
+    pub fn validate(&self, player_id: &PlayerId, token: &str) -> Result<String, SessionRefusal> {
+        let tickets = self.lock();
+        let Some(ticket) = tickets.get(player_id) else {
+            return Err(SessionRefusal::Unknown);
+        };
+        if !constant_time_eq(ticket.token.as_bytes(), token.as_bytes()) {
+            return Err(SessionRefusal::Unknown);
+        }
+        if Instant::now() >= ticket.expires {
+            return Err(SessionRefusal::Expired);
+        }
+        Ok(ticket.username.clone())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PlayerId, Ticket>> {
+        self.tickets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Compares two tokens without leaking through timing how much of them matches.
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right)
+            .fold(0, |diff, (l, r)| diff | (l ^ r))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_are_validated_locally() {
+        let (red, blue) = (PlayerId::from("red"), PlayerId::from("blue"));
+        let sessions = SessionTokens::new(Duration::from_secs(60));
+        let first = sessions.issue(&red, "Red");
+        assert_eq!(64, first.session_token.len());
+        assert_eq!(
+            Ok(String::from("Red")),
+            sessions.validate(&red, &first.session_token)
+        );
+        assert_eq!(
+            Err(SessionRefusal::Unknown),
+            sessions.validate(&blue, &first.session_token)
+        );
+
+        let second = sessions.issue(&red, "Red");
+        assert_eq!(
+            Err(SessionRefusal::Unknown),
+            sessions.validate(&red, &first.session_token)
+        );
+        assert!(sessions.validate(&red, &second.session_token).is_ok());
+
+        let expired = SessionTokens::new(Duration::ZERO);
+        let token = expired.issue(&red, "Red");
+        assert_eq!(
+            Err(SessionRefusal::Expired),
+            expired.validate(&red, &token.session_token)
+        );
+    }
+}
@@ -0,0 +1,367 @@
+use crate::game::entity::deck::Deck;
+use crate::game::entity::player::Player;
+use crate::game::match_format::DeckRules;
+use crate::game::sideboard::{self, SideboardSwap, SideboardView};
+use crate::models::client_requests::{MatchTarget, SideboardRequest};
+use crate::models::exit_code::{ExitCode, ExitStatus};
+use crate::models::ids::{MatchId, PlayerId};
+use crate::models::init_server::InitServerRequest;
+use crate::tcp::client::TemporaryClient;
+use crate::tcp::compat::WireFormat;
+use crate::tcp::error_response::ErrorResponse;
+use crate::tcp::header::HeaderType;
+use crate::tcp::lobby::Seat;
+use crate::tcp::packet::Packet;
+use crate::tcp::payload;
+use crate::tcp::rejection::Rejection;
+use crate::tcp::server::{MatchHost, ServerInstance};
+use crate::utils::errors::{PlayerConnectionError, ServerInstanceError, SideboardError};
+use crate::{
+    logger,
+    utils::logger::{match_span, Logger},
+    LIFECYCLE, MATCHES, SETTINGS,
+};
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+use tracing::Instrument;
+
+/// What the players of a series wait for between two games.
+#[derive(Clone)]
+enum SideboardPhase {
+    Open,         // Their swaps, until `SIDEBOARD_TIMEOUT` runs out.
+    Hosted(Seat), // The next game, created with the sideboarded decks, or why it could not be.
+}
+
+/// The decks of the players and those who submitted their swaps.
+struct SideboardState {
+    decks: HashMap<PlayerId, Deck>,
+    submitted: HashSet<PlayerId>,
+}
+
+/// The sideboarding of the players of a series, before a game after the first is hosted.
+///
+/// The players connect with their `Connect` request as they would to the match, and receive
+/// `Sideboard` with their deck and registered sideboard. Each of them may send one
+/// `SideboardRequest` swapping cards between the two, validated against the deck rules of the
+/// format. The game is hosted once every player submitted their swaps or `SIDEBOARD_TIMEOUT`
+/// seconds after the matchmaker initialized it, with the decks of the players who did not submit
+/// left unchanged. The connected players are then connected to the match, which starts as usual.
+pub struct SideboardRoom {
+    match_id: MatchId,
+    game: u32,
+    players: Vec<PlayerId>, // The players who sideboard; bots play their deck unchanged.
+    rules: DeckRules,       // The deck rules of the format the sideboarded decks must follow.
+    deadline: i64,          // Unix timestamp (milliseconds) the game is hosted at.
+    request: Mutex<Option<InitServerRequest>>, // Taken to host the game once sideboarding is over.
+    state: Mutex<SideboardState>,
+    phase: watch::Sender<SideboardPhase>,
+    submitted: Notify, // Wakes the room up when every player submitted their swaps.
+}
+
+impl SideboardRoom {
+    fn state(&self) -> MutexGuard<'_, SideboardState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Keeps a player of the series connected until they are connected to the next game or leave.
+    /// A player who leaves may connect again while the others sideboard.
+    ///
+    /// # Arguments
+    /// * `client` - The client, which must have completed the handshake.
+    /// * `connect` - Its `Connect` request, used again to connect it to the match.
+    pub async fn join(self: Arc<Self>, mut client: TemporaryClient, connect: Packet) {
+        let Some(negotiated) = client.negotiated else {
+            let _ = client
+                .reject(&connect, PlayerConnectionError::HandshakeRequired)
+                .await;
+            return;
+        };
+        let player_id = match Player::new_connection(&connect.payload).await {
+            Ok(player) => player.player_id,
+            Err(error) => {
+                let _ = client.reject(&connect, error).await;
+                return;
+            }
+        };
+        if !self.players.contains(&player_id) {
+            let _ = client
+                .reject(&connect, PlayerConnectionError::PlayerNotConnected)
+                .await;
+            return;
+        }
+        logger!(INFO, "[SIDEBOARD] `{player_id}` is sideboarding");
+
+        let wire_format = WireFormat::for_protocol(&negotiated);
+        let mut phase = self.phase.subscribe();
+        phase.mark_changed();
+        loop {
+            // Peeking does not consume anything, so a packet is never read halfway when the
+            // client is connected to its match.
+            let mut first = [0u8; 1];
+            let packet = tokio::select! {
+                changed = phase.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                    let current = phase.borrow_and_update().clone();
+                    match current {
+                        SideboardPhase::Open => self.send_deck(&mut client, &connect, &player_id).await,
+                        SideboardPhase::Hosted(seat) => {
+                            Self::take_seat(client, &connect, &player_id, seat).await;
+                            return;
+                        }
+                    }
+                    continue;
+                }
+                peeked = client.stream.peek(&mut first) => match peeked {
+                    Ok(read) if read > 0 => client.read_packet(wire_format).await,
+                    _ => Ok(None),
+                },
+            };
+
+            match packet {
+                Ok(Some(packet)) if packet.header.header_type == HeaderType::SideboardRequest => {
+                    let request = payload::decode::<SideboardRequest>(
+                        &HeaderType::SideboardRequest,
+                        &packet.payload,
+                    );
+                    let answer = match request {
+                        Ok(request) => match self.submit(&player_id, &request.swaps) {
+                            Ok(()) => Packet::reply_to(&packet, HeaderType::ActionAccepted, b""),
+                            Err(error) => ErrorResponse::from(&error)
+                                .reply(&packet, HeaderType::ActionRejected),
+                        },
+                        Err(error) => ErrorResponse::from(&error)
+                            .reply(&packet, error.reply_header(HeaderType::ActionRejected)),
+                    };
+                    client.send(&answer).await;
+                }
+                Ok(Some(packet)) if packet.header.header_type == HeaderType::Ping => {
+                    client
+                        .send(&Packet::reply_to(&packet, HeaderType::Pong, &[]))
+                        .await;
+                }
+                Ok(Some(_)) => continue,
+                Ok(None) | Err(_) => {
+                    logger!(INFO, "[SIDEBOARD] `{player_id}` left before the game");
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Applies the swaps of a player to their deck; a player submits their swaps once.
+    fn submit(&self, player_id: &PlayerId, swaps: &[SideboardSwap]) -> Result<(), SideboardError> {
+        let everyone = {
+            let mut state = self.state();
+            if state.submitted.contains(player_id) {
+                return Err(SideboardError::AlreadySubmitted);
+            }
+            if let Some(deck) = state.decks.get(player_id) {
+                let sideboarded = sideboard::apply(deck, swaps, &self.rules)?;
+                state.decks.insert(player_id.clone(), sideboarded);
+            }
+            state.submitted.insert(player_id.clone());
+            state.submitted.len() == self.players.len()
+        };
+
+        logger!(
+            INFO,
+            "[SIDEBOARD] `{player_id}` swapped {} cards",
+            swaps.len()
+        );
+        if everyone {
+            self.submitted.notify_one();
+        }
+        Ok(())
+    }
+
+    async fn send_deck(
+        &self,
+        client: &mut TemporaryClient,
+        request: &Packet,
+        player_id: &PlayerId,
+    ) {
+        let Some(deck) = self.state().decks.get(player_id).cloned() else {
+            return;
+        };
+        let view = SideboardView {
+            game: self.game,
+            deck,
+            deadline: self.deadline,
+        };
+        if let Ok(payload) = serde_cbor::to_vec(&view) {
+            let packet = Packet::reply_to(request, HeaderType::Sideboard, &payload);
+            client.send(&packet).await;
+        }
+    }
+
+    /// Connects a player to the next game, or tells them why it could not be created.
+    async fn take_seat(
+        mut client: TemporaryClient,
+        connect: &Packet,
+        player_id: &PlayerId,
+        seat: Seat,
+    ) {
+        let protocol = match seat {
+            Ok(protocol) => protocol,
+            Err(rejection) => {
+                client.send(&rejection.packet(Some(connect))).await;
+                return;
+            }
+        };
+
+        let span = match_span(&protocol.server_instance.match_id);
+        let connection = protocol.handle_connect(Arc::new(client), connect);
+        if let Err(error) = connection.instrument(span).await {
+            logger!(
+                ERROR,
+                "[SIDEBOARD] Could not connect `{player_id}` to their match ({error})"
+            );
+        }
+    }
+
+    /// Waits for every player to submit their swaps, or for the deadline, then hosts the game.
+    async fn run(self: Arc<Self>, host: Arc<MatchHost>) {
+        let everyone = self.state().submitted.len() == self.players.len();
+        if !everyone {
+            let left = (self.deadline - Utc::now().timestamp_millis()).max(0) as u64;
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_millis(left)) => logger!(
+                    INFO,
+                    "[SIDEBOARD] Time is up, the players who did not sideboard play their deck unchanged"
+                ),
+                _ = self.submitted.notified() => {}
+            }
+        }
+
+        self.host_match(&host).await;
+    }
+
+    /// Hosts the game with the sideboarded decks and takes the players to it.
+    async fn host_match(&self, host: &Arc<MatchHost>) {
+        let request = self
+            .request
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let Some(mut request) = request else {
+            return;
+        };
+        {
+            let state = self.state();
+            for player in &mut request.players {
+                if let Some(deck) = state.decks.get(&player.id) {
+                    player.deck = Some(deck.clone());
+                }
+            }
+        }
+
+        let seat = host.host(request).await.map_err(|error| {
+            logger!(
+                ERROR,
+                "[SIDEBOARD] Could not create game {} of `{}`: {error}",
+                self.game,
+                self.match_id
+            );
+            if !MATCHES.hosts_many() {
+                LIFECYCLE.exit(ExitStatus::new(ExitCode::from(&error), error.to_string()));
+            }
+            Rejection::from(&error)
+        });
+        host.sideboards.close(&self.match_id);
+        self.phase.send_replace(SideboardPhase::Hosted(seat));
+    }
+}
+
+/// The games of a series the matchmaker initialized, by match id, while their players sideboard.
+#[derive(Default)]
+pub struct Sideboards {
+    rooms: Mutex<HashMap<MatchId, Arc<SideboardRoom>>>,
+}
+
+impl Sideboards {
+    fn rooms(&self) -> MutexGuard<'_, HashMap<MatchId, Arc<SideboardRoom>>> {
+        self.rooms.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Fetches the decks of the players of a game after the first of its series and lets them
+    /// sideboard for `SIDEBOARD_TIMEOUT` seconds.
+    ///
+    /// # Returns
+    /// An error if the match is already hosted or sideboarding, there is no room for it, or the
+    /// deck of a player could not be fetched.
+    pub async fn open(
+        &self,
+        host: Arc<MatchHost>,
+        request: InitServerRequest,
+    ) -> Result<(), ServerInstanceError> {
+        let match_id = request.match_id.clone();
+        MATCHES.admits(&match_id).await?;
+        if self.rooms().contains_key(&match_id) {
+            return Err(ServerInstanceError::AlreadyInitialized(match_id));
+        }
+
+        let mut decks = HashMap::new();
+        for player in request.players.iter().filter(|p| p.bot.is_none()) {
+            let deck = match &player.deck {
+                Some(deck) => deck.clone(),
+                None => Player::preload_player_deck(&player.deck_id)
+                    .await
+                    .map_err(|source| ServerInstanceError::SideboardFailed {
+                        player_id: player.id.clone(),
+                        source,
+                    })?,
+            };
+            decks.insert(player.id.clone(), deck);
+        }
+
+        let timeout = SETTINGS.get().map_or(60, |s| s.sideboard_timeout);
+        let format = ServerInstance::match_format(&request.match_type).await;
+        let room = Arc::new(SideboardRoom {
+            match_id: match_id.clone(),
+            game: request.game,
+            players: decks.keys().cloned().collect(),
+            rules: format.deck,
+            deadline: Utc::now().timestamp_millis() + timeout as i64 * 1000,
+            request: Mutex::new(Some(request)),
+            state: Mutex::new(SideboardState {
+                decks,
+                submitted: HashSet::new(),
+            }),
+            phase: watch::Sender::new(SideboardPhase::Open),
+            submitted: Notify::new(),
+        });
+
+        logger!(
+            INFO,
+            "[SIDEBOARD] Game {} of `{match_id}`: the players sideboard for {timeout} seconds",
+            room.game
+        );
+        self.rooms().insert(match_id.clone(), Arc::clone(&room));
+        let span = match_span(&match_id);
+        tokio::spawn(room.run(host).instrument(span));
+        Ok(())
+    }
+
+    /// Finds the sideboarding a `Connect` request is for: the match it names, else the match of
+    /// its player.
+    pub fn route(&self, target: &MatchTarget) -> Option<Arc<SideboardRoom>> {
+        let rooms = self.rooms();
+        if let Some(match_id) = &target.match_id {
+            return rooms.get(match_id).cloned();
+        }
+        let player_id = target.player_id.as_ref()?;
+        rooms
+            .values()
+            .find(|room| room.players.contains(player_id))
+            .cloned()
+    }
+
+    fn close(&self, match_id: &MatchId) {
+        self.rooms().remove(match_id);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::game::board;
+use crate::models::client_requests::{ChatRequest, EmoteRequest};
+use crate::models::ids::PlayerId;
+use crate::utils::errors::SocialError;
+use crate::utils::profanity::{ProfanityFilter, WordListFilter};
+use crate::utils::rate_limit::RateLimiter;
+use crate::utils::runtime_config::SharedConfig;
+use crate::{logger, utils::logger::Logger, RUNTIME_CONFIG, SETTINGS};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The emotes clients may send; clients render them in the player's language.
+pub const EMOTES: &[&str] = &["hello", "well_played", "thanks", "oops", "wow", "threaten"];
+
+/// The social channel of the match: the emotes and chat messages players send each other and
+/// the spectators.
+pub struct Social {
+    config: SharedConfig,       // Rate limits and the longest chat message.
+    emote_limiter: RateLimiter, // Emotes each player may send, see `EMOTE_RATE_LIMIT`.
+    emotes_muted: AtomicBool,   // Set by operators to stop relaying emotes in this match.
+    chat_limiter: RateLimiter,  // Chat messages each player may send, see `CHAT_RATE_LIMIT`.
+    chat_to_spectators: bool,   // Whether the spectators are relayed the chat of the players.
+    filter: Option<Box<dyn ProfanityFilter>>, // Cleans up chat messages before they are relayed.
+    mutes: Mutex<HashMap<PlayerId, HashSet<PlayerId>>>, // The players each player muted.
+}
+
+impl Default for Social {
+    fn default() -> Self {
+        let settings = SETTINGS.get();
+        let filter = settings
+            .and_then(|s| s.profanity_wordlist_path.as_deref())
+            .and_then(|path| match WordListFilter::load(Path::new(path)) {
+                Ok(filter) => Some(Box::new(filter) as Box<dyn ProfanityFilter>),
+                Err(error) => {
+                    logger!(
+                        WARN,
+                        "[SOCIAL] Could not load the word list `{path}`: {error}"
+                    );
+                    None
+                }
+            });
+
+        Self {
+            config: RUNTIME_CONFIG.clone(),
+            emote_limiter: RateLimiter::default(),
+            emotes_muted: AtomicBool::new(false),
+            chat_limiter: RateLimiter::default(),
+            chat_to_spectators: settings.is_some_and(|s| s.chat_to_spectators),
+            filter,
+            mutes: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Social {
+    /// Reads the rate limits and the longest chat message from another configuration.
+    pub fn with_config(mut self, config: SharedConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Replaces the filter chat messages go through, or removes it.
+    pub fn with_filter(mut self, filter: Option<Box<dyn ProfanityFilter>>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Stops or resumes relaying emotes in this match.
+    pub fn mute_emotes(&self, muted: bool) {
+        self.emotes_muted.store(muted, Ordering::Relaxed);
+    }
+
+    /// Checks that an emote may be relayed, counting it against the allowance of the player.
+    ///
+    /// # Returns
+    /// * `Ok(())` - If the emote may be relayed.
+    /// * `Err(SocialError)` - If emotes are muted, the emote or its ping is invalid, or the
+    ///   player sent too many emotes lately.
+    pub fn check_emote(
+        &self,
+        player_id: &PlayerId,
+        request: &EmoteRequest,
+    ) -> Result<(), SocialError> {
+        if self.emotes_muted.load(Ordering::Relaxed) {
+            return Err(SocialError::EmotesMuted);
+        }
+        if !EMOTES.contains(&request.emote_id.as_str()) {
+            return Err(SocialError::UnknownEmote(request.emote_id.clone()));
+        }
+        if let Some(ping) = &request.ping {
+            board::parse_position(&ping.position)
+                .map_err(|_| SocialError::InvalidPing(ping.position.clone()))?;
+        }
+        let config = self.config.get();
+        let window = Duration::from_secs(config.emote_rate_window);
+        if !self
+            .emote_limiter
+            .try_acquire(player_id, config.emote_rate_limit, window)
+        {
+            return Err(SocialError::RateLimited);
+        }
+        Ok(())
+    }
+
+    /// Checks that a chat message may be relayed, counting it against the allowance of the
+    /// player, and runs it through the profanity filter.
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The message to relay.
+    /// * `Err(SocialError)` - If the message is empty, too long or refused by the filter, or the
+    ///   player sent too many messages lately.
+    pub fn check_chat(
+        &self,
+        player_id: &PlayerId,
+        request: &ChatRequest,
+    ) -> Result<String, SocialError> {
+        let message = request.message.trim();
+        if message.is_empty() {
+            return Err(SocialError::EmptyMessage);
+        }
+        let config = self.config.get();
+        if message.chars().count() > config.chat_max_length {
+            return Err(SocialError::MessageTooLong(config.chat_max_length));
+        }
+        let message = match &self.filter {
+            Some(filter) => filter.clean(message).ok_or(SocialError::MessageFiltered)?,
+            None => message.to_string(),
+        };
+        let window = Duration::from_secs(config.chat_rate_window);
+        if !self
+            .chat_limiter
+            .try_acquire(player_id, config.chat_rate_limit, window)
+        {
+            return Err(SocialError::RateLimited);
+        }
+        Ok(message)
+    }
+
+    /// Whether the spectators are relayed the chat of the players.
+    pub fn chat_to_spectators(&self) -> bool {
+        self.chat_to_spectators
+    }
+
+    /// Mutes or unmutes the chat of a player for another.
+    ///
+    /// # Arguments
+    /// * `player_id` - The player who no longer wants to read the other.
+    /// * `target_id` - The player muted.
+    /// * `muted` - `false` to unmute the player.
+    pub fn mute(&self, player_id: &PlayerId, target_id: &PlayerId, muted: bool) {
+        let mut mutes = self.mutes.lock().unwrap_or_else(|e| e.into_inner());
+        let muted_players = mutes.entry(player_id.clone()).or_default();
+        match muted {
+            true => muted_players.insert(target_id.clone()),
+            false => muted_players.remove(target_id),
+        };
+    }
+
+    /// Whether a player muted the chat of another.
+    pub fn is_muted(&self, player_id: &PlayerId, sender_id: &PlayerId) -> bool {
+        let mutes = self.mutes.lock().unwrap_or_else(|e| e.into_inner());
+        mutes
+            .get(player_id)
+            .is_some_and(|muted_players| muted_players.contains(sender_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::runtime_config::RuntimeConfig;
+
+    /// Refuses any message mentioning `spam`.
+    struct NoSpam;
+
+    impl ProfanityFilter for NoSpam {
+        fn clean(&self, message: &str) -> Option<String> {
+            (!message.contains("spam")).then(|| message.to_string())
+        }
+    }
+
+    #[test]
+    fn test_chat_is_checked_filtered_and_muted() {
+        let (red, blue) = (PlayerId::from("red"), PlayerId::from("blue"));
+        let config = SharedConfig::default();
+        let social = Social::default()
+            .with_config(config.clone())
+            .with_filter(Some(Box::new(NoSpam)));
+        let chat = |message: &str| ChatRequest {
+            message: message.to_string(),
+        };
+
+        assert_eq!(
+            Ok(String::from("gg")),
+            social.check_chat(&red, &chat("  gg "))
+        );
+        assert_eq!(
+            Err(SocialError::EmptyMessage),
+            social.check_chat(&red, &chat(" "))
+        );
+        assert_eq!(
+            Err(SocialError::MessageTooLong(200)),
+            social.check_chat(&red, &chat(&"a".repeat(201)))
+        );
+        assert_eq!(
+            Err(SocialError::MessageFiltered),
+            social.check_chat(&red, &chat("buy spam"))
+        );
+        for _ in 0..4 {
+            assert!(social.check_chat(&red, &chat("hi")).is_ok());
+        }
+        assert_eq!(
+            Err(SocialError::RateLimited),
+            social.check_chat(&red, &chat("hi"))
+        );
+        assert!(social.check_chat(&blue, &chat("hi")).is_ok());
+
+        // The limits follow the configuration as it is reloaded.
+        config.set(RuntimeConfig {
+            chat_max_length: 2,
+            ..RuntimeConfig::default()
+        });
+        assert_eq!(
+            Err(SocialError::MessageTooLong(2)),
+            social.check_chat(&blue, &chat("wp!"))
+        );
+
+        social.mute(&blue, &red, true);
+        assert!(social.is_muted(&blue, &red));
+        assert!(!social.is_muted(&red, &blue));
+        social.mute(&blue, &red, false);
+        assert!(!social.is_muted(&blue, &red));
+    }
+}
@@ -0,0 +1,260 @@
+use crate::tcp::handshake::{NegotiatedProtocol, FEATURE_ERROR_CODES};
+use crate::tcp::packet::Packet;
+use crate::tcp::transport::{ClientStream, WriteHalf};
+use crate::utils::checksum::Checksum;
+use crate::{logger, utils::logger::Logger};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::RwLock;
+use tokio::task::JoinSet;
+
+/// A client watching the match without playing in it.
+///
+/// Spectators only ever receive the public view of the game state, so hidden information such as
+/// the cards in hand never reaches them. Nothing they send is read, and they always use the
+/// current wire format.
+pub struct Spectator {
+    pub addr: SocketAddr,
+    pub negotiated: NegotiatedProtocol,
+    checksum: Box<dyn Checksum>,
+    write_stream: RwLock<WriteHalf>,
+}
+
+impl Spectator {
+    /// Creates a spectator from an accepted connection that completed the handshake.
+    pub fn new(stream: ClientStream, addr: SocketAddr, negotiated: NegotiatedProtocol) -> Self {
+        let (_, write_stream) = stream.into_split();
+        Self {
+            addr,
+            negotiated,
+            checksum: negotiated.checksum.build(None),
+            write_stream: RwLock::new(write_stream),
+        }
+    }
+
+    /// Encodes a packet as it is sent to this spectator: in the negotiated payload encoding,
+    /// sealed with the negotiated checksum and compressed with the negotiated algorithm.
+    ///
+    /// This is the CPU-heavy part of a send, kept apart so broadcasts can run it in parallel.
+    pub fn encode(&self, packet: &Packet) -> Box<[u8]> {
+        self.negotiated
+            .outgoing(packet)
+            .sealed(&*self.checksum, self.negotiated.compression)
+            .wrap_packet()
+    }
+
+    /// Writes an encoded frame to the spectator.
+    pub async fn write(&self, frame: &[u8]) -> std::io::Result<usize> {
+        let mut write_stream = self.write_stream.write().await;
+        write_stream.write_all(frame).await?;
+        write_stream.flush().await?;
+        Ok(frame.len())
+    }
+
+    /// Closes the connection to the spectator.
+    pub async fn close(&self) {
+        let _ = self.write_stream.write().await.shutdown().await;
+    }
+
+    /// Sends a packet to the spectator.
+    ///
+    /// # Returns
+    /// The number of bytes written.
+    pub async fn send(&self, packet: &Packet) -> std::io::Result<usize> {
+        self.write(&self.encode(packet)).await
+    }
+}
+
+/// Sends a packet to every spectator at once.
+///
+/// Spectators that negotiated the same checksum, compression, payload encoding and error codes
+/// receive identical frames, so each distinct frame is encoded once. The encodings run in parallel
+/// on the blocking thread pool, and every spectator is written to from its own task so a slow
+/// connection does not hold the others back.
+///
+/// # Returns
+/// The spectators that could not be reached, including those whose frame failed to encode.
+pub async fn broadcast(
+    spectators: Vec<Arc<Spectator>>,
+    packet: Arc<Packet>,
+) -> Vec<Arc<Spectator>> {
+    let mut groups: HashMap<_, Vec<Arc<Spectator>>> = HashMap::new();
+    for spectator in spectators {
+        let negotiated = spectator.negotiated;
+        let key = (
+            negotiated.checksum,
+            negotiated.compression,
+            negotiated.encoding,
+            negotiated.supports(FEATURE_ERROR_CODES),
+        );
+        groups.entry(key).or_default().push(spectator);
+    }
+
+    let mut encodes = JoinSet::new();
+    let mut pending = HashMap::new(); // The group each encoding task is for, by task id.
+    for group in groups.into_values() {
+        let packet = Arc::clone(&packet);
+        let first = Arc::clone(&group[0]);
+        let task = encodes.spawn_blocking(move || Arc::<[u8]>::from(first.encode(&packet)));
+        pending.insert(task.id(), group);
+    }
+
+    let mut sends = JoinSet::new();
+    let mut unreachable = Vec::new();
+    while let Some(encoded) = encodes.join_next_with_id().await {
+        let (frame, group) = match encoded {
+            Ok((id, frame)) => (frame, pending.remove(&id).unwrap_or_default()),
+            Err(error) => {
+                // Without a frame the group cannot be sent anything; treat it as gone.
+                logger!(
+                    ERROR,
+                    "[SPECTATOR] Could not encode a broadcast frame: {error}"
+                );
+                unreachable.extend(pending.remove(&error.id()).unwrap_or_default());
+                continue;
+            }
+        };
+
+        for spectator in group {
+            let frame = Arc::clone(&frame);
+            sends.spawn(async move { spectator.write(&frame).await.err().map(|_| spectator) });
+        }
+    }
+
+    while let Some(result) = sends.join_next().await {
+        if let Ok(Some(spectator)) = result {
+            unreachable.push(spectator);
+        }
+    }
+
+    unreachable
+}
+
+/// Broadcasts a packet to a spectator list, removing the spectators that could not be reached.
+pub async fn broadcast_and_prune(spectators: &RwLock<Vec<Arc<Spectator>>>, packet: Arc<Packet>) {
+    let recipients = spectators.read().await.clone();
+    let unreachable = broadcast(recipients, packet).await;
+    if unreachable.is_empty() {
+        return;
+    }
+
+    for spectator in &unreachable {
+        logger!(INFO, "[SPECTATOR] Spectator `{}` left", &spectator.addr);
+    }
+
+    spectators
+        .write()
+        .await
+        .retain(|s| !unreachable.iter().any(|u| Arc::ptr_eq(s, u)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tcp::header::HeaderType;
+    use crate::utils::checksum::ChecksumKind;
+    use crate::utils::compression::Compression;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Connects `count` spectators to a local listener that drains everything they are sent.
+    async fn spectators(count: usize, compression: Compression) -> Vec<Arc<Spectator>> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buffer = vec![0u8; 64 * 1024];
+                    while matches!(stream.read(&mut buffer).await, Ok(n) if n > 0) {}
+                });
+            }
+        });
+
+        let negotiated = NegotiatedProtocol {
+            checksum: ChecksumKind::Crc32c,
+            compression,
+            ..NegotiatedProtocol::legacy()
+        };
+
+        let mut spectators = Vec::with_capacity(count);
+        for _ in 0..count {
+            let stream = TcpStream::connect(local).await.unwrap();
+            spectators.push(Arc::new(Spectator::new(stream.into(), local, negotiated)));
+        }
+        spectators
+    }
+
+    fn game_state(size: usize) -> Arc<Packet> {
+        // A small alphabet in a pseudo-random order: compressible, but not trivially.
+        let mut seed = 0x2545_f491_u32;
+        let payload: Vec<u8> = (0..size)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                b"abcdefghijklmnop"[(seed >> 28) as usize]
+            })
+            .collect();
+        Arc::new(Packet::new(HeaderType::GameState, &payload))
+    }
+
+    /// Connects a spectator of each protocol, returning the spectators and the server side of
+    /// their connections, to read what they were sent.
+    async fn connected(protocols: &[NegotiatedProtocol]) -> (Vec<Arc<Spectator>>, Vec<TcpStream>) {
+        let mut spectators = Vec::with_capacity(protocols.len());
+        let mut peers = Vec::with_capacity(protocols.len());
+        for negotiated in protocols {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let local = listener.local_addr().unwrap();
+            let stream = TcpStream::connect(local).await.unwrap();
+            let (peer, _) = listener.accept().await.unwrap();
+            spectators.push(Arc::new(Spectator::new(stream.into(), local, *negotiated)));
+            peers.push(peer);
+        }
+        (spectators, peers)
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_sends_every_spectator_its_negotiated_frame() {
+        let mut protocols = Vec::new();
+        for checksum in [ChecksumKind::Xor, ChecksumKind::Crc32c] {
+            for compression in [Compression::None, Compression::Deflate, Compression::Zstd] {
+                let negotiated = NegotiatedProtocol {
+                    checksum,
+                    compression,
+                    ..NegotiatedProtocol::legacy()
+                };
+                // Two spectators of each protocol share an encoded frame.
+                protocols.extend([negotiated, negotiated]);
+            }
+        }
+
+        let (spectators, peers) = connected(&protocols).await;
+        let packet = game_state(4096);
+        let unreachable = broadcast(spectators.clone(), Arc::clone(&packet)).await;
+        assert!(unreachable.is_empty());
+
+        for ((spectator, mut peer), negotiated) in spectators.iter().zip(peers).zip(&protocols) {
+            spectator.close().await;
+            let received = Packet::read_from(&mut peer).await.unwrap().unwrap();
+            assert!(Packet::read_from(&mut peer).await.unwrap().is_none());
+
+            assert_eq!(received.header.header_type, HeaderType::GameState);
+            assert_eq!(
+                Compression::from_flags(received.header.flags).unwrap(),
+                negotiated.compression
+            );
+            assert_eq!(received.payload, packet.payload);
+            let checksum = negotiated.checksum.build(None);
+            assert!(checksum.verify(received.header.checksum, &received.payload));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_reaches_every_spectator() {
+        let mut spectators = spectators(3, Compression::Zstd).await;
+        spectators.extend(self::spectators(2, Compression::None).await);
+        let unreachable = broadcast(spectators, game_state(4096)).await;
+        assert!(unreachable.is_empty());
+    }
+}
@@ -0,0 +1,194 @@
+#[cfg(feature = "tls")]
+use crate::tcp::listener::Accepted;
+#[cfg(feature = "tls")]
+use crate::tcp::transport::{ClientStream, Connection, WriteHalf};
+use crate::{logger, utils::logger::Logger};
+use rustls::pki_types::pem::{self, PemObject};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use std::io;
+#[cfg(feature = "tls")]
+use std::net::SocketAddr;
+use std::sync::Arc;
+#[cfg(feature = "tls")]
+use std::time::Duration;
+#[cfg(feature = "tls")]
+use tokio::io::AsyncRead;
+#[cfg(feature = "tls")]
+use tokio::net::{TcpListener, TcpStream};
+#[cfg(feature = "tls")]
+use tokio::sync::mpsc;
+#[cfg(feature = "tls")]
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+
+/// Application protocol TLS and QUIC clients may offer in their handshake.
+pub const ALPN: &[u8] = b"ccg";
+
+/// How long a TCP client has to complete its TLS handshake.
+#[cfg(feature = "tls")]
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The certificate and private key served to TLS and QUIC clients.
+pub struct TlsIdentity {
+    certificates: Vec<CertificateDer<'static>>, // The chain, leaf first.
+    key: PrivateKeyDer<'static>,
+}
+
+impl TlsIdentity {
+    /// Loads the PEM certificate chain and private key of `TLS_CERTIFICATE` and
+    /// `TLS_PRIVATE_KEY`, or generates a self-signed certificate for `localhost` when either is
+    /// unset, which clients must then be told to trust.
+    pub fn load(certificate: Option<&str>, private_key: Option<&str>) -> io::Result<Self> {
+        let (Some(certificate), Some(private_key)) = (certificate, private_key) else {
+            logger!(
+                WARN,
+                "[SERVER] No TLS certificate set, serving a self-signed one for `localhost`"
+            );
+            return Self::self_signed();
+        };
+
+        let invalid = |error: pem::Error| io::Error::new(io::ErrorKind::InvalidData, error);
+        let certificates = CertificateDer::pem_file_iter(certificate)
+            .map_err(invalid)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(invalid)?;
+        let key = PrivateKeyDer::from_pem_file(private_key).map_err(invalid)?;
+        Ok(Self { certificates, key })
+    }
+
+    /// A self-signed certificate for `localhost`, for development and tests.
+    pub fn self_signed() -> io::Result<Self> {
+        let generated = rcgen::generate_simple_self_signed(vec![String::from("localhost")])
+            .map_err(io::Error::other)?;
+        Ok(Self {
+            certificates: vec![generated.cert.der().clone()],
+            key: PrivatePkcs8KeyDer::from(generated.key_pair.serialize_der()).into(),
+        })
+    }
+
+    /// The leaf certificate, for clients that pin it.
+    pub fn certificate(&self) -> &CertificateDer<'static> {
+        &self.certificates[0]
+    }
+
+    /// A TLS 1.3 server configuration serving the certificate.
+    pub fn server_config(&self) -> io::Result<rustls::ServerConfig> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .map_err(io::Error::other)?
+            .with_no_client_auth()
+            .with_single_cert(self.certificates.clone(), self.key.clone_key())
+            .map_err(io::Error::other)?;
+        config.alpn_protocols = vec![ALPN.to_vec()];
+        Ok(config)
+    }
+
+    /// Accepts TLS handshakes on TCP connections.
+    #[cfg(feature = "tls")]
+    pub fn acceptor(&self) -> io::Result<TlsAcceptor> {
+        Ok(TlsAcceptor::from(Arc::new(self.server_config()?)))
+    }
+}
+
+#[cfg(feature = "tls")]
+impl Connection for TlsStream<TcpStream> {
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.get_ref().0.peer_addr()
+    }
+
+    fn tcp_stream(&self) -> Option<&TcpStream> {
+        Some(self.get_ref().0)
+    }
+
+    fn split(self: Box<Self>) -> (Box<dyn AsyncRead + Send + Sync + Unpin>, WriteHalf) {
+        let (read, write) = tokio::io::split(*self);
+        (Box::new(read), Box::new(write))
+    }
+}
+
+/// Accepts TLS clients on one TCP listener until the accept pipeline is dropped.
+///
+/// Every client completes its handshake on its own task, so a slow one does not hold the others
+/// back, and joins the pipeline once the handshake is done; clients failing it are dropped.
+#[cfg(feature = "tls")]
+pub async fn accept_loop(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    sender: mpsc::Sender<Accepted>,
+) {
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(error) => match sender.send(Err(error)).await {
+                Ok(()) => continue,
+                Err(_) => break,
+            },
+        };
+        if sender.is_closed() {
+            break;
+        }
+
+        let acceptor = acceptor.clone();
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            let handshake = tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream));
+            match handshake.await {
+                Ok(Ok(stream)) => {
+                    let _ = sender.send(Ok((ClientStream::new(stream), addr))).await;
+                }
+                // A failed handshake only concerns its client, not the accept pipeline.
+                Ok(Err(error)) => {
+                    logger!(WARN, "[SERVER] TLS handshake of `{addr}` failed: {error}")
+                }
+                Err(_) => logger!(WARN, "[SERVER] `{addr}` did not complete its TLS handshake"),
+            }
+        });
+    }
+
+    if let Ok(address) = listener.local_addr() {
+        logger!(DEBUG, "[SERVER] Stopped accepting TLS on `{address}`");
+    }
+}
+
+#[cfg(all(test, feature = "tls"))]
+mod tests {
+    use super::*;
+    use crate::tcp::header::HeaderType;
+    use crate::tcp::packet::Packet;
+    use rustls::pki_types::ServerName;
+    use std::net::Ipv4Addr;
+    use tokio_rustls::TlsConnector;
+
+    #[tokio::test]
+    async fn test_packets_travel_over_tls() {
+        let identity = TlsIdentity::self_signed().unwrap();
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (accepted, mut pipeline) = mpsc::channel(1);
+        let acceptor = identity.acceptor().unwrap();
+        tokio::spawn(accept_loop(listener, acceptor, accepted));
+
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(identity.certificate().clone()).unwrap();
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let connector = TlsConnector::from(Arc::new(config));
+        let socket = TcpStream::connect(address).await.unwrap();
+        let server_name = ServerName::try_from("localhost").unwrap();
+        let mut client = connector.connect(server_name, socket).await.unwrap();
+
+        Packet::new(HeaderType::Ping, b"tls")
+            .write_to(&mut client)
+            .await
+            .unwrap();
+        let (mut server, peer) = pipeline.recv().await.unwrap().unwrap();
+        assert_eq!(server.peer_addr().unwrap(), peer);
+        assert!(server.tcp_stream().is_some());
+        let ping = Packet::read_from(&mut server).await.unwrap().unwrap();
+        assert_eq!(&b"tls"[..], &ping.payload[..]);
+    }
+}
@@ -0,0 +1,281 @@
+use crate::tcp::compat::WireFormat;
+use crate::tcp::packet::Packet;
+use crate::utils::errors::ProtocolError;
+use serde::Deserialize;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream, ReadBuf};
+use tokio::net::TcpStream;
+
+/// The transports a deployment accepts clients over, set with `TRANSPORT`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    #[default]
+    Tcp,
+    /// QUIC over UDP, experimental; needs a build with the `quic` feature.
+    Quic,
+    /// TCP and QUIC, on the same addresses.
+    Both,
+}
+
+impl Transport {
+    pub fn tcp(self) -> bool {
+        self != Transport::Quic
+    }
+
+    pub fn quic(self) -> bool {
+        self != Transport::Tcp
+    }
+}
+
+/// The half of a connection packets are written to.
+pub type WriteHalf = Box<dyn AsyncWrite + Send + Sync + Unpin>;
+
+/// A byte stream clients are served over: a TCP socket, TLS over TCP, a QUIC stream, or an
+/// in-memory pipe in tests.
+///
+/// Nothing above the listeners knows which one it is: clients are served through the
+/// `ClientStream` wrapping their connection, so a new transport only implements this trait.
+pub trait Connection: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static {
+    /// Address of the client.
+    fn peer_addr(&self) -> io::Result<SocketAddr>;
+
+    /// The TCP socket under the connection, for the socket tuning.
+    fn tcp_stream(&self) -> Option<&TcpStream> {
+        None
+    }
+
+    /// Splits the connection into halves, read and written from different tasks.
+    fn split(self: Box<Self>) -> (Box<dyn AsyncRead + Send + Sync + Unpin>, WriteHalf);
+}
+
+impl Connection for TcpStream {
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        TcpStream::peer_addr(self)
+    }
+
+    fn tcp_stream(&self) -> Option<&TcpStream> {
+        Some(self)
+    }
+
+    fn split(self: Box<Self>) -> (Box<dyn AsyncRead + Send + Sync + Unpin>, WriteHalf) {
+        let (read, write) = self.into_split();
+        (Box::new(read), Box::new(write))
+    }
+}
+
+/// One end of `tokio::io::duplex`, serving a client without a socket, such as a bot or a test.
+impl Connection for DuplexStream {
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        Ok(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0))
+    }
+
+    fn split(self: Box<Self>) -> (Box<dyn AsyncRead + Send + Sync + Unpin>, WriteHalf) {
+        let (read, write) = tokio::io::split(*self);
+        (Box::new(read), Box::new(write))
+    }
+}
+
+/// The connection of a client, whatever its transport, read and written a packet at a time.
+pub struct ClientStream {
+    connection: Box<dyn Connection>,
+    peeked: Vec<u8>, // Received but not read yet, see `peek`.
+}
+
+impl ClientStream {
+    pub fn new(connection: impl Connection) -> Self {
+        Self {
+            connection: Box::new(connection),
+            peeked: Vec::new(),
+        }
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.connection.peer_addr()
+    }
+
+    /// The TCP socket of the connection, if it has one, for the socket tuning.
+    pub fn tcp_stream(&self) -> Option<&TcpStream> {
+        self.connection.tcp_stream()
+    }
+
+    /// Reads the next bytes sent by the client without consuming them, like `TcpStream::peek`.
+    /// Cancelling it loses nothing, so it can be raced in `select!`.
+    ///
+    /// # Returns
+    /// The number of bytes peeked at, `0` if the client closed the connection.
+    pub async fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.peeked.is_empty() {
+            let mut chunk = vec![0u8; buf.len()];
+            let read = self.connection.read(&mut chunk).await?;
+            chunk.truncate(read);
+            self.peeked = chunk;
+        }
+
+        let peeked = buf.len().min(self.peeked.len());
+        buf[..peeked].copy_from_slice(&self.peeked[..peeked]);
+        Ok(peeked)
+    }
+
+    /// Reads the next packet in the framing of the client.
+    ///
+    /// # Returns
+    /// * `Ok(None)` - The client closed the connection.
+    pub async fn read_packet(
+        &mut self,
+        wire_format: WireFormat,
+    ) -> Result<Option<Packet>, ProtocolError> {
+        wire_format.read_packet(self).await
+    }
+
+    /// Writes a packet in the framing of the client.
+    pub async fn write_packet(
+        &mut self,
+        wire_format: WireFormat,
+        packet: &Packet,
+    ) -> io::Result<usize> {
+        wire_format.write_packet(packet, self).await
+    }
+
+    /// Flushes what was written and closes the connection.
+    pub async fn close(&mut self) -> io::Result<()> {
+        self.shutdown().await
+    }
+
+    /// Splits the connection into halves, read and written from different tasks. The bytes
+    /// peeked at are read first.
+    pub fn into_split(self) -> (ReadHalf, WriteHalf) {
+        let (read, write) = self.connection.split();
+        (
+            ReadHalf {
+                inner: read,
+                peeked: self.peeked,
+            },
+            write,
+        )
+    }
+}
+
+impl<C: Connection> From<C> for ClientStream {
+    fn from(connection: C) -> Self {
+        ClientStream::new(connection)
+    }
+}
+
+/// The half of a connection packets are read from.
+pub struct ReadHalf {
+    inner: Box<dyn AsyncRead + Send + Sync + Unpin>,
+    peeked: Vec<u8>, // Peeked at before the split and not read yet.
+}
+
+/// Reads the bytes peeked at before the reader.
+fn poll_read_peeked<R: AsyncRead + Unpin + ?Sized>(
+    peeked: &mut Vec<u8>,
+    reader: &mut R,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+) -> Poll<io::Result<()>> {
+    if peeked.is_empty() {
+        return Pin::new(reader).poll_read(cx, buf);
+    }
+
+    let read = buf.remaining().min(peeked.len());
+    buf.put_slice(&peeked[..read]);
+    peeked.drain(..read);
+    Poll::Ready(Ok(()))
+}
+
+impl AsyncRead for ClientStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let stream = self.get_mut();
+        poll_read_peeked(&mut stream.peeked, &mut *stream.connection, cx, buf)
+    }
+}
+
+impl AsyncRead for ReadHalf {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let half = self.get_mut();
+        poll_read_peeked(&mut half.peeked, &mut *half.inner, cx, buf)
+    }
+}
+
+impl AsyncWrite for ClientStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.get_mut().connection).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.get_mut().connection).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.get_mut().connection).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tcp::handshake::NegotiatedProtocol;
+    use crate::tcp::header::HeaderType;
+
+    #[tokio::test]
+    async fn test_packets_are_served_over_an_in_memory_pipe() {
+        let (server, mut client) = tokio::io::duplex(1024);
+        let mut stream = ClientStream::new(server);
+        let wire_format = WireFormat::Current;
+
+        Packet::new(HeaderType::Ping, b"first")
+            .write_to(&mut client)
+            .await
+            .unwrap();
+        let mut first = [0u8; 1];
+        assert_eq!(1, stream.peek(&mut first).await.unwrap());
+        assert_eq!(HeaderType::Ping as u8, first[0]);
+        let ping = stream.read_packet(wire_format).await.unwrap().unwrap();
+        assert_eq!(&b"first"[..], &ping.payload[..]);
+
+        stream
+            .write_packet(wire_format, &Packet::new(HeaderType::Pong, b"first"))
+            .await
+            .unwrap();
+        let pong = Packet::read_from(&mut client).await.unwrap().unwrap();
+        assert_eq!(HeaderType::Pong, pong.header.header_type);
+
+        stream.close().await.unwrap();
+        assert!(Packet::read_from(&mut client).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_peeked_bytes_survive_the_split() {
+        let (server, mut client) = tokio::io::duplex(1024);
+        let mut stream = ClientStream::from(server);
+        let legacy = WireFormat::for_protocol(&NegotiatedProtocol::legacy());
+
+        legacy
+            .write_packet(&Packet::new(HeaderType::Ping, b"legacy"), &mut client)
+            .await
+            .unwrap();
+        let mut first = [0u8; 1];
+        stream.peek(&mut first).await.unwrap();
+
+        let (mut read, _) = stream.into_split();
+        let ping = legacy.read_packet(&mut read).await.unwrap().unwrap();
+        assert_eq!(&b"legacy"[..], &ping.payload[..]);
+    }
+}
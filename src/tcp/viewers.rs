@@ -0,0 +1,139 @@
+use crate::game::entity::player::Player;
+use crate::models::ids::{MatchId, PlayerId};
+use crate::utils::artifacts::ArtifactBundle;
+use crate::utils::errors::SpectatorError;
+use crate::utils::replay::{self, ReplayEntry};
+use crate::SETTINGS;
+use serde::{Deserialize, Serialize};
+
+/// Name of the file of the artifact bundle keeping who may fetch the replay of the match.
+pub const VIEWERS_FILE: &str = "viewers.json";
+
+/// Who may spectate a match and fetch its replay.
+///
+/// An open match admits anyone without authenticating. Otherwise viewers present the token of
+/// their account, checked by the auth server as on `Connect`, and only the accounts allowed are
+/// admitted.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ViewerAccess {
+    #[serde(default)]
+    pub open: bool, // Anyone may watch, without authenticating.
+    #[serde(default)]
+    pub allowed: Vec<PlayerId>, // Accounts allowed to watch, such as the coaches of the players.
+}
+
+impl ViewerAccess {
+    /// The spectators of a match: the viewers of its init request, else anyone when it is
+    /// spectatable and nobody otherwise.
+    pub fn spectators(spectatable: bool, viewers: Option<&ViewerAccess>) -> Self {
+        match viewers {
+            Some(viewers) => viewers.clone(),
+            None => Self {
+                open: spectatable,
+                allowed: Vec::new(),
+            },
+        }
+    }
+
+    /// The viewers of the replay of a match: the viewers of its init request, and its players.
+    pub fn replay(viewers: Option<&ViewerAccess>, players: &[PlayerId]) -> Self {
+        let mut access = viewers.cloned().unwrap_or_default();
+        for player_id in players {
+            if !access.allowed.contains(player_id) {
+                access.allowed.push(player_id.clone());
+            }
+        }
+        access
+    }
+
+    /// Whether anyone at all may watch.
+    pub fn admits_anyone(&self) -> bool {
+        self.open || !self.allowed.is_empty()
+    }
+
+    /// Checks that the holder of `auth_token` may watch.
+    ///
+    /// # Returns
+    /// * `Ok(Some(PlayerId))` - The account of the viewer.
+    /// * `Ok(None)` - The match is open; the token is not checked.
+    /// * `Err(SpectatorError)` - The token was refused or its account is not allowed.
+    pub async fn authorize(&self, auth_token: &str) -> Result<Option<PlayerId>, SpectatorError> {
+        if self.open {
+            return Ok(None);
+        }
+
+        let viewer = Player::authenticate(auth_token).await?;
+        match self.allowed.contains(&viewer.player_id) {
+            true => Ok(Some(viewer.player_id)),
+            false => Err(SpectatorError::NotAllowed(viewer.player_id.to_string())),
+        }
+    }
+}
+
+/// Sent in a `Replay` packet answering `GetReplay`.
+#[derive(Serialize, Debug, Clone)]
+pub struct ReplayResponse {
+    pub match_id: MatchId,
+    pub entries: Vec<ReplayEntry>, // Every entry of the replay so far, in order.
+}
+
+/// Reads the replay of a match for a viewer, from the artifact bundle of the match.
+///
+/// The replay can be fetched while the match is played and after it ended, for as long as its
+/// files are kept.
+///
+/// # Returns
+/// * `Ok(Vec<ReplayEntry>)` - The entries of the replay, in order.
+/// * `Err(SpectatorError)` - The match has no replay, or the viewer is not allowed to fetch it.
+pub async fn fetch_replay(
+    match_id: &MatchId,
+    auth_token: &str,
+) -> Result<Vec<ReplayEntry>, SpectatorError> {
+    let not_found = || SpectatorError::ReplayNotFound(match_id.to_string());
+    let settings = SETTINGS.get().ok_or_else(not_found)?;
+    let bundle = ArtifactBundle::new(&settings.artifacts_path, match_id);
+
+    let viewers = tokio::fs::read(bundle.dir().join(VIEWERS_FILE))
+        .await
+        .map_err(|_| not_found())?;
+    let access: ViewerAccess = serde_json::from_slice(&viewers)
+        .map_err(|error| SpectatorError::InternalError(error.to_string()))?;
+    access.authorize(auth_token).await?;
+
+    replay::read_replay(bundle.dir())
+        .await
+        .map_err(|_| not_found())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replays_are_open_to_the_players() {
+        let (red, blue, coach) = (
+            PlayerId::from("red"),
+            PlayerId::from("blue"),
+            PlayerId::from("coach"),
+        );
+        let players = [red.clone(), blue.clone()];
+
+        let closed = ViewerAccess::spectators(false, None);
+        assert!(!closed.admits_anyone());
+        assert!(ViewerAccess::spectators(true, None).open);
+        assert_eq!(
+            vec![red.clone(), blue.clone()],
+            ViewerAccess::replay(None, &players).allowed
+        );
+
+        let coached = ViewerAccess {
+            open: false,
+            allowed: vec![coach.clone(), red.clone()],
+        };
+        assert_eq!(coached, ViewerAccess::spectators(false, Some(&coached)));
+        assert_eq!(
+            vec![coach, red, blue],
+            ViewerAccess::replay(Some(&coached), &players).allowed
+        );
+    }
+}
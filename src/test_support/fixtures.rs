@@ -0,0 +1,12 @@
+use crate::game::entity::card::Card;
+
+/// A card with no scripts, named after its id.
+pub fn sample_card(id: &str) -> Card {
+    serde_json::from_value(serde_json::json!({
+        "id": id, "name": id, "description": "", "play_cost": 1, "attack": 1, "health": 1,
+        "rarity": 0, "on_play": [], "on_draw": [], "on_attack": [], "on_hit": [],
+        "on_turn_start": [], "on_turn_end": [], "on_death": [], "on_ally_death": [],
+        "on_enemy_death": [],
+    }))
+    .expect("sample card")
+}
@@ -0,0 +1,1163 @@
+use crate::game::entity::card::CardRef;
+use crate::game::entity::deck::Deck;
+use crate::models::client_requests::ConnectionRequest;
+use crate::models::ids::{CardDefId, MatchId};
+use crate::models::init_server::{InitServerRequest, PreloadPlayer};
+use crate::models::settings::Settings;
+use crate::tcp::encoding::PayloadEncoding;
+use crate::tcp::error_response::ErrorCode;
+use crate::tcp::handshake::{HandshakeRequest, PROTOCOL_VERSION, SERVER_FEATURES};
+use crate::tcp::header::HeaderType;
+use crate::tcp::listener::{ListenerSet, Loopback};
+use crate::tcp::packet::Packet;
+use crate::tcp::server::{MatchHost, ServerInstance};
+use crate::tcp::transport::ClientStream;
+pub use crate::test_support::fixtures::sample_card;
+use crate::test_support::mock_services::{MockServices, PlayerFixture};
+use crate::{MATCHES, SETTINGS};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+/// Where test clients reach a test server.
+#[derive(Clone)]
+pub enum TestAddress {
+    Tcp(SocketAddr),         // A server listening on an ephemeral port of localhost.
+    Loopback(Arc<Loopback>), // A server binding no port, reached over in-memory pipes.
+}
+
+/// The mock services every test server of the process is pointed at.
+pub static MOCK_SERVICES: LazyLock<MockServices> = LazyLock::new(MockServices::start);
+
+/// How long a test client waits for a packet before giving up.
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Points every service at the mock services and the files of the server at a temporary
+/// directory. The settings are global, so the first test server of the process sets them.
+fn install_settings() {
+    if SETTINGS.initialized() {
+        return;
+    }
+
+    let url = MOCK_SERVICES.url();
+    let files = std::env::temp_dir().join(format!("ccg-test-server-{}", std::process::id()));
+    let _ = std::fs::create_dir_all(&files);
+    let file = |name: &str| files.join(name).to_string_lossy().to_string();
+    let settings: Settings = serde_json::from_value(serde_json::json!({
+        "AUTH_SERVER": url,
+        "CARD_SERVER": url,
+        "DECK_SERVER": url,
+        "RESULT_SERVER": url,
+        "LISTEN_ADDRESSES": ["127.0.0.1:0"],
+        "ARTIFACTS_PATH": file("artifacts"),
+        "DEAD_LETTER_PATH": file("dead_letters.jsonl"),
+        "SCRIPT_BLOCKLIST_PATH": file("script_blocklist.json"),
+        "HTTP_RETRIES": 0,
+        "BOT_THINK_TIME": 10,
+        "MATCH_START_COUNTDOWN": 0,
+        "MAX_MATCHES": 64,
+        "LOBBY_MODE": true,
+        "JSON_PAYLOADS": true,
+    }))
+    .expect("test settings");
+    let _ = SETTINGS.set(settings);
+}
+
+/// Registers a player with a legal standard deck, 3 copies of 10 sample cards, in the mock
+/// services.
+pub fn sample_player(id: &str) -> PlayerFixture {
+    sample_player_with_cards(id, "sample")
+}
+
+/// Registers a player as `sample_player` does, with sample cards named `<cards>-<index>`.
+pub fn sample_player_with_cards(id: &str, cards: &str) -> PlayerFixture {
+    let player = PlayerFixture::new(id);
+    let cards = (0..10)
+        .map(|index| {
+            let card = sample_card(&format!("{cards}-{index}"));
+            let card_ref = CardRef {
+                id: CardDefId::from(card.id.to_string()),
+                amount: 3,
+                owner_id: None,
+                instances: Vec::new(),
+            };
+            MOCK_SERVICES.add_card(card);
+            card_ref
+        })
+        .collect();
+    let deck = Deck {
+        id: format!("{id}-deck"),
+        player_id: player.id.clone(),
+        name: format!("{id}'s deck"),
+        cards,
+        sideboard: Vec::new(),
+    };
+    MOCK_SERVICES.add_player(player.clone(), deck);
+    player
+}
+
+/// The `InitServer` request of a standard match between players registered with
+/// `sample_player`.
+pub fn init_request(match_id: &str, players: &[&PlayerFixture]) -> InitServerRequest {
+    InitServerRequest {
+        match_id: MatchId::from(match_id),
+        match_type: String::from("standard"),
+        players: players
+            .iter()
+            .map(|player| PreloadPlayer {
+                id: player.id.clone(),
+                deck_id: format!("{}-deck", player.id),
+                pool: None,
+                bot: None,
+                deck: None,
+            })
+            .collect(),
+        spectatable: false,
+        viewers: None,
+        seed: Some(42),
+        stake: None,
+        rules: None,
+        scheduled_start: None,
+        draft: None,
+        game: 1,
+    }
+}
+
+/// A match of a full server, initialized as the matchmaker would, with the platform services
+/// mocked.
+///
+/// Servers are reached over in-memory pipes unless booted with `listen_tcp`, so the tests bind no
+/// port. Every match of the test process is hosted in `MATCHES`, whichever host accepted it.
+pub struct TestServer {
+    pub address: TestAddress,
+    pub server: Arc<ServerInstance>,
+}
+
+impl TestServer {
+    /// Boots a server and sends it `request` in an `InitServer` packet.
+    ///
+    /// # Returns
+    /// * `Ok(TestServer)` - The match, accepting players.
+    /// * `Err(Packet)` - The packet answering the matchmaker if the initialization failed.
+    pub async fn boot(request: InitServerRequest) -> Result<Self, Packet> {
+        let address = Self::listen().await;
+        Self::init(&address, request).await
+    }
+
+    /// Boots a server hosting no match yet, binding no port.
+    ///
+    /// # Returns
+    /// The address the server is reached at.
+    pub async fn listen() -> TestAddress {
+        install_settings();
+        let (listeners, loopback) = ListenerSet::loopback();
+        tokio::spawn(Arc::new(MatchHost::with_listeners(listeners)).listen());
+        TestAddress::Loopback(Arc::new(loopback))
+    }
+
+    /// Boots a server hosting no match yet, listening on an ephemeral port of localhost.
+    ///
+    /// # Returns
+    /// The address the server listens on.
+    pub async fn listen_tcp() -> TestAddress {
+        install_settings();
+        let host = MatchHost::create_instance()
+            .await
+            .expect("bind the test server");
+        let address = host.listeners.addresses()[0];
+        tokio::spawn(Arc::new(host).listen());
+        TestAddress::Tcp(address)
+    }
+
+    /// Sends `request` in an `InitServer` packet to a server already listening on `address`.
+    ///
+    /// # Returns
+    /// * `Ok(TestServer)` - The match, accepting players.
+    /// * `Err(Packet)` - The packet answering the matchmaker if the initialization failed.
+    pub async fn init(address: &TestAddress, request: InitServerRequest) -> Result<Self, Packet> {
+        let match_id = request.match_id.clone();
+        let mut matchmaker = TestClient::open(&address).await;
+        matchmaker.send(HeaderType::InitServer, &request).await;
+
+        // The server only answers the matchmaker when the initialization failed.
+        if let Some(answer) = matchmaker.receive().await {
+            return Err(answer);
+        }
+        let server = MATCHES
+            .servers()
+            .await
+            .into_iter()
+            .find(|server| server.match_id == match_id)
+            .expect("hosted match");
+        Ok(Self {
+            address: address.clone(),
+            server,
+        })
+    }
+
+    /// Opens a connection to the server and connects a player through it.
+    ///
+    /// # Returns
+    /// The client and the packet answering `Connect`.
+    pub async fn join(&self, player: &PlayerFixture) -> (TestClient, Packet) {
+        let mut client = TestClient::open(&self.address).await;
+        client.handshake().await;
+        let request = ConnectionRequest {
+            player_id: player.id.clone(),
+            auth_token: player.auth_token.clone(),
+            current_deck_id: format!("{}-deck", player.id),
+            match_id: Some(self.server.match_id.clone()),
+        };
+        client.send(HeaderType::Connect, &request).await;
+        let answer = client.receive().await.expect("answer to Connect");
+        (client, answer)
+    }
+}
+
+/// A client of a test server speaking the current protocol, without compression.
+pub struct TestClient {
+    stream: ClientStream,
+}
+
+impl TestClient {
+    pub async fn open(address: &TestAddress) -> Self {
+        let stream = match address {
+            TestAddress::Tcp(address) => TcpStream::connect(address).await.map(ClientStream::new),
+            TestAddress::Loopback(loopback) => loopback.connect().await.map(ClientStream::new),
+        };
+        Self {
+            stream: stream.expect("connect to the test server"),
+        }
+    }
+
+    /// Negotiates every feature of the server, with the default checksum and no compression.
+    pub async fn handshake(&mut self) {
+        let request = HandshakeRequest {
+            version: PROTOCOL_VERSION,
+            features: SERVER_FEATURES,
+            checksums: Vec::new(),
+            compression: Vec::new(),
+            encoding: PayloadEncoding::Cbor,
+        };
+        self.send(HeaderType::Handshake, &request).await;
+        let answer = self.receive().await.expect("answer to Handshake");
+        assert_eq!(HeaderType::Handshake, answer.header.header_type);
+    }
+
+    /// Sends a packet carrying `payload` in CBOR.
+    pub async fn send<T: Serialize>(&mut self, header_type: HeaderType, payload: &T) {
+        let payload = serde_cbor::to_vec(payload).expect("CBOR payload");
+        Packet::new(header_type, &payload)
+            .write_to(&mut self.stream)
+            .await
+            .expect("send to the test server");
+    }
+
+    /// Sends a packet carrying `payload` in JSON, as debugging clients do.
+    pub async fn send_json(&mut self, header_type: HeaderType, payload: &serde_json::Value) {
+        let payload = serde_json::to_vec(payload).expect("JSON payload");
+        Packet::new(header_type, &payload)
+            .write_to(&mut self.stream)
+            .await
+            .expect("send to the test server");
+    }
+
+    /// Sends raw bytes, such as a packet the server cannot decode.
+    pub async fn send_bytes(&mut self, bytes: &[u8]) {
+        self.stream
+            .write_all(bytes)
+            .await
+            .expect("send to the test server");
+    }
+
+    /// The next packet from the server, or `None` if the connection closed or nothing came in
+    /// time.
+    pub async fn receive(&mut self) -> Option<Packet> {
+        tokio::time::timeout(RECEIVE_TIMEOUT, Packet::read_from(&mut self.stream))
+            .await
+            .ok()?
+            .ok()?
+    }
+
+    /// The next packet of a type, skipping the packets of other types.
+    pub async fn expect(&mut self, header_type: HeaderType) -> Packet {
+        loop {
+            match self.receive().await {
+                Some(packet) if packet.header.header_type == header_type => return packet,
+                Some(_) => continue,
+                None => panic!("no `{header_type}` packet received"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::draft::{DraftSettings, DraftView};
+    use crate::game::entity::card::CardView;
+    use crate::game::entity::player::Player;
+    use crate::game::rules::RulesProfile;
+    use crate::game::sideboard::{SideboardSwap, SideboardView};
+    use crate::game::status_effect::{StatusEffect, StatusKind};
+    use crate::models::client_requests::{DraftPickRequest, SideboardRequest};
+    use crate::models::ids::CardInstanceId;
+    use crate::models::init_server::BotProfile;
+    use crate::tcp::audit::ViolationKind;
+    use crate::tcp::header::Header;
+    use crate::tcp::lobby::{LobbyStatus, PrivateMatchCreated};
+    use crate::tcp::rejection::{Rejection, RejectionReason};
+    use crate::tcp::viewers::ViewerAccess;
+    use crate::utils::compression::Compression;
+
+    #[tokio::test]
+    async fn test_players_connect_to_a_booted_match() {
+        let (red, blue) = (sample_player("harness-red"), sample_player("harness-blue"));
+        let address = TestServer::listen_tcp().await;
+        let server = TestServer::init(&address, init_request("harness-match", &[&red, &blue]))
+            .await
+            .unwrap_or_else(|packet| {
+                let reason = String::from_utf8_lossy(&packet.payload).to_string();
+                panic!("initialization failed: {reason}")
+            });
+
+        let (mut red_client, answer) = server.join(&red).await;
+        assert_eq!(HeaderType::ConnectAck, answer.header.header_type);
+        let ack: serde_cbor::Value = serde_cbor::from_slice(&answer.payload).unwrap();
+        let serde_cbor::Value::Map(ack) = ack else {
+            panic!("ConnectAck is not a map");
+        };
+        assert!(ack.contains_key(&serde_cbor::Value::Text(String::from("session_token"))));
+
+        let mut stranger = PlayerFixture::new("harness-stranger");
+        stranger.auth_token = String::from("unknown-token");
+        let (_, answer) = server.join(&stranger).await;
+        assert_eq!(HeaderType::ConnectionRejected, answer.header.header_type);
+
+        let (mut blue_client, _) = server.join(&blue).await;
+        assert_eq!(2, server.server.connected_clients.read().await.len());
+        for client in [&mut red_client, &mut blue_client] {
+            client.send(HeaderType::Ready, &()).await;
+        }
+        red_client.expect(HeaderType::MatchStart).await;
+        blue_client.expect(HeaderType::MatchStart).await;
+    }
+
+    #[tokio::test]
+    async fn test_played_cards_reach_both_players_without_a_socket() {
+        let (red, blue) = (
+            sample_player("harness-pipe-red"),
+            sample_player("harness-pipe-blue"),
+        );
+        let server = TestServer::boot(init_request("harness-pipe", &[&red, &blue]))
+            .await
+            .unwrap_or_else(|_| panic!("initialization failed"));
+        assert!(matches!(server.address, TestAddress::Loopback(_)));
+
+        let (mut red_client, _) = server.join(&red).await;
+        let (mut blue_client, _) = server.join(&blue).await;
+        let mut starts = Vec::new();
+        for client in [&mut red_client, &mut blue_client] {
+            client.send(HeaderType::Ready, &()).await;
+        }
+        for client in [&mut red_client, &mut blue_client] {
+            let start = client.expect(HeaderType::MatchStart).await;
+            starts.push(serde_cbor::from_slice::<serde_json::Value>(&start.payload).unwrap());
+        }
+
+        // The first player plays the first card of their opening hand once their turn started,
+        // while the other player cannot play before theirs.
+        let (actor, waiting, first, second) = match starts[0]["first_player"] == red.id.to_string()
+        {
+            true => (&starts[0], &starts[1], &mut red_client, &mut blue_client),
+            false => (&starts[1], &starts[0], &mut blue_client, &mut red_client),
+        };
+        first.expect(HeaderType::TurnStarted).await;
+        let play = |start: &serde_json::Value| {
+            serde_json::json!({
+                "actor_id": start["state"]["player"]["id"],
+                "instance_id": start["state"]["player"]["current_hand"][0]["instance_id"],
+            })
+        };
+        second.send(HeaderType::PlayCard, &play(waiting)).await;
+        let rejected = second.expect(HeaderType::ActionRejected).await;
+        let error: serde_json::Value = serde_cbor::from_slice(&rejected.payload).unwrap();
+        assert_eq!(ErrorCode::NotPlayerTurn as u16, error["code"]);
+
+        let card = actor["state"]["player"]["current_hand"][0].clone();
+        first.send(HeaderType::PlayCard, &play(actor)).await;
+        first.expect(HeaderType::ActionAccepted).await;
+
+        // Both players are sent the state with the card on the board, as they see it.
+        let on_board = |state: &serde_json::Value, seat: &str| {
+            let creatures = state[seat]["board"]["creatures"].as_array().cloned();
+            creatures
+                .unwrap_or_default()
+                .iter()
+                .any(|creature| creature["id"] == card["id"])
+        };
+        for (client, seat) in [(first, "player"), (second, "opponent")] {
+            loop {
+                let state = client.expect(HeaderType::GameState).await;
+                let state: serde_json::Value = serde_cbor::from_slice(&state.payload).unwrap();
+                if on_board(&state, seat) {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_players_end_their_turns_in_turn_and_are_timed_on_each() {
+        let (red, blue) = (
+            sample_player("harness-turns-red"),
+            sample_player("harness-turns-blue"),
+        );
+        let server = TestServer::boot(init_request("harness-turns", &[&red, &blue]))
+            .await
+            .unwrap_or_else(|_| panic!("initialization failed"));
+        let (mut red_client, _) = server.join(&red).await;
+        let (mut blue_client, _) = server.join(&blue).await;
+        for client in [&mut red_client, &mut blue_client] {
+            client.send(HeaderType::Ready, &()).await;
+        }
+
+        async fn turn_started(client: &mut TestClient, turn: u32) -> serde_json::Value {
+            loop {
+                let packet = client.expect(HeaderType::TurnStarted).await;
+                let started: serde_json::Value = serde_cbor::from_slice(&packet.payload).unwrap();
+                if started["turn"] == turn {
+                    return started;
+                }
+            }
+        }
+
+        let mut players = Vec::new();
+        for turn in 1..=3 {
+            let started = turn_started(&mut red_client, turn).await;
+            let player_id = started["player_id"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            let client = match player_id == red.id.to_string() {
+                true => &mut red_client,
+                false => &mut blue_client,
+            };
+            client.send(HeaderType::EndTurn, &()).await;
+            players.push(player_id);
+        }
+        turn_started(&mut red_client, 4).await;
+        assert_ne!(players[0], players[1]);
+        assert_eq!(players[0], players[2]);
+
+        // Every turn played was timed, on the player who played it.
+        let game_state = server.server.game_instance.game_state.read().await;
+        let turns = game_state.think_time.read().await.turns();
+        let timed: Vec<_> = turns
+            .iter()
+            .map(|turn| (turn.turn, turn.player_id.to_string()))
+            .collect();
+        assert_eq!(4, game_state.rounds);
+        assert_eq!(
+            vec![
+                (1, players[0].clone()),
+                (2, players[1].clone()),
+                (3, players[0].clone()),
+                (4, players[1].clone()),
+            ],
+            timed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_turns_end_once_their_time_runs_out() {
+        let (red, blue) = (
+            sample_player("harness-turn-time-red"),
+            sample_player("harness-turn-time-blue"),
+        );
+        let mut request = init_request("harness-turn-time", &[&red, &blue]);
+        request.rules = Some(RulesProfile {
+            turn_time_ms: 500,
+            ..RulesProfile::default()
+        });
+        let server = TestServer::boot(request)
+            .await
+            .unwrap_or_else(|_| panic!("initialization failed"));
+        let (mut red_client, _) = server.join(&red).await;
+        let (mut blue_client, _) = server.join(&blue).await;
+        for client in [&mut red_client, &mut blue_client] {
+            client.send(HeaderType::Ready, &()).await;
+        }
+
+        // Nobody ends the first turn, the server does once its time is used up.
+        let mut players = Vec::new();
+        while players.len() < 2 {
+            let packet = red_client.expect(HeaderType::TurnStarted).await;
+            let started: serde_json::Value = serde_cbor::from_slice(&packet.payload).unwrap();
+            players.push(started["player_id"].clone());
+        }
+        assert_ne!(players[0], players[1]);
+    }
+
+    #[tokio::test]
+    async fn test_stunned_players_cannot_play_until_the_stun_expires() {
+        let (red, blue) = (
+            sample_player("harness-stun-red"),
+            sample_player("harness-stun-blue"),
+        );
+        let server = TestServer::boot(init_request("harness-stun", &[&red, &blue]))
+            .await
+            .unwrap_or_else(|_| panic!("initialization failed"));
+        let (mut red_client, _) = server.join(&red).await;
+        let (mut blue_client, _) = server.join(&blue).await;
+        for client in [&mut red_client, &mut blue_client] {
+            client.send(HeaderType::Ready, &()).await;
+        }
+        let start = red_client.expect(HeaderType::MatchStart).await;
+        let start: serde_json::Value = serde_cbor::from_slice(&start.payload).unwrap();
+        blue_client.expect(HeaderType::MatchStart).await;
+        let (first, first_client) = match start["first_player"] == red.id.to_string() {
+            true => (&red, &mut red_client),
+            false => (&blue, &mut blue_client),
+        };
+        first_client.expect(HeaderType::TurnStarted).await;
+
+        let stun = StatusEffect {
+            kind: StatusKind::Stun,
+            magnitude: 1,
+            turns: Some(1),
+        };
+        let game_state = server.server.game_instance.game_state.read().await;
+        assert!(
+            game_state
+                .apply_status_effect(&first.id.to_string(), stun)
+                .await
+        );
+        drop(game_state);
+
+        // The stun is checked before the card, so the play is rejected whatever it plays.
+        let play = serde_json::json!({
+            "actor_id": first.id.to_string(),
+            "instance_id": CardInstanceId::nth(0).to_string(),
+        });
+
+        first_client.send(HeaderType::PlayCard, &play).await;
+        let rejected = first_client.expect(HeaderType::ActionRejected).await;
+        let error: serde_json::Value = serde_cbor::from_slice(&rejected.payload).unwrap();
+        assert_eq!(ErrorCode::Stunned as u16, error["code"]);
+
+        // A stunned player may still end their turn, and the stun wears off at its end.
+        first_client.send(HeaderType::EndTurn, &()).await;
+        first_client.expect(HeaderType::ActionAccepted).await;
+        let game_state = server.server.game_instance.game_state.read().await;
+        let view = game_state.player_view(&first.id).await.unwrap();
+        assert!(view.player.status_effects.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_batches_apply_whole_or_not_at_all() {
+        let (red, blue) = (
+            sample_player("harness-batch-red"),
+            sample_player("harness-batch-blue"),
+        );
+        let server = TestServer::boot(init_request("harness-batch", &[&red, &blue]))
+            .await
+            .unwrap_or_else(|_| panic!("initialization failed"));
+        let (mut red_client, _) = server.join(&red).await;
+        let (mut blue_client, _) = server.join(&blue).await;
+        for client in [&mut red_client, &mut blue_client] {
+            client.send(HeaderType::Ready, &()).await;
+        }
+        let start = red_client.expect(HeaderType::MatchStart).await;
+        let start: serde_json::Value = serde_cbor::from_slice(&start.payload).unwrap();
+        blue_client.expect(HeaderType::MatchStart).await;
+        let (first, first_client) = match start["first_player"] == red.id.to_string() {
+            true => (&red, &mut red_client),
+            false => (&blue, &mut blue_client),
+        };
+        first_client.expect(HeaderType::TurnStarted).await;
+
+        // Opening hands are not dealt, so two copies of the deck are put in hand by hand.
+        let game_state = server.server.game_instance.game_state.read().await;
+        let mut cards: Vec<_> = game_state
+            .card_instances
+            .values()
+            .filter(|card| card.owner_id == first.id)
+            .take(2)
+            .cloned()
+            .collect();
+        {
+            let views = game_state.player_views.read().await;
+            let mut view = views[&first.id].write().await;
+            for (slot, card) in cards.iter_mut().enumerate() {
+                card.in_deck = false;
+                card.in_hand = true;
+                view.current_hand[slot] = Some(card.clone());
+            }
+            view.hand_size = cards.len();
+        }
+        drop(game_state);
+
+        let play = |card: &CardView| {
+            serde_json::json!({
+                "type": "PlayCard",
+                "actor_id": first.id.to_string(),
+                "instance_id": card.instance_id.to_string(),
+            })
+        };
+        let view = || async {
+            let game_state = server.server.game_instance.game_state.read().await;
+            let mut views = game_state.snapshot_views().await;
+            views.remove(&first.id).unwrap()
+        };
+
+        // The second play fails, since the card already left the hand: the first is undone.
+        let batch = serde_json::json!({ "actions": [play(&cards[0]), play(&cards[0])] });
+        first_client.send(HeaderType::ActionBatch, &batch).await;
+        first_client.expect(HeaderType::ActionRejected).await;
+        let rolled_back = view().await;
+        assert_eq!(2, rolled_back.hand_size);
+        assert!(rolled_back.board.cards.is_empty());
+
+        let batch = serde_json::json!({ "actions": [play(&cards[0]), play(&cards[1])] });
+        first_client.send(HeaderType::ActionBatch, &batch).await;
+        first_client.expect(HeaderType::ActionAccepted).await;
+        let applied = view().await;
+        assert_eq!(0, applied.hand_size);
+        assert_eq!(2, applied.board.cards.len());
+    }
+
+    #[tokio::test]
+    async fn test_bot_takes_the_other_seat() {
+        let (red, bot) = (sample_player("harness-human"), sample_player("harness-bot"));
+        let mut request = init_request("harness-practice", &[&red, &bot]);
+        request.players[1].bot = Some(BotProfile::default());
+        let server = TestServer::boot(request)
+            .await
+            .unwrap_or_else(|_| panic!("initialization failed"));
+
+        // The match only starts once the bot is ready too.
+        let (mut client, _) = server.join(&red).await;
+        client.send(HeaderType::Ready, &()).await;
+        client.expect(HeaderType::MatchStart).await;
+    }
+
+    #[tokio::test]
+    async fn test_one_process_hosts_several_matches() {
+        let (red, blue) = (
+            sample_player("harness-host-red"),
+            sample_player("harness-host-blue"),
+        );
+        let first = TestServer::boot(init_request("harness-host-first", &[&red]))
+            .await
+            .unwrap_or_else(|_| panic!("initialization failed"));
+        let second = TestServer::init(
+            &first.address,
+            init_request("harness-host-second", &[&blue]),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("initialization failed"));
+
+        // Each player is routed to their own match, by id or by seat.
+        let (_, answer) = second.join(&blue).await;
+        assert_eq!(HeaderType::ConnectAck, answer.header.header_type);
+        let mut client = TestClient::open(&first.address).await;
+        client.handshake().await;
+        let request = ConnectionRequest {
+            player_id: red.id.clone(),
+            auth_token: red.auth_token.clone(),
+            current_deck_id: format!("{}-deck", red.id),
+            match_id: None,
+        };
+        client.send(HeaderType::Connect, &request).await;
+        assert_eq!(
+            HeaderType::ConnectAck,
+            client.receive().await.unwrap().header.header_type
+        );
+        assert_eq!(1, first.server.connected_clients.read().await.len());
+        assert_eq!(1, second.server.connected_clients.read().await.len());
+
+        // A match is only hosted once, and unknown matches are not initialized yet.
+        let answer = TestServer::init(&first.address, init_request("harness-host-first", &[&red]))
+            .await
+            .err()
+            .expect("the match is already hosted");
+        assert_eq!(HeaderType::ERROR, answer.header.header_type);
+        let request = ConnectionRequest {
+            match_id: Some(MatchId::from("harness-host-unknown")),
+            ..request
+        };
+        let mut client = TestClient::open(&first.address).await;
+        client.handshake().await;
+        client.send(HeaderType::Connect, &request).await;
+        let answer = client.receive().await.unwrap();
+        let rejection: Rejection = serde_cbor::from_slice(&answer.payload).unwrap();
+        assert_eq!(RejectionReason::NotInitialized, rejection.reason);
+    }
+
+    #[tokio::test]
+    async fn test_lobby_pairs_ready_players_without_a_matchmaker() {
+        let address = TestServer::listen().await;
+        let players = [
+            sample_player("harness-lobby-red"),
+            sample_player("harness-lobby-blue"),
+        ];
+        let mut clients = Vec::new();
+        for player in &players {
+            let mut client = TestClient::open(&address).await;
+            client.handshake().await;
+            let request = ConnectionRequest {
+                player_id: player.id.clone(),
+                auth_token: player.auth_token.clone(),
+                current_deck_id: format!("{}-deck", player.id),
+                match_id: None,
+            };
+            client.send(HeaderType::Connect, &request).await;
+            client.expect(HeaderType::LobbyStatus).await;
+            clients.push(client);
+        }
+
+        for client in &mut clients {
+            client.send(HeaderType::Ready, &()).await;
+        }
+        for client in &mut clients {
+            client.expect(HeaderType::ConnectAck).await;
+            client.expect(HeaderType::MatchStart).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_friends_play_a_private_match_with_a_join_code() {
+        #[derive(Serialize)]
+        struct JoinRequest {
+            #[serde(flatten)]
+            connect: ConnectionRequest,
+            join_code: String,
+        }
+
+        let address = TestServer::listen().await;
+        let [red, blue] = [
+            sample_player("harness-private-red"),
+            sample_player("harness-private-blue"),
+        ];
+        let connect = |player: &PlayerFixture| ConnectionRequest {
+            player_id: player.id.clone(),
+            auth_token: player.auth_token.clone(),
+            current_deck_id: format!("{}-deck", player.id),
+            match_id: None,
+        };
+
+        let mut host = TestClient::open(&address).await;
+        host.handshake().await;
+        host.send(HeaderType::CreatePrivateMatch, &connect(&red))
+            .await;
+        let created = host.expect(HeaderType::PrivateMatchCreated).await;
+        let created: PrivateMatchCreated = serde_cbor::from_slice(&created.payload).unwrap();
+
+        let mut friend = TestClient::open(&address).await;
+        friend.handshake().await;
+        let request = JoinRequest {
+            connect: connect(&blue),
+            join_code: String::from("WRONG1"),
+        };
+        friend.send(HeaderType::JoinPrivateMatch, &request).await;
+        let answer = friend.expect(HeaderType::ConnectionRejected).await;
+        let rejection: Rejection = serde_cbor::from_slice(&answer.payload).unwrap();
+        assert_eq!(RejectionReason::InvalidJoinCode, rejection.reason);
+
+        let mut friend = TestClient::open(&address).await;
+        friend.handshake().await;
+        let request = JoinRequest {
+            connect: connect(&blue),
+            join_code: created.join_code.to_lowercase(),
+        };
+        friend.send(HeaderType::JoinPrivateMatch, &request).await;
+        let status = friend.expect(HeaderType::LobbyStatus).await;
+        let status: LobbyStatus = serde_cbor::from_slice(&status.payload).unwrap();
+        assert_eq!(
+            LobbyStatus {
+                waiting: 2,
+                ready: 0
+            },
+            status
+        );
+
+        for client in [&mut host, &mut friend] {
+            client.send(HeaderType::Ready, &()).await;
+        }
+        for client in [&mut host, &mut friend] {
+            client.expect(HeaderType::ConnectAck).await;
+            client.expect(HeaderType::MatchStart).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drafted_decks_are_played_in_the_match() {
+        let address = TestServer::listen().await;
+        let players = [
+            sample_player("harness-draft-red"),
+            sample_player("harness-draft-blue"),
+        ];
+        let request = InitServerRequest {
+            match_type: String::from("draft"),
+            draft: Some(DraftSettings {
+                set: Some(String::from("core")),
+                packs: 1,
+                pack_size: 20,
+            }),
+            ..init_request("harness-draft", &[&players[0], &players[1]])
+        };
+        let mut matchmaker = TestClient::open(&address).await;
+        matchmaker.send(HeaderType::InitServer, &request).await;
+        assert!(
+            matchmaker.receive().await.is_none(),
+            "the draft should open"
+        );
+
+        let mut clients = Vec::new();
+        for player in &players {
+            let mut client = TestClient::open(&address).await;
+            client.handshake().await;
+            let request = ConnectionRequest {
+                player_id: player.id.clone(),
+                auth_token: player.auth_token.clone(),
+                current_deck_id: String::new(),
+                match_id: Some(MatchId::from("harness-draft")),
+            };
+            client.send(HeaderType::Connect, &request).await;
+            clients.push(client);
+        }
+
+        // Every pick moves the draft on for both players; the picker takes the first card.
+        for _ in 0..40 {
+            let mut views = Vec::new();
+            for client in &mut clients {
+                let pack = client.expect(HeaderType::DraftPack).await;
+                views.push(serde_cbor::from_slice::<DraftView>(&pack.payload).unwrap());
+            }
+            let picker = players
+                .iter()
+                .position(|p| p.id == views[0].picker)
+                .unwrap();
+            let pick = DraftPickRequest {
+                card_id: views[picker].pack[0].clone(),
+            };
+            clients[picker].send(HeaderType::DraftPick, &pick).await;
+        }
+
+        for client in &mut clients {
+            let complete = client.expect(HeaderType::DraftComplete).await;
+            let deck: Deck = serde_cbor::from_slice(&complete.payload).unwrap();
+            assert_eq!(20, deck.cards.iter().map(|card| card.amount).sum::<u32>());
+            client.expect(HeaderType::ConnectAck).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_players_sideboard_before_the_next_game_of_a_series() {
+        let address = TestServer::listen().await;
+        let players = [
+            sample_player("harness-series-red"),
+            sample_player("harness-series-blue"),
+        ];
+        MOCK_SERVICES.add_card(sample_card("harness-sideboard"));
+        let mut deck = Player::preload_player_deck("harness-series-red-deck")
+            .await
+            .unwrap();
+        deck.sideboard = vec![CardRef {
+            id: CardDefId::from("harness-sideboard"),
+            amount: 1,
+            owner_id: None,
+            instances: Vec::new(),
+        }];
+        MOCK_SERVICES.add_player(players[0].clone(), deck);
+
+        let request = InitServerRequest {
+            match_type: String::from("best-of-three"),
+            game: 2,
+            ..init_request("harness-series", &[&players[0], &players[1]])
+        };
+        let mut matchmaker = TestClient::open(&address).await;
+        matchmaker.send(HeaderType::InitServer, &request).await;
+        assert!(
+            matchmaker.receive().await.is_none(),
+            "the sideboarding should open"
+        );
+
+        let mut clients = Vec::new();
+        for player in &players {
+            let mut client = TestClient::open(&address).await;
+            client.handshake().await;
+            let request = ConnectionRequest {
+                player_id: player.id.clone(),
+                auth_token: player.auth_token.clone(),
+                current_deck_id: String::new(),
+                match_id: Some(MatchId::from("harness-series")),
+            };
+            client.send(HeaderType::Connect, &request).await;
+            let sideboard = client.expect(HeaderType::Sideboard).await;
+            let view: SideboardView = serde_cbor::from_slice(&sideboard.payload).unwrap();
+            assert_eq!(2, view.game);
+            clients.push(client);
+        }
+
+        let swap = |add: &str| SideboardRequest {
+            swaps: vec![SideboardSwap {
+                remove: CardDefId::from("sample-0"),
+                add: CardDefId::from(add),
+            }],
+        };
+        clients[0]
+            .send(HeaderType::SideboardRequest, &swap("sample-1"))
+            .await;
+        let rejected = clients[0].expect(HeaderType::ActionRejected).await;
+        let error: serde_json::Value = serde_cbor::from_slice(&rejected.payload).unwrap();
+        assert_eq!(ErrorCode::NotInSideboard as u16, error["code"]);
+        assert_eq!("sample-1", error["context"]["card_id"]);
+        clients[0]
+            .send(HeaderType::SideboardRequest, &swap("harness-sideboard"))
+            .await;
+        clients[0].expect(HeaderType::ActionAccepted).await;
+        let unchanged = SideboardRequest { swaps: Vec::new() };
+        clients[1]
+            .send(HeaderType::SideboardRequest, &unchanged)
+            .await;
+        clients[1].expect(HeaderType::ActionAccepted).await;
+
+        for client in &mut clients {
+            client.expect(HeaderType::ConnectAck).await;
+        }
+        let server = MATCHES
+            .servers()
+            .await
+            .into_iter()
+            .find(|server| server.match_id == MatchId::from("harness-series"))
+            .expect("hosted game");
+        let cards = server.game_instance.full_cards.read().await;
+        assert!(cards.contains_key(&CardDefId::from("harness-sideboard")));
+    }
+
+    #[tokio::test]
+    async fn test_four_players_play_a_team_match() {
+        let players: Vec<_> = ["ann", "bob", "cid", "dan"]
+            .iter()
+            .map(|name| sample_player(&format!("harness-team-{name}")))
+            .collect();
+        let seats: Vec<_> = players.iter().collect();
+
+        // A team format seats exactly its two teams.
+        let mut request = init_request("harness-short-team", &seats[..2]);
+        request.match_type = String::from("2v2");
+        assert!(TestServer::boot(request).await.is_err());
+
+        let mut request = init_request("harness-team", &seats);
+        request.match_type = String::from("2v2");
+        let server = TestServer::boot(request)
+            .await
+            .unwrap_or_else(|_| panic!("initialization failed"));
+
+        let mut clients = Vec::new();
+        for player in &players {
+            let (client, answer) = server.join(player).await;
+            assert_eq!(HeaderType::ConnectAck, answer.header.header_type);
+            clients.push(client);
+        }
+        let game_state = server.server.game_instance.game_state.read().await;
+        assert_eq!(4, game_state.seating.turn_order().len());
+        assert_eq!(
+            vec![&players[3].id],
+            game_state.seating.teammates_of(&players[1].id)
+        );
+        drop(game_state);
+
+        for client in &mut clients {
+            client.send(HeaderType::Ready, &()).await;
+        }
+        for client in &mut clients {
+            client.expect(HeaderType::MatchStart).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_only_allowed_viewers_spectate() {
+        let (red, blue) = (
+            sample_player("harness-view-red"),
+            sample_player("harness-view-blue"),
+        );
+        let (coach, stranger) = (
+            sample_player("harness-coach"),
+            sample_player("harness-onlooker"),
+        );
+        let request = InitServerRequest {
+            viewers: Some(ViewerAccess {
+                open: false,
+                allowed: vec![coach.id.clone()],
+            }),
+            ..init_request("harness-coached", &[&red, &blue])
+        };
+        let server = TestServer::boot(request)
+            .await
+            .unwrap_or_else(|_| panic!("initialization failed"));
+
+        let spectate = |viewer: &PlayerFixture| {
+            serde_json::json!({
+                "match_id": server.server.match_id.to_string(),
+                "auth_token": viewer.auth_token,
+            })
+        };
+        let mut onlooker = TestClient::open(&server.address).await;
+        onlooker.handshake().await;
+        onlooker
+            .send(HeaderType::Spectate, &spectate(&stranger))
+            .await;
+        let answer = onlooker.expect(HeaderType::ConnectionRejected).await;
+        let rejection: Rejection = serde_cbor::from_slice(&answer.payload).unwrap();
+        assert_eq!(RejectionReason::NotAllowed, rejection.reason);
+
+        let mut viewer = TestClient::open(&server.address).await;
+        viewer.handshake().await;
+        viewer.send(HeaderType::Spectate, &spectate(&coach)).await;
+        viewer.expect(HeaderType::GameState).await;
+        assert_eq!(1, server.server.spectators.read().await.len());
+    }
+
+    #[tokio::test]
+    async fn test_repeated_rejected_actions_flag_the_player() {
+        let (red, blue) = (
+            sample_player("harness-cheat-red"),
+            sample_player("harness-cheat-blue"),
+        );
+        let server = TestServer::boot(init_request("harness-audited", &[&red, &blue]))
+            .await
+            .unwrap_or_else(|_| panic!("initialization failed"));
+        let (mut red_client, _) = server.join(&red).await;
+        let (_blue_client, _) = server.join(&blue).await;
+
+        // The match has not started, so every action is rejected.
+        let limit = SETTINGS.get().map_or(10, |s| s.audit_violation_limit);
+        for _ in 0..limit {
+            red_client.send(HeaderType::PlayCard, &()).await;
+            red_client.expect(HeaderType::ActionRejected).await;
+        }
+        assert!(!server.server.audit.is_flagged(&red.id));
+
+        red_client.send(HeaderType::PlayCard, &()).await;
+        let answer = red_client.expect(HeaderType::ConnectionRejected).await;
+        let rejection: Rejection = serde_cbor::from_slice(&answer.payload).unwrap();
+        assert_eq!(RejectionReason::Flagged, rejection.reason);
+        assert!(server.server.audit.is_flagged(&red.id));
+
+        let summaries = server.server.audit.summaries();
+        assert_eq!(1, summaries.len());
+        assert_eq!(limit + 1, summaries[0].total);
+        assert_eq!(
+            Some(&(limit + 1)),
+            summaries[0].kinds.get(&ViolationKind::NotStarted)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repeated_malformed_packets_disconnect_the_client() {
+        let (red, blue) = (
+            sample_player("harness-garbled-red"),
+            sample_player("harness-garbled-blue"),
+        );
+        let server = TestServer::boot(init_request("harness-garbled", &[&red, &blue]))
+            .await
+            .unwrap_or_else(|_| panic!("initialization failed"));
+        let (mut red_client, _) = server.join(&red).await;
+        let (_blue_client, _) = server.join(&blue).await;
+
+        // A zstd payload that does not decompress, followed by packets with a wrong checksum.
+        let mut corrupt = Header::new(HeaderType::Ping, &[0xAB; 16]);
+        corrupt.flags = Compression::Zstd.flag();
+        let mut bytes = corrupt.wrap_header().to_vec();
+        bytes.extend_from_slice(&[0xAB; 16]);
+        red_client.send_bytes(&bytes).await;
+        red_client.expect(HeaderType::InvalidPacketPayload).await;
+
+        let mut tampered = Packet::new(HeaderType::Ping, b"ping");
+        tampered.header.checksum ^= 1;
+        let limit = SETTINGS.get().map_or(5, |s| s.max_malformed_packets);
+        for _ in 1..limit {
+            red_client.send_bytes(&tampered.wrap_packet()).await;
+            red_client.expect(HeaderType::InvalidChecksum).await;
+        }
+
+        red_client.send_bytes(&tampered.wrap_packet()).await;
+        let answer = red_client.expect(HeaderType::ConnectionRejected).await;
+        let rejection: Rejection = serde_cbor::from_slice(&answer.payload).unwrap();
+        assert_eq!(RejectionReason::Malformed, rejection.reason);
+    }
+
+    #[tokio::test]
+    async fn test_debugging_clients_speak_json() {
+        let (red, blue) = (
+            sample_player("harness-json-red"),
+            sample_player("harness-json-blue"),
+        );
+        let server = TestServer::boot(init_request("harness-json", &[&red, &blue]))
+            .await
+            .unwrap_or_else(|_| panic!("initialization failed"));
+
+        let mut client = TestClient::open(&server.address).await;
+        let handshake = serde_json::json!({
+            "version": PROTOCOL_VERSION,
+            "features": SERVER_FEATURES,
+            "encoding": "json",
+        });
+        client.send_json(HeaderType::Handshake, &handshake).await;
+        let answer = client.expect(HeaderType::Handshake).await;
+        let answer: serde_json::Value = serde_json::from_slice(&answer.payload).unwrap();
+        assert_eq!("json", answer["encoding"]);
+
+        let connect = serde_json::json!({
+            "player_id": red.id,
+            "auth_token": red.auth_token,
+            "current_deck_id": format!("{}-deck", red.id),
+            "match_id": server.server.match_id,
+        });
+        client.send_json(HeaderType::Connect, &connect).await;
+        let ack = client.expect(HeaderType::ConnectAck).await;
+        let ack: serde_json::Value = serde_json::from_slice(&ack.payload).unwrap();
+        assert!(ack["session_token"].is_string());
+
+        let garbled = Packet::new(HeaderType::PlayCard, b"{");
+        client.send_bytes(&garbled.wrap_packet()).await;
+        let invalid = client.expect(HeaderType::InvalidPacketPayload).await;
+        let error: serde_json::Value = serde_json::from_slice(&invalid.payload).unwrap();
+        assert_eq!(ErrorCode::InvalidPayload as u16, error["code"]);
+    }
+
+    #[tokio::test]
+    async fn test_cards_of_every_deck_are_fetched_in_one_request() {
+        let red = sample_player_with_cards("harness-batch-red", "batch-red");
+        let blue = sample_player_with_cards("harness-batch-blue", "batch-blue");
+        let _server = TestServer::boot(init_request("harness-batch", &[&red, &blue]))
+            .await
+            .unwrap_or_else(|_| panic!("initialization failed"));
+
+        let requests: Vec<String> = MOCK_SERVICES
+            .received("/api/card/selected")
+            .into_iter()
+            .filter(|body| body.contains("batch-red-0") || body.contains("batch-blue-0"))
+            .collect();
+        assert_eq!(1, requests.len());
+        assert!(requests[0].contains("batch-red-9") && requests[0].contains("batch-blue-9"));
+    }
+
+    #[tokio::test]
+    async fn test_failing_deck_service_fails_the_initialization() {
+        let red = sample_player("harness-unlucky");
+        MOCK_SERVICES.fail("/api/deck/harness-unlucky", 503);
+        let answer = TestServer::boot(init_request("harness-failed", &[&red]))
+            .await
+            .err()
+            .expect("initialization should fail");
+        assert_eq!(HeaderType::ERROR, answer.header.header_type);
+        MOCK_SERVICES.recover("/api/deck/harness-unlucky");
+    }
+}
@@ -0,0 +1,312 @@
+use crate::game::entity::card::Card;
+use crate::game::entity::deck::Deck;
+use crate::models::http_response::{
+    AuthenticatedPlayer, PartialPlayerProfile, PreloadedPlayer, SelectedCardsResponse,
+};
+use crate::models::ids::{CardDefId, PlayerId};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Largest request read; the card server is sent the biggest ones, listing the cards of a deck.
+const MAX_REQUEST_SIZE: usize = 256 * 1024;
+
+/// A player known to the mock auth and deck services.
+#[derive(Debug, Clone)]
+pub struct PlayerFixture {
+    pub id: PlayerId,
+    pub username: String,
+    pub level: u32,
+    pub auth_token: String, // The token the auth service accepts for the player.
+    pub banned: bool,
+}
+
+impl PlayerFixture {
+    pub fn new(id: &str) -> Self {
+        Self {
+            id: PlayerId::from(id),
+            username: id.to_string(),
+            level: 1,
+            auth_token: format!("{id}-token"),
+            banned: false,
+        }
+    }
+}
+
+/// What the mock services answer, programmed by the tests.
+#[derive(Default)]
+struct Fixtures {
+    players: HashMap<PlayerId, PlayerFixture>,
+    decks: HashMap<String, Deck>,
+    cards: HashMap<CardDefId, Card>,
+    failures: Vec<(String, u16)>, // Path prefixes answered with a status whatever the fixtures.
+    received: Vec<(String, String)>, // Path and body of every request, oldest first.
+}
+
+/// In-process stand-ins for the auth, deck, card and result services, all served from one
+/// address so the settings can point every service at it.
+///
+/// The services run on a thread of their own, so they outlive the runtime of the test that
+/// started them and can be shared by every test of the process.
+pub struct MockServices {
+    address: SocketAddr,
+    fixtures: Arc<Mutex<Fixtures>>,
+}
+
+impl MockServices {
+    /// Starts the services on an ephemeral port of localhost.
+    pub fn start() -> Self {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind mock services");
+        listener
+            .set_nonblocking(true)
+            .expect("configure mock services");
+        let address = listener.local_addr().expect("mock services address");
+        let fixtures = Arc::new(Mutex::new(Fixtures::default()));
+
+        let served = Arc::clone(&fixtures);
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("mock services runtime");
+            runtime.block_on(async move {
+                let listener = TcpListener::from_std(listener).expect("mock services listener");
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(answer(stream, Arc::clone(&served)));
+                }
+            });
+        });
+
+        Self { address, fixtures }
+    }
+
+    /// The base URL of every service.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// Makes a player known to the auth service, with a deck the deck service serves.
+    pub fn add_player(&self, player: PlayerFixture, deck: Deck) {
+        let mut fixtures = self.lock();
+        fixtures.decks.insert(deck.id.clone(), deck);
+        fixtures.players.insert(player.id.clone(), player);
+    }
+
+    /// Makes a card known to the card service.
+    pub fn add_card(&self, card: Card) {
+        self.lock().cards.insert(card.id.clone(), card);
+    }
+
+    /// Answers every request whose path starts with `prefix` with `status`.
+    pub fn fail(&self, prefix: &str, status: u16) {
+        self.lock().failures.push((prefix.to_string(), status));
+    }
+
+    /// The bodies of the requests received so far whose path starts with `prefix`.
+    pub fn received(&self, prefix: &str) -> Vec<String> {
+        self.lock()
+            .received
+            .iter()
+            .filter(|(path, _)| path.starts_with(prefix))
+            .map(|(_, body)| body.clone())
+            .collect()
+    }
+
+    /// Answers the requests under `prefix` from the fixtures again.
+    pub fn recover(&self, prefix: &str) {
+        self.lock()
+            .failures
+            .retain(|(failing, _)| failing != prefix);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Fixtures> {
+        self.fixtures.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A request read from a connection.
+struct Request {
+    method: String,
+    path: String,
+    bearer: Option<String>,
+    body: Vec<u8>,
+}
+
+/// Reads one request and answers it from the fixtures, closing the connection.
+async fn answer(mut stream: TcpStream, fixtures: Arc<Mutex<Fixtures>>) {
+    let Some(request) = read_request(&mut stream).await else {
+        return;
+    };
+    let (status, body) = {
+        let mut fixtures = fixtures.lock().unwrap_or_else(|e| e.into_inner());
+        let body = String::from_utf8_lossy(&request.body).to_string();
+        fixtures.received.push((request.path.clone(), body));
+        route(&mut fixtures, &request)
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status} MOCK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<Request> {
+    let mut buffer = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end + 4;
+        }
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 || buffer.len() + read > MAX_REQUEST_SIZE {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..head_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let mut content_length = 0;
+    let mut bearer = None;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => content_length = value.trim().parse().ok()?,
+            "authorization" => {
+                bearer = value.trim().strip_prefix("Bearer ").map(str::to_string);
+            }
+            _ => {}
+        }
+    }
+
+    let mut body = buffer.split_off(head_end);
+    while body.len() < content_length {
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 || body.len() + read > MAX_REQUEST_SIZE {
+            return None;
+        }
+        body.extend_from_slice(&chunk[..read]);
+    }
+
+    Some(Request {
+        method,
+        path,
+        bearer,
+        body,
+    })
+}
+
+/// The status and JSON body answering a request.
+fn route(fixtures: &mut Fixtures, request: &Request) -> (u16, String) {
+    if let Some((_, status)) = fixtures
+        .failures
+        .iter()
+        .find(|(prefix, _)| request.path.starts_with(prefix.as_str()))
+    {
+        return (*status, String::from("{}"));
+    }
+
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let authenticated = || {
+        let token = request.bearer.as_deref()?;
+        fixtures
+            .players
+            .values()
+            .find(|player| player.auth_token == token)
+    };
+
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["api", "auth", "verify"]) => match authenticated() {
+            Some(player) => json(&AuthenticatedPlayer {
+                player_id: player.id.clone(),
+                username: player.username.clone(),
+                is_banned: player.banned,
+                session_token: String::new(),
+            }),
+            None => (401, String::from("{}")),
+        },
+        ("GET", ["api", "player", "account"]) => match authenticated() {
+            Some(player) => json(&PartialPlayerProfile {
+                id: player.id.clone(),
+                level: player.level,
+                username: player.username.clone(),
+            }),
+            None => (401, String::from("{}")),
+        },
+        ("GET", ["api", "player", "preload", player_id]) => {
+            match fixtures.players.get(&PlayerId::from(*player_id)) {
+                Some(player) => json(&PreloadedPlayer {
+                    id: player.id.clone(),
+                    level: player.level,
+                    username: player.username.clone(),
+                }),
+                None => (404, String::from("{}")),
+            }
+        }
+        ("GET", ["api", "deck", deck_id]) => match fixtures.decks.get(*deck_id) {
+            Some(deck) => json(deck),
+            None => (404, String::from("{}")),
+        },
+        ("POST", ["api", "card", "selected"]) => {
+            let requested: serde_json::Value =
+                serde_json::from_slice(&request.body).unwrap_or_default();
+            let mut selected = SelectedCardsResponse::default();
+            for card_id in requested["cardIds"].as_array().into_iter().flatten() {
+                let card_id = CardDefId::from(card_id.as_str().unwrap_or_default());
+                match fixtures.cards.get(&card_id) {
+                    Some(card) => selected.cards.push(card.clone()),
+                    None => selected.cards_not_found.push(card_id.to_string()),
+                }
+            }
+            json(&selected)
+        }
+        ("GET", ["api", "card", card_id]) => match fixtures.cards.get(&CardDefId::from(*card_id)) {
+            Some(card) => json(card),
+            None => (404, String::from("{}")),
+        },
+        ("GET", ["api", "draft", _set, packs]) if packs.starts_with("packs?") => {
+            let query = |name: &str| {
+                packs
+                    .split(['?', '&'])
+                    .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .unwrap_or_default()
+            };
+            let (count, size) = (query("count"), query("size"));
+            let mut card_ids: Vec<&CardDefId> = fixtures.cards.keys().collect();
+            card_ids.sort();
+            if card_ids.is_empty() {
+                return (404, String::from("{}"));
+            }
+
+            // Deals the known cards in order, so every card shows up before any repeats.
+            let packs: Vec<Vec<&CardDefId>> = (0..count)
+                .map(|pack| {
+                    (0..size)
+                        .map(|slot| card_ids[(pack * size + slot) % card_ids.len()])
+                        .collect()
+                })
+                .collect();
+            json(&packs)
+        }
+        ("POST", ["api", "match", "result"]) => (200, String::from("{}")),
+        _ => (404, String::from("{}")),
+    }
+}
+
+fn json<T: Serialize>(value: &T) -> (u16, String) {
+    match serde_json::to_string(value) {
+        Ok(body) => (200, body),
+        Err(_) => (500, String::from("{}")),
+    }
+}
@@ -0,0 +1,5 @@
+pub mod fixtures;
+#[cfg(feature = "test-support")]
+pub mod harness;
+#[cfg(feature = "test-support")]
+pub mod mock_services;
@@ -0,0 +1,59 @@
+use crate::models::ids::MatchId;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// The directory holding the files a match leaves behind for later inspection.
+///
+/// Every match gets its own subdirectory, named after the match id, under the configured root.
+pub struct ArtifactBundle {
+    dir: PathBuf,
+}
+
+impl ArtifactBundle {
+    pub fn new(root: impl Into<PathBuf>, match_id: &MatchId) -> Self {
+        Self {
+            dir: root.into().join(match_id.to_string()),
+        }
+    }
+
+    /// The directory of the bundle, which may not exist until something is written into it.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes a value as pretty-printed JSON into the bundle.
+    ///
+    /// # Arguments
+    /// * `name` - The file name inside the bundle.
+    /// * `value` - The value to write.
+    ///
+    /// # Returns
+    /// * `Ok(PathBuf)` - The path of the written file.
+    /// * `Err(std::io::Error)` - If the directory or the file could not be written.
+    pub fn write_json<T: Serialize>(&self, name: &str, value: &T) -> std::io::Result<PathBuf> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(name);
+        std::fs::write(&path, serde_json::to_vec_pretty(value)?)?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_json_into_match_directory() {
+        let root = std::env::temp_dir().join(format!("artifacts-{}", uuid::Uuid::new_v4()));
+        let bundle = ArtifactBundle::new(&root, &"match-1".into());
+
+        let path = bundle.write_json("profile.json", &vec![1, 2, 3]).unwrap();
+        assert_eq!(root.join("match-1").join("profile.json"), path);
+        assert_eq!(
+            "[\n  1,\n  2,\n  3\n]",
+            std::fs::read_to_string(&path).unwrap()
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,196 @@
+use crate::models::settings::Settings;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Limits on the bytes a client may send to the server within a window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandwidthCaps {
+    pub window: Duration,  // Length of the window the caps apply to.
+    pub soft: Option<u64>, // Bytes after which a warning is logged, once per window.
+    pub hard: Option<u64>, // Bytes after which the client is disconnected.
+}
+
+impl BandwidthCaps {
+    /// Builds the caps from the settings; without settings, nothing is capped.
+    pub fn from_settings(settings: Option<&Settings>) -> Self {
+        match settings {
+            Some(settings) => Self {
+                window: Duration::from_secs(settings.bandwidth_window.max(1)),
+                soft: settings.bandwidth_soft_cap,
+                hard: settings.bandwidth_hard_cap,
+            },
+            None => Self::default(),
+        }
+    }
+}
+
+impl Default for BandwidthCaps {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(10),
+            soft: None,
+            hard: None,
+        }
+    }
+}
+
+/// Where a client stands against the bandwidth caps after receiving some bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CapStatus {
+    Within,
+    /// The soft cap was crossed for the first time in the window.
+    SoftExceeded(u64),
+    /// The hard cap was crossed; carries the bytes received in the window.
+    HardExceeded(u64),
+}
+
+/// Bytes received within the current window.
+struct ReceiveWindow {
+    started: Instant,
+    received: u64,
+    warned: bool,
+}
+
+/// Bytes sent to and received from one client over the whole match.
+pub struct BandwidthMeter {
+    sent: AtomicU64,
+    received: AtomicU64,
+    window: Mutex<ReceiveWindow>, // Received bytes checked against the caps.
+}
+
+impl BandwidthMeter {
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    pub fn record_sent(&self, bytes: u64) {
+        self.sent.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Records bytes received from the client and checks the caps of the current window.
+    ///
+    /// # Arguments
+    /// * `bytes` - The bytes read from the client's connection.
+    /// * `caps` - The caps to check against.
+    ///
+    /// # Returns
+    /// Whether the client is within the caps; the soft cap is only reported once per window.
+    pub fn record_received(&self, bytes: u64, caps: &BandwidthCaps) -> CapStatus {
+        self.received.fetch_add(bytes, Ordering::Relaxed);
+
+        let mut window = self.window.lock().unwrap_or_else(|e| e.into_inner());
+        if window.started.elapsed() >= caps.window {
+            *window = ReceiveWindow {
+                started: Instant::now(),
+                received: 0,
+                warned: false,
+            };
+        }
+
+        window.received += bytes;
+        if caps.hard.is_some_and(|hard| window.received > hard) {
+            return CapStatus::HardExceeded(window.received);
+        }
+
+        if caps.soft.is_some_and(|soft| window.received > soft) && !window.warned {
+            window.warned = true;
+            return CapStatus::SoftExceeded(window.received);
+        }
+
+        CapStatus::Within
+    }
+}
+
+impl Default for BandwidthMeter {
+    fn default() -> Self {
+        Self {
+            sent: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            window: Mutex::new(ReceiveWindow {
+                started: Instant::now(),
+                received: 0,
+                warned: false,
+            }),
+        }
+    }
+}
+
+/// Reader counting the bytes read through it, so traffic is measured as it is on the wire,
+/// before decompression and including malformed data.
+pub struct CountingReader<'a, R> {
+    inner: &'a mut R,
+    count: u64,
+}
+
+impl<'a, R> CountingReader<'a, R> {
+    pub fn new(inner: &'a mut R) -> Self {
+        Self { inner, count: 0 }
+    }
+
+    /// The bytes read so far.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for CountingReader<'_, R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut *self.inner).poll_read(cx, buf);
+        self.count += (buf.filled().len() - before) as u64;
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[test]
+    fn test_caps_are_checked_per_window() {
+        let caps = BandwidthCaps {
+            window: Duration::from_millis(50),
+            soft: Some(100),
+            hard: Some(200),
+        };
+        let meter = BandwidthMeter::default();
+
+        assert_eq!(CapStatus::Within, meter.record_received(80, &caps));
+        assert_eq!(
+            CapStatus::SoftExceeded(160),
+            meter.record_received(80, &caps)
+        );
+        assert_eq!(CapStatus::Within, meter.record_received(20, &caps));
+        assert_eq!(
+            CapStatus::HardExceeded(260),
+            meter.record_received(80, &caps)
+        );
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(CapStatus::Within, meter.record_received(80, &caps));
+        assert_eq!(340, meter.received());
+    }
+
+    #[tokio::test]
+    async fn test_counting_reader() {
+        let mut source: &[u8] = b"twelve bytes";
+        let mut reader = CountingReader::new(&mut source);
+        let mut buffer = [0; 6];
+        reader.read_exact(&mut buffer).await.unwrap();
+
+        assert_eq!(6, reader.count());
+    }
+}
@@ -0,0 +1,115 @@
+use serde::Serialize;
+use serde_cbor::Value;
+use sha2::{Digest, Sha256};
+
+/// Serializes a value into canonical CBOR, for hashing and replays.
+///
+/// Unlike the regular payload encoding, the output only depends on the value itself:
+/// - map entries (including struct fields) are sorted in canonical CBOR key order, so the
+///   iteration order of a `HashMap` does not leak into the bytes;
+/// - every NaN is encoded as the same NaN and `-0.0` as `0.0`;
+/// - floats use the shortest encoding that keeps their value.
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - The canonical encoding of the value.
+/// * `Err(serde_cbor::Error)` - If the value cannot be represented in CBOR.
+pub fn to_canonical_cbor<T: Serialize>(value: &T) -> Result<Vec<u8>, serde_cbor::Error> {
+    let value = canonicalize(serde_cbor::value::to_value(value)?);
+    serde_cbor::to_vec(&value)
+}
+
+/// Hashes the canonical encoding of a value with SHA-256.
+///
+/// # Returns
+/// The hex encoded digest, identical on every platform for equal values.
+pub fn canonical_hash<T: Serialize>(value: &T) -> Result<String, serde_cbor::Error> {
+    let digest = Sha256::digest(to_canonical_cbor(value)?);
+    Ok(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Rewrites the floats of a value into their canonical form.
+///
+/// Maps are already ordered: `Value::Map` is a `BTreeMap` sorted in canonical key order.
+fn canonicalize(value: Value) -> Value {
+    match value {
+        Value::Float(float) if float.is_nan() => Value::Float(f64::NAN),
+        // Also matches `-0.0`, since float patterns compare by value.
+        Value::Float(0.0) => Value::Float(0.0),
+        Value::Array(items) => Value::Array(items.into_iter().map(canonicalize).collect()),
+        Value::Map(entries) => Value::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (canonicalize(key), canonicalize(value)))
+                .collect(),
+        ),
+        Value::Tag(tag, value) => Value::Tag(tag, Box::new(canonicalize(*value))),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Serialize)]
+    struct Sample {
+        turn: u32,
+        health: HashMap<String, i32>,
+        ratio: f64,
+    }
+
+    fn sample(names: &[&str], ratio: f64) -> Sample {
+        Sample {
+            turn: 3,
+            health: names
+                .iter()
+                .map(|n| (n.to_string(), n.len() as i32))
+                .collect(),
+            ratio,
+        }
+    }
+
+    #[test]
+    fn test_map_order_does_not_change_the_bytes() {
+        let names = ["red", "blue", "green", "a", "purple"];
+        let mut reversed = names;
+        reversed.reverse();
+
+        let expected = to_canonical_cbor(&sample(&names, 0.5)).unwrap();
+        for _ in 0..20 {
+            assert_eq!(
+                expected,
+                to_canonical_cbor(&sample(&reversed, 0.5)).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_canonical_bytes_are_stable() {
+        // Shorter keys sort first, then keys of equal length sort bytewise.
+        let bytes = to_canonical_cbor(&sample(&["red", "a"], -0.0)).unwrap();
+        let expected: Vec<u8> = [
+            &[0xa3][..],
+            &[0x64, b't', b'u', b'r', b'n', 0x03],
+            &[0x65, b'r', b'a', b't', b'i', b'o', 0xf9, 0x00, 0x00],
+            &[0x66, b'h', b'e', b'a', b'l', b't', b'h', 0xa2],
+            &[0x61, b'a', 0x01],
+            &[0x63, b'r', b'e', b'd', 0x03],
+        ]
+        .concat();
+
+        assert_eq!(expected, bytes);
+    }
+
+    #[test]
+    fn test_nan_is_canonical() {
+        let quiet = to_canonical_cbor(&f64::NAN).unwrap();
+        let other = to_canonical_cbor(&f64::from_bits(0x7ff8_0000_0000_0001)).unwrap();
+        assert_eq!(quiet, other);
+        assert_eq!(
+            canonical_hash(&sample(&["red"], f64::NAN)).unwrap(),
+            canonical_hash(&sample(&["red"], -f64::NAN)).unwrap()
+        );
+    }
+}
@@ -1,17 +1,66 @@
-/// A simple checksum utility for validating data integrity using XOR.
-pub struct Checksum;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 
-impl Checksum {
-    /// Computes a 16-bit XOR-based checksum over the given payload.
+/// Computes and verifies the checksum carried in a packet header.
+pub trait Checksum: Send + Sync {
+    /// Computes the checksum of the given payload.
     ///
     /// # Arguments
     ///
     /// * `payload` - A byte slice containing the data to compute the checksum for.
+    fn compute(&self, payload: &[u8]) -> u32;
+
+    /// Verifies that the provided checksum matches the computed checksum for the payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `checksum` - The expected checksum, as read from the header.
+    /// * `payload` - A byte slice containing the data to validate.
     ///
     /// # Returns
     ///
-    /// A `u16` representing the XOR checksum of the input payload.
-    pub fn new(payload: &[u8]) -> u16 {
+    /// `true` if the provided checksum matches the computed checksum; `false` otherwise.
+    fn verify(&self, checksum: u32, payload: &[u8]) -> bool {
+        self.compute(payload) == checksum
+    }
+}
+
+/// The checksum algorithms a client can negotiate during the handshake.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ChecksumKind {
+    /// The original 16-bit XOR checksum, only kept for legacy clients.
+    Xor,
+    #[default]
+    Crc32c,
+    /// HMAC-SHA256 keyed by the session token, which also authenticates the payload.
+    HmacSha256,
+}
+
+impl ChecksumKind {
+    /// Builds the checksum implementation for this algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `session_token` - The key used by `HmacSha256`. Until the client has authenticated
+    ///   there is no key, and CRC32C is used instead.
+    pub fn build(&self, session_token: Option<&str>) -> Box<dyn Checksum> {
+        match (self, session_token) {
+            (ChecksumKind::Xor, _) => Box::new(XorChecksum),
+            (ChecksumKind::HmacSha256, Some(token)) => {
+                Box::new(HmacSha256Checksum::new(token.as_bytes()))
+            }
+            _ => Box::new(Crc32cChecksum),
+        }
+    }
+}
+
+/// A simple checksum utility for validating data integrity using XOR.
+pub struct XorChecksum;
+
+impl Checksum for XorChecksum {
+    /// Computes a 16-bit XOR-based checksum over the given payload.
+    fn compute(&self, payload: &[u8]) -> u32 {
         let mut checksum: u16 = 0;
         // Iterate over each byte in the payload
         for &byte in payload {
@@ -19,24 +68,48 @@ impl Checksum {
             checksum ^= byte as u16;
         }
         // Return the computed checksum
-        return checksum;
+        checksum as u32
     }
+}
 
-    /// Verifies that the provided checksum matches the computed checksum for the payload.
-    ///
-    /// # Arguments
-    ///
-    /// * `checksum` - A reference to the expected checksum as `i16`.
-    /// * `payload` - A byte slice containing the data to validate.
-    ///
-    /// # Returns
-    ///
-    /// `true` if the provided checksum matches the computed checksum; `false` otherwise.
-    pub fn check(checksum: &i16, payload: &[u8]) -> bool {
-        // Compute the checksum for the given payload
-        let check = Checksum::new(payload);
-        // Compare the provided checksum with the computed checksum
-        return *checksum == check as i16;
+/// CRC32C (Castagnoli), the default checksum.
+pub struct Crc32cChecksum;
+
+impl Checksum for Crc32cChecksum {
+    fn compute(&self, payload: &[u8]) -> u32 {
+        crc32c::crc32c(payload)
+    }
+}
+
+/// HMAC-SHA256 keyed by the player's session token, truncated to the 4 bytes a header carries.
+pub struct HmacSha256Checksum {
+    key: Vec<u8>,
+}
+
+impl HmacSha256Checksum {
+    pub fn new(key: &[u8]) -> Self {
+        Self { key: key.to_vec() }
+    }
+
+    fn mac(&self, payload: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any size");
+        mac.update(payload);
+        mac
+    }
+}
+
+impl Checksum for HmacSha256Checksum {
+    fn compute(&self, payload: &[u8]) -> u32 {
+        let tag = self.mac(payload).finalize().into_bytes();
+        u32::from_be_bytes([tag[0], tag[1], tag[2], tag[3]])
+    }
+
+    /// Verifies the truncated tag in constant time.
+    fn verify(&self, checksum: u32, payload: &[u8]) -> bool {
+        self.mac(payload)
+            .verify_truncated_left(&checksum.to_be_bytes())
+            .is_ok()
     }
 }
 
@@ -47,41 +120,59 @@ mod tests {
     #[test]
     fn test_checksum_empty_payload() {
         let payload: &[u8] = &[];
-        let expected: u16 = 0;
+        let expected: u32 = 0;
         // Verify that the checksum for an empty payload is 0
-        assert_eq!(Checksum::new(payload), expected);
+        assert_eq!(XorChecksum.compute(payload), expected);
     }
 
     #[test]
     fn test_checksum_single_byte() {
         let payload: &[u8] = &[0xAB];
-        let expected: u16 = 0xAB;
+        let expected: u32 = 0xAB;
         // Verify that the checksum for a single byte matches the byte value
-        assert_eq!(Checksum::new(payload), expected);
+        assert_eq!(XorChecksum.compute(payload), expected);
     }
 
     #[test]
     fn test_checksum_multiple_bytes() {
         let payload: &[u8] = &[0x01, 0x02, 0x03];
         // XOR: 0x01 ^ 0x02 = 0x03, 0x03 ^ 0x03 = 0x00
-        let expected: u16 = 0x00;
+        let expected: u32 = 0x00;
         // Verify that the checksum for multiple bytes is computed correctly
-        assert_eq!(Checksum::new(payload), expected);
+        assert_eq!(XorChecksum.compute(payload), expected);
     }
 
     #[test]
     fn test_checksum_check_valid() {
         let payload: &[u8] = &[0x10, 0x20, 0x30];
-        let checksum = Checksum::new(payload) as i16;
+        let checksum = XorChecksum.compute(payload);
         // Verify that the checksum validation passes for a valid checksum
-        assert!(Checksum::check(&checksum, payload));
+        assert!(XorChecksum.verify(checksum, payload));
     }
 
     #[test]
     fn test_checksum_check_invalid() {
         let payload: &[u8] = &[0x10, 0x20, 0x30];
-        let bad_checksum: i16 = 0xFF;
+        let bad_checksum: u32 = 0xFF;
         // Verify that the checksum validation fails for an invalid checksum
-        assert!(!Checksum::check(&bad_checksum, payload));
+        assert!(!XorChecksum.verify(bad_checksum, payload));
+    }
+
+    #[test]
+    fn test_crc32c_detects_swapped_bytes() {
+        // XOR cannot tell these apart, CRC32C can
+        assert_eq!(XorChecksum.compute(b"ab"), XorChecksum.compute(b"ba"));
+        assert_ne!(Crc32cChecksum.compute(b"ab"), Crc32cChecksum.compute(b"ba"));
+        assert_eq!(0xE3069283, Crc32cChecksum.compute(b"123456789"));
+    }
+
+    #[test]
+    fn test_hmac_depends_on_session_token() {
+        let payload = b"play card";
+        let checksum = ChecksumKind::HmacSha256.build(Some("token-a"));
+        let other = ChecksumKind::HmacSha256.build(Some("token-b"));
+
+        assert!(checksum.verify(checksum.compute(payload), payload));
+        assert!(!other.verify(checksum.compute(payload), payload));
     }
 }
@@ -0,0 +1,112 @@
+use crate::utils::errors::ProtocolError;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+/// Header flag bits holding the compression algorithm of the payload.
+pub const COMPRESSION_MASK: u8 = 0b0000_0011;
+
+/// Payloads smaller than this are not worth compressing.
+pub const COMPRESSION_THRESHOLD: usize = 512;
+
+/// The payload compression algorithms a client can negotiate during the handshake.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Deflate,
+    Zstd,
+}
+
+impl Compression {
+    /// The header flag bits marking a payload compressed with this algorithm.
+    pub fn flag(&self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Deflate => 1,
+            Compression::Zstd => 2,
+        }
+    }
+
+    /// Reads the compression algorithm from the header flags.
+    ///
+    /// # Returns
+    /// * `Ok(Compression)` - The algorithm the payload was compressed with.
+    /// * `Err(ProtocolError)` - If the flags name an unknown algorithm.
+    pub fn from_flags(flags: u8) -> Result<Self, ProtocolError> {
+        match flags & COMPRESSION_MASK {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Deflate),
+            2 => Ok(Compression::Zstd),
+            other => Err(ProtocolError::InvalidHeaderError(format!(
+                "Unknown compression flag: {other}"
+            ))),
+        }
+    }
+
+    /// Compresses a payload.
+    pub fn compress(&self, payload: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(payload.to_vec()),
+            Compression::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+                encoder.write_all(payload)?;
+                encoder.finish()
+            }
+            Compression::Zstd => zstd::encode_all(payload, 3),
+        }
+    }
+
+    /// Decompresses a payload, refusing to produce more than `max_size` bytes.
+    ///
+    /// # Returns
+    /// * `Ok(Vec<u8>)` - The decompressed payload.
+    /// * `Err(ProtocolError)` - If the payload is corrupt or decompresses past `max_size`.
+    pub fn decompress(&self, payload: &[u8], max_size: usize) -> Result<Vec<u8>, ProtocolError> {
+        let mut decompressed = Vec::new();
+        let limit = max_size as u64 + 1;
+        let read = match self {
+            Compression::None => return Ok(payload.to_vec()),
+            Compression::Deflate => DeflateDecoder::new(payload)
+                .take(limit)
+                .read_to_end(&mut decompressed),
+            Compression::Zstd => zstd::Decoder::new(payload)
+                .and_then(|decoder| decoder.take(limit).read_to_end(&mut decompressed)),
+        };
+
+        read.map_err(|e| ProtocolError::InvalidPacketError(format!("Corrupt payload: {e}")))?;
+        if decompressed.len() > max_size {
+            return Err(ProtocolError::PayloadTooLarge(decompressed.len() as u32));
+        }
+
+        Ok(decompressed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compression_round_trips() {
+        let payload: Vec<u8> = b"creature".repeat(500);
+        for compression in [Compression::None, Compression::Deflate, Compression::Zstd] {
+            let compressed = compression.compress(&payload).unwrap();
+            let decompressed = compression.decompress(&compressed, payload.len()).unwrap();
+
+            assert_eq!(payload, decompressed);
+            assert_eq!(
+                compression,
+                Compression::from_flags(compression.flag()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_decompression_is_bounded() {
+        let payload = vec![0u8; 10_000];
+        let compressed = Compression::Zstd.compress(&payload).unwrap();
+        assert!(Compression::Zstd.decompress(&compressed, 1_000).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use crate::models::ids::PlayerId;
+use crate::models::match_report::ConnectionQualityReport;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Round trips measured from the keepalive pings.
+#[derive(Default)]
+struct RoundTrips {
+    pending: Option<Instant>, // When the last unanswered ping was sent.
+    total_ms: u64,
+    samples: u64,
+}
+
+/// How well a client stayed connected over the whole match, so the match report can tell
+/// losses caused by the connection apart from losses caused by the game.
+#[derive(Default)]
+pub struct ConnectionQuality {
+    disconnects: AtomicU32,
+    reconnect_ms: AtomicU64, // Time spent disconnected before reconnecting.
+    packets_resent: AtomicU64,
+    disconnected_since: Mutex<Option<Instant>>,
+    round_trips: Mutex<RoundTrips>,
+}
+
+impl ConnectionQuality {
+    /// Records the client dropping; does nothing if it is already known to be disconnected.
+    ///
+    /// # Returns
+    /// `true` if the client was known to be connected until now.
+    pub fn record_disconnect(&self) -> bool {
+        let mut since = self
+            .disconnected_since
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if since.is_some() {
+            return false;
+        }
+        *since = Some(Instant::now());
+        self.disconnects.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// How long the client has been gone, `None` while it is connected.
+    pub fn disconnected_for(&self) -> Option<Duration> {
+        self.disconnected_since
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .map(|since| since.elapsed())
+    }
+
+    /// Times the client dropped so far.
+    pub fn disconnects(&self) -> u32 {
+        self.disconnects.load(Ordering::Relaxed)
+    }
+
+    /// Records the client coming back, adding the time it was away to the reconnect time.
+    ///
+    /// A connection that dropped without being noticed still counts as a disconnect.
+    pub fn record_reconnect(&self) {
+        self.record_disconnect();
+        let mut since = self
+            .disconnected_since
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if let Some(since) = since.take() {
+            let away = since.elapsed().as_millis() as u64;
+            self.reconnect_ms.fetch_add(away, Ordering::Relaxed);
+        }
+    }
+
+    /// Records a keepalive ping sent to the client; only the latest one is timed.
+    pub fn record_ping(&self) {
+        let mut round_trips = self.round_trips.lock().unwrap_or_else(|e| e.into_inner());
+        round_trips.pending = Some(Instant::now());
+    }
+
+    /// Records the client answering a ping, sampling the round trip time.
+    pub fn record_pong(&self) {
+        let mut round_trips = self.round_trips.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sent) = round_trips.pending.take() {
+            round_trips.total_ms += sent.elapsed().as_millis() as u64;
+            round_trips.samples += 1;
+        }
+    }
+
+    /// Records packets sent again after the client missed them.
+    pub fn record_resent(&self, packets: u64) {
+        self.packets_resent.fetch_add(packets, Ordering::Relaxed);
+    }
+
+    /// Summarizes the connection of a player for the match report.
+    ///
+    /// A player still disconnected when the match ends has the time away so far counted in.
+    pub fn report(&self, player_id: PlayerId) -> ConnectionQualityReport {
+        let away = self
+            .disconnected_since
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .map_or(0, |since| since.elapsed().as_millis() as u64);
+        let round_trips = self.round_trips.lock().unwrap_or_else(|e| e.into_inner());
+
+        ConnectionQualityReport {
+            player_id,
+            disconnects: self.disconnects.load(Ordering::Relaxed),
+            reconnect_ms: self.reconnect_ms.load(Ordering::Relaxed) + away,
+            average_rtt_ms: (round_trips.samples > 0)
+                .then(|| round_trips.total_ms / round_trips.samples),
+            packets_resent: self.packets_resent.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quality_counts_drops_and_resends() {
+        let quality = ConnectionQuality::default();
+        let clean = quality.report("red".into());
+        assert_eq!(0, clean.disconnects);
+        assert_eq!(None, clean.average_rtt_ms);
+
+        quality.record_disconnect();
+        quality.record_disconnect();
+        quality.record_reconnect();
+        quality.record_reconnect();
+        quality.record_resent(4);
+        quality.record_pong();
+        quality.record_ping();
+        quality.record_pong();
+
+        let report = quality.report("red".into());
+        assert_eq!(2, report.disconnects);
+        assert_eq!(4, report.packets_resent);
+        assert!(report.average_rtt_ms.is_some());
+    }
+}
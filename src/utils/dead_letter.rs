@@ -0,0 +1,136 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// A file-backed queue for messages that could not be delivered.
+///
+/// Each message is stored as one JSON line, so the queue survives restarts and can be inspected
+/// by hand. The file is only removed once every message has been delivered.
+pub struct DeadLetterQueue {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl DeadLetterQueue {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Appends a message to the queue.
+    pub fn push<T: Serialize>(&self, message: &T) -> std::io::Result<()> {
+        let line = serde_json::to_string(message)?;
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{line}")
+    }
+
+    /// Returns every queued message, without removing it, along with the line it is stored on.
+    ///
+    /// Lines that cannot be parsed are skipped and stay in the file, so nothing is silently dropped.
+    pub fn peek<T: DeserializeOwned>(&self) -> std::io::Result<Vec<(usize, T)>> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut messages = Vec::new();
+        for (index, line) in self.lines()?.iter().enumerate() {
+            if let Ok(message) = serde_json::from_str::<T>(line) {
+                messages.push((index, message));
+            }
+        }
+
+        Ok(messages)
+    }
+
+    /// Removes the messages stored on the given lines, as returned by `peek`.
+    ///
+    /// The rest of the queue, including messages pushed since, is written to a temporary file that
+    /// then replaces the queue, so a crash leaves either the old or the new queue on disk. The file
+    /// is removed once it holds no more messages.
+    pub fn remove(&self, delivered: &[usize]) -> std::io::Result<()> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let remaining: Vec<_> = self
+            .lines()?
+            .into_iter()
+            .enumerate()
+            .filter(|(index, line)| !delivered.contains(index) && !line.trim().is_empty())
+            .map(|(_, line)| line)
+            .collect();
+
+        if remaining.is_empty() {
+            return match std::fs::remove_file(&self.path) {
+                Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error),
+                _ => Ok(()),
+            };
+        }
+
+        let temporary = self.path.with_extension("tmp");
+        std::fs::write(&temporary, remaining.join("\n") + "\n")?;
+        std::fs::rename(&temporary, &self.path)
+    }
+
+    /// The number of messages currently queued.
+    pub fn len(&self) -> usize {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        std::fs::read_to_string(&self.path)
+            .map(|content| content.lines().filter(|l| !l.trim().is_empty()).count())
+            .unwrap_or(0)
+    }
+
+    /// Every line of the queue file, empty if there is none. Callers must hold the lock.
+    fn lines(&self) -> std::io::Result<Vec<String>> {
+        match std::fs::File::open(&self.path) {
+            Ok(file) => BufReader::new(file).lines().collect(),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_survive_until_drained() {
+        let path = std::env::temp_dir().join(format!("dlq-{}.jsonl", uuid::Uuid::new_v4()));
+        let queue = DeadLetterQueue::new(&path);
+        queue.push(&"first".to_string()).unwrap();
+        queue.push(&"second".to_string()).unwrap();
+
+        let reopened = DeadLetterQueue::new(&path);
+        assert_eq!(2, reopened.len());
+        let messages = reopened.peek::<String>().unwrap();
+        assert_eq!(
+            vec![(0, "first".to_string()), (1, "second".to_string())],
+            messages
+        );
+        assert_eq!(2, reopened.len());
+
+        reopened.remove(&[0, 1]).unwrap();
+        assert_eq!(0, reopened.len());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_removing_keeps_undelivered_and_newer_messages() {
+        let path = std::env::temp_dir().join(format!("dlq-{}.jsonl", uuid::Uuid::new_v4()));
+        let queue = DeadLetterQueue::new(&path);
+        queue.push(&"delivered".to_string()).unwrap();
+        queue.push(&"failed".to_string()).unwrap();
+        let peeked = queue.peek::<String>().unwrap();
+        queue.push(&"newer".to_string()).unwrap();
+
+        queue.remove(&[peeked[0].0]).unwrap();
+        assert_eq!(
+            vec![(0, "failed".to_string()), (1, "newer".to_string())],
+            queue.peek::<String>().unwrap()
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
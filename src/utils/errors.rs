@@ -1,3 +1,8 @@
+use crate::models::ids::{MatchId, PlayerId};
+#[cfg(feature = "game")]
+use crate::utils::http_service::Upstream;
+use serde::Serialize;
+
 #[derive(Debug, thiserror::Error)]
 pub enum PlayerConnectionError {
     #[error("{0}")]
@@ -33,6 +38,37 @@ pub enum PlayerConnectionError {
     #[error("Player does not have permission to access deck")]
     UnauthorizedDeckError,
 
+    #[error("Protocol handshake must happen before connecting")]
+    HandshakeRequired,
+
+    #[cfg(feature = "game")]
+    #[error(transparent)]
+    Upstream(#[from] HttpError),
+
+    #[error("{0}")]
+    InternalError(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SpectatorError {
+    #[error("Match is not open to spectators")]
+    SpectatingDisabled,
+
+    #[error("Match already has the maximum of {0} spectators")]
+    SpectatorsFull(usize),
+
+    #[error("Protocol handshake must happen before spectating")]
+    HandshakeRequired,
+
+    #[error("`{0}` is not allowed to watch this match")]
+    NotAllowed(String),
+
+    #[error("Match `{0}` has no replay")]
+    ReplayNotFound(String),
+
+    #[error(transparent)]
+    Unauthenticated(#[from] PlayerConnectionError),
+
     #[error("{0}")]
     InternalError(String),
 }
@@ -44,12 +80,54 @@ pub enum ProtocolError {
 
     #[error("Invalid packet: {0}")]
     InvalidPacketError(String),
+
+    #[error("Payload of {0} bytes exceeds the maximum packet size")]
+    PayloadTooLarge(u32),
+
+    #[error("Connection error: {0}")]
+    StreamError(String),
+
+    #[error("Payload could not be decoded: {0}")]
+    CorruptPayload(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PayloadError {
+    #[error("Payload of {0} bytes exceeds the {1} bytes allowed for this request")]
+    OverBudget(usize, usize),
+
+    #[error("Payload nests deeper than {0} levels")]
+    TooDeep(usize),
+
+    #[error("Payload holds a collection of {0} entries, more than the {1} allowed")]
+    CollectionTooLarge(u64, u64),
+
+    #[error("Payload holds more than {0} values")]
+    TooManyValues(u64),
+
+    #[error("Malformed CBOR payload: {0}")]
+    Malformed(String),
+
+    #[error("Payload schema version {0} is not supported; versions {1} to {2} are")]
+    UnsupportedPayloadVersion(u8, u8, u8),
+
+    #[error("{0}")]
+    Decode(#[from] serde_cbor::Error),
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum NetworkError {
     #[error("Could not send package: {0}")]
     PackageWriteError(String),
+
+    #[error("Bandwidth cap exceeded: {0} bytes received in {1} seconds")]
+    BandwidthCapExceeded(u64, u64),
+
+    #[error("Too many rejected actions: more than {0} in {1} seconds")]
+    TooManyViolations(u32, u64),
+
+    #[error("Too many malformed packets: more than {0}")]
+    TooManyMalformedPackets(u32),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -75,8 +153,200 @@ pub enum GameLogicError {
     #[error("Invalid GameAction return")]
     InvalidGameActions,
 
+    #[error("Hook `{0}` returned a value that cannot be converted")]
+    InvalidHookResult(String),
+
+    #[error("The stake has not been confirmed by every player yet")]
+    StakeNotConfirmed,
+
+    #[error("Confirmed stake does not match the stake of the match")]
+    StakeMismatch,
+
+    #[error("Match has no stake to confirm")]
+    NoStake,
+
+    #[error("The match has not started yet")]
+    MatchNotStarted,
+
+    #[error("`{0}` is not a valid board position")]
+    InvalidPosition(String),
+
+    #[error("Board slot `{0}` is already occupied")]
+    SlotOccupied(String),
+
+    #[error("A {0} cannot be placed at `{1}`")]
+    WrongZone(String, String),
+
+    #[error("No free slot left in the {0} zone")]
+    ZoneFull(String),
+
+    #[error("Card `{0}` has no activated ability")]
+    NoActivatedAbility(String),
+
+    #[error("The ability of `{0}` has no activation left")]
+    AbilityExhausted(String),
+
+    #[error("Card `{0}` is not on the player's board")]
+    CardNotOnBoard(String),
+
+    #[error("Several targets are legal, one must be chosen")]
+    TargetRequired,
+
+    #[error("Card `{0}` is not in a graveyard")]
+    NotInGraveyard(String),
+
+    #[error("Card `{0}` is not in a hand")]
+    CardNotInHand(String),
+
+    #[error("A {0} cannot be put onto the board")]
+    NotAPermanent(String),
+
+    #[error("Hand of `{0}` is full")]
+    HandFull(String),
+
     #[error("Not player's turn")]
     NotPlayerTurn,
+
+    #[error("`{0}` is stunned")]
+    Stunned(String),
+
+    #[error("`{0}` is not a legal target")]
+    InvalidTarget(String),
+
+    #[error("Card has no legal targets")]
+    NoLegalTargets,
+
+    #[error("Prompt `{0}` was not found")]
+    PromptNotFound(u32),
+
+    #[error("`{0}` is not one of the prompt's options")]
+    InvalidPromptChoice(String),
+
+    #[error("The action kept prompting after {0} default answers")]
+    TooManyPrompts(usize),
+
+    #[error("Batch of {0} actions exceeds the maximum batch size")]
+    BatchTooLarge(usize),
+
+    #[error("Batched action {0} failed, no action was applied: {1}")]
+    BatchActionFailed(usize, Box<GameLogicError>),
+
+    #[error("Batched actions must specify every choice up front")]
+    BatchActionPrompted,
+
+    #[error("Script `{0}` exceeded its instruction or time limit")]
+    ScriptTimeout(String),
+
+    #[error("Script `{0}` exceeded the Lua memory limit")]
+    ScriptMemoryLimit(String),
+
+    #[error("The Lua worker of the match stopped")]
+    ScriptWorkerStopped,
+
+    #[error("Plays cannot be undone in this match")]
+    UndoDisabled,
+
+    #[error("There is no play of the player left to undo")]
+    NothingToUndo,
+
+    #[error("Every one of the {0} undos of the match was used")]
+    UndoLimitReached(u32),
+
+    #[error("The opponent cannot be asked to consent to the undo")]
+    UndoConsentUnavailable,
+
+    #[error("Effects of `{0}` triggered more than {1} times in a chain, stopped as a loop")]
+    EffectLoopDetected(String, u32),
+
+    #[error("An attack is already waiting on blockers")]
+    CombatInProgress,
+
+    #[error("No creature was declared as attacker")]
+    NoAttackers,
+
+    #[error("Card `{0}` cannot attack")]
+    InvalidAttacker(String),
+
+    #[error("`{0}` is not an opponent to attack")]
+    InvalidDefender(String),
+
+    #[error("`{0}` cannot block or be blocked")]
+    InvalidBlock(String),
+
+    #[error("No attack is waiting on the player's blockers")]
+    NoCombatToBlock,
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum SocialError {
+    #[error("Emotes are muted in this match")]
+    EmotesMuted,
+
+    #[error("`{0}` is not an emote")]
+    UnknownEmote(String),
+
+    #[error("`{0}` is not a board position to ping")]
+    InvalidPing(String),
+
+    #[error("Too many messages, slow down")]
+    RateLimited,
+
+    #[error("The message is empty")]
+    EmptyMessage,
+
+    #[error("The message is longer than {0} characters")]
+    MessageTooLong(usize),
+
+    #[error("The message was refused by the chat filter")]
+    MessageFiltered,
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum DraftError {
+    #[error("No cards to deal the draft packs from")]
+    NoCards,
+
+    #[error("It is not your pick")]
+    NotYourPick,
+
+    #[error("`{0}` is not in the open pack")]
+    CardNotInPack(String),
+
+    #[error("The draft is complete")]
+    DraftComplete,
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum SideboardError {
+    #[error("The deck holds no `{0}` to take out")]
+    NotInDeck(String),
+
+    #[error("The sideboard holds no `{0}` to put in")]
+    NotInSideboard(String),
+
+    #[error("The sideboarded deck is illegal: {}", join_violations(.0))]
+    DeckIllegal(Vec<DeckConstraintError>),
+
+    #[error("Your swaps were already submitted")]
+    AlreadySubmitted,
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum LobbyError {
+    #[error("Private matches are only available in lobby mode")]
+    LobbyDisabled,
+
+    #[error("`{0}` is already waiting in the lobby")]
+    AlreadyWaiting(PlayerId),
+
+    #[error("No private match has the join code `{0}`")]
+    UnknownJoinCode(String),
+
+    #[error("The private match `{0}` already has its players")]
+    PrivateMatchFull(String),
+
+    #[error("The join code `{0}` expired")]
+    JoinCodeExpired(String),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -94,26 +364,204 @@ pub enum CardRequestError {
     MissingCardData(String),
 
     #[error("Failed to parse full cards response")]
-    SelectedCardsParseError
+    SelectedCardsParseError,
+
+    #[cfg(feature = "game")]
+    #[error(transparent)]
+    Upstream(#[from] HttpError),
 }
 
+/// Why a request to a platform service got no usable response.
+#[cfg(feature = "game")]
+#[derive(Debug, thiserror::Error)]
+pub enum HttpError {
+    #[error("The {0} service is failing, requests are paused")]
+    CircuitOpen(Upstream),
+
+    #[error("The {0} service did not answer in time")]
+    Timeout(Upstream),
+
+    #[error("The {0} service could not be reached: {1}")]
+    Unreachable(Upstream, String),
+
+    #[error("The {0} service answered with status {1}")]
+    ServerError(Upstream, u16),
+}
+
+/// Why the game of a match could not be created, with the player and deck it failed on.
 #[derive(Debug, thiserror::Error)]
 pub enum GameInstanceError {
-    #[error("Placeholder error, make a specific one")]
-    PlaceHolderError
+    #[error("Could not load the card scripts: {0}")]
+    ScriptsUnavailable(String),
+
+    #[error("Could not fetch the profile of `{player_id}`: {source}")]
+    ProfileUnavailable {
+        player_id: PlayerId,
+        #[source]
+        source: PlayerConnectionError,
+    },
+
+    #[error("Could not fetch deck `{deck_id}` of `{player_id}`: {source}")]
+    DeckUnavailable {
+        player_id: PlayerId,
+        deck_id: String,
+        #[source]
+        source: PlayerConnectionError,
+    },
+
+    #[error("Could not fetch the cards of decks {deck_ids:?}: {source}")]
+    CardsUnavailable {
+        deck_ids: Vec<String>, // Every deck of the match, whose cards are fetched together.
+        #[source]
+        source: CardRequestError,
+    },
+
+    #[error("Deck of `{0}` was rejected: {1}")]
+    DeckRejected(String, DeckConstraintError),
+
+    #[error("The format seats {0} players, {1} were listed")]
+    SeatsMismatch(usize, usize),
+
+    #[error("{0}")]
+    DeckIllegal(DeckIllegal),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, thiserror::Error)]
+#[serde(tag = "kind", content = "details", rename_all = "snake_case")]
+pub enum DeckConstraintError {
+    #[error("The match only accepts decks built from a signed card pool")]
+    MissingPool,
+
+    #[error("No secret is configured to verify card pools")]
+    NoPoolSecret,
+
+    #[error("The card pool signature is invalid, the pool was tampered with")]
+    TamperedPool,
+
+    #[error("The card pool of run `{0}` was issued for another player or deck")]
+    PoolMismatch(String),
+
+    #[error("Deck holds more copies of `{0}` than the card pool offered")]
+    CardNotInPool(String),
+
+    #[error("Deck holds {0} cards, the format requires between {1} and {2}")]
+    DeckSize(u32, u32, u32),
+
+    #[error("Deck holds more than {1} copies of `{0}`")]
+    TooManyCopies(String, u32),
+
+    #[error("`{0}` is banned in the format")]
+    BannedCard(String),
+
+    #[error("`{0}` is not legal in the format")]
+    NotLegal(String),
+}
+
+/// Every rule of its format a deck breaks, sent to the matchmaker in a `DeckIllegal` packet
+/// when the match cannot be initialized.
+#[derive(Debug, Clone, PartialEq, Serialize, thiserror::Error)]
+#[error(
+    "Deck `{deck_id}` of `{player_id}` is illegal in the `{format}` format: {}",
+    join_violations(violations)
+)]
+pub struct DeckIllegal {
+    pub player_id: PlayerId,
+    pub deck_id: String,
+    pub format: String,
+    pub violations: Vec<DeckConstraintError>,
+}
+
+fn join_violations(violations: &[DeckConstraintError]) -> String {
+    violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoadTestError {
+    #[error("Unknown option `{0}`")]
+    UnknownOption(String),
+
+    #[error("Option `{0}` expects a value")]
+    MissingValue(String),
+
+    #[error("Invalid value for `{0}`: {1}")]
+    InvalidValue(String, String),
+
+    #[error("Invalid script: {0}")]
+    InvalidScript(String),
+
+    #[error("{0}")]
+    ThresholdExceeded(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScriptManifestError {
+    #[error("Could not read the script manifest `{0}`: {1}")]
+    Unreadable(String, String),
+
+    #[error("Invalid script manifest: {0}")]
+    Invalid(String),
+
+    #[error("Script function `{1}` is already listed as `{0}`")]
+    Duplicate(String, String),
+
+    #[error("Script function `{0}` is not defined by any script")]
+    Missing(String),
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum ScriptLoadError {
+    #[error("Script `{2}` defines `{0}`, which `{1}` already defines")]
+    Collision(String, String, String),
+
+    #[error("Card script `{0}` is named after a script category")]
+    ReservedCardId(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScriptTestError {
+    #[error("Could not load the scripts: {0}")]
+    ScriptsUnavailable(String),
+
+    #[error("Invalid fixture `{0}`: {1}")]
+    InvalidFixture(String, String),
+
+    #[error("No fixture found in `{0}`")]
+    NoFixtures(String),
+
+    #[error("{0} of {1} script tests failed")]
+    Failed(usize, usize),
+}
+
+/// Why a match could not be hosted; the matchmaker is answered with its `reply` packet and a
+/// process hosting a single match exits with its `ExitCode`.
 #[derive(Debug, thiserror::Error)]
 pub enum ServerInstanceError {
-    #[error("Placeholder error, make a specific one")]
-    PlaceHolderError,
-    
-    #[error("Placeholder error, make a specific one")]
-    AlreadyInitialized,
-    
+    #[error("Invalid `InitServer` request: {0}")]
+    InvalidRequest(#[from] PayloadError),
+
+    #[error("Match `{0}` is already hosted")]
+    AlreadyInitialized(MatchId),
+
+    #[error("Already hosting {0} matches, the most allowed by `MAX_MATCHES`")]
+    CapacityReached(usize),
+
     #[error("Failed to create Game Instance: {0}")]
-    GameInstanceFail(String),
-    
-    #[error("Unable to unwrap UninitializedServer")]
-    UnwrapFailed
+    GameInstance(#[from] GameInstanceError),
+
+    #[error("Could not deal the draft packs: {0}")]
+    DraftFailed(String),
+
+    #[error("Could not fetch the deck of `{player_id}` to sideboard: {source}")]
+    SideboardFailed {
+        player_id: PlayerId,
+        #[source]
+        source: PlayerConnectionError,
+    },
+
+    #[error("{0}")]
+    DeckIllegal(DeckIllegal),
 }
@@ -0,0 +1,286 @@
+use crate::utils::errors::HttpError;
+use crate::{logger, utils::logger::Logger, SETTINGS};
+use rand::Rng;
+use reqwest::{Client, RequestBuilder, Response};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A platform service the server sends requests to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Upstream {
+    Auth,
+    Deck,
+    Card,
+}
+
+impl Upstream {
+    const ALL: [Upstream; 3] = [Upstream::Auth, Upstream::Deck, Upstream::Card];
+}
+
+impl fmt::Display for Upstream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Upstream::Auth => write!(f, "auth"),
+            Upstream::Deck => write!(f, "deck"),
+            Upstream::Card => write!(f, "card"),
+        }
+    }
+}
+
+/// How the requests to an upstream are sent and retried.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HttpPolicy {
+    pub timeout: Duration,          // Time a single attempt may take.
+    pub retries: u32,               // Attempts made after the first one fails.
+    pub backoff: Duration,          // Delay before the first retry, doubled for every next one.
+    pub breaker_threshold: u32,     // Failed requests in a row that open the circuit.
+    pub breaker_cooldown: Duration, // Time an open circuit refuses requests before a trial one.
+}
+
+impl Default for HttpPolicy {
+    fn default() -> Self {
+        let settings = SETTINGS.get();
+        Self {
+            timeout: Duration::from_millis(settings.map_or(5000, |s| s.http_timeout)),
+            retries: settings.map_or(2, |s| s.http_retries),
+            backoff: Duration::from_millis(settings.map_or(200, |s| s.http_retry_backoff)),
+            breaker_threshold: settings.map_or(5, |s| s.circuit_breaker_threshold),
+            breaker_cooldown: Duration::from_secs(
+                settings.map_or(30, |s| s.circuit_breaker_cooldown),
+            ),
+        }
+    }
+}
+
+/// Where the circuit of an upstream stands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Circuit {
+    /// Requests go through; counts the requests that failed in a row.
+    Closed(u32),
+    /// Requests are refused until the instant, after which one trial request may go through.
+    Open(Instant),
+    /// A trial request is in flight; the others are refused until it is answered, or until the
+    /// instant if it never is.
+    HalfOpen(Instant),
+}
+
+/// Stops sending requests to an upstream that keeps failing, so the players are told right
+/// away instead of waiting for every request to time out.
+pub struct CircuitBreaker {
+    circuit: Mutex<Circuit>,
+    threshold: u32,
+    cooldown: Duration,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            circuit: Mutex::new(Circuit::Closed(0)),
+            threshold: threshold.max(1),
+            cooldown,
+        }
+    }
+
+    /// Whether a request may be sent now; a trial request is let through once the cooldown of
+    /// an open circuit is over.
+    pub fn allow(&self) -> bool {
+        self.allow_at(Instant::now())
+    }
+
+    fn allow_at(&self, now: Instant) -> bool {
+        let mut circuit = self.lock();
+        match *circuit {
+            Circuit::Closed(_) => true,
+            Circuit::Open(until) | Circuit::HalfOpen(until) if now >= until => {
+                *circuit = Circuit::HalfOpen(now + self.cooldown);
+                true
+            }
+            Circuit::Open(_) | Circuit::HalfOpen(_) => false,
+        }
+    }
+
+    /// Records that a request was answered, closing the circuit.
+    pub fn record_success(&self) {
+        *self.lock() = Circuit::Closed(0);
+    }
+
+    /// Records that a request failed, opening the circuit once too many failed in a row.
+    ///
+    /// # Returns
+    /// `true` if the failure opened the circuit.
+    pub fn record_failure(&self) -> bool {
+        self.record_failure_at(Instant::now())
+    }
+
+    fn record_failure_at(&self, now: Instant) -> bool {
+        let mut circuit = self.lock();
+        let failures = match *circuit {
+            Circuit::Closed(failures) => failures + 1,
+            Circuit::Open(_) => return false,
+            Circuit::HalfOpen(_) => self.threshold,
+        };
+        if failures < self.threshold {
+            *circuit = Circuit::Closed(failures);
+            return false;
+        }
+        *circuit = Circuit::Open(now + self.cooldown);
+        true
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Circuit> {
+        self.circuit.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The HTTP client every request to the platform services goes through.
+///
+/// Connections are pooled across requests. Each attempt is bounded by a timeout, and attempts
+/// that fail to connect, time out or are answered with a server error are retried with a
+/// jittered, growing delay. Each upstream has its own circuit breaker.
+pub struct HttpService {
+    client: Client,
+    policy: HttpPolicy,
+    breakers: [CircuitBreaker; 3], // One per upstream, in the order of `Upstream::ALL`.
+}
+
+impl Default for HttpService {
+    fn default() -> Self {
+        Self::new(HttpPolicy::default())
+    }
+}
+
+impl HttpService {
+    pub fn new(policy: HttpPolicy) -> Self {
+        let client = Client::builder()
+            .timeout(policy.timeout)
+            .pool_idle_timeout(Duration::from_secs(90))
+            .build()
+            .unwrap_or_default();
+        Self {
+            client,
+            policy,
+            breakers: Upstream::ALL
+                .map(|_| CircuitBreaker::new(policy.breaker_threshold, policy.breaker_cooldown)),
+        }
+    }
+
+    /// The pooled client, for requests that handle failures on their own.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Sends a request to an upstream, retrying it as the policy allows.
+    ///
+    /// # Arguments
+    /// * `upstream` - The service the request is sent to.
+    /// * `request` - Builds the request from the pooled client, once per attempt.
+    ///
+    /// # Returns
+    /// * `Ok(Response)` - The first response that is not a server error.
+    /// * `Err(HttpError)` - If the circuit of the upstream is open or every attempt failed.
+    pub async fn send<F>(&self, upstream: Upstream, request: F) -> Result<Response, HttpError>
+    where
+        F: Fn(&Client) -> RequestBuilder,
+    {
+        let breaker = self.breaker(upstream);
+        if !breaker.allow() {
+            return Err(HttpError::CircuitOpen(upstream));
+        }
+
+        let mut error = HttpError::CircuitOpen(upstream);
+        for attempt in 0..=self.policy.retries {
+            if attempt > 0 {
+                tokio::time::sleep(self.backoff(attempt)).await;
+            }
+
+            error = match request(&self.client).send().await {
+                Ok(response) if !response.status().is_server_error() => {
+                    breaker.record_success();
+                    return Ok(response);
+                }
+                Ok(response) => HttpError::ServerError(upstream, response.status().as_u16()),
+                Err(error) if error.is_timeout() => HttpError::Timeout(upstream),
+                Err(error) => HttpError::Unreachable(upstream, error.to_string()),
+            };
+            logger!(
+                WARN,
+                "[HTTP] Attempt {} of a {upstream} request failed: {error}",
+                attempt + 1
+            );
+        }
+
+        if breaker.record_failure() {
+            logger!(
+                ERROR,
+                "[HTTP] Circuit of the {upstream} service opened for {:?}",
+                self.policy.breaker_cooldown
+            );
+        }
+        Err(error)
+    }
+
+    /// The delay before a retry: between half and all of the backoff doubled for every attempt.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.policy.backoff * 2u32.pow(attempt.saturating_sub(1).min(16));
+        delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+
+    fn breaker(&self, upstream: Upstream) -> &CircuitBreaker {
+        let index = Upstream::ALL
+            .iter()
+            .position(|known| *known == upstream)
+            .unwrap_or_default();
+        &self.breakers[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaker_opens_and_lets_a_trial_through() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+        let now = Instant::now();
+        assert!(!breaker.record_failure_at(now));
+        breaker.record_success();
+        assert!(!breaker.record_failure_at(now));
+        assert!(breaker.record_failure_at(now));
+        assert!(!breaker.allow_at(now));
+
+        let later = now + Duration::from_secs(30);
+        assert!(breaker.allow_at(later));
+        assert!(!breaker.allow_at(later));
+        assert!(breaker.record_failure_at(later));
+        assert!(!breaker.allow_at(later + Duration::from_secs(1)));
+
+        assert!(breaker.allow_at(later + Duration::from_secs(30)));
+        breaker.record_success();
+        assert!(breaker.allow_at(later + Duration::from_secs(30)));
+    }
+
+    #[tokio::test]
+    async fn test_open_circuit_refuses_requests() {
+        let service = HttpService::new(HttpPolicy {
+            timeout: Duration::from_millis(200),
+            retries: 1,
+            backoff: Duration::from_millis(1),
+            breaker_threshold: 1,
+            breaker_cooldown: Duration::from_secs(30),
+        });
+
+        // Nothing listens on the discard port of localhost.
+        let request = |client: &Client| client.get("http://127.0.0.1:9/api/card/wolf");
+        let first = service.send(Upstream::Card, request).await;
+        assert!(matches!(
+            first,
+            Err(HttpError::Unreachable(Upstream::Card, _))
+        ));
+        assert!(matches!(
+            service.send(Upstream::Card, request).await,
+            Err(HttpError::CircuitOpen(Upstream::Card))
+        ));
+        assert!(service.breaker(Upstream::Deck).allow());
+    }
+}
@@ -0,0 +1,131 @@
+use crate::models::exit_code::ExitStatus;
+use crate::{logger, utils::logger::Logger};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::watch;
+
+type HookFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Work run once when the process is about to exit.
+struct ShutdownHook {
+    name: &'static str,
+    run: Box<dyn FnOnce() -> HookFuture + Send>,
+}
+
+/// How the process ends: the first exit status set wins, then the shutdown hooks run in the order
+/// they were registered and the process exits with the code of the status, so the orchestrator
+/// that spawned the server can tell a finished match from a failure.
+pub struct Lifecycle {
+    status: watch::Sender<Option<ExitStatus>>, // Set once, when the match ends or a fatal error occurs.
+    hooks: Mutex<Vec<ShutdownHook>>,           // Run in registration order on shutdown.
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self {
+            status: watch::Sender::new(None),
+            hooks: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl Lifecycle {
+    /// Registers work to run before the process exits, such as reporting the result of the match
+    /// or flushing the replay.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the hook, for the logs.
+    /// * `hook` - Builds the future run on shutdown.
+    pub fn on_shutdown<F, Fut>(&self, name: &'static str, hook: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let run: Box<dyn FnOnce() -> HookFuture + Send> = Box::new(move || Box::pin(hook()));
+        self.hooks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(ShutdownHook { name, run });
+    }
+
+    /// Sets the exit status of the process.
+    ///
+    /// # Returns
+    /// `false` if an exit status was already set; the first one is kept.
+    pub fn exit(&self, status: ExitStatus) -> bool {
+        self.status.send_if_modified(|current| match current {
+            Some(_) => false,
+            None => {
+                *current = Some(status);
+                true
+            }
+        })
+    }
+
+    /// The exit status, if one was set.
+    pub fn status(&self) -> Option<ExitStatus> {
+        self.status.borrow().clone()
+    }
+
+    /// Waits until an exit status is set.
+    pub async fn wait(&self) -> ExitStatus {
+        let mut receiver = self.status.subscribe();
+        let status = receiver.wait_for(Option::is_some).await;
+        match status {
+            Ok(status) => status.clone().unwrap_or_default(),
+            Err(_) => ExitStatus::default(),
+        }
+    }
+
+    /// Runs every registered hook once, each for at most `timeout`, so a hung hook cannot keep
+    /// the process alive.
+    pub async fn shutdown(&self, timeout: Duration) {
+        let hooks = std::mem::take(&mut *self.hooks.lock().unwrap_or_else(|e| e.into_inner()));
+        for hook in hooks {
+            logger!(
+                DEBUG,
+                "[LIFECYCLE] Running the `{}` shutdown hook",
+                hook.name
+            );
+            if tokio::time::timeout(timeout, (hook.run)()).await.is_err() {
+                logger!(
+                    WARN,
+                    "[LIFECYCLE] The `{}` shutdown hook did not finish within {}ms",
+                    hook.name,
+                    timeout.as_millis()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_first_status_wins_and_hooks_run_in_order() {
+        let lifecycle = Lifecycle::default();
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        for name in ["report", "replay"] {
+            let ran = Arc::clone(&ran);
+            lifecycle.on_shutdown(name, move || async move {
+                ran.lock().unwrap().push(name);
+            });
+        }
+        lifecycle.on_shutdown("hung", std::future::pending);
+
+        assert!(lifecycle.exit(ExitStatus {
+            code: 20,
+            reason: "no show".to_string(),
+        }));
+        assert!(!lifecycle.exit(ExitStatus::default()));
+        assert_eq!(20, lifecycle.wait().await.code);
+
+        lifecycle.shutdown(Duration::from_millis(10)).await;
+        assert_eq!(vec!["report", "replay"], *ran.lock().unwrap());
+    }
+}
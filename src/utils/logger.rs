@@ -1,30 +1,127 @@
-use std::fmt::Arguments;
-use chrono::Local;
+use std::fmt::{Arguments, Display};
+use serde::Deserialize;
+use tracing::{Level, Metadata, Span, Subscriber};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::fmt::time::ChronoLocal;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::Layer;
+use crate::utils::runtime_flags::LogLevel;
+use crate::RUNTIME_FLAGS;
 
+/// How log lines are written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One human-readable line per event, prefixed with the spans it happened in.
+    #[default]
+    Text,
+    /// One JSON object per event, with the fields of its spans, for log aggregators.
+    Json,
+}
+
+/// Forwards the `logger!` call sites to `tracing`, so their lines carry the fields of the
+/// enclosing spans (match, player, packet).
 pub struct Logger;
 
 impl Logger {
     pub fn info(args: Arguments) {
-        let local = Local::now().format("%d/%m/%Y %H:%M:%S");
-        println!("[INFO ] [{local}] {args}");
+        tracing::info!("{args}");
     }
 
     pub fn debug(args: Arguments) {
-        let local = Local::now().format("%d/%m/%Y %H:%M:%S");
-        println!("[DEBUG] [{local}] {args}");
+        tracing::debug!("{args}");
     }
 
     pub fn warn(args: Arguments) {
-        let local = Local::now().format("%d/%m/%Y %H:%M:%S");
-        eprintln!("[WARN ] [{local}] {args}");
+        tracing::warn!("{args}");
     }
 
     pub fn error(args: Arguments) {
-        let local = Local::now().format("%d/%m/%Y %H:%M:%S");
-        eprintln!("[ERROR] [{local}] {args}");
+        tracing::error!("{args}");
+    }
+}
+
+/// Installs the global subscriber: info and debug lines go to the standard output, warnings and
+/// errors to the standard error.
+///
+/// # Arguments
+/// * `level` - The initial runtime log level; the admin `log-level` command changes it later.
+/// * `format` - Whether lines are written as text or JSON.
+pub fn init(level: LogLevel, format: LogFormat) {
+    RUNTIME_FLAGS.set_log_level(level);
+    let writer = std::io::stderr
+        .with_max_level(Level::WARN)
+        .or_else(std::io::stdout);
+    if tracing::subscriber::set_global_default(subscriber(format, writer)).is_err() {
+        Logger::warn(format_args!(
+            "[LOGGER] A log subscriber was already installed"
+        ));
+    }
+}
+
+/// Builds the subscriber writing the events enabled by the runtime log level.
+fn subscriber<W>(format: LogFormat, writer: W) -> impl Subscriber + Send + Sync
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .with_writer(writer)
+            .with_ansi(false)
+            .with_target(false)
+            .with_timer(ChronoLocal::new(String::from("%d/%m/%Y %H:%M:%S")))
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_writer(writer)
+            .with_target(false)
+            .with_current_span(false)
+            .with_span_list(true)
+            .boxed(),
+    };
+    tracing_subscriber::registry().with(layer.with_filter(filter_fn(enabled)))
+}
+
+/// Whether an event or span is written: the events of the server follow the runtime log level,
+/// while the libraries only get their warnings and errors through.
+///
+/// The spans of the server are always kept, since a span skipped at creation would be missing
+/// from the context of its events after the log level is lowered.
+fn enabled(metadata: &Metadata) -> bool {
+    let own = metadata.target().starts_with(env!("CARGO_CRATE_NAME"));
+    if metadata.is_span() {
+        return own;
+    }
+
+    let level = match *metadata.level() {
+        Level::ERROR => LogLevel::Error,
+        Level::WARN => LogLevel::Warn,
+        Level::INFO => LogLevel::Info,
+        _ => LogLevel::Debug,
+    };
+    match own {
+        true => RUNTIME_FLAGS.logs(level),
+        false => level >= LogLevel::Warn,
     }
 }
 
+/// The span of the match hosted by the server.
+pub fn match_span(match_id: impl Display) -> Span {
+    tracing::info_span!("match", match_id = %match_id)
+}
+
+/// The span of the connection of a player.
+pub fn player_span(player_id: impl Display) -> Span {
+    tracing::info_span!("player", player_id = %player_id)
+}
+
+/// The span of the handling of a packet.
+pub fn packet_span(header_type: impl Display) -> Span {
+    tracing::debug_span!("packet", packet_type = %header_type)
+}
+
 #[macro_export]
 macro_rules! logger {
     (INFO, $($arg:tt)*) => {
@@ -39,4 +136,45 @@ macro_rules! logger {
     (ERROR, $($arg:tt)*) => {
         Logger::error(format_args!($($arg)*))
     };
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_lines_carry_the_span_fields() {
+        let captured = Captured::default();
+        let writer = {
+            let captured = captured.clone();
+            move || captured.clone()
+        };
+
+        tracing::subscriber::with_default(subscriber(LogFormat::Json, writer), || {
+            let _match = match_span("match-1").entered();
+            let _player = player_span("red").entered();
+            logger!(INFO, "[PROTOCOL] Player is ready");
+        });
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let line: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!("[PROTOCOL] Player is ready", line["fields"]["message"]);
+        assert_eq!("match-1", line["spans"][0]["match_id"]);
+        assert_eq!("red", line["spans"][1]["player_id"]);
+    }
+}
@@ -0,0 +1,359 @@
+use crate::models::ids::MatchId;
+use crate::utils::profiler::ProfileReport;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds, in seconds, of the buckets of the latency histograms.
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+/// Execution statistics for the handler of one header type.
+#[derive(Debug, Default, Clone)]
+pub struct HandlerStats {
+    pub calls: u64,
+    pub slow_calls: u64,
+    pub total_micros: u64,
+    pub max_micros: u64,
+}
+
+impl HandlerStats {
+    pub fn average_micros(&self) -> u64 {
+        self.total_micros.checked_div(self.calls).unwrap_or(0)
+    }
+}
+
+/// Distribution of durations over `LATENCY_BUCKETS`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()], // Samples at most each bound, not cumulated.
+    count: u64,                            // Samples, the ones above every bound included.
+    sum_micros: u64,                       // Sum of the samples.
+}
+
+impl Histogram {
+    pub fn observe(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum_micros += elapsed.as_micros() as u64;
+    }
+
+    /// Writes the histogram in the Prometheus text format.
+    ///
+    /// # Arguments
+    /// * `out` - The exposition being written.
+    /// * `name` - The metric name, without the `_bucket`, `_sum` and `_count` suffixes.
+    /// * `labels` - Labels of the series, such as `header_type="PlayCard"`, or an empty string.
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut cumulated = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(self.buckets) {
+            cumulated += count;
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{{labels}{separator}le=\"{bound}\"}} {cumulated}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{{labels}{separator}le=\"+Inf\"}} {}",
+            self.count
+        );
+
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{labels}}}")
+        };
+        let _ = writeln!(
+            out,
+            "{name}_sum{labels} {}",
+            self.sum_micros as f64 / 1_000_000.0
+        );
+        let _ = writeln!(out, "{name}_count{labels} {}", self.count);
+    }
+}
+
+/// Whether a packet was read from or written to a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PacketDirection {
+    Received,
+    Sent,
+}
+
+/// Values read from the server when the metrics are scraped.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MatchGauges {
+    pub connected_clients: usize, // Players whose connection is open.
+    pub missed_packets: usize,    // Packets queued for disconnected players, over every player.
+    pub outbound_queued: usize,   // Packets waiting in the outbound queues, over every player.
+}
+
+/// Bytes exchanged with the clients of a match.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BandwidthTotals {
+    pub sent: u64,
+    pub received: u64,
+}
+
+/// In-process metrics registry shared by the whole server.
+#[derive(Default)]
+pub struct Metrics {
+    handlers: Mutex<HashMap<String, HandlerStats>>,
+    match_profiles: Mutex<HashMap<MatchId, ProfileReport>>,
+    bandwidth: Mutex<HashMap<MatchId, BandwidthTotals>>,
+    packets: Mutex<BTreeMap<(PacketDirection, String), u64>>,
+    handler_latency: Mutex<BTreeMap<String, Histogram>>,
+    lua_calls: Mutex<Histogram>,
+    outbound_dropped: AtomicU64,
+    card_cache_hits: AtomicU64,
+    card_cache_misses: AtomicU64,
+}
+
+impl Metrics {
+    /// Records one execution of the handler for `header_type`.
+    ///
+    /// # Arguments
+    /// * `header_type` - The header type the handled packet had.
+    /// * `elapsed` - How long the handler took.
+    /// * `slow` - Whether the execution crossed the slow-handler threshold.
+    ///
+    /// # Returns
+    /// The updated statistics for that header type.
+    pub fn record_handler(&self, header_type: &str, elapsed: Duration, slow: bool) -> HandlerStats {
+        let micros = elapsed.as_micros() as u64;
+        let mut handlers = self.handlers.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = handlers.entry(header_type.to_string()).or_default();
+
+        stats.calls += 1;
+        stats.total_micros += micros;
+        stats.max_micros = stats.max_micros.max(micros);
+        if slow {
+            stats.slow_calls += 1;
+        }
+
+        let mut latency = self
+            .handler_latency
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        latency
+            .entry(header_type.to_string())
+            .or_default()
+            .observe(elapsed);
+
+        stats.clone()
+    }
+
+    /// Counts a packet read from or written to a connection.
+    pub fn record_packet(&self, direction: PacketDirection, header_type: &str) {
+        let mut packets = self.packets.lock().unwrap_or_else(|e| e.into_inner());
+        *packets
+            .entry((direction, header_type.to_string()))
+            .or_default() += 1;
+    }
+
+    /// Counts a state packet dropped from the full outbound queue of a slow client.
+    pub fn record_outbound_drop(&self) {
+        self.outbound_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts card definitions served from the card catalog and fetched from the card server.
+    pub fn record_card_cache(&self, hits: u64, misses: u64) {
+        self.card_cache_hits.fetch_add(hits, Ordering::Relaxed);
+        self.card_cache_misses.fetch_add(misses, Ordering::Relaxed);
+    }
+
+    /// Records the duration of one call into a card script.
+    pub fn record_lua_call(&self, elapsed: Duration) {
+        let mut lua_calls = self.lua_calls.lock().unwrap_or_else(|e| e.into_inner());
+        lua_calls.observe(elapsed);
+    }
+
+    /// Writes every metric in the Prometheus text exposition format.
+    ///
+    /// # Arguments
+    /// * `gauges` - The current values read from the server.
+    pub fn render(&self, gauges: MatchGauges) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP ccg_packets_total Packets read from and written to the clients.\n");
+        out.push_str("# TYPE ccg_packets_total counter\n");
+        for ((direction, header_type), count) in self
+            .packets
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+        {
+            let direction = match direction {
+                PacketDirection::Received => "received",
+                PacketDirection::Sent => "sent",
+            };
+            let _ = writeln!(
+                out,
+                "ccg_packets_total{{direction=\"{direction}\",header_type=\"{header_type}\"}} {count}"
+            );
+        }
+
+        out.push_str("# HELP ccg_handler_duration_seconds Time taken to handle a packet.\n");
+        out.push_str("# TYPE ccg_handler_duration_seconds histogram\n");
+        for (header_type, histogram) in self
+            .handler_latency
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+        {
+            let labels = format!("header_type=\"{header_type}\"");
+            histogram.render(&mut out, "ccg_handler_duration_seconds", &labels);
+        }
+
+        out.push_str("# HELP ccg_lua_call_duration_seconds Time taken by a card script call.\n");
+        out.push_str("# TYPE ccg_lua_call_duration_seconds histogram\n");
+        self.lua_calls
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .render(&mut out, "ccg_lua_call_duration_seconds", "");
+
+        out.push_str("# HELP ccg_connected_clients Players whose connection is open.\n");
+        out.push_str("# TYPE ccg_connected_clients gauge\n");
+        let _ = writeln!(out, "ccg_connected_clients {}", gauges.connected_clients);
+
+        out.push_str("# HELP ccg_missed_packets Packets queued for disconnected players.\n");
+        out.push_str("# TYPE ccg_missed_packets gauge\n");
+        let _ = writeln!(out, "ccg_missed_packets {}", gauges.missed_packets);
+
+        out.push_str(
+            "# HELP ccg_outbound_queue_depth Packets waiting to be written to the players.\n",
+        );
+        out.push_str("# TYPE ccg_outbound_queue_depth gauge\n");
+        let _ = writeln!(out, "ccg_outbound_queue_depth {}", gauges.outbound_queued);
+
+        out.push_str(
+            "# HELP ccg_outbound_dropped_total State packets dropped from full outbound queues.\n",
+        );
+        out.push_str("# TYPE ccg_outbound_dropped_total counter\n");
+        let _ = writeln!(
+            out,
+            "ccg_outbound_dropped_total {}",
+            self.outbound_dropped.load(Ordering::Relaxed)
+        );
+
+        out.push_str(
+            "# HELP ccg_card_cache_requests_total Card definitions looked up in the card catalog.\n",
+        );
+        out.push_str("# TYPE ccg_card_cache_requests_total counter\n");
+        let _ = writeln!(
+            out,
+            "ccg_card_cache_requests_total{{result=\"hit\"}} {}",
+            self.card_cache_hits.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "ccg_card_cache_requests_total{{result=\"miss\"}} {}",
+            self.card_cache_misses.load(Ordering::Relaxed)
+        );
+
+        out
+    }
+
+    /// Keeps the performance report of a match that ended.
+    pub fn record_match_profile(&self, match_id: &MatchId, report: ProfileReport) {
+        let mut profiles = self
+            .match_profiles
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        profiles.insert(match_id.clone(), report);
+    }
+
+    /// Adds bytes sent to and received from a client of a match to the match totals.
+    pub fn record_bandwidth(&self, match_id: &MatchId, sent: u64, received: u64) {
+        let mut bandwidth = self.bandwidth.lock().unwrap_or_else(|e| e.into_inner());
+        let totals = bandwidth.entry(match_id.clone()).or_default();
+        totals.sent += sent;
+        totals.received += received;
+    }
+
+    /// The bytes exchanged with the clients of a match so far.
+    pub fn match_bandwidth(&self, match_id: &MatchId) -> BandwidthTotals {
+        let bandwidth = self.bandwidth.lock().unwrap_or_else(|e| e.into_inner());
+        bandwidth.get(match_id).copied().unwrap_or_default()
+    }
+
+    /// Drops the performance report and bandwidth totals of a closed match, so a process hosting
+    /// many matches does not keep them forever.
+    pub fn forget_match(&self, match_id: &MatchId) {
+        self.match_profiles
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(match_id);
+        self.bandwidth
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(match_id);
+    }
+
+    /// The performance report of a match that ended, if any.
+    pub fn match_profile(&self, match_id: &MatchId) -> Option<ProfileReport> {
+        let profiles = self
+            .match_profiles
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        profiles.get(match_id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prometheus_exposition() {
+        let metrics = Metrics::default();
+        metrics.record_packet(PacketDirection::Received, "PlayCard");
+        metrics.record_packet(PacketDirection::Received, "PlayCard");
+        metrics.record_packet(PacketDirection::Sent, "GameState");
+        metrics.record_handler("PlayCard", Duration::from_millis(3), false);
+        metrics.record_lua_call(Duration::from_secs(5));
+        metrics.record_card_cache(3, 1);
+
+        let gauges = MatchGauges {
+            connected_clients: 2,
+            missed_packets: 4,
+            outbound_queued: 1,
+        };
+        let out = metrics.render(gauges);
+        assert!(out.contains(r#"ccg_packets_total{direction="received",header_type="PlayCard"} 2"#));
+        assert!(out.contains(r#"ccg_packets_total{direction="sent",header_type="GameState"} 1"#));
+        assert!(out.contains(
+            r#"ccg_handler_duration_seconds_bucket{header_type="PlayCard",le="0.0025"} 0"#
+        ));
+        assert!(out.contains(
+            r#"ccg_handler_duration_seconds_bucket{header_type="PlayCard",le="0.005"} 1"#
+        ));
+        assert!(out.contains(r#"ccg_handler_duration_seconds_count{header_type="PlayCard"} 1"#));
+        assert!(out.contains(r#"ccg_lua_call_duration_seconds_bucket{le="2.5"} 0"#));
+        assert!(out.contains(r#"ccg_lua_call_duration_seconds_bucket{le="+Inf"} 1"#));
+        assert!(out.contains("ccg_lua_call_duration_seconds_sum 5\n"));
+        assert!(out.contains("ccg_connected_clients 2\n"));
+        assert!(out.contains("ccg_missed_packets 4\n"));
+        assert!(out.contains("ccg_outbound_queue_depth 1\n"));
+        assert!(out.contains(r#"ccg_card_cache_requests_total{result="hit"} 3"#));
+    }
+
+    #[test]
+    fn test_closed_matches_are_forgotten() {
+        let metrics = Metrics::default();
+        let match_id: MatchId = "match-1".into();
+        metrics.record_bandwidth(&match_id, 100, 20);
+        metrics.record_match_profile(&match_id, ProfileReport::default());
+
+        metrics.forget_match(&match_id);
+        assert_eq!(0, metrics.match_bandwidth(&match_id).sent);
+        assert!(metrics.match_profile(&match_id).is_none());
+    }
+}
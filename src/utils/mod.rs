@@ -1,3 +1,33 @@
+pub mod artifacts;
+#[cfg(feature = "game")]
+pub mod bandwidth;
+pub mod canonical;
 pub mod checksum;
+pub mod compression;
+#[cfg(feature = "game")]
+pub mod connection_quality;
+pub mod dead_letter;
 pub mod errors;
+#[cfg(feature = "game")]
+pub mod http_service;
+#[cfg(feature = "game")]
+pub mod lifecycle;
 pub mod logger;
+#[cfg(feature = "game")]
+pub mod metrics;
+#[cfg(feature = "game")]
+pub mod profanity;
+#[cfg(feature = "game")]
+pub mod profiler;
+pub mod ready;
+#[cfg(feature = "game")]
+pub mod rate_limit;
+#[cfg(feature = "game")]
+pub mod replay;
+#[cfg(feature = "game")]
+pub mod result_reporter;
+#[cfg(feature = "game")]
+pub mod runtime_config;
+pub mod runtime_flags;
+#[cfg(feature = "game")]
+pub mod socket;
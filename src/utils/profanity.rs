@@ -0,0 +1,85 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Cleans up chat messages before they are relayed.
+pub trait ProfanityFilter: Send + Sync {
+    /// The message as it may be relayed, or `None` if it may not be relayed at all.
+    fn clean(&self, message: &str) -> Option<String>;
+}
+
+/// Masks the words of a word list with asterisks, ignoring case.
+///
+/// Words are runs of letters and digits, so `darn!` and `DARN` are both masked when `darn` is
+/// listed, but `darned` is not.
+#[derive(Debug, Default)]
+pub struct WordListFilter {
+    words: HashSet<String>, // Lowercase words to mask.
+}
+
+impl WordListFilter {
+    pub fn new<I: IntoIterator<Item = S>, S: AsRef<str>>(words: I) -> Self {
+        Self {
+            words: words
+                .into_iter()
+                .map(|word| word.as_ref().trim().to_lowercase())
+                .filter(|word| !word.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Reads a word list with one word per line; lines starting with `#` are comments.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(Self::new(
+            content
+                .lines()
+                .filter(|line| !line.trim_start().starts_with('#')),
+        ))
+    }
+
+    /// Appends a word to the cleaned message, masked if it is listed.
+    fn push_word(&self, cleaned: &mut String, word: &str) {
+        match self.words.contains(&word.to_lowercase()) {
+            true => cleaned.extend(word.chars().map(|_| '*')),
+            false => cleaned.push_str(word),
+        }
+    }
+}
+
+impl ProfanityFilter for WordListFilter {
+    fn clean(&self, message: &str) -> Option<String> {
+        let mut cleaned = String::with_capacity(message.len());
+        let mut word_start = None;
+        for (index, character) in message.char_indices() {
+            match (character.is_alphanumeric(), word_start) {
+                (true, None) => word_start = Some(index),
+                (false, Some(start)) => {
+                    self.push_word(&mut cleaned, &message[start..index]);
+                    word_start = None;
+                    cleaned.push(character);
+                }
+                (false, None) => cleaned.push(character),
+                (true, Some(_)) => {}
+            }
+        }
+        if let Some(start) = word_start {
+            self.push_word(&mut cleaned, &message[start..]);
+        }
+        Some(cleaned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listed_words_are_masked() {
+        let filter = WordListFilter::new(["darn", " Heck ", ""]);
+        assert_eq!(
+            Some(String::from("**** it, what the ****! darned")),
+            filter.clean("darn it, what the HECK! darned")
+        );
+        assert_eq!(Some(String::from("gg wp")), filter.clean("gg wp"));
+    }
+}
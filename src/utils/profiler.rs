@@ -0,0 +1,154 @@
+use crate::game::think_time::TurnThinkTime;
+use crate::models::ids::PlayerId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Collects the timings of one match, summarized into a `ProfileReport` when the match ends.
+#[derive(Default)]
+pub struct MatchProfiler {
+    samples: Mutex<ProfileSamples>,
+}
+
+#[derive(Default)]
+struct ProfileSamples {
+    actions: Vec<u64>,
+    lua_micros: u64,
+    serialization_micros: u64,
+    bytes_sent: HashMap<PlayerId, u64>,
+}
+
+/// Per-match performance summary, written to the match artifacts and kept in the metrics.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ProfileReport {
+    pub actions: usize,
+    pub average_micros: u64,
+    pub p50_micros: u64,
+    pub p95_micros: u64,
+    pub p99_micros: u64,
+    pub max_micros: u64,
+    pub lua_micros: u64,
+    /// Fraction of the action resolution time spent running Lua scripts.
+    pub lua_share: f64,
+    /// Time spent serializing outgoing payloads and computing their checksums.
+    pub serialization_micros: u64,
+    pub bytes_sent: HashMap<PlayerId, u64>,
+    /// Time each player took on each of their turns, unless `THINK_TIME_ANALYTICS` is disabled.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub think_time: Vec<TurnThinkTime>,
+}
+
+impl MatchProfiler {
+    /// Records the resolution of one player action, from request to outcome.
+    pub fn record_action(&self, elapsed: Duration) {
+        self.samples().actions.push(elapsed.as_micros() as u64);
+    }
+
+    /// Records time spent inside a Lua script call.
+    pub fn record_lua(&self, elapsed: Duration) {
+        self.samples().lua_micros += elapsed.as_micros() as u64;
+    }
+
+    /// Records time spent serializing an outgoing payload or computing its checksum.
+    pub fn record_serialization(&self, elapsed: Duration) {
+        self.samples().serialization_micros += elapsed.as_micros() as u64;
+    }
+
+    /// Records the bytes written to a player's connection.
+    pub fn record_bytes_sent(&self, player_id: &PlayerId, bytes: usize) {
+        *self
+            .samples()
+            .bytes_sent
+            .entry(player_id.clone())
+            .or_default() += bytes as u64;
+    }
+
+    /// Summarizes everything recorded so far.
+    pub fn report(&self) -> ProfileReport {
+        let samples = self.samples();
+        let mut actions = samples.actions.clone();
+        actions.sort_unstable();
+
+        let total: u64 = actions.iter().sum();
+        ProfileReport {
+            actions: actions.len(),
+            average_micros: total.checked_div(actions.len() as u64).unwrap_or(0),
+            p50_micros: percentile(&actions, 50),
+            p95_micros: percentile(&actions, 95),
+            p99_micros: percentile(&actions, 99),
+            max_micros: actions.last().copied().unwrap_or(0),
+            lua_micros: samples.lua_micros,
+            lua_share: match total {
+                0 => 0.0,
+                total => samples.lua_micros as f64 / total as f64,
+            },
+            serialization_micros: samples.serialization_micros,
+            bytes_sent: samples.bytes_sent.clone(),
+            think_time: Vec::new(),
+        }
+    }
+
+    fn samples(&self) -> std::sync::MutexGuard<'_, ProfileSamples> {
+        self.samples.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Nearest-rank percentile of sorted samples.
+fn percentile(sorted: &[u64], percent: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+
+    let rank = (percent * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+impl Display for ProfileReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{{ actions: {}, avg_us: {}, p50_us: {}, p95_us: {}, p99_us: {}, max_us: {}, lua_share: {:.1}%, serialization_us: {}, bytes_sent: {} }}",
+            self.actions,
+            self.average_micros,
+            self.p50_micros,
+            self.p95_micros,
+            self.p99_micros,
+            self.max_micros,
+            self.lua_share * 100.0,
+            self.serialization_micros,
+            self.bytes_sent.values().sum::<u64>()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_summarizes_samples() {
+        let profiler = MatchProfiler::default();
+        for millis in 1..=100 {
+            profiler.record_action(Duration::from_millis(millis));
+        }
+        profiler.record_lua(Duration::from_millis(1010));
+        profiler.record_bytes_sent(&"red".into(), 300);
+        profiler.record_bytes_sent(&"red".into(), 200);
+
+        let report = profiler.report();
+        assert_eq!(100, report.actions);
+        assert_eq!(50_500, report.average_micros);
+        assert_eq!(50_000, report.p50_micros);
+        assert_eq!(95_000, report.p95_micros);
+        assert_eq!(100_000, report.max_micros);
+        assert!((report.lua_share - 0.2).abs() < 1e-9);
+        assert_eq!(Some(&500), report.bytes_sent.get(&"red".into()));
+    }
+
+    #[test]
+    fn test_empty_report() {
+        assert_eq!(ProfileReport::default(), MatchProfiler::default().report());
+    }
+}
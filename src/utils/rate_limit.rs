@@ -0,0 +1,85 @@
+use crate::models::ids::PlayerId;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The allowance left to a player.
+struct Bucket {
+    tokens: f64,       // Requests the player may still make right away.
+    refilled: Instant, // When tokens were last added.
+}
+
+/// Limits how often each player may make a request: up to `capacity` requests at once, refilled
+/// evenly over `window`.
+///
+/// The limit is given with each request rather than kept, so it can be tuned while the server
+/// runs; the allowance players have left carries over.
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<PlayerId, Bucket>>,
+}
+
+impl RateLimiter {
+    /// Counts a request of a player.
+    ///
+    /// # Arguments
+    /// * `capacity` - Requests a player may make at once.
+    /// * `window` - Time over which the allowance of a player refills entirely.
+    ///
+    /// # Returns
+    /// `false` if the player has used up their allowance, in which case the request is not counted.
+    pub fn try_acquire(&self, player_id: &PlayerId, capacity: u32, window: Duration) -> bool {
+        self.try_acquire_at(player_id, capacity, window, Instant::now())
+    }
+
+    fn try_acquire_at(
+        &self,
+        player_id: &PlayerId,
+        capacity: u32,
+        window: Duration,
+        now: Instant,
+    ) -> bool {
+        let capacity = capacity as f64;
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let bucket = buckets.entry(player_id.clone()).or_insert(Bucket {
+            tokens: capacity,
+            refilled: now,
+        });
+
+        if !window.is_zero() {
+            let elapsed = now.saturating_duration_since(bucket.refilled);
+            let refill = capacity * elapsed.as_secs_f64() / window.as_secs_f64();
+            bucket.tokens = (bucket.tokens + refill).min(capacity);
+        }
+        bucket.refilled = now;
+
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowance_refills_over_the_window() {
+        let (red, blue) = (PlayerId::from("red"), PlayerId::from("blue"));
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let limiter = RateLimiter::default();
+        let acquire = |player_id, millis| {
+            limiter.try_acquire_at(player_id, 2, Duration::from_secs(10), at(millis))
+        };
+
+        assert!(acquire(&red, 0));
+        assert!(acquire(&red, 0));
+        assert!(!acquire(&red, 1_000));
+        assert!(acquire(&blue, 1_000));
+        assert!(acquire(&red, 5_000));
+        assert!(!acquire(&red, 6_000));
+    }
+}
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::Path;
+
+/// The readiness line printed once the server is bound and waiting for `InitServer`.
+///
+/// Process supervisors and test harnesses wait for this line (or the ready file) instead of
+/// sleeping and hoping the port is open.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReadySignal {
+    pub status: String,             // Always `ready`, so the line is easy to pick out of the logs.
+    pub port: u16,                  // Port of the first address the server bound.
+    #[serde(default)]
+    pub addresses: Vec<SocketAddr>, // Every address the server listens on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quic_addresses: Vec<SocketAddr>, // Every address the server accepts QUIC clients on.
+    pub pid: u32,                   // Process id of the server.
+    pub version: String,            // Version of the server build.
+}
+
+impl ReadySignal {
+    pub fn new(addresses: &[SocketAddr]) -> Self {
+        Self {
+            status: String::from("ready"),
+            port: addresses.first().map_or(0, |address| address.port()),
+            addresses: addresses.to_vec(),
+            quic_addresses: Vec::new(),
+            pid: std::process::id(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// Adds the QUIC endpoints, whose port is announced when the server listens on no TCP address.
+    pub fn with_quic(mut self, quic_addresses: &[SocketAddr]) -> Self {
+        if self.addresses.is_empty() {
+            self.port = quic_addresses.first().map_or(0, |address| address.port());
+        }
+        self.quic_addresses = quic_addresses.to_vec();
+        self
+    }
+
+    /// Prints the signal as a single JSON line on the standard output and writes it to the ready
+    /// file, if one is configured.
+    ///
+    /// # Arguments
+    /// * `ready_file` - Path of the file to write once ready.
+    ///
+    /// # Returns
+    /// An error if the line could not be printed or the ready file could not be written.
+    pub fn announce(&self, ready_file: Option<&Path>) -> std::io::Result<()> {
+        let line = serde_json::to_string(self)?;
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{line}")?;
+        stdout.flush()?;
+
+        if let Some(path) = ready_file {
+            std::fs::write(path, format!("{line}\n"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Removes a ready file left behind by a previous run, so it never signals a server that is not
+/// up yet.
+pub fn clear_ready_file(ready_file: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(ready_file) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ready_file_holds_the_signal() {
+        let path = std::env::temp_dir().join(format!("ready-{}", uuid::Uuid::new_v4()));
+        let addresses = [
+            "127.0.0.1:8000".parse().unwrap(),
+            "[::1]:8001".parse().unwrap(),
+        ];
+        let signal = ReadySignal::new(&addresses);
+        signal.announce(Some(&path)).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(signal, serde_json::from_str(&content).unwrap());
+        assert_eq!(std::process::id(), signal.pid);
+        assert_eq!(8000, signal.port);
+
+        clear_ready_file(&path).unwrap();
+        assert!(!path.exists());
+        assert!(clear_ready_file(&path).is_ok());
+    }
+}
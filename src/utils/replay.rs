@@ -0,0 +1,287 @@
+use crate::game::rng::RandomDraw;
+use crate::models::game_action::GameAction;
+use crate::models::ids::{CardDefId, PlayerId};
+use crate::{logger, utils::logger::Logger};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+
+/// Something worth keeping to reconstruct a match.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind")]
+pub enum ReplayRecord {
+    /// An action packet received from a player, as it arrived.
+    Packet {
+        player_id: PlayerId,
+        header_type: u8,
+        sequence: u16,
+        payload: Vec<u8>,
+    },
+    /// A game action resolved by the game state, with the card whose script produced it.
+    Action {
+        source: CardDefId,
+        action: GameAction,
+    },
+    /// The seed of the match random number generator, recorded once when the match starts.
+    Seed { seed: u64 },
+    /// A random value drawn by a card script.
+    Draw { draw: RandomDraw },
+}
+
+/// A line of a replay file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplayEntry {
+    pub timestamp: i64, // Unix timestamp (milliseconds) of when the record was taken.
+    pub turn: u32,
+    #[serde(flatten)]
+    pub record: ReplayRecord,
+}
+
+/// Writes the replay of a match as JSON lines, in the background.
+///
+/// Recording only queues the entry, so the game never waits on the disk. A background task
+/// buffers the writes and starts a new file (`replay-0001.jsonl`, `replay-0002.jsonl`, ...) once
+/// the current one grows past the rotation size.
+///
+/// While an action batch resolves, records are held back by `hold` and only queued once the
+/// batch commits, so a rolled back batch leaves nothing in the replay.
+pub struct ReplayWriter {
+    sender: Mutex<Option<UnboundedSender<ReplayEntry>>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+    held: Mutex<Option<Vec<ReplayEntry>>>, // Records of the batch resolving, if one is.
+}
+
+impl ReplayWriter {
+    /// Starts writing a replay into `dir`.
+    ///
+    /// # Arguments
+    /// * `dir` - The directory receiving the replay files, usually the match artifact bundle.
+    /// * `rotate_size` - Size in bytes after which the next entries go to a new file.
+    pub fn start(dir: impl Into<PathBuf>, rotate_size: u64) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let task = tokio::spawn(write_entries(dir.into(), rotate_size, receiver));
+        Self {
+            sender: Mutex::new(Some(sender)),
+            task: Mutex::new(Some(task)),
+            held: Mutex::new(None),
+        }
+    }
+
+    /// Queues a record taken on the given turn, or holds it back while a batch resolves. Records
+    /// arriving after `finish` are dropped.
+    pub fn record(&self, turn: u32, record: ReplayRecord) {
+        let entry = ReplayEntry {
+            timestamp: Utc::now().timestamp_millis(),
+            turn,
+            record,
+        };
+        match self.held.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            Some(held) => held.push(entry),
+            None => self.send(entry),
+        }
+    }
+
+    /// Holds back the records taken from now on, until `release` or `discard`.
+    pub fn hold(&self) {
+        *self.held.lock().unwrap_or_else(|e| e.into_inner()) = Some(Vec::new());
+    }
+
+    /// Queues the records held back since `hold`, in the order they were taken.
+    pub fn release(&self) {
+        let held = self.held.lock().unwrap_or_else(|e| e.into_inner()).take();
+        for entry in held.into_iter().flatten() {
+            self.send(entry);
+        }
+    }
+
+    /// Drops the records held back since `hold`.
+    pub fn discard(&self) {
+        self.held.lock().unwrap_or_else(|e| e.into_inner()).take();
+    }
+
+    fn send(&self, entry: ReplayEntry) {
+        let sender = self.sender.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sender) = sender.as_ref() {
+            let _ = sender.send(entry);
+        }
+    }
+
+    /// Stops recording and waits for every queued entry to be written.
+    pub async fn finish(&self) {
+        self.sender.lock().unwrap_or_else(|e| e.into_inner()).take();
+        let task = self.task.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(task) = task {
+            let _ = task.await;
+        }
+    }
+}
+
+/// Name of the `index`-th file of a replay.
+fn replay_file(dir: &Path, index: u32) -> PathBuf {
+    dir.join(format!("replay-{index:04}.jsonl"))
+}
+
+/// Reads back the entries of a replay written into `dir`, in order.
+///
+/// Lines that cannot be parsed, such as the last one of a file still being written, are skipped.
+pub async fn read_replay(dir: &Path) -> std::io::Result<Vec<ReplayEntry>> {
+    let mut entries = Vec::new();
+    let mut index = 1;
+    loop {
+        let content = match tokio::fs::read_to_string(replay_file(dir, index)).await {
+            Ok(content) => content,
+            Err(error) if error.kind() == ErrorKind::NotFound && index > 1 => break,
+            Err(error) => return Err(error),
+        };
+        entries.extend(
+            content
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok()),
+        );
+        index += 1;
+    }
+    Ok(entries)
+}
+
+/// Writes the queued entries until the writer is finished.
+async fn write_entries(
+    dir: PathBuf,
+    rotate_size: u64,
+    mut receiver: UnboundedReceiver<ReplayEntry>,
+) {
+    if let Err(error) = tokio::fs::create_dir_all(&dir).await {
+        logger!(
+            ERROR,
+            "[REPLAY] Could not create `{}`: {error}",
+            dir.display()
+        );
+        return;
+    }
+
+    let mut index = 1;
+    let mut written = 0;
+    let mut file: Option<BufWriter<File>> = None;
+    while let Some(entry) = receiver.recv().await {
+        let mut line = match serde_json::to_vec(&entry) {
+            Ok(line) => line,
+            Err(error) => {
+                logger!(
+                    ERROR,
+                    "[REPLAY] Could not serialize a replay entry: {error}"
+                );
+                continue;
+            }
+        };
+        line.push(b'\n');
+
+        if written > 0 && written + line.len() as u64 > rotate_size {
+            if let Some(mut full) = file.take() {
+                let _ = full.flush().await;
+            }
+            index += 1;
+            written = 0;
+        }
+
+        if file.is_none() {
+            match File::create(replay_file(&dir, index)).await {
+                Ok(created) => file = Some(BufWriter::new(created)),
+                Err(error) => {
+                    logger!(ERROR, "[REPLAY] Could not create replay file: {error}");
+                    continue;
+                }
+            }
+        }
+
+        if let Some(writer) = file.as_mut() {
+            match writer.write_all(&line).await {
+                Ok(()) => written += line.len() as u64,
+                Err(error) => logger!(ERROR, "[REPLAY] Could not write replay entry: {error}"),
+            }
+
+            // Flush whenever the queue runs dry, so a crash loses as little as possible.
+            if receiver.is_empty() {
+                let _ = writer.flush().await;
+            }
+        }
+    }
+
+    if let Some(mut writer) = file {
+        let _ = writer.flush().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn damage(amount: u32) -> ReplayRecord {
+        ReplayRecord::Action {
+            source: "fireball".into(),
+            action: GameAction::DealDamage {
+                target: "blue".to_string(),
+                amount,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_rotates_and_keeps_every_entry() {
+        let dir = std::env::temp_dir().join(format!("replay-{}", uuid::Uuid::new_v4()));
+        let writer = ReplayWriter::start(&dir, 256);
+        for amount in 0..10 {
+            writer.record(1, damage(amount));
+        }
+        writer.finish().await;
+        writer.record(2, damage(99));
+
+        let mut entries = Vec::new();
+        let mut index = 1;
+        while let Ok(content) = std::fs::read_to_string(replay_file(&dir, index)) {
+            for line in content.lines() {
+                entries.push(serde_json::from_str::<ReplayEntry>(line).unwrap().record);
+            }
+            index += 1;
+        }
+
+        assert!(index > 2, "the replay should have been rotated");
+        assert_eq!((0..10).map(damage).collect::<Vec<_>>(), entries);
+        let read: Vec<_> = read_replay(&dir).await.unwrap();
+        assert_eq!(
+            entries,
+            read.into_iter()
+                .map(|entry| entry.record)
+                .collect::<Vec<_>>()
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_held_records_are_written_only_once_released() {
+        let dir = std::env::temp_dir().join(format!("replay-{}", uuid::Uuid::new_v4()));
+        let writer = ReplayWriter::start(&dir, 1 << 20);
+        writer.record(1, damage(1));
+        writer.hold();
+        writer.record(1, damage(2));
+        writer.discard();
+        writer.hold();
+        writer.record(1, damage(3));
+        writer.release();
+        writer.record(1, damage(4));
+        writer.finish().await;
+
+        let read: Vec<_> = read_replay(&dir).await.unwrap();
+        assert_eq!(
+            vec![damage(1), damage(3), damage(4)],
+            read.into_iter()
+                .map(|entry| entry.record)
+                .collect::<Vec<_>>()
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,147 @@
+use crate::models::match_report::MatchReport;
+use crate::utils::dead_letter::DeadLetterQueue;
+use crate::{logger, utils::logger::Logger, HTTP_SERVICE, SETTINGS};
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// Attempts made to deliver a report before it is moved to the dead-letter queue.
+const REPORT_ATTEMPTS: u32 = 3;
+
+/// Delivers match results to the platform.
+///
+/// Reports that cannot be delivered after `REPORT_ATTEMPTS` tries are persisted to a dead-letter
+/// queue and retried periodically, or on demand with the `flush-dead-letters` admin command.
+pub struct ResultReporter {
+    pub dead_letters: DeadLetterQueue,
+    flushing: Mutex<()>, // Held while flushing, so the lines read by one flush are not moved by another.
+}
+
+impl ResultReporter {
+    pub fn new(dead_letters: DeadLetterQueue) -> Self {
+        Self {
+            dead_letters,
+            flushing: Mutex::new(()),
+        }
+    }
+
+    /// Reports a match result, queueing it as a dead letter if every attempt fails.
+    ///
+    /// # Returns
+    /// `true` if the report was delivered.
+    pub async fn report(&self, report: &MatchReport) -> bool {
+        if SETTINGS.get().is_some_and(|s| s.result_server.is_none()) {
+            logger!(
+                WARN,
+                "[REPORTER] No RESULT_SERVER is set, match `{}` is not reported",
+                &report.match_id
+            );
+            return false;
+        }
+
+        if self.deliver(report).await {
+            return true;
+        }
+
+        logger!(
+            ERROR,
+            "[REPORTER] Could not report match `{}`, moving it to the dead-letter queue",
+            &report.match_id
+        );
+        if let Err(error) = self.dead_letters.push(report) {
+            logger!(
+                ERROR,
+                "[REPORTER] Could not persist the report of match `{}` ({error}): {}",
+                &report.match_id,
+                serde_json::to_string(report).unwrap_or_default()
+            );
+        }
+
+        false
+    }
+
+    /// Retries every report in the dead-letter queue once.
+    ///
+    /// Reports stay in the queue while they are retried, and the delivered ones are removed in a
+    /// single rewrite afterwards, so a crash during the flush loses none of them.
+    ///
+    /// # Returns
+    /// The number of reports delivered and the number still queued.
+    pub async fn flush_dead_letters(&self) -> (usize, usize) {
+        let _flushing = self.flushing.lock().await;
+        let reports = match self.dead_letters.peek::<MatchReport>() {
+            Ok(reports) => reports,
+            Err(error) => {
+                logger!(
+                    ERROR,
+                    "[REPORTER] Could not read the dead-letter queue ({error})"
+                );
+                return (0, self.dead_letters.len());
+            }
+        };
+
+        let mut delivered = Vec::new();
+        for (line, report) in reports {
+            if self.deliver(&report).await {
+                delivered.push(line);
+            }
+        }
+
+        if let Err(error) = self.dead_letters.remove(&delivered) {
+            logger!(
+                ERROR,
+                "[REPORTER] Could not remove {} delivered reports from the dead-letter queue ({error})",
+                delivered.len()
+            );
+        }
+        (delivered.len(), self.dead_letters.len())
+    }
+
+    /// Periodically flushes the dead-letter queue. Runs indefinitely.
+    pub async fn retry_dead_letters(&self) {
+        let interval = SETTINGS.get().map_or(300, |s| s.dead_letter_retry_interval);
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            if self.dead_letters.len() == 0 {
+                continue;
+            }
+
+            let (delivered, remaining) = self.flush_dead_letters().await;
+            logger!(
+                INFO,
+                "[REPORTER] Dead-letter retry delivered {delivered} reports, {remaining} remaining"
+            );
+        }
+    }
+
+    /// Sends a report to the platform, retrying with a growing delay.
+    async fn deliver(&self, report: &MatchReport) -> bool {
+        let Some(result_server) = SETTINGS.get().and_then(|s| s.result_server.as_ref()) else {
+            return false;
+        };
+
+        let api_url = format!("{result_server}/api/match/result");
+        let reqwest_client = HTTP_SERVICE.client();
+        for attempt in 0..REPORT_ATTEMPTS {
+            if attempt > 0 {
+                tokio::time::sleep(Duration::from_millis(500 * 2u64.pow(attempt))).await;
+            }
+
+            match reqwest_client.post(&api_url).json(report).send().await {
+                Ok(response) if response.status().is_success() => return true,
+                Ok(response) => logger!(
+                    WARN,
+                    "[REPORTER] Match `{}` report rejected with status {}",
+                    &report.match_id,
+                    response.status()
+                ),
+                Err(error) => logger!(
+                    WARN,
+                    "[REPORTER] Match `{}` report failed ({error})",
+                    &report.match_id
+                ),
+            }
+        }
+
+        false
+    }
+}
@@ -0,0 +1,287 @@
+use crate::models::settings::Settings;
+use crate::utils::runtime_flags::LogLevel;
+use crate::{logger, utils::logger::Logger, RUNTIME_FLAGS, SETTINGS};
+use config::{Config, File};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// The settings `config.toml` may change while the server runs. Any other setting only changes
+/// with a restart, and edits to them are rejected (see `ConfigWatcher`).
+pub const TUNABLE_SETTINGS: [&str; 10] = [
+    "LOG_LEVEL",
+    "PROMPT_TIMEOUT",
+    "BLOCKERS_TIMEOUT",
+    "EMOTE_RATE_LIMIT",
+    "EMOTE_RATE_WINDOW",
+    "CHAT_MAX_LENGTH",
+    "CHAT_RATE_LIMIT",
+    "CHAT_RATE_WINDOW",
+    "MISSED_PACKETS_LIMIT",
+    "OUTBOUND_QUEUE_CAPACITY",
+];
+
+/// The tunable settings in effect. See the fields of `Settings` for their meaning.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RuntimeConfig {
+    pub log_level: LogLevel,
+    pub prompt_timeout: u64,
+    pub blockers_timeout: u64,
+    pub emote_rate_limit: u32,
+    pub emote_rate_window: u64,
+    pub chat_max_length: usize,
+    pub chat_rate_limit: u32,
+    pub chat_rate_window: u64,
+    pub missed_packets_limit: usize,
+    pub outbound_queue_capacity: usize, // Applies to the clients connecting afterwards.
+}
+
+impl From<&Settings> for RuntimeConfig {
+    fn from(settings: &Settings) -> Self {
+        Self {
+            log_level: settings.log_level,
+            prompt_timeout: settings.prompt_timeout,
+            blockers_timeout: settings.blockers_timeout,
+            emote_rate_limit: settings.emote_rate_limit,
+            emote_rate_window: settings.emote_rate_window,
+            chat_max_length: settings.chat_max_length,
+            chat_rate_limit: settings.chat_rate_limit,
+            chat_rate_window: settings.chat_rate_window,
+            missed_packets_limit: settings.missed_packets_limit,
+            outbound_queue_capacity: settings.outbound_queue_capacity,
+        }
+    }
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            log_level: LogLevel::Debug,
+            prompt_timeout: 30,
+            blockers_timeout: 20,
+            emote_rate_limit: 3,
+            emote_rate_window: 10,
+            chat_max_length: 200,
+            chat_rate_limit: 5,
+            chat_rate_window: 10,
+            missed_packets_limit: 30,
+            outbound_queue_capacity: 64,
+        }
+    }
+}
+
+/// The tunable settings, shared by the subsystems that read them and the watcher replacing them.
+#[derive(Debug, Clone, Default)]
+pub struct SharedConfig(Arc<RwLock<RuntimeConfig>>);
+
+impl SharedConfig {
+    /// The settings loaded at startup, or the defaults if there are none.
+    pub fn from_settings() -> Self {
+        let config = SETTINGS.get().map(RuntimeConfig::from).unwrap_or_default();
+        Self(Arc::new(RwLock::new(config)))
+    }
+
+    pub fn get(&self) -> RuntimeConfig {
+        *self.0.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set(&self, config: RuntimeConfig) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = config;
+    }
+}
+
+/// The settings a reload of the configuration file changed.
+#[derive(Debug, Default, PartialEq)]
+pub struct ConfigReload {
+    pub applied: Vec<String>,  // Tunable settings now in effect.
+    pub rejected: Vec<String>, // Settings that only change with a restart, left as they were.
+}
+
+impl Display for ConfigReload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let list = |names: &[String]| match names.is_empty() {
+            true => String::from("none"),
+            false => names.join(", "),
+        };
+        write!(
+            f,
+            "applied: {}; rejected (restart required): {}",
+            list(&self.applied),
+            list(&self.rejected)
+        )
+    }
+}
+
+/// Reloads the tunable settings of the configuration file into a `SharedConfig` when the file
+/// changes.
+///
+/// The file must still hold valid settings as a whole for anything to be applied. Changes to the
+/// other settings, such as the listen addresses, are reported and ignored; they are compared to
+/// the values in effect, so they are reported again on every reload until they are reverted.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    config: SharedConfig,
+    values: Mutex<BTreeMap<String, serde_json::Value>>, // The settings in effect, by name.
+    modified: Mutex<Option<SystemTime>>,                // When the file was last modified.
+}
+
+impl ConfigWatcher {
+    /// Starts watching the file the settings in effect were loaded from.
+    pub fn new(path: &Path, config: SharedConfig) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            config,
+            values: Mutex::new(Self::read(path)?.0),
+            modified: Mutex::new(Self::modified(path)),
+        })
+    }
+
+    /// Reads the settings of the file, by name and as a whole.
+    fn read(path: &Path) -> Result<(BTreeMap<String, serde_json::Value>, Settings), String> {
+        let config = Config::builder()
+            .add_source(File::from(path))
+            .build()
+            .map_err(|e| e.to_string())?;
+        let values: BTreeMap<String, serde_json::Value> = config
+            .clone()
+            .try_deserialize()
+            .map_err(|e| e.to_string())?;
+        let settings: Settings = config.try_deserialize().map_err(|e| e.to_string())?;
+        let values = values
+            .into_iter()
+            .map(|(name, value)| (name.to_ascii_uppercase(), value))
+            .collect();
+        Ok((values, settings))
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Reads the file again and applies the tunable settings that changed.
+    ///
+    /// # Returns
+    /// * `Ok(ConfigReload)` - The settings applied and the ones rejected.
+    /// * `Err(String)` - Why the file could not be read; nothing is applied.
+    pub fn reload(&self) -> Result<ConfigReload, String> {
+        *self.modified.lock().unwrap_or_else(|e| e.into_inner()) = Self::modified(&self.path);
+        let (values, settings) = Self::read(&self.path)?;
+        let mut in_effect = self.values.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut reload = ConfigReload::default();
+        let names: BTreeSet<String> = in_effect.keys().chain(values.keys()).cloned().collect();
+        for name in names {
+            let value = values.get(&name);
+            if in_effect.get(&name) == value {
+                continue;
+            }
+            if !TUNABLE_SETTINGS.contains(&name.as_str()) {
+                reload.rejected.push(name);
+                continue;
+            }
+
+            match value {
+                Some(value) => in_effect.insert(name.clone(), value.clone()),
+                None => in_effect.remove(&name),
+            };
+            reload.applied.push(name);
+        }
+
+        if !reload.applied.is_empty() {
+            let previous = self.config.get();
+            let mut config = RuntimeConfig::from(&settings);
+            if !reload.applied.iter().any(|name| name == "LOG_LEVEL") {
+                config.log_level = previous.log_level;
+            }
+            if config.log_level != previous.log_level {
+                RUNTIME_FLAGS.set_log_level(config.log_level);
+            }
+            self.config.set(config);
+        }
+        Ok(reload)
+    }
+
+    /// Whether the file was modified since it was last read.
+    fn changed(&self) -> bool {
+        let modified = self.modified.lock().unwrap_or_else(|e| e.into_inner());
+        Self::modified(&self.path) != *modified
+    }
+
+    /// Checks the file for changes every `interval`, reloading it when it changed. Runs
+    /// indefinitely.
+    pub async fn watch(self: Arc<Self>, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            if !self.changed() {
+                continue;
+            }
+
+            match self.reload() {
+                Ok(reload) if reload.rejected.is_empty() => {
+                    logger!(
+                        INFO,
+                        "[CONFIG] Reloaded `{}`, {reload}",
+                        self.path.display()
+                    )
+                }
+                Ok(reload) => {
+                    logger!(
+                        WARN,
+                        "[CONFIG] Reloaded `{}`, {reload}",
+                        self.path.display()
+                    )
+                }
+                Err(error) => logger!(
+                    ERROR,
+                    "[CONFIG] Could not reload `{}`, keeping the settings in effect: {error}",
+                    self.path.display()
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+AUTH_SERVER = "http://127.0.0.1:5001"
+CARD_SERVER = "http://127.0.0.1:5002"
+DECK_SERVER = "http://127.0.0.1:5003"
+RESULT_SERVER = "http://127.0.0.1:5004"
+LISTEN_ADDRESSES = ["127.0.0.1:8000"]
+CHAT_MAX_LENGTH = 200
+LOG_LEVEL = "debug"
+"#;
+
+    #[test]
+    fn test_reload_applies_tunable_settings_only() {
+        let path = std::env::temp_dir().join(format!("ccg-config-{}.toml", std::process::id()));
+        std::fs::write(&path, CONFIG).unwrap();
+        let config = SharedConfig::default();
+        let watcher = ConfigWatcher::new(&path, config.clone()).unwrap();
+
+        let edited = CONFIG
+            .replace(
+                "CHAT_MAX_LENGTH = 200",
+                "CHAT_MAX_LENGTH = 80\nPROMPT_TIMEOUT = 45",
+            )
+            .replace("127.0.0.1:8000", "0.0.0.0:9000");
+        std::fs::write(&path, edited).unwrap();
+        let reload = watcher.reload().unwrap();
+        assert_eq!(vec!["CHAT_MAX_LENGTH", "PROMPT_TIMEOUT"], reload.applied);
+        assert_eq!(vec!["LISTEN_ADDRESSES"], reload.rejected);
+        assert_eq!(80, config.get().chat_max_length);
+        assert_eq!(45, config.get().prompt_timeout);
+
+        // The rejected change is still pending, and invalid files change nothing.
+        assert_eq!(vec!["LISTEN_ADDRESSES"], watcher.reload().unwrap().rejected);
+        std::fs::write(&path, "CHAT_MAX_LENGTH = 10").unwrap();
+        assert!(watcher.reload().is_err());
+        assert_eq!(80, config.get().chat_max_length);
+        let _ = std::fs::remove_file(&path);
+    }
+}
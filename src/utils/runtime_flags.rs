@@ -0,0 +1,186 @@
+use crate::tcp::handshake::{FEATURE_ACTION_BATCH, FEATURE_PROMPTS, SERVER_FEATURES};
+use serde::Deserialize;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
+use std::time::Duration;
+
+/// Protocol features operators may switch off on a live match, by admin name.
+pub const TOGGLEABLE_FEATURES: [(&str, u32); 2] = [
+    ("prompts", FEATURE_PROMPTS),
+    ("action-batch", FEATURE_ACTION_BATCH),
+];
+
+/// Severity of a log line, from the most to the least verbose.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug = 0,
+    Info = 1,
+    Warn = 2,
+    Error = 3,
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            other => Err(format!("Unknown log level `{other}`")),
+        }
+    }
+}
+
+impl Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Behavior operators can change on a running server through the admin channel.
+///
+/// Every flag is an atomic, so the subsystems consulting them on hot paths never wait on a lock.
+pub struct RuntimeFlags {
+    log_level: AtomicU8,         // Lowest `LogLevel` that is printed.
+    packet_dump: AtomicBool,     // Whether every packet sent and received is logged in full.
+    spectator_delay: AtomicU64,  // Milliseconds spectators lag behind the match.
+    enabled_features: AtomicU32, // Protocol features offered in new handshakes.
+}
+
+impl RuntimeFlags {
+    pub fn logs(&self, level: LogLevel) -> bool {
+        level as u8 >= self.log_level.load(Ordering::Relaxed)
+    }
+
+    pub fn log_level(&self) -> LogLevel {
+        match self.log_level.load(Ordering::Relaxed) {
+            0 => LogLevel::Debug,
+            1 => LogLevel::Info,
+            2 => LogLevel::Warn,
+            _ => LogLevel::Error,
+        }
+    }
+
+    pub fn set_log_level(&self, level: LogLevel) {
+        self.log_level.store(level as u8, Ordering::Relaxed);
+    }
+
+    pub fn packet_dump(&self) -> bool {
+        self.packet_dump.load(Ordering::Relaxed)
+    }
+
+    pub fn set_packet_dump(&self, enabled: bool) {
+        self.packet_dump.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn spectator_delay(&self) -> Duration {
+        Duration::from_millis(self.spectator_delay.load(Ordering::Relaxed))
+    }
+
+    pub fn set_spectator_delay(&self, delay: Duration) {
+        self.spectator_delay
+            .store(delay.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// The protocol features new handshakes may agree on. Clients that already negotiated keep
+    /// their features.
+    pub fn enabled_features(&self) -> u32 {
+        self.enabled_features.load(Ordering::Relaxed)
+    }
+
+    /// Switches a protocol feature on or off for the next handshakes.
+    ///
+    /// # Returns
+    /// * `Ok(())` - If the feature exists.
+    /// * `Err(String)` - A message listing the features that can be toggled.
+    pub fn set_feature(&self, name: &str, enabled: bool) -> Result<(), String> {
+        let Some((_, bit)) = TOGGLEABLE_FEATURES.iter().find(|(n, _)| *n == name) else {
+            let names: Vec<_> = TOGGLEABLE_FEATURES.iter().map(|(n, _)| *n).collect();
+            return Err(format!(
+                "Unknown feature `{name}`, expected one of: {}",
+                names.join(", ")
+            ));
+        };
+
+        match enabled {
+            true => self.enabled_features.fetch_or(*bit, Ordering::Relaxed),
+            false => self.enabled_features.fetch_and(!*bit, Ordering::Relaxed),
+        };
+        Ok(())
+    }
+}
+
+impl Default for RuntimeFlags {
+    fn default() -> Self {
+        Self {
+            log_level: AtomicU8::new(LogLevel::Debug as u8),
+            packet_dump: AtomicBool::new(false),
+            spectator_delay: AtomicU64::new(0),
+            enabled_features: AtomicU32::new(SERVER_FEATURES),
+        }
+    }
+}
+
+impl Display for RuntimeFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let features: Vec<_> = TOGGLEABLE_FEATURES
+            .iter()
+            .map(|(name, bit)| {
+                let state = match self.enabled_features() & bit {
+                    0 => "off",
+                    _ => "on",
+                };
+                format!("{name}={state}")
+            })
+            .collect();
+
+        write!(
+            f,
+            "log-level={}, packet-dump={}, spectator-delay={}s, features: {}",
+            self.log_level(),
+            self.packet_dump(),
+            self.spectator_delay().as_secs_f64(),
+            features.join(" ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_level_filters_lower_levels() {
+        let flags = RuntimeFlags::default();
+        flags.set_log_level("WARN".parse().unwrap());
+
+        assert!(!flags.logs(LogLevel::Info));
+        assert!(flags.logs(LogLevel::Warn));
+        assert!(flags.logs(LogLevel::Error));
+    }
+
+    #[test]
+    fn test_toggle_features() {
+        let flags = RuntimeFlags::default();
+        flags.set_feature("action-batch", false).unwrap();
+        assert_eq!(
+            SERVER_FEATURES & !FEATURE_ACTION_BATCH,
+            flags.enabled_features()
+        );
+
+        flags.set_feature("action-batch", true).unwrap();
+        assert_eq!(SERVER_FEATURES, flags.enabled_features());
+        assert!(flags.set_feature("time-travel", true).is_err());
+    }
+}
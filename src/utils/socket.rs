@@ -0,0 +1,106 @@
+use crate::models::settings::Settings;
+use socket2::{SockRef, TcpKeepalive};
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+/// Socket options applied to every accepted connection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SocketTuning {
+    pub nodelay: bool, // Disables Nagle's algorithm, so small packets leave at once.
+    pub keepalive: Option<Keepalive>, // OS-level keepalive, or `None` to leave it off.
+    pub linger: Option<Duration>, // How long closing waits for unsent data, or the OS default.
+}
+
+/// OS-level keepalive timings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keepalive {
+    pub idle: Duration,     // Silence before the first probe.
+    pub interval: Duration, // Time between unanswered probes.
+    pub retries: u32,       // Unanswered probes after which the connection is dropped.
+}
+
+impl SocketTuning {
+    /// Builds the tuning from the settings, using the defaults when they are not loaded.
+    ///
+    /// The OS keepalive follows the protocol heartbeat: it probes after the same silence and gives
+    /// up after the same number of misses. It still catches dead peers while the heartbeat task is
+    /// stalled, and half-open connections that never reach the read loop.
+    pub fn from_settings(settings: Option<&Settings>) -> Self {
+        let Some(settings) = settings else {
+            return Self::default();
+        };
+
+        let heartbeat = Duration::from_secs(settings.heartbeat_interval.max(1));
+        Self {
+            nodelay: settings.tcp_nodelay,
+            keepalive: settings.tcp_keepalive.then_some(Keepalive {
+                idle: heartbeat,
+                interval: heartbeat,
+                retries: settings.heartbeat_max_missed.max(1),
+            }),
+            linger: settings.tcp_linger.map(Duration::from_secs),
+        }
+    }
+
+    /// Applies the options to a connected stream.
+    pub fn apply(&self, stream: &TcpStream) -> std::io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
+
+        let socket = SockRef::from(stream);
+        if let Some(keepalive) = &self.keepalive {
+            let params = TcpKeepalive::new()
+                .with_time(keepalive.idle)
+                .with_interval(keepalive.interval);
+            #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+            let params = params.with_retries(keepalive.retries);
+            socket.set_tcp_keepalive(&params)?;
+        }
+
+        if self.linger.is_some() {
+            socket.set_linger(self.linger)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for SocketTuning {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            keepalive: Some(Keepalive {
+                idle: Duration::from_secs(5),
+                interval: Duration::from_secs(5),
+                retries: 3,
+            }),
+            linger: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_tuning_is_applied() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+
+        let tuning = SocketTuning {
+            linger: Some(Duration::from_secs(2)),
+            ..SocketTuning::default()
+        };
+        tuning.apply(&stream).unwrap();
+
+        let socket = SockRef::from(&stream);
+        assert!(stream.nodelay().unwrap());
+        assert!(socket.keepalive().unwrap());
+        assert_eq!(Some(Duration::from_secs(2)), socket.linger().unwrap());
+        drop(client);
+    }
+}